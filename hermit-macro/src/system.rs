@@ -1,6 +1,7 @@
 use proc_macro2::{Ident, Span};
 use syn::{
-	Abi, Attribute, FnArg, Item, ItemFn, Pat, Result, Signature, Stmt, Visibility, parse_quote,
+	Abi, Attribute, FnArg, Item, ItemFn, LitStr, Pat, Result, Signature, Stmt, Visibility,
+	parse_quote,
 };
 
 fn parse_attr(attr: Option<Ident>) -> Result<bool> {
@@ -38,11 +39,10 @@ fn parse_sig(sig: &Signature) -> Result<ParsedSig> {
 		Some(Abi {
 			extern_token: _,
 			name: Some(name),
-		}) if matches!(&*name.value(), "C" | "C-unwind") => {}
-		_ => bail!(
-			&sig.abi,
-			"#[system] functions must be `extern \"C\"` or `extern \"C-unwind\"`"
-		),
+		}) if name.value() == "C" => {}
+		// Unwinds must not cross the syscall boundary, which `extern "C"`
+		// turns into an abort.
+		_ => bail!(&sig.abi, "#[system] functions must be `extern \"C\"`"),
 	}
 	if !sig.generics.params.is_empty() {
 		bail!(
@@ -74,10 +74,7 @@ fn parse_sig(sig: &Signature) -> Result<ParsedSig> {
 fn validate_attrs(attrs: &[Attribute]) -> Result<()> {
 	let mut no_mangle_found = false;
 	for attr in attrs {
-		if attr.path().is_ident("unsafe")
-			&& let Ok(ident) = attr.parse_args::<Ident>()
-			&& ident == "no_mangle"
-		{
+		if is_no_mangle(attr) {
 			no_mangle_found = true;
 			continue;
 		}
@@ -100,6 +97,38 @@ fn validate_attrs(attrs: &[Attribute]) -> Result<()> {
 	Ok(())
 }
 
+fn is_no_mangle(attr: &Attribute) -> bool {
+	attr.path().is_ident("unsafe")
+		&& attr
+			.parse_args::<Ident>()
+			.is_ok_and(|ident| ident == "no_mangle")
+}
+
+/// Only exports the function if the application uses it.
+///
+/// If the kernel is built with `--cfg hermit_used_syscalls`, only syscalls,
+/// which are listed with `--cfg hermit_used_syscall="sys_..."`, are exported
+/// and all others are removed as dead code.
+fn emit_attrs(attrs: Vec<Attribute>, ident: &Ident) -> Vec<Attribute> {
+	let name = LitStr::new(&ident.to_string(), ident.span());
+	attrs
+		.into_iter()
+		.flat_map(|attr| -> Vec<Attribute> {
+			if is_no_mangle(&attr) {
+				parse_quote! {
+					#[cfg_attr(
+						any(not(hermit_used_syscalls), hermit_used_syscall = #name),
+						unsafe(no_mangle)
+					)]
+					#[cfg_attr(hermit_used_syscalls, allow(dead_code))]
+				}
+			} else {
+				vec![attr]
+			}
+		})
+		.collect()
+}
+
 fn emit_func(func: ItemFn, sig: &ParsedSig, errno: bool) -> Result<ItemFn> {
 	let inner_ident = Ident::new(&format!("__{}", func.sig.ident), Span::call_site());
 	let inner_func = ItemFn {
@@ -169,6 +198,7 @@ fn emit_func(func: ItemFn, sig: &ParsedSig, errno: bool) -> Result<ItemFn> {
 		Ident::new(&format!("kernel_function{}", args.len()), Span::call_site());
 
 	let sys_func = ItemFn {
+		attrs: emit_attrs(func.attrs.clone(), &func.sig.ident),
 		block: parse_quote! {{
 			#inner_func
 
@@ -229,7 +259,11 @@ mod tests {
 			///
 			/// This is very important.
 			#[cfg(target_os = "none")]
-			#[unsafe(no_mangle)]
+			#[cfg_attr(
+				any(not(hermit_used_syscalls), hermit_used_syscall = "sys_test"),
+				unsafe(no_mangle)
+			)]
+			#[cfg_attr(hermit_used_syscalls, allow(dead_code))]
 			pub extern "C" fn sys_test(a: i8, b: i16) -> i32 {
 				extern "C" fn __sys_test(a: i8, b: i16) -> i32 {
 					if a == 0 {
@@ -297,7 +331,11 @@ mod tests {
 			///
 			/// This is very important.
 			#[cfg(target_os = "none")]
-			#[unsafe(no_mangle)]
+			#[cfg_attr(
+				any(not(hermit_used_syscalls), hermit_used_syscall = "sys_test"),
+				unsafe(no_mangle)
+			)]
+			#[cfg_attr(hermit_used_syscalls, allow(dead_code))]
 			pub unsafe extern "C" fn sys_test(a: i8, b: i16) -> i32 {
 				unsafe extern "C" fn __sys_test(a: i8, b: i16) -> i32 {
 					if a == 0 {
@@ -365,7 +403,11 @@ mod tests {
 			///
 			/// This is very important.
 			#[cfg(target_os = "none")]
-			#[unsafe(no_mangle)]
+			#[cfg_attr(
+				any(not(hermit_used_syscalls), hermit_used_syscall = "sys_test"),
+				unsafe(no_mangle)
+			)]
+			#[cfg_attr(hermit_used_syscalls, allow(dead_code))]
 			pub extern "C" fn sys_test(a: i8, b: i16) -> i32 {
 				extern "C" fn __sys_test(a: i8, b: i16) -> i32 {
 					if a == 0 {
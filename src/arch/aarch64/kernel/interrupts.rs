@@ -1,5 +1,8 @@
 use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::arch::asm;
+use core::fmt::Write;
 use core::sync::atomic::{AtomicU64, Ordering};
 
 use aarch64::regs::*;
@@ -22,8 +25,10 @@ use crate::drivers::mmio::get_interrupt_handlers;
 use crate::drivers::pci::get_interrupt_handlers;
 use crate::drivers::{InterruptHandlerQueue, InterruptLine};
 use crate::kernel::serial::handle_uart_interrupt;
+use crate::mm::mmio::{self, MmioRegion};
 use crate::mm::virtualmem::KERNEL_FREE_LIST;
 use crate::scheduler::{self, CoreId};
+use crate::symbols::Symbolized;
 use crate::{core_id, core_scheduler, env};
 
 /// The ID of the first Private Peripheral Interrupt.
@@ -98,7 +103,9 @@ pub(crate) fn install_handlers() {
 		CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::CLEAR);
 	}
 
-	for (key, value) in get_interrupt_handlers().into_iter() {
+	let device_handlers = get_interrupt_handlers();
+	crate::drivers::irq_affinity::spread(device_handlers.keys().copied());
+	for (key, value) in device_handlers.into_iter() {
 		handlers.insert(key + SPI_START, value);
 	}
 
@@ -125,6 +132,37 @@ pub(crate) fn install_handlers() {
 	INTERRUPT_HANDLERS.set(handlers).unwrap();
 }
 
+/// Routes the interrupt line `irq` to the core `core_id`.
+///
+/// Returns `false`, because the shared peripheral interrupts are not routed
+/// to other cores yet.
+pub(crate) fn set_irq_affinity(_irq: u8, _core_id: CoreId) -> bool {
+	false
+}
+
+/// Masks or unmasks the shared peripheral interrupt `vector` for this core.
+///
+/// Returns `false`, if `vector` is no shared peripheral interrupt.
+pub(crate) fn set_irq_masked(vector: u8, masked: bool) -> bool {
+	if vector < SPI_START {
+		return false;
+	}
+	let Some(mut guard) = GIC.try_lock() else {
+		return false;
+	};
+	let Some(gic) = guard.as_mut() else {
+		return false;
+	};
+
+	let cpu_id: usize = core_id().try_into().unwrap();
+	gic.enable_interrupt(
+		IntId::spi((vector - SPI_START).into()),
+		Some(cpu_id),
+		!masked,
+	);
+	true
+}
+
 #[unsafe(no_mangle)]
 pub(crate) extern "C" fn do_fiq(_state: &State) -> *mut usize {
 	if let Some(irqid) = GicV3::get_and_acknowledge_interrupt(InterruptGroup::Group1) {
@@ -139,6 +177,7 @@ pub(crate) extern "C" fn do_fiq(_state: &State) -> *mut usize {
 			for handler in queue.iter() {
 				handler();
 			}
+			crate::drivers::irq_storm::count(vector);
 		}
 		crate::executor::run();
 		core_scheduler().handle_waiting_tasks();
@@ -165,6 +204,7 @@ pub(crate) extern "C" fn do_irq(_state: &State) -> *mut usize {
 			for handler in queue.iter() {
 				handler();
 			}
+			crate::drivers::irq_storm::count(vector);
 		}
 		crate::executor::run();
 		core_scheduler().handle_waiting_tasks();
@@ -204,7 +244,10 @@ pub(crate) extern "C" fn do_sync(state: &State) {
 
 			error!("Current stack pointer {state:p}");
 			error!("Unable to handle page fault at {far:#x}");
-			error!("Exception return address {:#x}", ELR_EL1.get());
+			error!(
+				"Exception return address {}",
+				Symbolized(ELR_EL1.get().try_into().unwrap())
+			);
 			error!("Thread ID register {:#x}", TPIDR_EL0.get());
 			error!("Table Base Register {:#x}", TTBR0_EL1.get());
 			error!("Exception Syndrome Register {esr:#x}");
@@ -220,7 +263,10 @@ pub(crate) extern "C" fn do_sync(state: &State) {
 			error!("Unknown exception");
 		}
 	} else if ec == ESR_EL1::EC::Value::Brk64 {
-		error!("Trap to debugger, PC={pc:#x}");
+		error!(
+			"Trap to debugger, PC={}",
+			Symbolized(pc.try_into().unwrap())
+		);
 		loop {
 			core::hint::spin_loop();
 		}
@@ -317,6 +363,9 @@ pub(crate) fn init() {
 	let gicd_address = VirtAddr::from(page_range.start());
 	debug!("Mapping GIC Distributor interface to virtual address {gicd_address:p}");
 
+	let region = MmioRegion::new("gic", "gicd", gicd_start, gicd_size.try_into().unwrap());
+	mmio::register(region, gicd_address).unwrap_or_else(|conflict| panic!("{conflict}"));
+
 	let mut flags = PageTableEntryFlags::empty();
 	flags.device().writable().execute_disable();
 	paging::map::<BasePageSize>(
@@ -330,6 +379,8 @@ pub(crate) fn init() {
 	let page_range = KERNEL_FREE_LIST.lock().allocate(layout).unwrap();
 	let gicr_address = VirtAddr::from(page_range.start());
 	debug!("Mapping generic interrupt controller to virtual address {gicr_address:p}");
+	let region = MmioRegion::new("gic", "gicr", gicr_start, gicr_size.try_into().unwrap());
+	mmio::register(region, gicr_address).unwrap_or_else(|conflict| panic!("{conflict}"));
 	paging::map::<BasePageSize>(
 		gicr_address,
 		gicr_start,
@@ -540,3 +591,31 @@ pub(crate) fn print_statistics() {
 		}
 	}
 }
+
+/// Returns the interrupt counters in the format of `/proc/interrupts`.
+pub(crate) fn proc_interrupts() -> String {
+	let irq_counters = IRQ_COUNTERS.lock();
+
+	let mut s = String::from("    ");
+	for core_id in irq_counters.keys() {
+		write!(s, " {:>10}", format_args!("CPU{core_id}")).unwrap();
+	}
+	s.push('\n');
+
+	for i in 0..=u8::MAX {
+		let counters = irq_counters
+			.values()
+			.map(|irq_statistics| irq_statistics.counters[usize::from(i)].load(Ordering::Relaxed))
+			.collect::<Vec<_>>();
+		if counters.iter().all(|counter| *counter == 0) {
+			continue;
+		}
+
+		write!(s, "{i:>3}:").unwrap();
+		for counter in counters {
+			write!(s, " {counter:>10}").unwrap();
+		}
+		writeln!(s, "   {}", get_irq_name(i).unwrap_or("")).unwrap();
+	}
+	s
+}
@@ -3,7 +3,7 @@ use core::ptr::NonNull;
 
 use align_address::Align;
 use arm_gic::{IntId, Trigger};
-#[cfg(feature = "console")]
+#[cfg(any(feature = "console", feature = "fuse", feature = "vsock"))]
 use hermit_sync::InterruptTicketMutex;
 use hermit_sync::without_interrupts;
 use virtio::mmio::{DeviceRegisters, DeviceRegistersVolatileFieldAccess};
@@ -17,11 +17,15 @@ use crate::console::IoDevice;
 use crate::drivers::console::VirtioConsoleDriver;
 #[cfg(feature = "console")]
 use crate::drivers::console::VirtioUART;
+#[cfg(feature = "fuse")]
+use crate::drivers::fs::virtio_fs::VirtioFsDriver;
 #[cfg(feature = "virtio-net")]
 use crate::drivers::net::virtio::VirtioNetDriver;
-use crate::drivers::virtio::transport::mmio::{self as mmio_virtio, VirtioDriver};
+use crate::drivers::virtio::transport::{VirtioDriver, mmio as mmio_virtio};
+#[cfg(feature = "vsock")]
+use crate::drivers::vsock::VirtioVsockDriver;
 #[cfg(feature = "virtio-net")]
-use crate::executor::device::NETWORK_DEVICE;
+use crate::executor::device::NETWORK_DEVICES;
 use crate::init_cell::InitCell;
 use crate::mm::PhysAddr;
 
@@ -30,18 +34,42 @@ pub(crate) static MMIO_DRIVERS: InitCell<Vec<MmioDriver>> = InitCell::new(Vec::n
 pub(crate) enum MmioDriver {
 	#[cfg(feature = "console")]
 	VirtioConsole(InterruptTicketMutex<VirtioConsoleDriver>),
+	#[cfg(feature = "fuse")]
+	VirtioFs(InterruptTicketMutex<VirtioFsDriver>),
+	#[cfg(feature = "vsock")]
+	VirtioVsock(InterruptTicketMutex<VirtioVsockDriver>),
 }
 
 impl MmioDriver {
 	#[cfg(feature = "console")]
 	fn get_console_driver(&self) -> Option<&InterruptTicketMutex<VirtioConsoleDriver>> {
+		#[allow(unreachable_patterns)]
 		match self {
 			Self::VirtioConsole(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	#[cfg(feature = "fuse")]
+	fn get_filesystem_driver(&self) -> Option<&InterruptTicketMutex<VirtioFsDriver>> {
+		#[allow(unreachable_patterns)]
+		match self {
+			Self::VirtioFs(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	#[cfg(feature = "vsock")]
+	fn get_vsock_driver(&self) -> Option<&InterruptTicketMutex<VirtioVsockDriver>> {
+		#[allow(unreachable_patterns)]
+		match self {
+			Self::VirtioVsock(drv) => Some(drv),
+			_ => None,
 		}
 	}
 }
 
-#[cfg(feature = "console")]
+#[cfg(any(feature = "console", feature = "fuse", feature = "vsock"))]
 pub(crate) fn register_driver(drv: MmioDriver) {
 	MMIO_DRIVERS.with(|mmio_drivers| mmio_drivers.unwrap().push(drv));
 }
@@ -57,6 +85,49 @@ pub(crate) fn get_console_driver() -> Option<&'static InterruptTicketMutex<Virti
 		.find_map(|drv| drv.get_console_driver())
 }
 
+#[cfg(feature = "fuse")]
+pub(crate) fn get_filesystem_driver() -> Option<&'static InterruptTicketMutex<VirtioFsDriver>> {
+	MMIO_DRIVERS
+		.get()?
+		.iter()
+		.find_map(|drv| drv.get_filesystem_driver())
+}
+
+#[cfg(feature = "vsock")]
+pub(crate) fn get_vsock_driver() -> Option<&'static InterruptTicketMutex<VirtioVsockDriver>> {
+	MMIO_DRIVERS
+		.get()?
+		.iter()
+		.find_map(|drv| drv.get_vsock_driver())
+}
+
+/// Routes the interrupt of a virtio-mmio device, as described by the
+/// `interrupts` property of its device tree node, to the boot processor.
+fn enable_virtio_interrupt(irq: u32, irqtype: u32, irqflags: u32) {
+	let cpu_id: usize = 0;
+
+	let Some(gic) = GIC.lock().as_mut() else {
+		return;
+	};
+
+	let virtio_irqid = if irqtype == 1 {
+		IntId::ppi(irq)
+	} else if irqtype == 0 {
+		IntId::spi(irq)
+	} else {
+		panic!("Invalid interrupt type");
+	};
+	gic.set_interrupt_priority(virtio_irqid, Some(cpu_id), 0x00);
+	if (irqflags & 0xf) == 4 || (irqflags & 0xf) == 8 {
+		gic.set_trigger(virtio_irqid, Some(cpu_id), Trigger::Level);
+	} else if (irqflags & 0xf) == 2 || (irqflags & 0xf) == 1 {
+		gic.set_trigger(virtio_irqid, Some(cpu_id), Trigger::Edge);
+	} else {
+		panic!("Invalid interrupt level!");
+	}
+	gic.enable_interrupt(virtio_irqid, Some(cpu_id), true);
+}
+
 pub fn init_drivers() {
 	without_interrupts(|| {
 		if let Some(fdt) = crate::env::fdt() {
@@ -114,100 +185,51 @@ pub fn init_drivers() {
 							// We found a MMIO-device (whose 512-bit address in this structure).
 							trace!("Found a MMIO-device at {mmio:p}");
 
-							// Verify the device-ID to find the network card
 							let id = mmio.as_ptr().device_id().read();
-							let cpu_id: usize = 0;
-
-							match id {
-								#[cfg(feature = "virtio-net")]
-								virtio::Id::Net => {
-									debug!(
-										"Found network card at {mmio:p}, irq: {irq}, type: {irqtype}, flags: {irqflags}"
-									);
-									if let Ok(VirtioDriver::Network(drv)) =
-										mmio_virtio::init_device(mmio, irq.try_into().unwrap())
-										&& let Some(gic) = GIC.lock().as_mut()
-									{
-										// enable timer interrupt
-										let virtio_irqid = if irqtype == 1 {
-											IntId::ppi(irq)
-										} else if irqtype == 0 {
-											IntId::spi(irq)
-										} else {
-											panic!("Invalid interrupt type");
-										};
-										gic.set_interrupt_priority(
-											virtio_irqid,
-											Some(cpu_id),
-											0x00,
-										);
-										if (irqflags & 0xf) == 4 || (irqflags & 0xf) == 8 {
-											gic.set_trigger(
-												virtio_irqid,
-												Some(cpu_id),
-												Trigger::Level,
-											);
-										} else if (irqflags & 0xf) == 2 || (irqflags & 0xf) == 1 {
-											gic.set_trigger(
-												virtio_irqid,
-												Some(cpu_id),
-												Trigger::Edge,
-											);
-										} else {
-											panic!("Invalid interrupt level!");
-										}
-										gic.enable_interrupt(virtio_irqid, Some(cpu_id), true);
-
-										*NETWORK_DEVICE.lock() = Some(drv);
-									}
-								}
-								#[cfg(feature = "console")]
-								virtio::Id::Console => {
-									debug!(
-										"Found console at {mmio:p}, irq: {irq}, type: {irqtype}, flags: {irqflags}"
-									);
-									if let Ok(VirtioDriver::Console(drv)) =
-										mmio_virtio::init_device(mmio, irq.try_into().unwrap())
-									{
-										if let Some(gic) = GIC.lock().as_mut() {
-											// enable timer interrupt
-											let virtio_irqid = if irqtype == 1 {
-												IntId::ppi(irq)
-											} else if irqtype == 0 {
-												IntId::spi(irq)
-											} else {
-												panic!("Invalid interrupt type");
-											};
-											gic.set_interrupt_priority(
-												virtio_irqid,
-												Some(cpu_id),
-												0x00,
-											);
-											if (irqflags & 0xf) == 4 || (irqflags & 0xf) == 8 {
-												gic.set_trigger(
-													virtio_irqid,
-													Some(cpu_id),
-													Trigger::Level,
-												);
-											} else if (irqflags & 0xf) == 2 || (irqflags & 0xf) == 1
-											{
-												gic.set_trigger(
-													virtio_irqid,
-													Some(cpu_id),
-													Trigger::Edge,
-												);
-											} else {
-												panic!("Invalid interrupt level!");
-											}
-											gic.enable_interrupt(virtio_irqid, Some(cpu_id), true);
+							if id == virtio::Id::from(0) {
+								// Placeholder slot without a device behind it
+								continue;
+							}
 
+							debug!(
+								"Found virtio device {id:?} at {mmio:p}, irq: {irq}, type: {irqtype}, flags: {irqflags}"
+							);
+							match mmio_virtio::init_device(mmio, irq.try_into().unwrap()) {
+								Ok(drv) => {
+									enable_virtio_interrupt(irq, irqtype, irqflags);
+
+									match drv {
+										#[cfg(feature = "virtio-net")]
+										VirtioDriver::Network(drv) => {
+											NETWORK_DEVICES.lock().push(drv);
+										}
+										#[cfg(feature = "console")]
+										VirtioDriver::Console(drv) => {
 											register_driver(MmioDriver::VirtioConsole(
-												hermit_sync::InterruptTicketMutex::new(*drv),
+												InterruptTicketMutex::new(*drv),
+											));
+										}
+										#[cfg(feature = "vsock")]
+										VirtioDriver::Vsock(drv) => {
+											register_driver(MmioDriver::VirtioVsock(
+												InterruptTicketMutex::new(*drv),
 											));
 										}
+										#[cfg(feature = "fuse")]
+										VirtioDriver::FileSystem(drv) => {
+											register_driver(MmioDriver::VirtioFs(
+												InterruptTicketMutex::new(drv),
+											));
+										}
+										#[cfg(feature = "virtio-9p")]
+										VirtioDriver::NineP(drv) => {
+											crate::drivers::fs::virtio_9p::register_driver(drv);
+										}
 									}
 								}
-								_ => {}
+								Err(err) => {
+									error!("Could not initialize virtio-mmio device: {err}")
+								}
 							}
 						}
 					}
@@ -2,7 +2,16 @@ pub mod core_local;
 pub mod interrupts;
 #[cfg(feature = "kernel-stack")]
 pub mod kernel_stack;
-#[cfg(all(not(feature = "pci"), any(feature = "virtio-net", feature = "console")))]
+#[cfg(all(
+	not(feature = "pci"),
+	any(
+		feature = "console",
+		feature = "virtio-net",
+		feature = "fuse",
+		feature = "virtio-9p",
+		feature = "vsock"
+	)
+))]
 pub mod mmio;
 #[cfg(feature = "pci")]
 pub mod pci;
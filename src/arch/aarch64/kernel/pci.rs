@@ -12,6 +12,7 @@ use pci_types::{
 use crate::arch::aarch64::kernel::interrupts::GIC;
 use crate::arch::aarch64::mm::paging::{self, BasePageSize, PageSize, PageTableEntryFlags};
 use crate::drivers::pci::{PCI_DEVICES, PciDevice};
+use crate::mm::mmio::{self, MmioRegion};
 use crate::mm::virtualmem::KERNEL_FREE_LIST;
 use crate::{core_id, env};
 
@@ -231,6 +232,9 @@ pub fn init() {
 			"Mapping PCI Enhanced Configuration Space interface to virtual address {pci_address:p} (size {size:#X})"
 		);
 
+		let region = MmioRegion::new("pci", "ecam", addr, size.try_into().unwrap());
+		mmio::register(region, pci_address).unwrap_or_else(|conflict| panic!("{conflict}"));
+
 		let mut flags = PageTableEntryFlags::empty();
 		flags.device().writable().execute_disable();
 		paging::map::<BasePageSize>(
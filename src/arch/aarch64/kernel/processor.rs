@@ -144,6 +144,7 @@ enum CpuFrequencySources {
 	Invalid,
 	CommandLine,
 	Register,
+	Fdt,
 }
 
 impl fmt::Display for CpuFrequencySources {
@@ -151,6 +152,7 @@ impl fmt::Display for CpuFrequencySources {
 		match &self {
 			CpuFrequencySources::CommandLine => write!(f, "Command Line"),
 			CpuFrequencySources::Register => write!(f, "CNTFRQ_EL0"),
+			CpuFrequencySources::Fdt => write!(f, "FDT"),
 			CpuFrequencySources::Invalid => {
 				panic!("Attempted to print an invalid CPU Frequency Source")
 			}
@@ -192,15 +194,38 @@ impl CpuFrequency {
 		self.set_detected_cpu_frequency(u32::from(mhz) * 1000, CpuFrequencySources::CommandLine)
 	}
 
+	/// Returns the counter frequency in KHz, which the hypervisor or the
+	/// firmware passes in the device tree.
+	///
+	/// According to the device tree binding of the timer, the clock-frequency
+	/// property overrides a wrongly configured CNTFRQ_EL0.
+	fn frequency_from_fdt() -> Option<u32> {
+		let hz = env::fdt()?
+			.find_compatible(&["arm,armv8-timer", "arm,armv7-timer"])?
+			.property("clock-frequency")?
+			.as_usize()?;
+		u32::try_from(hz / 1000).ok().filter(|&khz| khz > 0)
+	}
+
+	/// Reads CNTFRQ_EL0 and validates it against the device tree.
 	unsafe fn detect_from_register(&mut self) -> Result<(), ()> {
-		let khz = (CNTFRQ_EL0.get() & 0xffff_ffff) / 1000;
-		self.set_detected_cpu_frequency(khz.try_into().unwrap(), CpuFrequencySources::Register)
+		let khz = u32::try_from((CNTFRQ_EL0.get() & 0xffff_ffff) / 1000).unwrap();
+
+		match Self::frequency_from_fdt() {
+			Some(fdt_khz) if khz.abs_diff(fdt_khz) > fdt_khz / 100 => {
+				warn!(
+					"CNTFRQ_EL0 reports {khz} KHz, but the FDT reports {fdt_khz} KHz, using the latter"
+				);
+				self.set_detected_cpu_frequency(fdt_khz, CpuFrequencySources::Fdt)
+			}
+			_ => self.set_detected_cpu_frequency(khz, CpuFrequencySources::Register),
+		}
 	}
 
 	unsafe fn detect(&mut self) {
 		unsafe {
-			self.detect_from_register()
-				.or_else(|_e| self.detect_from_cmdline())
+			self.detect_from_cmdline()
+				.or_else(|_e| self.detect_from_register())
 				.unwrap();
 		}
 	}
@@ -227,6 +252,28 @@ pub fn halt() {
 	}
 }
 
+/// Resets the system through PSCI.
+pub fn reboot() -> ! {
+	info!("Rebooting system");
+
+	cfg_if::cfg_if! {
+		if #[cfg(feature = "semihosting")] {
+			// Semihosting cannot reset the system.
+			semihosting::process::exit(1)
+		} else {
+			unsafe {
+				const PSCI_SYSTEM_RESET: u64 = 0x8400_0009;
+				asm!("hvc #0", in("x0") PSCI_SYSTEM_RESET, options(nomem, nostack));
+
+				// we should never reach this point
+				loop {
+					asm!("wfe", options(nomem, nostack));
+				}
+			}
+		}
+	}
+}
+
 /// Shutdown the system
 #[allow(unused_variables)]
 pub fn shutdown(error_code: i32) -> ! {
@@ -281,6 +328,96 @@ pub fn supports_2mib_pages() -> bool {
 	false
 }
 
+bitflags! {
+	/// CPU features, which are available to applications
+	///
+	/// The bits are part of the ABI of `sys_cpu_features` and must not change.
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub struct CpuFeatureFlags: u64 {
+		const FP = 1 << 0;
+		const ASIMD = 1 << 1;
+		const AES = 1 << 2;
+		const PMULL = 1 << 3;
+		const SHA1 = 1 << 4;
+		const SHA2 = 1 << 5;
+		const SHA512 = 1 << 6;
+		const CRC32 = 1 << 7;
+		const ATOMICS = 1 << 8;
+		const RDM = 1 << 9;
+		const SHA3 = 1 << 10;
+		const DOTPROD = 1 << 11;
+		const RNG = 1 << 12;
+		const SVE = 1 << 13;
+	}
+}
+
+/// Returns the value of the 4-bit field at `shift` of an ID register.
+fn id_field(register: u64, shift: u32) -> u64 {
+	(register >> shift) & 0xf
+}
+
+/// Returns whether SVE is implemented and enabled for applications in `CPACR_EL1`.
+fn sve_enabled(pfr0: u64) -> bool {
+	let cpacr: u64;
+	unsafe {
+		asm!("mrs {}, cpacr_el1", out(reg) cpacr, options(nostack, nomem));
+	}
+	id_field(pfr0, 32) != 0 && (cpacr >> 16) & 0b11 == 0b11
+}
+
+/// Returns the CPU features, which are available to applications.
+pub fn cpu_features() -> CpuFeatureFlags {
+	let (isar0, pfr0): (u64, u64);
+	unsafe {
+		asm!(
+			"mrs {}, id_aa64isar0_el1",
+			"mrs {}, id_aa64pfr0_el1",
+			out(reg) isar0,
+			out(reg) pfr0,
+			options(nostack, nomem),
+		);
+	}
+
+	let mut flags = CpuFeatureFlags::empty();
+	// The FP and AdvSIMD fields are 0xf, if the feature is not implemented.
+	flags.set(CpuFeatureFlags::FP, id_field(pfr0, 16) != 0xf);
+	flags.set(CpuFeatureFlags::ASIMD, id_field(pfr0, 20) != 0xf);
+	flags.set(CpuFeatureFlags::AES, id_field(isar0, 4) >= 1);
+	flags.set(CpuFeatureFlags::PMULL, id_field(isar0, 4) >= 2);
+	flags.set(CpuFeatureFlags::SHA1, id_field(isar0, 8) >= 1);
+	flags.set(CpuFeatureFlags::SHA2, id_field(isar0, 12) >= 1);
+	flags.set(CpuFeatureFlags::SHA512, id_field(isar0, 12) >= 2);
+	flags.set(CpuFeatureFlags::CRC32, id_field(isar0, 16) >= 1);
+	flags.set(CpuFeatureFlags::ATOMICS, id_field(isar0, 20) >= 2);
+	flags.set(CpuFeatureFlags::RDM, id_field(isar0, 28) >= 1);
+	flags.set(CpuFeatureFlags::SHA3, id_field(isar0, 32) >= 1);
+	flags.set(CpuFeatureFlags::DOTPROD, id_field(isar0, 44) >= 1);
+	flags.set(CpuFeatureFlags::RNG, id_field(isar0, 60) >= 1);
+	flags.set(CpuFeatureFlags::SVE, sve_enabled(pfr0));
+	flags
+}
+
+/// Returns the width of the widest vector registers, which are available to applications, in bits.
+///
+/// The kernel does not enable SVE yet. Hence, only the width of the AdvSIMD registers is reported.
+pub fn vector_width() -> u32 {
+	let pfr0: u64;
+	unsafe {
+		asm!("mrs {}, id_aa64pfr0_el1", out(reg) pfr0, options(nostack, nomem));
+	}
+
+	if sve_enabled(pfr0) {
+		let bytes: u64;
+		// `rdvl x0, #1`, which is encoded manually to avoid depending on the `sve` target feature
+		unsafe {
+			asm!(".inst 0x04bf5020", out("x0") bytes, options(nostack, nomem));
+		}
+		u32::try_from(bytes * 8).unwrap()
+	} else {
+		128
+	}
+}
+
 pub fn configure() {
 	// TODO: PMCCNTR_EL0 is the best replacement for RDTSC on AArch64.
 	// However, this test code showed that it's apparently not supported under uhyve yet.
@@ -332,7 +469,7 @@ fn __set_oneshot_timer(wakeup_time: Option<u64>) {
 	if let Some(wt) = wakeup_time {
 		// wt is the absolute wakeup time in microseconds based on processor::get_timer_ticks.
 		let freq: u64 = CPU_FREQUENCY.get().into(); // frequency in KHz
-		let deadline = (wt / 1000) * freq;
+		let deadline = (u128::from(wt) * u128::from(freq) / 1000) as u64;
 
 		CNTP_CVAL_EL0.set(deadline);
 		CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::SET);
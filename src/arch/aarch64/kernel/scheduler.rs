@@ -343,9 +343,13 @@ extern "C" fn task_start(_f: extern "C" fn(usize), _arg: usize, _user_stack: u64
 }
 
 #[cfg(target_os = "none")]
-extern "C" fn thread_exit(status: i32) -> ! {
-	debug!("Exit thread with error code {status}!");
-	core_scheduler().exit(status)
+extern "C" fn task_entry(func: extern "C" fn(usize), arg: usize) -> ! {
+	// Call the actual entry point of the task.
+	crate::scheduler::call_task_entry(func, arg);
+
+	// Exit task
+	debug!("Exit thread with error code 0!");
+	core_scheduler().exit(0)
 }
 
 #[cfg(target_os = "none")]
@@ -356,15 +360,9 @@ extern "C" fn task_start(_f: extern "C" fn(usize), _arg: usize) -> ! {
 
 	naked_asm!(
 		"msr spsel, {l0}",
-		"mov x25, x0",
-		"mov x0, x1",
-		"blr x25",
-		"mov x0, xzr",
-		"adrp x4, {exit}",
-		"add  x4, x4, #:lo12:{exit}",
-		"br x4",
+		"b {task_entry}",
 		l0 = const 0,
-		exit = sym thread_exit,
+		task_entry = sym task_entry,
 	)
 }
 
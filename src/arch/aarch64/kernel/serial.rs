@@ -106,6 +106,10 @@ pub(crate) fn handle_uart_interrupt() {
 			byte = b'\n';
 		}
 
+		#[cfg(feature = "sysrq")]
+		let Some(byte) = crate::sysrq::receive(byte) else {
+			continue;
+		};
 		guard.buffer.push_back(byte);
 	}
 
@@ -114,6 +118,8 @@ pub(crate) fn handle_uart_interrupt() {
 		.clear_interrupts(Interrupts::RXI | Interrupts::RTI);
 
 	drop(guard);
+	#[cfg(feature = "sysrq")]
+	crate::sysrq::run_pending();
 
 	crate::console::CONSOLE_WAKER.lock().wake();
 }
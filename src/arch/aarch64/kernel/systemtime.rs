@@ -12,6 +12,7 @@ use time::OffsetDateTime;
 
 use crate::arch::aarch64::mm::paging::{self, BasePageSize, PageSize, PageTableEntryFlags};
 use crate::env;
+use crate::mm::mmio::{self, MmioRegion};
 use crate::mm::virtualmem;
 use crate::mm::virtualmem::KERNEL_FREE_LIST;
 
@@ -71,6 +72,10 @@ pub fn init() {
 				PL031_ADDRESS.set(pl031_address).unwrap();
 				debug!("Mapping RTC to virtual address {pl031_address:p}");
 
+				let region = MmioRegion::new("rtc", "pl031", addr, size.try_into().unwrap());
+				mmio::register(region, pl031_address)
+					.unwrap_or_else(|conflict| panic!("{conflict}"));
+
 				let mut flags = PageTableEntryFlags::empty();
 				flags.device().writable().execute_disable();
 				paging::map::<BasePageSize>(
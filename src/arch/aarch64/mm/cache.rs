@@ -0,0 +1,146 @@
+//! Cache maintenance for DMA.
+//!
+//! Devices are only assumed to be cache-coherent if the devicetree marks a
+//! device with `dma-coherent` (e.g., the PCIe host bridge of QEMU's virt
+//! machine). Otherwise, buffers are cleaned and invalidated by virtual address
+//! and memory of [`DeviceAlloc`](crate::mm::device_alloc::DeviceAlloc), such
+//! as virtqueues, is mapped as normal non-cacheable memory.
+
+use core::arch::asm;
+
+use align_address::Align;
+use hermit_sync::Lazy;
+use memory_addresses::VirtAddr;
+
+use crate::arch::aarch64::mm::paging::{self, BasePageSize, PageSize, PageTableEntryFlags};
+use crate::env;
+use crate::mm::dma::DmaBarrier;
+
+static DMA_COHERENT: Lazy<bool> = Lazy::new(|| {
+	let Some(fdt) = env::fdt() else {
+		return true;
+	};
+
+	let coherent = fdt
+		.all_nodes()
+		.any(|node| node.property("dma-coherent").is_some());
+	if !coherent {
+		info!("DMA is not cache-coherent");
+	}
+	coherent
+});
+
+/// Returns the size of the smallest data cache line in bytes.
+fn dcache_line_size() -> usize {
+	let ctr: u64;
+	unsafe {
+		asm!("mrs {}, ctr_el0", out(reg) ctr, options(nomem, nostack));
+	}
+	// DminLine is the log2 of the number of words
+	4 << ((ctr >> 16) & 0xf)
+}
+
+/// Applies the data cache instruction `op` (`dc <op>`) to all cache lines of the range.
+macro_rules! for_each_line {
+	($start:expr, $len:expr, $op:literal) => {{
+		let line_size = dcache_line_size();
+		let end = $start.as_usize() + $len;
+		let mut addr = $start.as_usize().align_down(line_size);
+		while addr < end {
+			unsafe {
+				asm!(concat!("dc ", $op, ", {}"), in(reg) addr, options(nostack));
+			}
+			addr += line_size;
+		}
+		unsafe {
+			asm!("dsb sy", options(nostack));
+		}
+	}};
+}
+
+/// Returns whether devices observe the contents of the CPU caches.
+#[inline]
+pub(crate) fn dma_coherent() -> bool {
+	*DMA_COHERENT
+}
+
+/// Writes dirty cache lines of the range back to memory (`dc cvac`).
+pub(crate) fn clean_dcache_range(start: VirtAddr, len: usize) {
+	if len > 0 {
+		for_each_line!(start, len, "cvac");
+	}
+}
+
+/// Discards the cache lines of the range (`dc ivac`).
+///
+/// Lines, which are only partially covered by the range, are flushed
+/// instead, so that adjacent data is not lost.
+pub(crate) fn invalidate_dcache_range(start: VirtAddr, len: usize) {
+	let line_size = dcache_line_size();
+	let end = start.as_usize() + len;
+	let head = start.as_usize().align_up(line_size).min(end);
+	let tail = end.align_down(line_size).max(head);
+
+	flush_dcache_range(start, head - start.as_usize());
+	if tail > head {
+		for_each_line!(VirtAddr::new(head as u64), tail - head, "ivac");
+	}
+	flush_dcache_range(VirtAddr::new(tail as u64), end - tail);
+}
+
+/// Writes dirty cache lines of the range back to memory and discards them (`dc civac`).
+pub(crate) fn flush_dcache_range(start: VirtAddr, len: usize) {
+	if len > 0 {
+		for_each_line!(start, len, "civac");
+	}
+}
+
+fn remap(start: VirtAddr, len: usize, flags: PageTableEntryFlags) {
+	let phys_addr = paging::virtual_to_physical(start).unwrap();
+	let count = len.div_ceil(BasePageSize::SIZE as usize);
+	paging::map::<BasePageSize>(start, phys_addr, count, flags);
+}
+
+/// Prepares `len` bytes of memory at `start`, which have been allocated for devices.
+///
+/// If DMA is not cache-coherent, the memory is remapped as normal
+/// non-cacheable memory.
+pub(crate) fn prepare_device_memory(start: VirtAddr, len: usize) {
+	if dma_coherent() {
+		return;
+	}
+
+	let mut flags = PageTableEntryFlags::empty();
+	flags.normal_non_cacheable().writable().execute_disable();
+	remap(start, len, flags);
+	// Lines of the cacheable mapping must not be written back or hit later.
+	flush_dcache_range(start, len);
+}
+
+/// Restores the mapping of memory, which is no longer used by devices.
+pub(crate) fn release_device_memory(start: VirtAddr, len: usize) {
+	if dma_coherent() {
+		return;
+	}
+
+	let mut flags = PageTableEntryFlags::empty();
+	flags.normal().writable().execute_disable();
+	remap(start, len, flags);
+}
+
+/// Orders the accesses of the CPU to memory and registers of devices.
+///
+/// Devices are in the outer shareable domain, so that `dmb osh` orders the
+/// accesses to shared memory. A write of a device register is only ordered
+/// after the writes of memory by `dsb st`.
+#[inline]
+pub(crate) fn dma_barrier(barrier: DmaBarrier) {
+	unsafe {
+		match barrier {
+			DmaBarrier::Read => asm!("dmb oshld", options(nostack, preserves_flags)),
+			DmaBarrier::Write => asm!("dmb oshst", options(nostack, preserves_flags)),
+			DmaBarrier::Full => asm!("dmb osh", options(nostack, preserves_flags)),
+			DmaBarrier::Doorbell => asm!("dsb st", options(nostack, preserves_flags)),
+		}
+	}
+}
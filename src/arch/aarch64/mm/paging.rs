@@ -109,6 +109,14 @@ impl PageTableEntryFlags {
 		self
 	}
 
+	pub fn normal_non_cacheable(&mut self) -> &mut Self {
+		self.remove(PageTableEntryFlags::NORMAL);
+		self.remove(PageTableEntryFlags::DEVICE_NGNRE);
+		self.remove(PageTableEntryFlags::DEVICE_GRE);
+		self.insert(PageTableEntryFlags::NORMAL_NC);
+		self
+	}
+
 	pub fn read_only(&mut self) -> &mut Self {
 		self.insert(PageTableEntryFlags::READ_ONLY);
 		self
@@ -1,38 +1,84 @@
 #![allow(dead_code)]
 
-#[cfg(all(any(feature = "virtio-net", feature = "console"), not(feature = "pci")))]
+#[cfg(all(
+	any(
+		feature = "virtio-net",
+		feature = "console",
+		feature = "fuse",
+		feature = "virtio-9p",
+		feature = "vsock"
+	),
+	not(feature = "pci"),
+))]
 use core::ptr::NonNull;
 
 use fdt::Fdt;
-use memory_addresses::PhysAddr;
-#[cfg(all(feature = "gem-net", not(feature = "pci")))]
-use memory_addresses::VirtAddr;
-#[cfg(all(any(feature = "virtio-net", feature = "console"), not(feature = "pci")))]
+use memory_addresses::{PhysAddr, VirtAddr};
+#[cfg(all(
+	any(
+		feature = "virtio-net",
+		feature = "console",
+		feature = "fuse",
+		feature = "virtio-9p",
+		feature = "vsock"
+	),
+	not(feature = "pci"),
+))]
 use virtio::mmio::{DeviceRegisters, DeviceRegistersVolatileFieldAccess};
-#[cfg(all(any(feature = "virtio-net", feature = "console"), not(feature = "pci")))]
+#[cfg(all(
+	any(
+		feature = "virtio-net",
+		feature = "console",
+		feature = "fuse",
+		feature = "virtio-9p",
+		feature = "vsock"
+	),
+	not(feature = "pci"),
+))]
 use volatile::VolatileRef;
 
 use crate::arch::riscv64::kernel::get_dtb_ptr;
 use crate::arch::riscv64::kernel::interrupts::init_plic;
-#[cfg(all(feature = "console", not(feature = "pci")))]
+#[cfg(all(
+	any(feature = "console", feature = "fuse", feature = "vsock"),
+	not(feature = "pci")
+))]
 use crate::arch::riscv64::kernel::mmio::MmioDriver;
 use crate::arch::riscv64::mm::paging::{self, PageSize};
 #[cfg(feature = "console")]
 use crate::console::IoDevice;
 #[cfg(feature = "console")]
 use crate::drivers::console::VirtioUART;
+#[cfg(all(feature = "gem-net", not(feature = "pci")))]
+use crate::drivers::failure::{self, DeviceClass};
 #[cfg(all(feature = "console", not(feature = "pci")))]
 use crate::drivers::mmio::get_console_driver;
 #[cfg(all(feature = "gem-net", not(feature = "pci")))]
 use crate::drivers::net::gem;
 #[cfg(all(feature = "console", feature = "pci"))]
 use crate::drivers::pci::get_console_driver;
-#[cfg(all(any(feature = "virtio-net", feature = "console"), not(feature = "pci")))]
-use crate::drivers::virtio::transport::mmio::{self as mmio_virtio, VirtioDriver};
+#[cfg(all(
+	any(
+		feature = "virtio-net",
+		feature = "console",
+		feature = "fuse",
+		feature = "virtio-9p",
+		feature = "vsock"
+	),
+	not(feature = "pci"),
+))]
+use crate::drivers::virtio::transport::{VirtioDriver, mmio as mmio_virtio};
+#[cfg(all(feature = "gem-net", not(feature = "pci")))]
+use crate::errno::Errno;
 #[cfg(all(any(feature = "gem-net", feature = "virtio-net"), not(feature = "pci")))]
-use crate::executor::device::NETWORK_DEVICE;
-#[cfg(all(feature = "console", not(feature = "pci")))]
+use crate::executor::device::NETWORK_DEVICES;
+#[cfg(all(
+	any(feature = "console", feature = "fuse", feature = "vsock"),
+	not(feature = "pci")
+))]
 use crate::kernel::mmio::register_driver;
+use crate::mm;
+use crate::mm::mmio::MmioRegion;
 
 static mut PLATFORM_MODEL: Model = Model::Unknown;
 
@@ -104,6 +150,10 @@ pub fn init_drivers() {
 						< usize::try_from(paging::HugePageSize::SIZE).unwrap()
 				);
 
+				let region =
+					MmioRegion::new("plic", "plic", plic_region_start, plic_region.size.unwrap());
+				mm::mmio::register(region, VirtAddr::new(plic_region_start.as_u64()))
+					.unwrap_or_else(|conflict| panic!("{conflict}"));
 				paging::identity_map::<paging::HugePageSize>(plic_region_start);
 
 				// TODO: Determine correct context via devicetree and allow more than one context
@@ -156,6 +206,14 @@ pub fn init_drivers() {
 				assert!(
 					gem_region.size.unwrap() < usize::try_from(paging::HugePageSize::SIZE).unwrap()
 				);
+				let region = MmioRegion::new(
+					"gem",
+					gem_node.name,
+					gem_region_start,
+					gem_region.size.unwrap(),
+				);
+				mm::mmio::register(region, VirtAddr::new(gem_region_start.as_u64()))
+					.unwrap_or_else(|conflict| panic!("{conflict}"));
 				paging::identity_map::<paging::HugePageSize>(gem_region_start);
 				match gem::init_device(
 					VirtAddr::new(gem_region_start.as_u64()),
@@ -163,13 +221,22 @@ pub fn init_drivers() {
 					phy_addr,
 					<[u8; 6]>::try_from(mac).expect("MAC with invalid length"),
 				) {
-					Ok(drv) => *NETWORK_DEVICE.lock() = Some(drv),
-					Err(err) => error!("Could not initialize GEM driver: {err}"),
+					Ok(drv) => NETWORK_DEVICES.lock().push(drv),
+					Err(err) => failure::record(DeviceClass::Network, 0, Errno::Io, err),
 				}
 			}
 
 			// Init virtio-mmio
-			#[cfg(all(any(feature = "virtio-net", feature = "console"), not(feature = "pci")))]
+			#[cfg(all(
+				any(
+					feature = "virtio-net",
+					feature = "console",
+					feature = "fuse",
+					feature = "virtio-9p",
+					feature = "vsock"
+				),
+				not(feature = "pci"),
+			))]
 			if let Some(virtio_node) = fdt.find_compatible(&["virtio,mmio"]) {
 				debug!("Found virtio mmio device");
 				let virtio_region = virtio_node
@@ -190,6 +257,18 @@ pub fn init_drivers() {
 					virtio_region.size.unwrap()
 						< usize::try_from(paging::HugePageSize::SIZE).unwrap()
 				);
+				let region = MmioRegion::new(
+					"virtio",
+					virtio_node.name,
+					virtio_region_start,
+					virtio_region.size.unwrap(),
+				);
+				if let Err(conflict) =
+					mm::mmio::register(region, VirtAddr::new(virtio_region_start.as_u64()))
+				{
+					error!("{conflict}");
+					return;
+				}
 				paging::identity_map::<paging::HugePageSize>(virtio_region_start);
 
 				// Verify the first register value to find out if this is really an MMIO magic-value.
@@ -230,31 +309,37 @@ pub fn init_drivers() {
 						.unwrap_err();
 				}
 
-				match id {
+				debug!("Found virtio device {id:?} at {mmio:p}");
+
+				match mmio_virtio::init_device(mmio, irq.try_into().unwrap()) {
 					#[cfg(all(feature = "virtio-net", not(feature = "gem-net")))]
-					virtio::Id::Net => {
-						debug!("Found virtio network card at {mmio:p}");
-
-						if let Ok(VirtioDriver::Network(drv)) =
-							mmio_virtio::init_device(mmio, irq.try_into().unwrap())
-						{
-							*NETWORK_DEVICE.lock() = Some(drv);
-						}
+					Ok(VirtioDriver::Network(drv)) => {
+						NETWORK_DEVICES.lock().push(drv);
 					}
 					#[cfg(feature = "console")]
-					virtio::Id::Console => {
-						debug!("Found virtio console at {mmio:p}");
-
-						if let Ok(VirtioDriver::Console(drv)) =
-							mmio_virtio::init_device(mmio, irq.try_into().unwrap())
-						{
-							register_driver(MmioDriver::VirtioConsole(
-								hermit_sync::InterruptSpinMutex::new(*drv),
-							));
-						}
+					Ok(VirtioDriver::Console(drv)) => {
+						register_driver(MmioDriver::VirtioConsole(
+							hermit_sync::InterruptSpinMutex::new(*drv),
+						));
+					}
+					#[cfg(feature = "vsock")]
+					Ok(VirtioDriver::Vsock(drv)) => {
+						register_driver(MmioDriver::VirtioVsock(
+							hermit_sync::InterruptSpinMutex::new(*drv),
+						));
+					}
+					#[cfg(feature = "fuse")]
+					Ok(VirtioDriver::FileSystem(drv)) => {
+						register_driver(MmioDriver::VirtioFs(
+							hermit_sync::InterruptSpinMutex::new(drv),
+						));
+					}
+					#[cfg(feature = "virtio-9p")]
+					Ok(VirtioDriver::NineP(drv)) => {
+						crate::drivers::fs::virtio_9p::register_driver(drv);
 					}
-					_ => {
-						warn!("Found unknown virtio device with ID {id:?} at {mmio:p}");
+					Err(err) => {
+						warn!("Could not initialize virtio device with ID {id:?}: {err}");
 					}
 				}
 			}
@@ -262,7 +347,14 @@ pub fn init_drivers() {
 	}
 
 	#[cfg(all(
-		any(feature = "virtio-net", feature = "console", feature = "gem-net"),
+		any(
+			feature = "virtio-net",
+			feature = "console",
+			feature = "fuse",
+			feature = "virtio-9p",
+			feature = "vsock",
+			feature = "gem-net"
+		),
 		not(feature = "pci"),
 	))]
 	super::mmio::MMIO_DRIVERS.finalize();
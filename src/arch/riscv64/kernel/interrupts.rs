@@ -1,3 +1,4 @@
+use alloc::string::String;
 use alloc::vec::Vec;
 
 use ahash::RandomState;
@@ -13,7 +14,7 @@ use crate::drivers::InterruptHandlerQueue;
 use crate::drivers::mmio::get_interrupt_handlers;
 #[cfg(feature = "pci")]
 use crate::drivers::pci::get_interrupt_handlers;
-use crate::scheduler;
+use crate::scheduler::{self, CoreId};
 
 /// base address of the PLIC, only one access at the same time is allowed
 static PLIC_BASE: SpinMutex<usize> = SpinMutex::new(0x0);
@@ -120,6 +121,7 @@ pub(crate) fn disable() {
 /// Currently not needed because we use the trapframe crate
 pub(crate) fn install_handlers() {
 	let handlers = get_interrupt_handlers();
+	crate::drivers::irq_affinity::spread(handlers.keys().copied());
 
 	for irq_number in handlers.keys() {
 		unsafe {
@@ -146,6 +148,39 @@ pub(crate) fn install_handlers() {
 	INTERRUPT_HANDLERS.set(handlers).unwrap();
 }
 
+/// Routes the interrupt line `irq` to the core `core_id`.
+///
+/// Returns `false`, because the PLIC is only enabled for the context of the
+/// boot core.
+pub(crate) fn set_irq_affinity(_irq: u8, _core_id: CoreId) -> bool {
+	false
+}
+
+/// Masks or unmasks the interrupt line `irq` at the PLIC for the current context.
+///
+/// Returns `false`, if the PLIC has not been initialized.
+pub(crate) fn set_irq_masked(irq: u8, masked: bool) -> bool {
+	let base_ptr = PLIC_BASE.lock();
+	if *base_ptr == 0 {
+		return false;
+	}
+	let context = PLIC_CONTEXT.lock();
+
+	const PLIC_ENABLE_OFFSET: usize = 0x0000_2000;
+	let enable_address =
+		*base_ptr + PLIC_ENABLE_OFFSET + 0x80 * (*context as usize) + ((irq / 32) * 4) as usize;
+	unsafe {
+		let enabled = core::ptr::read_volatile(enable_address as *const u32);
+		let enabled = if masked {
+			enabled & !(1 << (irq % 32))
+		} else {
+			enabled | (1 << (irq % 32))
+		};
+		core::ptr::write_volatile(enable_address as *mut u32, enabled);
+	}
+	true
+}
+
 // Derived from rCore: https://github.com/rcore-os/rCore
 /// Dispatch and handle interrupt.
 ///
@@ -176,7 +211,7 @@ pub extern "C" fn trap_handler(tf: &mut TrapFrame) {
 			error!("Interrupt: {cause:?}");
 			error!("tf = {tf:x?} ");
 			error!("stval = {stval:x}");
-			error!("sepc = {sepc:x}");
+			error!("sepc = {}", crate::symbols::Symbolized(sepc));
 			error!("SSTATUS FS = {:?}", sstatus::read().fs());
 			scheduler::abort();
 		}
@@ -190,9 +225,9 @@ fn external_handler() {
 	use crate::scheduler::PerCoreSchedulerExt;
 
 	// Claim interrupt
-	let base_ptr = PLIC_BASE.lock();
-	let context = PLIC_CONTEXT.lock();
-	let claim_address = *base_ptr + 0x20_0004 + 0x1000 * (*context as usize);
+	let base_ptr = *PLIC_BASE.lock();
+	let context = *PLIC_CONTEXT.lock();
+	let claim_address = base_ptr + 0x20_0004 + 0x1000 * (context as usize);
 	let irq = unsafe { core::ptr::read_volatile(claim_address as *mut u32) };
 
 	if irq != 0 {
@@ -212,6 +247,11 @@ fn external_handler() {
 			for handler in queue.iter() {
 				handler();
 			}
+
+			// Arm the timer, which unmasks the line again.
+			if crate::drivers::irq_storm::count(u8::try_from(irq).unwrap()) {
+				core_scheduler().handle_waiting_tasks();
+			}
 		}
 		crate::executor::run();
 
@@ -233,3 +273,10 @@ fn external_handler() {
 }
 
 pub(crate) fn print_statistics() {}
+
+/// Returns the interrupt counters in the format of `/proc/interrupts`.
+///
+/// Interrupts are not counted on RISC-V.
+pub(crate) fn proc_interrupts() -> String {
+	String::new()
+}
@@ -2,15 +2,19 @@
 
 use alloc::vec::Vec;
 
-#[cfg(feature = "console")]
+#[cfg(any(feature = "console", feature = "fuse", feature = "vsock"))]
 use hermit_sync::InterruptSpinMutex;
 
 #[cfg(feature = "console")]
 use crate::drivers::console::VirtioConsoleDriver;
+#[cfg(feature = "fuse")]
+use crate::drivers::fs::virtio_fs::VirtioFsDriver;
 #[cfg(feature = "gem-net")]
 use crate::drivers::net::gem::GEMDriver;
 #[cfg(all(not(feature = "gem-net"), feature = "virtio-net"))]
 use crate::drivers::net::virtio::VirtioNetDriver;
+#[cfg(feature = "vsock")]
+use crate::drivers::vsock::VirtioVsockDriver;
 use crate::init_cell::InitCell;
 
 pub(crate) static MMIO_DRIVERS: InitCell<Vec<MmioDriver>> = InitCell::new(Vec::new());
@@ -18,13 +22,37 @@ pub(crate) static MMIO_DRIVERS: InitCell<Vec<MmioDriver>> = InitCell::new(Vec::n
 pub(crate) enum MmioDriver {
 	#[cfg(feature = "console")]
 	VirtioConsole(InterruptSpinMutex<VirtioConsoleDriver>),
+	#[cfg(feature = "fuse")]
+	VirtioFs(InterruptSpinMutex<VirtioFsDriver>),
+	#[cfg(feature = "vsock")]
+	VirtioVsock(InterruptSpinMutex<VirtioVsockDriver>),
 }
 
 impl MmioDriver {
 	#[cfg(feature = "console")]
 	fn get_console_driver(&self) -> Option<&InterruptSpinMutex<VirtioConsoleDriver>> {
+		#[allow(unreachable_patterns)]
 		match self {
 			Self::VirtioConsole(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	#[cfg(feature = "fuse")]
+	fn get_filesystem_driver(&self) -> Option<&InterruptSpinMutex<VirtioFsDriver>> {
+		#[allow(unreachable_patterns)]
+		match self {
+			Self::VirtioFs(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	#[cfg(feature = "vsock")]
+	fn get_vsock_driver(&self) -> Option<&InterruptSpinMutex<VirtioVsockDriver>> {
+		#[allow(unreachable_patterns)]
+		match self {
+			Self::VirtioVsock(drv) => Some(drv),
+			_ => None,
 		}
 	}
 }
@@ -46,3 +74,19 @@ pub(crate) fn get_console_driver() -> Option<&'static InterruptSpinMutex<VirtioC
 		.iter()
 		.find_map(|drv| drv.get_console_driver())
 }
+
+#[cfg(feature = "fuse")]
+pub(crate) fn get_filesystem_driver() -> Option<&'static InterruptSpinMutex<VirtioFsDriver>> {
+	MMIO_DRIVERS
+		.get()?
+		.iter()
+		.find_map(|drv| drv.get_filesystem_driver())
+}
+
+#[cfg(feature = "vsock")]
+pub(crate) fn get_vsock_driver() -> Option<&'static InterruptSpinMutex<VirtioVsockDriver>> {
+	MMIO_DRIVERS
+		.get()?
+		.iter()
+		.find_map(|drv| drv.get_vsock_driver())
+}
@@ -2,9 +2,10 @@ use core::arch::asm;
 use core::convert::TryInto;
 use core::num::NonZeroU64;
 
+use fdt::Fdt;
 use riscv::register::{sie, sstatus, time};
 
-use crate::arch::riscv64::kernel::{HARTS_AVAILABLE, get_timebase_freq};
+use crate::arch::riscv64::kernel::{HARTS_AVAILABLE, get_dtb_ptr, get_timebase_freq};
 use crate::scheduler::CoreId;
 
 /// Current FPU state. Saved at context switch when changed
@@ -224,6 +225,28 @@ pub fn halt() {
 	riscv::asm::wfi();
 }
 
+/// Resets the system through SBI.
+pub fn reboot() -> ! {
+	info!("Rebooting system");
+
+	cfg_if::cfg_if! {
+		if #[cfg(feature = "semihosting")] {
+			// Semihosting cannot reset the system.
+			semihosting::process::exit(1)
+		} else {
+			match sbi_rt::system_reset(sbi_rt::ColdReboot, sbi_rt::NoReason).into_result() {
+				Ok(_) => unreachable!("System reset shouldn't have returned with success."),
+				Err(err) => {
+					error!("Could not reboot. SBI error: {err:?}");
+					loop {
+						core::hint::spin_loop();
+					}
+				}
+			}
+		}
+	}
+}
+
 /// Shutdown the system
 #[allow(unused_variables)]
 pub fn shutdown(error_code: i32) -> ! {
@@ -233,8 +256,13 @@ pub fn shutdown(error_code: i32) -> ! {
 		if #[cfg(feature = "semihosting")] {
 			semihosting::process::exit(error_code)
 		} else {
-			// use SBI shutdown
-			match sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::NoReason).into_result() {
+			// use SBI shutdown, which only distinguishes success and failure
+			let result = if error_code == 0 {
+				sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::NoReason)
+			} else {
+				sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::SystemFailure)
+			};
+			match result.into_result() {
 				Ok(_) => unreachable!("System reset shouldn't have returned with success."),
 				Err(err) => {
 					error!("Could not shutdown. SBI error: {err:?}");
@@ -267,6 +295,127 @@ pub fn supports_1gib_pages() -> bool {
 	true
 }
 
+bitflags! {
+	/// CPU features, which are available to applications
+	///
+	/// Bit `n` of the lower 26 bits represents the single-letter extension
+	/// `'a' + n`, as in the `AT_HWCAP` of Linux. The bits are part
+	/// of the ABI of `sys_cpu_features` and must not change.
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub struct CpuFeatureFlags: u64 {
+		const A = 1 << (b'a' - b'a');
+		const C = 1 << (b'c' - b'a');
+		const D = 1 << (b'd' - b'a');
+		const F = 1 << (b'f' - b'a');
+		const I = 1 << (b'i' - b'a');
+		const M = 1 << (b'm' - b'a');
+		const V = 1 << (b'v' - b'a');
+		const ZBA = 1 << 32;
+		const ZBB = 1 << 33;
+		const ZBC = 1 << 34;
+		const ZBS = 1 << 35;
+		const ZICBOM = 1 << 36;
+		const ZICBOZ = 1 << 37;
+		const ZKND = 1 << 38;
+		const ZKNE = 1 << 39;
+		const ZKNH = 1 << 40;
+		const ZKR = 1 << 41;
+	}
+}
+
+/// Multi-letter extensions and their bits
+const MULTI_LETTER_EXTENSIONS: &[(&str, CpuFeatureFlags)] = &[
+	("zba", CpuFeatureFlags::ZBA),
+	("zbb", CpuFeatureFlags::ZBB),
+	("zbc", CpuFeatureFlags::ZBC),
+	("zbs", CpuFeatureFlags::ZBS),
+	("zicbom", CpuFeatureFlags::ZICBOM),
+	("zicboz", CpuFeatureFlags::ZICBOZ),
+	("zknd", CpuFeatureFlags::ZKND),
+	("zkne", CpuFeatureFlags::ZKNE),
+	("zknh", CpuFeatureFlags::ZKNH),
+	("zkr", CpuFeatureFlags::ZKR),
+];
+
+/// Parses an ISA string like `rv64imafdc_zicsr_zba`.
+fn parse_isa(isa: &str) -> CpuFeatureFlags {
+	let isa = isa.trim_end_matches('\0').to_ascii_lowercase();
+	let Some(isa) = isa.strip_prefix("rv64") else {
+		return CpuFeatureFlags::empty();
+	};
+
+	let mut extensions = isa.split('_');
+	let mut flags = CpuFeatureFlags::empty();
+	for letter in extensions.next().unwrap_or_default().bytes() {
+		match letter {
+			// `g` is an abbreviation of `imafd`
+			b'g' => flags.insert(
+				CpuFeatureFlags::I
+					| CpuFeatureFlags::M
+					| CpuFeatureFlags::A
+					| CpuFeatureFlags::F
+					| CpuFeatureFlags::D,
+			),
+			b'a'..=b'z' => flags.insert(CpuFeatureFlags::from_bits_retain(1 << (letter - b'a'))),
+			_ => {}
+		}
+	}
+	for extension in extensions {
+		if let Some((_, flag)) = MULTI_LETTER_EXTENSIONS
+			.iter()
+			.find(|(name, _)| *name == extension)
+		{
+			flags.insert(*flag);
+		}
+	}
+	flags
+}
+
+/// Returns the CPU features, which are available to applications.
+///
+/// The features are read from the ISA string of the boot hart in the device tree.
+pub fn cpu_features() -> CpuFeatureFlags {
+	let fdt = unsafe { Fdt::from_ptr(get_dtb_ptr()).expect("FDT is invalid") };
+	let Some(isa) = fdt
+		.cpus()
+		.next()
+		.and_then(|cpu| cpu.property("riscv,isa"))
+		.and_then(|isa| isa.as_str())
+	else {
+		return CpuFeatureFlags::empty();
+	};
+
+	let mut flags = parse_isa(isa);
+	// Vector instructions trap, unless the kernel has enabled them in `sstatus`.
+	if !vector_enabled() {
+		flags.remove(CpuFeatureFlags::V);
+	}
+	flags
+}
+
+/// Returns whether the vector unit is enabled (`sstatus.VS` is not `Off`).
+fn vector_enabled() -> bool {
+	let sstatus: usize;
+	unsafe {
+		asm!("csrr {}, sstatus", out(reg) sstatus, options(nostack, nomem));
+	}
+	(sstatus >> 9) & 0b11 != 0
+}
+
+/// Returns the width of the widest vector registers, which are available to applications, in bits.
+pub fn vector_width() -> u32 {
+	if vector_enabled() {
+		let vlenb: usize;
+		// `vlenb` is accessed by its number to avoid depending on the `v` target feature.
+		unsafe {
+			asm!("csrr {}, 0xc22", out(reg) vlenb, options(nostack, nomem));
+		}
+		u32::try_from(vlenb * 8).unwrap()
+	} else {
+		0
+	}
+}
+
 pub fn supports_2mib_pages() -> bool {
 	true
 }
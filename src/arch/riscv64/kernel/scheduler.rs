@@ -386,7 +386,7 @@ extern "C" fn task_entry(func: extern "C" fn(usize), arg: usize) {
 	//unsafe{debug!("state: {:#X?}", *((func as usize -31*8 ) as *const crate::arch::riscv64::kernel::scheduler::State));}
 	//panic!("Not impl");
 	//println!("Task start");
-	func(arg);
+	crate::scheduler::call_task_entry(func, arg);
 	//println!("Task end");
 
 	// switch_to_kernel!();
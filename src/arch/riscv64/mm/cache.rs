@@ -0,0 +1,119 @@
+//! Cache maintenance for DMA.
+//!
+//! Devices are assumed to be cache-coherent, unless the devicetree marks the
+//! platform as `dma-noncoherent`. In this case, the cache blocks are managed
+//! by the instructions of the Zicbom extension.
+
+use core::arch::asm;
+
+use align_address::Align;
+use fdt::Fdt;
+use hermit_sync::Lazy;
+use memory_addresses::VirtAddr;
+
+use crate::arch::riscv64::kernel::get_dtb_ptr;
+use crate::mm::dma::DmaBarrier;
+
+/// Size of a cache block, if DMA is not cache-coherent
+static CACHE_BLOCK_SIZE: Lazy<Option<usize>> = Lazy::new(|| {
+	let dtb = get_dtb_ptr();
+	if dtb.is_null() {
+		return None;
+	}
+
+	let fdt = unsafe { Fdt::from_ptr(dtb) }.ok()?;
+	let noncoherent = ["/", "/soc"].iter().any(|path| {
+		fdt.find_node(path)
+			.is_some_and(|node| node.property("dma-noncoherent").is_some())
+	});
+	if !noncoherent {
+		return None;
+	}
+
+	let block_size = fdt
+		.find_node("/cpus")?
+		.children()
+		.find_map(|cpu| cpu.property("riscv,cbom-block-size"))
+		.and_then(|property| property.as_usize());
+	if block_size.is_none() {
+		warn!("DMA is not cache-coherent, but the Zicbom extension is not available");
+	}
+	block_size
+});
+
+/// Applies the cache-block management instruction `op` (see `cbo.*`) to all
+/// cache blocks of the range.
+macro_rules! for_each_block {
+	($start:expr, $len:expr, $op:literal) => {{
+		if let Some(block_size) = *CACHE_BLOCK_SIZE {
+			let end = $start.as_usize() + $len;
+			let mut addr = $start.as_usize().align_down(block_size);
+			while addr < end {
+				unsafe {
+					asm!(concat!(".insn i 0x0f, 2, x0, {0}, ", $op), in(reg) addr, options(nostack));
+				}
+				addr += block_size;
+			}
+			unsafe {
+				asm!("fence iorw, iorw", options(nostack));
+			}
+		}
+	}};
+}
+
+/// Returns whether devices observe the contents of the CPU caches.
+#[inline]
+pub(crate) fn dma_coherent() -> bool {
+	CACHE_BLOCK_SIZE.is_none()
+}
+
+/// Writes dirty cache blocks of the range back to memory (`cbo.clean`).
+pub(crate) fn clean_dcache_range(start: VirtAddr, len: usize) {
+	for_each_block!(start, len, "1");
+}
+
+/// Discards the cache blocks of the range (`cbo.inval`).
+///
+/// Blocks, which are only partially covered by the range, are flushed
+/// instead, so that adjacent data is not lost.
+pub(crate) fn invalidate_dcache_range(start: VirtAddr, len: usize) {
+	let Some(block_size) = *CACHE_BLOCK_SIZE else {
+		return;
+	};
+
+	let end = start.as_usize() + len;
+	let head = start.as_usize().align_up(block_size).min(end);
+	let tail = end.align_down(block_size).max(head);
+	flush_dcache_range(start, head - start.as_usize());
+	for_each_block!(VirtAddr::new(head as u64), tail - head, "0");
+	flush_dcache_range(VirtAddr::new(tail as u64), end - tail);
+}
+
+/// Writes dirty cache blocks of the range back to memory and discards them (`cbo.flush`).
+pub(crate) fn flush_dcache_range(start: VirtAddr, len: usize) {
+	for_each_block!(start, len, "2");
+}
+
+/// Prepares `len` bytes of memory at `start`, which have been allocated for devices.
+#[inline]
+pub(crate) fn prepare_device_memory(_start: VirtAddr, _len: usize) {}
+
+/// Restores the mapping of memory, which is no longer used by devices.
+#[inline]
+pub(crate) fn release_device_memory(_start: VirtAddr, _len: usize) {}
+
+/// Orders the accesses of the CPU to memory and registers of devices.
+///
+/// A write of a device register is an output (`o`) of the fence, since the
+/// registers are mapped as I/O memory.
+#[inline]
+pub(crate) fn dma_barrier(barrier: DmaBarrier) {
+	unsafe {
+		match barrier {
+			DmaBarrier::Read => asm!("fence r, r", options(nostack, preserves_flags)),
+			DmaBarrier::Write => asm!("fence w, w", options(nostack, preserves_flags)),
+			DmaBarrier::Full => asm!("fence rw, rw", options(nostack, preserves_flags)),
+			DmaBarrier::Doorbell => asm!("fence w, o", options(nostack, preserves_flags)),
+		}
+	}
+}
@@ -1,3 +1,4 @@
+pub mod cache;
 pub mod paging;
 
 pub use self::paging::init_page_tables;
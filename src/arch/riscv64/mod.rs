@@ -1,8 +1,12 @@
 pub mod kernel;
 pub mod mm;
 
+/// Force strict CPU ordering, serializes load and store operations.
 #[allow(dead_code)]
 #[inline(always)]
 pub(crate) fn memory_barrier() {
-	riscv::asm::sfence_vma_all();
+	use core::arch::asm;
+	unsafe {
+		asm!("fence iorw, iorw", options(nostack, preserves_flags));
+	}
 }
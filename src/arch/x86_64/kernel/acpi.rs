@@ -7,10 +7,12 @@ use memory_addresses::{PhysAddr, VirtAddr};
 use x86_64::instructions::port::Port;
 use x86_64::structures::paging::PhysFrame;
 
+use crate::arch::x86_64::kernel::interrupts;
 use crate::arch::x86_64::mm::paging;
 use crate::arch::x86_64::mm::paging::{
 	BasePageSize, PageSize, PageTableEntryFlags, PageTableEntryFlagsExt,
 };
+use crate::drivers::InterruptLine;
 use crate::env;
 use crate::mm::virtualmem::KERNEL_FREE_LIST;
 
@@ -42,6 +44,12 @@ const AML_BYTEPREFIX: u8 = 0x0a;
 
 /// Bit to enable an ACPI Sleep State.
 const SLP_EN: u16 = 1 << 13;
+/// Bit in the PM1 Control Register indicating that the system is in ACPI mode.
+const SCI_EN: u16 = 1 << 0;
+/// Bit of the power button in the PM1 Status and Enable Registers.
+const PWRBTN: u16 = 1 << 8;
+/// Number of attempts to read SCI_EN after requesting the ACPI mode.
+const ACPI_ENABLE_ATTEMPTS: usize = 1_000_000;
 
 /// The "Multiple APIC Description Table" (MADT) preserved for get_apic_table().
 static MADT: OnceCell<AcpiTable<'_>> = OnceCell::new();
@@ -49,6 +57,13 @@ static MADT: OnceCell<AcpiTable<'_>> = OnceCell::new();
 static PM1A_CNT_BLK: OnceCell<Port<u16>> = OnceCell::new();
 /// The Sleeping State Type code for powering off the computer through ACPI.
 static SLP_TYPA: OnceCell<u8> = OnceCell::new();
+/// The I/O port of the PM1A Event Block and its length in bytes.
+/// The first half of the block is the status register, the second half the enable register.
+static PM1A_EVT_BLK: OnceCell<(u16, u8)> = OnceCell::new();
+/// The interrupt line of the System Control Interrupt (SCI).
+static SCI_INT: OnceCell<u8> = OnceCell::new();
+/// The SMI Command Port and the value, which is written to it to enter ACPI mode.
+static SMI_CMD: OnceCell<(u16, u8)> = OnceCell::new();
 
 /// The "Root System Description Pointer" structure providing pointers to all other ACPI tables.
 #[repr(C, packed)]
@@ -446,6 +461,28 @@ fn parse_fadt(fadt: AcpiTable<'_>) {
 	};
 	PM1A_CNT_BLK.set(Port::new(pm1a_cnt_blk)).unwrap();
 
+	// The same applies to x_pm1a_evt_blk, which is used for the fixed power button.
+	let x_pm1a_evt_blk_field_address = ptr::from_ref(&fadt_table.x_pm1a_evt_blk).addr();
+	let pm1a_evt_blk = if x_pm1a_evt_blk_field_address < fadt.table_end_address()
+		&& fadt_table.x_pm1a_evt_blk.address_space == GENERIC_ADDRESS_IO_SPACE
+	{
+		fadt_table.x_pm1a_evt_blk.address as u16
+	} else {
+		fadt_table.pm1a_evt_blk as u16
+	};
+	let pm1_evt_len = fadt_table.pm1_evt_len;
+	if pm1a_evt_blk != 0 && pm1_evt_len >= 4 {
+		PM1A_EVT_BLK.set((pm1a_evt_blk, pm1_evt_len)).unwrap();
+	}
+	SCI_INT.set(fadt_table.sci_int as u8).unwrap();
+	// A zero SMI Command Port means that the system is always in ACPI mode.
+	let smi_cmd = fadt_table.smi_cmd;
+	if smi_cmd != 0 {
+		SMI_CMD
+			.set((smi_cmd as u16, fadt_table.acpi_enable))
+			.unwrap();
+	}
+
 	// Map the "Differentiated System Description Table" (DSDT).
 	let x_dsdt_field_address = ptr::addr_of!(fadt_table.x_dsdt) as usize;
 	let dsdt_address = if x_dsdt_field_address < fadt.table_end_address() && fadt_table.x_dsdt > 0 {
@@ -500,6 +537,68 @@ pub fn poweroff() {
 	}
 }
 
+/// Switches the system to ACPI mode, if the firmware has not done so.
+fn enable_acpi_mode(pm1a_cnt_blk: &mut Port<u16>) -> bool {
+	if unsafe { pm1a_cnt_blk.read() } & SCI_EN != 0 {
+		return true;
+	}
+
+	let Some(&(smi_cmd, acpi_enable)) = SMI_CMD.get() else {
+		return false;
+	};
+	debug!("Enabling ACPI mode through port {smi_cmd:#X}");
+	unsafe {
+		Port::<u8>::new(smi_cmd).write(acpi_enable);
+	}
+	(0..ACPI_ENABLE_ATTEMPTS).any(|_| unsafe { pm1a_cnt_blk.read() } & SCI_EN != 0)
+}
+
+/// Clears the status of the power button and returns whether it has been pressed.
+fn take_power_button() -> bool {
+	let Some(&(pm1a_evt_blk, _)) = PM1A_EVT_BLK.get() else {
+		return false;
+	};
+	let mut status = Port::<u16>::new(pm1a_evt_blk);
+	// The status bits are cleared by writing ones.
+	let pressed = unsafe { status.read() } & PWRBTN != 0;
+	if pressed {
+		unsafe {
+			status.write(PWRBTN);
+		}
+	}
+	pressed
+}
+
+/// Enables the fixed power button and returns the handler of the SCI, which
+/// turns a press of the power button into an orderly shutdown.
+pub(crate) fn get_power_button_handler() -> Option<(InterruptLine, fn())> {
+	fn sci_handler() {
+		if take_power_button() {
+			crate::shutdown::request_stop();
+		}
+	}
+
+	let (&(pm1a_evt_blk, pm1_evt_len), &sci_int) = (PM1A_EVT_BLK.get()?, SCI_INT.get()?);
+	let mut pm1a_cnt_blk = PM1A_CNT_BLK.get()?.clone();
+	if !enable_acpi_mode(&mut pm1a_cnt_blk) {
+		warn!("ACPI mode could not be enabled");
+		return None;
+	}
+
+	take_power_button();
+	let mut enable = Port::<u16>::new(pm1a_evt_blk + u16::from(pm1_evt_len / 2));
+	unsafe {
+		let bits = enable.read() | PWRBTN;
+		enable.write(bits);
+	}
+	debug!("Enabled the ACPI power button on interrupt {sci_int}");
+
+	interrupts::add_irq_name(sci_int, "ACPI");
+	crate::shutdown::watch_stop_requests();
+
+	Some((sci_int, sci_handler))
+}
+
 pub fn init() {
 	// Detect the RSDP and get a pointer to either the XSDT (64-bit) or RSDT (32-bit), whichever is available.
 	// Both are called RSDT in the following.
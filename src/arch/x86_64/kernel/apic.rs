@@ -14,7 +14,7 @@ use align_address::Align;
 use arch::x86_64::kernel::core_local::*;
 use arch::x86_64::kernel::{interrupts, processor};
 use free_list::{PageLayout, PageRange};
-use hermit_sync::{OnceCell, SpinMutex, without_interrupts};
+use hermit_sync::{InterruptSpinMutex, OnceCell, SpinMutex, without_interrupts};
 use memory_addresses::{AddrRange, PhysAddr, VirtAddr};
 #[cfg(feature = "smp")]
 use x86_64::registers::control::Cr3;
@@ -30,6 +30,7 @@ use crate::arch::x86_64::mm::paging::{
 };
 use crate::arch::x86_64::swapgs;
 use crate::config::*;
+use crate::mm::mmio::{self, MmioRegion};
 use crate::mm::virtualmem::KERNEL_FREE_LIST;
 use crate::scheduler::CoreId;
 use crate::{arch, env, scheduler};
@@ -144,6 +145,10 @@ static IOAPIC_ADDRESS: OnceCell<VirtAddr> = OnceCell::new();
 /// Both numbers often match, but don't need to (e.g. when a core has been disabled).
 static CPU_LOCAL_APIC_IDS: SpinMutex<Vec<u8>> = SpinMutex::new(Vec::new());
 
+/// Serializes the changes of the redirection table of the IO-APIC, which
+/// are made by all cores
+static IOAPIC_LOCK: InterruptSpinMutex<()> = InterruptSpinMutex::new(());
+
 /// After calibration, initialize the APIC Timer with this counter value to let it fire an interrupt
 /// after 1 microsecond.
 static CALIBRATED_COUNTER_VALUE: OnceCell<u64> = OnceCell::new();
@@ -327,6 +332,10 @@ fn init_ioapic_address(phys_addr: PhysAddr) {
 		flags.device().writable().execute_disable();
 		paging::map::<BasePageSize>(ioapic_address, phys_addr, 1, flags);
 	}
+
+	let region = MmioRegion::new("apic", "ioapic", phys_addr, BasePageSize::SIZE as usize);
+	mmio::register(region, *IOAPIC_ADDRESS.get().unwrap())
+		.unwrap_or_else(|conflict| panic!("{conflict}"));
 }
 
 #[cfg(not(feature = "acpi"))]
@@ -561,6 +570,15 @@ pub fn init() {
 			flags.device().writable().execute_disable();
 			paging::map::<BasePageSize>(local_apic_address, local_apic_physical_address, 1, flags);
 		}
+
+		let region = MmioRegion::new(
+			"apic",
+			"local-apic",
+			local_apic_physical_address,
+			BasePageSize::SIZE as usize,
+		);
+		mmio::register(region, *LOCAL_APIC_ADDRESS.get().unwrap())
+			.unwrap_or_else(|conflict| panic!("{conflict}"));
 	}
 
 	// Set gates to ISRs for the APIC interrupts we are going to enable.
@@ -615,6 +633,39 @@ fn init_ioapic() {
 	}
 }
 
+/// Masks or unmasks the line `irq` of the IO-APIC.
+///
+/// Returns `false`, if there is no IO-APIC or it has no such line.
+pub fn ioapic_set_masked(irq: u8, masked: bool) -> bool {
+	if IOAPIC_ADDRESS.get().is_none() || irq > ioapic_max_redirection_entry() {
+		return false;
+	}
+
+	let _guard = IOAPIC_LOCK.lock();
+	// keep the destination of the line
+	let apic_id = ioapic_read(IOAPIC_REG_TABLE + u32::from(irq) * 2 + 1) >> 24;
+	ioapic_set_interrupt(irq, apic_id as u8, !masked);
+	true
+}
+
+/// Routes the line `irq` of the IO-APIC to the core `core_id`.
+///
+/// Returns `false`, if there is no IO-APIC, it has no such line, or the core
+/// does not exist.
+pub fn ioapic_set_destination(irq: u8, core_id: CoreId) -> bool {
+	if IOAPIC_ADDRESS.get().is_none() || irq > ioapic_max_redirection_entry() {
+		return false;
+	}
+	let Some(&apic_id) = CPU_LOCAL_APIC_IDS.lock().get(core_id as usize) else {
+		return false;
+	};
+
+	let _guard = IOAPIC_LOCK.lock();
+	let off = u32::from(irq) * 2;
+	ioapic_write(IOAPIC_REG_TABLE + off + 1, u32::from(apic_id) << 24);
+	true
+}
+
 fn ioapic_set_interrupt(irq: u8, apicid: u8, enabled: bool) {
 	assert!(irq <= 24);
 
@@ -689,7 +740,7 @@ fn __set_oneshot_timer(wakeup_time: Option<u64>) {
 			// wt is the absolute wakeup time in microseconds based on processor::get_timer_ticks.
 			// We can simply multiply it by the processor frequency to get the absolute Time-Stamp Counter deadline
 			// (see processor::get_timer_ticks).
-			let tsc_deadline = wt * (u64::from(processor::get_frequency()));
+			let tsc_deadline = processor::ticks_to_timestamp(wt);
 
 			// Enable the APIC Timer in TSC-Deadline Mode and let it start by writing to the respective MSR.
 			local_apic_write(
@@ -867,6 +918,7 @@ pub fn boot_application_processors() {
 			while current_processor_count == arch::get_processor_count() {
 				hint::spin_loop();
 			}
+			super::tsc_sync::check_source(core_id_to_boot);
 		}
 	}
 
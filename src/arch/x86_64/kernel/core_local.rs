@@ -32,6 +32,8 @@ pub(crate) struct CoreLocal {
 	pub kernel_stack: Cell<*mut u8>,
 	/// Interface to the interrupt counters
 	irq_statistics: &'static IrqStatistics,
+	/// Number of nested kernel FPU guards
+	pub kernel_fpu_depth: Cell<u32>,
 	/// The core-local async executor.
 	ex: StaticExecutor<RawSpinMutex, RawRwSpinLock>,
 	#[cfg(feature = "smp")]
@@ -61,6 +63,7 @@ impl CoreLocal {
 			tss: Cell::new(ptr::null_mut()),
 			kernel_stack: Cell::new(ptr::null_mut()),
 			irq_statistics,
+			kernel_fpu_depth: Cell::new(0),
 			ex: StaticExecutor::new(),
 			#[cfg(feature = "smp")]
 			hlt: AtomicBool::new(false),
@@ -0,0 +1,165 @@
+//! Use of the FPU and SIMD registers by kernel code.
+//!
+//! The kernel is compiled without SSE and AVX and switches the FPU state of
+//! tasks lazily on the first use after a task switch (see
+//! `device_not_available_exception`). Kernel code, which uses SIMD registers
+//! (e.g., in functions with `#[target_feature(enable = "avx2")]`), has to hold
+//! a [`KernelFpuGuard`], which saves the state of the current FPU owner first.
+//! The guard can be used in interrupt and syscall context, but the guarded
+//! code must not block.
+
+use core::arch::asm;
+use core::arch::x86_64::{__m128i, _mm_setzero_si128, _mm_sfence, _mm_stream_si128};
+use core::marker::PhantomData;
+
+use x86_64::instructions::interrupts;
+use x86_64::registers::control::{Cr0, Cr0Flags};
+use x86_64::registers::mxcsr::{self, MxCsr};
+
+use crate::arch::x86_64::kernel::core_local::{CoreLocal, core_scheduler};
+
+/// Allows kernel code to use the FPU and SIMD registers as long as it is held.
+///
+/// Guards may be nested. Interrupts are disabled while a guard is held.
+pub(crate) struct KernelFpuGuard {
+	/// Interrupts were enabled when the guard was created.
+	interrupts_enabled: bool,
+	/// The guard belongs to the current core.
+	_not_send: PhantomData<*const ()>,
+}
+
+impl KernelFpuGuard {
+	pub fn new() -> Self {
+		let interrupts_enabled = interrupts::are_enabled();
+		interrupts::disable();
+
+		let depth = &CoreLocal::get().kernel_fpu_depth;
+		if depth.get() == 0 {
+			// Clear the task switched flag to access the FPU without a trap.
+			unsafe {
+				asm!("clts", options(nomem, nostack));
+			}
+
+			// Hand the registers from their owner to the kernel. The next use
+			// of the FPU by a task restores its state.
+			core_scheduler().fpu_release();
+
+			unsafe {
+				asm!("fninit", options(nomem, nostack));
+			}
+			mxcsr::write(MxCsr::default());
+		}
+		depth.set(depth.get() + 1);
+
+		Self {
+			interrupts_enabled,
+			_not_send: PhantomData,
+		}
+	}
+}
+
+impl Drop for KernelFpuGuard {
+	fn drop(&mut self) {
+		let depth = &CoreLocal::get().kernel_fpu_depth;
+		depth.set(depth.get() - 1);
+		if depth.get() == 0 {
+			// Let the next use of the FPU trap, so that the state of the task is restored.
+			unsafe {
+				Cr0::update(|flags| flags.insert(Cr0Flags::TASK_SWITCHED));
+			}
+		}
+
+		if self.interrupts_enabled {
+			interrupts::enable();
+		}
+	}
+}
+
+/// Largest number of bytes, which [`zero_nontemporal`] clears while
+/// interrupts are disabled
+const ZERO_CHUNK_SIZE: usize = 0x1_0000;
+
+/// Zeroes `len` bytes at `dst` with non-temporal SSE2 stores, which bypass the
+/// caches, so that clearing large buffers (e.g., stacks) does not evict the
+/// working set of the caches.
+///
+/// # Safety
+///
+/// `dst` has to be valid for writes of `len` bytes.
+pub(crate) unsafe fn zero_nontemporal(dst: *mut u8, len: usize) {
+	let head = dst.align_offset(16).min(len);
+	let end = head + ((len - head) & !15);
+
+	unsafe {
+		dst.write_bytes(0, head);
+	}
+	let mut offset = head;
+	while offset < end {
+		let chunk = (end - offset).min(ZERO_CHUNK_SIZE);
+		let _guard = KernelFpuGuard::new();
+		unsafe {
+			stream_zero(dst.add(offset), chunk);
+		}
+		offset += chunk;
+	}
+	unsafe {
+		dst.add(end).write_bytes(0, len - end);
+	}
+}
+
+/// Zeroes `len` bytes at the 16-byte aligned `dst`, where `len` is a multiple
+/// of 16.
+#[target_feature(enable = "sse2")]
+unsafe fn stream_zero(dst: *mut u8, len: usize) {
+	let zero = _mm_setzero_si128();
+	for offset in (0..len).step_by(16) {
+		unsafe {
+			_mm_stream_si128(dst.add(offset).cast::<__m128i>(), zero);
+		}
+	}
+	// Non-temporal stores are weakly ordered.
+	_mm_sfence();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_zero_nontemporal() {
+		let mut buf = vec![0xffu8; 2 * ZERO_CHUNK_SIZE + 64];
+		// Unaligned head, several chunks and an unaligned tail
+		let len = buf.len() - 3;
+		unsafe {
+			zero_nontemporal(buf[1..].as_mut_ptr(), len - 1);
+		}
+		assert_eq!(buf[0], 0xff);
+		assert!(buf[1..len].iter().all(|&byte| byte == 0));
+		assert!(buf[len..].iter().all(|&byte| byte == 0xff));
+
+		unsafe {
+			zero_nontemporal(buf.as_mut_ptr(), 0);
+		}
+		assert_eq!(buf[0], 0xff);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_kernel_fpu_guard_nesting() {
+		let enabled = interrupts::are_enabled();
+		{
+			let _outer = KernelFpuGuard::new();
+			{
+				let _inner = KernelFpuGuard::new();
+				assert_eq!(CoreLocal::get().kernel_fpu_depth.get(), 2);
+			}
+			assert_eq!(CoreLocal::get().kernel_fpu_depth.get(), 1);
+			assert!(!interrupts::are_enabled());
+			assert!(!Cr0::read().contains(Cr0Flags::TASK_SWITCHED));
+		}
+		assert_eq!(CoreLocal::get().kernel_fpu_depth.get(), 0);
+		assert!(Cr0::read().contains(Cr0Flags::TASK_SWITCHED));
+		assert_eq!(interrupts::are_enabled(), enabled);
+	}
+}
@@ -1,5 +1,8 @@
 use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::arch::asm;
+use core::fmt::Write;
 use core::sync::atomic::{AtomicU64, Ordering};
 
 use ahash::RandomState;
@@ -12,7 +15,7 @@ use x86_64::set_general_handler;
 use x86_64::structures::idt::InterruptDescriptorTable;
 pub use x86_64::structures::idt::InterruptStackFrame as ExceptionStackFrame;
 
-use crate::arch::x86_64::kernel::core_local::{core_scheduler, increment_irq_counter};
+use crate::arch::x86_64::kernel::core_local::{core_id, core_scheduler, increment_irq_counter};
 use crate::arch::x86_64::kernel::{apic, processor};
 use crate::arch::x86_64::mm::paging::{BasePageSize, PageSize, page_fault_handler};
 use crate::arch::x86_64::swapgs;
@@ -161,8 +164,24 @@ pub(crate) fn install() {
 	IRQ_NAMES.lock().insert(7, "FPU");
 }
 
+/// Masks or unmasks the interrupt line `irq` at the IO-APIC.
+///
+/// Returns `false`, if the line cannot be masked.
+pub(crate) fn set_irq_masked(irq: u8, masked: bool) -> bool {
+	apic::ioapic_set_masked(irq, masked)
+}
+
+/// Routes the interrupt line `irq` to the core `core_id` at the IO-APIC.
+///
+/// Returns `false`, if the line cannot be routed.
+pub(crate) fn set_irq_affinity(irq: u8, core_id: CoreId) -> bool {
+	apic::ioapic_set_destination(irq, core_id)
+}
+
 pub(crate) fn install_handlers() {
-	IRQ_HANDLERS.set(get_interrupt_handlers()).unwrap();
+	let handlers = get_interrupt_handlers();
+	crate::drivers::irq_affinity::spread(handlers.keys().copied());
+	IRQ_HANDLERS.set(handlers).unwrap();
 }
 
 fn handle_interrupt(stack_frame: ExceptionStackFrame, index: u8, _error_code: Option<u64>) {
@@ -178,6 +197,16 @@ fn handle_interrupt(stack_frame: ExceptionStackFrame, index: u8, _error_code: Op
 		for handler in map.iter() {
 			handler();
 		}
+
+		// The boot core runs the asynchronous tasks, which the handlers may have woken.
+		if core_id() != 0 {
+			apic::wakeup_core(0);
+		}
+
+		// Arm the timer, which unmasks the line again.
+		if crate::drivers::irq_storm::count(index - 32) {
+			core_scheduler().handle_waiting_tasks();
+		}
 	}
 
 	apic::eoi();
@@ -192,6 +221,10 @@ fn handle_interrupt(stack_frame: ExceptionStackFrame, index: u8, _error_code: Op
 fn abort(stack_frame: ExceptionStackFrame, index: u8, error_code: Option<u64>) {
 	error!("Exception {index}");
 	error!("Error code: {error_code:?}");
+	error!(
+		"Instruction: {}",
+		crate::symbols::Symbolized(stack_frame.instruction_pointer.as_u64().try_into().unwrap())
+	);
 	error!("Stack frame: {stack_frame:#?}");
 	scheduler::abort();
 }
@@ -380,3 +413,31 @@ pub(crate) fn print_statistics() {
 		}
 	}
 }
+
+/// Returns the interrupt counters in the format of `/proc/interrupts`.
+pub(crate) fn proc_interrupts() -> String {
+	let irq_counters = IRQ_COUNTERS.lock();
+
+	let mut s = String::from("    ");
+	for core_id in irq_counters.keys() {
+		write!(s, " {:>10}", format_args!("CPU{core_id}")).unwrap();
+	}
+	s.push('\n');
+
+	for i in 0..=u8::MAX {
+		let counters = irq_counters
+			.values()
+			.map(|irq_statistics| irq_statistics.counters[usize::from(i)].load(Ordering::Relaxed))
+			.collect::<Vec<_>>();
+		if counters.iter().all(|counter| *counter == 0) {
+			continue;
+		}
+
+		write!(s, "{i:>3}:").unwrap();
+		for counter in counters {
+			write!(s, " {counter:>10}").unwrap();
+		}
+		writeln!(s, "   {}", get_irq_name(i).unwrap_or("")).unwrap();
+	}
+	s
+}
@@ -19,13 +19,16 @@ use crate::arch::x86_64::mm::paging::{
 };
 #[cfg(feature = "console")]
 use crate::drivers::console::VirtioConsoleDriver;
+#[cfg(feature = "fuse")]
+use crate::drivers::fs::virtio_fs::VirtioFsDriver;
 #[cfg(feature = "virtio-net")]
 use crate::drivers::net::virtio::VirtioNetDriver;
-use crate::drivers::virtio::transport::mmio as mmio_virtio;
-use crate::drivers::virtio::transport::mmio::VirtioDriver;
+use crate::drivers::virtio::transport::{VirtioDriver, mmio as mmio_virtio};
+#[cfg(feature = "vsock")]
+use crate::drivers::vsock::VirtioVsockDriver;
 use crate::env;
 #[cfg(any(feature = "rtl8139", feature = "virtio-net"))]
-use crate::executor::device::NETWORK_DEVICE;
+use crate::executor::device::NETWORK_DEVICES;
 use crate::init_cell::InitCell;
 use crate::mm::physicalmem::PHYSICAL_FREE_LIST;
 use crate::mm::virtualmem::KERNEL_FREE_LIST;
@@ -41,13 +44,37 @@ static MMIO_DRIVERS: InitCell<Vec<MmioDriver>> = InitCell::new(Vec::new());
 pub(crate) enum MmioDriver {
 	#[cfg(feature = "console")]
 	VirtioConsole(InterruptTicketMutex<VirtioConsoleDriver>),
+	#[cfg(feature = "fuse")]
+	VirtioFs(InterruptTicketMutex<VirtioFsDriver>),
+	#[cfg(feature = "vsock")]
+	VirtioVsock(InterruptTicketMutex<VirtioVsockDriver>),
 }
 
 impl MmioDriver {
 	#[cfg(feature = "console")]
 	fn get_console_driver(&self) -> Option<&InterruptTicketMutex<VirtioConsoleDriver>> {
+		#[allow(unreachable_patterns)]
 		match self {
 			Self::VirtioConsole(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	#[cfg(feature = "fuse")]
+	fn get_filesystem_driver(&self) -> Option<&InterruptTicketMutex<VirtioFsDriver>> {
+		#[allow(unreachable_patterns)]
+		match self {
+			Self::VirtioFs(drv) => Some(drv),
+			_ => None,
+		}
+	}
+
+	#[cfg(feature = "vsock")]
+	fn get_vsock_driver(&self) -> Option<&InterruptTicketMutex<VirtioVsockDriver>> {
+		#[allow(unreachable_patterns)]
+		match self {
+			Self::VirtioVsock(drv) => Some(drv),
+			_ => None,
 		}
 	}
 }
@@ -232,6 +259,22 @@ pub(crate) fn get_console_driver() -> Option<&'static InterruptTicketMutex<Virti
 		.find_map(|drv| drv.get_console_driver())
 }
 
+#[cfg(feature = "fuse")]
+pub(crate) fn get_filesystem_driver() -> Option<&'static InterruptTicketMutex<VirtioFsDriver>> {
+	MMIO_DRIVERS
+		.get()?
+		.iter()
+		.find_map(|drv| drv.get_filesystem_driver())
+}
+
+#[cfg(feature = "vsock")]
+pub(crate) fn get_vsock_driver() -> Option<&'static InterruptTicketMutex<VirtioVsockDriver>> {
+	MMIO_DRIVERS
+		.get()?
+		.iter()
+		.find_map(|drv| drv.get_vsock_driver())
+}
+
 pub(crate) fn init_drivers() {
 	// virtio: MMIO Device Discovery
 	without_interrupts(|| {
@@ -240,10 +283,24 @@ pub(crate) fn init_drivers() {
 			warn!("Found MMIO device, but we guess the interrupt number {irq}!");
 			match mmio_virtio::init_device(mmio, irq) {
 				Ok(VirtioDriver::Network(drv)) => {
-					*NETWORK_DEVICE.lock() = Some(drv);
+					NETWORK_DEVICES.lock().push(drv);
 				}
 				#[cfg(feature = "console")]
-				Ok(VirtioDriver::Console(_)) => unreachable!(),
+				Ok(VirtioDriver::Console(drv)) => {
+					register_driver(MmioDriver::VirtioConsole(InterruptTicketMutex::new(*drv)));
+				}
+				#[cfg(feature = "vsock")]
+				Ok(VirtioDriver::Vsock(drv)) => {
+					register_driver(MmioDriver::VirtioVsock(InterruptTicketMutex::new(*drv)));
+				}
+				#[cfg(feature = "fuse")]
+				Ok(VirtioDriver::FileSystem(drv)) => {
+					register_driver(MmioDriver::VirtioFs(InterruptTicketMutex::new(drv)));
+				}
+				#[cfg(feature = "virtio-9p")]
+				Ok(VirtioDriver::NineP(drv)) => {
+					crate::drivers::fs::virtio_9p::register_driver(drv);
+				}
 				Err(err) => error!("Could not initialize virtio-mmio device: {err}"),
 			}
 		} else {
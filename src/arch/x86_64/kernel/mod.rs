@@ -14,11 +14,21 @@ use crate::env::{self, is_uhyve};
 pub mod acpi;
 pub mod apic;
 pub mod core_local;
+pub mod fpu;
 pub mod gdt;
 pub mod interrupts;
 #[cfg(feature = "kernel-stack")]
 pub mod kernel_stack;
-#[cfg(all(not(feature = "pci"), any(feature = "console", feature = "virtio-net")))]
+#[cfg(all(
+	not(feature = "pci"),
+	any(
+		feature = "console",
+		feature = "virtio-net",
+		feature = "fuse",
+		feature = "virtio-9p",
+		feature = "vsock"
+	)
+))]
 pub mod mmio;
 #[cfg(feature = "pci")]
 pub mod pci;
@@ -33,6 +43,8 @@ pub mod switch;
 #[cfg(feature = "common-os")]
 mod syscall;
 pub(crate) mod systemtime;
+#[cfg(all(target_os = "none", feature = "smp"))]
+mod tsc_sync;
 #[cfg(feature = "vga")]
 pub mod vga;
 
@@ -166,6 +178,10 @@ pub fn boot_next_processor() {
 		if cpu_online == 0 {
 			#[cfg(all(target_os = "none", feature = "smp"))]
 			apic::boot_application_processors();
+		} else {
+			// The boot processor checks the TSC of this core, before it boots the next one.
+			#[cfg(all(target_os = "none", feature = "smp"))]
+			tsc_sync::check_target();
 		}
 
 		if !cfg!(feature = "smp") {
@@ -9,7 +9,7 @@ use core::arch::x86_64::{
 use core::fmt;
 use core::hint::spin_loop;
 use core::num::{NonZero, NonZeroU32};
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 use hermit_entry::boot_info::PlatformInfo;
 use hermit_sync::Lazy;
@@ -68,6 +68,9 @@ struct Features {
 	run_on_hypervisor: bool,
 	supports_fsgs: bool,
 	supports_rdtscp: bool,
+	/// The TSC runs at a constant rate in all ACPI P-, C- and T-states.
+	has_invariant_tsc: bool,
+	supports_tsc_adjust: bool,
 	cpu_speedstep: CpuSpeedStep,
 	has_xsaveopt: bool,
 	has_xsavec: bool,
@@ -109,6 +112,10 @@ static FEATURES: Lazy<Features> = Lazy::new(|| {
 		run_on_hypervisor: feature_info.has_hypervisor(),
 		supports_fsgs: extended_feature_info.has_fsgsbase(),
 		supports_rdtscp: extend_processor_identifiers.has_rdtscp(),
+		has_invariant_tsc: cpuid
+			.get_advanced_power_mgmt_info()
+			.is_some_and(|info| info.has_invariant_tsc()),
+		supports_tsc_adjust: extended_feature_info.has_tsc_adjust_msr(),
 		cpu_speedstep: {
 			let mut cpu_speedstep = CpuSpeedStep::new();
 			cpu_speedstep.detect_features(&cpuid);
@@ -122,6 +129,92 @@ static FEATURES: Lazy<Features> = Lazy::new(|| {
 	}
 });
 
+bitflags! {
+	/// CPU features, which are available to applications
+	///
+	/// The bits are part of the ABI of `sys_cpu_features` and must not change.
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub struct CpuFeatureFlags: u64 {
+		const SSE3 = 1 << 0;
+		const SSSE3 = 1 << 1;
+		const SSE4_1 = 1 << 2;
+		const SSE4_2 = 1 << 3;
+		const POPCNT = 1 << 4;
+		const AES = 1 << 5;
+		const PCLMULQDQ = 1 << 6;
+		const RDRAND = 1 << 7;
+		const RDSEED = 1 << 8;
+		const SHA = 1 << 9;
+		const BMI1 = 1 << 10;
+		const BMI2 = 1 << 11;
+		const ADX = 1 << 12;
+		const AVX = 1 << 13;
+		const AVX2 = 1 << 14;
+		const FMA = 1 << 15;
+		const F16C = 1 << 16;
+		const AVX512F = 1 << 17;
+		const AVX512CD = 1 << 18;
+		const AVX512DQ = 1 << 19;
+		const AVX512BW = 1 << 20;
+		const AVX512VL = 1 << 21;
+	}
+}
+
+static CPU_FEATURES: Lazy<CpuFeatureFlags> = Lazy::new(|| {
+	let cpuid = CpuId::new();
+	let feature_info = cpuid.get_feature_info().unwrap();
+	let extended_feature_info = cpuid.get_extended_feature_info().unwrap();
+
+	let mut flags = CpuFeatureFlags::empty();
+	flags.set(CpuFeatureFlags::SSE3, feature_info.has_sse3());
+	flags.set(CpuFeatureFlags::SSSE3, feature_info.has_ssse3());
+	flags.set(CpuFeatureFlags::SSE4_1, feature_info.has_sse41());
+	flags.set(CpuFeatureFlags::SSE4_2, feature_info.has_sse42());
+	flags.set(CpuFeatureFlags::POPCNT, feature_info.has_popcnt());
+	flags.set(CpuFeatureFlags::AES, feature_info.has_aesni());
+	flags.set(CpuFeatureFlags::PCLMULQDQ, feature_info.has_pclmulqdq());
+	flags.set(CpuFeatureFlags::RDRAND, feature_info.has_rdrand());
+	flags.set(CpuFeatureFlags::RDSEED, extended_feature_info.has_rdseed());
+	flags.set(CpuFeatureFlags::SHA, extended_feature_info.has_sha());
+	flags.set(CpuFeatureFlags::BMI1, extended_feature_info.has_bmi1());
+	flags.set(CpuFeatureFlags::BMI2, extended_feature_info.has_bmi2());
+	flags.set(CpuFeatureFlags::ADX, extended_feature_info.has_adx());
+
+	// The register state of AVX and AVX-512 has to be enabled in XCR0 (see `configure`).
+	if supports_xsave() && supports_avx() {
+		flags.insert(CpuFeatureFlags::AVX);
+		flags.set(CpuFeatureFlags::AVX2, extended_feature_info.has_avx2());
+		flags.set(CpuFeatureFlags::FMA, feature_info.has_fma());
+		flags.set(CpuFeatureFlags::F16C, feature_info.has_f16c());
+
+		if xcr0_supports_avx512_opmask()
+			&& xcr0_supports_avx512_zmm_hi16()
+			&& xcr0_supports_avx512_zmm_hi256()
+			&& extended_feature_info.has_avx512f()
+		{
+			flags.insert(CpuFeatureFlags::AVX512F);
+			flags.set(
+				CpuFeatureFlags::AVX512CD,
+				extended_feature_info.has_avx512cd(),
+			);
+			flags.set(
+				CpuFeatureFlags::AVX512DQ,
+				extended_feature_info.has_avx512dq(),
+			);
+			flags.set(
+				CpuFeatureFlags::AVX512BW,
+				extended_feature_info.has_avx512bw(),
+			);
+			flags.set(
+				CpuFeatureFlags::AVX512VL,
+				extended_feature_info.has_avx512vl(),
+			);
+		}
+	}
+
+	flags
+});
+
 static CPU_FREQUENCY: Lazy<CpuFrequency> = Lazy::new(|| {
 	let mut cpu_frequency = CpuFrequency::new();
 	unsafe {
@@ -232,6 +325,7 @@ enum CpuFrequencySources {
 	CpuId,
 	CpuIdTscInfo,
 	HypervisorTscInfo,
+	Kvmclock,
 	Visionary,
 	Fdt,
 }
@@ -246,6 +340,7 @@ impl fmt::Display for CpuFrequencySources {
 			CpuFrequencySources::CpuId => write!(f, "CpuId"),
 			CpuFrequencySources::CpuIdTscInfo => write!(f, "CpuId Tsc Info"),
 			CpuFrequencySources::HypervisorTscInfo => write!(f, "Tsc Info from Hypervisor"),
+			CpuFrequencySources::Kvmclock => write!(f, "kvmclock"),
 			CpuFrequencySources::Visionary => write!(f, "Visionary"),
 			CpuFrequencySources::Invalid => {
 				panic!("Attempted to print an invalid CPU Frequency Source")
@@ -255,28 +350,46 @@ impl fmt::Display for CpuFrequencySources {
 	}
 }
 
+/// Layout of `pvclock_vcpu_time_info`, which KVM updates for kvmclock
+#[repr(C, align(32))]
+#[derive(Default)]
+struct PvclockVcpuTimeInfo {
+	version: u32,
+	pad0: u32,
+	tsc_timestamp: u64,
+	system_time: u64,
+	tsc_to_system_mul: u32,
+	tsc_shift: i8,
+	flags: u8,
+	pad: [u8; 2],
+}
+
 struct CpuFrequency {
-	mhz: u16,
+	khz: u32,
+	/// Microseconds per TSC cycle as 0.64 fixed-point number
+	ticks_mult: u64,
 	source: CpuFrequencySources,
 }
 
 impl CpuFrequency {
 	const fn new() -> Self {
 		CpuFrequency {
-			mhz: 0,
+			khz: 0,
+			ticks_mult: 0,
 			source: CpuFrequencySources::Invalid,
 		}
 	}
 
 	fn set_detected_cpu_frequency(
 		&mut self,
-		mhz: u16,
+		khz: u32,
 		source: CpuFrequencySources,
 	) -> Result<(), ()> {
 		//The clock frequency must never be set to zero, otherwise a division by zero will
-		//occur during runtime
-		if mhz > 0 {
-			self.mhz = mhz;
+		//occur during runtime. Frequencies below 1 MHz do not fit the fixed-point factor.
+		if khz > 1000 {
+			self.khz = khz;
+			self.ticks_mult = ((1000u128 << 64) / u128::from(khz)).try_into().unwrap();
 			self.source = source;
 			Ok(())
 		} else {
@@ -286,7 +399,7 @@ impl CpuFrequency {
 
 	unsafe fn detect_from_cmdline(&mut self) -> Result<(), ()> {
 		let mhz = env::freq().ok_or(())?;
-		self.set_detected_cpu_frequency(mhz, CpuFrequencySources::CommandLine)
+		self.set_detected_cpu_frequency(u32::from(mhz) * 1000, CpuFrequencySources::CommandLine)
 	}
 
 	unsafe fn detect_from_cpuid(&mut self, cpuid: &CpuId<CpuIdReaderNative>) -> Result<(), ()> {
@@ -295,7 +408,7 @@ impl CpuFrequency {
 		match processor_frequency_info {
 			Some(freq_info) => {
 				let mhz = freq_info.processor_base_frequency();
-				self.set_detected_cpu_frequency(mhz, CpuFrequencySources::CpuId)
+				self.set_detected_cpu_frequency(u32::from(mhz) * 1000, CpuFrequencySources::CpuId)
 			}
 			None => Err(()),
 		}
@@ -307,20 +420,86 @@ impl CpuFrequency {
 	) -> Result<(), ()> {
 		let tsc_info = cpuid.get_tsc_info().ok_or(())?;
 		let freq = tsc_info.tsc_frequency().ok_or(())?;
-		let mhz = (freq / 1_000_000u64) as u16;
-		self.set_detected_cpu_frequency(mhz, CpuFrequencySources::CpuIdTscInfo)
+		let khz = u32::try_from(freq / 1000).map_err(|_| ())?;
+		self.set_detected_cpu_frequency(khz, CpuFrequencySources::CpuIdTscInfo)
 	}
 
 	unsafe fn detect_from_cpuid_hypervisor_info(
 		&mut self,
 		cpuid: &CpuId<CpuIdReaderNative>,
 	) -> Result<(), ()> {
-		const KHZ_TO_HZ: u64 = 1000;
-		const MHZ_TO_HZ: u64 = 1_000_000;
 		let hypervisor_info = cpuid.get_hypervisor_info().ok_or(())?;
-		let freq = u64::from(hypervisor_info.tsc_frequency().ok_or(())?) * KHZ_TO_HZ;
-		let mhz: u16 = (freq / MHZ_TO_HZ).try_into().unwrap();
-		self.set_detected_cpu_frequency(mhz, CpuFrequencySources::HypervisorTscInfo)
+		let khz = hypervisor_info.tsc_frequency().ok_or(())?;
+		self.set_detected_cpu_frequency(khz, CpuFrequencySources::HypervisorTscInfo)
+	}
+
+	/// Derives the TSC frequency from the scale factor, which KVM passes for kvmclock.
+	#[cfg(target_os = "none")]
+	unsafe fn detect_from_kvmclock(&mut self, cpuid: &CpuId<CpuIdReaderNative>) -> Result<(), ()> {
+		use core::arch::x86_64::__cpuid;
+		use core::ptr;
+
+		use crate::arch::mm::paging;
+
+		const KVM_CPUID_FEATURES: u32 = 0x4000_0001;
+		const KVM_FEATURE_CLOCKSOURCE2: u32 = 1 << 3;
+		const MSR_KVM_SYSTEM_TIME_NEW: u32 = 0x4b56_4d01;
+
+		let hypervisor_info = cpuid.get_hypervisor_info().ok_or(())?;
+		if !matches!(hypervisor_info.identify(), Hypervisor::KVM) {
+			return Err(());
+		}
+		let features = unsafe { __cpuid(KVM_CPUID_FEATURES) };
+		if features.eax & KVM_FEATURE_CLOCKSOURCE2 == 0 {
+			return Err(());
+		}
+
+		// KVM updates the structure, as soon as the MSR has been written.
+		let mut info = PvclockVcpuTimeInfo::default();
+		let phys_addr =
+			paging::virtual_to_physical(memory_addresses::VirtAddr::from_ptr(&raw mut info))
+				.ok_or(())?;
+		let mut msr = Msr::new(MSR_KVM_SYSTEM_TIME_NEW);
+		unsafe {
+			msr.write(phys_addr.as_u64() | 1);
+		}
+		// An odd version indicates, that an update is in progress.
+		let scale = (0..1_000_000).find_map(|_| {
+			let version = unsafe { ptr::read_volatile(&raw const info.version) };
+			let mul = unsafe { ptr::read_volatile(&raw const info.tsc_to_system_mul) };
+			let shift = unsafe { ptr::read_volatile(&raw const info.tsc_shift) };
+			let stable = version != 0
+				&& version.is_multiple_of(2)
+				&& version == unsafe { ptr::read_volatile(&raw const info.version) };
+			if !stable {
+				spin_loop();
+			}
+			stable.then_some((mul, shift))
+		});
+		unsafe {
+			msr.write(0);
+		}
+
+		let (mul, shift) = scale.ok_or(())?;
+		if mul == 0 {
+			return Err(());
+		}
+		// system time in ns = ((tsc << shift) * mul) >> 32
+		let khz = (1_000_000u64 << 32) / u64::from(mul);
+		let khz = if shift < 0 {
+			khz << -shift
+		} else {
+			khz >> shift
+		};
+		self.set_detected_cpu_frequency(
+			u32::try_from(khz).map_err(|_| ())?,
+			CpuFrequencySources::Kvmclock,
+		)
+	}
+
+	#[cfg(not(target_os = "none"))]
+	unsafe fn detect_from_kvmclock(&mut self, _cpuid: &CpuId<CpuIdReaderNative>) -> Result<(), ()> {
+		Err(())
 	}
 
 	unsafe fn detect_from_cpuid_brand_string(
@@ -344,8 +523,9 @@ impl CpuFrequency {
 					hundred_char.to_digit(10),
 					ten_char.to_digit(10),
 				) {
-					let mhz = (thousand * 1000 + hundred * 100 + ten * 10) as u16;
-					return self.set_detected_cpu_frequency(mhz, CpuFrequencySources::CpuIdTscInfo);
+					let khz = (thousand * 1000 + hundred * 100 + ten * 10) * 1000;
+					return self
+						.set_detected_cpu_frequency(khz, CpuFrequencySources::CpuIdBrandString);
 				}
 			}
 		}
@@ -354,29 +534,26 @@ impl CpuFrequency {
 	}
 
 	fn detect_from_fdt(&mut self) -> Result<(), ()> {
-		fn mhz_from_fdt() -> Option<NonZero<u16>> {
+		fn khz_from_fdt() -> Option<NonZero<u32>> {
 			let khz = env::fdt()?
 				.find_node("/hermit,tsc")?
 				.property("khz")?
 				.as_usize()?;
-			let khz = u32::try_from(khz).ok()?;
-			let mhz = u16::try_from(khz / 1000).ok()?;
-			NonZero::new(mhz)
+			NonZero::new(u32::try_from(khz).ok()?)
 		}
 
-		let mhz = mhz_from_fdt().ok_or(())?;
-		self.set_detected_cpu_frequency(mhz.get(), CpuFrequencySources::Fdt)?;
+		let khz = khz_from_fdt().ok_or(())?;
+		self.set_detected_cpu_frequency(khz.get(), CpuFrequencySources::Fdt)?;
 
 		Ok(())
 	}
 
 	fn detect_from_hypervisor(&mut self) -> Result<(), ()> {
-		fn detect_from_uhyve() -> Result<u16, ()> {
+		fn detect_from_uhyve() -> Result<u32, ()> {
 			match env::boot_info().platform_info {
-				PlatformInfo::Uhyve { cpu_freq, .. } => Ok(u16::try_from(
-					cpu_freq.map(NonZeroU32::get).unwrap_or_default() / 1000,
-				)
-				.unwrap()),
+				PlatformInfo::Uhyve { cpu_freq, .. } => {
+					Ok(cpu_freq.map(NonZeroU32::get).unwrap_or_default())
+				}
 				_ => Err(()),
 			}
 		}
@@ -473,39 +650,56 @@ impl CpuFrequency {
 
 		// Calculate the CPU frequency out of this measurement.
 		let cycle_count = end - start;
-		let mhz = (measurement_frequency * cycle_count / (1_000_000 * tick_count)) as u16;
-		self.set_detected_cpu_frequency(mhz, CpuFrequencySources::Measurement)
+		let khz = measurement_frequency * cycle_count / (1000 * tick_count);
+		self.set_detected_cpu_frequency(
+			u32::try_from(khz).map_err(|_| ())?,
+			CpuFrequencySources::Measurement,
+		)
 	}
 
 	unsafe fn detect(&mut self) {
 		let cpuid = CpuId::new();
+		if !FEATURES.has_invariant_tsc {
+			warn!("The TSC is not invariant, timestamps may drift with the processor frequency");
+		}
+
+		// Sources, which report the TSC frequency itself, are preferred over
+		// the nominal processor frequency, which may differ from the TSC
+		// frequency by a few percent.
 		unsafe {
-			self.detect_from_fdt()
-				.or_else(|_e| self.detect_from_cpuid(&cpuid))
+			self.detect_from_cmdline()
 				.or_else(|_e| self.detect_from_cpuid_tsc_info(&cpuid))
 				.or_else(|_e| self.detect_from_cpuid_hypervisor_info(&cpuid))
+				.or_else(|_e| self.detect_from_kvmclock(&cpuid))
+				.or_else(|_e| self.detect_from_fdt())
 				.or_else(|_e| self.detect_from_hypervisor())
-				.or_else(|_e| self.detect_from_cmdline())
+				.or_else(|_e| self.detect_from_cpuid(&cpuid))
 				.or_else(|_e| self.detect_from_cpuid_brand_string(&cpuid))
 				.or_else(|_e| self.measure_frequency())
 				.or_else(|_e| {
 					warn!(
 						"Could not determine the processor frequency! Guess a frequency of 2Ghz!"
 					);
-					self.set_detected_cpu_frequency(2000, CpuFrequencySources::Visionary)
+					self.set_detected_cpu_frequency(2_000_000, CpuFrequencySources::Visionary)
 				})
 				.unwrap();
 		}
 	}
 
 	fn get(&self) -> u16 {
-		self.mhz
+		(self.khz / 1000).try_into().unwrap()
 	}
 }
 
 impl fmt::Display for CpuFrequency {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "{} MHz (from {})", self.mhz, self.source)
+		write!(
+			f,
+			"{}.{:03} MHz (from {})",
+			self.khz / 1000,
+			self.khz % 1000,
+			self.source
+		)
 	}
 }
 
@@ -1016,6 +1210,16 @@ pub fn supports_tsc_deadline() -> bool {
 	FEATURES.supports_tsc_deadline
 }
 
+#[inline]
+pub fn has_invariant_tsc() -> bool {
+	FEATURES.has_invariant_tsc
+}
+
+#[inline]
+pub fn supports_tsc_adjust() -> bool {
+	FEATURES.supports_tsc_adjust
+}
+
 #[inline]
 pub fn supports_x2apic() -> bool {
 	FEATURES.supports_x2apic
@@ -1066,6 +1270,22 @@ pub fn xcr0_supports_avx512_zmm_hi256() -> bool {
 	FEATURES.xcr0_supports_avx512_zmm_hi256
 }
 
+/// Returns the CPU features, which are available to applications.
+pub fn cpu_features() -> CpuFeatureFlags {
+	*CPU_FEATURES
+}
+
+/// Returns the width of the widest vector registers, which are available to applications, in bits.
+pub fn vector_width() -> u32 {
+	if CPU_FEATURES.contains(CpuFeatureFlags::AVX512F) {
+		512
+	} else if CPU_FEATURES.contains(CpuFeatureFlags::AVX) {
+		256
+	} else {
+		128
+	}
+}
+
 /// The halt function stops the processor until the next interrupt arrives
 pub fn halt() {
 	instructions::hlt();
@@ -1088,16 +1308,39 @@ fn triple_fault() -> ! {
 	unreachable!()
 }
 
-fn qemu_exit(success: bool) {
-	let code = if success { 3 >> 1 } else { 0 };
+/// Exits QEMU through the ISA debug exit device, which exits with `(value << 1) | 1`.
+///
+/// Success is reported as 3 and the error code 1 as 1. Other error codes are
+/// passed through, so that they can be distinguished.
+fn qemu_exit(error_code: i32) {
+	let value = match error_code {
+		0 => 3 >> 1,
+		1 => 0,
+		code => u32::try_from(code).unwrap_or(0),
+	};
+	unsafe {
+		Port::<u32>::new(0xf4).write(value);
+	}
+}
+
+/// Resets the system.
+///
+/// Tries the reset control register of the chipset and the keyboard
+/// controller. If both are not available, a triple fault resets the CPU.
+pub fn reboot() -> ! {
+	info!("Rebooting system");
+
 	unsafe {
-		Port::<u32>::new(0xf4).write(code);
+		Port::<u8>::new(0xcf9).write(0x06);
+		Port::<u8>::new(0x64).write(0xfe);
 	}
+
+	triple_fault()
 }
 
 /// Shutdown the system
 pub fn shutdown(error_code: i32) -> ! {
-	qemu_exit(error_code == 0);
+	qemu_exit(error_code);
 
 	#[cfg(feature = "acpi")]
 	{
@@ -1109,8 +1352,36 @@ pub fn shutdown(error_code: i32) -> ! {
 
 pub fn get_timer_ticks() -> u64 {
 	// We simulate a timer with a 1 microsecond resolution by taking the CPU timestamp
-	// and dividing it by the CPU frequency in MHz.
-	get_timestamp() / u64::from(get_frequency())
+	// and dividing it by the CPU frequency. The division is replaced by a
+	// fixed-point multiplication, which keeps the precision of the frequency in kHz.
+	let ticks = ((u128::from(get_timestamp()) * u128::from(CPU_FREQUENCY.ticks_mult)) >> 64) as u64;
+
+	if !TSC_UNSYNCHRONIZED.load(Ordering::Relaxed) {
+		return ticks;
+	}
+	// A task, which migrates to another core, must not observe time going backwards.
+	LAST_TIMER_TICKS
+		.fetch_max(ticks, Ordering::Relaxed)
+		.max(ticks)
+}
+
+/// The TSCs of the cores are not synchronized.
+static TSC_UNSYNCHRONIZED: AtomicBool = AtomicBool::new(false);
+
+/// Largest value returned by [`get_timer_ticks`] on any core
+static LAST_TIMER_TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Clamps the timer ticks of all cores to the largest value observed so far.
+#[cfg_attr(not(all(target_os = "none", feature = "smp")), allow(dead_code))]
+pub(crate) fn mark_tsc_unsynchronized() {
+	if !TSC_UNSYNCHRONIZED.swap(true, Ordering::Relaxed) {
+		warn!("The TSCs are not synchronized, the monotonic clock is clamped across cores");
+	}
+}
+
+/// Converts timer ticks in microseconds to TSC cycles.
+pub fn ticks_to_timestamp(ticks: u64) -> u64 {
+	(u128::from(ticks) * u128::from(CPU_FREQUENCY.khz) / 1000) as u64
 }
 
 /// Returns the timer frequency in MHz
@@ -1196,7 +1467,7 @@ unsafe fn get_timestamp_rdtscp() -> u64 {
 /// Delay execution by the given number of microseconds using busy-waiting.
 #[inline]
 pub fn udelay(usecs: u64) {
-	let end = get_timestamp() + u64::from(get_frequency()) * usecs;
+	let end = get_timestamp() + ticks_to_timestamp(usecs);
 	while get_timestamp() < end {
 		spin_loop();
 	}
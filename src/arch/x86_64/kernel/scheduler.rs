@@ -149,10 +149,9 @@ impl TaskStacks {
 
 		// clear user stack
 		unsafe {
-			ptr::write_bytes(
+			super::fpu::zero_nontemporal(
 				(virt_addr + IST_SIZE + DEFAULT_STACK_SIZE + 3 * BasePageSize::SIZE)
 					.as_mut_ptr::<u8>(),
-				0,
 				user_stack_size,
 			);
 		}
@@ -346,7 +345,7 @@ extern "C" fn task_start(_f: extern "C" fn(usize), _arg: usize, _user_stack: u64
 
 extern "C" fn task_entry(func: extern "C" fn(usize), arg: usize) -> ! {
 	// Call the actual entry point of the task.
-	func(arg);
+	crate::scheduler::call_task_entry(func, arg);
 
 	// Exit task
 	debug!("Exit thread with error code 0!");
@@ -396,6 +395,8 @@ impl TaskFrame for Task {
 
 extern "x86-interrupt" fn timer_handler(_stack_frame: interrupts::ExceptionStackFrame) {
 	increment_irq_counter(apic::TIMER_INTERRUPT_NUMBER);
+	#[cfg(all(target_os = "none", feature = "smp"))]
+	super::tsc_sync::check_periodic();
 	core_scheduler().handle_waiting_tasks();
 	apic::eoi();
 	core_scheduler().reschedule();
@@ -92,10 +92,16 @@ pub(crate) fn get_serial_handler() -> (InterruptLine, fn()) {
 	fn serial_handler() {
 		let mut guard = UART_DEVICE.lock();
 		if let Ok(c) = guard.uart.try_receive() {
-			guard.buffer.push_back(c);
+			#[cfg(feature = "sysrq")]
+			let c = crate::sysrq::receive(c);
+			#[cfg(not(feature = "sysrq"))]
+			let c = Some(c);
+			guard.buffer.extend(c);
 		}
 
 		drop(guard);
+		#[cfg(feature = "sysrq")]
+		crate::sysrq::run_pending();
 		crate::console::CONSOLE_WAKER.lock().wake();
 	}
 
@@ -187,8 +187,7 @@ pub(crate) unsafe extern "C" fn switch_to_task(_old_stack: *mut usize, _new_stac
 	);
 }
 
-/// Performs a context switch to an idle task or a task, which already is owner
-/// of the FPU.
+/// Performs a context switch to a task, which already is owner of the FPU.
 #[unsafe(naked)]
 pub(crate) unsafe extern "C" fn switch_to_fpu_owner(_old_stack: *mut usize, _new_stack: usize) {
 	// `old_stack` is in `rdi` register
@@ -200,7 +199,8 @@ pub(crate) unsafe extern "C" fn switch_to_fpu_owner(_old_stack: *mut usize, _new
 		"mov [rdi], rsp",
 		// Set `rsp` to `new_stack`
 		"mov rsp, rsi",
-		// Don't set task switched flag, as we switch to fpu owner.
+		// Clear task switched flag, as the FPU registers already belong to the new task.
+		"clts",
 		// Set stack pointer in TSS
 		"call {set_current_kernel_stack}",
 		restore_context!(),
@@ -0,0 +1,197 @@
+//! Synchronization of the TSCs between cores.
+//!
+//! While an application processor boots, it exchanges timestamps with the boot
+//! processor to measure the offset between their TSCs. If the processor
+//! supports `IA32_TSC_ADJUST`, the application processor compensates the
+//! offset.
+//!
+//! Afterwards, both cores read their TSCs alternately and record the last
+//! value under a lock. If a core reads a value, which is smaller than the
+//! value recorded by the other core, the TSCs are still offset against each
+//! other. In this case, and if a later check on a timer interrupt observes
+//! time going backwards, the timer ticks are clamped to the largest value
+//! returned on any core (see [`processor::mark_tsc_unsynchronized`]).
+
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicI64, AtomicU8, AtomicU32, AtomicU64, Ordering};
+
+use hermit_sync::InterruptSpinMutex;
+use x86_64::registers::model_specific::Msr;
+
+use crate::arch::x86_64::kernel::processor;
+
+const IA32_TSC_ADJUST: u32 = 0x3b;
+
+/// Number of timestamps read by each core
+const ITERATIONS: usize = 10_000;
+
+/// Number of timestamp exchanges to measure the offset
+const SAMPLES: u32 = 1_000;
+
+const IDLE: u8 = 0;
+/// The application processor waits for the boot processor.
+const READY: u8 = 1;
+/// The application processor measures the offset of its TSC.
+const MEASURING: u8 = 2;
+/// The application processor has compensated the offset.
+const MEASURED: u8 = 3;
+/// Both cores read their TSCs.
+const RUNNING: u8 = 4;
+/// The application processor has finished.
+const DONE: u8 = 5;
+
+static STATE: AtomicU8 = AtomicU8::new(IDLE);
+
+/// Number of the last timestamp requested by the application processor
+static REQUEST: AtomicU32 = AtomicU32::new(0);
+
+/// Number of the last timestamp provided by the boot processor
+static REPLY: AtomicU32 = AtomicU32::new(0);
+
+/// Timestamp of the boot processor for the last request
+static REPLY_TIMESTAMP: AtomicU64 = AtomicU64::new(0);
+
+/// Offset of the TSC of the boot processor to the TSC of the application
+/// processor in TSC cycles, before it has been compensated
+static OFFSET: AtomicI64 = AtomicI64::new(0);
+
+/// Last timestamp read by any of the two cores
+static LAST: InterruptSpinMutex<u64> = InterruptSpinMutex::new(0);
+
+/// Largest backwards step between the two cores in TSC cycles
+static MAX_WARP: AtomicU64 = AtomicU64::new(0);
+
+/// Largest timestamp observed by the periodic check on any core
+static LAST_CHECKED: AtomicU64 = AtomicU64::new(0);
+
+fn check_warps() {
+	for _ in 0..ITERATIONS {
+		let mut last = LAST.lock();
+		let now = processor::get_timestamp();
+		if now < *last {
+			MAX_WARP.fetch_max(*last - now, Ordering::Relaxed);
+		}
+		*last = now;
+	}
+}
+
+fn wait_for(state: u8) {
+	while STATE.load(Ordering::Acquire) != state {
+		spin_loop();
+	}
+}
+
+/// Answers the timestamp requests of the application processor.
+fn serve_timestamps() {
+	for sample in 1..=SAMPLES {
+		while REQUEST.load(Ordering::Acquire) != sample {
+			spin_loop();
+		}
+		REPLY_TIMESTAMP.store(processor::get_timestamp(), Ordering::Relaxed);
+		REPLY.store(sample, Ordering::Release);
+	}
+}
+
+/// Returns the offset of the TSC of the boot processor to the local TSC and
+/// the uncertainty of the measurement in TSC cycles.
+///
+/// The timestamp of the boot processor is assumed to be taken in the middle
+/// of the round trip. The sample with the shortest round trip is the most
+/// accurate one.
+fn measure_offset() -> (i64, u64) {
+	let mut best = (0, u64::MAX);
+	for sample in 1..=SAMPLES {
+		let start = processor::get_timestamp();
+		REQUEST.store(sample, Ordering::Release);
+		while REPLY.load(Ordering::Acquire) != sample {
+			spin_loop();
+		}
+		let end = processor::get_timestamp();
+
+		let round_trip = end - start;
+		if round_trip < best.1 {
+			let remote = REPLY_TIMESTAMP.load(Ordering::Relaxed);
+			let local = start + round_trip / 2;
+			best = (remote.wrapping_sub(local) as i64, round_trip);
+		}
+	}
+	(best.0, best.1 / 2)
+}
+
+/// Runs the check on the boot processor, while `core_id` boots.
+pub fn check_source(core_id: u32) {
+	wait_for(READY);
+	REQUEST.store(0, Ordering::Relaxed);
+	REPLY.store(0, Ordering::Relaxed);
+	STATE.store(MEASURING, Ordering::Release);
+	serve_timestamps();
+	wait_for(MEASURED);
+
+	*LAST.lock() = 0;
+	MAX_WARP.store(0, Ordering::Relaxed);
+	STATE.store(RUNNING, Ordering::Release);
+
+	check_warps();
+	wait_for(DONE);
+
+	let offset = OFFSET.load(Ordering::Relaxed);
+	if offset != 0 {
+		info!("Compensated an offset of {offset} cycles of the TSC of core {core_id}");
+	}
+
+	let warp = MAX_WARP.load(Ordering::Relaxed);
+	if warp > 0 {
+		warn!(
+			"The TSC of core {core_id} is offset by at least {warp} cycles ({} µs) from the boot processor",
+			warp / u64::from(processor::get_frequency())
+		);
+		processor::mark_tsc_unsynchronized();
+	} else {
+		debug!("The TSC of core {core_id} is synchronized with the boot processor");
+	}
+
+	if !processor::has_invariant_tsc() {
+		processor::mark_tsc_unsynchronized();
+	}
+	STATE.store(IDLE, Ordering::Release);
+}
+
+/// Runs the check on the booting application processor.
+pub fn check_target() {
+	STATE.store(READY, Ordering::Release);
+	wait_for(MEASURING);
+
+	let (offset, uncertainty) = measure_offset();
+	// Offsets within the uncertainty of the measurement cannot be compensated.
+	let offset = if offset.unsigned_abs() > uncertainty && processor::supports_tsc_adjust() {
+		let mut tsc_adjust = Msr::new(IA32_TSC_ADJUST);
+		unsafe {
+			let value = tsc_adjust.read();
+			tsc_adjust.write(value.wrapping_add_signed(offset));
+		}
+		offset
+	} else {
+		0
+	};
+	OFFSET.store(offset, Ordering::Relaxed);
+	STATE.store(MEASURED, Ordering::Release);
+
+	wait_for(RUNNING);
+	check_warps();
+	STATE.store(DONE, Ordering::Release);
+}
+
+/// Checks on a timer interrupt, that the TSC has not fallen behind a
+/// timestamp read on another core.
+pub fn check_periodic() {
+	// The timestamp of the other cores has to be loaded before the local one
+	// is read. Otherwise, a later timestamp of another core could be mistaken
+	// for a warp.
+	let last = LAST_CHECKED.load(Ordering::Acquire);
+	let now = processor::get_timestamp();
+	if now < last {
+		debug!("The TSC fell {} cycles behind another core", last - now);
+		processor::mark_tsc_unsynchronized();
+	}
+	LAST_CHECKED.fetch_max(now, Ordering::AcqRel);
+}
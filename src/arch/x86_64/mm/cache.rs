@@ -0,0 +1,52 @@
+//! Cache maintenance for DMA.
+//!
+//! DMA is cache-coherent on x86-64, so no maintenance is required.
+
+use core::arch::asm;
+use core::sync::atomic::{Ordering, compiler_fence};
+
+use memory_addresses::VirtAddr;
+
+use crate::mm::dma::DmaBarrier;
+
+/// Returns whether devices observe the contents of the CPU caches.
+#[inline]
+pub(crate) fn dma_coherent() -> bool {
+	true
+}
+
+/// Writes dirty cache lines of the range back to memory.
+#[inline]
+pub(crate) fn clean_dcache_range(_start: VirtAddr, _len: usize) {}
+
+/// Discards the cache lines of the range.
+#[inline]
+pub(crate) fn invalidate_dcache_range(_start: VirtAddr, _len: usize) {}
+
+/// Writes dirty cache lines of the range back to memory and discards them.
+#[inline]
+pub(crate) fn flush_dcache_range(_start: VirtAddr, _len: usize) {}
+
+/// Prepares `len` bytes of memory at `start`, which have been allocated for devices.
+#[inline]
+pub(crate) fn prepare_device_memory(_start: VirtAddr, _len: usize) {}
+
+/// Restores the mapping of memory, which is no longer used by devices.
+#[inline]
+pub(crate) fn release_device_memory(_start: VirtAddr, _len: usize) {}
+
+/// Orders the accesses of the CPU to memory and registers of devices.
+///
+/// x86-64 only reorders writes after later reads, so that the other
+/// barriers only prevent the compiler from reordering accesses.
+#[inline]
+pub(crate) fn dma_barrier(barrier: DmaBarrier) {
+	match barrier {
+		DmaBarrier::Read | DmaBarrier::Write | DmaBarrier::Doorbell => {
+			compiler_fence(Ordering::SeqCst);
+		}
+		DmaBarrier::Full => unsafe {
+			asm!("mfence", options(nostack, preserves_flags));
+		},
+	}
+}
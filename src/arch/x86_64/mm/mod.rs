@@ -1,3 +1,4 @@
+pub(crate) mod cache;
 pub(crate) mod paging;
 
 use memory_addresses::arch::x86_64::{PhysAddr, VirtAddr};
@@ -19,6 +19,7 @@ use crate::arch::x86_64::kernel::processor;
 use crate::arch::x86_64::mm::{PhysAddr, VirtAddr};
 use crate::mm::physicalmem;
 use crate::mm::physicalmem::PHYSICAL_FREE_LIST;
+use crate::symbols::Symbolized;
 use crate::{env, scheduler};
 
 pub trait PageTableEntryFlagsExt {
@@ -282,6 +283,10 @@ pub(crate) extern "x86-interrupt" fn page_fault_handler(
 	error!("Page fault (#PF)!");
 	error!("page_fault_linear_address = {:p}", Cr2::read().unwrap());
 	error!("error_code = {error_code:?}");
+	error!(
+		"instruction = {}",
+		Symbolized(stack_frame.instruction_pointer.as_u64().try_into().unwrap())
+	);
 	error!("fs = {:#X}", processor::readfs());
 	error!("gs = {:#X}", processor::readgs());
 	error!("stack_frame = {stack_frame:#?}");
@@ -301,6 +306,10 @@ pub(crate) extern "x86-interrupt" fn page_fault_handler(
 	error!("Page fault (#PF)!");
 	error!("page_fault_linear_address = {:p}", Cr2::read().unwrap());
 	error!("error_code = {error_code:?}");
+	error!(
+		"instruction = {}",
+		Symbolized(stack_frame.instruction_pointer.as_u64().try_into().unwrap())
+	);
 	error!("fs = {:#X}", processor::readfs());
 	error!("gs = {:#X}", processor::readgs());
 	error!("stack_frame = {stack_frame:#?}");
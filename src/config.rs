@@ -9,10 +9,12 @@ pub(crate) const USER_STACK_SIZE: usize = 0x0010_0000;
 		not(any(
 			all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
 			all(target_arch = "x86_64", feature = "rtl8139"),
+			feature = "e1000",
 		)),
 		feature = "virtio-net",
 	),
 	feature = "fuse",
+	feature = "virtio-9p",
 	feature = "vsock",
 	feature = "console",
 ))]
@@ -22,6 +24,14 @@ pub(crate) const VIRTIO_MAX_QUEUE_SIZE: u16 = if cfg!(feature = "pci") { 2048 }
 #[cfg(feature = "tcp")]
 pub(crate) const DEFAULT_KEEP_ALIVE_INTERVAL: u64 = 75000;
 
+/// Default number of unanswered keep alive probes, before a connection is dropped
+#[cfg(feature = "tcp")]
+pub(crate) const DEFAULT_KEEP_ALIVE_COUNT: u32 = 9;
+
+/// Size of the section, which `cargo xtask symbols` fills with the symbol table
+#[cfg(feature = "symbols")]
+pub(crate) const SYMBOL_TABLE_SIZE: usize = 0x10_0000;
+
 #[cfg(feature = "vsock")]
 pub(crate) const VSOCK_PACKET_SIZE: u32 = 8192;
 
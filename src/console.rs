@@ -1,9 +1,11 @@
 #![allow(dead_code)]
 
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use core::{fmt, mem};
 
 use embedded_io::{ErrorType, Read, ReadReady, Write};
-use heapless::Vec;
 use hermit_sync::{InterruptTicketMutex, Lazy};
 
 use crate::arch::SerialDevice;
@@ -14,8 +16,18 @@ use crate::executor::WakerRegistration;
 #[cfg(not(target_arch = "riscv64"))]
 use crate::syscalls::interfaces::serial_buf_hypercall;
 
+/// Size, up to which output without a newline is buffered
 const SERIAL_BUFFER_SIZE: usize = 256;
 
+/// Maximum size of the buffer, if the device does not accept output
+const MAX_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Number of bytes, which have been dropped, because the buffer was full
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// Output of isolated cores is waiting for the housekeeping core
+static FLUSH_PENDING: AtomicBool = AtomicBool::new(false);
+
 pub(crate) enum IoDevice {
 	#[cfg(not(target_arch = "riscv64"))]
 	Uhyve(UhyveSerial),
@@ -121,14 +133,27 @@ impl Write for UhyveSerial {
 
 pub(crate) struct Console {
 	device: IoDevice,
-	buffer: Vec<u8, SERIAL_BUFFER_SIZE>,
+	/// Output, which has not been written to the device yet
+	///
+	/// The buffer grows on the kernel heap, if the device does not accept
+	/// output, and shrinks again, once the output has been written.
+	buffer: VecDeque<u8>,
 }
 
 impl Console {
 	pub fn new(device: IoDevice) -> Self {
 		Self {
 			device,
-			buffer: Vec::new(),
+			buffer: VecDeque::new(),
+		}
+	}
+
+	/// Drops the oldest output, which exceeds [`MAX_BUFFER_SIZE`].
+	fn drop_overflow(&mut self) {
+		if self.buffer.len() > MAX_BUFFER_SIZE {
+			let excess = self.buffer.len() - MAX_BUFFER_SIZE;
+			self.buffer.drain(..excess);
+			DROPPED.fetch_add(excess, Ordering::Relaxed);
 		}
 	}
 
@@ -136,6 +161,12 @@ impl Console {
 	pub fn replace_device(&mut self, device: IoDevice) {
 		self.device = device;
 	}
+
+	/// Reads from the device, bypassing the debug shell.
+	#[cfg(feature = "shell")]
+	pub fn read_device(&mut self, buf: &mut [u8]) -> Result<usize, Errno> {
+		self.device.read(buf)
+	}
 }
 
 impl ErrorType for Console {
@@ -143,55 +174,102 @@ impl ErrorType for Console {
 }
 
 impl Read for Console {
+	/// Reads the input of the application.
+	///
+	/// With the debug shell, the shell consumes the input of the device and
+	/// passes it on, while it is inactive.
 	fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
-		self.device.read(buf)
+		#[cfg(feature = "shell")]
+		let len = crate::shell::read_input(buf);
+		#[cfg(not(feature = "shell"))]
+		let len = self.device.read(buf)?;
+		Ok(len)
 	}
 }
 
 impl ReadReady for Console {
 	fn read_ready(&mut self) -> Result<bool, Self::Error> {
-		self.device.read_ready()
+		#[cfg(feature = "shell")]
+		let ready = crate::shell::input_ready();
+		#[cfg(not(feature = "shell"))]
+		let ready = self.device.read_ready()?;
+		Ok(ready)
 	}
 }
 
 impl Write for Console {
 	/// Writes a buffer to the console.
-	/// The content is buffered until a newline is encountered or the internal buffer is full.
+	/// The content is buffered until a newline is encountered or [`SERIAL_BUFFER_SIZE`] is exceeded.
 	/// To force early output, use [`flush`](Self::flush).
+	///
+	/// If the device fails, the output is kept and written with the next
+	/// output. Before the kernel heap is available, the output is written
+	/// directly. On isolated cores, the housekeeping core writes the output,
+	/// so that the application does not wait for the device.
 	fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
-		if SERIAL_BUFFER_SIZE - self.buffer.len() >= buf.len() {
-			// unwrap: we checked that buf fits in self.buffer
-			self.buffer.extend_from_slice(buf).unwrap();
-			if buf.contains(&b'\n') {
-				self.flush()?;
+		if self.buffer.try_reserve(buf.len()).is_err() {
+			if self.flush().is_err() {
+				DROPPED.fetch_add(buf.len(), Ordering::Relaxed);
+				return Ok(buf.len());
 			}
-		} else {
-			self.device.write_all(&self.buffer)?;
-			self.buffer.clear();
-			if buf.len() >= SERIAL_BUFFER_SIZE {
-				self.device.write_all(buf)?;
+			self.device.write_all(buf)?;
+			return Ok(buf.len());
+		}
+
+		self.buffer.extend(buf);
+		if buf.contains(&b'\n') || self.buffer.len() >= SERIAL_BUFFER_SIZE {
+			if crate::scheduler::is_isolated() {
+				FLUSH_PENDING.store(true, Ordering::Relaxed);
+				crate::arch::wakeup_core(crate::scheduler::HOUSEKEEPING_CORE);
 			} else {
-				// unwrap: we checked that buf fits in self.buffer
-				self.buffer.extend_from_slice(buf).unwrap();
-				if buf.contains(&b'\n') {
-					self.flush()?;
-				}
+				// the output remains buffered, if the device fails
+				self.flush().ok();
 			}
 		}
+		self.drop_overflow();
 
 		Ok(buf.len())
 	}
 
 	/// Immediately writes everything in the internal buffer to the output.
 	fn flush(&mut self) -> Result<(), Self::Error> {
-		if !self.buffer.is_empty() {
-			self.device.write_all(&self.buffer)?;
-			self.buffer.clear();
+		if self.buffer.is_empty() {
+			return Ok(());
+		}
+
+		let (front, back) = self.buffer.as_slices();
+		self.device.write_all(front)?;
+		self.device.write_all(back)?;
+		self.buffer.clear();
+		if self.buffer.capacity() > 4 * SERIAL_BUFFER_SIZE {
+			self.buffer.shrink_to(SERIAL_BUFFER_SIZE);
 		}
 		Ok(())
 	}
 }
 
+/// Writes the output, which isolated cores have buffered.
+///
+/// Called by the housekeeping core, when it handles its scheduler input and
+/// the waiting tasks.
+pub(crate) fn flush_deferred() {
+	if FLUSH_PENDING.swap(false, Ordering::Relaxed) {
+		match CONSOLE.try_lock() {
+			Some(mut console) => {
+				console.flush().ok();
+			}
+			None => FLUSH_PENDING.store(true, Ordering::Relaxed),
+		}
+	}
+}
+
+/// Shows the output, which is buffered, and the output, which has been dropped.
+pub(crate) fn proc_console() -> String {
+	let buffered = CONSOLE.lock().buffer.len();
+	let dropped = DROPPED.load(Ordering::Relaxed);
+	format!("Buffered:       {buffered:>8} bytes\nDropped:        {dropped:>8} bytes\n")
+}
+
 pub(crate) static CONSOLE_WAKER: InterruptTicketMutex<WakerRegistration> =
 	InterruptTicketMutex::new(WakerRegistration::new());
 pub(crate) static CONSOLE: Lazy<InterruptTicketMutex<Console>> = Lazy::new(|| {
@@ -216,6 +294,8 @@ pub fn _print(args: fmt::Arguments<'_>) {
 pub fn _panic_print(args: fmt::Arguments<'_>) {
 	let mut console = unsafe { CONSOLE.make_guard_unchecked() };
 	console.write_fmt(args).ok();
+	// the housekeeping core may not get to the output anymore
+	console.flush().ok();
 	mem::forget(console);
 }
 
@@ -0,0 +1,57 @@
+//! Runtime binding of kernel drivers to devices.
+//!
+//! A device can be unbound from its kernel driver at runtime, e.g., to hand
+//! the network card to an application, which drives it through the raw-frame
+//! interface. Binding the device again returns it to the kernel.
+
+use crate::errno::Errno;
+use crate::executor::network::NIC;
+
+/// Devices, which can be unbound from their kernel driver.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Device {
+	/// The network card driving the network stack, named `net0`
+	Network,
+}
+
+impl Device {
+	pub(crate) fn from_name(name: &str) -> Result<Self, Errno> {
+		match name {
+			"net0" => Ok(Self::Network),
+			_ => Err(Errno::Nodev),
+		}
+	}
+}
+
+/// Detaches the kernel driver from `device` and tears down its interrupts.
+pub(crate) fn unbind(device: Device) -> Result<(), Errno> {
+	match device {
+		Device::Network => NIC
+			.lock()
+			.as_nic_mut()
+			.map_err(|_| Errno::Nodev)?
+			.detach_device(),
+	}
+}
+
+/// Binds `device` to its kernel driver again.
+pub(crate) fn bind(device: Device) -> Result<(), Errno> {
+	match device {
+		Device::Network => NIC
+			.lock()
+			.as_nic_mut()
+			.map_err(|_| Errno::Nodev)?
+			.attach_device(),
+	}
+}
+
+/// Returns whether `device` is bound to its kernel driver.
+pub(crate) fn is_bound(device: Device) -> Result<bool, Errno> {
+	match device {
+		Device::Network => Ok(!NIC
+			.lock()
+			.as_nic_mut()
+			.map_err(|_| Errno::Nodev)?
+			.is_detached()),
+	}
+}
@@ -0,0 +1,284 @@
+//! A module containing the block device layer.
+//!
+//! Block devices are registered under a unique name (e.g. `nvme0n1`) and can
+//! be looked up by other parts of the kernel, like filesystems or virtual
+//! block devices stacked on top of other block devices. Partitions of the
+//! registered devices are registered as block devices of their own.
+//!
+//! With the feature `blktrace`, the requests to the registered devices are
+//! traced (see [`trace`]).
+
+#[cfg(feature = "nvme")]
+pub(crate) mod nvme;
+pub(crate) mod partition;
+#[cfg(feature = "raid")]
+pub(crate) mod raid;
+#[cfg(feature = "blktrace")]
+pub(crate) mod trace;
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::errno::Errno;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum BlockError {
+	/// The request exceeds the capacity of the device.
+	OutOfRange,
+	/// The buffer length is not a multiple of the block size.
+	Unaligned,
+	/// The device failed to process the request.
+	#[cfg(any(feature = "nvme", feature = "raid"))]
+	Io,
+	/// The block device does not exist.
+	NotFound,
+	/// A block device with the same name is already registered.
+	AlreadyExists,
+	/// The configuration of a virtual block device is invalid.
+	InvalidConfig,
+}
+
+impl From<BlockError> for Errno {
+	fn from(value: BlockError) -> Self {
+		match value {
+			BlockError::OutOfRange | BlockError::Unaligned | BlockError::InvalidConfig => {
+				Errno::Inval
+			}
+			#[cfg(any(feature = "nvme", feature = "raid"))]
+			BlockError::Io => Errno::Io,
+			BlockError::NotFound => Errno::Nodev,
+			BlockError::AlreadyExists => Errno::Exist,
+		}
+	}
+}
+
+/// Interface of a device, which is addressed in fixed-size blocks.
+pub(crate) trait BlockDevice: Send {
+	/// Returns the size of a block in bytes.
+	fn block_size(&self) -> usize;
+
+	/// Returns the capacity of the device in blocks.
+	fn num_blocks(&self) -> u64;
+
+	/// Reads `buf.len() / block_size()` blocks starting at block `lba` into `buf`.
+	fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError>;
+
+	/// Writes `buf.len() / block_size()` blocks starting at block `lba` from `buf`.
+	fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError>;
+
+	/// Ensures that all previously written blocks reached stable storage.
+	fn flush(&mut self) -> Result<(), BlockError> {
+		Ok(())
+	}
+}
+
+pub(crate) type BlockDeviceRef = Arc<InterruptTicketMutex<dyn BlockDevice>>;
+
+/// Block device in memory for the tests of the file systems and of the
+/// virtual block devices
+#[cfg(all(test, any(feature = "ext2", feature = "fat", feature = "raid")))]
+pub(crate) struct RamDisk {
+	block_size: usize,
+	data: Vec<u8>,
+}
+
+#[cfg(all(test, any(feature = "ext2", feature = "fat", feature = "raid")))]
+impl RamDisk {
+	pub fn new(block_size: usize, data: Vec<u8>) -> Self {
+		assert!(data.len().is_multiple_of(block_size));
+		Self { block_size, data }
+	}
+}
+
+#[cfg(all(test, any(feature = "ext2", feature = "fat", feature = "raid")))]
+impl BlockDevice for RamDisk {
+	fn block_size(&self) -> usize {
+		self.block_size
+	}
+
+	fn num_blocks(&self) -> u64 {
+		u64::try_from(self.data.len() / self.block_size).unwrap()
+	}
+
+	fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+		check_request(self, lba, buf.len())?;
+		let start = usize::try_from(lba).unwrap() * self.block_size;
+		buf.copy_from_slice(&self.data[start..start + buf.len()]);
+		Ok(())
+	}
+
+	fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+		check_request(self, lba, buf.len())?;
+		let start = usize::try_from(lba).unwrap() * self.block_size;
+		self.data[start..start + buf.len()].copy_from_slice(buf);
+		Ok(())
+	}
+}
+
+/// Counters of the requests, which have been completed by the physical block
+/// devices. Requests to partitions and RAID devices are counted, when they
+/// reach the underlying devices.
+pub(crate) struct BlockStatistics {
+	reads: AtomicU64,
+	read_bytes: AtomicU64,
+	writes: AtomicU64,
+	written_bytes: AtomicU64,
+	flushes: AtomicU64,
+}
+
+impl BlockStatistics {
+	const fn new() -> Self {
+		Self {
+			reads: AtomicU64::new(0),
+			read_bytes: AtomicU64::new(0),
+			writes: AtomicU64::new(0),
+			written_bytes: AtomicU64::new(0),
+			flushes: AtomicU64::new(0),
+		}
+	}
+
+	/// Counts a read of `len` bytes.
+	#[cfg(feature = "nvme")]
+	pub fn read(&self, len: usize) {
+		self.reads.fetch_add(1, Ordering::Relaxed);
+		self.read_bytes
+			.fetch_add(len.try_into().unwrap(), Ordering::Relaxed);
+	}
+
+	/// Counts a write of `len` bytes.
+	#[cfg(feature = "nvme")]
+	pub fn written(&self, len: usize) {
+		self.writes.fetch_add(1, Ordering::Relaxed);
+		self.written_bytes
+			.fetch_add(len.try_into().unwrap(), Ordering::Relaxed);
+	}
+
+	/// Counts a flush.
+	#[cfg(feature = "nvme")]
+	pub fn flushed(&self) {
+		self.flushes.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Returns the number of reads and read bytes.
+	pub fn reads(&self) -> (u64, u64) {
+		(
+			self.reads.load(Ordering::Relaxed),
+			self.read_bytes.load(Ordering::Relaxed),
+		)
+	}
+
+	/// Returns the number of writes and written bytes.
+	pub fn writes(&self) -> (u64, u64) {
+		(
+			self.writes.load(Ordering::Relaxed),
+			self.written_bytes.load(Ordering::Relaxed),
+		)
+	}
+
+	/// Returns the number of flushes.
+	pub fn flushes(&self) -> u64 {
+		self.flushes.load(Ordering::Relaxed)
+	}
+}
+
+/// Statistics of all physical block devices
+pub(crate) static STATISTICS: BlockStatistics = BlockStatistics::new();
+
+/// Checks whether a request of `len` bytes starting at block `lba` is valid
+/// for `device` and returns the number of requested blocks.
+pub(crate) fn check_request(
+	device: &(impl BlockDevice + ?Sized),
+	lba: u64,
+	len: usize,
+) -> Result<u64, BlockError> {
+	let block_size = device.block_size();
+	if len % block_size != 0 {
+		return Err(BlockError::Unaligned);
+	}
+
+	let count = u64::try_from(len / block_size).unwrap();
+	match lba.checked_add(count) {
+		Some(end) if end <= device.num_blocks() => Ok(count),
+		_ => Err(BlockError::OutOfRange),
+	}
+}
+
+/// Checks whether a direct transfer of `len` bytes at the byte offset `pos`
+/// from or to the buffer at `addr` is aligned to the block size of `device`.
+pub(crate) fn check_direct(
+	device: &(impl BlockDevice + ?Sized),
+	pos: u64,
+	addr: usize,
+	len: usize,
+) -> Result<(), BlockError> {
+	let block_size = device.block_size();
+	if !pos.is_multiple_of(u64::try_from(block_size).unwrap())
+		|| !addr.is_multiple_of(block_size)
+		|| !len.is_multiple_of(block_size)
+	{
+		return Err(BlockError::Unaligned);
+	}
+
+	Ok(())
+}
+
+static BLOCK_DEVICES: InterruptTicketMutex<Vec<(String, BlockDeviceRef)>> =
+	InterruptTicketMutex::new(Vec::new());
+
+/// Registers `device` under the given `name`.
+pub(crate) fn register(name: String, device: BlockDeviceRef) -> Result<(), BlockError> {
+	let mut devices = BLOCK_DEVICES.lock();
+	if devices.iter().any(|(n, _)| *n == name) {
+		return Err(BlockError::AlreadyExists);
+	}
+
+	{
+		let guard = device.lock();
+		info!(
+			"Register block device {name} with {} blocks of {} bytes",
+			guard.num_blocks(),
+			guard.block_size()
+		);
+	}
+	#[cfg(feature = "blktrace")]
+	let device: BlockDeviceRef = Arc::new(InterruptTicketMutex::new(trace::TracedDevice::new(
+		&name, device,
+	)));
+	devices.push((name, device));
+	Ok(())
+}
+
+/// Returns the block device with the given `name`.
+pub(crate) fn get(name: &str) -> Result<BlockDeviceRef, BlockError> {
+	BLOCK_DEVICES
+		.lock()
+		.iter()
+		.find(|(n, _)| n == name)
+		.map(|(_, device)| device.clone())
+		.ok_or(BlockError::NotFound)
+}
+
+/// Returns the names of all registered block devices.
+pub(crate) fn names() -> Vec<String> {
+	BLOCK_DEVICES
+		.lock()
+		.iter()
+		.map(|(name, _)| name.clone())
+		.collect()
+}
+
+/// Registers the block devices of all initialized drivers and assembles the
+/// virtual block devices stacked on top of them.
+pub(crate) fn init() {
+	#[cfg(feature = "nvme")]
+	nvme::init();
+
+	partition::init();
+
+	#[cfg(feature = "raid")]
+	raid::init();
+}
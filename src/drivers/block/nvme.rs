@@ -0,0 +1,137 @@
+//! Block device interface of NVMe namespaces.
+
+use alloc::format;
+use alloc::sync::Arc;
+
+use hermit_sync::InterruptTicketMutex;
+use vroom::{IoQueuePairId, Namespace, NamespaceId};
+
+use crate::drivers::block::{self, BlockDevice, BlockError};
+use crate::drivers::nvme::{IoQueuePairOwner, NvmeDriver};
+use crate::drivers::pci::get_nvme_driver;
+use crate::syscalls::nvme::SysNvmeError;
+
+/// Number of entries of the IO queue pair used by a namespace block device.
+const IO_QUEUE_ENTRIES: u32 = 64;
+
+/// A NVMe namespace, which is accessed through its own IO queue pair.
+pub(crate) struct NvmeBlockDevice {
+	namespace: Namespace,
+	io_queue_pair_id: IoQueuePairId,
+	/// Largest transfer in bytes, rounded down to a multiple of the block size
+	maximum_transfer_size: usize,
+}
+
+impl NvmeBlockDevice {
+	fn new(driver: &mut NvmeDriver, namespace_id: &NamespaceId) -> Result<Self, SysNvmeError> {
+		let namespace = driver.namespace(namespace_id)?;
+		let entries = IO_QUEUE_ENTRIES.min(driver.maximum_queue_entries_supported());
+		let io_queue_pair_id =
+			driver.create_io_queue_pair(namespace_id, entries, IoQueuePairOwner::Kernel)?;
+
+		let block_size = usize::try_from(namespace.block_size).unwrap();
+		let maximum_transfer_size =
+			(driver.maximum_transfer_size() / block_size).max(1) * block_size;
+
+		Ok(Self {
+			namespace,
+			io_queue_pair_id,
+			maximum_transfer_size,
+		})
+	}
+
+	fn blocks_of(&self, len: usize) -> u64 {
+		u64::try_from(len / self.block_size()).unwrap()
+	}
+}
+
+impl BlockDevice for NvmeBlockDevice {
+	fn block_size(&self) -> usize {
+		usize::try_from(self.namespace.block_size).unwrap()
+	}
+
+	fn num_blocks(&self) -> u64 {
+		self.namespace.block_count
+	}
+
+	fn read_blocks(&mut self, mut lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+		block::check_request(self, lba, buf.len())?;
+		let mut driver = get_nvme_driver().ok_or(BlockError::NotFound)?.lock();
+
+		for chunk in buf.chunks_mut(self.maximum_transfer_size) {
+			let mut buffer = driver
+				.allocate_buffer::<u8>(&self.io_queue_pair_id, chunk.len())
+				.map_err(|_| BlockError::Io)?;
+			let result = driver.read_from_io_queue_pair(&self.io_queue_pair_id, &mut buffer, lba);
+			if result.is_ok() {
+				chunk.copy_from_slice(unsafe {
+					core::slice::from_raw_parts(buffer.virt.cast_const(), chunk.len())
+				});
+			}
+			driver
+				.deallocate_buffer(&self.io_queue_pair_id, buffer)
+				.map_err(|_| BlockError::Io)?;
+			result.map_err(|_| BlockError::Io)?;
+
+			lba += self.blocks_of(chunk.len());
+		}
+
+		block::STATISTICS.read(buf.len());
+		Ok(())
+	}
+
+	fn write_blocks(&mut self, mut lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+		block::check_request(self, lba, buf.len())?;
+		let mut driver = get_nvme_driver().ok_or(BlockError::NotFound)?.lock();
+
+		for chunk in buf.chunks(self.maximum_transfer_size) {
+			let buffer = driver
+				.allocate_buffer::<u8>(&self.io_queue_pair_id, chunk.len())
+				.map_err(|_| BlockError::Io)?;
+			unsafe {
+				core::slice::from_raw_parts_mut(buffer.virt, chunk.len()).copy_from_slice(chunk);
+			}
+			let result = driver.write_to_io_queue_pair(&self.io_queue_pair_id, &buffer, lba);
+			driver
+				.deallocate_buffer(&self.io_queue_pair_id, buffer)
+				.map_err(|_| BlockError::Io)?;
+			result.map_err(|_| BlockError::Io)?;
+
+			lba += self.blocks_of(chunk.len());
+		}
+
+		block::STATISTICS.written(buf.len());
+		Ok(())
+	}
+
+	fn flush(&mut self) -> Result<(), BlockError> {
+		get_nvme_driver()
+			.ok_or(BlockError::NotFound)?
+			.lock()
+			.flush_io_queue_pair(&self.io_queue_pair_id)
+			.map_err(|_| BlockError::Io)?;
+		block::STATISTICS.flushed();
+		Ok(())
+	}
+}
+
+/// Registers every namespace of the NVMe controller as block device `nvme0n<i>`.
+pub(crate) fn init() {
+	let Some(driver) = get_nvme_driver() else {
+		return;
+	};
+
+	let mut driver = driver.lock();
+	for (i, namespace_id) in driver.namespace_ids().iter().enumerate() {
+		let name = format!("nvme0n{}", i + 1);
+		match NvmeBlockDevice::new(&mut driver, namespace_id) {
+			Ok(device) => {
+				if let Err(err) = block::register(name, Arc::new(InterruptTicketMutex::new(device)))
+				{
+					error!("Could not register NVMe namespace as block device: {err:?}");
+				}
+			}
+			Err(err) => error!("Could not create block device {name}: {err:?}"),
+		}
+	}
+}
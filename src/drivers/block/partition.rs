@@ -0,0 +1,177 @@
+//! Partitions of block devices.
+//!
+//! Every registered block device is scanned for a GUID partition table or
+//! a MBR partition table. Each partition is registered as block device
+//! `<device>p<n>`, e.g. `nvme0n1p1`. For GPT, `n` is the index of the
+//! partition entry, for MBR the index of the primary partition (starting
+//! at 1). Extended MBR partitions are not supported.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::drivers::block::{self, BlockDevice, BlockDeviceRef, BlockError};
+
+/// Partition type of a protective MBR, which is followed by a GPT
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xee;
+/// Partition types of extended partitions
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+
+/// A contiguous range of blocks of another block device.
+pub(crate) struct Partition {
+	device: BlockDeviceRef,
+	/// First block of the partition on the underlying device
+	start: u64,
+	num_blocks: u64,
+	block_size: usize,
+}
+
+impl BlockDevice for Partition {
+	fn block_size(&self) -> usize {
+		self.block_size
+	}
+
+	fn num_blocks(&self) -> u64 {
+		self.num_blocks
+	}
+
+	fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+		block::check_request(self, lba, buf.len())?;
+		self.device.lock().read_blocks(self.start + lba, buf)
+	}
+
+	fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+		block::check_request(self, lba, buf.len())?;
+		self.device.lock().write_blocks(self.start + lba, buf)
+	}
+
+	fn flush(&mut self) -> Result<(), BlockError> {
+		self.device.lock().flush()
+	}
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+	u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+	u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// Returns the partitions of a GPT as `(index, first block, number of blocks)`.
+fn scan_gpt(device: &mut dyn BlockDevice) -> Result<Vec<(usize, u64, u64)>, BlockError> {
+	let block_size = device.block_size();
+	let mut header = vec![0u8; block_size];
+	device.read_blocks(1, &mut header)?;
+	if &header[..8] != GPT_SIGNATURE {
+		return Err(BlockError::InvalidConfig);
+	}
+
+	let entries_lba = read_u64(&header, 72);
+	let num_entries = usize::try_from(read_u32(&header, 80)).unwrap();
+	let entry_size = usize::try_from(read_u32(&header, 84)).unwrap();
+	if entry_size < 128 || block_size % entry_size != 0 {
+		return Err(BlockError::InvalidConfig);
+	}
+
+	let table_blocks = (num_entries * entry_size).div_ceil(block_size);
+	let mut table = vec![0u8; table_blocks * block_size];
+	device.read_blocks(entries_lba, &mut table)?;
+
+	let partitions = table
+		.chunks_exact(entry_size)
+		.take(num_entries)
+		.enumerate()
+		.filter(|(_, entry)| entry[..16].iter().any(|b| *b != 0))
+		.filter_map(|(i, entry)| {
+			let first = read_u64(entry, 32);
+			let last = read_u64(entry, 40);
+			(last >= first).then(|| (i + 1, first, last - first + 1))
+		})
+		.collect();
+
+	Ok(partitions)
+}
+
+/// Returns the partitions of a MBR as `(index, first block, number of blocks)`
+/// or `None`, if the device does not contain a MBR.
+fn scan_mbr(device: &mut dyn BlockDevice) -> Result<Option<Vec<(usize, u64, u64)>>, BlockError> {
+	let mut mbr = vec![0u8; device.block_size()];
+	device.read_blocks(0, &mut mbr)?;
+	if mbr.len() < 512 || mbr[510..512] != [0x55, 0xaa] {
+		return Ok(None);
+	}
+
+	// A file system without partition table (e.g. FAT) also ends its first
+	// sector with the signature, but the boot indicators are not valid.
+	let entries = &mbr[446..510];
+	if entries.chunks_exact(16).any(|entry| entry[0] & 0x7f != 0) {
+		return Ok(None);
+	}
+
+	let mut partitions = Vec::new();
+	for i in 0..4 {
+		let entry = &mbr[446 + i * 16..446 + (i + 1) * 16];
+		let kind = entry[4];
+		let start = u64::from(read_u32(entry, 8));
+		let num_blocks = u64::from(read_u32(entry, 12));
+
+		if kind == MBR_TYPE_GPT_PROTECTIVE {
+			return scan_gpt(device).map(Some);
+		}
+		if kind == 0 || num_blocks == 0 {
+			continue;
+		}
+		if MBR_TYPES_EXTENDED.contains(&kind) {
+			warn!("Extended MBR partitions are not supported");
+			continue;
+		}
+
+		partitions.push((i + 1, start, num_blocks));
+	}
+
+	Ok(Some(partitions))
+}
+
+/// Scans the registered block devices and registers their partitions.
+pub(crate) fn init() {
+	for name in block::names() {
+		let device = block::get(&name).unwrap();
+		let (partitions, num_blocks, block_size) = {
+			let mut guard = device.lock();
+			let partitions = match scan_mbr(&mut *guard) {
+				Ok(Some(partitions)) => partitions,
+				Ok(None) => continue,
+				Err(err) => {
+					warn!("Could not read partition table of {name}: {err:?}");
+					continue;
+				}
+			};
+			(partitions, guard.num_blocks(), guard.block_size())
+		};
+
+		for (index, start, len) in partitions {
+			if start.checked_add(len).is_none_or(|end| end > num_blocks) {
+				warn!("Partition {index} of {name} exceeds the device, skipping");
+				continue;
+			}
+
+			let partition = Partition {
+				device: device.clone(),
+				start,
+				num_blocks: len,
+				block_size,
+			};
+			let partition_name = format!("{name}p{index}");
+			if let Err(err) = block::register(
+				partition_name,
+				Arc::new(InterruptTicketMutex::new(partition)),
+			) {
+				error!("Could not register partition {index} of {name}: {err:?}");
+			}
+		}
+	}
+}
@@ -0,0 +1,415 @@
+//! md-style software RAID on top of other block devices.
+//!
+//! Arrays are configured on the kernel command line by
+//! `md=<name>:<level>:<member>,<member>[,...][:<chunk size in KiB>]`,
+//! e.g. `md=md0:raid1:nvme0n1,nvme0n2`. Supported levels are `raid0`, which
+//! stripes the blocks over all members, and `raid1`, which mirrors the blocks
+//! on all members. The chunk size is only relevant for `raid0` and defaults
+//! to 64 KiB.
+
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::drivers::block::{self, BlockDevice, BlockDeviceRef, BlockError};
+use crate::env;
+
+/// Default chunk size of striped arrays in KiB
+const DEFAULT_CHUNK_SIZE: usize = 64;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum RaidLevel {
+	/// Striping without redundancy
+	Raid0,
+	/// Mirroring
+	Raid1,
+}
+
+impl RaidLevel {
+	fn parse(s: &str) -> Option<Self> {
+		match s {
+			"raid0" | "0" => Some(Self::Raid0),
+			"raid1" | "1" => Some(Self::Raid1),
+			_ => None,
+		}
+	}
+}
+
+struct Member {
+	name: String,
+	device: BlockDeviceRef,
+	/// A failed member of a mirror is no longer used
+	failed: bool,
+}
+
+pub(crate) struct RaidDevice {
+	level: RaidLevel,
+	members: Vec<Member>,
+	block_size: usize,
+	num_blocks: u64,
+	/// Number of blocks of a chunk in a striped array
+	chunk_blocks: u64,
+}
+
+impl RaidDevice {
+	/// Assembles an array of `level` from the block devices `members`.
+	///
+	/// `chunk_size` is the size of a stripe unit in bytes and has to be a
+	/// multiple of the block size of the members.
+	pub(crate) fn new(
+		level: RaidLevel,
+		members: Vec<(String, BlockDeviceRef)>,
+		chunk_size: usize,
+	) -> Result<Self, BlockError> {
+		if members.len() < 2 {
+			error!("A RAID array requires at least two members");
+			return Err(BlockError::InvalidConfig);
+		}
+
+		for (i, (name, device)) in members.iter().enumerate() {
+			if members[..i].iter().any(|(_, d)| Arc::ptr_eq(d, device)) {
+				error!("Block device {name} is used twice in the same RAID array");
+				return Err(BlockError::InvalidConfig);
+			}
+		}
+
+		let (block_size, min_blocks) = {
+			let first = members[0].1.lock();
+			let mut min_blocks = first.num_blocks();
+			let block_size = first.block_size();
+			drop(first);
+
+			for (name, device) in &members[1..] {
+				let device = device.lock();
+				if device.block_size() != block_size {
+					error!("Block size of {name} differs from the other members of the RAID array");
+					return Err(BlockError::InvalidConfig);
+				}
+				min_blocks = min_blocks.min(device.num_blocks());
+			}
+
+			(block_size, min_blocks)
+		};
+
+		if chunk_size == 0 || chunk_size % block_size != 0 {
+			error!("The RAID chunk size has to be a multiple of {block_size} bytes");
+			return Err(BlockError::InvalidConfig);
+		}
+		let chunk_blocks = u64::try_from(chunk_size / block_size).unwrap();
+		let width = u64::try_from(members.len()).unwrap();
+
+		let num_blocks = match level {
+			RaidLevel::Raid0 => min_blocks / chunk_blocks * chunk_blocks * width,
+			RaidLevel::Raid1 => min_blocks,
+		};
+
+		let members = members
+			.into_iter()
+			.map(|(name, device)| Member {
+				name,
+				device,
+				failed: false,
+			})
+			.collect();
+
+		Ok(Self {
+			level,
+			members,
+			block_size,
+			num_blocks,
+			chunk_blocks,
+		})
+	}
+
+	/// Maps block `lba` of a striped array to the index of the member, the
+	/// block of the member and the number of blocks left in the chunk.
+	fn map_stripe(&self, lba: u64) -> (usize, u64, u64) {
+		let width = u64::try_from(self.members.len()).unwrap();
+		let chunk = lba / self.chunk_blocks;
+		let offset = lba % self.chunk_blocks;
+
+		let member = usize::try_from(chunk % width).unwrap();
+		let member_lba = chunk / width * self.chunk_blocks + offset;
+		(member, member_lba, self.chunk_blocks - offset)
+	}
+
+	fn mark_failed(&mut self, index: usize, err: BlockError) {
+		let member = &mut self.members[index];
+		if !member.failed {
+			member.failed = true;
+			error!(
+				"RAID member {} failed ({err:?}), array is degraded",
+				member.name
+			);
+		}
+	}
+
+	fn read_striped(&mut self, mut lba: u64, mut buf: &mut [u8]) -> Result<(), BlockError> {
+		while !buf.is_empty() {
+			let (index, member_lba, remaining) = self.map_stripe(lba);
+			let len = buf
+				.len()
+				.min(usize::try_from(remaining).unwrap() * self.block_size);
+			let (head, tail) = buf.split_at_mut(len);
+
+			self.members[index]
+				.device
+				.lock()
+				.read_blocks(member_lba, head)?;
+
+			lba += u64::try_from(len / self.block_size).unwrap();
+			buf = tail;
+		}
+
+		Ok(())
+	}
+
+	fn write_striped(&mut self, mut lba: u64, mut buf: &[u8]) -> Result<(), BlockError> {
+		while !buf.is_empty() {
+			let (index, member_lba, remaining) = self.map_stripe(lba);
+			let len = buf
+				.len()
+				.min(usize::try_from(remaining).unwrap() * self.block_size);
+			let (head, tail) = buf.split_at(len);
+
+			self.members[index]
+				.device
+				.lock()
+				.write_blocks(member_lba, head)?;
+
+			lba += u64::try_from(len / self.block_size).unwrap();
+			buf = tail;
+		}
+
+		Ok(())
+	}
+
+	fn read_mirrored(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+		for index in 0..self.members.len() {
+			if self.members[index].failed {
+				continue;
+			}
+
+			let result = self.members[index].device.lock().read_blocks(lba, buf);
+			match result {
+				Ok(()) => return Ok(()),
+				Err(err) => self.mark_failed(index, err),
+			}
+		}
+
+		Err(BlockError::Io)
+	}
+
+	fn write_mirrored(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+		let mut written = false;
+		for index in 0..self.members.len() {
+			if self.members[index].failed {
+				continue;
+			}
+
+			let result = self.members[index].device.lock().write_blocks(lba, buf);
+			match result {
+				Ok(()) => written = true,
+				Err(err) => self.mark_failed(index, err),
+			}
+		}
+
+		if written { Ok(()) } else { Err(BlockError::Io) }
+	}
+}
+
+impl BlockDevice for RaidDevice {
+	fn block_size(&self) -> usize {
+		self.block_size
+	}
+
+	fn num_blocks(&self) -> u64 {
+		self.num_blocks
+	}
+
+	fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+		block::check_request(self, lba, buf.len())?;
+		match self.level {
+			RaidLevel::Raid0 => self.read_striped(lba, buf),
+			RaidLevel::Raid1 => self.read_mirrored(lba, buf),
+		}
+	}
+
+	fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+		block::check_request(self, lba, buf.len())?;
+		match self.level {
+			RaidLevel::Raid0 => self.write_striped(lba, buf),
+			RaidLevel::Raid1 => self.write_mirrored(lba, buf),
+		}
+	}
+
+	fn flush(&mut self) -> Result<(), BlockError> {
+		let mut flushed = false;
+		for index in 0..self.members.len() {
+			if self.members[index].failed {
+				continue;
+			}
+
+			let result = self.members[index].device.lock().flush();
+			match (self.level, result) {
+				(_, Ok(())) => flushed = true,
+				(RaidLevel::Raid0, Err(err)) => return Err(err),
+				(RaidLevel::Raid1, Err(err)) => self.mark_failed(index, err),
+			}
+		}
+
+		if flushed { Ok(()) } else { Err(BlockError::Io) }
+	}
+}
+
+/// Parses a single `md=` argument into the name, the level, the names of the
+/// members and the chunk size in bytes of the array.
+fn parse(spec: &str) -> Result<(&str, RaidLevel, Vec<&str>, usize), BlockError> {
+	let mut fields = spec.split(':');
+	let (Some(name), Some(level), Some(members)) = (fields.next(), fields.next(), fields.next())
+	else {
+		return Err(BlockError::InvalidConfig);
+	};
+	let level = RaidLevel::parse(level).ok_or(BlockError::InvalidConfig)?;
+	let chunk_size = match fields.next() {
+		Some(chunk) => chunk
+			.parse::<usize>()
+			.map_err(|_| BlockError::InvalidConfig)?,
+		None => DEFAULT_CHUNK_SIZE,
+	};
+	if fields.next().is_some() || name.is_empty() {
+		return Err(BlockError::InvalidConfig);
+	}
+	let chunk_size = chunk_size
+		.checked_mul(1024)
+		.ok_or(BlockError::InvalidConfig)?;
+
+	Ok((name, level, members.split(',').collect(), chunk_size))
+}
+
+/// Parses a single `md=` argument and assembles the described array.
+fn assemble(spec: &str) -> Result<(String, RaidDevice), BlockError> {
+	let (name, level, members, chunk_size) = parse(spec)?;
+	let members = members
+		.into_iter()
+		.map(|member| Ok((member.to_string(), block::get(member)?)))
+		.collect::<Result<Vec<_>, BlockError>>()?;
+
+	let device = RaidDevice::new(level, members, chunk_size)?;
+	Ok((name.to_string(), device))
+}
+
+/// Assembles all arrays given on the kernel command line.
+pub(crate) fn init() {
+	for spec in env::md() {
+		match assemble(spec) {
+			Ok((name, device)) => {
+				info!(
+					"Assembled {:?} array {name} with {} members",
+					device.level,
+					device.members.len()
+				);
+				if let Err(err) = block::register(name, Arc::new(InterruptTicketMutex::new(device)))
+				{
+					error!("Could not register RAID array: {err:?}");
+				}
+			}
+			Err(err) => error!("Could not assemble RAID array md={spec}: {err:?}"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::{format, vec};
+
+	use super::*;
+	use crate::drivers::block::RamDisk;
+
+	const BLOCK_SIZE: usize = 512;
+
+	/// Returns `count` members of 10 blocks.
+	fn members(count: usize) -> Vec<(String, BlockDeviceRef)> {
+		(0..count)
+			.map(|i| {
+				let device: BlockDeviceRef = Arc::new(InterruptTicketMutex::new(RamDisk::new(
+					BLOCK_SIZE,
+					vec![0; 10 * BLOCK_SIZE],
+				)));
+				(format!("disk{i}"), device)
+			})
+			.collect()
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_raid0_stripe_mapping() {
+		let device = RaidDevice::new(RaidLevel::Raid0, members(3), 2 * BLOCK_SIZE).unwrap();
+		assert_eq!(device.num_blocks(), 30);
+
+		assert_eq!(device.map_stripe(0), (0, 0, 2));
+		assert_eq!(device.map_stripe(1), (0, 1, 1));
+		assert_eq!(device.map_stripe(2), (1, 0, 2));
+		assert_eq!(device.map_stripe(5), (2, 1, 1));
+		assert_eq!(device.map_stripe(6), (0, 2, 2));
+		assert_eq!(device.map_stripe(13), (0, 5, 1));
+		assert_eq!(device.map_stripe(29), (2, 9, 1));
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_raid0_requests_across_chunks() {
+		let members = members(2);
+		let disks: Vec<BlockDeviceRef> = members.iter().map(|(_, d)| d.clone()).collect();
+		// The last block of each member is not part of a full chunk.
+		let mut device = RaidDevice::new(RaidLevel::Raid0, members, 3 * BLOCK_SIZE).unwrap();
+		assert_eq!(device.num_blocks(), 18);
+
+		let data: Vec<u8> = (0..18 * BLOCK_SIZE)
+			.map(|i| u8::try_from(i / BLOCK_SIZE).unwrap())
+			.collect();
+		device.write_blocks(0, &data).unwrap();
+		let mut block = vec![0; BLOCK_SIZE];
+		disks[1].lock().read_blocks(4, &mut block).unwrap();
+		assert!(block.iter().all(|byte| *byte == 10));
+
+		let mut buf = vec![0; 7 * BLOCK_SIZE];
+		device.read_blocks(2, &mut buf).unwrap();
+		assert_eq!(buf, data[2 * BLOCK_SIZE..9 * BLOCK_SIZE]);
+		assert_eq!(
+			device.read_blocks(17, &mut buf),
+			Err(BlockError::OutOfRange)
+		);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_md_argument() {
+		assert_eq!(
+			parse("md0:raid1:nvme0n1,nvme0n2"),
+			Ok((
+				"md0",
+				RaidLevel::Raid1,
+				vec!["nvme0n1", "nvme0n2"],
+				DEFAULT_CHUNK_SIZE * 1024
+			))
+		);
+		assert_eq!(
+			parse("md1:0:a,b,c:128"),
+			Ok(("md1", RaidLevel::Raid0, vec!["a", "b", "c"], 128 * 1024))
+		);
+
+		for spec in [
+			"md0",
+			"md0:raid1",
+			"md0:raid5:a,b",
+			"md0:raid0:a,b:chunk",
+			"md0:raid0:a,b:64:extra",
+			":raid0:a,b",
+		] {
+			assert_eq!(parse(spec), Err(BlockError::InvalidConfig), "{spec}");
+		}
+	}
+}
@@ -0,0 +1,214 @@
+//! Tracing of block requests.
+//!
+//! Every registered block device is wrapped by [`TracedDevice`], which records
+//! an event, when a request is submitted to the device and when it completes.
+//! The completion carries the latency and the outcome of the request. Since
+//! partitions and RAID devices are stacked on top of other block devices, a
+//! request to them is followed by the requests to the underlying devices.
+//!
+//! The events are kept in a ring buffer, which drops the oldest events, and
+//! are read through `/proc/blocktrace`.
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::arch::processor::get_timer_ticks;
+use crate::drivers::block::{BlockDevice, BlockDeviceRef, BlockError};
+
+/// Maximum number of buffered events
+const CAPACITY: usize = 4096;
+
+static EVENTS: InterruptTicketMutex<VecDeque<Event>> = InterruptTicketMutex::new(VecDeque::new());
+
+/// Number of events, which have been dropped, since the buffer was full
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Identifier of the next request, which relates its submission to its completion
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Operation {
+	Read,
+	Write,
+	Flush,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Kind {
+	Submit,
+	Complete {
+		/// Time between submission and completion in microseconds
+		latency: u64,
+		result: Result<(), BlockError>,
+	},
+}
+
+#[derive(Debug)]
+struct Event {
+	/// Time of the event in microseconds since boot
+	timestamp: u64,
+	id: u64,
+	device: Arc<str>,
+	operation: Operation,
+	lba: u64,
+	blocks: u64,
+	kind: Kind,
+}
+
+impl fmt::Display for Event {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let seconds = self.timestamp / 1_000_000;
+		let microseconds = self.timestamp % 1_000_000;
+		let operation = match self.operation {
+			Operation::Read => 'R',
+			Operation::Write => 'W',
+			Operation::Flush => 'F',
+		};
+		write!(
+			f,
+			"{seconds:5}.{microseconds:06} {:<10} {:>8}",
+			self.device, self.id
+		)?;
+		let kind = match self.kind {
+			Kind::Submit => 'S',
+			Kind::Complete { .. } => 'C',
+		};
+		write!(f, " {kind} {operation}")?;
+		if self.operation != Operation::Flush {
+			write!(f, " {}+{}", self.lba, self.blocks)?;
+		}
+		match self.kind {
+			Kind::Submit => writeln!(f),
+			Kind::Complete {
+				latency,
+				result: Ok(()),
+			} => writeln!(f, " {latency}us ok"),
+			Kind::Complete {
+				latency,
+				result: Err(err),
+			} => writeln!(f, " {latency}us {err:?}"),
+		}
+	}
+}
+
+fn record(event: Event) {
+	let mut events = EVENTS.lock();
+	if events.len() == CAPACITY {
+		events.pop_front();
+		DROPPED.fetch_add(1, Ordering::Relaxed);
+	}
+	events.push_back(event);
+}
+
+/// Block device, which records the requests to the wrapped device.
+pub(crate) struct TracedDevice {
+	name: Arc<str>,
+	device: BlockDeviceRef,
+}
+
+impl TracedDevice {
+	pub fn new(name: &str, device: BlockDeviceRef) -> Self {
+		Self {
+			name: name.into(),
+			device,
+		}
+	}
+
+	fn trace(
+		&self,
+		operation: Operation,
+		lba: u64,
+		len: usize,
+		f: impl FnOnce(&mut dyn BlockDevice) -> Result<(), BlockError>,
+	) -> Result<(), BlockError> {
+		let mut device = self.device.lock();
+		let blocks = u64::try_from(len / device.block_size()).unwrap();
+		let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+		let submitted = get_timer_ticks();
+		record(Event {
+			timestamp: submitted,
+			id,
+			device: self.name.clone(),
+			operation,
+			lba,
+			blocks,
+			kind: Kind::Submit,
+		});
+
+		let result = f(&mut *device);
+
+		let completed = get_timer_ticks();
+		record(Event {
+			timestamp: completed,
+			id,
+			device: self.name.clone(),
+			operation,
+			lba,
+			blocks,
+			kind: Kind::Complete {
+				latency: completed.saturating_sub(submitted),
+				result,
+			},
+		});
+		result
+	}
+}
+
+impl BlockDevice for TracedDevice {
+	fn block_size(&self) -> usize {
+		self.device.lock().block_size()
+	}
+
+	fn num_blocks(&self) -> u64 {
+		self.device.lock().num_blocks()
+	}
+
+	fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+		self.trace(Operation::Read, lba, buf.len(), |device| {
+			device.read_blocks(lba, buf)
+		})
+	}
+
+	fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+		self.trace(Operation::Write, lba, buf.len(), |device| {
+			device.write_blocks(lba, buf)
+		})
+	}
+
+	fn flush(&mut self) -> Result<(), BlockError> {
+		self.trace(Operation::Flush, 0, 0, |device| device.flush())
+	}
+}
+
+/// Shows the buffered events, one event per line.
+///
+/// Each line consists of the timestamp, the device, the identifier of the
+/// request, `S` for its submission or `C` for its completion, the operation,
+/// and for reads and writes the first block and the number of blocks.
+/// Completions end with the latency and the outcome.
+pub(crate) fn proc_blocktrace() -> String {
+	let mut s = String::new();
+	let dropped = DROPPED.load(Ordering::Relaxed);
+	if dropped > 0 {
+		writeln!(s, "# {dropped} events dropped").unwrap();
+	}
+
+	let events = EVENTS
+		.lock()
+		.iter()
+		.map(|event| Event {
+			device: event.device.clone(),
+			..*event
+		})
+		.collect::<Vec<_>>();
+	for event in &events {
+		write!(s, "{event}").unwrap();
+	}
+	s
+}
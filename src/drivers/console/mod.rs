@@ -1,13 +1,5 @@
 #![allow(dead_code)]
 
-cfg_if::cfg_if! {
-	if #[cfg(feature = "pci")] {
-		mod pci;
-	} else {
-		mod mmio;
-	}
-}
-
 use alloc::vec::Vec;
 
 use embedded_io::{ErrorType, Read, ReadReady, Write};
@@ -23,11 +15,8 @@ use crate::drivers::error::DriverError;
 use crate::drivers::mmio::get_console_driver;
 #[cfg(feature = "pci")]
 use crate::drivers::pci::get_console_driver;
-use crate::drivers::virtio::error::VirtioConsoleError;
-#[cfg(not(feature = "pci"))]
-use crate::drivers::virtio::transport::mmio::{ComCfg, IsrStatus, NotifCfg};
-#[cfg(feature = "pci")]
-use crate::drivers::virtio::transport::pci::{ComCfg, IsrStatus, NotifCfg};
+use crate::drivers::virtio::error::{VirtioConsoleError, VirtioError};
+use crate::drivers::virtio::transport::{ComCfg, IsrStatus, NotifCfg, Transport};
 use crate::drivers::virtio::virtqueue::split::SplitVq;
 use crate::drivers::virtio::virtqueue::{
 	AvailBufferToken, BufferElem, BufferType, UsedBufferToken, VirtQueue, Virtq, VqIndex, VqSize,
@@ -268,6 +257,63 @@ impl Driver for VirtioConsoleDriver {
 	}
 }
 
+// Transport-independent construction of the Virtio console driver
+impl VirtioConsoleDriver {
+	/// Instantiates a new VirtioConsoleDriver struct, by mapping the device specific
+	/// configuration and moving the configuration structures of the transport into the struct.
+	pub fn new(mut transport: impl Transport) -> Result<Self, VirtioConsoleError> {
+		let dev_id = transport.dev_id();
+		let irq = transport.irq();
+
+		let Some(dev_cfg) = transport.map_dev_cfg::<Config>() else {
+			error!("No dev config. Aborting!");
+			return Err(VirtioConsoleError::NoDevCfg(dev_id));
+		};
+		let dev_cfg = ConsoleDevCfg {
+			raw: VolatileRef::from_ref(dev_cfg),
+			dev_id,
+			features: virtio::console::F::empty(),
+		};
+
+		let (com_cfg, notif_cfg, isr_stat) = transport.into_cfgs();
+
+		Ok(VirtioConsoleDriver {
+			dev_cfg,
+			com_cfg,
+			isr_stat,
+			notif_cfg,
+			irq,
+			recv_vq: RxQueue::new(),
+			send_vq: TxQueue::new(),
+		})
+	}
+
+	/// Initializes virtio console device
+	///
+	/// Returns a driver instance of VirtioConsoleDriver.
+	pub(crate) fn init(transport: impl Transport) -> Result<VirtioConsoleDriver, VirtioError> {
+		let mut drv = VirtioConsoleDriver::new(transport).map_err(|console_err| {
+			error!("Initializing new virtio console device driver failed. Aborting!");
+			VirtioError::ConsoleDriver(console_err)
+		})?;
+
+		match drv.init_dev() {
+			Ok(()) => {
+				info!(
+					"Console device with id {:x}, has been initialized by driver!",
+					drv.dev_cfg.dev_id
+				);
+
+				Ok(drv)
+			}
+			Err(console_err) => {
+				drv.set_failed();
+				Err(VirtioError::ConsoleDriver(console_err))
+			}
+		}
+	}
+}
+
 impl VirtioConsoleDriver {
 	pub fn has_packet(&self) -> bool {
 		self.recv_vq.has_packet()
@@ -281,7 +327,6 @@ impl VirtioConsoleDriver {
 		self.isr_stat.acknowledge();
 	}
 
-	#[cfg(feature = "pci")]
 	pub fn set_failed(&mut self) {
 		self.com_cfg.set_failed();
 	}
@@ -324,7 +369,10 @@ impl VirtioConsoleDriver {
 		// Indicate device, that driver is able to handle it
 		self.com_cfg.set_drv();
 
-		let features = virtio::console::F::VERSION_1;
+		// Event index based notification suppression is used whenever the device offers it.
+		let device_features = virtio::console::F::from(self.com_cfg.dev_features());
+		let features =
+			virtio::console::F::VERSION_1 | (device_features & virtio::console::F::EVENT_IDX);
 		self.negotiate_features(features)?;
 
 		// Indicates the device, that the current feature set is final for the driver
@@ -409,7 +457,6 @@ pub mod error {
 	/// Virtio console device error enum.
 	#[derive(Debug, Copy, Clone)]
 	pub enum VirtioConsoleError {
-		#[cfg(feature = "pci")]
 		NoDevCfg(u16),
 		/// The device did not acknowledge the negotiated feature set.
 		FailFeatureNeg(u16),
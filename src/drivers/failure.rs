@@ -0,0 +1,83 @@
+//! Drivers, which failed to initialize their device.
+//!
+//! Missing or broken optional devices are not fatal. Instead, the failure is
+//! recorded, so that the application can query it and decide, whether it runs
+//! without the device or exits.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::errno::Errno;
+
+/// Class of the device, whose driver failed
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub(crate) enum DeviceClass {
+	Network = 1,
+	Block = 2,
+	Console = 3,
+	Vsock = 4,
+	FileSystem = 5,
+}
+
+/// Maximum length of the reason including the terminating null byte
+const REASON_LEN: usize = 96;
+
+/// Failure of a driver, as returned to the application
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct DriverFailure {
+	/// See [`DeviceClass`]
+	pub class: u32,
+	/// Device ID of the bus, e.g., the PCI device ID
+	pub device_id: u32,
+	/// Error number, which describes the failure
+	pub errno: i32,
+	/// Human-readable reason, which is null-terminated
+	pub reason: [u8; REASON_LEN],
+}
+
+struct Failure {
+	class: DeviceClass,
+	device_id: u16,
+	errno: Errno,
+	reason: String,
+}
+
+static FAILURES: InterruptTicketMutex<Vec<Failure>> = InterruptTicketMutex::new(Vec::new());
+
+/// Records, that the driver of the device `device_id` could not be initialized.
+#[allow(dead_code)]
+pub(crate) fn record(class: DeviceClass, device_id: u16, errno: Errno, reason: impl fmt::Display) {
+	let reason = reason.to_string();
+	error!("{class:?} device {device_id:#x} is not available: {reason}");
+	FAILURES.lock().push(Failure {
+		class,
+		device_id,
+		errno,
+		reason,
+	});
+}
+
+/// Copies the recorded failures into `buf` and returns the number of all
+/// recorded failures, which may be larger than `buf`.
+pub(crate) fn failures(buf: &mut [DriverFailure]) -> usize {
+	let failures = FAILURES.lock();
+	for (dst, failure) in buf.iter_mut().zip(failures.iter()) {
+		let mut reason = [0u8; REASON_LEN];
+		let len = failure.reason.len().min(REASON_LEN - 1);
+		reason[..len].copy_from_slice(&failure.reason.as_bytes()[..len]);
+
+		*dst = DriverFailure {
+			class: failure.class as u32,
+			device_id: failure.device_id.into(),
+			errno: failure.errno.into(),
+			reason,
+		};
+	}
+	failures.len()
+}
@@ -1,4 +1,4 @@
-#[cfg(feature = "pci")]
+#[cfg(feature = "virtio-9p")]
+pub mod virtio_9p;
+#[cfg(feature = "fuse")]
 pub mod virtio_fs;
-#[cfg(feature = "pci")]
-pub mod virtio_pci;
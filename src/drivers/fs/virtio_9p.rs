@@ -0,0 +1,249 @@
+//! Driver for virtio 9P transport devices.
+//!
+//! A device exports a directory of the host, which is identified by its mount
+//! tag. Requests are 9P messages, which are sent through a single virtqueue.
+//! The protocol itself is implemented by [`crate::fs::p9`].
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::{mem, ptr};
+
+use hermit_sync::InterruptTicketMutex;
+use smallvec::SmallVec;
+use virtio::le16;
+
+use crate::config::VIRTIO_MAX_QUEUE_SIZE;
+use crate::drivers::virtio::error::{Virtio9pError, VirtioError};
+use crate::drivers::virtio::transport::{ComCfg, IsrStatus, NotifCfg, Transport};
+use crate::drivers::virtio::virtqueue::error::VirtqError;
+use crate::drivers::virtio::virtqueue::split::SplitVq;
+use crate::drivers::virtio::virtqueue::{
+	AvailBufferToken, BufferElem, BufferType, VirtQueue, Virtq, VqIndex, VqSize,
+};
+use crate::drivers::{Driver, InterruptLine};
+use crate::mm::device_alloc::DeviceAlloc;
+
+/// The mount tag is available in the device configuration.
+const VIRTIO_9P_MOUNT_TAG: u128 = 1 << 0;
+
+/// Device configuration of a 9P device.
+///
+/// The header is followed by `tag_len` bytes of the mount tag, which is not
+/// null-terminated.
+#[repr(C)]
+struct Config {
+	tag_len: le16,
+}
+
+/// Virtio 9P driver struct.
+#[allow(dead_code)]
+pub(crate) struct Virtio9pDriver {
+	dev_id: u16,
+	features: virtio::F,
+	tag: String,
+	com_cfg: ComCfg,
+	isr_stat: IsrStatus,
+	notif_cfg: NotifCfg,
+	vqueues: Vec<VirtQueue>,
+	irq: InterruptLine,
+}
+
+impl Virtio9pDriver {
+	/// Instantiates a new [`Virtio9pDriver`] struct, by reading the mount tag
+	/// and moving the configuration structures of the transport into the struct.
+	pub fn new(mut transport: impl Transport) -> Result<Self, Virtio9pError> {
+		let dev_id = transport.dev_id();
+		let irq = transport.irq();
+
+		let Some(dev_cfg) = transport.map_dev_cfg::<Config>() else {
+			error!("No dev config. Aborting!");
+			return Err(Virtio9pError::NoDevCfg(dev_id));
+		};
+
+		let tag = unsafe {
+			let tag_len = usize::from(ptr::addr_of!(dev_cfg.tag_len).read_volatile().to_ne());
+			let tag_ptr = ptr::from_ref(dev_cfg)
+				.cast::<u8>()
+				.add(mem::size_of::<Config>());
+			(0..tag_len)
+				.map(|i| tag_ptr.add(i).read_volatile())
+				.collect::<Vec<_>>()
+		};
+		let tag = String::from_utf8_lossy(&tag).into_owned();
+
+		let (com_cfg, notif_cfg, isr_stat) = transport.into_cfgs();
+
+		Ok(Virtio9pDriver {
+			dev_id,
+			features: virtio::F::empty(),
+			tag,
+			com_cfg,
+			isr_stat,
+			notif_cfg,
+			vqueues: Vec::new(),
+			irq,
+		})
+	}
+
+	/// Initializes virtio 9P device
+	pub fn init(transport: impl Transport) -> Result<Virtio9pDriver, VirtioError> {
+		let mut drv = Virtio9pDriver::new(transport).map_err(|p9_err| {
+			error!("Initializing new 9P driver failed. Aborting!");
+			VirtioError::NinePDriver(p9_err)
+		})?;
+
+		match drv.init_dev() {
+			Ok(()) => info!(
+				"9P device with id {:x} and mount tag {}, has been initialized by driver!",
+				drv.dev_id, drv.tag
+			),
+			Err(p9_err) => {
+				drv.com_cfg.set_failed();
+				return Err(VirtioError::NinePDriver(p9_err));
+			}
+		}
+
+		Ok(drv)
+	}
+
+	/// Initializes the device in adherence to specification.
+	///
+	/// See Virtio specification v1.1. - 3.1.1.
+	fn init_dev(&mut self) -> Result<(), Virtio9pError> {
+		// Reset
+		self.com_cfg.reset_dev();
+
+		// Indicate device, that OS noticed it
+		self.com_cfg.ack_dev();
+
+		// Indicate device, that driver is able to handle it
+		self.com_cfg.set_drv();
+
+		let device_features = self.com_cfg.dev_features();
+		let mount_tag = virtio::F::from_bits_retain(VIRTIO_9P_MOUNT_TAG.into());
+		if !device_features.contains(mount_tag) {
+			return Err(Virtio9pError::NoMountTag(self.dev_id));
+		}
+		let features = virtio::F::VERSION_1 | mount_tag;
+		if !device_features.contains(features) {
+			return Err(Virtio9pError::IncompatibleFeatureSets(
+				features,
+				device_features,
+			));
+		}
+		self.com_cfg.set_drv_features(features);
+
+		// Indicates the device, that the current feature set is final for the driver
+		// and will not be changed.
+		self.com_cfg.features_ok();
+
+		// Checks if the device has accepted final set. This finishes feature negotiation.
+		if self.com_cfg.check_features() {
+			self.features = features;
+		} else {
+			return Err(Virtio9pError::FailFeatureNeg(self.dev_id));
+		}
+
+		// A single queue for requests
+		let vq = VirtQueue::Split(
+			SplitVq::new(
+				&mut self.com_cfg,
+				&self.notif_cfg,
+				VqSize::from(VIRTIO_MAX_QUEUE_SIZE),
+				VqIndex::from(0u16),
+				self.features,
+			)
+			.unwrap(),
+		);
+		self.vqueues.push(vq);
+
+		// At this point the device is "live"
+		self.com_cfg.drv_ok();
+
+		Ok(())
+	}
+
+	/// Returns the mount tag of the device.
+	pub fn tag(&self) -> &str {
+		&self.tag
+	}
+
+	/// Sends the 9P message `request` to the device and returns its response,
+	/// which may be up to `rsp_len` bytes long.
+	pub fn request(
+		&mut self,
+		request: Vec<u8, DeviceAlloc>,
+		rsp_len: u32,
+	) -> Result<Vec<u8, DeviceAlloc>, VirtqError> {
+		let mut send = SmallVec::new();
+		send.push(BufferElem::Vector(request));
+		let mut recv = SmallVec::new();
+		recv.push(BufferElem::Vector(Vec::with_capacity_in(
+			rsp_len.try_into().unwrap(),
+			DeviceAlloc,
+		)));
+
+		let buffer_tkn = AvailBufferToken::new(send, recv).unwrap();
+		let mut transfer_result =
+			self.vqueues[0].dispatch_blocking(buffer_tkn, BufferType::Direct)?;
+		transfer_result
+			.used_recv_buff
+			.pop_front_vec()
+			.ok_or(VirtqError::IncompleteWrite)
+	}
+}
+
+impl Driver for Virtio9pDriver {
+	fn get_interrupt_number(&self) -> InterruptLine {
+		self.irq
+	}
+
+	fn get_name(&self) -> &'static str {
+		"virtio"
+	}
+}
+
+/// Initialized 9P devices
+///
+/// The drivers are never removed, so references to them remain valid.
+static DRIVERS: InterruptTicketMutex<Vec<&'static InterruptTicketMutex<Virtio9pDriver>>> =
+	InterruptTicketMutex::new(Vec::new());
+
+pub(crate) fn register_driver(drv: Virtio9pDriver) {
+	DRIVERS
+		.lock()
+		.push(Box::leak(Box::new(InterruptTicketMutex::new(drv))));
+}
+
+/// Returns the driver of the device with the mount tag `tag`.
+pub(crate) fn get_driver(tag: &str) -> Option<&'static InterruptTicketMutex<Virtio9pDriver>> {
+	DRIVERS
+		.lock()
+		.iter()
+		.find(|drv| drv.lock().tag() == tag)
+		.copied()
+}
+
+/// Returns the mount tags of all devices.
+pub(crate) fn tags() -> Vec<String> {
+	DRIVERS
+		.lock()
+		.iter()
+		.map(|drv| drv.lock().tag().into())
+		.collect()
+}
+
+/// Error module of the virtio 9P driver.
+pub mod error {
+	#[derive(Debug, Copy, Clone)]
+	pub enum Virtio9pError {
+		NoDevCfg(u16),
+		/// The device does not provide a mount tag.
+		NoMountTag(u16),
+		FailFeatureNeg(u16),
+		/// The first field contains the feature bits wanted by the driver.
+		/// but which are incompatible with the device feature set, second field.
+		IncompatibleFeatureSets(virtio::F, virtio::F),
+	}
+}
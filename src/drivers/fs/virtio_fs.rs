@@ -1,12 +1,14 @@
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
-use core::mem::MaybeUninit;
+use core::any::Any;
+use core::mem::{self, MaybeUninit};
 use core::str;
+use core::task::Waker;
 
-use fuse_abi::linux::fuse_out_header;
+use fuse_abi::linux::{fuse_in_header, fuse_out_header};
 use num_enum::TryFromPrimitive;
-use pci_types::InterruptLine;
 use smallvec::SmallVec;
 use virtio::FeatureBits;
 use virtio::fs::ConfigVolatileFieldAccess;
@@ -14,21 +16,23 @@ use volatile::VolatileRef;
 use volatile::access::ReadOnly;
 
 use crate::config::VIRTIO_MAX_QUEUE_SIZE;
-use crate::drivers::Driver;
-use crate::drivers::virtio::error::VirtioFsError;
-#[cfg(not(feature = "pci"))]
-use crate::drivers::virtio::transport::mmio::{ComCfg, IsrStatus, NotifCfg};
-#[cfg(feature = "pci")]
-use crate::drivers::virtio::transport::pci::{ComCfg, IsrStatus, NotifCfg};
+use crate::drivers::virtio::error::{VirtioError, VirtioFsError};
+use crate::drivers::virtio::transport::{ComCfg, IsrStatus, NotifCfg, ShmRegion, Transport};
 use crate::drivers::virtio::virtqueue::error::VirtqError;
 use crate::drivers::virtio::virtqueue::split::SplitVq;
 use crate::drivers::virtio::virtqueue::{
-	AvailBufferToken, BufferElem, BufferType, VirtQueue, Virtq, VqIndex, VqSize,
+	AvailBufferToken, BufferElem, BufferType, UsedBufferToken, VirtQueue, Virtq, VqIndex, VqSize,
 };
+use crate::drivers::{Driver, InterruptLine};
 use crate::errno::Errno;
+use crate::executor::WakerRegistration;
 use crate::fs::fuse::{self, FuseError, FuseInterface, Rsp, RspHeader};
 use crate::mm::device_alloc::DeviceAlloc;
 
+/// Id of the shared memory region, which is used as DAX window.
+/// See Virtio specification v1.2. - 5.11.6.1
+const VIRTIO_FS_SHMCAP_ID_CACHE: u8 = 0;
+
 /// A wrapper struct for the raw configuration structure.
 /// Handling the right access to fields, as some are read-only
 /// for the driver.
@@ -38,6 +42,33 @@ pub(crate) struct FsDevCfg {
 	pub features: virtio::fs::F,
 }
 
+/// Request, which has been submitted to the device
+enum Request {
+	/// The response is outstanding, the waker is woken on its arrival.
+	Pending(WakerRegistration),
+	/// The response has been received, before it was collected.
+	Completed(UsedBufferToken),
+	/// The waiting future has been dropped, the response is discarded.
+	Abandoned,
+}
+
+/// Maximum number of descriptors of a request
+///
+/// The request and the response consist of a header and a payload each.
+const DESCRIPTORS_PER_REQUEST: u16 = 4;
+
+/// Returns the `unique` field of the request, whose buffers are `tkn`.
+fn unique_of(tkn: &UsedBufferToken) -> Option<u64> {
+	let BufferElem::Sized(headers) = tkn.send_buff.first()? else {
+		return None;
+	};
+	let headers: *const (dyn Any + Send) = &**headers;
+	// SAFETY: The first buffer of a request is a `CmdHeader`, which starts
+	// with the initialized `fuse_in_header`.
+	let in_header = unsafe { &*headers.cast::<fuse_in_header>() };
+	Some(in_header.unique)
+}
+
 /// Virtio file system driver struct.
 ///
 /// Struct allows to control devices virtqueues as also
@@ -50,20 +81,133 @@ pub(crate) struct VirtioFsDriver {
 	pub(super) notif_cfg: NotifCfg,
 	pub(super) vqueues: Vec<VirtQueue>,
 	pub(super) irq: InterruptLine,
+	/// Window, into which the device maps extents of files
+	pub(super) dax_window: Option<ShmRegion>,
+	/// Submitted requests by their `unique` field
+	requests: BTreeMap<u64, Request>,
+	/// Number of outstanding requests of each queue
+	outstanding: Vec<u16>,
+	next_unique: u64,
 }
 
-// Backend-independent interface for Virtio network driver
+// Backend-independent interface for Virtio filesystem driver
 impl VirtioFsDriver {
-	#[cfg(feature = "pci")]
+	/// Instantiates a new (VirtioFsDriver)[VirtioFsDriver] struct, by mapping the device
+	/// specific configuration and moving the configuration structures of the transport into the struct.
+	pub fn new(mut transport: impl Transport) -> Result<Self, VirtioFsError> {
+		let dev_id = transport.dev_id();
+		let irq = transport.irq();
+
+		let Some(dev_cfg) = transport.map_dev_cfg::<virtio::fs::Config>() else {
+			error!("No dev config. Aborting!");
+			return Err(VirtioFsError::NoDevCfg(dev_id));
+		};
+		let dev_cfg = FsDevCfg {
+			raw: VolatileRef::from_ref(dev_cfg),
+			dev_id,
+			features: virtio::fs::F::empty(),
+		};
+
+		let dax_window = transport.shm_region(VIRTIO_FS_SHMCAP_ID_CACHE);
+		if let Some(window) = dax_window {
+			info!(
+				"Filesystem device {dev_id:x} provides a DAX window of {:#x} bytes",
+				window.len
+			);
+		}
+
+		let (com_cfg, notif_cfg, isr_stat) = transport.into_cfgs();
+
+		Ok(VirtioFsDriver {
+			dev_cfg,
+			com_cfg,
+			isr_stat,
+			notif_cfg,
+			vqueues: Vec::new(),
+			irq,
+			dax_window,
+			requests: BTreeMap::new(),
+			outstanding: Vec::new(),
+			next_unique: 1,
+		})
+	}
+
+	/// Initializes virtio filesystem device
+	pub fn init(transport: impl Transport) -> Result<VirtioFsDriver, VirtioError> {
+		let mut drv = VirtioFsDriver::new(transport).map_err(|fs_err| {
+			error!("Initializing new filesystem driver failed. Aborting!");
+			VirtioError::FsDriver(fs_err)
+		})?;
+
+		match drv.init_dev() {
+			Ok(()) => info!(
+				"Filesystem device with id {:x}, has been initialized by driver!",
+				drv.get_dev_id()
+			),
+			Err(fs_err) => {
+				drv.set_failed();
+				return Err(VirtioError::FsDriver(fs_err));
+			}
+		}
+
+		Ok(drv)
+	}
+
 	pub fn get_dev_id(&self) -> u16 {
 		self.dev_cfg.dev_id
 	}
 
-	#[cfg(feature = "pci")]
 	pub fn set_failed(&mut self) {
 		self.com_cfg.set_failed();
 	}
 
+	/// Wakes the tasks, which wait for the responses of their requests.
+	pub fn handle_interrupt(&mut self) {
+		let _status = self.isr_stat.is_queue_interrupt();
+
+		self.collect();
+		self.isr_stat.acknowledge();
+	}
+
+	/// Assigns the used buffers of all request queues to their requests.
+	///
+	/// The device may complete the requests in any order.
+	fn collect(&mut self) {
+		for index in 1..self.vqueues.len() {
+			while let Ok(tkn) = self.vqueues[index].try_recv() {
+				self.outstanding[index] -= 1;
+
+				let Some(unique) = unique_of(&tkn) else {
+					continue;
+				};
+				match self.requests.get_mut(&unique) {
+					Some(request @ Request::Pending(_)) => {
+						if let Request::Pending(mut waker) =
+							mem::replace(request, Request::Completed(tkn))
+						{
+							waker.wake();
+						}
+					}
+					Some(Request::Abandoned) => {
+						self.requests.remove(&unique);
+					}
+					_ => warn!("Received a response to the unknown FUSE request {unique}"),
+				}
+			}
+		}
+	}
+
+	/// Returns the request queue with the fewest outstanding requests, which
+	/// has room for another request.
+	fn free_queue(&self) -> Option<usize> {
+		(1..self.vqueues.len())
+			.filter(|&index| {
+				self.outstanding[index]
+					< u16::from(self.vqueues[index].size()) / DESCRIPTORS_PER_REQUEST
+			})
+			.min_by_key(|&index| self.outstanding[index])
+	}
+
 	/// Negotiates a subset of features, understood and wanted by both the OS
 	/// and the device.
 	fn negotiate_features(&mut self, driver_features: virtio::fs::F) -> Result<(), VirtioFsError> {
@@ -104,7 +248,9 @@ impl VirtioFsDriver {
 		// Indicate device, that driver is able to handle it
 		self.com_cfg.set_drv();
 
-		let features = virtio::fs::F::VERSION_1;
+		// Event index based notification suppression is used whenever the device offers it.
+		let device_features = virtio::fs::F::from(self.com_cfg.dev_features());
+		let features = virtio::fs::F::VERSION_1 | (device_features & virtio::fs::F::EVENT_IDX);
 		self.negotiate_features(features)?;
 
 		// Indicates the device, that the current feature set is final for the driver
@@ -149,6 +295,7 @@ impl VirtioFsDriver {
 				.unwrap(),
 			);
 			self.vqueues.push(vq);
+			self.outstanding.push(0);
 		}
 
 		// At this point the device is "live"
@@ -158,84 +305,146 @@ impl VirtioFsDriver {
 	}
 }
 
+/// Creates the buffers of the request `cmd` and of its response.
+fn buffer_token<O: fuse::ops::Op + 'static>(
+	cmd: fuse::Cmd<O>,
+	rsp_payload_len: u32,
+) -> AvailBufferToken
+where
+	<O as fuse::ops::Op>::InStruct: Send,
+	<O as fuse::ops::Op>::OutStruct: Send,
+{
+	let fuse::Cmd {
+		headers: cmd_headers,
+		payload: cmd_payload_opt,
+	} = cmd;
+	let send = if let Some(cmd_payload) = cmd_payload_opt {
+		SmallVec::from_buf([
+			BufferElem::Sized(cmd_headers),
+			BufferElem::Vector(cmd_payload),
+		])
+	} else {
+		let mut vec = SmallVec::new();
+		vec.push(BufferElem::Sized(cmd_headers));
+		vec
+	};
+
+	// If the operation fails, it is possible for its header to be uninitialized.
+	// For this reason, we use a instantiation of the RspHeader structure where
+	// the op_header field is MaybeUninit
+	let rsp_headers = Box::<RspHeader<O, MaybeUninit<O::OutStruct>>, _>::new_uninit_in(DeviceAlloc);
+	let recv = if rsp_payload_len == 0 {
+		let mut vec = SmallVec::new();
+		vec.push(BufferElem::Sized(rsp_headers));
+		vec
+	} else {
+		SmallVec::from_buf([
+			BufferElem::Sized(rsp_headers),
+			BufferElem::Vector(Vec::with_capacity_in(rsp_payload_len as usize, DeviceAlloc)),
+		])
+	};
+
+	AvailBufferToken::new(send, recv).unwrap()
+}
+
+/// Extracts the response from the buffers, which have been used by the device.
+fn decode<O: fuse::ops::Op + 'static>(
+	mut transfer_result: UsedBufferToken,
+) -> Result<fuse::Rsp<O>, FuseError> {
+	let (dyn_headers, written_header_len) = transfer_result.used_recv_buff.pop_front_raw().unwrap();
+	let headers = dyn_headers
+		.downcast::<MaybeUninit<RspHeader<O, MaybeUninit<O::OutStruct>>>>()
+		.unwrap();
+	if written_header_len < size_of::<fuse_out_header>() {
+		return Err(VirtqError::IncompleteWrite.into());
+	}
+
+	// SAFETY: we confirmed that the out_header was written. The op_header does not need to be initialized at this stage,
+	// as it is behind a nested MaybeUninit.
+	let headers = unsafe { headers.assume_init() };
+
+	if headers.out_header.error != 0
+		|| (written_header_len - size_of::<fuse_out_header>()) != size_of::<O::OutStruct>()
+	{
+		// "However, if the reply is an error reply (i.e., error is set), then no further payload data should be sent,
+		// independent of the request." (fuse man page)
+
+		return Err(FuseError::IOError(
+			Errno::try_from_primitive(-headers.out_header.error).unwrap_or(Errno::Io),
+		));
+	}
+
+	// SAFETY: the conditional above ensures that the second field was filled in, so we can transmute it from MaybeUninit to normal.
+	let headers = unsafe {
+		core::mem::transmute::<Box<RspHeader<O, MaybeUninit<O::OutStruct>>, _>, Box<RspHeader<O>, _>>(
+			headers,
+		)
+	};
+	let payload = transfer_result.used_recv_buff.pop_front_vec();
+	Ok(Rsp { headers, payload })
+}
+
 impl FuseInterface for VirtioFsDriver {
-	fn send_command<O: fuse::ops::Op + 'static>(
+	fn is_full(&mut self) -> bool {
+		self.collect();
+		self.free_queue().is_none()
+	}
+
+	fn submit<O: fuse::ops::Op + 'static>(
 		&mut self,
-		cmd: fuse::Cmd<O>,
+		mut cmd: fuse::Cmd<O>,
 		rsp_payload_len: u32,
-	) -> Result<fuse::Rsp<O>, FuseError>
+	) -> Result<u64, FuseError>
 	where
 		<O as fuse::ops::Op>::InStruct: Send,
 		<O as fuse::ops::Op>::OutStruct: Send,
 	{
-		let fuse::Cmd {
-			headers: cmd_headers,
-			payload: cmd_payload_opt,
-		} = cmd;
-		let send = if let Some(cmd_payload) = cmd_payload_opt {
-			SmallVec::from_buf([
-				BufferElem::Sized(cmd_headers),
-				BufferElem::Vector(cmd_payload),
-			])
-		} else {
-			let mut vec = SmallVec::new();
-			vec.push(BufferElem::Sized(cmd_headers));
-			vec
-		};
-
-		// If the operation fails, it is possible for its header to be uninitialized.
-		// For this reason, we use a instantiation of the RspHeader structure where
-		// the op_header field is MaybeUninit
-		let rsp_headers =
-			Box::<RspHeader<O, MaybeUninit<O::OutStruct>>, _>::new_uninit_in(DeviceAlloc);
-		let recv = if rsp_payload_len == 0 {
-			let mut vec = SmallVec::new();
-			vec.push(BufferElem::Sized(rsp_headers));
-			vec
-		} else {
-			SmallVec::from_buf([
-				BufferElem::Sized(rsp_headers),
-				BufferElem::Vector(Vec::with_capacity_in(rsp_payload_len as usize, DeviceAlloc)),
-			])
-		};
-
-		let buffer_tkn = AvailBufferToken::new(send, recv).unwrap();
-		let mut transfer_result =
-			self.vqueues[1].dispatch_blocking(buffer_tkn, BufferType::Direct)?;
-
-		let (dyn_headers, written_header_len) =
-			transfer_result.used_recv_buff.pop_front_raw().unwrap();
-		let headers = dyn_headers
-			.downcast::<MaybeUninit<RspHeader<O, MaybeUninit<O::OutStruct>>>>()
-			.unwrap();
-		if written_header_len < size_of::<fuse_out_header>() {
-			return Err(VirtqError::IncompleteWrite.into());
-		}
+		let index = self.free_queue().ok_or(FuseError::IOError(Errno::Busy))?;
+		let unique = self.next_unique;
+		self.next_unique += 1;
+		cmd.headers.in_header.unique = unique;
 
-		// SAFETY: we confirmed that the out_header was written. The op_header does not need to be initialized at this stage,
-		// as it is behind a nested MaybeUninit.
-		let headers = unsafe { headers.assume_init() };
+		let buffer_tkn = buffer_token(cmd, rsp_payload_len);
+		self.vqueues[index].dispatch(buffer_tkn, true, BufferType::Direct)?;
+		self.outstanding[index] += 1;
+		self.requests
+			.insert(unique, Request::Pending(WakerRegistration::new()));
+		Ok(unique)
+	}
 
-		if headers.out_header.error != 0
-			|| (written_header_len - size_of::<fuse_out_header>()) != size_of::<O::OutStruct>()
-		{
-			// "However, if the reply is an error reply (i.e., error is set), then no further payload data should be sent,
-			// independent of the request." (fuse man page)
+	fn try_complete<O: fuse::ops::Op + 'static>(
+		&mut self,
+		unique: u64,
+		waker: Option<&Waker>,
+	) -> Option<Result<fuse::Rsp<O>, FuseError>> {
+		self.collect();
 
-			return Err(FuseError::IOError(
-				Errno::try_from_primitive(-headers.out_header.error).unwrap_or(Errno::Io),
-			));
+		match self.requests.get_mut(&unique) {
+			Some(Request::Pending(registration)) => {
+				// The interrupt handler cannot run, while the driver is locked.
+				if let Some(waker) = waker {
+					registration.register(waker);
+				}
+				None
+			}
+			Some(Request::Completed(_)) => {
+				let Some(Request::Completed(tkn)) = self.requests.remove(&unique) else {
+					unreachable!()
+				};
+				Some(decode(tkn))
+			}
+			_ => Some(Err(FuseError::IOError(Errno::Inval))),
 		}
+	}
 
-		// SAFETY: the conditional above ensures that the second field was filled in, so we can transmute it from MaybeUninit to normal.
-		let headers = unsafe {
-			core::mem::transmute::<
-				Box<RspHeader<O, MaybeUninit<O::OutStruct>>, _>,
-				Box<RspHeader<O>, _>,
-			>(headers)
-		};
-		let payload = transfer_result.used_recv_buff.pop_front_vec();
-		Ok(Rsp { headers, payload })
+	fn abandon(&mut self, unique: u64) {
+		match self.requests.get_mut(&unique) {
+			Some(request @ Request::Pending(_)) => *request = Request::Abandoned,
+			Some(Request::Completed(_)) => {
+				self.requests.remove(&unique);
+			}
+			_ => {}
+		}
 	}
 
 	fn get_mount_point(&self) -> String {
@@ -244,6 +453,10 @@ impl FuseInterface for VirtioFsDriver {
 		let tag = tag.split('\0').next().unwrap();
 		tag.to_string()
 	}
+
+	fn get_dax_window(&self) -> Option<ShmRegion> {
+		self.dax_window
+	}
 }
 
 impl Driver for VirtioFsDriver {
@@ -261,7 +474,6 @@ pub mod error {
 	/// Network filesystem error enum.
 	#[derive(Debug, Copy, Clone)]
 	pub enum VirtioFsError {
-		#[cfg(feature = "pci")]
 		NoDevCfg(u16),
 		FailFeatureNeg(u16),
 		/// The first field contains the feature bits wanted by the driver.
@@ -0,0 +1,143 @@
+//! Affinity of the interrupt lines of the devices.
+//!
+//! At boot, the lines of the devices are distributed round-robin among the
+//! cores given by `irqaffinity=<cores>`, e.g., `irqaffinity=0-3,6`, or among
+//! all cores, starting with the second one, so that the interrupts of the
+//! network and the storage do not queue up on the boot core.
+//!
+//! The lines of the network cards are pinned to the boot core, because its
+//! executor runs the network stack. With `housekeeping=dedicated`, all lines
+//! remain on the housekeeping core. `sys_irq_set_affinity` moves a line to
+//! another core.
+//!
+//! Currently, only the IO-APIC of x86-64 routes lines to other cores.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use hermit_sync::InterruptSpinMutex;
+
+use crate::arch::get_processor_count;
+use crate::arch::kernel::interrupts::set_irq_affinity;
+use crate::env;
+use crate::errno::Errno;
+use crate::scheduler::{CoreId, HOUSEKEEPING_CORE};
+
+/// Lines, which remain on the boot core
+static PINNED: InterruptSpinMutex<Vec<u8>> = InterruptSpinMutex::new(Vec::new());
+
+/// Cores of the lines, which have been routed
+static AFFINITY: InterruptSpinMutex<BTreeMap<u8, CoreId>> =
+	InterruptSpinMutex::new(BTreeMap::new());
+
+/// Keeps the line `line` on the boot core, when the lines are distributed.
+#[allow(dead_code)]
+pub(crate) fn pin(line: u8) {
+	let mut pinned = PINNED.lock();
+	if !pinned.contains(&line) {
+		pinned.push(line);
+	}
+}
+
+/// Parses a list of cores like `0-3,6`.
+///
+/// The ranges are clamped to the `processor_count` cores, so that a range like
+/// `0-4294967295` does not allocate a list of all possible IDs.
+fn parse_cores(list: &str, processor_count: u32) -> Option<Vec<CoreId>> {
+	let mut cores = Vec::new();
+	for range in list.split(',') {
+		let (start, end) = range.split_once('-').unwrap_or((range, range));
+		let (start, end) = (start.parse::<CoreId>().ok()?, end.parse::<CoreId>().ok()?);
+		if start > end {
+			return None;
+		}
+		cores.extend(start..end.saturating_add(1).min(processor_count));
+	}
+	Some(cores)
+}
+
+/// Returns the cores, among which the lines are distributed.
+fn cores() -> Vec<CoreId> {
+	if env::dedicated_housekeeping() {
+		return vec![HOUSEKEEPING_CORE];
+	}
+
+	let processor_count = get_processor_count();
+	let Some(list) = env::irq_affinity() else {
+		// start with the second core, the boot core serves the network
+		return (1..processor_count).chain([0]).collect();
+	};
+
+	let Some(mut cores) = parse_cores(list, processor_count) else {
+		error!("could not parse bootarg: irqaffinity={list}");
+		return vec![0];
+	};
+	cores.sort_unstable();
+	cores.dedup();
+	if cores.is_empty() {
+		cores.push(0);
+	}
+	cores
+}
+
+/// Distributes the lines `lines` among the cores.
+///
+/// Called, when the interrupt handlers of the devices are installed.
+pub(crate) fn spread(lines: impl Iterator<Item = u8>) {
+	let pinned = PINNED.lock().clone();
+	let mut lines = lines
+		.filter(|line| !pinned.contains(line))
+		.collect::<Vec<_>>();
+	lines.sort_unstable();
+
+	let cores = cores();
+	let mut affinity = AFFINITY.lock();
+	for (line, core_id) in lines.into_iter().zip(cores.into_iter().cycle()) {
+		if core_id != 0 && set_irq_affinity(line, core_id) {
+			info!("Route interrupt line {line} to core {core_id}");
+			affinity.insert(line, core_id);
+		}
+	}
+}
+
+/// Routes the line `line` to the core `core_id`.
+pub(crate) fn set(line: u8, core_id: CoreId) -> Result<(), Errno> {
+	if core_id >= get_processor_count() {
+		return Err(Errno::Inval);
+	}
+	if !set_irq_affinity(line, core_id) {
+		return Err(Errno::Nosys);
+	}
+
+	AFFINITY.lock().insert(line, core_id);
+	Ok(())
+}
+
+/// Returns the core, to which the line `line` is routed.
+pub(crate) fn get(line: u8) -> CoreId {
+	AFFINITY.lock().get(&line).copied().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_parse_cores() {
+		assert_eq!(parse_cores("0-3,6", 8), Some(vec![0, 1, 2, 3, 6]));
+		assert_eq!(parse_cores("2", 8), Some(vec![2]));
+		assert_eq!(parse_cores("3-1", 8), None);
+		assert_eq!(parse_cores("0-", 8), None);
+		assert_eq!(parse_cores("", 8), None);
+		assert_eq!(parse_cores("a,1", 8), None);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_parse_cores_clamped() {
+		assert_eq!(parse_cores("0-4294967295", 4), Some(vec![0, 1, 2, 3]));
+		assert_eq!(parse_cores("2-6,9", 4), Some(vec![2, 3]));
+		assert_eq!(parse_cores("4294967295", 4), Some(vec![]));
+	}
+}
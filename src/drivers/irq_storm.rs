@@ -0,0 +1,153 @@
+//! Guard against interrupt storms.
+//!
+//! A device, which raises interrupts faster than the kernel handles them,
+//! starves the scheduler, because the core hardly leaves the interrupt
+//! handlers. Therefore, the interrupts of each line are counted in windows of
+//! [`WINDOW`] microseconds. A line, which raises more than [`THRESHOLD`]
+//! interrupts within a window, is masked and logged. The scheduler unmasks
+//! it again after a backoff. The backoff starts at [`MIN_BACKOFF`] and
+//! doubles up to [`MAX_BACKOFF`], if the line storms again shortly after it
+//! has been unmasked.
+//!
+//! The network stack polls its devices also without interrupts, so that a
+//! masked line delays the processing instead of losing it.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use hermit_sync::InterruptSpinMutex;
+
+use crate::arch::kernel::interrupts::set_irq_masked;
+use crate::arch::processor::get_timer_ticks;
+
+/// Length of a window in microseconds
+const WINDOW: u64 = 10_000;
+/// Maximum number of interrupts of a line within a window
+const THRESHOLD: u32 = 10_000;
+/// Backoff of the first storm in microseconds
+const MIN_BACKOFF: u64 = 1_000;
+/// Maximum backoff in microseconds
+const MAX_BACKOFF: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Line {
+	/// Start of the current window
+	window_start: u64,
+	/// Interrupts within the current window
+	count: u32,
+	/// Backoff of the last storm
+	backoff: u64,
+	/// Time, at which a masked line is unmasked
+	masked_until: Option<u64>,
+	/// Time, at which the line has been unmasked the last time
+	unmasked_at: u64,
+	/// Number of storms
+	storms: u64,
+}
+
+impl Line {
+	const fn new() -> Self {
+		Self {
+			window_start: 0,
+			count: 0,
+			backoff: 0,
+			masked_until: None,
+			unmasked_at: 0,
+			storms: 0,
+		}
+	}
+}
+
+static LINES: InterruptSpinMutex<[Line; 256]> =
+	InterruptSpinMutex::new([const { Line::new() }; 256]);
+
+/// Earliest time, at which a masked line is unmasked, or `u64::MAX`
+static NEXT_UNMASK: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Counts an interrupt of `line` after its handlers ran and masks the line,
+/// if it storms.
+///
+/// Returns `true`, if the line has been masked.
+pub(crate) fn count(line: u8) -> bool {
+	let now = get_timer_ticks();
+	let mut lines = LINES.lock();
+	let state = &mut lines[usize::from(line)];
+
+	if now.saturating_sub(state.window_start) >= WINDOW {
+		state.window_start = now;
+		state.count = 0;
+	}
+	state.count += 1;
+	if state.count <= THRESHOLD || state.masked_until.is_some() {
+		return false;
+	}
+
+	state.window_start = now;
+	state.count = 0;
+	state.storms += 1;
+	state.backoff = if state.storms > 1 && now.saturating_sub(state.unmasked_at) < MAX_BACKOFF {
+		(state.backoff * 2).clamp(MIN_BACKOFF, MAX_BACKOFF)
+	} else {
+		MIN_BACKOFF
+	};
+	let Line {
+		backoff, storms, ..
+	} = *state;
+
+	if !set_irq_masked(line, true) {
+		drop(lines);
+		if storms == 1 {
+			warn!("Interrupt storm on line {line}, which cannot be masked");
+		}
+		return false;
+	}
+
+	let until = now + backoff;
+	state.masked_until = Some(until);
+	NEXT_UNMASK.fetch_min(until, Ordering::Relaxed);
+	drop(lines);
+
+	warn!("Interrupt storm on line {line}: masked for {backoff} µs (storm {storms})");
+	true
+}
+
+/// Unmasks the lines, whose backoff has elapsed.
+///
+/// Called by the scheduler, when it handles the waiting tasks.
+pub(crate) fn unmask_expired() {
+	let now = get_timer_ticks();
+	if NEXT_UNMASK.load(Ordering::Relaxed) > now {
+		return;
+	}
+
+	let mut lines = LINES.lock();
+	let mut next = u64::MAX;
+	for (line, state) in lines.iter_mut().enumerate() {
+		let Some(until) = state.masked_until else {
+			continue;
+		};
+
+		if until <= now {
+			let line = u8::try_from(line).unwrap();
+			if set_irq_masked(line, false) {
+				debug!("Unmask interrupt line {line}");
+				state.masked_until = None;
+				state.unmasked_at = now;
+				state.window_start = now;
+				state.count = 0;
+				continue;
+			}
+
+			// The interrupt controller is busy, try again later.
+			state.masked_until = Some(now + MIN_BACKOFF);
+		}
+
+		next = next.min(state.masked_until.unwrap());
+	}
+	NEXT_UNMASK.store(next, Ordering::Relaxed);
+}
+
+/// Returns the earliest time, at which a masked line is unmasked.
+pub(crate) fn next_unmask() -> Option<u64> {
+	let next = NEXT_UNMASK.load(Ordering::Relaxed);
+	(next != u64::MAX).then_some(next)
+}
@@ -6,8 +6,14 @@ use hashbrown::HashMap;
 
 #[cfg(feature = "console")]
 pub(crate) use crate::arch::kernel::mmio::get_console_driver;
+#[cfg(feature = "fuse")]
+use crate::arch::kernel::mmio::get_filesystem_driver;
+#[cfg(feature = "vsock")]
+pub(crate) use crate::arch::kernel::mmio::get_vsock_driver;
 #[cfg(any(
 	feature = "console",
+	feature = "fuse",
+	feature = "vsock",
 	all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
 	feature = "virtio-net",
 ))]
@@ -17,7 +23,7 @@ use crate::drivers::{InterruptHandlerQueue, InterruptLine};
 	all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
 	feature = "virtio-net",
 ))]
-use crate::executor::device::NETWORK_DEVICE;
+use crate::executor::device::NETWORK_DEVICES;
 
 pub(crate) fn get_interrupt_handlers() -> HashMap<InterruptLine, InterruptHandlerQueue, RandomState>
 {
@@ -29,11 +35,17 @@ pub(crate) fn get_interrupt_handlers() -> HashMap<InterruptLine, InterruptHandle
 		all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
 		feature = "virtio-net",
 	))]
-	if let Some(device) = NETWORK_DEVICE.lock().as_ref() {
-		handlers
-			.entry(device.get_interrupt_number())
-			.or_default()
-			.push_back(crate::executor::network::network_handler);
+	for device in NETWORK_DEVICES.lock().iter() {
+		// The handler serves all network cards, it is registered once per line.
+		let queue = handlers.entry(device.get_interrupt_number()).or_default();
+		crate::drivers::irq_affinity::pin(device.get_interrupt_number());
+		let handler: fn() = crate::executor::network::network_handler;
+		if !queue
+			.iter()
+			.any(|registered| core::ptr::fn_addr_eq(*registered, handler))
+		{
+			queue.push_back(handler);
+		}
 	}
 
 	#[cfg(feature = "console")]
@@ -55,5 +67,42 @@ pub(crate) fn get_interrupt_handlers() -> HashMap<InterruptLine, InterruptHandle
 		}
 	}
 
+	#[cfg(feature = "fuse")]
+	if let Some(drv) = get_filesystem_driver() {
+		fn fuse_handler() {
+			if let Some(driver) = get_filesystem_driver() {
+				driver.lock().handle_interrupt();
+			}
+		}
+
+		let irq_number = drv.lock().get_interrupt_number();
+
+		handlers
+			.entry(irq_number)
+			.or_default()
+			.push_back(fuse_handler);
+	}
+
+	#[cfg(feature = "vsock")]
+	if let Some(drv) = get_vsock_driver() {
+		fn vsock_handler() {
+			if let Some(driver) = get_vsock_driver() {
+				driver.lock().handle_interrupt();
+			}
+		}
+
+		let irq_number = drv.lock().get_interrupt_number();
+
+		handlers
+			.entry(irq_number)
+			.or_default()
+			.push_back(vsock_handler);
+	}
+
+	#[cfg(all(target_arch = "x86_64", feature = "acpi"))]
+	if let Some((irq_number, handler)) = crate::arch::kernel::acpi::get_power_button_handler() {
+		handlers.entry(irq_number).or_default().push_back(handler);
+	}
+
 	handlers
 }
@@ -1,9 +1,16 @@
 //! A module containing hermit-rs driver, hermit-rs driver trait and driver specific errors.
 
+#[cfg(feature = "net")]
+pub(crate) mod binding;
+#[cfg(feature = "block")]
+pub mod block;
 #[cfg(feature = "console")]
 pub mod console;
-#[cfg(feature = "fuse")]
+pub(crate) mod failure;
+#[cfg(any(feature = "fuse", feature = "virtio-9p"))]
 pub mod fs;
+pub(crate) mod irq_affinity;
+pub(crate) mod irq_storm;
 #[cfg(not(feature = "pci"))]
 pub mod mmio;
 #[cfg(feature = "net")]
@@ -16,9 +23,11 @@ pub mod pci;
 	all(
 		not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
 		not(all(target_arch = "x86_64", feature = "rtl8139")),
+		not(feature = "e1000"),
 		feature = "virtio-net",
 	),
 	feature = "fuse",
+	feature = "virtio-9p",
 	feature = "vsock",
 	feature = "console",
 ))]
@@ -39,6 +48,11 @@ pub(crate) type InterruptHandlerQueue = VecDeque<fn()>;
 /// [DriverError](error::DriverError) values will be
 /// passed on to higher layers.
 pub mod error {
+	#[cfg(all(
+		feature = "e1000",
+		not(all(target_arch = "x86_64", feature = "rtl8139")),
+	))]
+	use crate::drivers::net::e1000::E1000Error;
 	#[cfg(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")))]
 	use crate::drivers::net::gem::GEMError;
 	#[cfg(all(target_arch = "x86_64", feature = "rtl8139"))]
@@ -47,9 +61,11 @@ pub mod error {
 		all(
 			not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
 			not(all(target_arch = "x86_64", feature = "rtl8139")),
+			not(feature = "e1000"),
 			feature = "virtio-net",
 		),
 		feature = "fuse",
+		feature = "virtio-9p",
 		feature = "vsock",
 		feature = "console",
 	))]
@@ -58,8 +74,10 @@ pub mod error {
 	#[cfg(any(
 		all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
 		all(target_arch = "x86_64", feature = "rtl8139"),
+		feature = "e1000",
 		feature = "virtio-net",
 		feature = "fuse",
+		feature = "virtio-9p",
 		feature = "vsock",
 		feature = "console",
 	))]
@@ -69,15 +87,22 @@ pub mod error {
 			all(
 				not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
 				not(all(target_arch = "x86_64", feature = "rtl8139")),
+				not(feature = "e1000"),
 				feature = "virtio-net",
 			),
 			feature = "fuse",
+			feature = "virtio-9p",
 			feature = "vsock",
 			feature = "console",
 		))]
 		InitVirtioDevFail(VirtioError),
 		#[cfg(all(target_arch = "x86_64", feature = "rtl8139"))]
 		InitRTL8139DevFail(RTL8139Error),
+		#[cfg(all(
+			feature = "e1000",
+			not(all(target_arch = "x86_64", feature = "rtl8139")),
+		))]
+		InitE1000DevFail(E1000Error),
 		#[cfg(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")))]
 		InitGEMDevFail(GEMError),
 	}
@@ -86,9 +111,11 @@ pub mod error {
 		all(
 			not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
 			not(all(target_arch = "x86_64", feature = "rtl8139")),
+			not(feature = "e1000"),
 			feature = "virtio-net",
 		),
 		feature = "fuse",
+		feature = "virtio-9p",
 		feature = "vsock",
 		feature = "console",
 	))]
@@ -105,6 +132,16 @@ pub mod error {
 		}
 	}
 
+	#[cfg(all(
+		feature = "e1000",
+		not(all(target_arch = "x86_64", feature = "rtl8139")),
+	))]
+	impl From<E1000Error> for DriverError {
+		fn from(err: E1000Error) -> Self {
+			DriverError::InitE1000DevFail(err)
+		}
+	}
+
 	#[cfg(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")))]
 	impl From<GEMError> for DriverError {
 		fn from(err: GEMError) -> Self {
@@ -115,8 +152,10 @@ pub mod error {
 	#[cfg(any(
 		all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
 		all(target_arch = "x86_64", feature = "rtl8139"),
+		feature = "e1000",
 		feature = "virtio-net",
 		feature = "fuse",
+		feature = "virtio-9p",
 		feature = "vsock",
 		feature = "console",
 	))]
@@ -132,9 +171,11 @@ pub mod error {
 							not(feature = "pci"),
 						)),
 						not(all(target_arch = "x86_64", feature = "rtl8139")),
+						not(feature = "e1000"),
 						feature = "virtio-net",
 					),
 					feature = "fuse",
+					feature = "virtio-9p",
 					feature = "vsock",
 					feature = "console",
 				))]
@@ -145,6 +186,13 @@ pub mod error {
 				DriverError::InitRTL8139DevFail(ref err) => {
 					write!(f, "RTL8139 driver failed: {err:?}")
 				}
+				#[cfg(all(
+					feature = "e1000",
+					not(all(target_arch = "x86_64", feature = "rtl8139")),
+				))]
+				DriverError::InitE1000DevFail(ref err) => {
+					write!(f, "e1000 driver failed: {err}")
+				}
 				#[cfg(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")))]
 				DriverError::InitGEMDevFail(ref err) => {
 					write!(f, "GEM driver failed: {err:?}")
@@ -173,12 +221,21 @@ pub(crate) fn init() {
 	#[cfg(all(
 		not(feature = "pci"),
 		target_arch = "aarch64",
-		any(feature = "console", feature = "virtio-net"),
+		any(
+			feature = "console",
+			feature = "virtio-net",
+			feature = "fuse",
+			feature = "virtio-9p",
+			feature = "vsock"
+		),
 	))]
 	crate::arch::aarch64::kernel::mmio::init_drivers();
 
 	#[cfg(target_arch = "riscv64")]
 	crate::arch::riscv64::kernel::init_drivers();
 
+	#[cfg(feature = "block")]
+	crate::drivers::block::init();
+
 	crate::arch::interrupts::install_handlers();
 }
@@ -0,0 +1,635 @@
+//! Driver for the Intel gigabit network cards of the e1000 and e1000e family.
+//!
+//! The driver is based on the "PCI/PCI-X Family of Gigabit Ethernet
+//! Controllers Software Developer's Manual" and uses the legacy descriptors,
+//! which all members of the family support. The registers are accessed
+//! through the memory mapped BAR 0, so that the driver does not depend on
+//! port I/O.
+
+use alloc::boxed::Box;
+use core::sync::atomic::{Ordering, fence};
+use core::{fmt, ptr};
+
+use memory_addresses::VirtAddr;
+use pci_types::{CommandRegister, InterruptLine};
+use smoltcp::phy::DeviceCapabilities;
+
+use crate::arch::kernel::interrupts::add_irq_name;
+use crate::arch::pci::PciConfigRegion;
+use crate::arch::processor::get_timer_ticks;
+use crate::drivers::Driver;
+use crate::drivers::error::DriverError;
+use crate::drivers::net::{NetworkDriver, STATISTICS, mtu};
+use crate::drivers::pci::PciDevice;
+use crate::mm::device_alloc::DeviceAlloc;
+
+/// Device ids of the supported network cards, whose vendor is Intel
+pub(crate) const DEVICE_IDS: &[u16] = &[
+	0x100e, // 82540EM, emulated by Qemu as `e1000`
+	0x100f, // 82545EM
+	0x10d3, // 82574L, emulated by Qemu as `e1000e`
+	0x1502, // 82579LM
+	0x153a, // I217-LM
+];
+
+/// Number of receive descriptors
+const NUM_RX_DESCRIPTORS: usize = 32;
+/// Number of transmit descriptors
+const NUM_TX_DESCRIPTORS: usize = 32;
+/// Size of the buffer of each descriptor
+const BUFFER_SIZE: usize = 2048;
+/// Timeout of the reset in microseconds
+const RESET_TIMEOUT: u64 = 10_000;
+
+/// device control register
+const CTRL: usize = 0x0000;
+/// device status register
+const STATUS: usize = 0x0008;
+/// interrupt cause read register, cleared by reading
+const ICR: usize = 0x00c0;
+/// interrupt mask set/read register
+const IMS: usize = 0x00d0;
+/// interrupt mask clear register
+const IMC: usize = 0x00d8;
+/// receive control register
+const RCTL: usize = 0x0100;
+/// transmit control register
+const TCTL: usize = 0x0400;
+/// transmit inter packet gap register
+const TIPG: usize = 0x0410;
+/// receive descriptor base address (low and high 32 bits)
+const RDBAL: usize = 0x2800;
+const RDBAH: usize = 0x2804;
+/// receive descriptor ring length in bytes
+const RDLEN: usize = 0x2808;
+/// receive descriptor head
+const RDH: usize = 0x2810;
+/// receive descriptor tail
+const RDT: usize = 0x2818;
+/// transmit descriptor base address (low and high 32 bits)
+const TDBAL: usize = 0x3800;
+const TDBAH: usize = 0x3804;
+/// transmit descriptor ring length in bytes
+const TDLEN: usize = 0x3808;
+/// transmit descriptor head
+const TDH: usize = 0x3810;
+/// transmit descriptor tail
+const TDT: usize = 0x3818;
+/// multicast table array (128 entries of 4 bytes)
+const MTA: usize = 0x5200;
+/// receive address low and high of the first entry
+const RAL0: usize = 0x5400;
+const RAH0: usize = 0x5404;
+
+const CTRL_ASDE: u32 = 1 << 5; // auto-speed detection enable
+const CTRL_SLU: u32 = 1 << 6; // set link up
+const CTRL_RST: u32 = 1 << 26; // device reset, self-clearing
+const CTRL_PHY_RST: u32 = 1 << 31; // PHY reset
+
+const STATUS_FD: u32 = 1 << 0; // full duplex
+const STATUS_LU: u32 = 1 << 1; // link up
+const STATUS_SPEED_SHIFT: u32 = 6;
+const STATUS_SPEED_MASK: u32 = 0b11;
+
+// Interrupt Cause Read/Mask Registers
+const ICR_TXDW: u32 = 1 << 0; // transmit descriptor written back
+const ICR_LSC: u32 = 1 << 2; // link status change
+const ICR_RXDMT0: u32 = 1 << 4; // receive descriptor minimum threshold reached
+const ICR_RXO: u32 = 1 << 6; // receiver overrun
+const ICR_RXT0: u32 = 1 << 7; // receiver timer interrupt
+
+const RCTL_EN: u32 = 1 << 1; // receiver enable
+const RCTL_MPE: u32 = 1 << 4; // multicast promiscuous enable
+const RCTL_LPE: u32 = 1 << 5; // long packet enable
+const RCTL_BAM: u32 = 1 << 15; // broadcast accept mode
+const RCTL_BSIZE_2048: u32 = 0 << 16; // receive buffer size of 2048 bytes
+const RCTL_SECRC: u32 = 1 << 26; // strip ethernet CRC
+
+const TCTL_EN: u32 = 1 << 1; // transmit enable
+const TCTL_PSP: u32 = 1 << 3; // pad short packets
+const TCTL_CT: u32 = 0x0f << 4; // collision threshold
+const TCTL_COLD: u32 = 0x40 << 12; // collision distance of full duplex
+const TCTL_RTLC: u32 = 1 << 24; // re-transmit on late collision
+
+/// Recommended inter packet gap of the copper interfaces
+const TIPG_COPPER: u32 = 10 | (8 << 10) | (6 << 20);
+
+/// The receive address is valid
+const RAH_AV: u32 = 1 << 31;
+
+const RX_STATUS_DD: u8 = 1 << 0; // descriptor done
+const RX_STATUS_EOP: u8 = 1 << 1; // end of packet
+
+const TX_CMD_EOP: u8 = 1 << 0; // end of packet
+const TX_CMD_IFCS: u8 = 1 << 1; // insert FCS
+const TX_CMD_RS: u8 = 1 << 3; // report status
+const TX_STATUS_DD: u8 = 1 << 0; // descriptor done
+
+/// Interrupts, which are enabled beside the polling mode
+const INT_MASK: u32 = ICR_RXT0 | ICR_RXO | ICR_RXDMT0 | ICR_LSC;
+/// Interrupts, which are enabled in the polling mode
+const INT_MASK_POLLING: u32 = ICR_LSC;
+
+#[derive(Debug)]
+pub enum E1000Error {
+	/// BAR 0 is missing or cannot be mapped
+	NoBar,
+	NoIrq,
+	ResetFailed,
+	/// The EEPROM did not load a valid MAC address
+	NoMacAddress,
+	/// The MTU exceeds the size of a receive buffer
+	MtuTooLarge(u16),
+}
+
+impl fmt::Display for E1000Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NoBar => write!(f, "BAR 0 cannot be mapped"),
+			Self::NoIrq => write!(f, "no interrupt line"),
+			Self::ResetFailed => write!(f, "reset failed"),
+			Self::NoMacAddress => write!(f, "no valid MAC address"),
+			Self::MtuTooLarge(mtu) => write!(f, "MTU {mtu} exceeds {BUFFER_SIZE} bytes"),
+		}
+	}
+}
+
+/// Legacy receive descriptor
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct RxDescriptor {
+	addr: u64,
+	length: u16,
+	checksum: u16,
+	status: u8,
+	errors: u8,
+	special: u16,
+}
+
+const _: () = assert!(size_of::<RxDescriptor>() == 16);
+
+/// Legacy transmit descriptor
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct TxDescriptor {
+	addr: u64,
+	length: u16,
+	cso: u8,
+	cmd: u8,
+	status: u8,
+	css: u8,
+	special: u16,
+}
+
+const _: () = assert!(size_of::<TxDescriptor>() == 16);
+
+/// Memory mapped registers of the device
+#[derive(Debug, Clone, Copy)]
+struct Registers {
+	base: VirtAddr,
+}
+
+impl Registers {
+	fn read(&self, register: usize) -> u32 {
+		unsafe { ptr::read_volatile((self.base + register).as_ptr::<u32>()) }
+	}
+
+	fn write(&self, register: usize, value: u32) {
+		unsafe { ptr::write_volatile((self.base + register).as_mut_ptr::<u32>(), value) }
+	}
+}
+
+/// Waits up to `timeout` microseconds until `condition` holds.
+fn wait_until(timeout: u64, mut condition: impl FnMut() -> bool) -> bool {
+	let start = get_timer_ticks();
+	while !condition() {
+		if get_timer_ticks() - start > timeout {
+			return false;
+		}
+		core::hint::spin_loop();
+	}
+	true
+}
+
+fn phys_addr<T>(ptr: *const T) -> u64 {
+	DeviceAlloc.phys_addr_from(ptr.cast_mut()).as_u64()
+}
+
+struct RxRing {
+	descriptors: Box<[RxDescriptor], DeviceAlloc>,
+	buffers: Box<[u8], DeviceAlloc>,
+	/// Next descriptor, which the device fills
+	next: usize,
+}
+
+impl RxRing {
+	fn new() -> Self {
+		let descriptors = Box::new_zeroed_slice_in(NUM_RX_DESCRIPTORS, DeviceAlloc);
+		let mut descriptors = unsafe { descriptors.assume_init() };
+		let buffers = Box::new_zeroed_slice_in(NUM_RX_DESCRIPTORS * BUFFER_SIZE, DeviceAlloc);
+		let buffers = unsafe { buffers.assume_init() };
+
+		for (i, descriptor) in descriptors.iter_mut().enumerate() {
+			descriptor.addr = phys_addr(buffers[i * BUFFER_SIZE..].as_ptr());
+		}
+
+		Self {
+			descriptors,
+			buffers,
+			next: 0,
+		}
+	}
+
+	fn status(&self) -> u8 {
+		unsafe { ptr::read_volatile(&self.descriptors[self.next].status) }
+	}
+
+	fn has_packet(&self) -> bool {
+		self.status() & RX_STATUS_DD == RX_STATUS_DD
+	}
+
+	/// Returns the current descriptor to the device.
+	fn recycle(&mut self, regs: Registers) {
+		let index = self.next;
+		unsafe {
+			ptr::write_volatile(&mut self.descriptors[index].status, 0);
+		}
+		self.next = (index + 1) % NUM_RX_DESCRIPTORS;
+		fence(Ordering::SeqCst);
+		regs.write(RDT, index.try_into().unwrap());
+	}
+
+	/// Drops the frames, which span several descriptors or are erroneous.
+	fn skip_invalid(&mut self, regs: Registers) {
+		while self.has_packet() {
+			fence(Ordering::Acquire);
+			let descriptor = self.descriptors[self.next];
+			if descriptor.status & RX_STATUS_EOP == RX_STATUS_EOP && descriptor.errors == 0 {
+				break;
+			}
+
+			trace!(
+				"e1000: drop frame with status {:#x} and errors {:#x}",
+				descriptor.status, descriptor.errors
+			);
+			self.recycle(regs);
+		}
+	}
+}
+
+struct TxRing {
+	descriptors: Box<[TxDescriptor], DeviceAlloc>,
+	buffers: Box<[u8], DeviceAlloc>,
+	/// Next descriptor, which the driver fills
+	next: usize,
+	/// Oldest descriptor, which the device has not yet completed
+	clean: usize,
+	/// Number of descriptors, which are owned by the device
+	in_flight: usize,
+}
+
+impl TxRing {
+	fn new() -> Self {
+		let descriptors = Box::new_zeroed_slice_in(NUM_TX_DESCRIPTORS, DeviceAlloc);
+		let mut descriptors = unsafe { descriptors.assume_init() };
+		let buffers = Box::new_zeroed_slice_in(NUM_TX_DESCRIPTORS * BUFFER_SIZE, DeviceAlloc);
+		let buffers = unsafe { buffers.assume_init() };
+
+		for (i, descriptor) in descriptors.iter_mut().enumerate() {
+			descriptor.addr = phys_addr(buffers[i * BUFFER_SIZE..].as_ptr());
+		}
+
+		Self {
+			descriptors,
+			buffers,
+			next: 0,
+			clean: 0,
+			in_flight: 0,
+		}
+	}
+
+	/// Releases the descriptors, which the device has sent.
+	fn reclaim(&mut self) {
+		while self.in_flight > 0 {
+			let status = unsafe { ptr::read_volatile(&self.descriptors[self.clean].status) };
+			if status & TX_STATUS_DD != TX_STATUS_DD {
+				break;
+			}
+
+			self.clean = (self.clean + 1) % NUM_TX_DESCRIPTORS;
+			self.in_flight -= 1;
+		}
+	}
+}
+
+/// Intel e1000 network driver struct.
+///
+/// Struct allows to control device queues as also
+/// the device itself.
+pub(crate) struct E1000Driver {
+	regs: Registers,
+	mtu: u16,
+	irq: InterruptLine,
+	mac: [u8; 6],
+	rx: RxRing,
+	tx: TxRing,
+}
+
+pub struct RxToken<'a> {
+	regs: Registers,
+	rx: &'a mut RxRing,
+}
+
+impl<'a> smoltcp::phy::RxToken for RxToken<'a> {
+	fn consume<R, F>(self, f: F) -> R
+	where
+		F: FnOnce(&[u8]) -> R,
+	{
+		let index = self.rx.next;
+		let length = usize::from(self.rx.descriptors[index].length);
+		let frame = &self.rx.buffers[index * BUFFER_SIZE..][..length];
+
+		STATISTICS.received(frame.len());
+		let result = f(frame);
+
+		self.rx.recycle(self.regs);
+		result
+	}
+}
+
+pub struct TxToken<'a> {
+	regs: Registers,
+	tx: &'a mut TxRing,
+}
+
+impl<'a> smoltcp::phy::TxToken for TxToken<'a> {
+	fn consume<R, F>(self, len: usize, f: F) -> R
+	where
+		F: FnOnce(&mut [u8]) -> R,
+	{
+		assert!(len <= BUFFER_SIZE, "Frame exceeds the TX buffer");
+
+		let index = self.tx.next;
+		let result = f(&mut self.tx.buffers[index * BUFFER_SIZE..][..len]);
+
+		let descriptor = &mut self.tx.descriptors[index];
+		descriptor.length = len.try_into().unwrap();
+		descriptor.cmd = TX_CMD_EOP | TX_CMD_IFCS | TX_CMD_RS;
+		unsafe {
+			ptr::write_volatile(&mut descriptor.status, 0);
+		}
+
+		self.tx.next = (index + 1) % NUM_TX_DESCRIPTORS;
+		self.tx.in_flight += 1;
+		fence(Ordering::SeqCst);
+		self.regs.write(TDT, self.tx.next.try_into().unwrap());
+		STATISTICS.transmitted(len);
+
+		result
+	}
+}
+
+impl smoltcp::phy::Device for E1000Driver {
+	type RxToken<'a> = RxToken<'a>;
+	type TxToken<'a> = TxToken<'a>;
+
+	fn receive(&mut self, _: smoltcp::time::Instant) -> Option<(RxToken<'_>, TxToken<'_>)> {
+		self.rx.skip_invalid(self.regs);
+		self.tx.reclaim();
+		// A descriptor stays free for the transmission of the response.
+		if self.rx.has_packet() && self.tx.in_flight < NUM_TX_DESCRIPTORS - 1 {
+			fence(Ordering::Acquire);
+			Some((
+				RxToken {
+					regs: self.regs,
+					rx: &mut self.rx,
+				},
+				TxToken {
+					regs: self.regs,
+					tx: &mut self.tx,
+				},
+			))
+		} else {
+			None
+		}
+	}
+
+	fn transmit(&mut self, _: smoltcp::time::Instant) -> Option<TxToken<'_>> {
+		self.tx.reclaim();
+		if self.tx.in_flight < NUM_TX_DESCRIPTORS - 1 {
+			Some(TxToken {
+				regs: self.regs,
+				tx: &mut self.tx,
+			})
+		} else {
+			None
+		}
+	}
+
+	fn capabilities(&self) -> DeviceCapabilities {
+		let mut device_capabilities = DeviceCapabilities::default();
+		device_capabilities.medium = smoltcp::phy::Medium::Ethernet;
+		device_capabilities.max_transmission_unit = usize::from(self.mtu);
+		device_capabilities.max_burst_size =
+			Some(usize::min(NUM_TX_DESCRIPTORS, NUM_RX_DESCRIPTORS) - 1);
+		device_capabilities
+	}
+}
+
+impl NetworkDriver for E1000Driver {
+	/// Returns the MAC address of the network interface
+	fn get_mac_address(&self) -> [u8; 6] {
+		self.mac
+	}
+
+	fn has_packet(&self) -> bool {
+		self.rx.has_packet()
+	}
+
+	fn set_polling_mode(&mut self, value: bool) {
+		if value {
+			self.regs.write(IMC, INT_MASK & !INT_MASK_POLLING);
+			self.regs.write(IMS, INT_MASK_POLLING);
+		} else {
+			self.regs.write(IMS, INT_MASK);
+		}
+	}
+
+	fn handle_interrupt(&mut self) {
+		let cause = self.regs.read(ICR);
+
+		if cause & ICR_LSC == ICR_LSC {
+			self.log_link_status();
+		}
+
+		if cause & ICR_RXO == ICR_RXO {
+			trace!("e1000: RX overrun detected!");
+		}
+
+		if cause & ICR_TXDW == ICR_TXDW {
+			self.tx.reclaim();
+		}
+	}
+}
+
+impl Driver for E1000Driver {
+	fn get_interrupt_number(&self) -> InterruptLine {
+		self.irq
+	}
+
+	fn get_name(&self) -> &'static str {
+		"e1000"
+	}
+}
+
+impl E1000Driver {
+	fn log_link_status(&self) {
+		let status = self.regs.read(STATUS);
+		if status & STATUS_LU != STATUS_LU {
+			info!("e1000: link down");
+			return;
+		}
+
+		let speed = match (status >> STATUS_SPEED_SHIFT) & STATUS_SPEED_MASK {
+			0b00 => 10,
+			0b01 => 100,
+			_ => 1000,
+		};
+		let duplex = if status & STATUS_FD == STATUS_FD {
+			"full"
+		} else {
+			"half"
+		};
+		info!("e1000: link up, speed = {speed} mbps, {duplex} duplex");
+	}
+}
+
+impl Drop for E1000Driver {
+	fn drop(&mut self) {
+		debug!("Dropping E1000Driver!");
+
+		// Stop the DMA before the rings are freed.
+		self.regs.write(IMC, u32::MAX);
+		self.regs.write(RCTL, 0);
+		self.regs.write(TCTL, 0);
+		self.regs.write(CTRL, self.regs.read(CTRL) | CTRL_RST);
+	}
+}
+
+pub(crate) fn init_device(device: &PciDevice<PciConfigRegion>) -> Result<E1000Driver, DriverError> {
+	let irq = device.get_irq().ok_or(E1000Error::NoIrq)?;
+	let (base, _size) = device
+		.memory_map_bar(0, true, "e1000")
+		.ok_or(E1000Error::NoBar)?;
+	let regs = Registers { base };
+
+	debug!("Found e1000 at {base:p} (irq {irq})");
+
+	device.set_command(CommandRegister::MEMORY_ENABLE | CommandRegister::BUS_MASTER_ENABLE);
+
+	// Mask the interrupts and reset the device.
+	regs.write(IMC, u32::MAX);
+	regs.write(CTRL, regs.read(CTRL) | CTRL_RST);
+	if !wait_until(RESET_TIMEOUT, || regs.read(CTRL) & CTRL_RST == 0) {
+		error!("e1000 reset failed");
+		return Err(E1000Error::ResetFailed.into());
+	}
+	regs.write(IMC, u32::MAX);
+	regs.read(ICR);
+
+	regs.write(
+		CTRL,
+		(regs.read(CTRL) | CTRL_SLU | CTRL_ASDE) & !CTRL_PHY_RST,
+	);
+
+	// After the reset, the device loads the MAC address from the EEPROM into
+	// the first receive address.
+	let ral = regs.read(RAL0);
+	let rah = regs.read(RAH0);
+	if rah & RAH_AV != RAH_AV {
+		error!("e1000 has no valid MAC address");
+		return Err(E1000Error::NoMacAddress.into());
+	}
+	let mut mac = [0u8; 6];
+	mac[..4].copy_from_slice(&ral.to_le_bytes());
+	mac[4..].copy_from_slice(&rah.to_le_bytes()[..2]);
+
+	debug!(
+		"MAC address {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+		mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+	);
+
+	let mtu = mtu();
+	if usize::from(mtu) > BUFFER_SIZE {
+		error!("e1000 does not support the MTU {mtu}");
+		return Err(E1000Error::MtuTooLarge(mtu).into());
+	}
+
+	for i in 0..128 {
+		regs.write(MTA + 4 * i, 0);
+	}
+
+	let rx = RxRing::new();
+	let tx = TxRing::new();
+
+	debug!(
+		"Allocate TxRing at {:p} and RxRing at {:p}",
+		tx.descriptors.as_ptr(),
+		rx.descriptors.as_ptr()
+	);
+
+	let rx_ring = phys_addr(rx.descriptors.as_ptr());
+	regs.write(RDBAL, rx_ring as u32);
+	regs.write(RDBAH, (rx_ring >> 32) as u32);
+	regs.write(
+		RDLEN,
+		(NUM_RX_DESCRIPTORS * size_of::<RxDescriptor>())
+			.try_into()
+			.unwrap(),
+	);
+	regs.write(RDH, 0);
+	regs.write(RDT, (NUM_RX_DESCRIPTORS - 1).try_into().unwrap());
+
+	let tx_ring = phys_addr(tx.descriptors.as_ptr());
+	regs.write(TDBAL, tx_ring as u32);
+	regs.write(TDBAH, (tx_ring >> 32) as u32);
+	regs.write(
+		TDLEN,
+		(NUM_TX_DESCRIPTORS * size_of::<TxDescriptor>())
+			.try_into()
+			.unwrap(),
+	);
+	regs.write(TDH, 0);
+	regs.write(TDT, 0);
+
+	// configure the receiver
+	// BAM - Broadcast Accept Mode: Accept broadcast packets.
+	// MPE - Multicast Promiscuous Enable: Accept all multicast packets.
+	// SECRC - Strip Ethernet CRC: The CRC is not passed to the network stack.
+	let mut rctl = RCTL_EN | RCTL_BAM | RCTL_MPE | RCTL_SECRC | RCTL_BSIZE_2048;
+	if mtu > 1514 {
+		rctl |= RCTL_LPE;
+	}
+	regs.write(RCTL, rctl);
+
+	regs.write(TIPG, TIPG_COPPER);
+	regs.write(TCTL, TCTL_EN | TCTL_PSP | TCTL_CT | TCTL_COLD | TCTL_RTLC);
+
+	let driver = E1000Driver {
+		regs,
+		mtu,
+		irq,
+		mac,
+		rx,
+		tx,
+	};
+
+	// Enable all known interrupts by setting the interrupt mask.
+	regs.write(IMS, INT_MASK);
+	driver.log_link_status();
+
+	info!("e1000 use interrupt line {irq}");
+	add_irq_name(irq, "e1000");
+
+	Ok(driver)
+}
@@ -27,7 +27,7 @@ use crate::arch::kernel::interrupts::*;
 use crate::arch::kernel::mmio as hardware;
 use crate::arch::mm::paging::PageTableEntryFlags;
 use crate::drivers::error::DriverError;
-use crate::drivers::net::{NetworkDriver, mtu};
+use crate::drivers::net::{NetworkDriver, STATISTICS, mtu};
 #[cfg(feature = "pci")]
 use crate::drivers::pci as hardware;
 use crate::drivers::{Driver, InterruptLine};
@@ -480,6 +480,7 @@ impl<'a> smoltcp::phy::RxToken for RxToken<'a> {
 			)
 		};
 		trace!("BUFFER: {buffer:x?}");
+		STATISTICS.received(buffer.len());
 		let res = f(buffer);
 		self.rx_buffer_consumed();
 		res
@@ -551,6 +552,7 @@ impl<'a> smoltcp::phy::TxToken for TxToken<'a> {
 		unsafe {
 			core::ptr::write_volatile(word1_addr, word1 | TX_DESC_USED);
 		}
+		STATISTICS.transmitted(len);
 
 		result
 	}
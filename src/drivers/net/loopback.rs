@@ -7,9 +7,25 @@ use smoltcp::phy;
 use smoltcp::time::Instant;
 
 use crate::drivers::net::NetworkDriver;
+#[cfg(not(any(
+	all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
+	all(target_arch = "x86_64", feature = "rtl8139"),
+	feature = "e1000",
+	feature = "virtio-net",
+)))]
+use crate::drivers::net::STATISTICS;
 use crate::drivers::{Driver, InterruptLine};
 use crate::mm::device_alloc::DeviceAlloc;
 
+/// Statistics of the loopback interface `lo` beside the network cards
+#[cfg(any(
+	all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
+	all(target_arch = "x86_64", feature = "rtl8139"),
+	feature = "e1000",
+	feature = "virtio-net",
+))]
+pub(crate) static STATISTICS: super::NetworkStatistics = super::NetworkStatistics::new();
+
 pub(crate) struct LoopbackDriver {
 	queue: SpinMutex<VecDeque<Vec<u8, DeviceAlloc>>>,
 	reserved_receives: AtomicUsize,
@@ -49,6 +65,7 @@ impl smoltcp::phy::TxToken for TxToken<'_> {
 		buffer.resize(len, 0);
 		let result = f(&mut buffer);
 		self.queue.lock().push_back(buffer);
+		STATISTICS.transmitted(len);
 		result
 	}
 }
@@ -63,8 +80,9 @@ impl smoltcp::phy::RxToken for RxToken<'_> {
 	where
 		F: FnOnce(&[u8]) -> R,
 	{
-		let frame = self.queue.lock().pop_front();
-		f(&frame.unwrap())
+		let frame = self.queue.lock().pop_front().unwrap();
+		STATISTICS.received(frame.len());
+		f(&frame)
 	}
 }
 
@@ -126,4 +144,10 @@ impl NetworkDriver for LoopbackDriver {
 	}
 }
 
+#[cfg(not(any(
+	all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
+	all(target_arch = "x86_64", feature = "rtl8139"),
+	feature = "e1000",
+	feature = "virtio-net",
+)))]
 pub(crate) type NetworkDevice = LoopbackDriver;
@@ -1,19 +1,22 @@
+#[cfg(all(
+	feature = "e1000",
+	not(all(target_arch = "x86_64", feature = "rtl8139")),
+))]
+pub mod e1000;
 #[cfg(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")))]
 pub mod gem;
-#[cfg(not(any(
-	all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
-	all(target_arch = "x86_64", feature = "rtl8139"),
-	feature = "virtio-net",
-)))]
 pub mod loopback;
 #[cfg(all(target_arch = "x86_64", feature = "rtl8139"))]
 pub mod rtl8139;
 #[cfg(all(
 	not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
 	not(all(target_arch = "x86_64", feature = "rtl8139")),
+	not(feature = "e1000"),
 	feature = "virtio-net",
 ))]
 pub mod virtio;
+use core::sync::atomic::{AtomicU64, Ordering};
+
 #[allow(unused_imports)]
 use crate::arch::kernel::core_local::*;
 use crate::drivers::Driver;
@@ -32,11 +35,89 @@ pub(crate) trait NetworkDriver: Driver + smoltcp::phy::Device {
 	fn handle_interrupt(&mut self);
 }
 
+/// Counters of the frames, which have been passed through the network device
+pub(crate) struct NetworkStatistics {
+	rx_packets: AtomicU64,
+	rx_bytes: AtomicU64,
+	tx_packets: AtomicU64,
+	tx_bytes: AtomicU64,
+}
+
+impl NetworkStatistics {
+	const fn new() -> Self {
+		Self {
+			rx_packets: AtomicU64::new(0),
+			rx_bytes: AtomicU64::new(0),
+			tx_packets: AtomicU64::new(0),
+			tx_bytes: AtomicU64::new(0),
+		}
+	}
+
+	/// Counts a received frame of `len` bytes.
+	pub fn received(&self, len: usize) {
+		self.rx_packets.fetch_add(1, Ordering::Relaxed);
+		self.rx_bytes
+			.fetch_add(len.try_into().unwrap(), Ordering::Relaxed);
+	}
+
+	/// Counts a transmitted frame of `len` bytes.
+	pub fn transmitted(&self, len: usize) {
+		self.tx_packets.fetch_add(1, Ordering::Relaxed);
+		self.tx_bytes
+			.fetch_add(len.try_into().unwrap(), Ordering::Relaxed);
+	}
+
+	/// Returns the number of received frames and bytes.
+	pub fn rx(&self) -> (u64, u64) {
+		(
+			self.rx_packets.load(Ordering::Relaxed),
+			self.rx_bytes.load(Ordering::Relaxed),
+		)
+	}
+
+	/// Returns the number of transmitted frames and bytes.
+	pub fn tx(&self) -> (u64, u64) {
+		(
+			self.tx_packets.load(Ordering::Relaxed),
+			self.tx_bytes.load(Ordering::Relaxed),
+		)
+	}
+}
+
+/// Statistics of the network device, which are exported as `/proc/net/dev`
+pub(crate) static STATISTICS: NetworkStatistics = NetworkStatistics::new();
+
+/// Name of the network interface in `/proc/net/dev`
+#[cfg(any(
+	all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
+	all(target_arch = "x86_64", feature = "rtl8139"),
+	feature = "e1000",
+	feature = "virtio-net",
+))]
+pub(crate) const INTERFACE_NAME: &str = "eth0";
+#[cfg(not(any(
+	all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
+	all(target_arch = "x86_64", feature = "rtl8139"),
+	feature = "e1000",
+	feature = "virtio-net",
+)))]
+pub(crate) const INTERFACE_NAME: &str = "lo";
+
+/// Size of the header of an Ethernet frame, which the MTU of a device includes
+pub(crate) const ETHERNET_HEADER_LEN: u16 = 14;
+
+/// Smallest MTU of the IP layer, which can be configured, the minimum of IPv6
+pub(crate) const MIN_IP_MTU: u16 = 1280;
+
 /// Determines the MTU that should be used as configured by crate features
 /// or environment variables.
+///
+/// The kernel argument `net.mtu=<bytes>` gives the MTU of the IP layer and
+/// takes precedence over `HERMIT_MTU`, which includes the Ethernet header.
 #[cfg(any(
 	all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
 	all(target_arch = "x86_64", feature = "rtl8139"),
+	feature = "e1000",
 	feature = "virtio-net",
 ))]
 pub(crate) fn mtu() -> u16 {
@@ -48,7 +129,17 @@ pub(crate) fn mtu() -> u16 {
 	/// Default MTU to use.
 	///
 	/// This is 1500 IP MTU and a 14-byte ethernet header.
-	const DEFAULT_MTU: u16 = DEFAULT_IP_MTU + 14;
+	const DEFAULT_MTU: u16 = DEFAULT_IP_MTU + ETHERNET_HEADER_LEN;
+
+	/// Largest IP level MTU, which can be configured, i.e., jumbo frames
+	const MAX_IP_MTU: u16 = 9000;
+
+	if let Some(ip_mtu) = crate::env::mtu() {
+		if (MIN_IP_MTU..=MAX_IP_MTU).contains(&ip_mtu) {
+			return ip_mtu + ETHERNET_HEADER_LEN;
+		}
+		error!("net.mtu={ip_mtu} is not between {MIN_IP_MTU} and {MAX_IP_MTU}");
+	}
 
 	if let Some(my_mtu) = hermit_var!("HERMIT_MTU") {
 		u16::from_str(&my_mtu).unwrap()
@@ -70,6 +161,7 @@ cfg_if::cfg_if! {
 		feature = "pci",
 		any(
 			all(target_arch = "x86_64", feature = "rtl8139"),
+			feature = "e1000",
 			feature = "virtio-net",
 		),
 	))] {
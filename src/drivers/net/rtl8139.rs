@@ -14,7 +14,7 @@ use crate::arch::kernel::interrupts::*;
 use crate::arch::pci::PciConfigRegion;
 use crate::drivers::Driver;
 use crate::drivers::error::DriverError;
-use crate::drivers::net::{NetworkDriver, mtu};
+use crate::drivers::net::{NetworkDriver, STATISTICS, mtu};
 use crate::drivers::pci::PciDevice;
 use crate::mm::device_alloc::DeviceAlloc;
 
@@ -300,6 +300,7 @@ impl<'a> smoltcp::phy::RxToken for RxToken<'a> {
 			&self.rx_fields.rxbuffer[pos..][..length.into()]
 		};
 
+		STATISTICS.received(frame.len());
 		let result = f(frame);
 
 		self.consume_current_buffer();
@@ -344,6 +345,7 @@ impl<'a> smoltcp::phy::TxToken for TxToken<'a> {
 		unsafe {
 			Port::<u32>::new(token.iobase + TSD0 + (4 * id as u16)).write(len.try_into().unwrap()); //|0x3A0000);
 		}
+		STATISTICS.transmitted(len);
 
 		result
 	}
@@ -2,23 +2,20 @@
 //!
 //! The module contains ...
 
-cfg_if::cfg_if! {
-	if #[cfg(feature = "pci")] {
-		mod pci;
-	} else {
-		mod mmio;
-	}
-}
-
 use alloc::boxed::Box;
 use alloc::vec::Vec;
-use core::mem::{ManuallyDrop, MaybeUninit, transmute};
+use core::hash::{BuildHasher, Hasher};
+use core::mem::{MaybeUninit, transmute};
 
+use ahash::RandomState;
 use smallvec::SmallVec;
 use smoltcp::phy::{Checksum, ChecksumCapabilities, DeviceCapabilities};
-use smoltcp::wire::{ETHERNET_HEADER_LEN, EthernetFrame, Ipv4Packet, Ipv6Packet};
+use smoltcp::wire::{
+	ETHERNET_HEADER_LEN, EthernetFrame, EthernetProtocol, IpAddress, IpProtocol, Ipv4Packet,
+	Ipv6Packet, TcpPacket, UdpPacket,
+};
 use virtio::net::{ConfigVolatileFieldAccess, Hdr, HdrF};
-use virtio::{DeviceConfigSpace, FeatureBits};
+use virtio::{DeviceConfigSpace, FeatureBits, le16};
 use volatile::VolatileRef;
 use volatile::access::ReadOnly;
 
@@ -26,11 +23,9 @@ use self::constants::MAX_NUM_VQ;
 use self::error::VirtioNetError;
 use crate::config::VIRTIO_MAX_QUEUE_SIZE;
 use crate::drivers::net::virtio::constants::BUFF_PER_PACKET;
-use crate::drivers::net::{NetworkDriver, mtu};
-#[cfg(not(feature = "pci"))]
-use crate::drivers::virtio::transport::mmio::{ComCfg, IsrStatus, NotifCfg};
-#[cfg(feature = "pci")]
-use crate::drivers::virtio::transport::pci::{ComCfg, IsrStatus, NotifCfg};
+use crate::drivers::net::{NetworkDriver, STATISTICS, mtu};
+use crate::drivers::virtio::error::VirtioError;
+use crate::drivers::virtio::transport::{ComCfg, IsrStatus, NotifCfg, Transport};
 use crate::drivers::virtio::virtqueue::packed::PackedVq;
 use crate::drivers::virtio::virtqueue::split::SplitVq;
 use crate::drivers::virtio::virtqueue::{
@@ -52,13 +47,27 @@ fn determine_mtu(dev_cfg: &NetDevCfg) -> u16 {
 	// If VIRTIO_NET_F_MTU is negotiated, "the driver uses mtu as the maximum MTU value"
 	// (VirtIO specification, 5.1.3, "Feature bits")
 	if dev_cfg.features.contains(virtio::net::F::MTU) {
-		dev_cfg.raw.as_ptr().mtu().read().to_ne()
+		// The device reports the MTU of the IP layer, which excludes the
+		// Ethernet header. Jumbo frames are only used, if they are configured.
+		let max_mtu = dev_cfg.raw.as_ptr().mtu().read().to_ne();
+		mtu().min(max_mtu.saturating_add(super::ETHERNET_HEADER_LEN))
 	} else {
 		// Otherwise, we can just use the MTU we want to use
 		mtu()
 	}
 }
 
+/// Returns the number of queue pairs, which the device supports.
+///
+/// Returns 1 (i.e. minimum number of pairs) if VIRTIO_NET_F_MQ is not set.
+fn max_vq_pairs(dev_cfg: &NetDevCfg) -> u16 {
+	if dev_cfg.features.contains(virtio::net::F::MQ) {
+		dev_cfg.raw.as_ptr().max_virtqueue_pairs().read().to_ne()
+	} else {
+		1
+	}
+}
+
 fn determine_buf_size(dev_cfg: &NetDevCfg) -> u32 {
 	// See Virtio specification v1.1 - 5.1.6.3.1 and 5.1.4.2
 
@@ -86,6 +95,8 @@ fn determine_buf_size(dev_cfg: &NetDevCfg) -> u32 {
 pub struct RxQueues {
 	vqs: Vec<VirtQueue>,
 	buf_size: u32,
+	/// Queue, which is checked first for the next packet
+	next: usize,
 }
 
 impl RxQueues {
@@ -93,6 +104,7 @@ impl RxQueues {
 		Self {
 			vqs,
 			buf_size: determine_buf_size(dev_cfg),
+			next: 0,
 		}
 	}
 
@@ -105,8 +117,18 @@ impl RxQueues {
 		self.vqs.push(vq);
 	}
 
-	fn get_next(&mut self) -> Option<UsedBufferToken> {
-		self.vqs[0].try_recv().ok()
+	/// Returns the next received buffer and the index of its queue.
+	///
+	/// The queues take turns, so that a busy queue does not starve the others.
+	fn get_next(&mut self) -> Option<(usize, UsedBufferToken)> {
+		for i in 0..self.vqs.len() {
+			let index = (self.next + i) % self.vqs.len();
+			if let Ok(tkn) = self.vqs[index].try_recv() {
+				self.next = (index + 1) % self.vqs.len();
+				return Some((index, tkn));
+			}
+		}
+		None
 	}
 
 	fn enable_notifs(&mut self) {
@@ -161,6 +183,8 @@ fn fill_queue(vq: &mut VirtQueue, num_bufs: u16, buf_size: u32) {
 pub struct TxQueues {
 	vqs: Vec<VirtQueue>,
 	buf_size: u32,
+	/// Capacity of each queue in number of buffer descriptors, not frames.
+	capacities: Vec<u32>,
 }
 
 impl TxQueues {
@@ -168,6 +192,7 @@ impl TxQueues {
 		Self {
 			vqs,
 			buf_size: determine_buf_size(dev_cfg),
+			capacities: Vec::new(),
 		}
 	}
 
@@ -185,34 +210,112 @@ impl TxQueues {
 		}
 	}
 
-	/// Polls all queues for buffers whose transmission has been completed and returns the number of such buffers.
-	fn poll(&mut self) -> u32 {
-		let mut released_buffers = 0u32;
-		for vq in &mut self.vqs {
+	/// Polls all queues for buffers whose transmission has been completed and restores the capacity of their queues.
+	fn poll(&mut self) {
+		for (vq, capacity) in self.vqs.iter_mut().zip(&mut self.capacities) {
 			// We don't do anything with the buffers but we need to receive them for the
 			// ring slots to be emptied and the memory from the previous transfers to be freed.
 			while vq.try_recv().is_ok() {
-				released_buffers += 1;
+				*capacity += u32::from(BUFF_PER_PACKET);
 			}
 		}
-		released_buffers
+	}
+
+	/// Returns the capacity of the fullest queue, which every frame fits in.
+	fn capacity(&self) -> u32 {
+		self.capacities.iter().copied().min().unwrap_or(0)
+	}
+
+	/// Returns whether every queue has room for another frame and polls the
+	/// queues otherwise.
+	fn has_capacity(&mut self) -> bool {
+		if self.capacity() < u32::from(BUFF_PER_PACKET) {
+			self.poll();
+		}
+		self.capacity() >= u32::from(BUFF_PER_PACKET)
 	}
 
 	fn add(&mut self, vq: VirtQueue) {
-		// Currently we are doing nothing with the additional queues. They are inactive and might be used in the
-		// future
+		self.capacities.push(u32::from(u16::from(vq.size())));
 		self.vqs.push(vq);
 	}
+
+	/// Removes all queues except the first pair.
+	fn truncate(&mut self) {
+		self.vqs.truncate(1);
+		self.capacities.truncate(1);
+	}
+}
+
+/// Returns the index of the send queue for the flow of `frame`.
+///
+/// The flow consists of the addresses and, for TCP and UDP, of the ports. All
+/// frames of a flow use the same queue, so that they are not reordered.
+fn flow_queue(frame: &[u8], num_queues: usize) -> usize {
+	if num_queues <= 1 {
+		return 0;
+	}
+	let Ok(ethernet_frame) = EthernetFrame::new_checked(frame) else {
+		return 0;
+	};
+
+	let mut hasher = RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+	let (protocol, ip_payload) = match ethernet_frame.ethertype() {
+		EthernetProtocol::Ipv4 => {
+			let Ok(ip_packet) = Ipv4Packet::new_checked(ethernet_frame.payload()) else {
+				return 0;
+			};
+			hasher.write(&ip_packet.src_addr().octets());
+			hasher.write(&ip_packet.dst_addr().octets());
+			// Only the first fragment contains the ports.
+			if ip_packet.more_frags() || ip_packet.frag_offset() != 0 {
+				(None, [].as_slice())
+			} else {
+				(Some(ip_packet.next_header()), ip_packet.payload())
+			}
+		}
+		EthernetProtocol::Ipv6 => {
+			let Ok(ip_packet) = Ipv6Packet::new_checked(ethernet_frame.payload()) else {
+				return 0;
+			};
+			hasher.write(&ip_packet.src_addr().octets());
+			hasher.write(&ip_packet.dst_addr().octets());
+			(Some(ip_packet.next_header()), ip_packet.payload())
+		}
+		_ => return 0,
+	};
+
+	if let Some(protocol @ (IpProtocol::Tcp | IpProtocol::Udp)) = protocol
+		&& let Some(ports) = ip_payload.get(..4)
+	{
+		hasher.write_u8(protocol.into());
+		hasher.write(ports);
+	}
+
+	(hasher.finish() % u64::try_from(num_queues).unwrap())
+		.try_into()
+		.unwrap()
 }
 
+/// Header of a command on the control queue, see Virtio specification v1.2 - 5.1.6.5
+#[repr(C)]
+struct CtrlHdr {
+	class: u8,
+	command: u8,
+}
+
+/// Class of the commands, which control the number of queue pairs
+const VIRTIO_NET_CTRL_MQ: u8 = 4;
+const VIRTIO_NET_CTRL_MQ_VQ_PAIRS_SET: u8 = 0;
+/// Acknowledgement of a successful command
+const VIRTIO_NET_OK: u8 = 0;
+
 pub(crate) struct Uninit;
 pub(crate) struct Init {
 	pub(super) mtu: u16,
 	pub(super) ctrl_vq: Option<VirtQueue>,
 	pub(super) recv_vqs: RxQueues,
 	pub(super) send_vqs: TxQueues,
-	/// Capacity in number of buffer descriptors, not frames.
-	pub(super) send_capacity: u32,
 }
 
 /// Virtio network driver struct.
@@ -235,13 +338,6 @@ pub(crate) struct VirtioNetDriver<T = Init> {
 pub struct TxToken<'a> {
 	send_vqs: &'a mut TxQueues,
 	checksums: ChecksumCapabilities,
-	send_capacity: &'a mut u32,
-}
-
-impl Drop for TxToken<'_> {
-	fn drop(&mut self) {
-		*self.send_capacity += u32::from(BUFF_PER_PACKET);
-	}
 }
 
 impl smoltcp::phy::TxToken for TxToken<'_> {
@@ -249,13 +345,10 @@ impl smoltcp::phy::TxToken for TxToken<'_> {
 	where
 		F: FnOnce(&mut [u8]) -> R,
 	{
-		// When the token is consumed, the capacity cannot be returned until its buffer is marked by the device as used.
-		// Thus, we bypass the Drop implementation that would do that prematurely and let the call to poll in the next
-		// call to this function return the capacity.
-		let mut token = ManuallyDrop::new(self);
+		let mut token = self;
 		// We need to poll to get the queue to remove elements from the table and make space for
 		// what we are about to add
-		*token.send_capacity += token.send_vqs.poll() * u32::from(BUFF_PER_PACKET);
+		token.send_vqs.poll();
 
 		assert!(len <= usize::try_from(token.send_vqs.buf_size).unwrap());
 		let mut packet = Vec::with_capacity_in(len, DeviceAlloc);
@@ -278,15 +371,20 @@ impl smoltcp::phy::TxToken for TxToken<'_> {
 			header.csum_offset = csum_offset.into();
 		}
 
+		// The capacity of the queue is returned by poll, when the device has used the buffer.
+		let index = flow_queue(&packet, token.send_vqs.vqs.len());
+		token.send_vqs.capacities[index] -= u32::from(BUFF_PER_PACKET);
+
 		let buff_tkn = AvailBufferToken::new(
 			SmallVec::from_buf([BufferElem::Sized(header), BufferElem::Vector(packet)]),
 			SmallVec::new(),
 		)
 		.unwrap();
 
-		token.send_vqs.vqs[0]
+		token.send_vqs.vqs[index]
 			.dispatch(buff_tkn, false, BufferType::Direct)
 			.unwrap();
+		STATISTICS.transmitted(len);
 
 		result
 	}
@@ -295,6 +393,7 @@ impl smoltcp::phy::TxToken for TxToken<'_> {
 pub struct RxToken<'a> {
 	recv_vqs: &'a mut RxQueues,
 	is_mrg_rxbuf_enabled: bool,
+	is_guest_csum_enabled: bool,
 }
 
 impl smoltcp::phy::RxToken for RxToken<'_> {
@@ -302,7 +401,7 @@ impl smoltcp::phy::RxToken for RxToken<'_> {
 	where
 		F: FnOnce(&[u8]) -> R,
 	{
-		let Some(mut buffer_tkn) = self.recv_vqs.get_next() else {
+		let Some((index, mut buffer_tkn)) = self.recv_vqs.get_next() else {
 			// We overpromised a frame. The best we can do is to provide an empty frame to smoltcp and let it handle it as a faulty reception.
 			return f(&[]);
 		};
@@ -315,6 +414,7 @@ impl smoltcp::phy::RxToken for RxToken<'_> {
 				.unwrap()
 		};
 		let first_packet = buffer_tkn.used_recv_buff.pop_front_vec().unwrap();
+		let flags = first_header.flags;
 
 		// According to VIRTIO spec v1.2 sec. 5.1.6.3.2, "num_buffers will always be 1 if VIRTIO_NET_F_MRG_RXBUF is not negotiated."
 		// Unfortunately, NVIDIA MLX5 does not comply with this requirement and we have to manually set the value to the correct one.
@@ -333,12 +433,13 @@ impl smoltcp::phy::RxToken for RxToken<'_> {
 			},
 			self.recv_vqs.buf_size,
 		);
-		self.recv_vqs.vqs[0]
+		self.recv_vqs.vqs[index]
 			.dispatch(first_tkn, false, BufferType::Direct)
 			.unwrap();
 
+		// The remaining buffers of a frame are taken from the same queue.
 		for _ in 1..num_buffers {
-			let mut buffer_tkn = self.recv_vqs.get_next().unwrap();
+			let mut buffer_tkn = self.recv_vqs.vqs[index].try_recv().unwrap();
 			// The descriptor that was meant for the header of another frame was used for a portion of the current frame's contents.
 			// Thus, we cannot cast it to a Hdr.
 			let (header_descriptor, used_len) = buffer_tkn.used_recv_buff.pop_front_raw().unwrap();
@@ -356,11 +457,25 @@ impl smoltcp::phy::RxToken for RxToken<'_> {
 				header,
 				self.recv_vqs.buf_size,
 			);
-			self.recv_vqs.vqs[0]
+			self.recv_vqs.vqs[index]
 				.dispatch(tkn, false, BufferType::Direct)
 				.unwrap();
 		}
 
+		STATISTICS.received(combined_packets.len());
+
+		// With VIRTIO_NET_F_GUEST_CSUM, smoltcp does not verify the TCP and UDP checksums.
+		// The device has either validated them or the frame comes from a local peer and
+		// carries only a partial checksum, see Virtio specification v1.2 - 5.1.6.4.1.
+		// Otherwise, the driver has to validate them itself.
+		if self.is_guest_csum_enabled
+			&& !flags.intersects(HdrF::DATA_VALID | HdrF::NEEDS_CSUM)
+			&& !VirtioNetDriver::has_valid_checksum(&combined_packets)
+		{
+			debug!("Dropping frame with an invalid checksum");
+			return f(&[]);
+		}
+
 		f(&combined_packets)
 	}
 }
@@ -418,8 +533,9 @@ impl smoltcp::phy::Device for VirtioNetDriver {
 		let mut device_capabilities = DeviceCapabilities::default();
 		device_capabilities.medium = smoltcp::phy::Medium::Ethernet;
 		device_capabilities.max_transmission_unit = self.inner.mtu.into();
-		device_capabilities.max_burst_size =
-			Some(usize::try_from(self.inner.send_capacity).unwrap() / usize::from(BUFF_PER_PACKET));
+		device_capabilities.max_burst_size = Some(
+			usize::try_from(self.inner.send_vqs.capacity()).unwrap() / usize::from(BUFF_PER_PACKET),
+		);
 		device_capabilities.checksum = self.checksums.clone();
 		device_capabilities
 	}
@@ -428,19 +544,19 @@ impl smoltcp::phy::Device for VirtioNetDriver {
 		&mut self,
 		_timestamp: smoltcp::time::Instant,
 	) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
-		if self.inner.recv_vqs.has_packet()
-			&& self.inner.send_capacity >= u32::from(BUFF_PER_PACKET)
-		{
-			self.inner.send_capacity -= u32::from(BUFF_PER_PACKET);
+		if self.inner.recv_vqs.has_packet() && self.inner.send_vqs.has_capacity() {
 			Some((
 				RxToken {
 					recv_vqs: &mut self.inner.recv_vqs,
 					is_mrg_rxbuf_enabled: self.dev_cfg.features.contains(virtio::net::F::MRG_RXBUF),
+					is_guest_csum_enabled: self
+						.dev_cfg
+						.features
+						.contains(virtio::net::F::GUEST_CSUM),
 				},
 				TxToken {
 					send_vqs: &mut self.inner.send_vqs,
 					checksums: self.checksums.clone(),
-					send_capacity: &mut self.inner.send_capacity,
 				},
 			))
 		} else {
@@ -449,12 +565,10 @@ impl smoltcp::phy::Device for VirtioNetDriver {
 	}
 
 	fn transmit(&mut self, _timestamp: smoltcp::time::Instant) -> Option<Self::TxToken<'_>> {
-		if self.inner.send_capacity >= u32::from(BUFF_PER_PACKET) {
-			self.inner.send_capacity -= u32::from(BUFF_PER_PACKET);
+		if self.inner.send_vqs.has_capacity() {
 			Some(TxToken {
 				send_vqs: &mut self.inner.send_vqs,
 				checksums: self.checksums.clone(),
-				send_capacity: &mut self.inner.send_capacity,
 			})
 		} else {
 			None
@@ -474,25 +588,12 @@ impl Driver for VirtioNetDriver<Init> {
 
 // Backend-independent interface for Virtio network driver
 impl VirtioNetDriver<Init> {
-	#[cfg(feature = "pci")]
 	pub fn get_dev_id(&self) -> u16 {
 		self.dev_cfg.dev_id
 	}
 
-	/// Returns the current status of the device, if VIRTIO_NET_F_STATUS
-	/// has been negotiated. Otherwise assumes an active device.
-	#[cfg(not(feature = "pci"))]
-	pub fn dev_status(&self) -> virtio::net::S {
-		if self.dev_cfg.features.contains(virtio::net::F::STATUS) {
-			self.dev_cfg.raw.as_ptr().status().read()
-		} else {
-			virtio::net::S::LINK_UP
-		}
-	}
-
 	/// Returns the links status.
 	/// If feature VIRTIO_NET_F_STATUS has not been negotiated, then we assume the link is up!
-	#[cfg(feature = "pci")]
 	pub fn is_link_up(&self) -> bool {
 		if self.dev_cfg.features.contains(virtio::net::F::STATUS) {
 			self.dev_cfg
@@ -527,16 +628,7 @@ impl VirtioNetDriver<Init> {
 	/// Returns 1 (i.e. minimum number of pairs) if VIRTIO_NET_F_MQ is not set.
 	#[allow(dead_code)]
 	pub fn get_max_vq_pairs(&self) -> u16 {
-		if self.dev_cfg.features.contains(virtio::net::F::MQ) {
-			self.dev_cfg
-				.raw
-				.as_ptr()
-				.max_virtqueue_pairs()
-				.read()
-				.to_ne()
-		} else {
-			1
-		}
+		max_vq_pairs(&self.dev_cfg)
 	}
 
 	pub fn disable_interrupts(&mut self) {
@@ -609,6 +701,120 @@ impl VirtioNetDriver<Init> {
 
 		Some((ip_header_len, csum_offset))
 	}
+
+	/// Returns whether the TCP or UDP checksum of `frame` is valid.
+	///
+	/// Frames, which are neither TCP nor UDP, as well as fragments and
+	/// malformed frames, are left to smoltcp and are reported as valid.
+	fn has_valid_checksum(frame: &[u8]) -> bool {
+		let Ok(ethernet_frame) = EthernetFrame::new_checked(frame) else {
+			return true;
+		};
+
+		let (src_addr, dst_addr, protocol, ip_payload): (IpAddress, IpAddress, _, _) =
+			match ethernet_frame.ethertype() {
+				EthernetProtocol::Ipv4 => {
+					let Ok(ip_packet) = Ipv4Packet::new_checked(ethernet_frame.payload()) else {
+						return true;
+					};
+					if ip_packet.more_frags() || ip_packet.frag_offset() != 0 {
+						return true;
+					}
+					(
+						ip_packet.src_addr().into(),
+						ip_packet.dst_addr().into(),
+						ip_packet.next_header(),
+						ip_packet.payload(),
+					)
+				}
+				EthernetProtocol::Ipv6 => {
+					let Ok(ip_packet) = Ipv6Packet::new_checked(ethernet_frame.payload()) else {
+						return true;
+					};
+					(
+						ip_packet.src_addr().into(),
+						ip_packet.dst_addr().into(),
+						ip_packet.next_header(),
+						ip_packet.payload(),
+					)
+				}
+				_ => return true,
+			};
+
+		match protocol {
+			IpProtocol::Tcp => {
+				let Ok(tcp_packet) = TcpPacket::new_checked(ip_payload) else {
+					return true;
+				};
+				tcp_packet.verify_checksum(&src_addr, &dst_addr)
+			}
+			IpProtocol::Udp => {
+				let Ok(udp_packet) = UdpPacket::new_checked(ip_payload) else {
+					return true;
+				};
+				udp_packet.verify_checksum(&src_addr, &dst_addr)
+			}
+			_ => true,
+		}
+	}
+}
+
+// Transport-independent construction of the Virtio network driver
+impl VirtioNetDriver<Uninit> {
+	/// Instantiates a new (VirtioNetDriver)[VirtioNetDriver] struct, by mapping the device
+	/// specific configuration and moving the configuration structures of the transport into the struct.
+	pub(crate) fn new(mut transport: impl Transport) -> Result<Self, VirtioNetError> {
+		let dev_id = transport.dev_id();
+		let irq = transport.irq();
+
+		let Some(dev_cfg) = transport.map_dev_cfg::<virtio::net::Config>() else {
+			error!("No dev config. Aborting!");
+			return Err(VirtioNetError::NoDevCfg(dev_id));
+		};
+		let dev_cfg = NetDevCfg {
+			raw: VolatileRef::from_ref(dev_cfg),
+			dev_id,
+			features: virtio::net::F::empty(),
+		};
+
+		let (com_cfg, notif_cfg, isr_stat) = transport.into_cfgs();
+
+		Ok(VirtioNetDriver {
+			dev_cfg,
+			com_cfg,
+			isr_stat,
+			notif_cfg,
+			inner: Uninit,
+			num_vqs: 0,
+			irq,
+			checksums: ChecksumCapabilities::default(),
+		})
+	}
+
+	/// Initializes virtio network device on top of the given transport.
+	///
+	/// Returns a driver instance of
+	/// [VirtioNetDriver](structs.virtionetdriver.html) or an [VirtioError](enums.virtioerror.html).
+	pub(crate) fn init(transport: impl Transport) -> Result<VirtioNetDriver<Init>, VirtioError> {
+		let drv = VirtioNetDriver::new(transport).map_err(|vnet_err| {
+			error!("Initializing new network driver failed. Aborting!");
+			VirtioError::NetDriver(vnet_err)
+		})?;
+
+		let initialized_drv = drv.init_dev().map_err(VirtioError::NetDriver)?;
+		info!(
+			"Network device with id {:x}, has been initialized by driver!",
+			initialized_drv.get_dev_id()
+		);
+
+		if initialized_drv.is_link_up() {
+			info!("Virtio-net link is up after initialization.");
+		} else {
+			info!("Virtio-net link is down after initialization!");
+		}
+
+		Ok(initialized_drv)
+	}
 }
 
 impl VirtioNetDriver<Uninit> {
@@ -636,6 +842,8 @@ impl VirtioNetDriver<Uninit> {
 			// Packed Vq can be used
 			| virtio::net::F::RING_PACKED
 			| virtio::net::F::NOTIFICATION_DATA
+			// Notifications can be suppressed via event indices
+			| virtio::net::F::EVENT_IDX
 			// MTU setting can be used
 			| virtio::net::F::MTU
 			// Driver can merge receive buffers
@@ -647,13 +855,12 @@ impl VirtioNetDriver<Uninit> {
 			// Multiqueue support
 			| virtio::net::F::MQ
 			// Checksum calculation can partially be offloaded to the device
-			| virtio::net::F::CSUM;
-
-		// Currently the driver does NOT support the features below.
-		// In order to provide functionality for these, the driver
-		// needs to take care of calculating checksum.
-		// | virtio::net::F::GUEST_TSO4
-		// | virtio::net::F::GUEST_TSO6
+			| virtio::net::F::CSUM
+			// Driver accepts frames with partial or already validated checksums
+			| virtio::net::F::GUEST_CSUM
+			// Driver accepts large TCP segments, which the device has not split
+			| virtio::net::F::GUEST_TSO4
+			| virtio::net::F::GUEST_TSO6;
 
 		// Negotiate features with device. Automatically reduces selected feats in order to meet device capabilities.
 		// Aborts in case incompatible features are selected by the driver or the device does not support min_feat_set.
@@ -715,7 +922,6 @@ impl VirtioNetDriver<Uninit> {
 						);
 						return Err(vnet_err);
 					}
-					#[cfg(feature = "pci")]
 					VirtioNetError::NoDevCfg(_) => {
 						error!("No device config found.");
 						return Err(vnet_err);
@@ -745,7 +951,6 @@ impl VirtioNetDriver<Uninit> {
 			ctrl_vq: None,
 			recv_vqs: RxQueues::new(Vec::new(), &self.dev_cfg),
 			send_vqs: TxQueues::new(Vec::new(), &self.dev_cfg),
-			send_capacity: 0,
 		};
 
 		debug!("Using RX buffer size of {}", inner.recv_vqs.buf_size);
@@ -759,6 +964,20 @@ impl VirtioNetDriver<Uninit> {
 		// At this point the device is "live"
 		self.com_cfg.drv_ok();
 
+		// The device uses only the first pair of queues, until it is told otherwise.
+		let num_pairs = self.num_vqs / 2;
+		if num_pairs > 1 {
+			match inner.ctrl_vq.as_mut().map(|vq| set_vq_pairs(vq, num_pairs)) {
+				Some(true) => info!("Virtio-net uses {num_pairs} pairs of queues"),
+				_ => {
+					warn!("Unable to activate {num_pairs} pairs of queues, only one is used");
+					inner.recv_vqs.vqs.truncate(1);
+					inner.send_vqs.truncate();
+					self.num_vqs = 2;
+				}
+			}
+		}
+
 		if self.dev_cfg.features.contains(virtio::net::F::CSUM)
 			&& self.dev_cfg.features.contains(virtio::net::F::GUEST_CSUM)
 		{
@@ -818,13 +1037,15 @@ impl VirtioNetDriver<Uninit> {
 
 		// Add a control if feature is negotiated
 		if self.dev_cfg.features.contains(virtio::net::F::CTRL_VQ) {
+			// The control queue follows all possible queue pairs, see Virtio specification v1.2 - 5.1.2
+			let index = 2 * max_vq_pairs(&self.dev_cfg);
 			let mut ctrl_vq = if self.dev_cfg.features.contains(virtio::net::F::RING_PACKED) {
 				VirtQueue::Packed(
 					PackedVq::new(
 						&mut self.com_cfg,
 						&self.notif_cfg,
 						VqSize::from(VIRTIO_MAX_QUEUE_SIZE),
-						VqIndex::from(self.num_vqs),
+						VqIndex::from(index),
 						self.dev_cfg.features.into(),
 					)
 					.unwrap(),
@@ -835,7 +1056,7 @@ impl VirtioNetDriver<Uninit> {
 						&mut self.com_cfg,
 						&self.notif_cfg,
 						VqSize::from(VIRTIO_MAX_QUEUE_SIZE),
-						VqIndex::from(self.num_vqs),
+						VqIndex::from(index),
 						self.dev_cfg.features.into(),
 					)
 					.unwrap(),
@@ -859,29 +1080,15 @@ impl VirtioNetDriver<Uninit> {
 		// - the plus 1 is due to the possibility of an existing control queue
 		// - the num_queues is found in the ComCfg struct of the device and defines the maximal number
 		// of supported queues.
-		if self.dev_cfg.features.contains(virtio::net::F::MQ) {
-			if self
-				.dev_cfg
-				.raw
-				.as_ptr()
-				.max_virtqueue_pairs()
-				.read()
-				.to_ne() * 2 >= MAX_NUM_VQ
-			{
-				self.num_vqs = MAX_NUM_VQ;
-			} else {
-				self.num_vqs = self
-					.dev_cfg
-					.raw
-					.as_ptr()
-					.max_virtqueue_pairs()
-					.read()
-					.to_ne() * 2;
-			}
-		} else {
-			// Minimal number of virtqueues defined in the standard v1.1. - 5.1.5 Step 1
-			self.num_vqs = 2;
-		}
+		//
+		// With MQ, one pair of queues per core is used. Otherwise, the device
+		// provides the minimal number of virtqueues defined in the standard
+		// v1.1. - 5.1.5 Step 1.
+		let cores = u16::try_from(crate::arch::kernel::get_possible_cpus()).unwrap_or(u16::MAX);
+		let num_pairs = max_vq_pairs(&self.dev_cfg)
+			.min(cores)
+			.clamp(1, MAX_NUM_VQ / 2);
+		self.num_vqs = 2 * num_pairs;
 
 		// The loop is running from 0 to num_vqs and the indexes are provided to the VqIndex::from function in this way
 		// in order to allow the indexes of the queues to be in a form of:
@@ -920,7 +1127,6 @@ impl VirtioNetDriver<Uninit> {
 				// Interrupt for communicating that a sent packet left, is not needed
 				vq.disable_notifs();
 
-				inner.send_capacity += u32::from(u16::from(vq.size()));
 				inner.send_vqs.add(VirtQueue::Packed(vq));
 			} else {
 				let mut vq = SplitVq::new(
@@ -946,7 +1152,6 @@ impl VirtioNetDriver<Uninit> {
 				.unwrap();
 				// Interrupt for communicating that a sent packet left, is not needed
 				vq.disable_notifs();
-				inner.send_capacity += u32::from(u16::from(vq.size()));
 				inner.send_vqs.add(VirtQueue::Split(vq));
 			}
 		}
@@ -955,9 +1160,33 @@ impl VirtioNetDriver<Uninit> {
 	}
 }
 
+/// Tells the device to use `num_pairs` pairs of queues and returns, whether
+/// it has acknowledged the command, see Virtio specification v1.2 - 5.1.6.5.5
+fn set_vq_pairs(ctrl_vq: &mut VirtQueue, num_pairs: u16) -> bool {
+	let hdr = CtrlHdr {
+		class: VIRTIO_NET_CTRL_MQ,
+		command: VIRTIO_NET_CTRL_MQ_VQ_PAIRS_SET,
+	};
+	let send = SmallVec::from_buf([
+		BufferElem::Sized(Box::new_in(hdr, DeviceAlloc)),
+		BufferElem::Sized(Box::new_in(le16::from_ne(num_pairs), DeviceAlloc)),
+	]);
+	let mut recv = SmallVec::new();
+	recv.push(BufferElem::Sized(Box::<u8, _>::new_uninit_in(DeviceAlloc)));
+	let buffer_tkn = AvailBufferToken::new(send, recv).unwrap();
+
+	let Ok(mut transfer_result) = ctrl_vq.dispatch_blocking(buffer_tkn, BufferType::Direct) else {
+		return false;
+	};
+	// SAFETY: The device writes the acknowledgement as u8.
+	let ack = unsafe { transfer_result.used_recv_buff.pop_front_downcast::<u8>() };
+	ack.is_some_and(|ack| *ack == VIRTIO_NET_OK)
+}
+
 pub mod constants {
 	// Configuration constants
-	pub const MAX_NUM_VQ: u16 = 2;
+	/// Maximum number of receive and send queues
+	pub const MAX_NUM_VQ: u16 = 64;
 	pub(super) const BUFF_PER_PACKET: u16 = 2;
 }
 
@@ -967,7 +1196,6 @@ pub mod error {
 	/// Network drivers error enum.
 	#[derive(Debug, Copy, Clone)]
 	pub enum VirtioNetError {
-		#[cfg(feature = "pci")]
 		NoDevCfg(u16),
 		FailFeatureNeg(u16),
 		/// Set of features does not adhere to the requirements of features
@@ -1,20 +1,25 @@
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::alloc::{Allocator, Layout};
+use core::fmt;
 use core::ptr::NonNull;
 
 use ahash::RandomState;
 use hashbrown::HashMap;
 use hermit_sync::{InterruptTicketMutex, Lazy};
 use memory_addresses::VirtAddr;
+use num_enum::TryFromPrimitive;
 use pci_types::InterruptLine;
 use vroom::{Dma, IoQueuePair, IoQueuePairId, Namespace, NamespaceId, NvmeDevice};
 
 use crate::arch::mm::paging::{BasePageSize, PageSize, virtual_to_physical};
 use crate::arch::pci::PciConfigRegion;
+use crate::arch::processor::get_timer_ticks;
 use crate::drivers::Driver;
 use crate::drivers::pci::PciDevice;
 use crate::mm::device_alloc::DeviceAlloc;
+use crate::mm::dma::{self, DmaBarrier, DmaDirection};
+use crate::scheduler::task::TaskId;
 use crate::syscalls::nvme::SysNvmeError;
 
 pub(crate) struct NvmeDriver {
@@ -23,32 +28,145 @@ pub(crate) struct NvmeDriver {
 	// TODO: Replace with a concurrent hashmap. See crate::synch::futex.
 	io_queue_pairs:
 		Lazy<InterruptTicketMutex<HashMap<IoQueuePairId, IoQueuePair<NvmeAllocator>, RandomState>>>,
+	/// Buffers (address and size) of submitted reads, which have to be
+	/// synchronized for the CPU once the IO is completed
+	pending_reads:
+		Lazy<InterruptTicketMutex<HashMap<IoQueuePairId, Vec<(usize, usize)>, RandomState>>>,
+	/// Owners of the IO queue pairs
+	owners: Lazy<InterruptTicketMutex<HashMap<IoQueuePairId, IoQueuePairOwner, RandomState>>>,
+	/// IO queue pairs, which use hybrid polling
+	hybrid_polling: Lazy<InterruptTicketMutex<HashMap<IoQueuePairId, HybridPolling, RandomState>>>,
+}
+
+/// How the completions of an IO queue pair are awaited
+#[derive(TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum PollingMode {
+	/// The completion queue is polled at once until the commands have
+	/// completed.
+	Classic = 0,
+	/// The task sleeps for half of the mean latency of the previous commands
+	/// first and leaves the core to other tasks. Afterwards, the completion
+	/// queue is polled like in the classic mode.
+	Hybrid = 1,
+}
+
+/// Latencies of an IO queue pair with hybrid polling
+#[derive(Debug, Copy, Clone, Default)]
+struct HybridPolling {
+	/// Time in microseconds of the first submission, which has not been
+	/// completed yet
+	submitted: Option<u64>,
+	/// Moving average of the latencies in microseconds
+	mean_latency: u64,
+}
+
+impl HybridPolling {
+	/// Returns the time in microseconds, for which a task sleeps at `now`,
+	/// which is half of the mean latency minus the time since the submission.
+	fn sleep_time(&self, now: u64) -> u64 {
+		match self.submitted {
+			Some(submitted) => {
+				(self.mean_latency / 2).saturating_sub(now.saturating_sub(submitted))
+			}
+			None => 0,
+		}
+	}
+
+	/// Notes a submission at `now`, unless an earlier one is still pending.
+	fn submit(&mut self, now: u64) {
+		self.submitted.get_or_insert(now);
+	}
+
+	/// Notes the completion of all pending submissions at `now` and adds
+	/// their latency to the moving average.
+	fn complete(&mut self, now: u64) {
+		if let Some(submitted) = self.submitted.take() {
+			let latency = now.saturating_sub(submitted);
+			self.mean_latency = if self.mean_latency == 0 {
+				latency
+			} else {
+				(self.mean_latency * 7 + latency) / 8
+			};
+		}
+	}
+}
+
+/// Owner of an IO queue pair
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum IoQueuePairOwner {
+	/// The queue pair is used by the kernel, e.g., for a block device, and
+	/// cannot be accessed through system calls.
+	Kernel,
+	/// The queue pair has been created by a task through a system call.
+	///
+	/// Unless it is shared, only this task may use or delete it.
+	Task { id: TaskId, shared: bool },
+	/// The owning task has finished, while it held the queue pair.
+	///
+	/// The pair is reclaimed with the next creation of a queue pair.
+	Orphaned,
+}
+
+/// Returns the memory of `buffer` for cache maintenance.
+fn dma_region<T>(buffer: &Dma<T>) -> (*const u8, usize) {
+	(buffer.virt.cast_const().cast(), buffer.size)
+}
+
+/// Reasons, why the NVMe driver could not be initialized
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum NvmeInitError {
+	/// The memory BAR of the controller could not be mapped.
+	NoBar,
+	/// The controller did not come up.
+	Controller,
+	/// The device has no interrupt line.
+	NoIrq,
+}
+
+impl fmt::Display for NvmeInitError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NoBar => write!(f, "cannot map BAR 0"),
+			Self::Controller => write!(f, "controller initialization failed"),
+			Self::NoIrq => write!(f, "no interrupt line"),
+		}
+	}
 }
 
 impl NvmeDriver {
-	pub(crate) fn init(pci_device: &PciDevice<PciConfigRegion>) -> Result<Self, ()> {
+	pub(crate) fn init(pci_device: &PciDevice<PciConfigRegion>) -> Result<Self, NvmeInitError> {
 		let allocator: NvmeAllocator = NvmeAllocator {
 			device_allocator: DeviceAlloc {},
 			allocations: Lazy::new(|| {
 				InterruptTicketMutex::new(HashMap::with_hasher(RandomState::with_seeds(0, 0, 0, 0)))
 			}),
 		};
-		let (virtual_address, size) = pci_device.memory_map_bar(0, true).ok_or(())?;
+		let (virtual_address, size) = pci_device
+			.memory_map_bar(0, true, "nvme")
+			.ok_or(NvmeInitError::NoBar)?;
 		let nvme_device: NvmeDevice<NvmeAllocator> = NvmeDevice::new(
 			virtual_address.as_mut_ptr(),
 			size,
 			BasePageSize::SIZE as usize,
 			allocator,
 		)
-		.map_err(|_| ())?;
+		.map_err(|_| NvmeInitError::Controller)?;
 		let driver = Self {
-			irq: pci_device
-				.get_irq()
-				.expect("NVMe driver: Could not get irq from device."),
+			irq: pci_device.get_irq().ok_or(NvmeInitError::NoIrq)?,
 			device: InterruptTicketMutex::new(nvme_device),
 			io_queue_pairs: Lazy::new(|| {
 				InterruptTicketMutex::new(HashMap::with_hasher(RandomState::with_seeds(0, 0, 0, 0)))
 			}),
+			pending_reads: Lazy::new(|| {
+				InterruptTicketMutex::new(HashMap::with_hasher(RandomState::with_seeds(0, 0, 0, 0)))
+			}),
+			owners: Lazy::new(|| {
+				InterruptTicketMutex::new(HashMap::with_hasher(RandomState::with_seeds(0, 0, 0, 0)))
+			}),
+			hybrid_polling: Lazy::new(|| {
+				InterruptTicketMutex::new(HashMap::with_hasher(RandomState::with_seeds(0, 0, 0, 0)))
+			}),
 		};
 		Ok(driver)
 	}
@@ -98,7 +216,9 @@ impl NvmeDriver {
 		&mut self,
 		namespace_id: &NamespaceId,
 		number_of_entries: u32,
+		owner: IoQueuePairOwner,
 	) -> Result<IoQueuePairId, SysNvmeError> {
+		self.reclaim_orphaned_io_queue_pairs();
 		let mut device = self.device.lock();
 		if !device.namespace_ids().contains(namespace_id) {
 			return Err(SysNvmeError::NamespaceDoesNotExist);
@@ -117,9 +237,46 @@ impl NvmeDriver {
 			.map_err(|_| SysNvmeError::CouldNotCreateIoQueuePair)?;
 		let id = io_queue_pair.id();
 		io_queue_pairs.insert(id, io_queue_pair);
+		self.owners.lock().insert(id, owner);
 		Ok(id)
 	}
 
+	/// Checks that the task `task_id` may use the IO queue pair with ID `io_queue_pair_id`.
+	pub(crate) fn check_access(
+		&self,
+		io_queue_pair_id: &IoQueuePairId,
+		task_id: TaskId,
+	) -> Result<(), SysNvmeError> {
+		let owners = self.owners.lock();
+		let owner = owners
+			.get(io_queue_pair_id)
+			.ok_or(SysNvmeError::CouldNotFindIoQueuePair)?;
+		match *owner {
+			IoQueuePairOwner::Task { id, shared } if shared || id == task_id => Ok(()),
+			_ => Err(SysNvmeError::IoQueuePairNotOwned),
+		}
+	}
+
+	/// Allows other tasks than the owner `task_id` to use the IO queue pair, if `shared` is true.
+	pub(crate) fn share_io_queue_pair(
+		&self,
+		io_queue_pair_id: &IoQueuePairId,
+		task_id: TaskId,
+		shared: bool,
+	) -> Result<(), SysNvmeError> {
+		let mut owners = self.owners.lock();
+		let owner = owners
+			.get_mut(io_queue_pair_id)
+			.ok_or(SysNvmeError::CouldNotFindIoQueuePair)?;
+		match owner {
+			IoQueuePairOwner::Task { id, shared: old } if *id == task_id => {
+				*old = shared;
+				Ok(())
+			}
+			_ => Err(SysNvmeError::IoQueuePairNotOwned),
+		}
+	}
+
 	/// Deletes an IO queue pair and frees its resources.
 	pub(crate) fn delete_io_queue_pair(
 		&mut self,
@@ -131,11 +288,110 @@ impl NvmeDriver {
 			.lock()
 			.remove(&io_queue_pair_id)
 			.ok_or(SysNvmeError::CouldNotFindIoQueuePair)?;
+		self.pending_reads.lock().remove(&io_queue_pair_id);
+		self.owners.lock().remove(&io_queue_pair_id);
+		self.hybrid_polling.lock().remove(&io_queue_pair_id);
 		device
 			.delete_io_queue_pair(io_queue_pair)
 			.map_err(|_error| SysNvmeError::CouldNotDeleteIoQueuePair)
 	}
 
+	/// Selects, how the completions of the IO queue pair with ID
+	/// `io_queue_pair_id` are awaited.
+	pub(crate) fn set_polling_mode(
+		&self,
+		io_queue_pair_id: &IoQueuePairId,
+		mode: PollingMode,
+	) -> Result<(), SysNvmeError> {
+		if !self.io_queue_pairs.lock().contains_key(io_queue_pair_id) {
+			return Err(SysNvmeError::CouldNotFindIoQueuePair);
+		}
+		let mut hybrid_polling = self.hybrid_polling.lock();
+		match mode {
+			PollingMode::Classic => {
+				hybrid_polling.remove(io_queue_pair_id);
+			}
+			PollingMode::Hybrid => {
+				hybrid_polling.entry(*io_queue_pair_id).or_default();
+			}
+		}
+		Ok(())
+	}
+
+	/// Returns the polling mode of the IO queue pair with ID `io_queue_pair_id`.
+	pub(crate) fn polling_mode(&self, io_queue_pair_id: &IoQueuePairId) -> PollingMode {
+		if self.hybrid_polling.lock().contains_key(io_queue_pair_id) {
+			PollingMode::Hybrid
+		} else {
+			PollingMode::Classic
+		}
+	}
+
+	/// Returns the time in microseconds, for which a task sleeps, before it
+	/// polls the completions of the IO queue pair with ID `io_queue_pair_id`.
+	///
+	/// This is half of the mean latency minus the time since the submission,
+	/// or 0 without hybrid polling.
+	pub(crate) fn hybrid_sleep_time(&self, io_queue_pair_id: &IoQueuePairId) -> u64 {
+		self.hybrid_polling
+			.lock()
+			.get(io_queue_pair_id)
+			.map_or(0, |polling| polling.sleep_time(get_timer_ticks()))
+	}
+
+	/// Notes the submission of a command for hybrid polling.
+	fn note_submission(&self, io_queue_pair_id: &IoQueuePairId) {
+		if let Some(polling) = self.hybrid_polling.lock().get_mut(io_queue_pair_id) {
+			polling.submit(get_timer_ticks());
+		}
+	}
+
+	/// Notes the completion of all submitted commands for hybrid polling.
+	fn note_completion(&self, io_queue_pair_id: &IoQueuePairId) {
+		if let Some(polling) = self.hybrid_polling.lock().get_mut(io_queue_pair_id) {
+			polling.complete(get_timer_ticks());
+		}
+	}
+
+	/// Marks the IO queue pairs, which the finished task `task_id` has not
+	/// shared, as orphaned.
+	///
+	/// This runs, while the task exits with interrupts disabled, and therefore
+	/// does not wait for the device. Shared pairs are kept for the other tasks.
+	pub(crate) fn orphan_io_queue_pairs(&self, task_id: TaskId) {
+		for owner in self.owners.lock().values_mut() {
+			if matches!(*owner, IoQueuePairOwner::Task { id, shared: false } if id == task_id) {
+				debug!("NVMe driver: orphan IO queue pair of task {task_id}");
+				*owner = IoQueuePairOwner::Orphaned;
+			}
+		}
+	}
+
+	/// Completes the outstanding commands of the orphaned IO queue pairs and
+	/// deletes these pairs.
+	fn reclaim_orphaned_io_queue_pairs(&mut self) {
+		let orphaned: Vec<IoQueuePairId> = self
+			.owners
+			.lock()
+			.iter()
+			.filter(|(_, owner)| **owner == IoQueuePairOwner::Orphaned)
+			.map(|(io_queue_pair_id, _)| *io_queue_pair_id)
+			.collect();
+
+		for io_queue_pair_id in orphaned {
+			debug!("NVMe driver: reclaim orphaned IO queue pair");
+			if self
+				.complete_io_with_io_queue_pair(&io_queue_pair_id)
+				.is_err()
+			{
+				warn!("NVMe driver: IO of an orphaned queue pair did not complete");
+			}
+			if self.delete_io_queue_pair(io_queue_pair_id).is_err() {
+				warn!("NVMe driver: could not delete an orphaned IO queue pair");
+			}
+		}
+	}
+
 	pub(crate) fn allocate_buffer<T>(
 		&self,
 		io_queue_pair_id: &IoQueuePairId,
@@ -176,9 +432,16 @@ impl NvmeDriver {
 		let io_queue_pair = io_queue_pairs
 			.get_mut(io_queue_pair_id)
 			.ok_or(SysNvmeError::CouldNotFindIoQueuePair)?;
+		let (ptr, len) = dma_region(buffer);
+		dma::sync_for_device(ptr, len, DmaDirection::FromDevice);
+		// vroom does not order the writes of the buffer and of the command
+		// before its write of the doorbell.
+		dma::dma_barrier(DmaBarrier::Doorbell);
 		io_queue_pair
 			.read(buffer, logical_block_address)
 			.map_err(|_error| SysNvmeError::CouldNotReadFromIoQueuePair)?;
+		dma::dma_barrier(DmaBarrier::Read);
+		dma::sync_for_cpu(ptr, len, DmaDirection::FromDevice);
 		Ok(())
 	}
 
@@ -194,6 +457,9 @@ impl NvmeDriver {
 		let io_queue_pair = io_queue_pairs
 			.get_mut(io_queue_pair_id)
 			.ok_or(SysNvmeError::CouldNotFindIoQueuePair)?;
+		let (ptr, len) = dma_region(buffer);
+		dma::sync_for_device(ptr, len, DmaDirection::ToDevice);
+		dma::dma_barrier(DmaBarrier::Doorbell);
 		io_queue_pair
 			.write(buffer, logical_block_address)
 			.map_err(|_error| SysNvmeError::CouldNotWriteToIoQueuePair)?;
@@ -212,9 +478,18 @@ impl NvmeDriver {
 		let io_queue_pair = io_queue_pairs
 			.get_mut(io_queue_pair_id)
 			.ok_or(SysNvmeError::CouldNotFindIoQueuePair)?;
+		let (ptr, len) = dma_region(buffer);
+		dma::sync_for_device(ptr, len, DmaDirection::FromDevice);
+		dma::dma_barrier(DmaBarrier::Doorbell);
 		io_queue_pair
 			.submit_read(buffer, logical_block_address)
 			.map_err(|_error| SysNvmeError::CouldNotReadFromIoQueuePair)?;
+		self.note_submission(io_queue_pair_id);
+		self.pending_reads
+			.lock()
+			.entry(*io_queue_pair_id)
+			.or_default()
+			.push((ptr.addr(), len));
 		Ok(())
 	}
 
@@ -230,12 +505,31 @@ impl NvmeDriver {
 		let io_queue_pair = io_queue_pairs
 			.get_mut(io_queue_pair_id)
 			.ok_or(SysNvmeError::CouldNotFindIoQueuePair)?;
+		let (ptr, len) = dma_region(buffer);
+		dma::sync_for_device(ptr, len, DmaDirection::ToDevice);
+		dma::dma_barrier(DmaBarrier::Doorbell);
 		io_queue_pair
 			.submit_write(buffer, logical_block_address)
 			.map_err(|_error| SysNvmeError::CouldNotReadFromIoQueuePair)?;
+		self.note_submission(io_queue_pair_id);
 		Ok(())
 	}
 
+	/// Flushes the volatile write cache of the namespace of the IO queue pair
+	/// with ID `io_queue_pair_id`, so that all completed writes reach stable storage.
+	pub(crate) fn flush_io_queue_pair(
+		&mut self,
+		io_queue_pair_id: &IoQueuePairId,
+	) -> Result<(), SysNvmeError> {
+		let mut io_queue_pairs = self.io_queue_pairs.lock();
+		let io_queue_pair = io_queue_pairs
+			.get_mut(io_queue_pair_id)
+			.ok_or(SysNvmeError::CouldNotFindIoQueuePair)?;
+		io_queue_pair
+			.flush()
+			.map_err(|_error| SysNvmeError::CouldNotFlushIoQueuePair)
+	}
+
 	pub(crate) fn complete_io_with_io_queue_pair(
 		&mut self,
 		io_queue_pair_id: &IoQueuePairId,
@@ -247,6 +541,18 @@ impl NvmeDriver {
 		io_queue_pair
 			.complete_io()
 			.map_err(|_error| SysNvmeError::CouldNotReadFromIoQueuePair)?;
+		self.note_completion(io_queue_pair_id);
+		// The data must not be read before the completions.
+		dma::dma_barrier(DmaBarrier::Read);
+		if let Some(reads) = self.pending_reads.lock().remove(io_queue_pair_id) {
+			for (addr, len) in reads {
+				dma::sync_for_cpu(
+					core::ptr::with_exposed_provenance(addr),
+					len,
+					DmaDirection::FromDevice,
+				);
+			}
+		}
 		Ok(())
 	}
 }
@@ -319,3 +625,49 @@ impl Driver for NvmeDriver {
 		"nvme"
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_hybrid_polling_mean_latency() {
+		let mut polling = HybridPolling::default();
+		assert_eq!(polling.sleep_time(100), 0);
+
+		// The first latency initializes the mean.
+		polling.submit(100);
+		polling.complete(180);
+		assert_eq!(polling.mean_latency, 80);
+		assert_eq!(polling.submitted, None);
+
+		// Later latencies are weighted with 1/8.
+		polling.submit(1000);
+		polling.submit(1010);
+		polling.complete(1160);
+		assert_eq!(polling.mean_latency, (80 * 7 + 160) / 8);
+
+		// A completion without a submission does not change the mean.
+		polling.complete(2000);
+		assert_eq!(polling.mean_latency, 90);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_hybrid_polling_sleep_time() {
+		let mut polling = HybridPolling {
+			submitted: None,
+			mean_latency: 100,
+		};
+		assert_eq!(polling.sleep_time(0), 0);
+
+		polling.submit(1000);
+		assert_eq!(polling.sleep_time(1000), 50);
+		assert_eq!(polling.sleep_time(1030), 20);
+		assert_eq!(polling.sleep_time(1050), 0);
+		assert_eq!(polling.sleep_time(5000), 0);
+		// A clock, which seems to run backwards, does not prolong the sleep.
+		assert_eq!(polling.sleep_time(900), 50);
+	}
+}
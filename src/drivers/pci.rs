@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
 use alloc::collections::VecDeque;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::fmt;
 
@@ -28,47 +29,71 @@ use crate::arch::pci::PciConfigRegion;
 use crate::console::IoDevice;
 #[cfg(feature = "console")]
 use crate::drivers::console::{VirtioConsoleDriver, VirtioUART};
+#[cfg(any(
+	feature = "nvme",
+	all(target_arch = "x86_64", feature = "rtl8139"),
+	feature = "e1000",
+))]
+use crate::drivers::failure::{self, DeviceClass};
 #[cfg(feature = "fuse")]
 use crate::drivers::fs::virtio_fs::VirtioFsDriver;
+#[cfg(all(
+	feature = "e1000",
+	not(all(target_arch = "x86_64", feature = "rtl8139")),
+))]
+use crate::drivers::net::e1000::{self, E1000Driver};
 #[cfg(all(target_arch = "x86_64", feature = "rtl8139"))]
 use crate::drivers::net::rtl8139::{self, RTL8139Driver};
 #[cfg(all(
 	not(all(target_arch = "x86_64", feature = "rtl8139")),
+	not(feature = "e1000"),
 	feature = "virtio-net",
 ))]
 use crate::drivers::net::virtio::VirtioNetDriver;
 #[cfg(feature = "nvme")]
-use crate::drivers::nvme::NvmeDriver;
+use crate::drivers::nvme::{NvmeDriver, NvmeInitError};
 #[cfg(any(
 	all(
 		feature = "virtio-net",
 		not(all(target_arch = "x86_64", feature = "rtl8139")),
+		not(feature = "e1000"),
 	),
 	feature = "fuse",
+	feature = "virtio-9p",
 	feature = "vsock",
 	feature = "console",
 ))]
-use crate::drivers::virtio::transport::pci as pci_virtio;
+use crate::drivers::virtio::transport::VirtioDriver;
 #[cfg(any(
 	all(
 		feature = "virtio-net",
 		not(all(target_arch = "x86_64", feature = "rtl8139")),
+		not(feature = "e1000"),
 	),
 	feature = "fuse",
+	feature = "virtio-9p",
 	feature = "vsock",
 	feature = "console",
 ))]
-use crate::drivers::virtio::transport::pci::VirtioDriver;
+use crate::drivers::virtio::transport::pci as pci_virtio;
 #[cfg(feature = "vsock")]
 use crate::drivers::vsock::VirtioVsockDriver;
 #[allow(unused_imports)]
 use crate::drivers::{Driver, InterruptHandlerQueue};
+#[cfg(any(
+	feature = "nvme",
+	all(target_arch = "x86_64", feature = "rtl8139"),
+	feature = "e1000",
+))]
+use crate::errno::Errno;
 #[cfg(any(
 	all(target_arch = "x86_64", feature = "rtl8139"),
+	feature = "e1000",
 	feature = "virtio-net",
 ))]
-use crate::executor::device::NETWORK_DEVICE;
+use crate::executor::device::NETWORK_DEVICES;
 use crate::init_cell::InitCell;
+use crate::mm::mmio::{self, MmioAttributes, MmioRegion};
 
 pub(crate) static PCI_DEVICES: InitCell<Vec<PciDevice<PciConfigRegion>>> =
 	InitCell::new(Vec::new());
@@ -144,9 +169,17 @@ impl<T: ConfigRegionAccess> PciDevice<T> {
 
 	/// Memory maps pci bar with specified index to identical location in virtual memory.
 	/// no_cache determines if we set the `Cache Disable` flag in the page-table-entry.
-	/// Returns (virtual-pointer, size) if successful, else None (if bar non-existent or IOSpace)
-	pub fn memory_map_bar(&self, index: u8, no_cache: bool) -> Option<(VirtAddr, usize)> {
-		let (address, size, prefetchable, width) = match self.get_bar(index) {
+	/// owner names the subsystem, which uses the bar. If the same bar has already been
+	/// mapped for this subsystem, the existing mapping is returned.
+	/// Returns (virtual-pointer, size) if successful, else None (if bar non-existent, IOSpace,
+	/// or already mapped by another subsystem)
+	pub fn memory_map_bar(
+		&self,
+		index: u8,
+		no_cache: bool,
+		owner: &'static str,
+	) -> Option<(VirtAddr, usize)> {
+		let (address, size, prefetchable) = match self.get_bar(index) {
 			Some(Bar::Io { .. }) => {
 				warn!("Cannot map IOBar!");
 				return None;
@@ -159,13 +192,12 @@ impl<T: ConfigRegionAccess> PciDevice<T> {
 				u64::from(address),
 				usize::try_from(size).unwrap(),
 				prefetchable,
-				32,
 			),
 			Some(Bar::Memory64 {
 				address,
 				size,
 				prefetchable,
-			}) => (address, usize::try_from(size).unwrap(), prefetchable, 64),
+			}) => (address, usize::try_from(size).unwrap(), prefetchable),
 			_ => {
 				return None;
 			}
@@ -177,10 +209,6 @@ impl<T: ConfigRegionAccess> PciDevice<T> {
 
 		debug!("Mapping bar {index} at {address:#x} with length {size:#x}");
 
-		if width != 64 {
-			warn!("Currently only mapping of 64 bit bars is supported!");
-			return None;
-		}
 		if !prefetchable {
 			warn!("Currently only mapping of prefetchable bars is supported!");
 		}
@@ -188,11 +216,25 @@ impl<T: ConfigRegionAccess> PciDevice<T> {
 		// Since the bios/bootloader manages the physical address space, the address got from the bar is unique and not overlapping.
 		// We therefore do not need to reserve any additional memory in our kernel.
 		// Map bar into RW^X virtual memory
-		let physical_address = address;
-		let virtual_address =
-			crate::mm::map(PhysAddr::new(physical_address), size, true, true, no_cache);
-
-		Some((virtual_address, size))
+		let mut attributes = MmioAttributes::WRITABLE | MmioAttributes::EXECUTE_DISABLE;
+		attributes.set(MmioAttributes::NO_CACHE, no_cache);
+		let region = MmioRegion {
+			owner,
+			device: self.address.to_string(),
+			bar: Some(index),
+			phys_addr: PhysAddr::new(address),
+			size,
+			attributes,
+		};
+		match mmio::map(region, |region| {
+			crate::mm::map(region.phys_addr, region.size, true, true, no_cache)
+		}) {
+			Ok(virtual_address) => Some((virtual_address, size)),
+			Err(conflict) => {
+				error!("{conflict}");
+				None
+			}
+		}
 	}
 
 	pub fn get_irq(&self) -> Option<InterruptLine> {
@@ -407,7 +449,11 @@ impl PciDriver {
 			}
 			#[cfg(feature = "fuse")]
 			Self::VirtioFs(drv) => {
-				fn fuse_handler() {}
+				fn fuse_handler() {
+					if let Some(driver) = get_filesystem_driver() {
+						driver.lock().handle_interrupt();
+					}
+				}
 
 				let irq_number = drv.lock().get_interrupt_number();
 
@@ -471,15 +517,27 @@ pub(crate) fn get_interrupt_handlers() -> HashMap<InterruptLine, InterruptHandle
 		}
 	}
 
+	#[cfg(all(target_arch = "x86_64", feature = "acpi"))]
+	if let Some((irq_number, handler)) = crate::arch::kernel::acpi::get_power_button_handler() {
+		handlers.entry(irq_number).or_default().push_back(handler);
+	}
+
 	#[cfg(any(
 		all(target_arch = "x86_64", feature = "rtl8139"),
+		feature = "e1000",
 		feature = "virtio-net",
 	))]
-	if let Some(device) = NETWORK_DEVICE.lock().as_ref() {
-		handlers
-			.entry(device.get_interrupt_number())
-			.or_default()
-			.push_back(crate::executor::network::network_handler);
+	for device in NETWORK_DEVICES.lock().iter() {
+		// The handler serves all network cards, it is registered once per line.
+		let queue = handlers.entry(device.get_interrupt_number()).or_default();
+		crate::drivers::irq_affinity::pin(device.get_interrupt_number());
+		let handler: fn() = crate::executor::network::network_handler;
+		if !queue
+			.iter()
+			.any(|registered| core::ptr::fn_addr_eq(*registered, handler))
+		{
+			queue.push_back(handler);
+		}
 	}
 
 	handlers
@@ -487,6 +545,7 @@ pub(crate) fn get_interrupt_handlers() -> HashMap<InterruptLine, InterruptHandle
 
 #[cfg(all(
 	not(all(target_arch = "x86_64", feature = "rtl8139")),
+	not(feature = "e1000"),
 	feature = "virtio-net",
 ))]
 pub(crate) type NetworkDevice = VirtioNetDriver;
@@ -494,6 +553,12 @@ pub(crate) type NetworkDevice = VirtioNetDriver;
 #[cfg(all(target_arch = "x86_64", feature = "rtl8139"))]
 pub(crate) type NetworkDevice = RTL8139Driver;
 
+#[cfg(all(
+	feature = "e1000",
+	not(all(target_arch = "x86_64", feature = "rtl8139")),
+))]
+pub(crate) type NetworkDevice = E1000Driver;
+
 #[cfg(feature = "console")]
 pub(crate) fn get_console_driver() -> Option<&'static InterruptTicketMutex<VirtioConsoleDriver>> {
 	PCI_DRIVERS
@@ -542,17 +607,20 @@ pub(crate) fn init() {
 				all(
 					feature = "virtio-net",
 					not(all(target_arch = "x86_64", feature = "rtl8139")),
+					not(feature = "e1000"),
 				),
 				feature = "fuse",
+				feature = "virtio-9p",
 				feature = "vsock",
 				feature = "console",
 			))]
 			match pci_virtio::init_device(adapter) {
 				#[cfg(all(
 					not(all(target_arch = "x86_64", feature = "rtl8139")),
+					not(feature = "e1000"),
 					feature = "virtio-net",
 				))]
-				Ok(VirtioDriver::Network(drv)) => *crate::executor::device::NETWORK_DEVICE.lock() = Some(drv),
+				Ok(VirtioDriver::Network(drv)) => crate::executor::device::NETWORK_DEVICES.lock().push(drv),
 
 				#[cfg(feature = "console")]
 				Ok(VirtioDriver::Console(drv)) => {
@@ -570,6 +638,10 @@ pub(crate) fn init() {
 				Ok(VirtioDriver::FileSystem(drv)) => {
 					register_driver(PciDriver::VirtioFs(InterruptTicketMutex::new(drv)));
 				}
+				#[cfg(feature = "virtio-9p")]
+				Ok(VirtioDriver::NineP(drv)) => {
+					crate::drivers::fs::virtio_9p::register_driver(drv);
+				}
 				_ => {}
 			}
 		}
@@ -591,11 +663,12 @@ pub(crate) fn init() {
 					info!("NVMe driver initialized.");
 					register_driver(PciDriver::Nvme(InterruptTicketMutex::new(nvme_driver)));
 				}
-				Err(()) => {
-					error!(
-						"NVMe driver could not be initialized for device: {:#x}",
-						adapter.device_id()
-					);
+				Err(err) => {
+					let errno = match err {
+						NvmeInitError::Controller => Errno::Io,
+						NvmeInitError::NoBar | NvmeInitError::NoIrq => Errno::Nodev,
+					};
+					failure::record(DeviceClass::Block, adapter.device_id(), errno, err);
 				}
 			}
 		}
@@ -611,8 +684,34 @@ pub(crate) fn init() {
 				adapter.device_id()
 			);
 
-			if let Ok(drv) = rtl8139::init_device(adapter) {
-				*crate::executor::device::NETWORK_DEVICE.lock() = Some(drv);
+			match rtl8139::init_device(adapter) {
+				Ok(drv) => crate::executor::device::NETWORK_DEVICES.lock().push(drv),
+				Err(err) => {
+					failure::record(DeviceClass::Network, adapter.device_id(), Errno::Io, err);
+				}
+			}
+		}
+
+		// Searching for Intel network cards of the e1000 family, which are
+		// emulated by Qemu as `e1000` and `e1000e`
+		#[cfg(all(
+			feature = "e1000",
+			not(all(target_arch = "x86_64", feature = "rtl8139")),
+		))]
+		for adapter in PCI_DEVICES.finalize().iter().filter(|x| {
+			let (vendor_id, device_id) = x.id();
+			vendor_id == 0x8086 && e1000::DEVICE_IDS.contains(&device_id)
+		}) {
+			info!(
+				"Found Intel network device with device id {:#x}",
+				adapter.device_id()
+			);
+
+			match e1000::init_device(adapter) {
+				Ok(drv) => crate::executor::device::NETWORK_DEVICES.lock().push(drv),
+				Err(err) => {
+					failure::record(DeviceClass::Network, adapter.device_id(), Errno::Io, err);
+				}
 			}
 		}
 	});
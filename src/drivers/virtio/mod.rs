@@ -9,11 +9,14 @@ pub mod error {
 
 	#[cfg(feature = "console")]
 	pub use crate::drivers::console::error::VirtioConsoleError;
+	#[cfg(feature = "virtio-9p")]
+	pub use crate::drivers::fs::virtio_9p::error::Virtio9pError;
 	#[cfg(feature = "fuse")]
 	pub use crate::drivers::fs::virtio_fs::error::VirtioFsError;
 	#[cfg(all(
 		not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
 		not(all(target_arch = "x86_64", feature = "rtl8139")),
+		not(feature = "e1000"),
 		feature = "virtio-net",
 	))]
 	pub use crate::drivers::net::virtio::error::VirtioNetError;
@@ -37,24 +40,23 @@ pub mod error {
 		#[cfg(all(
 			not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
 			not(all(target_arch = "x86_64", feature = "rtl8139")),
+			not(feature = "e1000"),
 			feature = "virtio-net",
 		))]
 		NetDriver(VirtioNetError),
 		#[cfg(feature = "fuse")]
 		FsDriver(VirtioFsError),
+		#[cfg(feature = "virtio-9p")]
+		NinePDriver(Virtio9pError),
 		#[cfg(feature = "vsock")]
 		VsockDriver(VirtioVsockError),
 		#[cfg(feature = "console")]
 		ConsoleDriver(VirtioConsoleError),
-		#[cfg(not(feature = "pci"))]
-		Unknown,
 	}
 
 	impl fmt::Display for VirtioError {
 		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 			match self {
-				#[cfg(not(feature = "pci"))]
-				VirtioError::Unknown => write!(f, "Driver failure"),
 				#[cfg(feature = "pci")]
 				VirtioError::FromPci(pci_error) => match pci_error {
 					PciError::General(id) => write!(
@@ -95,10 +97,10 @@ pub mod error {
 				#[cfg(all(
 					not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
 					not(all(target_arch = "x86_64", feature = "rtl8139")),
+					not(feature = "e1000"),
 					feature = "virtio-net",
 				))]
 				VirtioError::NetDriver(net_error) => match net_error {
-					#[cfg(feature = "pci")]
 					VirtioNetError::NoDevCfg(id) => write!(
 						f,
 						"Virtio network driver failed, for device {id:x}, due to a missing or malformed device config!"
@@ -120,7 +122,6 @@ pub mod error {
 				},
 				#[cfg(feature = "fuse")]
 				VirtioError::FsDriver(fs_error) => match fs_error {
-					#[cfg(feature = "pci")]
 					VirtioFsError::NoDevCfg(id) => write!(
 						f,
 						"Virtio filesystem driver failed, for device {id:x}, due to a missing or malformed device config!"
@@ -144,9 +145,29 @@ pub mod error {
 						"Virtio filesystem failed, driver failed due unknown reason!"
 					),
 				},
+				#[cfg(feature = "virtio-9p")]
+				VirtioError::NinePDriver(p9_error) => match p9_error {
+					Virtio9pError::NoDevCfg(id) => write!(
+						f,
+						"Virtio 9P driver failed, for device {id:x}, due to a missing or malformed device config!"
+					),
+					Virtio9pError::NoMountTag(id) => write!(
+						f,
+						"Virtio 9P driver failed, for device {id:x}, device does not provide a mount tag!"
+					),
+					Virtio9pError::FailFeatureNeg(id) => write!(
+						f,
+						"Virtio 9P driver failed, for device {id:x}, device did not acknowledge negotiated feature set!"
+					),
+					Virtio9pError::IncompatibleFeatureSets(driver_features, device_features) => {
+						write!(
+							f,
+							"Feature set: {driver_features:?} , is incompatible with the device features: {device_features:?}",
+						)
+					}
+				},
 				#[cfg(feature = "console")]
 				VirtioError::ConsoleDriver(console_error) => match console_error {
-					#[cfg(feature = "pci")]
 					VirtioConsoleError::NoDevCfg(id) => write!(
 						f,
 						"Virtio console device driver failed, for device {id:x}, due to a missing or malformed device config!"
@@ -171,22 +192,18 @@ pub mod error {
 				},
 				#[cfg(feature = "vsock")]
 				VirtioError::VsockDriver(vsock_error) => match vsock_error {
-					#[cfg(feature = "pci")]
 					VirtioVsockError::NoDevCfg(id) => write!(
 						f,
 						"Virtio socket device driver failed, for device {id:x}, due to a missing or malformed device config!"
 					),
-					#[cfg(feature = "pci")]
 					VirtioVsockError::NoComCfg(id) => write!(
 						f,
 						"Virtio socket device driver failed, for device {id:x}, due to a missing or malformed common config!"
 					),
-					#[cfg(feature = "pci")]
 					VirtioVsockError::NoIsrCfg(id) => write!(
 						f,
 						"Virtio socket device driver failed, for device {id:x}, due to a missing or malformed ISR status config!"
 					),
-					#[cfg(feature = "pci")]
 					VirtioVsockError::NoNotifCfg(id) => write!(
 						f,
 						"Virtio socket device driver failed, for device {id:x}, due to a missing or malformed notification config!"
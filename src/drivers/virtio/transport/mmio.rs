@@ -1,10 +1,8 @@
-//! A module containing all virtio specific pci functionality
+//! A module containing all virtio specific mmio functionality
 //!
 //! The module contains ...
 #![allow(dead_code)]
 
-#[cfg(feature = "console")]
-use alloc::boxed::Box;
 use core::mem;
 
 use memory_addresses::PhysAddr;
@@ -17,12 +15,10 @@ use volatile::access::ReadOnly;
 use volatile::{VolatilePtr, VolatileRef};
 
 use crate::drivers::InterruptLine;
-#[cfg(feature = "console")]
-use crate::drivers::console::VirtioConsoleDriver;
 use crate::drivers::error::DriverError;
-#[cfg(feature = "virtio-net")]
-use crate::drivers::net::virtio::VirtioNetDriver;
 use crate::drivers::virtio::error::VirtioError;
+use crate::drivers::virtio::transport::{Transport, VirtioDriver};
+use crate::mm::dma::{DmaBarrier, dma_barrier};
 
 pub struct VqCfgHandler<'a> {
 	vq_index: u16,
@@ -328,6 +324,7 @@ impl NotifCtrl {
 			u32::from(data.vqn()).into()
 		};
 
+		dma_barrier(DmaBarrier::Doorbell);
 		unsafe {
 			self.notif_addr.write_volatile(notification_data);
 		}
@@ -363,14 +360,63 @@ impl IsrStatus {
 	}
 }
 
-pub(crate) enum VirtioDriver {
-	#[cfg(feature = "virtio-net")]
-	Network(VirtioNetDriver),
-	#[cfg(feature = "console")]
-	Console(Box<VirtioConsoleDriver>),
+/// MMIO transport of a virtio device.
+///
+/// Wraps the register block of the device together with its interrupt line.
+pub(crate) struct MmioTransport {
+	registers: VolatileRef<'static, DeviceRegisters>,
+	dev_id: u16,
+	irq: InterruptLine,
+}
+
+impl MmioTransport {
+	pub(crate) fn new(
+		registers: VolatileRef<'static, DeviceRegisters>,
+		dev_id: u16,
+		irq: InterruptLine,
+	) -> Self {
+		Self {
+			registers,
+			dev_id,
+			irq,
+		}
+	}
+}
+
+impl Transport for MmioTransport {
+	fn dev_id(&self) -> u16 {
+		self.dev_id
+	}
+
+	fn irq(&self) -> InterruptLine {
+		self.irq
+	}
+
+	fn map_dev_cfg<T>(&mut self) -> Option<&'static mut T> {
+		let dev_cfg = unsafe {
+			&mut *self
+				.registers
+				.borrow_mut()
+				.as_mut_ptr()
+				.config()
+				.as_raw_ptr()
+				.cast::<T>()
+				.as_ptr()
+		};
+
+		Some(dev_cfg)
+	}
+
+	fn into_cfgs(mut self) -> (ComCfg, NotifCfg, IsrStatus) {
+		let isr_stat = IsrStatus::new(self.registers.borrow_mut());
+		let notif_cfg = NotifCfg::new(self.registers.borrow_mut());
+		let mut com_cfg = ComCfg::new(self.registers, 1);
+		com_cfg.print_information();
+
+		(com_cfg, notif_cfg, isr_stat)
+	}
 }
 
-#[allow(unused_variables)]
 pub(crate) fn init_device(
 	registers: VolatileRef<'static, DeviceRegisters>,
 	irq_no: InterruptLine,
@@ -384,59 +430,8 @@ pub(crate) fn init_device(
 		));
 	}
 
-	// Verify the device-ID to find the network card
-	match registers.as_ptr().device_id().read() {
-		#[cfg(feature = "virtio-net")]
-		virtio::Id::Net => match VirtioNetDriver::init(dev_id, registers, irq_no) {
-			Ok(virt_net_drv) => {
-				info!("Virtio network driver initialized.");
-
-				crate::arch::interrupts::add_irq_name(irq_no, "virtio");
-				info!("Virtio interrupt handler at line {irq_no}");
-
-				Ok(VirtioDriver::Network(virt_net_drv))
-			}
-			Err(virtio_error) => {
-				error!("Virtio network driver could not be initialized with device");
-				Err(DriverError::InitVirtioDevFail(virtio_error))
-			}
-		},
-		#[cfg(feature = "console")]
-		virtio::Id::Console => match VirtioConsoleDriver::init(dev_id, registers, irq_no) {
-			Ok(virt_console_drv) => {
-				info!("Virtio console driver initialized.");
-
-				crate::arch::interrupts::add_irq_name(irq_no, "virtio");
-				info!("Virtio interrupt handler at line {}", irq_no);
-
-				Ok(VirtioDriver::Console(Box::new(virt_console_drv)))
-			}
-			Err(virtio_error) => {
-				error!("Virtio console driver could not be initialized with device");
-				Err(DriverError::InitVirtioDevFail(virtio_error))
-			}
-		},
-		#[cfg(feature = "vsock")]
-		virtio::Id::Vsock => match VirtioVsockDriver::init(dev_id, registers, irq_no) {
-			Ok(virt_net_drv) => {
-				info!("Virtio sock driver initialized.");
-
-				crate::arch::interrupts::add_irq_name(irq_no, "virtio");
-				info!("Virtio interrupt handler at line {}", irq_no);
-
-				Ok(VirtioDriver::Vsock(virt_vsock_drv))
-			}
-			Err(virtio_error) => {
-				error!("Virtio sock driver could not be initialized with device");
-				Err(DriverError::InitVirtioDevFail(virtio_error))
-			}
-		},
-		device_id => {
-			error!("Device with id {device_id:?} is currently not supported!");
-			// Return Driver error inidacting device is not supported
-			Err(DriverError::InitVirtioDevFail(
-				VirtioError::DevNotSupported(dev_id),
-			))
-		}
-	}
+	let id = registers.as_ptr().device_id().read();
+	let transport = MmioTransport::new(registers, dev_id, irq_no);
+
+	super::init_driver(transport, id)
 }
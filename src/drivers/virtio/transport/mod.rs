@@ -1,10 +1,198 @@
 //! A module containing virtios transport mechanisms.
 //!
-//! The module contains only PCI specific transport mechanism.
-//! Other mechanisms (MMIO and Channel I/O) are currently not
-//! supported.
-
-#[cfg(not(feature = "pci"))]
-pub mod mmio;
-#[cfg(feature = "pci")]
-pub mod pci;
+//! The kernel supports the PCI and the MMIO transport. Exactly one of them is
+//! compiled in, depending on the `pci` feature. Both provide the same
+//! configuration structures ([`ComCfg`], [`NotifCfg`], [`NotifCtrl`], [`IsrStatus`])
+//! and a type implementing [`Transport`], so that every driver is written once and
+//! works on both transports. Channel I/O is currently not supported.
+
+#[cfg(any(feature = "vsock", feature = "console"))]
+use alloc::boxed::Box;
+
+cfg_if::cfg_if! {
+	if #[cfg(feature = "pci")] {
+		pub mod pci;
+		pub(crate) use self::pci::{ComCfg, IsrStatus, NotifCfg, NotifCtrl};
+	} else {
+		pub mod mmio;
+		pub(crate) use self::mmio::{ComCfg, IsrStatus, NotifCfg, NotifCtrl};
+	}
+}
+
+use memory_addresses::VirtAddr;
+
+use crate::drivers::InterruptLine;
+#[cfg(feature = "console")]
+use crate::drivers::console::VirtioConsoleDriver;
+use crate::drivers::error::DriverError;
+use crate::drivers::failure::{self, DeviceClass};
+#[cfg(feature = "virtio-9p")]
+use crate::drivers::fs::virtio_9p::Virtio9pDriver;
+#[cfg(feature = "fuse")]
+use crate::drivers::fs::virtio_fs::VirtioFsDriver;
+#[cfg(all(
+	not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
+	not(all(target_arch = "x86_64", feature = "rtl8139")),
+	not(feature = "e1000"),
+	feature = "virtio-net",
+))]
+use crate::drivers::net::virtio::VirtioNetDriver;
+use crate::drivers::virtio::error::VirtioError;
+#[cfg(feature = "vsock")]
+use crate::drivers::vsock::VirtioVsockDriver;
+use crate::errno::Errno;
+
+/// Shared memory region of a device, which is mapped into the address space
+/// of the kernel.
+///
+/// See Virtio specification v1.2. - 2.10
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ShmRegion {
+	pub addr: VirtAddr,
+	pub len: usize,
+}
+
+/// Transport-independent access to a virtio device.
+///
+/// A transport locates the configuration structures of a device. Drivers are
+/// instantiated from a `Transport` and therefore do not depend on how the
+/// device is attached to the system.
+pub(crate) trait Transport {
+	/// Returns the id of the device, which is used to identify it in messages.
+	fn dev_id(&self) -> u16;
+
+	/// Returns the interrupt line of the device.
+	fn irq(&self) -> InterruptLine;
+
+	/// Maps the device specific configuration structure and returns a static
+	/// reference to it.
+	///
+	/// Returns `None` if the transport does not provide a configuration
+	/// structure which is large enough to hold a `T`.
+	fn map_dev_cfg<T>(&mut self) -> Option<&'static mut T>;
+
+	/// Returns the shared memory region with the id `id`.
+	///
+	/// Returns `None` if the device does not provide such a region or the
+	/// transport does not support shared memory regions.
+	fn shm_region(&self, _id: u8) -> Option<ShmRegion> {
+		None
+	}
+
+	/// Consumes the transport and returns the configuration structures needed
+	/// to drive the device.
+	fn into_cfgs(self) -> (ComCfg, NotifCfg, IsrStatus);
+}
+
+pub(crate) enum VirtioDriver {
+	#[cfg(all(
+		not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
+		not(all(target_arch = "x86_64", feature = "rtl8139")),
+		not(feature = "e1000"),
+		feature = "virtio-net",
+	))]
+	Network(VirtioNetDriver),
+	#[cfg(feature = "console")]
+	Console(Box<VirtioConsoleDriver>),
+	#[cfg(feature = "vsock")]
+	Vsock(Box<VirtioVsockDriver>),
+	#[cfg(feature = "fuse")]
+	FileSystem(VirtioFsDriver),
+	#[cfg(feature = "virtio-9p")]
+	NineP(Virtio9pDriver),
+}
+
+/// Initializes the driver, which is responsible for the device type `id`, on top
+/// of the given transport.
+fn init_driver(transport: impl Transport, id: virtio::Id) -> Result<VirtioDriver, DriverError> {
+	let dev_id = transport.dev_id();
+	#[allow(unused_variables)]
+	let irq = transport.irq();
+
+	match id {
+		#[cfg(all(
+			not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
+			not(all(target_arch = "x86_64", feature = "rtl8139")),
+			not(feature = "e1000"),
+			feature = "virtio-net",
+		))]
+		virtio::Id::Net => match VirtioNetDriver::init(transport) {
+			Ok(virt_net_drv) => {
+				info!("Virtio network driver initialized.");
+
+				crate::arch::interrupts::add_irq_name(irq, "virtio");
+				info!("Virtio interrupt handler at line {irq}");
+
+				Ok(VirtioDriver::Network(virt_net_drv))
+			}
+			Err(virtio_error) => {
+				failure::record(DeviceClass::Network, dev_id, Errno::Io, &virtio_error);
+				Err(DriverError::InitVirtioDevFail(virtio_error))
+			}
+		},
+		#[cfg(feature = "console")]
+		virtio::Id::Console => match VirtioConsoleDriver::init(transport) {
+			Ok(virt_console_drv) => {
+				info!("Virtio console driver initialized.");
+
+				crate::arch::interrupts::add_irq_name(irq, "virtio");
+				info!("Virtio interrupt handler at line {irq}");
+
+				Ok(VirtioDriver::Console(Box::new(virt_console_drv)))
+			}
+			Err(virtio_error) => {
+				failure::record(DeviceClass::Console, dev_id, Errno::Io, &virtio_error);
+				Err(DriverError::InitVirtioDevFail(virtio_error))
+			}
+		},
+		#[cfg(feature = "vsock")]
+		virtio::Id::Vsock => match VirtioVsockDriver::init(transport) {
+			Ok(virt_sock_drv) => {
+				info!("Virtio sock driver initialized.");
+
+				crate::arch::interrupts::add_irq_name(irq, "virtio");
+				info!("Virtio interrupt handler at line {irq}");
+
+				Ok(VirtioDriver::Vsock(Box::new(virt_sock_drv)))
+			}
+			Err(virtio_error) => {
+				failure::record(DeviceClass::Vsock, dev_id, Errno::Io, &virtio_error);
+				Err(DriverError::InitVirtioDevFail(virtio_error))
+			}
+		},
+		#[cfg(feature = "fuse")]
+		virtio::Id::Fs => {
+			// TODO: check subclass
+			// TODO: proper error handling on driver creation fail
+			match VirtioFsDriver::init(transport) {
+				Ok(virt_fs_drv) => {
+					info!("Virtio filesystem driver initialized.");
+					Ok(VirtioDriver::FileSystem(virt_fs_drv))
+				}
+				Err(virtio_error) => {
+					failure::record(DeviceClass::FileSystem, dev_id, Errno::Io, &virtio_error);
+					Err(DriverError::InitVirtioDevFail(virtio_error))
+				}
+			}
+		}
+		#[cfg(feature = "virtio-9p")]
+		virtio::Id::NineP => match Virtio9pDriver::init(transport) {
+			Ok(virt_9p_drv) => {
+				info!("Virtio 9P driver initialized.");
+				Ok(VirtioDriver::NineP(virt_9p_drv))
+			}
+			Err(virtio_error) => {
+				failure::record(DeviceClass::FileSystem, dev_id, Errno::Io, &virtio_error);
+				Err(DriverError::InitVirtioDevFail(virtio_error))
+			}
+		},
+		id => {
+			warn!("Virtio device {id:?} is not supported, skipping!");
+
+			// Return Driver error inidacting device is not supported
+			Err(DriverError::InitVirtioDevFail(
+				VirtioError::DevNotSupported(dev_id),
+			))
+		}
+	}
+}
@@ -3,13 +3,12 @@
 //! The module contains ...
 #![allow(dead_code)]
 
-#[cfg(any(feature = "vsock", feature = "console"))]
-use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::ptr::NonNull;
 use core::{mem, ptr};
 
-use memory_addresses::PhysAddr;
+use memory_addresses::{PhysAddr, VirtAddr};
+use pci_types::CommandRegister;
 use pci_types::capability::PciCapability;
 use virtio::pci::{
 	CapCfgType, CapData, CommonCfg, CommonCfgVolatileFieldAccess, CommonCfgVolatileWideFieldAccess,
@@ -21,23 +20,14 @@ use volatile::{VolatilePtr, VolatileRef};
 
 use crate::arch::memory_barrier;
 use crate::arch::pci::PciConfigRegion;
-#[cfg(feature = "console")]
-use crate::drivers::console::VirtioConsoleDriver;
+use crate::drivers::InterruptLine;
 use crate::drivers::error::DriverError;
-#[cfg(feature = "fuse")]
-use crate::drivers::fs::virtio_fs::VirtioFsDriver;
-#[cfg(all(
-	not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
-	not(all(target_arch = "x86_64", feature = "rtl8139")),
-	feature = "virtio-net",
-))]
-use crate::drivers::net::virtio::VirtioNetDriver;
 use crate::drivers::pci::PciDevice;
 use crate::drivers::pci::error::PciError;
 use crate::drivers::virtio::error::VirtioError;
 use crate::drivers::virtio::transport::pci::PciBar as VirtioPciBar;
-#[cfg(feature = "vsock")]
-use crate::drivers::vsock::VirtioVsockDriver;
+use crate::drivers::virtio::transport::{ShmRegion, Transport, VirtioDriver};
+use crate::mm::dma::{DmaBarrier, dma_barrier};
 
 /// Maps a given device specific pci configuration structure and
 /// returns a static reference to it.
@@ -507,6 +497,7 @@ impl NotifCtrl {
 		// Depending in the feature negotiation, we write either only the
 		// virtqueue index or the index and the next position inside the queue.
 
+		dma_barrier(DmaBarrier::Doorbell);
 		if self.f_notif_data {
 			unsafe {
 				self.notif_addr.write_volatile(data.into_bits());
@@ -572,7 +563,6 @@ impl IsrStatus {
 pub struct ShMemCfg {
 	mem_addr: u64,
 	length: u64,
-	sh_mem: ShMem,
 	/// Shared memory regions are identified via an ID
 	/// See Virtio specification v1.1. - 4.1.4.7
 	id: u8,
@@ -588,66 +578,20 @@ impl ShMemCfg {
 			return None;
 		}
 
-		let offset = cap.cap.offset.to_ne();
-		let length = cap.cap.length.to_ne();
-
-		let virt_addr_raw = cap.bar.mem_addr + offset;
-		let raw_ptr = ptr::with_exposed_provenance_mut::<u8>(virt_addr_raw.try_into().unwrap());
-
-		// Zero initialize shared memory area
-		unsafe {
-			for i in 0..usize::try_from(length).unwrap() {
-				*(raw_ptr.add(i)) = 0;
-			}
-		};
-
-		// Currently in place in order to ensure a safe cast below
-		// "len: cap.bar.length as usize"
-		// In order to remove this assert a safe conversion from
-		// kernel PciBar struct into usize must be made
-		assert!(mem::size_of::<usize>() == 8);
-
+		// The region is not initialized, since the device may back only parts
+		// of it (e.g., the DAX window of virtio-fs) and accesses to the other
+		// parts fault on the host.
 		Some(ShMemCfg {
-			mem_addr: virt_addr_raw,
+			mem_addr: cap.bar.mem_addr + cap.offset(),
 			length: cap.len(),
-			sh_mem: ShMem {
-				ptr: raw_ptr,
-				len: cap.bar.length as usize,
-			},
 			id: cap.cap.id,
 		})
 	}
-}
-
-/// Defines a shared memory locate at location ptr with a length of len.
-/// The shared memories Drop implementation does not dealloc the memory
-/// behind the pointer but sets it to zero, to prevent leakage of data.
-struct ShMem {
-	ptr: *mut u8,
-	len: usize,
-}
-
-impl core::ops::Deref for ShMem {
-	type Target = [u8];
-
-	fn deref(&self) -> &[u8] {
-		unsafe { core::slice::from_raw_parts(self.ptr, self.len) }
-	}
-}
-
-impl core::ops::DerefMut for ShMem {
-	fn deref_mut(&mut self) -> &mut [u8] {
-		unsafe { core::slice::from_raw_parts_mut(self.ptr, self.len) }
-	}
-}
 
-// Upon drop the shared memory region is "deleted" with zeros.
-impl Drop for ShMem {
-	fn drop(&mut self) {
-		for i in 0..self.len {
-			unsafe {
-				*(self.ptr.add(i)) = 0;
-			}
+	fn region(&self) -> ShmRegion {
+		ShmRegion {
+			addr: VirtAddr::new(self.mem_addr),
+			len: self.length.try_into().unwrap(),
 		}
 	}
 }
@@ -692,7 +636,7 @@ fn read_caps(device: &PciDevice<PciConfigRegion>) -> Result<Vec<PciCap>, PciErro
 		.filter(|cap| cap.cfg_type != CapCfgType::Pci)
 		.map(|cap| {
 			let slot = cap.bar;
-			let (addr, size) = device.memory_map_bar(slot, true).unwrap();
+			let (addr, size) = device.memory_map_bar(slot, true, "virtio").unwrap();
 			PciCap {
 				bar: VirtioPciBar::new(slot, addr.as_u64(), size.try_into().unwrap()),
 				dev_id: device_id,
@@ -789,8 +733,70 @@ pub(crate) fn map_caps(device: &PciDevice<PciConfigRegion>) -> Result<UniCapsCol
 	})
 }
 
+/// PCI transport of a virtio device.
+///
+/// Bundles the mapped capabilities of the device with its id and interrupt line.
+pub(crate) struct PciTransport {
+	caps_coll: UniCapsColl,
+	dev_id: u16,
+	irq: InterruptLine,
+}
+
+impl PciTransport {
+	/// Enables bus mastering for `device` and maps its virtio capabilities.
+	pub(crate) fn new(device: &PciDevice<PciConfigRegion>) -> Result<Self, VirtioError> {
+		// enable bus master mode
+		device.set_command(CommandRegister::BUS_MASTER_ENABLE);
+
+		let caps_coll = map_caps(device).inspect_err(|_| {
+			error!("Mapping capabilities failed. Aborting!");
+		})?;
+
+		Ok(Self {
+			caps_coll,
+			dev_id: device.device_id(),
+			irq: device.get_irq().unwrap(),
+		})
+	}
+}
+
+impl Transport for PciTransport {
+	fn dev_id(&self) -> u16 {
+		self.dev_id
+	}
+
+	fn irq(&self) -> InterruptLine {
+		self.irq
+	}
+
+	fn map_dev_cfg<T>(&mut self) -> Option<&'static mut T> {
+		self.caps_coll
+			.dev_cfg_list
+			.iter()
+			.find_map(map_dev_cfg::<T>)
+	}
+
+	fn shm_region(&self, id: u8) -> Option<ShmRegion> {
+		self.caps_coll
+			.sh_mem_cfg_list
+			.iter()
+			.find(|sh_mem| sh_mem.id == id)
+			.map(ShMemCfg::region)
+	}
+
+	fn into_cfgs(self) -> (ComCfg, NotifCfg, IsrStatus) {
+		let UniCapsColl {
+			com_cfg,
+			notif_cfg,
+			isr_cfg,
+			..
+		} = self.caps_coll;
+		(com_cfg, notif_cfg, isr_cfg)
+	}
+}
+
 /// Checks existing drivers for support of given device. Upon match, provides
-/// driver with a [`PciDevice<PciConfigRegion>`] reference, allowing access to the capabilities
+/// driver with a [`PciTransport`] of the device, allowing access to the capabilities
 /// list of the given device through [map_caps].
 pub(crate) fn init_device(
 	device: &PciDevice<PciConfigRegion>,
@@ -809,101 +815,7 @@ pub(crate) fn init_device(
 	}
 
 	let id = virtio::Id::from(u8::try_from(device_id - 0x1040).unwrap());
+	let transport = PciTransport::new(device).map_err(DriverError::InitVirtioDevFail)?;
 
-	match id {
-		#[cfg(all(
-			not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
-			not(all(target_arch = "x86_64", feature = "rtl8139")),
-			feature = "virtio-net",
-		))]
-		virtio::Id::Net => match VirtioNetDriver::init(device) {
-			Ok(virt_net_drv) => {
-				info!("Virtio network driver initialized.");
-
-				let irq = device.get_irq().unwrap();
-				crate::arch::interrupts::add_irq_name(irq, "virtio");
-				info!("Virtio interrupt handler at line {irq}");
-
-				Ok(VirtioDriver::Network(virt_net_drv))
-			}
-			Err(virtio_error) => {
-				error!(
-					"Virtio networkd driver could not be initialized with device: {device_id:x}"
-				);
-				Err(DriverError::InitVirtioDevFail(virtio_error))
-			}
-		},
-		#[cfg(feature = "console")]
-		virtio::Id::Console => match VirtioConsoleDriver::init(device) {
-			Ok(virt_console_drv) => {
-				info!("Virtio console driver initialized.");
-
-				let irq = device.get_irq().unwrap();
-				crate::arch::interrupts::add_irq_name(irq, "virtio");
-				info!("Virtio interrupt handler at line {irq}");
-
-				Ok(VirtioDriver::Console(Box::new(virt_console_drv)))
-			}
-			Err(virtio_error) => {
-				error!("Virtio console driver could not be initialized with device: {device_id:x}");
-				Err(DriverError::InitVirtioDevFail(virtio_error))
-			}
-		},
-		#[cfg(feature = "vsock")]
-		virtio::Id::Vsock => match VirtioVsockDriver::init(device) {
-			Ok(virt_sock_drv) => {
-				info!("Virtio sock driver initialized.");
-
-				let irq = device.get_irq().unwrap();
-				crate::arch::interrupts::add_irq_name(irq, "virtio");
-				info!("Virtio interrupt handler at line {irq}");
-
-				Ok(VirtioDriver::Vsock(Box::new(virt_sock_drv)))
-			}
-			Err(virtio_error) => {
-				error!("Virtio sock driver could not be initialized with device: {device_id:x}");
-				Err(DriverError::InitVirtioDevFail(virtio_error))
-			}
-		},
-		#[cfg(feature = "fuse")]
-		virtio::Id::Fs => {
-			// TODO: check subclass
-			// TODO: proper error handling on driver creation fail
-			match VirtioFsDriver::init(device) {
-				Ok(virt_fs_drv) => {
-					info!("Virtio filesystem driver initialized.");
-					Ok(VirtioDriver::FileSystem(virt_fs_drv))
-				}
-				Err(virtio_error) => {
-					error!(
-						"Virtio filesystem driver could not be initialized with device: {device_id:x}"
-					);
-					Err(DriverError::InitVirtioDevFail(virtio_error))
-				}
-			}
-		}
-		id => {
-			warn!("Virtio device {id:?} is not supported, skipping!");
-
-			// Return Driver error inidacting device is not supported
-			Err(DriverError::InitVirtioDevFail(
-				VirtioError::DevNotSupported(device_id),
-			))
-		}
-	}
-}
-
-pub(crate) enum VirtioDriver {
-	#[cfg(all(
-		not(all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci"))),
-		not(all(target_arch = "x86_64", feature = "rtl8139")),
-		feature = "virtio-net",
-	))]
-	Network(VirtioNetDriver),
-	#[cfg(feature = "console")]
-	Console(Box<VirtioConsoleDriver>),
-	#[cfg(feature = "vsock")]
-	Vsock(Box<VirtioVsockDriver>),
-	#[cfg(feature = "fuse")]
-	FileSystem(VirtioFsDriver),
+	super::init_driver(transport, id)
 }
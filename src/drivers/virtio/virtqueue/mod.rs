@@ -16,6 +16,7 @@ use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::any::Any;
 use core::mem::MaybeUninit;
+use core::ptr::NonNull;
 use core::{mem, ptr};
 
 use enum_dispatch::enum_dispatch;
@@ -26,6 +27,7 @@ use self::error::VirtqError;
 use crate::drivers::virtio::virtqueue::packed::PackedVq;
 use crate::drivers::virtio::virtqueue::split::SplitVq;
 use crate::mm::device_alloc::DeviceAlloc;
+use crate::mm::dma::{self, DmaDirection, DmaMapping};
 
 /// A u16 newtype. If instantiated via ``VqIndex::from(T)``, the newtype is ensured to be
 /// smaller-equal to `min(u16::MAX , T::MAX)`.
@@ -198,17 +200,13 @@ pub trait Virtq: Send {
 trait VirtqPrivate {
 	type Descriptor: VirtqDescriptor;
 
-	fn create_indirect_ctrl(
-		buffer_tkn: &AvailBufferToken,
-	) -> Result<Box<[Self::Descriptor]>, VirtqError>;
+	fn create_indirect_ctrl(mappings: &[DmaMapping])
+	-> Result<Box<[Self::Descriptor]>, VirtqError>;
 
-	fn indirect_desc(table: &[Self::Descriptor]) -> Self::Descriptor {
+	fn indirect_desc(table: &DmaMapping) -> Self::Descriptor {
 		Self::Descriptor::incomplete_desc(
-			DeviceAlloc
-				.phys_addr_from(table.as_ptr().cast_mut())
-				.as_u64()
-				.into(),
-			(mem::size_of_val(table) as u32).into(),
+			table.dma_addr().as_u64().into(),
+			u32::try_from(table.len()).unwrap().into(),
 			virtq::DescF::INDIRECT,
 		)
 	}
@@ -220,43 +218,45 @@ trait VirtqPrivate {
 		buff_tkn: AvailBufferToken,
 		buffer_type: BufferType,
 	) -> TransferToken<Self::Descriptor> {
+		let dma = buff_tkn.map_dma();
 		let ctrl_desc = match buffer_type {
 			BufferType::Direct => None,
-			BufferType::Indirect => Some(Self::create_indirect_ctrl(&buff_tkn).unwrap()),
+			BufferType::Indirect => Some(Self::create_indirect_ctrl(&dma).unwrap()),
 		};
+		// The indirect table is not modified after this point.
+		let ctrl_dma = ctrl_desc.as_ref().map(|table| unsafe {
+			dma::map_single(
+				NonNull::from(table.as_ref()).cast(),
+				mem::size_of_val(table.as_ref()),
+				DmaDirection::ToDevice,
+			)
+		});
 
 		TransferToken {
 			buff_tkn,
 			ctrl_desc,
+			dma,
+			ctrl_dma,
 		}
 	}
 
 	// The descriptors returned by the iterator will be incomplete, as they do not
 	// have all the information necessary.
 	fn descriptor_iter(
-		buffer_tkn: &AvailBufferToken,
+		mappings: &[DmaMapping],
 	) -> Result<impl DoubleEndedIterator<Item = Self::Descriptor>, VirtqError> {
-		let send_desc_iter = buffer_tkn
-			.send_buff
-			.iter()
-			.map(|elem| (elem, elem.len(), virtq::DescF::empty()));
-		let recv_desc_iter = buffer_tkn
-			.recv_buff
-			.iter()
-			.map(|elem| (elem, elem.capacity(), virtq::DescF::WRITE));
-		let mut all_desc_iter =
-			send_desc_iter
-				.chain(recv_desc_iter)
-				.map(|(mem_descr, len, incomplete_flags)| {
-					Self::Descriptor::incomplete_desc(
-						DeviceAlloc
-							.phys_addr_from(mem_descr.as_ptr().cast_mut())
-							.as_u64()
-							.into(),
-						len.into(),
-						incomplete_flags | virtq::DescF::NEXT,
-					)
-				});
+		let mut all_desc_iter = mappings.iter().map(|mapping| {
+			let incomplete_flags = if mapping.direction() == DmaDirection::FromDevice {
+				virtq::DescF::WRITE
+			} else {
+				virtq::DescF::empty()
+			};
+			Self::Descriptor::incomplete_desc(
+				mapping.dma_addr().as_u64().into(),
+				u32::try_from(mapping.len()).unwrap().into(),
+				incomplete_flags | virtq::DescF::NEXT,
+			)
+		});
 
 		let mut last_desc = all_desc_iter
 			.next_back()
@@ -267,6 +267,14 @@ trait VirtqPrivate {
 	}
 }
 
+/// Returns whether the other side has to be notified after moving a ring index
+/// from `old` to `new`, given the event index `event` it published.
+///
+/// See Virtio specification v1.1. - 2.6.7.2 and 2.6.8.2
+fn vring_need_event(event: u16, new: u16, old: u16) -> bool {
+	new.wrapping_sub(event).wrapping_sub(1) < new.wrapping_sub(old)
+}
+
 #[enum_dispatch(Virtq)]
 pub(crate) enum VirtQueue {
 	Split(SplitVq),
@@ -317,6 +325,10 @@ pub struct TransferToken<Descriptor> {
 	buff_tkn: AvailBufferToken,
 	// Contains the [MemDescr] for the indirect table if the transfer is indirect.
 	ctrl_desc: Option<Box<[Descriptor]>>,
+	/// DMA mappings of the buffers of `buff_tkn` in the order of their descriptors
+	dma: Vec<DmaMapping>,
+	/// DMA mapping of `ctrl_desc`
+	ctrl_dma: Option<DmaMapping>,
 }
 
 /// Public Interface for TransferToken
@@ -332,6 +344,21 @@ impl<Descriptor> TransferToken<Descriptor> {
 			self.buff_tkn.num_descr()
 		}
 	}
+
+	/// Hands the buffers back to the CPU and returns them as [UsedBufferToken].
+	fn into_used_buffer_token(self, written_len: u32) -> UsedBufferToken {
+		let Self {
+			buff_tkn,
+			ctrl_desc,
+			dma,
+			ctrl_dma,
+		} = self;
+		// The mappings synchronize the buffers for the CPU when dropped.
+		drop(ctrl_dma);
+		drop(ctrl_desc);
+		drop(dma);
+		UsedBufferToken::from_avail_buffer_token(buff_tkn, written_len)
+	}
 }
 
 #[derive(Debug)]
@@ -371,6 +398,12 @@ impl BufferElem {
 			BufferElem::Vector(vec) => vec.as_ptr(),
 		}
 	}
+
+	/// Returns the first `len` bytes of the element for DMA mappings.
+	fn as_dma_buffer(&self, len: u32) -> NonNull<[u8]> {
+		let ptr = NonNull::new(self.as_ptr().cast_mut()).unwrap();
+		NonNull::slice_from_raw_parts(ptr, len.try_into().unwrap())
+	}
 }
 
 /// The struct represents buffers which are ready to be written or to be send.
@@ -518,6 +551,27 @@ impl AvailBufferToken {
 	fn num_descr(&self) -> u16 {
 		u16::try_from(self.send_buff.len() + self.recv_buff.len()).unwrap()
 	}
+
+	/// Maps the elements for DMA, the elements of the *send* buffer first.
+	fn map_dma(&self) -> Vec<DmaMapping> {
+		let send = self
+			.send_buff
+			.iter()
+			.map(|elem| elem.as_dma_buffer(elem.len()))
+			.collect::<SmallVec<[_; 2]>>();
+		let recv = self
+			.recv_buff
+			.iter()
+			.map(|elem| elem.as_dma_buffer(elem.capacity()))
+			.collect::<SmallVec<[_; 2]>>();
+
+		// SAFETY: The elements are allocated by `DeviceAlloc` and owned by the
+		// token, which is kept together with the mappings in the same transfer
+		// token.
+		let mut mappings = unsafe { dma::map_sg_device(&send, DmaDirection::ToDevice) };
+		mappings.extend(unsafe { dma::map_sg_device(&recv, DmaDirection::FromDevice) });
+		mappings
+	}
 }
 
 // Public interface of BufferToken
@@ -6,7 +6,6 @@ use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::cell::Cell;
 use core::ops;
-use core::sync::atomic::{Ordering, fence};
 
 use align_address::Align;
 #[cfg(not(feature = "pci"))]
@@ -17,10 +16,7 @@ use virtio::pvirtq::{EventSuppressDesc, EventSuppressFlags};
 use virtio::virtq::DescF;
 use virtio::{RingEventFlags, pvirtq, virtq};
 
-#[cfg(not(feature = "pci"))]
-use super::super::transport::mmio::{ComCfg, NotifCfg, NotifCtrl};
-#[cfg(feature = "pci")]
-use super::super::transport::pci::{ComCfg, NotifCfg, NotifCtrl};
+use super::super::transport::{ComCfg, NotifCfg, NotifCtrl};
 use super::error::VirtqError;
 use super::{
 	AvailBufferToken, BufferType, MemDescrId, MemPool, TransferToken, UsedBufferToken, Virtq,
@@ -28,6 +24,7 @@ use super::{
 };
 use crate::arch::mm::paging::{BasePageSize, PageSize};
 use crate::mm::device_alloc::DeviceAlloc;
+use crate::mm::dma::{DmaBarrier, DmaMapping, dma_barrier};
 
 #[derive(Default, PartialEq, Eq, Clone, Copy, Debug)]
 struct RingIdx {
@@ -131,9 +128,7 @@ impl DescriptorRing {
 		let mut ctrl = self.get_read_ctrler();
 
 		ctrl.poll_next()
-			.map(|(tkn, written_len)| {
-				UsedBufferToken::from_avail_buffer_token(tkn.buff_tkn, written_len)
-			})
+			.map(|(tkn, written_len)| tkn.into_used_buffer_token(written_len))
 			.ok_or(VirtqError::NoNewUsed)
 	}
 
@@ -198,11 +193,11 @@ impl DescriptorRing {
 		// * make them available in the right order (the first descriptor last) (VIRTIO Spec. v1.2 section 2.8.6)
 
 		// The buffer uses indirect descriptors if the ctrl_desc field is Some.
-		if let Some(ctrl_desc) = tkn.ctrl_desc.as_ref() {
-			let desc = PackedVq::indirect_desc(ctrl_desc.as_ref());
+		if let Some(ctrl_dma) = tkn.ctrl_dma.as_ref() {
+			let desc = PackedVq::indirect_desc(ctrl_dma);
 			ctrl.write_desc(desc);
 		} else {
-			for incomplete_desc in PackedVq::descriptor_iter(&tkn.buff_tkn)? {
+			for incomplete_desc in PackedVq::descriptor_iter(&tkn.dma)? {
 				ctrl.write_desc(incomplete_desc);
 			}
 		}
@@ -250,7 +245,7 @@ impl DescriptorRing {
 		self.tkn_ref_ring[usize::from(buff_id.0)] = Some(raw_tkn);
 		// The driver performs a suitable memory barrier to ensure the device sees the updated descriptor table and available ring before the next step.
 		// See Virtio specfification v1.1. - 2.7.21
-		fence(Ordering::SeqCst);
+		dma_barrier(DmaBarrier::Write);
 		self.ring[usize::from(start)].flags = first_flags;
 	}
 
@@ -302,6 +297,8 @@ impl ReadCtrl<'_> {
 		// Check if descriptor has been marked used.
 		let desc = &self.desc_ring.ring[usize::from(self.position)];
 		if self.desc_ring.is_marked_used(desc.flags) {
+			// The id and the length must not be read before the flags.
+			dma_barrier(DmaBarrier::Read);
 			let buff_id = desc.id.to_ne();
 			let tkn = self.desc_ring.tkn_ref_ring[usize::from(buff_id)]
 				.take()
@@ -551,6 +548,8 @@ impl Virtq for PackedVq {
 			self.drv_event.enable_specific(next_idx);
 		}
 
+		// The device must see the descriptors before we read its event suppression settings.
+		dma_barrier(DmaBarrier::Full);
 		let range = self.last_next.get()..next_idx;
 		let notif_specific = self
 			.dev_event
@@ -586,6 +585,8 @@ impl Virtq for PackedVq {
 			self.drv_event.enable_specific(next_idx);
 		}
 
+		// The device must see the descriptors before we read its event suppression settings.
+		dma_barrier(DmaBarrier::Full);
 		let range = self.last_next.get()..next_idx;
 		let notif_specific = self
 			.dev_event
@@ -616,7 +617,13 @@ impl Virtq for PackedVq {
 			self.drv_event.enable_specific(next_idx);
 		}
 
-		let notif_specific = self.dev_event.notif_specific() == Some(self.last_next.get());
+		// The device must see the descriptors before we read its event suppression settings.
+		dma_barrier(DmaBarrier::Full);
+		let range = self.last_next.get()..next_idx;
+		let notif_specific = self
+			.dev_event
+			.notif_specific()
+			.is_some_and(|idx| range.wrapping_contains(&idx));
 
 		if self.dev_event.is_notif() || notif_specific {
 			let notification_data = NotificationData::new()
@@ -647,9 +654,9 @@ impl VirtqPrivate for PackedVq {
 	type Descriptor = pvirtq::Desc;
 
 	fn create_indirect_ctrl(
-		buffer_tkn: &AvailBufferToken,
+		mappings: &[DmaMapping],
 	) -> Result<Box<[Self::Descriptor]>, VirtqError> {
-		Ok(Self::descriptor_iter(buffer_tkn)?
+		Ok(Self::descriptor_iter(mappings)?
 			.collect::<Vec<_>>()
 			.into_boxed_slice())
 	}
@@ -714,7 +721,7 @@ impl PackedVq {
 			raw: drv_event,
 		};
 
-		let dev_event = DevNotif {
+		let mut dev_event = DevNotif {
 			f_notif_idx: false,
 			raw: dev_event,
 		};
@@ -727,6 +734,7 @@ impl PackedVq {
 
 		if features.contains(virtio::F::EVENT_IDX) {
 			drv_event.f_notif_idx = true;
+			dev_event.enable_notif_specific();
 		}
 
 		vq_handler.enable_queue();
@@ -12,22 +12,23 @@ use virtio::mmio::NotificationData;
 use virtio::pci::NotificationData;
 use virtio::{le16, virtq};
 
-#[cfg(not(feature = "pci"))]
-use super::super::transport::mmio::{ComCfg, NotifCfg, NotifCtrl};
-#[cfg(feature = "pci")]
-use super::super::transport::pci::{ComCfg, NotifCfg, NotifCtrl};
+use super::super::transport::{ComCfg, NotifCfg, NotifCtrl};
 use super::error::VirtqError;
 use super::{
 	AvailBufferToken, BufferType, MemPool, TransferToken, UsedBufferToken, Virtq, VirtqPrivate,
-	VqIndex, VqSize,
+	VqIndex, VqSize, vring_need_event,
 };
-use crate::arch::memory_barrier;
 use crate::mm::device_alloc::DeviceAlloc;
+use crate::mm::dma::{DmaBarrier, DmaMapping, dma_barrier};
 
 struct DescrRing {
 	read_idx: u16,
 	token_ring: Box<[Option<Box<TransferToken<virtq::Desc>>>]>,
 	mem_pool: MemPool,
+	/// Indicates if VIRTIO_F_EVENT_IDX has been negotiated
+	f_event_idx: bool,
+	/// Indicates if the driver currently wants used buffer notifications
+	drv_notif: bool,
 
 	/// Descriptor Tables
 	///
@@ -56,13 +57,13 @@ impl DescrRing {
 
 	fn push(&mut self, tkn: TransferToken<virtq::Desc>) -> Result<u16, VirtqError> {
 		let mut index;
-		if let Some(ctrl_desc) = tkn.ctrl_desc.as_ref() {
-			let descriptor = SplitVq::indirect_desc(ctrl_desc.as_ref());
+		if let Some(ctrl_dma) = tkn.ctrl_dma.as_ref() {
+			let descriptor = SplitVq::indirect_desc(ctrl_dma);
 
 			index = self.mem_pool.pool.pop().ok_or(VirtqError::NoDescrAvail)?.0;
 			self.descr_table_mut()[usize::from(index)] = MaybeUninit::new(descriptor);
 		} else {
-			let mut rev_all_desc_iter = SplitVq::descriptor_iter(&tkn.buff_tkn)?.rev();
+			let mut rev_all_desc_iter = SplitVq::descriptor_iter(&tkn.dma)?.rev();
 
 			// We need to handle the last descriptor (the first for the reversed iterator) specially to not set the next flag.
 			{
@@ -89,7 +90,8 @@ impl DescrRing {
 		let idx = self.avail_ring_mut().idx.to_ne();
 		self.avail_ring_mut().ring_mut(true)[idx as usize % len] = index.into();
 
-		memory_barrier();
+		// The device must see the descriptors before the new available index.
+		dma_barrier(DmaBarrier::Write);
 		let next_idx = idx.wrapping_add(1);
 		self.avail_ring_mut().idx = next_idx.into();
 
@@ -100,6 +102,8 @@ impl DescrRing {
 		if self.read_idx == self.used_ring().idx.to_ne() {
 			return Err(VirtqError::NoNewUsed);
 		}
+		// The used element must not be read before the used index.
+		dma_barrier(DmaBarrier::Read);
 		let cur_ring_index = self.read_idx as usize % self.token_ring.len();
 		let used_elem = self.used_ring().ring()[cur_ring_index];
 
@@ -122,28 +126,56 @@ impl DescrRing {
 			}
 		}
 
-		memory_barrier();
 		self.read_idx = self.read_idx.wrapping_add(1);
-		Ok(UsedBufferToken::from_avail_buffer_token(
-			tkn.buff_tkn,
-			used_elem.len.to_ne(),
-		))
+		if self.f_event_idx && self.drv_notif {
+			// Ask for a notification as soon as the device uses the next buffer.
+			let read_idx = self.read_idx;
+			*self.avail_ring_mut().used_event_mut() = read_idx.into();
+			// The used event must be visible before the used index is checked again.
+			dma_barrier(DmaBarrier::Full);
+		}
+		Ok(tkn.into_used_buffer_token(used_elem.len.to_ne()))
 	}
 
 	fn drv_enable_notif(&mut self) {
-		self.avail_ring_mut()
-			.flags
-			.remove(virtq::AvailF::NO_INTERRUPT);
+		self.drv_notif = true;
+		if self.f_event_idx {
+			// The device ignores the flags if VIRTIO_F_EVENT_IDX has been negotiated.
+			// See Virtio specification v1.1. - 2.6.7.2
+			let read_idx = self.read_idx;
+			*self.avail_ring_mut().used_event_mut() = read_idx.into();
+			dma_barrier(DmaBarrier::Full);
+		} else {
+			self.avail_ring_mut()
+				.flags
+				.remove(virtq::AvailF::NO_INTERRUPT);
+		}
 	}
 
 	fn drv_disable_notif(&mut self) {
-		self.avail_ring_mut()
-			.flags
-			.insert(virtq::AvailF::NO_INTERRUPT);
+		self.drv_notif = false;
+		if self.f_event_idx {
+			// Notifications cannot be turned off completely with event indices. Moving the
+			// event index half the ring index space away suppresses them until the device
+			// has used 2^15 further buffers, which does not happen with our queue sizes.
+			let used_event = self.read_idx.wrapping_add(0x8000);
+			*self.avail_ring_mut().used_event_mut() = used_event.into();
+		} else {
+			self.avail_ring_mut()
+				.flags
+				.insert(virtq::AvailF::NO_INTERRUPT);
+		}
 	}
 
-	fn dev_is_notif(&self) -> bool {
-		!self.used_ring().flags.contains(virtq::UsedF::NO_NOTIFY)
+	/// Returns whether the device wants to be notified after the available
+	/// index moved from `old_idx` to `new_idx`.
+	fn dev_is_notif(&self, old_idx: u16, new_idx: u16) -> bool {
+		if self.f_event_idx {
+			let avail_event = self.used_ring().avail_event().to_ne();
+			vring_need_event(avail_event, new_idx, old_idx)
+		} else {
+			!self.used_ring().flags.contains(virtq::UsedF::NO_NOTIFY)
+		}
 	}
 }
 
@@ -200,7 +232,9 @@ impl Virtq for SplitVq {
 			unimplemented!();
 		}
 
-		if self.ring.dev_is_notif() {
+		// The device must see the new available index before we read its event suppression settings.
+		dma_barrier(DmaBarrier::Full);
+		if self.ring.dev_is_notif(next_idx.wrapping_sub(1), next_idx) {
 			let notification_data = NotificationData::new()
 				.with_vqn(self.index.0)
 				.with_next_idx(next_idx);
@@ -225,9 +259,9 @@ impl Virtq for SplitVq {
 impl VirtqPrivate for SplitVq {
 	type Descriptor = virtq::Desc;
 	fn create_indirect_ctrl(
-		buffer_tkn: &AvailBufferToken,
+		mappings: &[DmaMapping],
 	) -> Result<Box<[Self::Descriptor]>, VirtqError> {
-		Ok(Self::descriptor_iter(buffer_tkn)?
+		Ok(Self::descriptor_iter(mappings)?
 			.zip(1..)
 			.map(|(descriptor, next_id)| Self::Descriptor {
 				next: next_id.into(),
@@ -297,6 +331,8 @@ impl SplitVq {
 				.collect::<Vec<_>>()
 				.into_boxed_slice(),
 			mem_pool: MemPool::new(size),
+			f_event_idx: features.contains(virtio::F::EVENT_IDX),
+			drv_notif: true,
 
 			descr_table_cell,
 			avail_ring_cell,
@@ -1,29 +1,22 @@
 #![allow(dead_code)]
 
-#[cfg(feature = "pci")]
-pub mod pci;
-
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::mem;
 
-use pci_types::InterruptLine;
 use smallvec::SmallVec;
 use virtio::FeatureBits;
 use virtio::vsock::Hdr;
 
 use super::virtio::virtqueue::VirtQueue;
 use crate::config::VIRTIO_MAX_QUEUE_SIZE;
-use crate::drivers::Driver;
-use crate::drivers::virtio::error::VirtioVsockError;
-#[cfg(feature = "pci")]
-use crate::drivers::virtio::transport::pci::{ComCfg, IsrStatus, NotifCfg};
+use crate::drivers::virtio::error::{VirtioError, VirtioVsockError};
+use crate::drivers::virtio::transport::{ComCfg, IsrStatus, NotifCfg, Transport};
 use crate::drivers::virtio::virtqueue::split::SplitVq;
 use crate::drivers::virtio::virtqueue::{
 	AvailBufferToken, BufferElem, BufferType, UsedBufferToken, Virtq, VqIndex, VqSize,
 };
-#[cfg(feature = "pci")]
-use crate::drivers::vsock::pci::VsockDevCfgRaw;
+use crate::drivers::{Driver, InterruptLine};
 use crate::mm::device_alloc::DeviceAlloc;
 
 fn fill_queue(vq: &mut VirtQueue, num_packets: u16, packet_size: u32) {
@@ -229,6 +222,17 @@ impl EventQueue {
 	}
 }
 
+/// Virtio's socket device configuration structure.
+/// See specification v1.1. - 5.11.4
+///
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub(crate) struct VsockDevCfgRaw {
+	/// The guest_cid field contains the guest’s context ID, which uniquely identifies the device
+	/// for its lifetime. The upper 32 bits of the CID are reserved and zeroed.
+	pub guest_cid: u64,
+}
+
 /// A wrapper struct for the raw configuration structure.
 /// Handling the right access to fields, as some are read-only
 /// for the driver.
@@ -261,7 +265,61 @@ impl Driver for VirtioVsockDriver {
 }
 
 impl VirtioVsockDriver {
-	#[cfg(feature = "pci")]
+	/// Instantiates a new VirtioVsockDriver struct, by mapping the device specific
+	/// configuration and moving the configuration structures of the transport into the struct.
+	pub fn new(mut transport: impl Transport) -> Result<Self, VirtioVsockError> {
+		let dev_id = transport.dev_id();
+		let irq = transport.irq();
+
+		let Some(raw) = transport.map_dev_cfg::<VsockDevCfgRaw>() else {
+			error!("No dev config. Aborting!");
+			return Err(VirtioVsockError::NoDevCfg(dev_id));
+		};
+		let dev_cfg = VsockDevCfg {
+			raw,
+			dev_id,
+			features: virtio::vsock::F::empty(),
+		};
+
+		let (com_cfg, notif_cfg, isr_stat) = transport.into_cfgs();
+
+		Ok(VirtioVsockDriver {
+			dev_cfg,
+			com_cfg,
+			isr_stat,
+			notif_cfg,
+			irq,
+			event_vq: EventQueue::new(),
+			recv_vq: RxQueue::new(),
+			send_vq: TxQueue::new(),
+		})
+	}
+
+	/// Initializes virtio socket device
+	///
+	/// Returns a driver instance of VirtioVsockDriver.
+	pub(crate) fn init(transport: impl Transport) -> Result<VirtioVsockDriver, VirtioError> {
+		let mut drv = VirtioVsockDriver::new(transport).map_err(|vsock_err| {
+			error!("Initializing new virtio socket device driver failed. Aborting!");
+			VirtioError::VsockDriver(vsock_err)
+		})?;
+
+		match drv.init_dev() {
+			Ok(()) => {
+				info!(
+					"Socket device with cid {:x}, has been initialized by driver!",
+					drv.dev_cfg.raw.guest_cid
+				);
+
+				Ok(drv)
+			}
+			Err(vsock_err) => {
+				drv.set_failed();
+				Err(VirtioError::VsockDriver(vsock_err))
+			}
+		}
+	}
+
 	pub fn get_dev_id(&self) -> u16 {
 		self.dev_cfg.dev_id
 	}
@@ -271,7 +329,6 @@ impl VirtioVsockDriver {
 		self.dev_cfg.raw.guest_cid
 	}
 
-	#[cfg(feature = "pci")]
 	pub fn set_failed(&mut self) {
 		self.com_cfg.set_failed();
 	}
@@ -347,7 +404,10 @@ impl VirtioVsockDriver {
 		// Indicate device, that driver is able to handle it
 		self.com_cfg.set_drv();
 
-		let features = virtio::vsock::F::VERSION_1;
+		// Event index based notification suppression is used whenever the device offers it.
+		let device_features = virtio::vsock::F::from(self.com_cfg.dev_features());
+		let features =
+			virtio::vsock::F::VERSION_1 | (device_features & virtio::vsock::F::EVENT_IDX);
 		self.negotiate_features(features)?;
 
 		// Indicates the device, that the current feature set is final for the driver
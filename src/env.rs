@@ -2,6 +2,7 @@
 
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use core::ops::Range;
 use core::{ptr, str};
 
 use ahash::RandomState;
@@ -40,6 +41,48 @@ struct Cli {
 	args: Vec<String>,
 	#[allow(dead_code)]
 	mmio: Vec<String>,
+	/// RAID arrays given by `md=` arguments
+	#[cfg(feature = "raid")]
+	md: Vec<String>,
+	/// Root file system given by `root=<device>,<type>`
+	#[cfg(any(feature = "fat", feature = "ext2"))]
+	root: Option<String>,
+	/// Remote collector of kernel messages given by `logsink=<udp|tcp>:<address>:<port>`
+	#[cfg(feature = "log-net")]
+	log_sink: Option<String>,
+	/// Failures of the application are escalated to a reboot (`supervisor=reboot`).
+	reboot_on_failure: bool,
+	/// Core 0 is dedicated to the housekeeping of the kernel (`housekeeping=dedicated`).
+	dedicated_housekeeping: bool,
+	/// Cores, among which the interrupt lines are distributed, given by `irqaffinity=<cores>`
+	irq_affinity: Option<String>,
+	/// Behavior after a panic given by `panic=<exit[:code]|halt|seconds>`
+	panic_action: Option<String>,
+	/// Seconds, which the shutdown hooks may take, given by `shutdown_timeout=<seconds>`
+	shutdown_timeout: Option<u64>,
+	/// Lookups and attributes of virtio-fs are cached, unless `virtiofs.cache=none` is given.
+	#[cfg(feature = "fuse")]
+	virtiofs_cache: bool,
+	/// Maximum number of file descriptors given by `nofile=<count>`
+	nofile: Option<usize>,
+	#[cfg(feature = "net")]
+	socket_buffers: SocketBuffers,
+	/// MTU of the IP layer of the network cards given by `net.mtu=<bytes>`
+	#[cfg(feature = "net")]
+	mtu: Option<u16>,
+}
+
+/// Sizes of the socket buffers in bytes and the maximum number of sockets,
+/// see [`crate::executor::socket_buffer`]
+#[cfg(feature = "net")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SocketBuffers {
+	pub rmem_default: Option<usize>,
+	pub rmem_max: Option<usize>,
+	pub wmem_default: Option<usize>,
+	pub wmem_max: Option<usize>,
+	pub mem_max: Option<usize>,
+	pub max_sockets: Option<usize>,
 }
 
 /// Whether Hermit is running under the "uhyve" hypervisor.
@@ -74,6 +117,16 @@ pub fn fdt_args() -> Option<&'static str> {
 	fdt().and_then(|fdt| fdt.chosen().bootargs())
 }
 
+/// Returns the physical address range of the initramfs, which the loader
+/// passes as `linux,initrd-start` and `linux,initrd-end` in `/chosen`.
+pub fn initrd() -> Option<Range<usize>> {
+	let fdt = fdt()?;
+	let chosen = fdt.find_node("/chosen")?;
+	let start = chosen.property("linux,initrd-start")?.as_usize()?;
+	let end = chosen.property("linux,initrd-end")?.as_usize()?;
+	(start < end).then_some(start..end)
+}
+
 impl Default for Cli {
 	fn default() -> Self {
 		let mut image_path = None;
@@ -96,6 +149,24 @@ impl Default for Cli {
 
 		let mut args = Vec::new();
 		let mut mmio = Vec::new();
+		#[cfg(feature = "raid")]
+		let mut md = Vec::new();
+		#[cfg(any(feature = "fat", feature = "ext2"))]
+		let mut root = None;
+		#[cfg(feature = "log-net")]
+		let mut log_sink = None;
+		let mut reboot_on_failure = false;
+		let mut dedicated_housekeeping = false;
+		let mut irq_affinity = None;
+		let mut panic_action = None;
+		let mut shutdown_timeout = None;
+		#[cfg(feature = "fuse")]
+		let mut virtiofs_cache = true;
+		let mut nofile = None;
+		#[cfg(feature = "net")]
+		let mut socket_buffers = SocketBuffers::default();
+		#[cfg(feature = "net")]
+		let mut mtu = None;
 		while let Some(word) = words.next() {
 			if word.as_str().starts_with("virtio_mmio.device=") {
 				let v: Vec<&str> = word.as_str().split('=').collect();
@@ -137,6 +208,52 @@ impl Default for Cli {
 							};
 							env_vars.insert(key.to_string(), value.to_string());
 						}
+						#[cfg(feature = "raid")]
+						"md" => md.push(value.to_string()),
+						#[cfg(any(feature = "fat", feature = "ext2"))]
+						"root" => root = Some(value.to_string()),
+						#[cfg(feature = "log-net")]
+						"logsink" => log_sink = Some(value.to_string()),
+						"supervisor" if value == "reboot" => reboot_on_failure = true,
+						"housekeeping" if value == "dedicated" => dedicated_housekeeping = true,
+						"irqaffinity" => irq_affinity = Some(value.to_string()),
+						"panic" => panic_action = Some(value.to_string()),
+						"shutdown_timeout" => match value.parse() {
+							Ok(seconds) => shutdown_timeout = Some(seconds),
+							Err(_) => error!("could not parse bootarg: {word}"),
+						},
+						#[cfg(feature = "fuse")]
+						"virtiofs.cache" => match value {
+							"auto" => virtiofs_cache = true,
+							"none" => virtiofs_cache = false,
+							_ => error!("could not parse bootarg: {word}"),
+						},
+						"nofile" => match value.parse() {
+							Ok(count) => nofile = Some(count),
+							Err(_) => error!("could not parse bootarg: {word}"),
+						},
+						#[cfg(feature = "net")]
+						"net.rmem_default" | "net.rmem_max" | "net.wmem_default"
+						| "net.wmem_max" | "net.mem_max" | "net.max_sockets" => {
+							let Ok(size) = value.parse() else {
+								error!("could not parse bootarg: {word}");
+								continue;
+							};
+							let field = match arg {
+								"net.rmem_default" => &mut socket_buffers.rmem_default,
+								"net.rmem_max" => &mut socket_buffers.rmem_max,
+								"net.wmem_default" => &mut socket_buffers.wmem_default,
+								"net.wmem_max" => &mut socket_buffers.wmem_max,
+								"net.mem_max" => &mut socket_buffers.mem_max,
+								_ => &mut socket_buffers.max_sockets,
+							};
+							*field = Some(size);
+						}
+						#[cfg(feature = "net")]
+						"net.mtu" => match value.parse() {
+							Ok(bytes) => mtu = Some(bytes),
+							Err(_) => error!("could not parse bootarg: {word}"),
+						},
 						_ => error!("could not parse bootarg: {word}"),
 					}
 				}
@@ -153,6 +270,24 @@ impl Default for Cli {
 			args,
 			#[allow(dead_code)]
 			mmio,
+			#[cfg(feature = "raid")]
+			md,
+			#[cfg(any(feature = "fat", feature = "ext2"))]
+			root,
+			#[cfg(feature = "log-net")]
+			log_sink,
+			reboot_on_failure,
+			dedicated_housekeeping,
+			irq_affinity,
+			panic_action,
+			shutdown_timeout,
+			#[cfg(feature = "fuse")]
+			virtiofs_cache,
+			nofile,
+			#[cfg(feature = "net")]
+			socket_buffers,
+			#[cfg(feature = "net")]
+			mtu,
 		}
 	}
 }
@@ -177,6 +312,74 @@ pub fn args() -> &'static [String] {
 	CLI.get().unwrap().args.as_slice()
 }
 
+/// Returns the configuration of all RAID arrays given by `md=` arguments
+#[cfg(feature = "raid")]
+pub fn md() -> &'static [String] {
+	CLI.get().unwrap().md.as_slice()
+}
+
+/// Returns the root file system given by the `root=` argument
+#[cfg(any(feature = "fat", feature = "ext2"))]
+pub fn root() -> Option<&'static str> {
+	CLI.get().unwrap().root.as_deref()
+}
+
+/// Returns the network log sink given by the `logsink=` argument
+#[cfg(feature = "log-net")]
+pub fn log_sink() -> Option<&'static str> {
+	CLI.get().unwrap().log_sink.as_deref()
+}
+
+/// Whether the system is rebooted instead of shut down, if the application fails
+pub fn reboot_on_failure() -> bool {
+	CLI.get().unwrap().reboot_on_failure
+}
+
+/// Whether core 0 is dedicated to the housekeeping of the kernel and the
+/// other cores are left to the application, see `housekeeping=dedicated`
+pub fn dedicated_housekeeping() -> bool {
+	CLI.get().unwrap().dedicated_housekeeping
+}
+
+/// Returns the cores given by the `irqaffinity=` argument, e.g., `0-3,6`
+pub fn irq_affinity() -> Option<&'static str> {
+	CLI.get().unwrap().irq_affinity.as_deref()
+}
+
+/// Returns the behavior after a panic given by the `panic=` argument
+pub fn panic_action() -> Option<&'static str> {
+	CLI.get().unwrap().panic_action.as_deref()
+}
+
+/// Returns the seconds given by the `shutdown_timeout=` argument
+pub fn shutdown_timeout() -> Option<u64> {
+	CLI.get().unwrap().shutdown_timeout
+}
+
+/// Whether virtio-fs may cache lookups and attributes, see `virtiofs.cache=<auto|none>`
+#[cfg(feature = "fuse")]
+pub fn virtiofs_cache() -> bool {
+	CLI.get().unwrap().virtiofs_cache
+}
+
+/// Maximum number of file descriptors, see `nofile=<count>`
+pub fn nofile() -> Option<usize> {
+	CLI.get().unwrap().nofile
+}
+
+/// Returns the limits of the sockets given by the `net.*mem*=` and
+/// `net.max_sockets=` arguments
+#[cfg(feature = "net")]
+pub fn socket_buffers() -> SocketBuffers {
+	CLI.get().unwrap().socket_buffers
+}
+
+/// Returns the MTU of the IP layer given by the `net.mtu=` argument
+#[cfg(feature = "net")]
+pub fn mtu() -> Option<u16> {
+	CLI.get().unwrap().mtu
+}
+
 /// Returns the configuration of all mmio devices
 #[allow(dead_code)]
 pub fn mmio() -> &'static [String] {
@@ -0,0 +1,239 @@
+//! Capture of the frames of the network interfaces.
+//!
+//! Each device of the network stack passes its received frames, before the
+//! smoltcp interface processes them, and its transmitted frames, after the
+//! interface has built them, to [`frame`]. The frames, which match the
+//! [`Filter`] of a capture, are copied into the ring buffer of the capture.
+//!
+//! The ring buffer contains the frames in the format of libpcap, so that the
+//! descriptor of a capture can be read like a capture file of `tcpdump` and
+//! the output opened with Wireshark. Frames, which do not fit into the ring
+//! buffer, are dropped.
+
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::task::Waker;
+
+use hermit_sync::InterruptSpinMutex;
+use smoltcp::wire::{
+	EthernetFrame, EthernetProtocol, IpProtocol, Ipv4Packet, Ipv6Packet, TcpPacket, UdpPacket,
+};
+
+/// Size of the ring buffer of a capture in bytes
+const RING_SIZE: usize = 256 * 1024;
+
+/// Magic number of the libpcap format with timestamps in microseconds
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+
+/// Link type of Ethernet frames
+const LINKTYPE_ETHERNET: u32 = 1;
+
+/// Size of the header of a frame in the libpcap format
+const RECORD_HEADER_SIZE: usize = 16;
+
+/// Direction of a frame
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Direction {
+	Received,
+	Transmitted,
+}
+
+/// Filter of a capture, whose fields match any frame, if they are zero or
+/// `None`
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct Filter {
+	pub direction: Option<Direction>,
+	/// Ethertype, e.g., `0x0800` for IPv4
+	pub ethertype: u16,
+	/// Protocol of IPv4 or IPv6, e.g., `6` for TCP
+	pub protocol: u8,
+	/// Source or destination port of TCP or UDP
+	pub port: u16,
+}
+
+impl Filter {
+	fn matches(&self, direction: Direction, frame: &[u8]) -> bool {
+		if self.direction.is_some_and(|filter| filter != direction) {
+			return false;
+		}
+		if self.ethertype == 0 && self.protocol == 0 && self.port == 0 {
+			return true;
+		}
+
+		self.matches_headers(frame).unwrap_or(false)
+	}
+
+	/// Returns `None`, if the headers, which the filter needs, are truncated.
+	fn matches_headers(&self, frame: &[u8]) -> Option<bool> {
+		let frame = EthernetFrame::new_checked(frame).ok()?;
+		if self.ethertype != 0 && u16::from(frame.ethertype()) != self.ethertype {
+			return Some(false);
+		}
+		if self.protocol == 0 && self.port == 0 {
+			return Some(true);
+		}
+
+		let (protocol, payload) = match frame.ethertype() {
+			EthernetProtocol::Ipv4 => {
+				let packet = Ipv4Packet::new_checked(frame.payload()).ok()?;
+				(packet.next_header(), packet.payload())
+			}
+			EthernetProtocol::Ipv6 => {
+				let packet = Ipv6Packet::new_checked(frame.payload()).ok()?;
+				(packet.next_header(), packet.payload())
+			}
+			_ => return Some(false),
+		};
+		if self.protocol != 0 && u8::from(protocol) != self.protocol {
+			return Some(false);
+		}
+		if self.port == 0 {
+			return Some(true);
+		}
+
+		let (src_port, dst_port) = match protocol {
+			IpProtocol::Tcp => {
+				let packet = TcpPacket::new_checked(payload).ok()?;
+				(packet.src_port(), packet.dst_port())
+			}
+			IpProtocol::Udp => {
+				let packet = UdpPacket::new_checked(payload).ok()?;
+				(packet.src_port(), packet.dst_port())
+			}
+			_ => return Some(false),
+		};
+		Some(src_port == self.port || dst_port == self.port)
+	}
+}
+
+/// Frames, which have been captured, but not read yet
+#[derive(Debug)]
+pub(crate) struct Capture {
+	filter: Filter,
+	/// Maximum number of bytes of a frame, which are captured
+	snaplen: usize,
+	ring: InterruptSpinMutex<VecDeque<u8>>,
+	/// Number of frames, which did not fit into the ring buffer
+	dropped: AtomicU64,
+	wakers: InterruptSpinMutex<Vec<Waker>>,
+}
+
+impl Capture {
+	fn push(&self, timestamp: u64, frame: &[u8]) {
+		let len = frame.len().min(self.snaplen);
+		let mut ring = self.ring.lock();
+		if ring.len() + RECORD_HEADER_SIZE + len > RING_SIZE {
+			drop(ring);
+			self.dropped.fetch_add(1, Ordering::Relaxed);
+			return;
+		}
+
+		let header = [
+			(timestamp / 1_000_000) as u32,
+			(timestamp % 1_000_000) as u32,
+			len as u32,
+			frame.len() as u32,
+		];
+		for field in header {
+			ring.extend(field.to_ne_bytes());
+		}
+		ring.extend(&frame[..len]);
+		drop(ring);
+
+		for waker in self.wakers.lock().drain(..) {
+			waker.wake();
+		}
+	}
+
+	/// Moves the captured bytes into `buf` and returns their number.
+	pub fn read(&self, buf: &mut [u8]) -> usize {
+		let mut ring = self.ring.lock();
+		let len = buf.len().min(ring.len());
+		for (dest, byte) in buf.iter_mut().zip(ring.drain(..len)) {
+			*dest = byte;
+		}
+		len
+	}
+
+	/// Registers `waker`, which is woken by the next captured frame.
+	pub fn register(&self, waker: &Waker) {
+		let mut wakers = self.wakers.lock();
+		if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+			wakers.push(waker.clone());
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.ring.lock().is_empty()
+	}
+}
+
+impl Drop for Capture {
+	fn drop(&mut self) {
+		let dropped = self.dropped.load(Ordering::Relaxed);
+		if dropped > 0 {
+			info!("Capture has dropped {dropped} frames");
+		}
+		ACTIVE.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+static CAPTURES: InterruptSpinMutex<Vec<Weak<Capture>>> = InterruptSpinMutex::new(Vec::new());
+
+/// Number of captures, so that frames are only inspected, while captures exist
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+/// Copies `frame` into the captures, whose filter matches it.
+///
+/// Called by the devices of the network stack for every frame.
+pub(crate) fn frame(direction: Direction, frame: &[u8]) {
+	if ACTIVE.load(Ordering::Relaxed) == 0 {
+		return;
+	}
+
+	let timestamp = crate::arch::kernel::systemtime::now_micros();
+	let captures = CAPTURES
+		.lock()
+		.iter()
+		.filter_map(Weak::upgrade)
+		.filter(|capture| capture.filter.matches(direction, frame))
+		.collect::<Vec<_>>();
+	for capture in captures {
+		capture.push(timestamp, frame);
+	}
+}
+
+/// Starts a capture of the frames, which match `filter`, and of at most
+/// `snaplen` bytes of each frame.
+///
+/// The ring buffer starts with the header of the libpcap format.
+pub(crate) fn open(filter: Filter, snaplen: usize) -> Arc<Capture> {
+	let mut ring = VecDeque::new();
+	ring.extend(PCAP_MAGIC.to_ne_bytes());
+	// version 2.4
+	ring.extend(2u16.to_ne_bytes());
+	ring.extend(4u16.to_ne_bytes());
+	// no time zone offset and no accuracy of the timestamps
+	let header = [0, 0, snaplen as u32, LINKTYPE_ETHERNET];
+	for field in header {
+		ring.extend(field.to_ne_bytes());
+	}
+
+	let capture = Arc::new(Capture {
+		filter,
+		snaplen,
+		ring: InterruptSpinMutex::new(ring),
+		dropped: AtomicU64::new(0),
+		wakers: InterruptSpinMutex::new(Vec::new()),
+	});
+	ACTIVE.fetch_add(1, Ordering::Relaxed);
+
+	let mut captures = CAPTURES.lock();
+	captures.retain(|capture| capture.strong_count() > 0);
+	captures.push(Arc::downgrade(&capture));
+	drop(captures);
+
+	capture
+}
@@ -1,5 +1,8 @@
 use alloc::boxed::Box;
-#[cfg(not(feature = "dhcpv4"))]
+use alloc::format;
+use alloc::vec::Vec;
+#[cfg(feature = "dns")]
+use core::net::IpAddr;
 use core::str::FromStr;
 
 use cfg_if::cfg_if;
@@ -9,165 +12,402 @@ use smoltcp::phy::Tracer;
 use smoltcp::phy::{Device, Medium};
 #[cfg(feature = "dhcpv4")]
 use smoltcp::socket::dhcpv4;
-#[cfg(all(feature = "dns", not(feature = "dhcpv4")))]
+#[cfg(feature = "dns")]
 use smoltcp::socket::dns;
-use smoltcp::wire::{EthernetAddress, HardwareAddress};
-#[cfg(not(feature = "dhcpv4"))]
-use smoltcp::wire::{IpCidr, Ipv4Address, Ipv4Cidr};
+use smoltcp::wire::{EthernetAddress, HardwareAddress, IpCidr, Ipv4Address, Ipv4Cidr, Ipv6Address};
 
-use super::network::{NetworkInterface, NetworkState};
+use super::mtu::Mtus;
+use super::neighbor::Neighbors;
+use super::netstat::Counted;
+use super::network::{Link, NetworkInterface, NetworkState};
+use super::route::{self, Route};
 use crate::arch;
-use crate::drivers::net::NetworkDriver;
+use crate::drivers::net::loopback::LoopbackDriver;
+use crate::drivers::net::{NetworkDevice, NetworkDriver};
 
 cfg_if! {
 	if #[cfg(any(
 		all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
 		all(target_arch = "x86_64", feature = "rtl8139"),
+		feature = "e1000",
 		feature = "virtio-net",
 	))] {
 		use hermit_sync::SpinMutex;
-		use crate::drivers::net::NetworkDevice;
 
-		pub(crate) static NETWORK_DEVICE: SpinMutex<Option<NetworkDevice>> = SpinMutex::new(Option::None);
-	} else {
-		use crate::drivers::net::loopback::LoopbackDriver;
+		use super::network::Loopback;
+
+		/// Network cards in the order of their discovery, the first one is the primary interface
+		pub(crate) static NETWORK_DEVICES: SpinMutex<Vec<NetworkDevice>> = SpinMutex::new(Vec::new());
 	}
 }
 
-impl<'a> NetworkInterface<'a> {
-	#[cfg(feature = "dhcpv4")]
-	pub(crate) fn create() -> NetworkState<'a> {
-		cfg_if! {
-			if #[cfg(any(
-				all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
-				all(target_arch = "x86_64", feature = "rtl8139"),
-				feature = "virtio-net",
-			))] {
-				#[cfg_attr(feature = "trace", expect(unused_mut))]
-				let Some(mut device) = NETWORK_DEVICE.lock().take() else {
-					return NetworkState::InitializationFailed;
-				};
-			} else {
-				#[cfg_attr(feature = "trace", expect(unused_mut))]
-				let mut device = LoopbackDriver::new();
-			}
-		}
+/// Returns the link-local IPv6 address, which is derived from the MAC address (RFC 4291).
+pub(crate) fn link_local_address(mac: [u8; 6]) -> Ipv6Address {
+	Ipv6Address::from([
+		0xfe,
+		0x80,
+		0,
+		0,
+		0,
+		0,
+		0,
+		0,
+		mac[0] ^ 0x02,
+		mac[1],
+		mac[2],
+		0xff,
+		0xfe,
+		mac[3],
+		mac[4],
+		mac[5],
+	])
+}
 
-		let mac = device.get_mac_address();
+/// Returns the variable `name` of the interface `index`, e.g.,
+/// `HERMIT_IP_ETH1` of `eth1`.
+///
+/// The variables of the primary interface are read by the callers, because
+/// they may also be given at compile time.
+fn interface_var(name: &str, index: usize) -> Option<&'static str> {
+	crate::env::var(&format!("{name}_ETH{index}")).map(|value| value.as_str())
+}
 
-		#[cfg(feature = "trace")]
-		let mut device = Tracer::new(device, |timestamp, printer| trace!("{timestamp} {printer}"));
+/// Assigns the link-local IPv6 address and the static IPv6 address `ip` with
+/// the default route through `gateway`.
+///
+/// Returns `true`, if the global address has to be configured automatically.
+fn configure_ipv6(
+	iface: &mut Interface,
+	mac: [u8; 6],
+	ip: Option<&str>,
+	gateway: Option<&str>,
+	routes: &mut Vec<Route>,
+	index: usize,
+) -> bool {
+	// The loopback device has no MAC address.
+	if mac == [0; 6] {
+		return false;
+	}
 
-		if hermit_var!("HERMIT_IP").is_some() {
-			warn!(
-				"A static IP address is specified with the environment variable HERMIT_IP, but the device is configured to use DHCPv4!"
-			);
-		}
+	let name = route::interface_name(index);
+	let link_local = IpCidr::new(link_local_address(mac).into(), 64);
+	info!("Configure network interface {name} with address {link_local}");
+	iface.update_ip_addrs(|ip_addrs| {
+		ip_addrs.push(link_local).unwrap();
+	});
 
-		let ethernet_addr = EthernetAddress([mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]]);
-		let hardware_addr = HardwareAddress::Ethernet(ethernet_addr);
+	let Some(ip) = ip else {
+		return true;
+	};
+	let Some(ip_addr) = ip.split_once('/').and_then(|(addr, prefix_len)| {
+		let addr = Ipv6Address::from_str(addr).ok()?;
+		let prefix_len = prefix_len.parse::<u8>().ok().filter(|len| *len <= 128)?;
+		Some(IpCidr::new(addr.into(), prefix_len))
+	}) else {
+		warn!(
+			"Unable to parse the IPv6 address {ip} of {name}, expected an address and a prefix length"
+		);
+		return false;
+	};
 
-		info!("MAC address {hardware_addr}");
-		let capabilities = device.capabilities();
-		info!("{:?}", capabilities.checksum);
-		info!("MTU: {} bytes", capabilities.max_transmission_unit);
+	info!("Configure network interface {name} with address {ip_addr}");
+	iface.update_ip_addrs(|ip_addrs| {
+		ip_addrs.push(ip_addr).unwrap();
+	});
 
-		let dhcp = dhcpv4::Socket::new();
+	if let Some(gateway) = gateway {
+		let gateway = Ipv6Address::from_str(gateway).unwrap();
+		info!("Configure gateway with address {gateway}");
+		routes.push(Route::default_via(gateway.into(), index));
+	}
 
-		// use the current time based on the wall-clock time as seed
-		let mut config = Config::new(hardware_addr);
-		config.random_seed = (arch::kernel::systemtime::now_micros()) / 1_000_000;
-		if capabilities.medium == Medium::Ethernet {
-			config.hardware_addr = hardware_addr;
-		}
+	false
+}
 
-		let iface = Interface::new(config, &mut device, crate::executor::network::now());
-		let mut sockets = SocketSet::new(vec![]);
-		let dhcp_handle = sockets.add(dhcp);
+/// Assigns the static IPv4 address `ip` with the netmask `mask` and the
+/// default route through `gateway`.
+fn configure_ipv4(
+	iface: &mut Interface,
+	ip: &str,
+	mask: &str,
+	gateway: Option<&str>,
+	routes: &mut Vec<Route>,
+	index: usize,
+) {
+	let myip = Ipv4Address::from_str(ip).unwrap();
+	let mymask = Ipv4Address::from_str(mask).unwrap();
+	let ip_addr = IpCidr::from(Ipv4Cidr::from_netmask(myip, mymask).unwrap());
 
-		NetworkState::Initialized(Box::new(Self {
-			iface,
-			sockets,
-			device,
-			dhcp_handle,
-			#[cfg(feature = "dns")]
-			dns_handle: None,
-		}))
+	info!(
+		"Configure network interface {} with address {ip_addr}",
+		route::interface_name(index)
+	);
+	iface.update_ip_addrs(|ip_addrs| {
+		ip_addrs.push(ip_addr).unwrap();
+	});
+
+	if let Some(gateway) = gateway {
+		let mygw = Ipv4Address::from_str(gateway).unwrap();
+		info!("Configure gateway with address {mygw}");
+		routes.push(Route::default_via(mygw.into(), index));
+	}
+}
+
+/// Assigns the loopback addresses `127.0.0.1/8` and `::1/128`.
+fn configure_loopback(iface: &mut Interface) {
+	info!("Configure network interface lo with addresses 127.0.0.1/8 and ::1/128");
+	iface.update_ip_addrs(|ip_addrs| {
+		ip_addrs
+			.push(IpCidr::new(Ipv4Address::LOCALHOST.into(), 8))
+			.unwrap();
+		ip_addrs
+			.push(IpCidr::new(Ipv6Address::LOCALHOST.into(), 128))
+			.unwrap();
+	});
+}
+
+/// Creates the smoltcp interface of `device`.
+fn create_iface(device: &mut impl Device, mac: [u8; 6]) -> Interface {
+	let ethernet_addr = EthernetAddress(mac);
+	let hardware_addr = HardwareAddress::Ethernet(ethernet_addr);
+
+	info!("MAC address {hardware_addr}");
+	let capabilities = device.capabilities();
+	info!("{:?}", capabilities.checksum);
+	info!("MTU: {} bytes", capabilities.max_transmission_unit);
+
+	// use the current time based on the wall-clock time as seed
+	let mut config = Config::new(hardware_addr);
+	config.random_seed = (arch::kernel::systemtime::now_micros()) / 1_000_000;
+	if capabilities.medium == Medium::Ethernet {
+		config.hardware_addr = hardware_addr;
 	}
 
-	#[cfg(not(feature = "dhcpv4"))]
+	Interface::new(config, device, crate::executor::network::now())
+}
+
+/// Creates a new interface of `device` with the addresses of `iface`, so that
+/// the new interface reads the capabilities of `device` again.
+///
+/// The routes of the interface have to be programmed again, see
+/// [`NetworkInterface::update_routes`].
+pub(super) fn recreate_iface(iface: &Interface, device: &mut impl Device) -> Interface {
+	let mut config = Config::new(iface.hardware_addr());
+	config.random_seed = (arch::kernel::systemtime::now_micros()) / 1_000_000;
+
+	let mut new_iface = Interface::new(config, device, crate::executor::network::now());
+	new_iface.update_ip_addrs(|ip_addrs| {
+		ip_addrs.extend_from_slice(iface.ip_addrs()).unwrap();
+	});
+	new_iface
+}
+
+/// Creates the interface of a network card beside the primary one.
+///
+/// It is configured statically by `HERMIT_IP_ETH<index>`,
+/// `HERMIT_MASK_ETH<index>`, and `HERMIT_IPV6_ETH<index>`.
+fn create_link(device: NetworkDevice, routes: &mut Vec<Route>, index: usize) -> Link {
+	let mac = device.get_mac_address();
+
+	#[cfg(feature = "trace")]
+	let device = Tracer::new(device, |timestamp, printer| trace!("{timestamp} {printer}"));
+	let mut device = Counted::new(device);
+
+	let mut iface = create_iface(&mut device, mac);
+	if let Some(ip) = interface_var("HERMIT_IP", index) {
+		let mask = interface_var("HERMIT_MASK", index).unwrap_or("255.255.255.0");
+		configure_ipv4(&mut iface, ip, mask, None, routes, index);
+	}
+	configure_ipv6(
+		&mut iface,
+		mac,
+		interface_var("HERMIT_IPV6", index),
+		None,
+		routes,
+		index,
+	);
+
+	Link { iface, device }
+}
+
+/// Creates the loopback interface beside the network cards.
+#[cfg(any(
+	all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
+	all(target_arch = "x86_64", feature = "rtl8139"),
+	feature = "e1000",
+	feature = "virtio-net",
+))]
+fn create_loopback() -> Loopback {
+	let device = LoopbackDriver::new();
+	let mac = device.get_mac_address();
+
+	#[cfg(feature = "trace")]
+	let device = Tracer::new(device, |timestamp, printer| trace!("{timestamp} {printer}"));
+	let mut device = Counted::new(device);
+
+	let mut iface = create_iface(&mut device, mac);
+	configure_loopback(&mut iface);
+
+	Loopback { iface, device }
+}
+
+/// Adds the static routes of `HERMIT_ROUTES` to `nic`.
+///
+/// The routes are separated by commas, e.g.,
+/// `HERMIT_ROUTES="10.2.0.0/16 via 10.0.6.1 dev eth1,fd00::/8 via fe80::1"`.
+/// Without `dev`, the route uses the interface, which reaches the gateway.
+fn configure_routes(nic: &mut NetworkInterface<'_>) {
+	let Some(routes) = hermit_var!("HERMIT_ROUTES") else {
+		return;
+	};
+
+	for entry in routes
+		.split(',')
+		.map(str::trim)
+		.filter(|entry| !entry.is_empty())
+	{
+		let Some((destination, gateway, interface)) = route::parse(entry) else {
+			warn!("Unable to parse the route {entry}");
+			continue;
+		};
+		let interface = interface.unwrap_or_else(|| nic.interface_of(gateway));
+		if interface >= nic.interface_count() {
+			warn!("Unable to add the route {entry}, the interface does not exist");
+			continue;
+		}
+
+		info!(
+			"Configure route to {destination} via {gateway} through {}",
+			route::interface_name(interface)
+		);
+		nic.add_route(Route {
+			destination,
+			gateway: Some(gateway),
+			interface,
+		});
+	}
+}
+
+impl<'a> NetworkInterface<'a> {
+	/// Creates the network interface.
+	///
+	/// With the feature `dhcpv4`, the IPv4 configuration is acquired through
+	/// DHCP, unless a static address is specified with `HERMIT_IP`. Further
+	/// network cards are configured statically and share the sockets of the
+	/// primary interface.
 	pub(crate) fn create() -> NetworkState<'a> {
 		cfg_if! {
 			if #[cfg(any(
 				all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
 				all(target_arch = "x86_64", feature = "rtl8139"),
+				feature = "e1000",
 				feature = "virtio-net",
 			))] {
-				#[cfg_attr(feature = "trace", expect(unused_mut))]
-				let Some(mut device) = NETWORK_DEVICE.lock().take() else {
+				let mut devices = core::mem::take(&mut *NETWORK_DEVICES.lock()).into_iter();
+				let Some(device) = devices.next() else {
 					return NetworkState::InitializationFailed;
 				};
+				let loopback = Some(create_loopback());
 			} else {
-				#[cfg_attr(feature = "trace", expect(unused_mut))]
-				let mut device = LoopbackDriver::new();
+				let device = LoopbackDriver::new();
+				let devices = Vec::<NetworkDevice>::new().into_iter();
+				let loopback = None;
 			}
 		}
 
 		let mac = device.get_mac_address();
 
 		#[cfg(feature = "trace")]
-		let mut device = Tracer::new(device, |timestamp, printer| trace!("{timestamp} {printer}"));
+		let device = Tracer::new(device, |timestamp, printer| trace!("{timestamp} {printer}"));
+		let mut device = Counted::new(device);
 
-		let myip = Ipv4Address::from_str(hermit_var_or!("HERMIT_IP", "10.0.5.3")).unwrap();
-		let mygw = Ipv4Address::from_str(hermit_var_or!("HERMIT_GATEWAY", "10.0.5.1")).unwrap();
-		let mymask = Ipv4Address::from_str(hermit_var_or!("HERMIT_MASK", "255.255.255.0")).unwrap();
-		// Quad9 DNS server
-		#[cfg(feature = "dns")]
-		let mydns1 = Ipv4Address::from_str(hermit_var_or!("HERMIT_DNS1", "9.9.9.9")).unwrap();
-		// Cloudflare DNS server
-		#[cfg(feature = "dns")]
-		let mydns2 = Ipv4Address::from_str(hermit_var_or!("HERMIT_DNS2", "1.1.1.1")).unwrap();
+		let mut iface = create_iface(&mut device, mac);
+		let mut routes = Vec::new();
 
-		let ethernet_addr = EthernetAddress(mac);
-		let hardware_addr = HardwareAddress::Ethernet(ethernet_addr);
-		let ip_addr = IpCidr::from(Ipv4Cidr::from_netmask(myip, mymask).unwrap());
+		let use_dhcp = cfg!(feature = "dhcpv4") && hermit_var!("HERMIT_IP").is_none();
+		if use_dhcp {
+			info!("Configure network interface through DHCPv4");
+		} else {
+			configure_ipv4(
+				&mut iface,
+				hermit_var_or!("HERMIT_IP", "10.0.5.3"),
+				hermit_var_or!("HERMIT_MASK", "255.255.255.0"),
+				Some(hermit_var_or!("HERMIT_GATEWAY", "10.0.5.1")),
+				&mut routes,
+				0,
+			);
+		}
+		#[cfg_attr(not(feature = "slaac"), expect(unused_variables))]
+		let autoconfigure = configure_ipv6(
+			&mut iface,
+			mac,
+			hermit_var!("HERMIT_IPV6").as_deref(),
+			hermit_var!("HERMIT_IPV6_GATEWAY").as_deref(),
+			&mut routes,
+			0,
+		);
 
-		info!("MAC address {hardware_addr}");
-		info!("Configure network interface with address {ip_addr}");
-		info!("Configure gateway with address {mygw}");
-		let capabilities = device.capabilities();
-		info!("{:?}", capabilities.checksum);
-		info!("MTU: {} bytes", capabilities.max_transmission_unit);
-
-		// use the current time based on the wall-clock time as seed
-		let mut config = Config::new(hardware_addr);
-		config.random_seed = (arch::kernel::systemtime::now_micros()) / 1_000_000;
-		if capabilities.medium == Medium::Ethernet {
-			config.hardware_addr = hardware_addr;
+		if loopback.is_none() {
+			configure_loopback(&mut iface);
 		}
 
-		let mut iface = Interface::new(config, &mut device, crate::executor::network::now());
-		iface.update_ip_addrs(|ip_addrs| {
-			ip_addrs.push(ip_addr).unwrap();
-		});
-		iface.routes_mut().add_default_ipv4_route(mygw).unwrap();
+		let links = devices
+			.enumerate()
+			.map(|(i, device)| create_link(device, &mut routes, i + 1))
+			.collect::<Vec<_>>();
 
 		#[allow(unused_mut)]
 		let mut sockets = SocketSet::new(vec![]);
 
+		#[cfg(feature = "dhcpv4")]
+		let dhcp_handle = use_dhcp.then(|| sockets.add(dhcpv4::Socket::new()));
+
+		// The raw socket receives the router advertisements of all interfaces,
+		// which cannot be told apart.
+		#[cfg(feature = "slaac")]
+		let autoconfigure = autoconfigure && links.is_empty();
+		#[cfg(feature = "slaac")]
+		let slaac_handle = autoconfigure.then(|| sockets.add(crate::executor::slaac::socket()));
+
+		// The DNS servers of the DHCP server are used, once the lease has been acquired.
 		#[cfg(feature = "dns")]
-		let dns_handle = {
-			let servers = &[mydns1.into(), mydns2.into()];
-			let dns_socket = dns::Socket::new(servers, vec![]);
-			sockets.add(dns_socket)
+		let dns_servers = if use_dhcp {
+			vec![]
+		} else {
+			// Quad9 DNS server
+			let mydns1 = IpAddr::from_str(hermit_var_or!("HERMIT_DNS1", "9.9.9.9")).unwrap();
+			// Cloudflare DNS server
+			let mydns2 = IpAddr::from_str(hermit_var_or!("HERMIT_DNS2", "1.1.1.1")).unwrap();
+			vec![mydns1.into(), mydns2.into()]
 		};
+		#[cfg(feature = "dns")]
+		let dns_handle = (!dns_servers.is_empty())
+			.then(|| sockets.add(dns::Socket::new(dns_servers.as_slice(), vec![])));
 
-		NetworkState::Initialized(Box::new(Self {
+		let mut nic = Box::new(Self {
 			iface,
 			sockets,
 			device,
+			detached: false,
+			#[cfg(feature = "dhcpv4")]
+			dhcp_handle,
+			#[cfg(feature = "slaac")]
+			slaac_handle,
+			#[cfg(feature = "dns")]
+			dns_handle,
 			#[cfg(feature = "dns")]
-			dns_handle: Some(dns_handle),
-		}))
+			dns_servers,
+			#[cfg(feature = "udp")]
+			multicast_groups: vec![],
+			links,
+			routes,
+			neighbors: Neighbors::default(),
+			mtus: Mtus::default(),
+			loopback,
+		});
+		nic.update_routes();
+		configure_routes(&mut nic);
+
+		NetworkState::Initialized(nic)
 	}
 }
@@ -1,7 +1,23 @@
+#[cfg(feature = "pcap")]
+pub(crate) mod capture;
 #[cfg(feature = "net")]
 pub(crate) mod device;
 #[cfg(feature = "net")]
+pub(crate) mod mtu;
+#[cfg(feature = "net")]
+pub(crate) mod neighbor;
+#[cfg(feature = "log-net")]
+pub(crate) mod netlog;
+#[cfg(feature = "net")]
+pub(crate) mod netstat;
+#[cfg(feature = "net")]
 pub(crate) mod network;
+#[cfg(feature = "net")]
+pub(crate) mod route;
+#[cfg(feature = "slaac")]
+pub(crate) mod slaac;
+#[cfg(feature = "net")]
+pub(crate) mod socket_buffer;
 pub(crate) mod task;
 #[cfg(feature = "vsock")]
 pub(crate) mod vsock;
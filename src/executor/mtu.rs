@@ -0,0 +1,648 @@
+//! MTU of the network cards and discovery of the MTU of a path.
+//!
+//! The MTU of a network card is given at boot by `net.mtu=<bytes>` up to
+//! jumbo frames of 9000 bytes, so that the drivers allocate buffers of the
+//! size. At runtime, [`NetworkInterface::set_mtu`] lowers the MTU or raises
+//! it up to the MTU of the device. smoltcp reads the MTU of its device only,
+//! when the interface is created. Therefore, [`super::netstat::Counted`]
+//! limits the MTU, which the device reports, and the interface is recreated,
+//! whenever its MTU changes.
+//!
+//! smoltcp sends IPv4 packets with the Don't Fragment flag, but ignores the
+//! ICMP messages, which report that a packet exceeds the MTU of a router on
+//! its path. With the feature `tcp`, the devices pass such reports of TCP
+//! segments to [`inspect`]. A report is only accepted, if its checksum is
+//! valid and the quoted segment belongs to an open connection and lies within
+//! the data, which has been sent and has not been acknowledged. Since smoltcp
+//! does not expose its sequence numbers, the devices pass the sent segments
+//! to [`sent`]. The interface, which reaches the destination,
+//! lowers its MTU to the MTU of the path, until the report expires after ten
+//! minutes. Since smoltcp has no MTU per destination, the MTU of the path
+//! applies to all destinations of the interface. TCP sizes its segments by
+//! the MTU at each transmission, so that established connections adapt to
+//! it, including the retransmission of the lost segment.
+
+use alloc::collections::BTreeMap;
+#[cfg(feature = "tcp")]
+use alloc::vec::Vec;
+#[cfg(feature = "tcp")]
+use core::mem;
+#[cfg(feature = "tcp")]
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "tcp")]
+use hermit_sync::InterruptSpinMutex;
+use smoltcp::phy::{Device, Medium};
+#[cfg(feature = "tcp")]
+use smoltcp::socket::{AnySocket, tcp};
+#[cfg(feature = "tcp")]
+use smoltcp::time::{Duration, Instant};
+#[cfg(feature = "tcp")]
+use smoltcp::wire::{
+	EthernetFrame, EthernetProtocol, Icmpv4Packet, Icmpv6Packet, IpAddress, IpEndpoint, IpProtocol,
+	Ipv4Packet, Ipv6Packet, TcpPacket,
+};
+
+use crate::drivers::net::{ETHERNET_HEADER_LEN, MIN_IP_MTU};
+use crate::errno::Errno;
+use crate::executor::device::recreate_iface;
+use crate::executor::network::NetworkInterface;
+use crate::executor::route;
+
+/// Lifetime of a reported MTU of a path
+#[cfg(feature = "tcp")]
+const PATH_MTU_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Smallest MTU of an IPv4 path, which is accepted, like `min_pmtu` of Linux
+#[cfg(feature = "tcp")]
+const MIN_PATH_MTU_IPV4: u16 = 552;
+
+/// Maximum number of reports, which wait for the next poll
+#[cfg(feature = "tcp")]
+const MAX_REPORTS: usize = 16;
+
+/// Maximum number of connections, whose sent segments are tracked
+#[cfg(feature = "tcp")]
+const MAX_CONNECTIONS: usize = 1024;
+
+/// ICMP message, which reports the MTU of the path of a TCP segment
+#[cfg(feature = "tcp")]
+#[derive(Copy, Clone, Debug)]
+struct Report {
+	/// Source of the segment
+	source: IpEndpoint,
+	/// Destination of the segment
+	destination: IpEndpoint,
+	/// Sequence number of the segment
+	seq_number: u32,
+	/// MTU of the IP layer of the path
+	mtu: u16,
+}
+
+#[cfg(feature = "tcp")]
+static REPORTS: InterruptSpinMutex<Vec<Report>> = InterruptSpinMutex::new(Vec::new());
+
+/// Whether [`REPORTS`] is not empty, so that the polls do not take the lock
+#[cfg(feature = "tcp")]
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+/// End of the sequence numbers, which have been sent on a connection, by its
+/// local and its remote endpoint
+#[cfg(feature = "tcp")]
+static SENT: InterruptSpinMutex<BTreeMap<(IpEndpoint, IpEndpoint), u32>> =
+	InterruptSpinMutex::new(BTreeMap::new());
+
+/// Whether [`SENT`] is full, so that the next poll removes the closed
+/// connections
+#[cfg(feature = "tcp")]
+static SENT_FULL: AtomicBool = AtomicBool::new(false);
+
+/// MTU of a path, which a router has reported
+#[cfg(feature = "tcp")]
+#[derive(Copy, Clone, Debug)]
+struct PathMtu {
+	destination: IpAddress,
+	interface: usize,
+	mtu: u16,
+	expires_at: Instant,
+}
+
+/// MTUs of the interfaces
+#[derive(Debug, Default)]
+pub(crate) struct Mtus {
+	/// MTU of the IP layer, which has been set for an interface
+	configured: BTreeMap<usize, u16>,
+	#[cfg(feature = "tcp")]
+	paths: Vec<PathMtu>,
+}
+
+/// Parses an ICMP message of `frame`, which reports the MTU of the path of
+/// a TCP segment.
+#[cfg(feature = "tcp")]
+fn parse(frame: &[u8]) -> Option<Report> {
+	let frame = EthernetFrame::new_checked(frame).ok()?;
+	let (source, destination, quote, mtu) = match frame.ethertype() {
+		EthernetProtocol::Ipv4 => {
+			let packet = Ipv4Packet::new_checked(frame.payload()).ok()?;
+			if packet.next_header() != IpProtocol::Icmp {
+				return None;
+			}
+
+			// Destination Unreachable, Fragmentation Needed, followed by the
+			// MTU of the next hop and the header of the segment
+			let icmp = packet.payload();
+			if icmp.len() < 8 + 20 || icmp[0] != 3 || icmp[1] != 4 {
+				return None;
+			}
+			if !Icmpv4Packet::new_unchecked(icmp).verify_checksum() {
+				return None;
+			}
+			let original = Ipv4Packet::new_unchecked(&icmp[8..]);
+			let header_len = usize::from(original.header_len());
+			if original.version() != 4 || header_len < 20 {
+				return None;
+			}
+			if original.next_header() != IpProtocol::Tcp {
+				return None;
+			}
+
+			let mtu = u16::from_be_bytes([icmp[6], icmp[7]]);
+			let quote = icmp.get(8 + header_len..)?;
+			let source = IpAddress::from(original.src_addr());
+			let destination = IpAddress::from(original.dst_addr());
+			(source, destination, quote, mtu)
+		}
+		EthernetProtocol::Ipv6 => {
+			let packet = Ipv6Packet::new_checked(frame.payload()).ok()?;
+			if packet.next_header() != IpProtocol::Icmpv6 {
+				return None;
+			}
+
+			// Packet Too Big, followed by the MTU and the header of the segment
+			let icmp = packet.payload();
+			if icmp.len() < 8 + 40 || icmp[0] != 2 {
+				return None;
+			}
+			let message = Icmpv6Packet::new_unchecked(icmp);
+			if !message.verify_checksum(&packet.src_addr(), &packet.dst_addr()) {
+				return None;
+			}
+			let original = Ipv6Packet::new_unchecked(&icmp[8..]);
+			if original.version() != 6 || original.next_header() != IpProtocol::Tcp {
+				return None;
+			}
+
+			let mtu = u32::from_be_bytes(icmp[4..8].try_into().unwrap());
+			let mtu = mtu.try_into().unwrap_or(u16::MAX);
+			let quote = &icmp[8 + 40..];
+			let source = IpAddress::from(original.src_addr());
+			let destination = IpAddress::from(original.dst_addr());
+			(source, destination, quote, mtu)
+		}
+		_ => return None,
+	};
+
+	// The quote contains at least the ports and the sequence number.
+	let segment = quote.get(..8)?;
+	let source_port = u16::from_be_bytes([segment[0], segment[1]]);
+	let destination_port = u16::from_be_bytes([segment[2], segment[3]]);
+	Some(Report {
+		source: IpEndpoint::new(source, source_port),
+		destination: IpEndpoint::new(destination, destination_port),
+		seq_number: u32::from_be_bytes(segment[4..8].try_into().unwrap()),
+		mtu,
+	})
+}
+
+/// Returns whether the segment with `seq_number` lies within the data, which
+/// has been sent up to `end` and of which at most `queued` bytes have not been
+/// acknowledged.
+#[cfg(feature = "tcp")]
+fn is_in_flight(seq_number: u32, end: u32, queued: usize) -> bool {
+	let behind = end.wrapping_sub(seq_number);
+	behind != 0 && usize::try_from(behind).is_ok_and(|behind| behind <= queued)
+}
+
+/// Records the end of the sequence numbers of the TCP segment in `frame`.
+///
+/// Called by the devices of the network stack for every transmitted frame.
+/// Only segments, which exceed the smallest accepted MTU of a path, are
+/// recorded, since only they can cause a report.
+#[cfg(feature = "tcp")]
+pub(crate) fn sent(frame: &[u8]) {
+	let Ok(frame) = EthernetFrame::new_checked(frame) else {
+		return;
+	};
+	let (source, destination, segment) = match frame.ethertype() {
+		EthernetProtocol::Ipv4 => {
+			let Ok(packet) = Ipv4Packet::new_checked(frame.payload()) else {
+				return;
+			};
+			if packet.next_header() != IpProtocol::Tcp || packet.total_len() <= MIN_PATH_MTU_IPV4 {
+				return;
+			}
+			(
+				IpAddress::from(packet.src_addr()),
+				IpAddress::from(packet.dst_addr()),
+				packet.payload(),
+			)
+		}
+		EthernetProtocol::Ipv6 => {
+			let Ok(packet) = Ipv6Packet::new_checked(frame.payload()) else {
+				return;
+			};
+			if packet.next_header() != IpProtocol::Tcp
+				|| usize::from(packet.payload_len()) + 40 <= usize::from(MIN_IP_MTU)
+			{
+				return;
+			}
+			(
+				IpAddress::from(packet.src_addr()),
+				IpAddress::from(packet.dst_addr()),
+				packet.payload(),
+			)
+		}
+		_ => return,
+	};
+	let Ok(segment) = TcpPacket::new_checked(segment) else {
+		return;
+	};
+	let Ok(len) = u32::try_from(segment.payload().len()) else {
+		return;
+	};
+	let end = segment.seq_number().0.cast_unsigned().wrapping_add(len);
+	let connection = (
+		IpEndpoint::new(source, segment.src_port()),
+		IpEndpoint::new(destination, segment.dst_port()),
+	);
+
+	let mut sent = SENT.lock();
+	let count = sent.len();
+	match sent.get_mut(&connection) {
+		// A retransmission does not move the end backwards.
+		Some(sent_end) => {
+			if end.wrapping_sub(*sent_end).cast_signed() > 0 {
+				*sent_end = end;
+			}
+		}
+		None if count < MAX_CONNECTIONS => {
+			sent.insert(connection, end);
+		}
+		None => SENT_FULL.store(true, Ordering::Relaxed),
+	}
+}
+
+/// Records the report of the MTU of a path, if `frame` contains one.
+///
+/// Called by the devices of the network stack for every received frame.
+#[cfg(feature = "tcp")]
+pub(crate) fn inspect(frame: &[u8]) {
+	let Some(report) = parse(frame) else {
+		return;
+	};
+
+	let mut reports = REPORTS.lock();
+	if reports.len() < MAX_REPORTS {
+		reports.push(report);
+		PENDING.store(true, Ordering::Relaxed);
+	}
+}
+
+impl NetworkInterface<'_> {
+	/// Returns the MTU of the IP layer of the network card `index`.
+	pub(crate) fn mtu(&self, index: usize) -> Option<u16> {
+		let capabilities = match index.checked_sub(1) {
+			None => self.device.capabilities(),
+			Some(i) => self.links.get(i)?.device.capabilities(),
+		};
+		header_len(capabilities.medium)
+			.and_then(|header_len| capabilities.max_transmission_unit.checked_sub(header_len))
+			.map(|mtu| mtu.try_into().unwrap_or(u16::MAX))
+	}
+
+	/// Sets the MTU of the IP layer of the network card `index` to `mtu`.
+	///
+	/// `None` restores the MTU of the device.
+	pub(crate) fn set_mtu(&mut self, index: usize, mtu: Option<u16>) -> Result<(), Errno> {
+		if index >= self.interface_count() || self.mtu(index).is_none() {
+			return Err(Errno::Nodev);
+		}
+
+		match mtu {
+			Some(mtu) => {
+				if mtu < MIN_IP_MTU || mtu > self.device_mtu(index) {
+					return Err(Errno::Inval);
+				}
+				self.mtus.configured.insert(index, mtu);
+			}
+			None => {
+				self.mtus.configured.remove(&index);
+			}
+		}
+		self.apply_mtu(index);
+		Ok(())
+	}
+
+	/// Returns the MTU of the IP layer of the device of the network card `index`.
+	fn device_mtu(&self, index: usize) -> u16 {
+		let capabilities = match index.checked_sub(1) {
+			None => (*self.device).capabilities(),
+			Some(i) => (*self.links[i].device).capabilities(),
+		};
+		let header_len = header_len(capabilities.medium).unwrap_or(0);
+		(capabilities.max_transmission_unit - header_len)
+			.try_into()
+			.unwrap_or(u16::MAX)
+	}
+
+	/// Limits the MTU of the network card `index` to the configured MTU and
+	/// the MTUs of its paths and recreates the interface, if the MTU changes.
+	fn apply_mtu(&mut self, index: usize) {
+		let configured = self.mtus.configured.get(&index).copied();
+		#[cfg(feature = "tcp")]
+		let path = self
+			.mtus
+			.paths
+			.iter()
+			.filter(|path| path.interface == index)
+			.map(|path| path.mtu)
+			.min();
+		#[cfg(not(feature = "tcp"))]
+		let path = None;
+
+		let mtu = configured.into_iter().chain(path).min();
+		if self.mtu(index) == Some(mtu.unwrap_or_else(|| self.device_mtu(index))) {
+			return;
+		}
+
+		let (iface, device) = match index.checked_sub(1) {
+			None => (&mut self.iface, &mut self.device),
+			Some(i) => {
+				let link = &mut self.links[i];
+				(&mut link.iface, &mut link.device)
+			}
+		};
+		let header_len = header_len(device.capabilities().medium).unwrap_or(0);
+		device.set_mtu(mtu.map(|mtu| usize::from(mtu) + header_len));
+		*iface = recreate_iface(iface, device);
+		info!(
+			"MTU of {}: {} bytes",
+			route::interface_name(index),
+			device.capabilities().max_transmission_unit - header_len
+		);
+
+		#[cfg(feature = "udp")]
+		if index == 0 {
+			for (group, _) in &self.multicast_groups {
+				if self.iface.join_multicast_group(*group).is_err() {
+					warn!("Unable to join the multicast group {group} again");
+				}
+			}
+		}
+		self.update_routes();
+		self.neighbors.advertise_again();
+	}
+
+	/// Applies the reports of the MTUs of paths and expires the old ones.
+	#[cfg(feature = "tcp")]
+	pub(super) fn update_path_mtus(&mut self, timestamp: Instant) {
+		let mut changed = Vec::new();
+
+		if PENDING.swap(false, Ordering::Relaxed) {
+			let reports = mem::take(&mut *REPORTS.lock());
+			for report in reports {
+				if !self
+					.ip_addrs()
+					.any(|cidr| cidr.address() == report.source.addr)
+					|| route::is_loopback(report.destination.addr)
+					|| !self.is_report_in_flight(&report)
+				{
+					continue;
+				}
+
+				let destination = report.destination.addr;
+				let min_mtu = match destination {
+					IpAddress::Ipv4(_) => MIN_PATH_MTU_IPV4,
+					IpAddress::Ipv6(_) => MIN_IP_MTU,
+				};
+				let mtu = report.mtu.max(min_mtu);
+				let interface = self.interface_of(destination);
+				// A report can only lower the MTU.
+				if self.mtu(interface).is_none_or(|current| mtu >= current) {
+					continue;
+				}
+
+				debug!("MTU of the path to {destination}: {mtu} bytes");
+				self.mtus
+					.paths
+					.retain(|path| path.destination != destination);
+				self.mtus.paths.push(PathMtu {
+					destination,
+					interface,
+					mtu,
+					expires_at: timestamp + PATH_MTU_TIMEOUT,
+				});
+				changed.push(interface);
+			}
+		}
+
+		self.mtus.paths.retain(|path| {
+			let expired = path.expires_at <= timestamp;
+			if expired {
+				changed.push(path.interface);
+			}
+			!expired
+		});
+
+		changed.sort_unstable();
+		changed.dedup();
+		for index in changed {
+			self.apply_mtu(index);
+		}
+
+		if SENT_FULL.swap(false, Ordering::Relaxed) {
+			let open: Vec<(IpEndpoint, IpEndpoint)> = self
+				.sockets
+				.iter()
+				.filter_map(|(_, socket)| {
+					let socket = tcp::Socket::downcast(socket)?;
+					Some((socket.local_endpoint()?, socket.remote_endpoint()?))
+				})
+				.collect();
+			SENT.lock()
+				.retain(|connection, _| open.contains(connection));
+		}
+	}
+
+	/// Returns whether the segment of `report` belongs to an open connection
+	/// and has been sent, but not been acknowledged.
+	#[cfg(feature = "tcp")]
+	fn is_report_in_flight(&self, report: &Report) -> bool {
+		let Some(end) = SENT
+			.lock()
+			.get(&(report.source, report.destination))
+			.copied()
+		else {
+			return false;
+		};
+
+		self.sockets.iter().any(|(_, socket)| {
+			tcp::Socket::downcast(socket).is_some_and(|socket| {
+				socket.local_endpoint() == Some(report.source)
+					&& socket.remote_endpoint() == Some(report.destination)
+					&& is_in_flight(report.seq_number, end, socket.send_queue())
+			})
+		})
+	}
+}
+
+/// Returns the length of the link-layer header, which the MTU of a device of
+/// the medium `medium` includes, or `None`, if it is not a network card.
+fn header_len(medium: Medium) -> Option<usize> {
+	match medium {
+		Medium::Ethernet => Some(ETHERNET_HEADER_LEN.into()),
+		_ => None,
+	}
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+	use smoltcp::phy::ChecksumCapabilities;
+	use smoltcp::wire::{Ipv4Address, Ipv4Repr, Ipv6Address, Ipv6Repr};
+
+	use super::*;
+
+	const LOCAL_IPV4: Ipv4Address = Ipv4Address::new(10, 0, 5, 3);
+	const REMOTE_IPV4: Ipv4Address = Ipv4Address::new(192, 0, 2, 1);
+	const ROUTER_IPV4: Ipv4Address = Ipv4Address::new(10, 0, 5, 2);
+	const LOCAL_IPV6: Ipv6Address = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 3);
+	const REMOTE_IPV6: Ipv6Address = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+	const ROUTER_IPV6: Ipv6Address = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2);
+
+	/// Writes the ports and the sequence number of the quoted segment.
+	fn quote_segment(quote: &mut [u8]) {
+		quote[..2].copy_from_slice(&50000u16.to_be_bytes());
+		quote[2..4].copy_from_slice(&80u16.to_be_bytes());
+		quote[4..8].copy_from_slice(&0x1000u32.to_be_bytes());
+	}
+
+	/// Returns the IPv4 header and the start of a segment of 1500 bytes.
+	fn quote_ipv4(next_header: IpProtocol) -> Vec<u8> {
+		let repr = Ipv4Repr {
+			src_addr: LOCAL_IPV4,
+			dst_addr: REMOTE_IPV4,
+			next_header,
+			payload_len: 1480,
+			hop_limit: 64,
+		};
+		let mut quote = vec![0; repr.buffer_len() + 8];
+		repr.emit(
+			&mut Ipv4Packet::new_unchecked(&mut quote[..]),
+			&ChecksumCapabilities::default(),
+		);
+		quote_segment(&mut quote[repr.buffer_len()..]);
+		quote
+	}
+
+	/// Returns the IPv6 header and the start of a segment of 1500 bytes.
+	fn quote_ipv6(next_header: IpProtocol) -> Vec<u8> {
+		let repr = Ipv6Repr {
+			src_addr: LOCAL_IPV6,
+			dst_addr: REMOTE_IPV6,
+			next_header,
+			payload_len: 1460,
+			hop_limit: 64,
+		};
+		let mut quote = vec![0; repr.buffer_len() + 8];
+		repr.emit(&mut Ipv6Packet::new_unchecked(&mut quote[..]));
+		quote_segment(&mut quote[repr.buffer_len()..]);
+		quote
+	}
+
+	/// Returns an Ethernet frame with the ICMP message `icmp_type` and `code`
+	/// from the router, which reports `mtu` for the `quote`.
+	fn frame_ipv4(icmp_type: u8, code: u8, mtu: u16, quote: &[u8]) -> Vec<u8> {
+		let mut icmp = vec![icmp_type, code, 0, 0, 0, 0];
+		icmp.extend_from_slice(&mtu.to_be_bytes());
+		icmp.extend_from_slice(quote);
+		Icmpv4Packet::new_unchecked(&mut icmp[..]).fill_checksum();
+
+		let repr = Ipv4Repr {
+			src_addr: ROUTER_IPV4,
+			dst_addr: LOCAL_IPV4,
+			next_header: IpProtocol::Icmp,
+			payload_len: icmp.len(),
+			hop_limit: 64,
+		};
+		let mut frame = vec![0; usize::from(ETHERNET_HEADER_LEN) + repr.buffer_len() + icmp.len()];
+		let mut ethernet = EthernetFrame::new_unchecked(&mut frame[..]);
+		ethernet.set_ethertype(EthernetProtocol::Ipv4);
+		let mut packet = Ipv4Packet::new_unchecked(ethernet.payload_mut());
+		repr.emit(&mut packet, &ChecksumCapabilities::default());
+		packet.payload_mut().copy_from_slice(&icmp);
+		frame
+	}
+
+	/// Returns an Ethernet frame with a Packet Too Big message from the
+	/// router, which reports `mtu` for the `quote`.
+	fn frame_ipv6(mtu: u32, quote: &[u8]) -> Vec<u8> {
+		let mut icmp = vec![2, 0, 0, 0];
+		icmp.extend_from_slice(&mtu.to_be_bytes());
+		icmp.extend_from_slice(quote);
+		Icmpv6Packet::new_unchecked(&mut icmp[..]).fill_checksum(&ROUTER_IPV6, &LOCAL_IPV6);
+
+		let repr = Ipv6Repr {
+			src_addr: ROUTER_IPV6,
+			dst_addr: LOCAL_IPV6,
+			next_header: IpProtocol::Icmpv6,
+			payload_len: icmp.len(),
+			hop_limit: 64,
+		};
+		let mut frame = vec![0; usize::from(ETHERNET_HEADER_LEN) + repr.buffer_len() + icmp.len()];
+		let mut ethernet = EthernetFrame::new_unchecked(&mut frame[..]);
+		ethernet.set_ethertype(EthernetProtocol::Ipv6);
+		let mut packet = Ipv6Packet::new_unchecked(ethernet.payload_mut());
+		repr.emit(&mut packet);
+		packet.payload_mut().copy_from_slice(&icmp);
+		frame
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_mtu_parse_ipv4() {
+		let report = parse(&frame_ipv4(3, 4, 1400, &quote_ipv4(IpProtocol::Tcp))).unwrap();
+		assert_eq!(report.source, IpEndpoint::new(LOCAL_IPV4.into(), 50000));
+		assert_eq!(report.destination, IpEndpoint::new(REMOTE_IPV4.into(), 80));
+		assert_eq!(report.seq_number, 0x1000);
+		assert_eq!(report.mtu, 1400);
+
+		// Another Destination Unreachable code
+		assert!(parse(&frame_ipv4(3, 1, 1400, &quote_ipv4(IpProtocol::Tcp))).is_none());
+		// A UDP datagram
+		assert!(parse(&frame_ipv4(3, 4, 1400, &quote_ipv4(IpProtocol::Udp))).is_none());
+		// A quote without the sequence number
+		let quote = quote_ipv4(IpProtocol::Tcp);
+		assert!(parse(&frame_ipv4(3, 4, 1400, &quote[..quote.len() - 4])).is_none());
+		// An invalid checksum
+		let mut frame = frame_ipv4(3, 4, 1400, &quote);
+		let mtu_offset = usize::from(ETHERNET_HEADER_LEN) + 20 + 6;
+		frame[mtu_offset] ^= 0x01;
+		assert!(parse(&frame).is_none());
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_mtu_parse_ipv6() {
+		let report = parse(&frame_ipv6(1400, &quote_ipv6(IpProtocol::Tcp))).unwrap();
+		assert_eq!(report.source, IpEndpoint::new(LOCAL_IPV6.into(), 50000));
+		assert_eq!(report.destination, IpEndpoint::new(REMOTE_IPV6.into(), 80));
+		assert_eq!(report.seq_number, 0x1000);
+		assert_eq!(report.mtu, 1400);
+
+		// An MTU beyond 16 bits is limited.
+		let report = parse(&frame_ipv6(0x10000, &quote_ipv6(IpProtocol::Tcp))).unwrap();
+		assert_eq!(report.mtu, u16::MAX);
+
+		assert!(parse(&frame_ipv6(1400, &quote_ipv6(IpProtocol::Udp))).is_none());
+		let quote = quote_ipv6(IpProtocol::Tcp);
+		assert!(parse(&frame_ipv6(1400, &quote[..quote.len() - 4])).is_none());
+		let mut frame = frame_ipv6(1400, &quote);
+		let mtu_offset = usize::from(ETHERNET_HEADER_LEN) + 40 + 7;
+		frame[mtu_offset] ^= 0x01;
+		assert!(parse(&frame).is_none());
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_mtu_is_in_flight() {
+		assert!(is_in_flight(0x1000, 0x1600, 0x1000));
+		assert!(is_in_flight(0x1000, 0x2000, 0x1000));
+		// The segment starts at the end or after it.
+		assert!(!is_in_flight(0x1600, 0x1600, 0x1000));
+		assert!(!is_in_flight(0x1700, 0x1600, 0x1000));
+		// The segment has been acknowledged already.
+		assert!(!is_in_flight(0x1000, 0x2001, 0x1000));
+		// The sequence numbers wrap around.
+		assert!(is_in_flight(u32::MAX - 0x10, 0x10, 0x100));
+	}
+}
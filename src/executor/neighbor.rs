@@ -0,0 +1,263 @@
+//! Static entries of the neighbor caches.
+//!
+//! smoltcp learns the link-layer addresses of its neighbors only through ARP
+//! and NDP and does not allow to insert entries into its neighbor cache.
+//! Therefore, a static entry is inserted by passing a synthetic ARP reply or
+//! IPv6 neighbor advertisement of the neighbor to the interface, which fills
+//! its cache from it. The advertisements are repeated, before the entries of
+//! the cache expire after one minute, so that the interface does not resolve
+//! a static neighbor itself.
+//!
+//! The IPv4 address of a static neighbor has to be in a subnet of the
+//! interface, otherwise the interface ignores the ARP reply. An answer of the
+//! real neighbor replaces the entry until the next advertisement and a
+//! removed entry remains in the cache, until it expires.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use smoltcp::iface::{Interface, PollResult};
+use smoltcp::phy::{self, ChecksumCapabilities, Device, DeviceCapabilities};
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{
+	ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol,
+	EthernetRepr, HardwareAddress, Icmpv6Packet, Icmpv6Repr, IpAddress, IpProtocol, Ipv6Packet,
+	Ipv6Repr, NdiscNeighborFlags, NdiscRepr, RawHardwareAddress,
+};
+
+use crate::executor::network::NetworkInterface;
+
+/// Interval of the advertisements, a third of the lifetime of the entries
+const REFRESH_INTERVAL: Duration = Duration::from_secs(20);
+
+/// Delay of the next attempt, if a device could not advertise an entry
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Static entry of a neighbor cache
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Neighbor {
+	pub addr: IpAddress,
+	pub hardware_addr: EthernetAddress,
+	/// Index of the interface, 0 is the primary interface
+	pub interface: usize,
+}
+
+/// Static entries of the interfaces
+#[derive(Debug, Default)]
+pub(crate) struct Neighbors {
+	entries: Vec<Neighbor>,
+	/// Time of the next advertisement, `None`, if the entries have changed
+	refresh_at: Option<Instant>,
+}
+
+impl Neighbors {
+	/// Returns the delay until the next advertisement.
+	pub fn poll_delay(&self, timestamp: Instant) -> Option<Duration> {
+		if self.entries.is_empty() {
+			return None;
+		}
+
+		Some(match self.refresh_at {
+			Some(refresh_at) if refresh_at > timestamp => refresh_at - timestamp,
+			_ => Duration::ZERO,
+		})
+	}
+
+	/// Advertises the entries again at the next poll, e.g., after the
+	/// interface has been recreated.
+	pub fn advertise_again(&mut self) {
+		self.refresh_at = None;
+	}
+}
+
+/// Receives the frame `frame` and then transmits through `inner`.
+struct Injector<'a, D> {
+	inner: &'a mut D,
+	frame: Option<Vec<u8>>,
+}
+
+impl<D: Device> Device for Injector<'_, D> {
+	type RxToken<'a>
+		= InjectedFrame
+	where
+		Self: 'a;
+	type TxToken<'a>
+		= D::TxToken<'a>
+	where
+		Self: 'a;
+
+	fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+		let frame = self.frame.take()?;
+		let Some(tx_token) = self.inner.transmit(timestamp) else {
+			self.frame = Some(frame);
+			return None;
+		};
+		Some((InjectedFrame(frame), tx_token))
+	}
+
+	fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+		self.inner.transmit(timestamp)
+	}
+
+	fn capabilities(&self) -> DeviceCapabilities {
+		self.inner.capabilities()
+	}
+}
+
+struct InjectedFrame(Vec<u8>);
+
+impl phy::RxToken for InjectedFrame {
+	fn consume<R, F>(self, f: F) -> R
+	where
+		F: FnOnce(&[u8]) -> R,
+	{
+		f(&self.0)
+	}
+}
+
+/// Returns the frame, which advertises `neighbor` to `iface`.
+///
+/// Returns `None`, if the interface is not an Ethernet interface or has no
+/// address of the address family of the neighbor yet.
+fn advertisement(iface: &Interface, neighbor: &Neighbor) -> Option<Vec<u8>> {
+	let HardwareAddress::Ethernet(own_hardware_addr) = iface.hardware_addr() else {
+		return None;
+	};
+	// prefer the address of the subnet of the neighbor
+	let own_addr = iface
+		.ip_addrs()
+		.iter()
+		.filter(|cidr| {
+			cidr.address().version() == neighbor.addr.version() && !cidr.address().is_unspecified()
+		})
+		.max_by_key(|cidr| cidr.contains_addr(&neighbor.addr))?
+		.address();
+
+	match (neighbor.addr, own_addr) {
+		(IpAddress::Ipv4(addr), IpAddress::Ipv4(own_addr)) => {
+			let ethernet = EthernetRepr {
+				src_addr: neighbor.hardware_addr,
+				dst_addr: own_hardware_addr,
+				ethertype: EthernetProtocol::Arp,
+			};
+			let arp = ArpRepr::EthernetIpv4 {
+				operation: ArpOperation::Reply,
+				source_hardware_addr: neighbor.hardware_addr,
+				source_protocol_addr: addr,
+				target_hardware_addr: own_hardware_addr,
+				target_protocol_addr: own_addr,
+			};
+
+			let mut buf = vec![0; ethernet.buffer_len() + arp.buffer_len()];
+			let mut frame = EthernetFrame::new_unchecked(&mut buf[..]);
+			ethernet.emit(&mut frame);
+			arp.emit(&mut ArpPacket::new_unchecked(frame.payload_mut()));
+			Some(buf)
+		}
+		(IpAddress::Ipv6(addr), IpAddress::Ipv6(own_addr)) => {
+			let ethernet = EthernetRepr {
+				src_addr: neighbor.hardware_addr,
+				dst_addr: own_hardware_addr,
+				ethertype: EthernetProtocol::Ipv6,
+			};
+			let icmp = Icmpv6Repr::Ndisc(NdiscRepr::NeighborAdvert {
+				flags: NdiscNeighborFlags::OVERRIDE,
+				target_addr: addr,
+				lladdr: Some(RawHardwareAddress::from(HardwareAddress::Ethernet(
+					neighbor.hardware_addr,
+				))),
+			});
+			// The interface only accepts neighbor advertisements with the hop limit 255.
+			let ip = Ipv6Repr {
+				src_addr: addr,
+				dst_addr: own_addr,
+				next_header: IpProtocol::Icmpv6,
+				payload_len: icmp.buffer_len(),
+				hop_limit: 255,
+			};
+
+			let mut buf = vec![0; ethernet.buffer_len() + ip.buffer_len() + icmp.buffer_len()];
+			let mut frame = EthernetFrame::new_unchecked(&mut buf[..]);
+			ethernet.emit(&mut frame);
+			let mut packet = Ipv6Packet::new_unchecked(frame.payload_mut());
+			ip.emit(&mut packet);
+			icmp.emit(
+				&addr,
+				&own_addr,
+				&mut Icmpv6Packet::new_unchecked(packet.payload_mut()),
+				&ChecksumCapabilities::default(),
+			);
+			Some(buf)
+		}
+		_ => None,
+	}
+}
+
+impl NetworkInterface<'_> {
+	/// Adds a static entry, which replaces the entry of the same address on
+	/// the same interface.
+	///
+	/// Returns the replaced entry.
+	pub(crate) fn add_neighbor(&mut self, neighbor: Neighbor) -> Option<Neighbor> {
+		let previous = self.remove_neighbor(neighbor.addr, neighbor.interface);
+		self.neighbors.entries.push(neighbor);
+		self.neighbors.advertise_again();
+		previous
+	}
+
+	/// Removes the static entry of `addr` on the interface `interface`.
+	pub(crate) fn remove_neighbor(
+		&mut self,
+		addr: IpAddress,
+		interface: usize,
+	) -> Option<Neighbor> {
+		let entries = &mut self.neighbors.entries;
+		entries
+			.iter()
+			.position(|entry| entry.addr == addr && entry.interface == interface)
+			.map(|index| entries.remove(index))
+	}
+
+	/// Advertises the static entries to their interfaces, if it is due.
+	pub(super) fn refresh_neighbors(&mut self, timestamp: Instant) -> PollResult {
+		let mut result = PollResult::None;
+		if self.neighbors.poll_delay(timestamp) != Some(Duration::ZERO) {
+			return result;
+		}
+
+		let mut complete = true;
+		for neighbor in self.neighbors.entries.clone() {
+			let (iface, device) = match neighbor.interface.checked_sub(1) {
+				None if self.detached => continue,
+				None => (&mut self.iface, &mut self.device),
+				Some(i) => {
+					let link = &mut self.links[i];
+					(&mut link.iface, &mut link.device)
+				}
+			};
+			let Some(frame) = advertisement(iface, &neighbor) else {
+				continue;
+			};
+
+			let mut device = Injector {
+				inner: device,
+				frame: Some(frame),
+			};
+			if matches!(
+				iface.poll(timestamp, &mut device, &mut self.sockets),
+				PollResult::SocketStateChanged
+			) {
+				result = PollResult::SocketStateChanged;
+			}
+			complete &= device.frame.is_none();
+		}
+
+		let interval = if complete {
+			REFRESH_INTERVAL
+		} else {
+			RETRY_INTERVAL
+		};
+		self.neighbors.refresh_at = Some(timestamp + interval);
+		result
+	}
+}
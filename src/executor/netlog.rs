@@ -0,0 +1,269 @@
+//! Forwarding of kernel messages to a remote collector.
+//!
+//! With `logsink=udp:<address>:<port>`, each message is sent as a syslog
+//! datagram (RFC 3164). With `logsink=tcp:<address>:<port>`, the messages are
+//! sent as newline-terminated syslog records (RFC 6587) and the connection is
+//! reestablished, if it breaks.
+//!
+//! The messages are buffered in a ring buffer, until the network is configured
+//! and the collector accepts them. If the buffer is full, the oldest messages
+//! are dropped.
+
+use core::fmt::{self, Write};
+use core::future;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Poll;
+
+use hermit_sync::InterruptTicketMutex;
+use log::Level;
+use smoltcp::socket::{tcp, udp};
+use smoltcp::time::Duration;
+use smoltcp::wire::IpEndpoint;
+
+use crate::executor::network::{Handle, NIC, now};
+use crate::executor::spawn;
+use crate::fd::socket::tcp::get_ephemeral_port;
+
+/// Size of the ring buffer in bytes
+const BUFFER_SIZE: usize = 0x10000;
+
+/// Maximum length of a message including its header
+const MAX_LINE: usize = 1024;
+
+/// Delay between two connection attempts to the collector
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Messages are not buffered anymore, since no sink has been configured.
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+static BUFFER: InterruptTicketMutex<LogBuffer> = InterruptTicketMutex::new(LogBuffer::new());
+
+/// Ring buffer of newline-terminated messages
+struct LogBuffer {
+	data: [u8; BUFFER_SIZE],
+	start: usize,
+	len: usize,
+}
+
+impl LogBuffer {
+	const fn new() -> Self {
+		Self {
+			data: [0; BUFFER_SIZE],
+			start: 0,
+			len: 0,
+		}
+	}
+
+	fn byte(&self, i: usize) -> u8 {
+		self.data[(self.start + i) % BUFFER_SIZE]
+	}
+
+	/// Returns the length of the oldest message including its newline.
+	fn first_len(&self) -> Option<usize> {
+		(0..self.len)
+			.find(|&i| self.byte(i) == b'\n')
+			.map(|i| i + 1)
+	}
+
+	fn consume(&mut self, len: usize) {
+		self.start = (self.start + len) % BUFFER_SIZE;
+		self.len -= len;
+	}
+
+	fn push(&mut self, line: &[u8]) {
+		while BUFFER_SIZE - self.len < line.len() {
+			let len = self.first_len().unwrap_or(self.len);
+			self.consume(len);
+		}
+
+		for &byte in line {
+			self.data[(self.start + self.len) % BUFFER_SIZE] = byte;
+			self.len += 1;
+		}
+	}
+
+	/// Copies the oldest message including its newline into `buf`.
+	fn peek(&self, buf: &mut [u8; MAX_LINE]) -> Option<usize> {
+		let len = self.first_len()?;
+		for (i, dst) in buf[..len].iter_mut().enumerate() {
+			*dst = self.byte(i);
+		}
+		Some(len)
+	}
+}
+
+/// Formats a message into a fixed buffer and truncates it, if it is too long.
+struct Line {
+	buf: [u8; MAX_LINE],
+	len: usize,
+}
+
+impl Write for Line {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		// keep room for the newline
+		let len = s.len().min(MAX_LINE - 1 - self.len);
+		self.buf[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+		self.len += len;
+		Ok(())
+	}
+}
+
+/// Buffers a kernel message for the log sink.
+///
+/// Does not allocate, so that it can be called before the heap is available.
+pub(crate) fn push(level: Level, args: &fmt::Arguments<'_>) {
+	if DISABLED.load(Ordering::Relaxed) {
+		return;
+	}
+
+	// facility kern (0) and the severity of the level
+	let severity = match level {
+		Level::Error => 3,
+		Level::Warn => 4,
+		Level::Info => 6,
+		Level::Debug | Level::Trace => 7,
+	};
+
+	let mut line = Line {
+		buf: [0; MAX_LINE],
+		len: 0,
+	};
+	let _ = write!(line, "<{severity}>hermit: {args}");
+	// Newlines within the message would split it into several records.
+	for byte in &mut line.buf[..line.len] {
+		if *byte == b'\n' {
+			*byte = b' ';
+		}
+	}
+	line.buf[line.len] = b'\n';
+	line.len += 1;
+
+	BUFFER.lock().push(&line.buf[..line.len]);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Protocol {
+	Udp,
+	Tcp,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Sink {
+	protocol: Protocol,
+	endpoint: IpEndpoint,
+}
+
+impl Sink {
+	fn parse(sink: &str) -> Option<Self> {
+		let (protocol, endpoint) = sink.split_once(':')?;
+		let protocol = match protocol {
+			"udp" => Protocol::Udp,
+			"tcp" => Protocol::Tcp,
+			_ => return None,
+		};
+
+		Some(Self {
+			protocol,
+			endpoint: endpoint.parse().ok()?,
+		})
+	}
+}
+
+/// Starts forwarding the buffered messages, if a log sink has been configured.
+pub(crate) fn init() {
+	let Some(sink) = crate::env::log_sink() else {
+		DISABLED.store(true, Ordering::Relaxed);
+		return;
+	};
+
+	match Sink::parse(sink) {
+		Some(sink) => {
+			info!("Forwarding kernel messages to {sink:?}");
+			spawn(run(sink));
+		}
+		None => {
+			DISABLED.store(true, Ordering::Relaxed);
+			error!("Invalid log sink {sink}, expected logsink=<udp|tcp>:<address>:<port>");
+		}
+	}
+}
+
+async fn run(sink: Sink) {
+	let mut handle: Option<Handle> = None;
+	let mut next_connect = now();
+	let mut line = [0u8; MAX_LINE];
+
+	future::poll_fn(|cx| {
+		let Some(mut guard) = NIC.try_lock() else {
+			// FIXME: only wake when progress can be made
+			cx.waker().wake_by_ref();
+			return Poll::Pending;
+		};
+		let Ok(nic) = guard.as_nic_mut() else {
+			return Poll::Ready(());
+		};
+
+		// wait until the interface has an address, e.g., from DHCP
+		if nic.ip_addrs().all(|cidr| cidr.address().is_unspecified()) {
+			cx.waker().wake_by_ref();
+			return Poll::Pending;
+		}
+
+		let handle = *handle.get_or_insert_with(|| match sink.protocol {
+			Protocol::Udp => {
+				let handle = nic.create_udp_handle().unwrap();
+				nic.get_mut_socket::<udp::Socket<'_>>(handle)
+					.bind(get_ephemeral_port())
+					.unwrap();
+				handle
+			}
+			Protocol::Tcp => nic.create_tcp_handle().unwrap(),
+		});
+
+		match sink.protocol {
+			Protocol::Udp => {
+				let socket = nic.get_mut_socket::<udp::Socket<'_>>(handle);
+				while socket.can_send() {
+					let Some(len) = BUFFER.lock().peek(&mut line) else {
+						break;
+					};
+					// The datagram carries a single record without the newline.
+					if socket.send_slice(&line[..len - 1], sink.endpoint).is_err() {
+						break;
+					}
+					BUFFER.lock().consume(len);
+				}
+			}
+			Protocol::Tcp => {
+				let (socket, context) =
+					nic.get_socket_and_context::<tcp::Socket<'_>>(handle, sink.endpoint.addr);
+				if socket.state() == tcp::State::CloseWait {
+					socket.close();
+				}
+				if !socket.is_open() && now() >= next_connect {
+					next_connect = now() + RECONNECT_DELAY;
+					if let Err(err) = socket.connect(context, sink.endpoint, get_ephemeral_port()) {
+						debug!("Unable to connect to the log sink: {err:?}");
+					}
+				}
+
+				while socket.may_send() {
+					let Some(len) = BUFFER.lock().peek(&mut line) else {
+						break;
+					};
+					// records are not split across sends
+					if socket.send_capacity() - socket.send_queue() < len {
+						break;
+					}
+					socket.send_slice(&line[..len]).unwrap();
+					BUFFER.lock().consume(len);
+				}
+			}
+		}
+
+		// FIXME: only wake when progress can be made
+		cx.waker().wake_by_ref();
+		Poll::<()>::Pending
+	})
+	.await;
+}
@@ -0,0 +1,289 @@
+//! Statistics of the network interfaces.
+//!
+//! Each device of the network stack is wrapped in a [`Counted`] device,
+//! which counts the frames and bytes in both directions by their protocol.
+//! Unlike the counters of the drivers, they are kept per interface and
+//! include the frames of the raw-frame interface of a detached device.
+//!
+//! Received frames, whose headers are truncated, are counted as errors.
+//! Received frames of a protocol, which the network stack does not handle,
+//! are counted as dropped.
+//!
+//! With the feature `pcap`, the frames are passed on to the captures, too.
+//! With the feature `tcp`, the received frames are inspected for reports of
+//! the MTU of a path, see [`super::mtu`].
+
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use smoltcp::phy::{self, Device, DeviceCapabilities};
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetFrame, EthernetProtocol, IpProtocol, Ipv4Packet, Ipv6Packet};
+
+/// Protocols, which are counted separately
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Protocol {
+	Arp,
+	/// ICMP and ICMPv6
+	Icmp,
+	Tcp,
+	Udp,
+	Other,
+}
+
+impl Protocol {
+	const COUNT: usize = 5;
+
+	/// Returns the protocol of the Ethernet frame `frame` and whether the
+	/// network stack handles it, or `None`, if the headers are truncated.
+	fn of_frame(frame: &[u8]) -> Option<(Self, bool)> {
+		let frame = EthernetFrame::new_checked(frame).ok()?;
+		let protocol = match frame.ethertype() {
+			EthernetProtocol::Arp => return Some((Self::Arp, true)),
+			EthernetProtocol::Ipv4 => Ipv4Packet::new_checked(frame.payload()).ok()?.next_header(),
+			EthernetProtocol::Ipv6 => Ipv6Packet::new_checked(frame.payload()).ok()?.next_header(),
+			_ => return Some((Self::Other, false)),
+		};
+
+		let protocol = match protocol {
+			IpProtocol::Icmp | IpProtocol::Icmpv6 => Self::Icmp,
+			IpProtocol::Tcp => Self::Tcp,
+			IpProtocol::Udp => Self::Udp,
+			_ => Self::Other,
+		};
+		Some((protocol, true))
+	}
+}
+
+/// Number of frames and bytes
+struct Counter {
+	packets: AtomicU64,
+	bytes: AtomicU64,
+}
+
+impl Counter {
+	const fn new() -> Self {
+		Self {
+			packets: AtomicU64::new(0),
+			bytes: AtomicU64::new(0),
+		}
+	}
+
+	fn add(&self, len: usize) {
+		self.packets.fetch_add(1, Ordering::Relaxed);
+		self.bytes
+			.fetch_add(len.try_into().unwrap(), Ordering::Relaxed);
+	}
+
+	fn get(&self) -> (u64, u64) {
+		(
+			self.packets.load(Ordering::Relaxed),
+			self.bytes.load(Ordering::Relaxed),
+		)
+	}
+}
+
+/// Counters of a network interface
+pub(crate) struct InterfaceStatistics {
+	rx: [Counter; Protocol::COUNT],
+	tx: [Counter; Protocol::COUNT],
+	rx_errors: AtomicU64,
+	rx_dropped: AtomicU64,
+	tx_busy: AtomicU64,
+}
+
+impl InterfaceStatistics {
+	const fn new() -> Self {
+		Self {
+			rx: [const { Counter::new() }; Protocol::COUNT],
+			tx: [const { Counter::new() }; Protocol::COUNT],
+			rx_errors: AtomicU64::new(0),
+			rx_dropped: AtomicU64::new(0),
+			tx_busy: AtomicU64::new(0),
+		}
+	}
+
+	fn received(&self, frame: &[u8]) {
+		let Some((protocol, handled)) = Protocol::of_frame(frame) else {
+			self.rx_errors.fetch_add(1, Ordering::Relaxed);
+			return;
+		};
+
+		self.rx[protocol as usize].add(frame.len());
+		if !handled {
+			self.rx_dropped.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+
+	fn transmitted(&self, frame: &[u8]) {
+		let protocol = Protocol::of_frame(frame).map_or(Protocol::Other, |(protocol, _)| protocol);
+		self.tx[protocol as usize].add(frame.len());
+	}
+
+	/// Returns the number of received frames and bytes of `protocol`.
+	pub fn rx(&self, protocol: Protocol) -> (u64, u64) {
+		self.rx[protocol as usize].get()
+	}
+
+	/// Returns the number of transmitted frames and bytes of `protocol`.
+	pub fn tx(&self, protocol: Protocol) -> (u64, u64) {
+		self.tx[protocol as usize].get()
+	}
+
+	/// Returns the number of received frames, whose headers are truncated.
+	pub fn rx_errors(&self) -> u64 {
+		self.rx_errors.load(Ordering::Relaxed)
+	}
+
+	/// Returns the number of received frames, which no protocol handles.
+	pub fn rx_dropped(&self) -> u64 {
+		self.rx_dropped.load(Ordering::Relaxed)
+	}
+
+	/// Returns the number of transmissions, which have been deferred,
+	/// because the device had no free buffer.
+	pub fn tx_busy(&self) -> u64 {
+		self.tx_busy.load(Ordering::Relaxed)
+	}
+}
+
+/// Device, which counts the frames of the device `D`
+///
+/// It dereferences to `D`, so that the methods of the driver remain accessible.
+pub(crate) struct Counted<D> {
+	inner: D,
+	statistics: InterfaceStatistics,
+	/// Limit of the MTU of `D`, see [`super::mtu`]
+	mtu: Option<usize>,
+}
+
+impl<D> Counted<D> {
+	pub fn new(inner: D) -> Self {
+		Self {
+			inner,
+			statistics: InterfaceStatistics::new(),
+			mtu: None,
+		}
+	}
+
+	pub fn statistics(&self) -> &InterfaceStatistics {
+		&self.statistics
+	}
+
+	/// Limits the MTU, which the device reports to the interface, to `mtu`.
+	///
+	/// `None` restores the MTU of `D`.
+	pub fn set_mtu(&mut self, mtu: Option<usize>) {
+		self.mtu = mtu;
+	}
+}
+
+impl<D> Deref for Counted<D> {
+	type Target = D;
+
+	fn deref(&self) -> &D {
+		&self.inner
+	}
+}
+
+impl<D> DerefMut for Counted<D> {
+	fn deref_mut(&mut self) -> &mut D {
+		&mut self.inner
+	}
+}
+
+impl<D: Device> Device for Counted<D> {
+	type RxToken<'a>
+		= RxToken<'a, D::RxToken<'a>>
+	where
+		Self: 'a;
+	type TxToken<'a>
+		= TxToken<'a, D::TxToken<'a>>
+	where
+		Self: 'a;
+
+	fn receive(&mut self, timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+		let statistics = &self.statistics;
+		let (rx_token, tx_token) = self.inner.receive(timestamp)?;
+		Some((
+			RxToken {
+				token: rx_token,
+				statistics,
+			},
+			TxToken {
+				token: tx_token,
+				statistics,
+			},
+		))
+	}
+
+	fn transmit(&mut self, timestamp: Instant) -> Option<Self::TxToken<'_>> {
+		let statistics = &self.statistics;
+		let Some(token) = self.inner.transmit(timestamp) else {
+			statistics.tx_busy.fetch_add(1, Ordering::Relaxed);
+			return None;
+		};
+		Some(TxToken { token, statistics })
+	}
+
+	fn capabilities(&self) -> DeviceCapabilities {
+		let mut capabilities = self.inner.capabilities();
+		if let Some(mtu) = self.mtu {
+			capabilities.max_transmission_unit = capabilities.max_transmission_unit.min(mtu);
+		}
+		capabilities
+	}
+}
+
+pub(crate) struct RxToken<'a, T> {
+	token: T,
+	statistics: &'a InterfaceStatistics,
+}
+
+impl<T: phy::RxToken> phy::RxToken for RxToken<'_, T> {
+	fn consume<R, F>(self, f: F) -> R
+	where
+		F: FnOnce(&[u8]) -> R,
+	{
+		let Self { token, statistics } = self;
+		token.consume(|frame| {
+			statistics.received(frame);
+			#[cfg(feature = "pcap")]
+			super::capture::frame(super::capture::Direction::Received, frame);
+			#[cfg(feature = "tcp")]
+			super::mtu::inspect(frame);
+			f(frame)
+		})
+	}
+
+	fn meta(&self) -> phy::PacketMeta {
+		self.token.meta()
+	}
+}
+
+pub(crate) struct TxToken<'a, T> {
+	token: T,
+	statistics: &'a InterfaceStatistics,
+}
+
+impl<T: phy::TxToken> phy::TxToken for TxToken<'_, T> {
+	fn consume<R, F>(self, len: usize, f: F) -> R
+	where
+		F: FnOnce(&mut [u8]) -> R,
+	{
+		let Self { token, statistics } = self;
+		token.consume(len, |buffer| {
+			let result = f(buffer);
+			statistics.transmitted(buffer);
+			#[cfg(feature = "pcap")]
+			super::capture::frame(super::capture::Direction::Transmitted, buffer);
+			#[cfg(feature = "tcp")]
+			super::mtu::sent(buffer);
+			result
+		})
+	}
+
+	fn set_meta(&mut self, meta: phy::PacketMeta) {
+		self.token.set_meta(meta);
+	}
+}
@@ -1,33 +1,42 @@
 use alloc::boxed::Box;
-#[cfg(feature = "dns")]
 use alloc::vec::Vec;
 use core::future;
 use core::sync::atomic::{AtomicU16, Ordering};
 use core::task::Poll;
 
 use hermit_sync::InterruptTicketMutex;
-use smoltcp::iface::{PollResult, SocketHandle, SocketSet};
+use smoltcp::iface::{Interface, PollResult, SocketHandle, SocketSet};
+use smoltcp::phy::{Device, RxToken, TxToken};
 use smoltcp::socket::AnySocket;
 #[cfg(feature = "dhcpv4")]
 use smoltcp::socket::dhcpv4;
 #[cfg(feature = "dns")]
 use smoltcp::socket::dns::{self, GetQueryResultError, QueryHandle};
+#[cfg(feature = "raw")]
+use smoltcp::socket::raw;
 #[cfg(feature = "tcp")]
 use smoltcp::socket::tcp;
 #[cfg(feature = "udp")]
 use smoltcp::socket::udp;
 use smoltcp::time::{Duration, Instant};
 #[cfg(feature = "dns")]
-use smoltcp::wire::{DnsQueryType, IpAddress};
+use smoltcp::wire::DnsQueryType;
+use smoltcp::wire::{IpAddress, IpCidr};
+#[cfg(feature = "raw")]
+use smoltcp::wire::{IpProtocol, IpVersion};
 #[cfg(feature = "dhcpv4")]
-use smoltcp::wire::{IpCidr, Ipv4Address, Ipv4Cidr};
+use smoltcp::wire::{Ipv4Address, Ipv4Cidr};
 
 use crate::arch;
+use crate::drivers::net::loopback::LoopbackDriver;
 use crate::drivers::net::{NetworkDevice, NetworkDriver};
-#[cfg(feature = "dns")]
 use crate::errno::Errno;
+use crate::executor::mtu::Mtus;
+use crate::executor::neighbor::Neighbors;
+use crate::executor::netstat::{Counted, InterfaceStatistics};
+use crate::executor::route::{self, Route};
 use crate::executor::spawn;
-#[cfg(feature = "dns")]
+#[cfg(any(feature = "dns", feature = "udp"))]
 use crate::io;
 use crate::scheduler::PerCoreSchedulerExt;
 
@@ -42,6 +51,7 @@ pub(crate) enum NetworkState<'a> {
 #[cfg(any(
 	all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
 	all(target_arch = "x86_64", feature = "rtl8139"),
+	feature = "e1000",
 	feature = "virtio-net",
 ))]
 pub(crate) fn network_handler() {
@@ -64,16 +74,60 @@ pub(crate) static NIC: InterruptTicketMutex<NetworkState<'_>> =
 	InterruptTicketMutex::new(NetworkState::Missing);
 
 pub(crate) struct NetworkInterface<'a> {
-	pub(super) iface: smoltcp::iface::Interface,
+	pub(super) iface: Interface,
 	pub(super) sockets: SocketSet<'a>,
 	#[cfg(feature = "trace")]
-	pub(super) device: smoltcp::phy::Tracer<NetworkDevice>,
+	pub(super) device: Counted<smoltcp::phy::Tracer<NetworkDevice>>,
 	#[cfg(not(feature = "trace"))]
-	pub(super) device: NetworkDevice,
+	pub(super) device: Counted<NetworkDevice>,
+	/// The device is detached from the network stack and driven by the
+	/// application through the raw-frame interface.
+	pub(super) detached: bool,
+	/// DHCP client, if the address is not configured statically
 	#[cfg(feature = "dhcpv4")]
-	pub(super) dhcp_handle: SocketHandle,
+	pub(super) dhcp_handle: Option<SocketHandle>,
+	/// Raw socket for the stateless address autoconfiguration of IPv6
+	#[cfg(feature = "slaac")]
+	pub(super) slaac_handle: Option<SocketHandle>,
 	#[cfg(feature = "dns")]
 	pub(super) dns_handle: Option<SocketHandle>,
+	/// Name servers of the DNS socket
+	#[cfg(feature = "dns")]
+	pub(super) dns_servers: Vec<IpAddress>,
+	/// Joined multicast groups and the number of sockets, which have joined them
+	#[cfg(feature = "udp")]
+	pub(super) multicast_groups: Vec<(IpAddress, usize)>,
+	/// Network cards beside the primary one, named `eth1`, `eth2`, ...
+	pub(super) links: Vec<Link>,
+	/// Routes through gateways, see [`route`]
+	pub(super) routes: Vec<Route>,
+	/// Static entries of the neighbor caches, see [`super::neighbor`]
+	pub(super) neighbors: Neighbors,
+	/// Configured MTUs and MTUs of paths, see [`super::mtu`]
+	pub(super) mtus: Mtus,
+	/// Loopback interface beside the network cards
+	///
+	/// Without a network card, the primary interface is the loopback device
+	/// and has the loopback addresses itself.
+	pub(super) loopback: Option<Loopback>,
+}
+
+/// Network card, which shares the sockets of the primary interface
+pub(crate) struct Link {
+	pub(super) iface: Interface,
+	#[cfg(feature = "trace")]
+	pub(super) device: Counted<smoltcp::phy::Tracer<NetworkDevice>>,
+	#[cfg(not(feature = "trace"))]
+	pub(super) device: Counted<NetworkDevice>,
+}
+
+/// Loopback interface `lo`, which shares the sockets of the primary interface
+pub(crate) struct Loopback {
+	pub(super) iface: Interface,
+	#[cfg(feature = "trace")]
+	pub(super) device: Counted<smoltcp::phy::Tracer<LoopbackDriver>>,
+	#[cfg(not(feature = "trace"))]
+	pub(super) device: Counted<LoopbackDriver>,
 }
 
 #[cfg(target_arch = "x86_64")]
@@ -121,7 +175,9 @@ async fn dhcpv4_run() {
 		};
 
 		let nic = guard.as_nic_mut().unwrap();
-		let dhcp_handle = nic.dhcp_handle;
+		let Some(dhcp_handle) = nic.dhcp_handle else {
+			return Poll::Ready(());
+		};
 		let socket = nic.sockets.get_mut::<dhcpv4::Socket<'_>>(dhcp_handle);
 
 		socket.register_waker(cx.waker());
@@ -132,7 +188,9 @@ async fn dhcpv4_run() {
 				info!("DHCP config acquired!");
 				info!("IP address:      {}", config.address);
 				nic.iface.update_ip_addrs(|addrs| {
-					if let Some(dest) = addrs.iter_mut().next() {
+					if let Some(dest) = addrs.iter_mut().find(
+						|addr| matches!(addr, IpCidr::Ipv4(cidr) if !cidr.address().is_loopback()),
+					) {
 						*dest = IpCidr::Ipv4(config.address);
 					} else if addrs.push(IpCidr::Ipv4(config.address)).is_err() {
 						info!("Unable to update IP address");
@@ -140,13 +198,10 @@ async fn dhcpv4_run() {
 				});
 				if let Some(router) = config.router {
 					info!("Default gateway: {router}");
-					nic.iface
-						.routes_mut()
-						.add_default_ipv4_route(router)
-						.unwrap();
+					nic.add_route(Route::default_via(router.into(), 0));
 				} else {
 					info!("Default gateway: None");
-					nic.iface.routes_mut().remove_default_ipv4_route();
+					nic.remove_route(route::default_destination(Ipv4Address::UNSPECIFIED.into()));
 				}
 
 				#[cfg(feature = "dns")]
@@ -157,30 +212,24 @@ async fn dhcpv4_run() {
 					dns_servers.push(IpAddress::Ipv4(*s));
 				}
 
+				// The servers may have changed on the renewal of the lease.
 				#[cfg(feature = "dns")]
-				if !dns_servers.is_empty() {
-					let dns_socket = dns::Socket::new(dns_servers.as_slice(), vec![]);
-					nic.dns_handle = Some(nic.sockets.add(dns_socket));
-				}
+				nic.set_dns_servers(dns_servers);
 			}
 			Some(dhcpv4::Event::Deconfigured) => {
 				info!("DHCP lost config!");
 				let cidr = Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0);
 				nic.iface.update_ip_addrs(|addrs| {
-					if let Some(dest) = addrs.iter_mut().next() {
+					if let Some(dest) = addrs.iter_mut().find(
+						|addr| matches!(addr, IpCidr::Ipv4(cidr) if !cidr.address().is_loopback()),
+					) {
 						*dest = IpCidr::Ipv4(cidr);
 					}
 				});
-				nic.iface.routes_mut().remove_default_ipv4_route();
+				nic.remove_route(route::default_destination(Ipv4Address::UNSPECIFIED.into()));
 
 				#[cfg(feature = "dns")]
-				{
-					if let Some(dns_handle) = nic.dns_handle {
-						nic.sockets.remove(dns_handle);
-					}
-
-					nic.dns_handle = None;
-				}
+				nic.set_dns_servers(Vec::new());
 			}
 		};
 
@@ -265,26 +314,76 @@ pub(crate) fn init() {
 		spawn(network_run());
 		#[cfg(feature = "dhcpv4")]
 		spawn(dhcpv4_run());
+		#[cfg(feature = "slaac")]
+		spawn(crate::executor::slaac::run());
 	}
+	drop(guard);
+
+	#[cfg(feature = "log-net")]
+	crate::executor::netlog::init();
 }
 
 impl<'a> NetworkInterface<'a> {
 	#[cfg(feature = "udp")]
 	pub(crate) fn create_udp_handle(&mut self) -> Result<Handle, ()> {
+		let limits = crate::executor::socket_buffer::limits();
+		self.create_udp_handle_with_capacity(limits.rx_default, limits.tx_default)
+	}
+
+	/// Creates a UDP socket with receive and send buffers, which hold the
+	/// given number of payload bytes.
+	#[cfg(feature = "udp")]
+	pub(crate) fn create_udp_handle_with_capacity(
+		&mut self,
+		rx_capacity: usize,
+		tx_capacity: usize,
+	) -> Result<Handle, ()> {
+		self.reserve_buffers(rx_capacity, tx_capacity)?;
 		let udp_rx_buffer =
-			udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 4], vec![0; 0x10000]);
+			udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 4], vec![0; rx_capacity]);
 		let udp_tx_buffer =
-			udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 4], vec![0; 0x10000]);
+			udp::PacketBuffer::new(vec![udp::PacketMetadata::EMPTY; 4], vec![0; tx_capacity]);
 		let udp_socket = udp::Socket::new(udp_rx_buffer, udp_tx_buffer);
 		let udp_handle = self.sockets.add(udp_socket);
 
 		Ok(udp_handle)
 	}
 
+	#[cfg(feature = "raw")]
+	pub(crate) fn create_raw_handle(
+		&mut self,
+		ip_version: IpVersion,
+		protocol: IpProtocol,
+	) -> Result<Handle, ()> {
+		const CAPACITY: usize = 0x10000;
+
+		self.reserve_buffers(CAPACITY, CAPACITY)?;
+		let raw_rx_buffer =
+			raw::PacketBuffer::new(vec![raw::PacketMetadata::EMPTY; 4], vec![0; CAPACITY]);
+		let raw_tx_buffer =
+			raw::PacketBuffer::new(vec![raw::PacketMetadata::EMPTY; 4], vec![0; CAPACITY]);
+		let raw_socket = raw::Socket::new(ip_version, protocol, raw_rx_buffer, raw_tx_buffer);
+		let raw_handle = self.sockets.add(raw_socket);
+
+		Ok(raw_handle)
+	}
+
 	#[cfg(feature = "tcp")]
 	pub(crate) fn create_tcp_handle(&mut self) -> Result<Handle, ()> {
-		let tcp_rx_buffer = tcp::SocketBuffer::new(vec![0; 0x10000]);
-		let tcp_tx_buffer = tcp::SocketBuffer::new(vec![0; 0x10000]);
+		let limits = crate::executor::socket_buffer::limits();
+		self.create_tcp_handle_with_capacity(limits.rx_default, limits.tx_default)
+	}
+
+	/// Creates a TCP socket with receive and send buffers of the given capacities.
+	#[cfg(feature = "tcp")]
+	pub(crate) fn create_tcp_handle_with_capacity(
+		&mut self,
+		rx_capacity: usize,
+		tx_capacity: usize,
+	) -> Result<Handle, ()> {
+		self.reserve_buffers(rx_capacity, tx_capacity)?;
+		let tcp_rx_buffer = tcp::SocketBuffer::new(vec![0; rx_capacity]);
+		let tcp_tx_buffer = tcp::SocketBuffer::new(vec![0; tx_capacity]);
 		let mut tcp_socket = tcp::Socket::new(tcp_rx_buffer, tcp_tx_buffer);
 		tcp_socket.set_nagle_enabled(true);
 		let tcp_handle = self.sockets.add(tcp_socket);
@@ -292,25 +391,278 @@ impl<'a> NetworkInterface<'a> {
 		Ok(tcp_handle)
 	}
 
-	pub(crate) fn poll_common(&mut self, timestamp: Instant) -> PollResult {
+	/// Joins the multicast group `group` on behalf of a socket.
+	///
+	/// The interface stays a member of the group, until all sockets, which
+	/// have joined it, have left it again.
+	#[cfg(feature = "udp")]
+	pub(crate) fn join_multicast_group(&mut self, group: IpAddress) -> io::Result<()> {
+		if !group.is_multicast() {
+			return Err(Errno::Inval);
+		}
+
+		if let Some((_, sockets)) = self
+			.multicast_groups
+			.iter_mut()
+			.find(|(addr, _)| *addr == group)
+		{
+			*sockets += 1;
+			return Ok(());
+		}
+
 		self.iface
-			.poll(timestamp, &mut self.device, &mut self.sockets)
+			.join_multicast_group(group)
+			.map_err(|_| Errno::Nobufs)?;
+		self.multicast_groups.push((group, 1));
+		Ok(())
+	}
+
+	/// Leaves the multicast group `group` on behalf of a socket.
+	#[cfg(feature = "udp")]
+	pub(crate) fn leave_multicast_group(&mut self, group: IpAddress) -> io::Result<()> {
+		let index = self
+			.multicast_groups
+			.iter()
+			.position(|(addr, _)| *addr == group)
+			.ok_or(Errno::Addrnotavail)?;
+
+		let sockets = &mut self.multicast_groups[index].1;
+		*sockets -= 1;
+		if *sockets == 0 {
+			self.multicast_groups.swap_remove(index);
+			self.iface
+				.leave_multicast_group(group)
+				.map_err(|_| Errno::Io)?;
+		}
+		Ok(())
+	}
+
+	pub(crate) fn poll_common(&mut self, timestamp: Instant) -> PollResult {
+		#[cfg(feature = "tcp")]
+		self.update_path_mtus(timestamp);
+
+		let mut result = self.refresh_neighbors(timestamp);
+		if !self.detached
+			&& matches!(
+				self.iface
+					.poll(timestamp, &mut self.device, &mut self.sockets),
+				PollResult::SocketStateChanged
+			) {
+			result = PollResult::SocketStateChanged;
+		}
+
+		// The primary interface is polled first, so that broadcasts of
+		// DHCP and SLAAC leave through it.
+		for link in &mut self.links {
+			if matches!(
+				link.iface
+					.poll(timestamp, &mut link.device, &mut self.sockets),
+				PollResult::SocketStateChanged
+			) {
+				result = PollResult::SocketStateChanged;
+			}
+		}
+
+		// The loopback device delivers its packets within the same poll.
+		if let Some(loopback) = &mut self.loopback
+			&& matches!(
+				loopback
+					.iface
+					.poll(timestamp, &mut loopback.device, &mut self.sockets),
+				PollResult::SocketStateChanged
+			) {
+			result = PollResult::SocketStateChanged;
+		}
+
+		result
 	}
 
 	pub(crate) fn poll_delay(&mut self, timestamp: Instant) -> Option<Duration> {
-		self.iface.poll_delay(timestamp, &self.sockets)
+		let mut delay = self.iface.poll_delay(timestamp, &self.sockets);
+		let ifaces = self
+			.links
+			.iter_mut()
+			.map(|link| &mut link.iface)
+			.chain(self.loopback.as_mut().map(|loopback| &mut loopback.iface));
+		for iface in ifaces {
+			if let Some(iface_delay) = iface.poll_delay(timestamp, &self.sockets) {
+				delay = Some(delay.map_or(iface_delay, |delay| delay.min(iface_delay)));
+			}
+		}
+		if let Some(refresh_delay) = self.neighbors.poll_delay(timestamp) {
+			delay = Some(delay.map_or(refresh_delay, |delay| delay.min(refresh_delay)));
+		}
+		delay
+	}
+
+	/// Returns the number of interfaces.
+	pub(crate) fn interface_count(&self) -> usize {
+		self.links.len() + 1
+	}
+
+	/// Returns the statistics of the interface `index`, 0 is `eth0`.
+	///
+	/// The loopback interface `lo` beside the network cards follows the
+	/// last network card.
+	pub(crate) fn statistics(&self, index: usize) -> Option<&InterfaceStatistics> {
+		core::iter::once(self.device.statistics())
+			.chain(self.links.iter().map(|link| link.device.statistics()))
+			.chain(
+				self.loopback
+					.iter()
+					.map(|loopback| loopback.device.statistics()),
+			)
+			.nth(index)
+	}
+
+	/// Returns the interface `index` in the same order as [`Self::statistics`].
+	pub(crate) fn interface(&self, index: usize) -> Option<&Interface> {
+		self.ifaces()
+			.chain(self.loopback.iter().map(|loopback| &loopback.iface))
+			.nth(index)
+	}
+
+	fn ifaces(&self) -> impl Iterator<Item = &Interface> {
+		core::iter::once(&self.iface).chain(self.links.iter().map(|link| &link.iface))
+	}
+
+	fn iface_mut(&mut self, index: usize) -> &mut Interface {
+		match index.checked_sub(1) {
+			None => &mut self.iface,
+			Some(i) => &mut self.links[i].iface,
+		}
+	}
+
+	/// Returns the subnets of the interfaces followed by the routes through
+	/// gateways.
+	pub(crate) fn routes(&self) -> Vec<Route> {
+		let mut routes = self
+			.ifaces()
+			.enumerate()
+			.flat_map(|(interface, iface)| {
+				iface
+					.ip_addrs()
+					.iter()
+					.filter(|cidr| route::has_subnet(cidr))
+					.map(move |cidr| Route {
+						destination: route::network(*cidr),
+						gateway: None,
+						interface,
+					})
+			})
+			.collect::<Vec<_>>();
+		routes.extend_from_slice(&self.routes);
+		routes
+	}
+
+	/// Adds a route through a gateway, which replaces the route to the same
+	/// destination.
+	///
+	/// Returns the replaced route.
+	pub(crate) fn add_route(&mut self, route: Route) -> Option<Route> {
+		let previous = self
+			.routes
+			.iter()
+			.position(|entry| entry.destination == route.destination)
+			.map(|index| self.routes.remove(index));
+		self.routes.push(route);
+		self.update_routes();
+		previous
+	}
+
+	/// Removes the route to `destination`.
+	pub(crate) fn remove_route(&mut self, destination: IpCidr) -> Option<Route> {
+		let previous = self
+			.routes
+			.iter()
+			.position(|entry| entry.destination == destination)
+			.map(|index| self.routes.remove(index));
+		// The subnets may have changed as well.
+		self.update_routes();
+		previous
+	}
+
+	/// Programs the routing tables of the interfaces, see [`route`].
+	///
+	/// This has to be called, whenever the routes or the addresses of the
+	/// interfaces change.
+	pub(crate) fn update_routes(&mut self) {
+		let claims = self.routes();
+		let mut parts = Vec::new();
+
+		for index in 0..self.interface_count() {
+			let mut entries = Vec::new();
+			for route in self.routes.iter().filter(|route| route.interface == index) {
+				let excluded = claims
+					.iter()
+					.filter(|claim| {
+						claim.interface != index
+							&& claim.destination.prefix_len() > route.destination.prefix_len()
+					})
+					.map(|claim| claim.destination)
+					.chain(route::loopback_prefixes())
+					.collect::<Vec<_>>();
+				parts.clear();
+				route::subtract(route.destination, &excluded, &mut parts);
+				let via_router = route.gateway.unwrap();
+				entries.extend(parts.iter().map(|cidr| smoltcp::iface::Route {
+					cidr: *cidr,
+					via_router,
+					preferred_until: None,
+					expires_at: None,
+				}));
+			}
+
+			self.iface_mut(index).routes_mut().update(|routes| {
+				routes.clear();
+				for entry in entries {
+					if routes.push(entry).is_err() {
+						warn!("Routing table of {} is full", route::interface_name(index));
+						break;
+					}
+				}
+			});
+		}
+	}
+
+	/// Returns the index of the interface, which reaches `addr`.
+	pub(crate) fn interface_of(&self, addr: IpAddress) -> usize {
+		let mut best: Option<Route> = None;
+		for route in self.routes() {
+			if route.destination.contains_addr(&addr)
+				&& best.is_none_or(|best| {
+					route.destination.prefix_len() > best.destination.prefix_len()
+				}) {
+				best = Some(route);
+			}
+		}
+		best.map_or(0, |route| route.interface)
 	}
 
 	pub(crate) fn get_mut_socket<T: AnySocket<'a>>(&mut self, handle: SocketHandle) -> &mut T {
 		self.sockets.get_mut(handle)
 	}
 
+	/// Returns the socket and the context of the interface, which reaches `remote`.
 	#[cfg(feature = "tcp")]
 	pub(crate) fn get_socket_and_context<T: AnySocket<'a>>(
 		&mut self,
 		handle: SocketHandle,
+		remote: IpAddress,
 	) -> (&mut T, &mut smoltcp::iface::Context) {
-		(self.sockets.get_mut(handle), self.iface.context())
+		let iface = if let Some(loopback) = self
+			.loopback
+			.as_mut()
+			.filter(|_| route::is_loopback(remote))
+		{
+			&mut loopback.iface
+		} else {
+			match self.interface_of(remote).checked_sub(1) {
+				None => &mut self.iface,
+				Some(i) => &mut self.links[i].iface,
+			}
+		};
+		(self.sockets.get_mut(handle), iface.context())
 	}
 
 	pub(crate) fn destroy_socket(&mut self, handle: Handle) {
@@ -331,6 +683,42 @@ impl<'a> NetworkInterface<'a> {
 			.map_err(|_| Errno::Io)
 	}
 
+	/// Replaces the DNS socket by one, which queries `servers`.
+	///
+	/// Pending queries of the old socket are dropped.
+	#[cfg(feature = "dns")]
+	pub(crate) fn set_dns_servers(&mut self, servers: Vec<IpAddress>) {
+		if let Some(dns_handle) = self.dns_handle.take() {
+			self.sockets.remove(dns_handle);
+		}
+		if !servers.is_empty() {
+			let dns_socket = dns::Socket::new(servers.as_slice(), vec![]);
+			self.dns_handle = Some(self.sockets.add(dns_socket));
+		}
+		self.dns_servers = servers;
+	}
+
+	/// Returns the addresses of all interfaces.
+	pub(crate) fn ip_addrs(&self) -> impl Iterator<Item = &IpCidr> {
+		self.ifaces().flat_map(|iface| iface.ip_addrs())
+	}
+
+	/// Returns the addresses of the interface, which reaches `addr`.
+	#[cfg(feature = "raw")]
+	pub(crate) fn source_addrs(&self, addr: IpAddress) -> &[IpCidr] {
+		if let Some(loopback) = self.loopback.as_ref().filter(|_| route::is_loopback(addr)) {
+			return loopback.iface.ip_addrs();
+		}
+
+		let index = self.interface_of(addr);
+		self.ifaces().nth(index).unwrap().ip_addrs()
+	}
+
+	#[cfg(feature = "dns")]
+	pub(crate) fn dns_servers(&self) -> &[IpAddress] {
+		&self.dns_servers
+	}
+
 	#[cfg(feature = "dns")]
 	pub(crate) fn get_mut_dns_socket(&mut self) -> io::Result<&mut dns::Socket<'a>> {
 		let dns_handle = self.dns_handle.ok_or(Errno::Inval)?;
@@ -340,6 +728,7 @@ impl<'a> NetworkInterface<'a> {
 	#[cfg(any(
 		all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
 		all(target_arch = "x86_64", feature = "rtl8139"),
+		feature = "e1000",
 		feature = "virtio-net",
 	))]
 	fn handle_interrupt(&mut self) {
@@ -347,12 +736,93 @@ impl<'a> NetworkInterface<'a> {
 		self.device.get_mut().handle_interrupt();
 		#[cfg(not(feature = "trace"))]
 		self.device.handle_interrupt();
+
+		for link in &mut self.links {
+			#[cfg(feature = "trace")]
+			link.device.get_mut().handle_interrupt();
+			#[cfg(not(feature = "trace"))]
+			link.device.handle_interrupt();
+		}
 	}
 
 	pub(crate) fn set_polling_mode(&mut self, value: bool) {
+		for link in &mut self.links {
+			#[cfg(feature = "trace")]
+			link.device.get_mut().set_polling_mode(value);
+			#[cfg(not(feature = "trace"))]
+			link.device.set_polling_mode(value);
+		}
+
+		// A detached device is polled by the application, keep its interrupts disabled
+		let value = value || self.detached;
+
 		#[cfg(feature = "trace")]
 		self.device.get_mut().set_polling_mode(value);
 		#[cfg(not(feature = "trace"))]
 		self.device.set_polling_mode(value);
 	}
+
+	/// Detaches the device from the network stack. Afterwards, the sockets
+	/// of the kernel are no longer served and the device interrupts are
+	/// disabled.
+	pub(crate) fn detach_device(&mut self) -> Result<(), Errno> {
+		if self.detached {
+			return Err(Errno::Busy);
+		}
+
+		self.set_polling_mode(true);
+		self.detached = true;
+		info!("Network device detached from the network stack");
+
+		Ok(())
+	}
+
+	/// Attaches a previously detached device to the network stack again.
+	pub(crate) fn attach_device(&mut self) -> Result<(), Errno> {
+		if !self.detached {
+			return Err(Errno::Busy);
+		}
+
+		self.detached = false;
+		self.set_polling_mode(false);
+		info!("Network device attached to the network stack");
+
+		Ok(())
+	}
+
+	pub(crate) fn is_detached(&self) -> bool {
+		self.detached
+	}
+
+	/// Receives a single raw frame of a detached device.
+	///
+	/// Frames, which are larger than `buf`, are truncated.
+	pub(crate) fn raw_receive(&mut self, buf: &mut [u8]) -> Result<usize, Errno> {
+		if !self.detached {
+			return Err(Errno::Perm);
+		}
+
+		let (rx_token, _) = self.device.receive(now()).ok_or(Errno::Again)?;
+		Ok(rx_token.consume(|frame| {
+			let len = frame.len().min(buf.len());
+			buf[..len].copy_from_slice(&frame[..len]);
+			len
+		}))
+	}
+
+	/// Transmits a single raw frame through a detached device.
+	pub(crate) fn raw_transmit(&mut self, buf: &[u8]) -> Result<usize, Errno> {
+		if !self.detached {
+			return Err(Errno::Perm);
+		}
+
+		if buf.len() > self.device.capabilities().max_transmission_unit {
+			return Err(Errno::Msgsize);
+		}
+
+		let tx_token = self.device.transmit(now()).ok_or(Errno::Again)?;
+		tx_token.consume(buf.len(), |frame| frame.copy_from_slice(buf));
+
+		Ok(buf.len())
+	}
 }
@@ -0,0 +1,255 @@
+//! Routing table of the network interfaces.
+//!
+//! A route maps a destination prefix to an interface and a gateway. The
+//! subnets of the interface addresses are reached directly and are not part
+//! of the table. The most specific route or subnet, which contains the
+//! destination, selects the interface.
+//!
+//! All interfaces share the sockets and each interface sends the packets,
+//! for which it finds a route in its own smoltcp routing table. Therefore,
+//! the routes of an interface are programmed without the destinations, which
+//! other interfaces reach by more specific routes or subnets. Only the
+//! subnet of an interface itself always takes precedence over the routes of
+//! other interfaces.
+//!
+//! The loopback interface `lo` reaches `127.0.0.0/8` and `::1` and has no
+//! routes. The routes of the other interfaces exclude the loopback addresses.
+
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::vec::Vec;
+use core::net::IpAddr;
+use core::str::FromStr;
+
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv6Address};
+
+/// Entry of the routing table
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Route {
+	/// Destination prefix, whose host bits are zero
+	pub destination: IpCidr,
+	/// Router, which forwards the packets, or `None` for a subnet
+	pub gateway: Option<IpAddress>,
+	/// Index of the interface, 0 is the primary interface
+	pub interface: usize,
+}
+
+impl Route {
+	/// Returns the default route of the address family of `gateway`.
+	pub fn default_via(gateway: IpAddress, interface: usize) -> Self {
+		Self {
+			destination: default_destination(gateway),
+			gateway: Some(gateway),
+			interface,
+		}
+	}
+}
+
+/// Returns the prefix of the default route of the address family of `addr`.
+pub(crate) fn default_destination(addr: IpAddress) -> IpCidr {
+	match addr {
+		IpAddress::Ipv4(_) => IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0),
+		IpAddress::Ipv6(_) => IpCidr::new(Ipv6Address::UNSPECIFIED.into(), 0),
+	}
+}
+
+/// Returns whether `addr` is reached through the loopback interface.
+pub(crate) fn is_loopback(addr: IpAddress) -> bool {
+	match addr {
+		IpAddress::Ipv4(addr) => addr.is_loopback(),
+		IpAddress::Ipv6(addr) => addr.is_loopback(),
+	}
+}
+
+/// Returns the prefixes, which the routes of the network cards exclude.
+///
+/// Instead of `::1/128`, which would split a default route into 128 parts,
+/// `::/10` is excluded. It contains no other unicast addresses in use, but
+/// the IPv4-mapped ones.
+pub(crate) fn loopback_prefixes() -> [IpCidr; 2] {
+	[
+		IpCidr::new(Ipv4Address::new(127, 0, 0, 0).into(), 8),
+		IpCidr::new(Ipv6Address::UNSPECIFIED.into(), 10),
+	]
+}
+
+/// Returns the name of the interface `index`.
+pub(crate) fn interface_name(index: usize) -> Cow<'static, str> {
+	if index == 0 {
+		Cow::Borrowed(crate::drivers::net::INTERFACE_NAME)
+	} else {
+		Cow::Owned(format!("eth{index}"))
+	}
+}
+
+/// Returns the index of the interface `name`.
+pub(crate) fn interface_index(name: &str) -> Option<usize> {
+	if name == crate::drivers::net::INTERFACE_NAME {
+		return Some(0);
+	}
+	name.strip_prefix("eth")?.parse().ok()
+}
+
+/// Parses a route in the form `<destination>/<prefix length> via <gateway> [dev <interface>]`.
+pub(crate) fn parse(entry: &str) -> Option<(IpCidr, IpAddress, Option<usize>)> {
+	let mut words = entry.split_whitespace();
+	let (addr, prefix_len) = words.next()?.split_once('/')?;
+	let addr = IpAddress::from(IpAddr::from_str(addr).ok()?);
+	let prefix_len = prefix_len.parse::<u8>().ok()?;
+	let max_len = match addr {
+		IpAddress::Ipv4(_) => 32,
+		IpAddress::Ipv6(_) => 128,
+	};
+	if prefix_len > max_len || words.next()? != "via" {
+		return None;
+	}
+
+	let gateway = IpAddress::from(IpAddr::from_str(words.next()?).ok()?);
+	if gateway.version() != addr.version() {
+		return None;
+	}
+
+	let interface = match words.next() {
+		None => None,
+		Some("dev") => Some(interface_index(words.next()?)?),
+		Some(_) => return None,
+	};
+	if words.next().is_some() {
+		return None;
+	}
+
+	Some((network(IpCidr::new(addr, prefix_len)), gateway, interface))
+}
+
+/// Returns whether the subnet of the interface address `cidr` is routed.
+///
+/// Link-local subnets exist on all interfaces and the unspecified address is
+/// a placeholder, until DHCP has acquired an address.
+pub(crate) fn has_subnet(cidr: &IpCidr) -> bool {
+	match cidr.address() {
+		IpAddress::Ipv4(addr) => !addr.is_unspecified() && !addr.is_loopback(),
+		IpAddress::Ipv6(addr) => {
+			!addr.is_unspecified() && !addr.is_loopback() && !addr.is_unicast_link_local()
+		}
+	}
+}
+
+/// Returns `cidr` without its host bits.
+pub(crate) fn network(cidr: IpCidr) -> IpCidr {
+	let len = cidr.prefix_len();
+	match cidr.address() {
+		IpAddress::Ipv4(addr) => {
+			let mask = u32::MAX.checked_shl(32 - u32::from(len)).unwrap_or(0);
+			IpCidr::new(Ipv4Address::from(u32::from(addr) & mask).into(), len)
+		}
+		IpAddress::Ipv6(addr) => {
+			let mask = u128::MAX.checked_shl(128 - u32::from(len)).unwrap_or(0);
+			IpCidr::new(Ipv6Address::from(u128::from(addr) & mask).into(), len)
+		}
+	}
+}
+
+/// Splits the prefix `cidr` into its halves, which are one bit more specific.
+fn halves(cidr: IpCidr) -> (IpCidr, IpCidr) {
+	let len = cidr.prefix_len() + 1;
+	match network(cidr).address() {
+		IpAddress::Ipv4(addr) => {
+			let low = u32::from(addr);
+			let high = low | (1 << (32 - u32::from(len)));
+			(
+				IpCidr::new(Ipv4Address::from(low).into(), len),
+				IpCidr::new(Ipv4Address::from(high).into(), len),
+			)
+		}
+		IpAddress::Ipv6(addr) => {
+			let low = u128::from(addr);
+			let high = low | (1 << (128 - u32::from(len)));
+			(
+				IpCidr::new(Ipv6Address::from(low).into(), len),
+				IpCidr::new(Ipv6Address::from(high).into(), len),
+			)
+		}
+	}
+}
+
+/// Appends the parts of `cidr`, which are not covered by `excluded`, to `parts`.
+///
+/// All prefixes of `excluded` have to be more specific than `cidr`.
+pub(crate) fn subtract(cidr: IpCidr, excluded: &[IpCidr], parts: &mut Vec<IpCidr>) {
+	if excluded.iter().any(|prefix| prefix.contains_subnet(&cidr)) {
+		return;
+	}
+	if !excluded.iter().any(|prefix| cidr.contains_subnet(prefix)) {
+		parts.push(cidr);
+		return;
+	}
+
+	let (low, high) = halves(cidr);
+	subtract(low, excluded, parts);
+	subtract(high, excluded, parts);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const GATEWAY: IpAddress = IpAddress::v4(192, 168, 1, 1);
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_route_parse() {
+		let destination = IpCidr::new(IpAddress::v4(10, 0, 0, 0), 8);
+		assert_eq!(
+			parse("10.0.0.0/8 via 192.168.1.1"),
+			Some((destination, GATEWAY, None))
+		);
+		// The host bits are cleared.
+		assert_eq!(
+			parse("10.1.2.3/8  via 192.168.1.1 dev eth1"),
+			Some((destination, GATEWAY, Some(1)))
+		);
+		assert_eq!(
+			parse("2001:db8::/32 via fe80::1"),
+			Some((
+				IpCidr::new(IpAddress::v6(0x2001, 0x0db8, 0, 0, 0, 0, 0, 0), 32),
+				IpAddress::v6(0xfe80, 0, 0, 0, 0, 0, 0, 1),
+				None
+			))
+		);
+		assert_eq!(
+			parse("0.0.0.0/0 via 192.168.1.1"),
+			Some((default_destination(GATEWAY), GATEWAY, None))
+		);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_route_parse_prefix_len() {
+		assert_eq!(parse("10.0.0.0/33 via 192.168.1.1"), None);
+		assert_eq!(parse("2001:db8::/129 via fe80::1"), None);
+		assert!(parse("2001:db8::/128 via fe80::1").is_some());
+		assert_eq!(parse("10.0.0.0/-1 via 192.168.1.1"), None);
+		assert_eq!(parse("10.0.0.0/ via 192.168.1.1"), None);
+		assert_eq!(parse("10.0.0.0 via 192.168.1.1"), None);
+		assert_eq!(parse("/8 via 192.168.1.1"), None);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_route_parse_malformed() {
+		assert_eq!(parse(""), None);
+		assert_eq!(parse("10.0.0.0/8"), None);
+		assert_eq!(parse("10.0.0.0/8 192.168.1.1"), None);
+		assert_eq!(parse("10.0.0.0/8 via"), None);
+		assert_eq!(parse("10.0.0.0/8 via fe80::1"), None);
+		assert_eq!(parse("10.0.0.0/8 via 192.168.1.1 dev"), None);
+		assert_eq!(parse("10.0.0.0/8 via 192.168.1.1 dev wlan0"), None);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_route_parse_trailing_words() {
+		assert_eq!(parse("10.0.0.0/8 via 192.168.1.1 metric"), None);
+		assert_eq!(parse("10.0.0.0/8 via 192.168.1.1 dev eth1 metric 5"), None);
+	}
+}
@@ -0,0 +1,367 @@
+//! Stateless address autoconfiguration of IPv6 (RFC 4862).
+//!
+//! The interface solicits router advertisements through a raw ICMPv6 socket.
+//! For each advertised prefix of 64 bits with the autonomous flag, an address
+//! is formed from the prefix and the interface identifier of the link-local
+//! address. The sender of the advertisement becomes the default gateway.
+//!
+//! Lifetimes of prefixes and routers are not tracked. They are only removed,
+//! if a router advertises a lifetime of zero.
+
+use alloc::vec::Vec;
+use core::future;
+use core::task::Poll;
+
+use smoltcp::socket::raw;
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{
+	HardwareAddress, IpAddress, IpCidr, IpProtocol, IpVersion, Ipv6Address, Ipv6Packet, Ipv6Repr,
+};
+
+use crate::executor::device::link_local_address;
+use crate::executor::network::{NIC, now};
+use crate::executor::route::{self, Route};
+
+/// Maximum number of router solicitations, which are sent during startup
+const MAX_ROUTER_SOLICITATIONS: u32 = 3;
+
+/// Interval between two router solicitations
+const ROUTER_SOLICITATION_INTERVAL: Duration = Duration::from_secs(4);
+
+/// All-routers multicast address of the link
+const ALL_ROUTERS: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+const ROUTER_SOLICITATION: u8 = 133;
+const ROUTER_ADVERTISEMENT: u8 = 134;
+
+const OPTION_SOURCE_LINK_LAYER_ADDRESS: u8 = 1;
+const OPTION_PREFIX_INFORMATION: u8 = 3;
+
+/// Autonomous address-configuration flag of the prefix information
+const PREFIX_AUTONOMOUS: u8 = 0x40;
+
+/// Hop limit of all neighbor discovery messages
+const NDISC_HOP_LIMIT: u8 = 255;
+
+/// Creates the raw socket, which receives the router advertisements.
+pub(crate) fn socket<'a>() -> raw::Socket<'a> {
+	let rx_buffer = raw::PacketBuffer::new(vec![raw::PacketMetadata::EMPTY; 4], vec![0; 0x1000]);
+	let tx_buffer = raw::PacketBuffer::new(vec![raw::PacketMetadata::EMPTY; 1], vec![0; 0x100]);
+	raw::Socket::new(IpVersion::Ipv6, IpProtocol::Icmpv6, rx_buffer, tx_buffer)
+}
+
+/// Computes the checksum of an ICMPv6 message including the pseudo header.
+fn checksum(src_addr: &Ipv6Address, dst_addr: &Ipv6Address, message: &[u8]) -> u16 {
+	let mut sum = 0u32;
+	let mut add = |bytes: &[u8]| {
+		for chunk in bytes.chunks(2) {
+			let high = u32::from(chunk[0]) << 8;
+			sum += high | chunk.get(1).copied().map_or(0, u32::from);
+		}
+	};
+
+	add(&src_addr.octets());
+	add(&dst_addr.octets());
+	add(&u32::try_from(message.len()).unwrap().to_be_bytes());
+	add(&[0, 0, 0, IpProtocol::Icmpv6.into()]);
+	add(message);
+
+	while sum > 0xffff {
+		sum = (sum & 0xffff) + (sum >> 16);
+	}
+	!(sum as u16)
+}
+
+/// Sends a router solicitation to all routers of the link.
+fn solicit(socket: &mut raw::Socket<'_>, src_addr: Ipv6Address, mac: [u8; 6]) {
+	let mut message = [0u8; 16];
+	message[0] = ROUTER_SOLICITATION;
+	message[8] = OPTION_SOURCE_LINK_LAYER_ADDRESS;
+	message[9] = 1;
+	message[10..].copy_from_slice(&mac);
+	let checksum = checksum(&src_addr, &ALL_ROUTERS, &message);
+	message[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+	let repr = Ipv6Repr {
+		src_addr,
+		dst_addr: ALL_ROUTERS,
+		next_header: IpProtocol::Icmpv6,
+		payload_len: message.len(),
+		hop_limit: NDISC_HOP_LIMIT,
+	};
+	let mut packet = [0u8; 56];
+	repr.emit(&mut Ipv6Packet::new_unchecked(&mut packet[..]));
+	packet[repr.buffer_len()..].copy_from_slice(&message);
+
+	if socket.send_slice(&packet).is_err() {
+		debug!("Unable to send a router solicitation");
+	}
+}
+
+/// Prefix information of a router advertisement
+struct Prefix {
+	prefix: [u8; 8],
+	valid: bool,
+}
+
+/// Contents of a router advertisement, which are used for the configuration
+struct Advertisement {
+	router: Ipv6Address,
+	is_default_router: bool,
+	prefixes: Vec<Prefix>,
+}
+
+/// Parses a router advertisement and ignores all other packets.
+fn parse_advertisement(packet: &[u8]) -> Option<Advertisement> {
+	let packet = Ipv6Packet::new_checked(packet).ok()?;
+	let router = packet.src_addr();
+	if packet.next_header() != IpProtocol::Icmpv6
+		|| packet.hop_limit() != NDISC_HOP_LIMIT
+		|| !router.is_unicast_link_local()
+	{
+		return None;
+	}
+
+	let message = packet.payload();
+	if message.len() < 16
+		|| message[0] != ROUTER_ADVERTISEMENT
+		|| message[1] != 0
+		|| checksum(&router, &packet.dst_addr(), message) != 0
+	{
+		return None;
+	}
+
+	let router_lifetime = u16::from_be_bytes([message[6], message[7]]);
+	let mut advertisement = Advertisement {
+		router,
+		is_default_router: router_lifetime > 0,
+		prefixes: Vec::new(),
+	};
+
+	let mut options = &message[16..];
+	while options.len() >= 8 {
+		let len = usize::from(options[1]) * 8;
+		if len == 0 || len > options.len() {
+			return None;
+		}
+		let (option, rest) = options.split_at(len);
+		options = rest;
+
+		// Only prefixes of 64 bits can be combined with the interface identifier.
+		if option[0] != OPTION_PREFIX_INFORMATION
+			|| len != 32
+			|| option[2] != 64
+			|| option[3] & PREFIX_AUTONOMOUS == 0
+		{
+			continue;
+		}
+
+		let valid_lifetime = u32::from_be_bytes(option[4..8].try_into().unwrap());
+		let prefix = Prefix {
+			prefix: option[16..24].try_into().unwrap(),
+			valid: valid_lifetime > 0,
+		};
+		advertisement.prefixes.push(prefix);
+	}
+
+	Some(advertisement)
+}
+
+pub(crate) async fn run() {
+	let mut solicitations = 0;
+	let mut next_solicitation = Instant::ZERO;
+
+	future::poll_fn(|cx| {
+		let Some(mut guard) = NIC.try_lock() else {
+			// FIXME: only wake when progress can be made
+			cx.waker().wake_by_ref();
+			return Poll::Pending;
+		};
+
+		let nic = guard.as_nic_mut().unwrap();
+		let Some(handle) = nic.slaac_handle else {
+			return Poll::Ready(());
+		};
+		let HardwareAddress::Ethernet(ethernet_addr) = nic.iface.hardware_addr() else {
+			return Poll::Ready(());
+		};
+		let mac = ethernet_addr.0;
+		let link_local = link_local_address(mac);
+
+		let socket = nic.sockets.get_mut::<raw::Socket<'_>>(handle);
+		socket.register_recv_waker(cx.waker());
+
+		let time = now();
+		if solicitations < MAX_ROUTER_SOLICITATIONS && time >= next_solicitation {
+			solicit(socket, link_local, mac);
+			solicitations += 1;
+			next_solicitation = time + ROUTER_SOLICITATION_INTERVAL;
+		}
+
+		while let Ok(packet) = socket.recv() {
+			let Some(advertisement) = parse_advertisement(packet) else {
+				continue;
+			};
+
+			for prefix in &advertisement.prefixes {
+				let mut octets = link_local.octets();
+				octets[..8].copy_from_slice(&prefix.prefix);
+				let cidr = IpCidr::new(Ipv6Address::from(octets).into(), 64);
+				nic.iface.update_ip_addrs(|addrs| {
+					if !prefix.valid {
+						addrs.retain(|addr| *addr != cidr);
+					} else if !addrs.contains(&cidr) {
+						if addrs.push(cidr).is_ok() {
+							info!("IPv6 address:    {cidr}");
+						} else {
+							info!("Unable to add IPv6 address {cidr}");
+						}
+					}
+				});
+			}
+
+			let router = IpAddress::from(advertisement.router);
+			if advertisement.is_default_router {
+				let previous = nic.add_route(Route::default_via(router, 0));
+				if previous.is_none_or(|route| route.gateway != Some(router)) {
+					info!("IPv6 gateway:    {}", advertisement.router);
+				}
+			} else {
+				let destination = route::default_destination(router);
+				if nic
+					.routes()
+					.iter()
+					.any(|route| route.destination == destination && route.gateway == Some(router))
+				{
+					nic.remove_route(destination);
+				}
+			}
+
+			// The subnets of the new addresses are routed.
+			if !advertisement.prefixes.is_empty() {
+				nic.update_routes();
+			}
+		}
+
+		if solicitations < MAX_ROUTER_SOLICITATIONS {
+			// FIXME: only wake when the next solicitation is due
+			cx.waker().wake_by_ref();
+		}
+
+		Poll::<()>::Pending
+	})
+	.await;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ROUTER: Ipv6Address = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+	const ALL_NODES: Ipv6Address = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+	/// Returns a prefix information option for `2001:db8:1:2::/<prefix_len>`.
+	fn prefix_option(prefix_len: u8, flags: u8, valid_lifetime: u32) -> Vec<u8> {
+		let mut option = vec![0u8; 32];
+		option[0] = OPTION_PREFIX_INFORMATION;
+		option[1] = 4;
+		option[2] = prefix_len;
+		option[3] = flags;
+		option[4..8].copy_from_slice(&valid_lifetime.to_be_bytes());
+		option[16..24].copy_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 1, 0, 2]);
+		option
+	}
+
+	/// Returns an IPv6 packet with a router advertisement of `ROUTER`.
+	fn router_advertisement(router_lifetime: u16, options: &[u8]) -> Vec<u8> {
+		let mut message = vec![0u8; 16];
+		message[0] = ROUTER_ADVERTISEMENT;
+		message[4] = 64;
+		message[6..8].copy_from_slice(&router_lifetime.to_be_bytes());
+		message.extend_from_slice(options);
+		let checksum = checksum(&ROUTER, &ALL_NODES, &message);
+		message[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+		let repr = Ipv6Repr {
+			src_addr: ROUTER,
+			dst_addr: ALL_NODES,
+			next_header: IpProtocol::Icmpv6,
+			payload_len: message.len(),
+			hop_limit: NDISC_HOP_LIMIT,
+		};
+		let mut packet = vec![0u8; repr.buffer_len() + message.len()];
+		repr.emit(&mut Ipv6Packet::new_unchecked(&mut packet[..]));
+		packet[repr.buffer_len()..].copy_from_slice(&message);
+		packet
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_parse_advertisement() {
+		let packet = router_advertisement(1800, &prefix_option(64, PREFIX_AUTONOMOUS, 3600));
+		let advertisement = parse_advertisement(&packet).unwrap();
+		assert_eq!(advertisement.router, ROUTER);
+		assert!(advertisement.is_default_router);
+		assert_eq!(advertisement.prefixes.len(), 1);
+		assert_eq!(
+			advertisement.prefixes[0].prefix,
+			[0x20, 0x01, 0x0d, 0xb8, 0, 1, 0, 2]
+		);
+		assert!(advertisement.prefixes[0].valid);
+
+		// Zero lifetimes remove the router and the prefix.
+		let packet = router_advertisement(0, &prefix_option(64, PREFIX_AUTONOMOUS, 0));
+		let advertisement = parse_advertisement(&packet).unwrap();
+		assert!(!advertisement.is_default_router);
+		assert!(!advertisement.prefixes[0].valid);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_parse_advertisement_ignored_prefixes() {
+		let mut options = prefix_option(48, PREFIX_AUTONOMOUS, 3600);
+		options.extend(prefix_option(64, 0, 3600));
+		// A prefix information option of 24 bytes
+		let mut short = prefix_option(64, PREFIX_AUTONOMOUS, 3600);
+		short[1] = 3;
+		short.truncate(24);
+		options.extend(short);
+
+		let advertisement = parse_advertisement(&router_advertisement(1800, &options)).unwrap();
+		assert!(advertisement.prefixes.is_empty());
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_parse_advertisement_malformed() {
+		// Options of length zero would never end.
+		let mut option = prefix_option(64, PREFIX_AUTONOMOUS, 3600);
+		option[1] = 0;
+		assert!(parse_advertisement(&router_advertisement(1800, &option)).is_none());
+
+		// The option exceeds the message.
+		let mut option = prefix_option(64, PREFIX_AUTONOMOUS, 3600);
+		option[1] = 5;
+		assert!(parse_advertisement(&router_advertisement(1800, &option)).is_none());
+
+		// The checksum does not match.
+		let mut packet = router_advertisement(1800, &prefix_option(64, PREFIX_AUTONOMOUS, 3600));
+		*packet.last_mut().unwrap() ^= 1;
+		assert!(parse_advertisement(&packet).is_none());
+
+		// Routers must not forward neighbor discovery messages.
+		let mut packet = router_advertisement(1800, &[]);
+		Ipv6Packet::new_unchecked(&mut packet[..]).set_hop_limit(64);
+		assert!(parse_advertisement(&packet).is_none());
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_parse_advertisement_trailing_bytes() {
+		// Bytes, which do not form a complete option, are ignored.
+		let mut options = prefix_option(64, PREFIX_AUTONOMOUS, 3600);
+		options.extend([0u8; 4]);
+		let advertisement = parse_advertisement(&router_advertisement(1800, &options)).unwrap();
+		assert_eq!(advertisement.prefixes.len(), 1);
+	}
+}
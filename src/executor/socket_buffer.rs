@@ -0,0 +1,193 @@
+//! Capacities of the socket buffers.
+//!
+//! New TCP and UDP sockets get buffers of the default capacities, which
+//! `SO_RCVBUF` and `SO_SNDBUF` change within the minimum and the maximum
+//! capacity. The buffers of all TCP, UDP and raw sockets together may not
+//! occupy more memory than the budget, so that many connections cannot
+//! exhaust the memory of the kernel. A socket, which would exceed the
+//! budget, is not created and the call fails with `ENOBUFS`.
+//!
+//! The limits are given by the arguments `net.rmem_default=<bytes>`,
+//! `net.rmem_max=<bytes>`, `net.wmem_default=<bytes>`, `net.wmem_max=<bytes>`
+//! and `net.mem_max=<bytes>`. The budget defaults to a quarter of the memory.
+//! The number of these sockets is limited by `net.max_sockets=<count>`, which
+//! is unlimited by default. At runtime, `sys_socket_set_buffer_sizes` changes
+//! the limits, which apply to the sockets created afterwards.
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use hermit_sync::SpinMutex;
+use smoltcp::socket::Socket;
+
+use crate::arch::mm::paging::{BasePageSize, PageSize};
+use crate::executor::network::{NIC, NetworkInterface};
+
+/// Minimum capacity of the send and receive buffers given by `SO_SNDBUF` and `SO_RCVBUF`
+const MIN_CAPACITY: usize = 0x800;
+/// Default capacity of the send and receive buffers
+const DEFAULT_CAPACITY: usize = 0x10000;
+/// Default maximum capacity of the send and receive buffers
+const DEFAULT_MAX_CAPACITY: usize = 0x40_0000;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BufferLimits {
+	pub rx_default: usize,
+	pub rx_max: usize,
+	pub tx_default: usize,
+	pub tx_max: usize,
+	/// Memory, which the buffers of all sockets may occupy together
+	pub budget: usize,
+	/// Maximum number of sockets, whose buffers count against the budget
+	pub max_sockets: usize,
+}
+
+impl BufferLimits {
+	fn new() -> Self {
+		let args = crate::env::socket_buffers();
+		let mut limits = Self {
+			rx_default: args.rmem_default.unwrap_or(DEFAULT_CAPACITY),
+			rx_max: args.rmem_max.unwrap_or(DEFAULT_MAX_CAPACITY),
+			tx_default: args.wmem_default.unwrap_or(DEFAULT_CAPACITY),
+			tx_max: args.wmem_max.unwrap_or(DEFAULT_MAX_CAPACITY),
+			budget: args
+				.mem_max
+				.unwrap_or_else(|| crate::mm::physicalmem::total_memory_size() / 4),
+			max_sockets: args.max_sockets.unwrap_or(usize::MAX),
+		};
+		limits.normalize();
+		limits
+	}
+
+	/// Raises the maximum capacities to the minimum capacity and clamps the
+	/// default capacities between them.
+	fn normalize(&mut self) {
+		self.rx_max = self.rx_max.max(MIN_CAPACITY);
+		self.tx_max = self.tx_max.max(MIN_CAPACITY);
+		self.rx_default = self.rx_default.clamp(MIN_CAPACITY, self.rx_max);
+		self.tx_default = self.tx_default.clamp(MIN_CAPACITY, self.tx_max);
+	}
+
+	/// Returns the capacity, which `SO_RCVBUF` selects with `size`.
+	pub fn recv_capacity(&self, size: usize) -> usize {
+		size.clamp(MIN_CAPACITY, self.rx_max)
+	}
+
+	/// Returns the capacity, which `SO_SNDBUF` selects with `size`.
+	pub fn send_capacity(&self, size: usize) -> usize {
+		size.clamp(MIN_CAPACITY, self.tx_max)
+	}
+}
+
+static LIMITS: SpinMutex<Option<BufferLimits>> = SpinMutex::new(None);
+
+pub(crate) fn limits() -> BufferLimits {
+	*LIMITS.lock().get_or_insert_with(BufferLimits::new)
+}
+
+/// Changes the limits by `f`. Afterwards, the capacities are normalized like
+/// the ones of the arguments.
+pub(crate) fn update_limits(f: impl FnOnce(&mut BufferLimits)) {
+	let mut guard = LIMITS.lock();
+	let limits = guard.get_or_insert_with(BufferLimits::new);
+	f(limits);
+	limits.normalize();
+}
+
+/// Returns the capacity of the receive and the send buffer of `socket`,
+/// if they count against the budget.
+fn capacities(socket: &Socket<'_>) -> Option<(usize, usize)> {
+	match socket {
+		#[cfg(feature = "tcp")]
+		Socket::Tcp(socket) => Some((socket.recv_capacity(), socket.send_capacity())),
+		#[cfg(feature = "udp")]
+		Socket::Udp(socket) => Some((
+			socket.payload_recv_capacity(),
+			socket.payload_send_capacity(),
+		)),
+		#[cfg(feature = "raw")]
+		Socket::Raw(socket) => Some((
+			socket.payload_recv_capacity(),
+			socket.payload_send_capacity(),
+		)),
+		#[allow(unreachable_patterns)]
+		_ => None,
+	}
+}
+
+impl NetworkInterface<'_> {
+	/// Returns the memory, which the buffers of the sockets occupy.
+	pub(crate) fn buffer_usage(&self) -> usize {
+		self.sockets
+			.iter()
+			.filter_map(|(_, socket)| capacities(socket))
+			.map(|(rx, tx)| rx + tx)
+			.sum()
+	}
+
+	/// Checks, whether a socket with buffers of the given capacities fits
+	/// into the budget and the maximum number of sockets.
+	pub(super) fn reserve_buffers(&self, rx_capacity: usize, tx_capacity: usize) -> Result<(), ()> {
+		let limits = limits();
+		let count = self
+			.sockets
+			.iter()
+			.filter(|(_, socket)| capacities(socket).is_some())
+			.count();
+		if count >= limits.max_sockets {
+			warn!(
+				"The number of sockets exceeds the limit of {}",
+				limits.max_sockets
+			);
+			return Err(());
+		}
+		if self.buffer_usage() + rx_capacity + tx_capacity > limits.budget {
+			warn!(
+				"The socket buffers exceed the budget of {} bytes",
+				limits.budget
+			);
+			return Err(());
+		}
+		Ok(())
+	}
+}
+
+/// Number of sockets and their buffers in pages, like `/proc/net/sockstat` of Linux.
+pub(crate) fn proc_sockstat() -> String {
+	let mut tcp = (0, 0);
+	let mut udp = (0, 0);
+	let mut raw = 0;
+
+	let mut guard = NIC.lock();
+	if let Ok(nic) = guard.as_nic_mut() {
+		for (_, socket) in nic.sockets.iter() {
+			let usage = capacities(socket).map_or(0, |(rx, tx)| rx + tx);
+			match socket {
+				#[cfg(feature = "tcp")]
+				Socket::Tcp(_) => tcp = (tcp.0 + 1, tcp.1 + usage),
+				#[cfg(feature = "udp")]
+				Socket::Udp(_) => udp = (udp.0 + 1, udp.1 + usage),
+				#[cfg(feature = "raw")]
+				Socket::Raw(_) => raw += 1,
+				#[allow(unreachable_patterns)]
+				_ => {}
+			}
+		}
+	}
+	drop(guard);
+
+	let pages = |bytes: usize| bytes.div_ceil(BasePageSize::SIZE as usize);
+	let mut s = String::new();
+	writeln!(s, "sockets: used {}", tcp.0 + udp.0 + raw).unwrap();
+	writeln!(
+		s,
+		"TCP: inuse {} orphan 0 tw 0 alloc {} mem {}",
+		tcp.0,
+		tcp.0,
+		pages(tcp.1)
+	)
+	.unwrap();
+	writeln!(s, "UDP: inuse {} mem {}", udp.0, pages(udp.1)).unwrap();
+	writeln!(s, "RAW: inuse {raw}").unwrap();
+	s
+}
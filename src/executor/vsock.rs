@@ -1,16 +1,17 @@
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::vec::Vec;
 use core::future;
 use core::task::Poll;
 
 use hermit_sync::InterruptTicketMutex;
 use virtio::vsock::{Hdr, Op, Type};
-use virtio::{le16, le32};
+use virtio::{le16, le32, le64};
 
 #[cfg(not(feature = "pci"))]
 use crate::arch::kernel::mmio as hardware;
 #[cfg(feature = "pci")]
 use crate::drivers::pci as hardware;
+use crate::drivers::vsock::VirtioVsockDriver;
 use crate::errno::Errno;
 use crate::executor::{WakerRegistration, spawn};
 use crate::io;
@@ -18,26 +19,73 @@ use crate::io;
 pub(crate) static VSOCK_MAP: InterruptTicketMutex<VsockMap> =
 	InterruptTicketMutex::new(VsockMap::new());
 
+/// CID, which matches any local CID (`VMADDR_CID_ANY`)
+pub(crate) const VMADDR_CID_ANY: u32 = u32::MAX;
+
+/// The peer will not receive any more data.
+pub(crate) const SHUTDOWN_RCV: u32 = 1;
+/// The peer will not send any more data.
+pub(crate) const SHUTDOWN_SEND: u32 = 2;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub(crate) enum VsockState {
-	Listen,
-	ReceiveRequest,
-	Connected,
 	Connecting,
+	Connected,
+	/// The peer has shut down the connection or has reset it
 	Shutdown,
+	/// The peer has refused the connection
+	Refused,
 }
 
 pub(crate) const RAW_SOCKET_BUFFER_SIZE: usize = 256 * 1024;
 
-#[derive(Debug)]
-pub(crate) struct RawSocket {
+/// Identifies a connection by its local port and the address of the peer
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct ConnectionId {
+	pub local_port: u32,
 	pub remote_cid: u32,
 	pub remote_port: u32,
+}
+
+impl ConnectionId {
+	/// Returns a header without payload for this connection.
+	pub fn header(&self, local_cid: u64, op: Op, flags: u32, fwd_cnt: u32) -> Hdr {
+		Hdr {
+			src_cid: le64::from_ne(local_cid),
+			dst_cid: le64::from_ne(self.remote_cid.into()),
+			src_port: le32::from_ne(self.local_port),
+			dst_port: le32::from_ne(self.remote_port),
+			len: le32::from_ne(0),
+			type_: le16::from_ne(Type::Stream.into()),
+			op: le16::from_ne(op.into()),
+			flags: le32::from_ne(flags),
+			buf_alloc: le32::from_ne(RAW_SOCKET_BUFFER_SIZE as u32),
+			fwd_cnt: le32::from_ne(fwd_cnt),
+		}
+	}
+}
+
+/// Sends a packet without payload.
+pub(crate) fn send_header(driver: &mut VirtioVsockDriver, hdr: Hdr) {
+	const HEADER_SIZE: usize = core::mem::size_of::<Hdr>();
+
+	driver.send_packet(HEADER_SIZE, |buffer| {
+		let response = unsafe { &mut *buffer.as_mut_ptr().cast::<Hdr>() };
+		*response = hdr;
+	});
+}
+
+#[derive(Debug)]
+pub(crate) struct RawSocket {
 	pub fwd_cnt: u32,
 	pub peer_fwd_cnt: u32,
 	pub peer_buf_alloc: u32,
 	pub tx_cnt: u32,
 	pub state: VsockState,
+	/// Directions, which the peer has shut down
+	pub peer_shutdown: u32,
+	/// Directions, which have been shut down locally
+	pub local_shutdown: u32,
 	pub rx_waker: WakerRegistration,
 	pub tx_waker: WakerRegistration,
 	pub buffer: Vec<u8>,
@@ -46,106 +94,148 @@ pub(crate) struct RawSocket {
 impl RawSocket {
 	pub fn new(state: VsockState) -> Self {
 		Self {
-			remote_cid: 0,
-			remote_port: 0,
 			fwd_cnt: 0,
 			peer_fwd_cnt: 0,
 			peer_buf_alloc: 0,
 			tx_cnt: 0,
 			state,
+			peer_shutdown: 0,
+			local_shutdown: 0,
 			rx_waker: WakerRegistration::new(),
 			tx_waker: WakerRegistration::new(),
 			buffer: Vec::with_capacity(RAW_SOCKET_BUFFER_SIZE),
 		}
 	}
+
+	fn wake(&mut self) {
+		self.rx_waker.wake();
+		self.tx_waker.wake();
+	}
+}
+
+/// Socket, which accepts connections on a port
+#[derive(Debug)]
+pub(crate) struct Listener {
+	/// Local CID, to which the connections have to be addressed
+	pub cid: u32,
+	/// Maximum number of pending connections, 0 before `listen`
+	pub backlog: usize,
+	/// Established connections, which have not been accepted yet
+	pub pending: VecDeque<ConnectionId>,
+	/// Connection requests, which have been reset, because the queue was full
+	pub overflows: u64,
+	pub waker: WakerRegistration,
+}
+
+/// Handles a packet and returns the header of the reply, if any.
+fn handle_packet(map: &mut VsockMap, local_cid: u64, header: &Hdr, data: &[u8]) -> Option<Hdr> {
+	let op = Op::try_from(header.op.to_ne()).ok()?;
+	let type_ = Type::try_from(header.type_.to_ne()).ok()?;
+	let id = ConnectionId {
+		local_port: header.dst_port.to_ne(),
+		remote_cid: header.src_cid.to_ne().try_into().ok()?,
+		remote_port: header.src_port.to_ne(),
+	};
+	let reset = || Some(id.header(local_cid, Op::Rst, 0, 0));
+
+	if type_ != Type::Stream {
+		return if op == Op::Rst { None } else { reset() };
+	}
+
+	let Some(raw) = map.connections.get_mut(&id) else {
+		if op != Op::Request {
+			// Like Linux, answer packets of unknown connections with a reset,
+			// but never a reset itself.
+			return if op == Op::Rst { None } else { reset() };
+		}
+
+		let Some(listener) = map.listeners.get_mut(&id.local_port) else {
+			return reset();
+		};
+		let cid_matches = listener.cid == VMADDR_CID_ANY || u64::from(listener.cid) == local_cid;
+		if !cid_matches {
+			return reset();
+		}
+		if listener.pending.len() >= listener.backlog {
+			if listener.backlog > 0 {
+				listener.overflows += 1;
+			}
+			return reset();
+		}
+
+		let mut raw = RawSocket::new(VsockState::Connected);
+		raw.peer_buf_alloc = header.buf_alloc.to_ne();
+		raw.peer_fwd_cnt = header.fwd_cnt.to_ne();
+		listener.pending.push_back(id);
+		listener.waker.wake();
+		map.connections.insert(id, raw);
+		return Some(id.header(local_cid, Op::Response, 0, 0));
+	};
+
+	raw.peer_buf_alloc = header.buf_alloc.to_ne();
+	raw.peer_fwd_cnt = header.fwd_cnt.to_ne();
+	raw.tx_waker.wake();
+
+	match op {
+		Op::Response if raw.state == VsockState::Connecting => {
+			raw.state = VsockState::Connected;
+			raw.wake();
+			None
+		}
+		Op::Rw if raw.state == VsockState::Connected => {
+			raw.buffer.extend_from_slice(data);
+			raw.fwd_cnt = raw.fwd_cnt.wrapping_add(u32::try_from(data.len()).unwrap());
+			raw.rx_waker.wake();
+			Some(id.header(local_cid, Op::CreditUpdate, 0, raw.fwd_cnt))
+		}
+		Op::CreditRequest => Some(id.header(local_cid, Op::CreditUpdate, 0, raw.fwd_cnt)),
+		Op::CreditUpdate => None,
+		Op::Shutdown => {
+			raw.peer_shutdown |= header.flags.to_ne() & (SHUTDOWN_RCV | SHUTDOWN_SEND);
+			if raw.peer_shutdown & SHUTDOWN_SEND != 0 {
+				raw.state = VsockState::Shutdown;
+			}
+			raw.wake();
+			None
+		}
+		Op::Rst => {
+			raw.state = if raw.state == VsockState::Connecting {
+				VsockState::Refused
+			} else {
+				VsockState::Shutdown
+			};
+			raw.peer_shutdown = SHUTDOWN_RCV | SHUTDOWN_SEND;
+			raw.wake();
+			None
+		}
+		_ => {
+			trace!(
+				"Ignore vsock packet {} in state {:?}",
+				header.op.to_ne(),
+				raw.state
+			);
+			None
+		}
+	}
 }
 
 async fn vsock_run() {
 	future::poll_fn(|cx| {
 		if let Some(driver) = hardware::get_vsock_driver() {
-			const HEADER_SIZE: usize = core::mem::size_of::<Hdr>();
+			// The map is always locked before the driver.
+			let mut vsock_guard = VSOCK_MAP.lock();
 			let mut driver_guard = driver.lock();
-			let mut hdr: Option<Hdr> = None;
-			let mut fwd_cnt: u32 = 0;
+			let local_cid = driver_guard.get_cid();
+			let mut replies = Vec::new();
 
 			driver_guard.process_packet(|header, data| {
-				let op = Op::try_from(header.op.to_ne()).unwrap();
-				let port = header.dst_port.to_ne();
-				let type_ = Type::try_from(header.type_.to_ne()).unwrap();
-				let mut vsock_guard = VSOCK_MAP.lock();
-				let header_cid: u32 = header.src_cid.to_ne().try_into().unwrap();
-
-				if let Some(raw) = vsock_guard.get_mut_socket(port) {
-					if op == Op::Request && raw.state == VsockState::Listen && type_ == Type::Stream
-					{
-						raw.state = VsockState::ReceiveRequest;
-						raw.remote_cid = header_cid;
-						raw.remote_port = header.src_port.to_ne();
-						raw.peer_buf_alloc = header.buf_alloc.to_ne();
-						raw.rx_waker.wake();
-					} else if (raw.state == VsockState::Connected
-						|| raw.state == VsockState::Shutdown)
-						&& type_ == Type::Stream
-						&& op == Op::Rw
-					{
-						if raw.remote_cid == header_cid {
-							raw.buffer.extend_from_slice(data);
-							raw.fwd_cnt =
-								raw.fwd_cnt.wrapping_add(u32::try_from(data.len()).unwrap());
-							raw.peer_fwd_cnt = header.fwd_cnt.to_ne();
-							raw.tx_waker.wake();
-							raw.rx_waker.wake();
-							hdr = Some(*header);
-							fwd_cnt = raw.fwd_cnt;
-						} else {
-							trace!("Receive message from invalid source {header_cid}");
-						}
-					} else if op == Op::CreditUpdate {
-						if raw.remote_cid == header_cid {
-							raw.peer_fwd_cnt = header.fwd_cnt.to_ne();
-							raw.tx_waker.wake();
-						} else {
-							trace!("Receive message from invalid source {header_cid}");
-						}
-					} else if op == Op::Shutdown {
-						if raw.remote_cid == header_cid {
-							raw.state = VsockState::Shutdown;
-						} else {
-							trace!("Receive message from invalid source {header_cid}");
-						}
-					} else if op == Op::Response && type_ == Type::Stream {
-						if raw.remote_cid == header_cid && raw.state == VsockState::Connecting {
-							raw.state = VsockState::Connected;
-						}
-					} else if raw.remote_cid == header_cid {
-						hdr = Some(*header);
-						fwd_cnt = raw.fwd_cnt;
-					}
+				if let Some(reply) = handle_packet(&mut vsock_guard, local_cid, header, data) {
+					replies.push(reply);
 				}
 			});
 
-			if let Some(hdr) = hdr {
-				driver_guard.send_packet(HEADER_SIZE, |buffer| {
-					let response = unsafe { &mut *buffer.as_mut_ptr().cast::<Hdr>() };
-
-					response.src_cid = hdr.dst_cid;
-					response.dst_cid = hdr.src_cid;
-					response.src_port = hdr.dst_port;
-					response.dst_port = hdr.src_port;
-					response.len = le32::from_ne(0);
-					response.type_ = hdr.type_;
-					if hdr.op.to_ne() == u16::from(Op::CreditRequest)
-						|| hdr.op.to_ne() == u16::from(Op::Rw)
-					{
-						response.op = le16::from_ne(Op::CreditUpdate.into());
-					} else {
-						// reset connection
-						response.op = le16::from_ne(Op::Rst.into());
-					}
-					response.flags = le32::from_ne(0);
-					response.buf_alloc = le32::from_ne(RAW_SOCKET_BUFFER_SIZE as u32);
-					response.fwd_cnt = le32::from_ne(fwd_cnt);
-				});
+			for reply in replies {
+				send_header(&mut driver_guard, reply);
 			}
 
 			// FIXME: only wake when progress can be made
@@ -159,47 +249,116 @@ async fn vsock_run() {
 }
 
 pub(crate) struct VsockMap {
-	port_map: BTreeMap<u32, RawSocket>,
+	listeners: BTreeMap<u32, Listener>,
+	connections: BTreeMap<ConnectionId, RawSocket>,
 }
 
 impl VsockMap {
 	pub const fn new() -> Self {
 		Self {
-			port_map: BTreeMap::new(),
+			listeners: BTreeMap::new(),
+			connections: BTreeMap::new(),
 		}
 	}
 
-	pub fn bind(&mut self, port: u32) -> io::Result<()> {
-		self.port_map
-			.try_insert(port, RawSocket::new(VsockState::Listen))
-			.map_err(|_| Errno::Addrinuse)?;
+	fn is_port_used(&self, port: u32) -> bool {
+		let first = ConnectionId {
+			local_port: port,
+			remote_cid: 0,
+			remote_port: 0,
+		};
+		self.listeners.contains_key(&port)
+			|| self
+				.connections
+				.range(first..)
+				.next()
+				.is_some_and(|(id, _)| id.local_port == port)
+	}
+
+	/// Reserves `port` for a listener, which accepts connections to `cid`.
+	pub fn bind(&mut self, port: u32, cid: u32) -> io::Result<()> {
+		if self.is_port_used(port) {
+			return Err(Errno::Addrinuse);
+		}
+
+		self.listeners.insert(
+			port,
+			Listener {
+				cid,
+				backlog: 0,
+				pending: VecDeque::new(),
+				overflows: 0,
+				waker: WakerRegistration::new(),
+			},
+		);
 		Ok(())
 	}
 
-	pub fn connect(&mut self, port: u32, cid: u32) -> io::Result<u32> {
-		for i in u32::MAX / 4..u32::MAX {
-			let mut raw = RawSocket::new(VsockState::Connecting);
-			raw.remote_cid = cid;
-			raw.remote_port = port;
+	pub fn listen(&mut self, port: u32, backlog: usize) -> io::Result<()> {
+		let listener = self.listeners.get_mut(&port).ok_or(Errno::Inval)?;
+		listener.backlog = backlog.max(1);
+		Ok(())
+	}
 
-			if self.port_map.try_insert(i, raw).is_ok() {
-				return Ok(i);
+	/// Creates a connection to `cid`:`port` and returns its identifier.
+	///
+	/// Without `local_port`, an unused local port is chosen.
+	pub fn connect(
+		&mut self,
+		port: u32,
+		cid: u32,
+		local_port: Option<u32>,
+	) -> io::Result<ConnectionId> {
+		let local_port = match local_port {
+			Some(local_port) => {
+				self.listeners.remove(&local_port);
+				local_port
 			}
+			None => (u32::MAX / 4..u32::MAX)
+				.find(|&i| !self.is_port_used(i))
+				.ok_or(Errno::Addrnotavail)?,
+		};
+
+		let id = ConnectionId {
+			local_port,
+			remote_cid: cid,
+			remote_port: port,
+		};
+		if self.connections.contains_key(&id) {
+			return Err(Errno::Addrinuse);
 		}
+		self.connections
+			.insert(id, RawSocket::new(VsockState::Connecting));
+		Ok(id)
+	}
 
-		Err(Errno::Badf)
+	pub fn get_listener_mut(&mut self, port: u32) -> Option<&mut Listener> {
+		self.listeners.get_mut(&port)
+	}
+
+	/// Removes the listener and returns the connections, which have not been accepted.
+	pub fn remove_listener(&mut self, port: u32) -> VecDeque<ConnectionId> {
+		let pending = self
+			.listeners
+			.remove(&port)
+			.map(|listener| listener.pending)
+			.unwrap_or_default();
+		for id in &pending {
+			self.connections.remove(id);
+		}
+		pending
 	}
 
-	pub fn get_socket(&self, port: u32) -> Option<&RawSocket> {
-		self.port_map.get(&port)
+	pub fn get_socket(&self, id: &ConnectionId) -> Option<&RawSocket> {
+		self.connections.get(id)
 	}
 
-	pub fn get_mut_socket(&mut self, port: u32) -> Option<&mut RawSocket> {
-		self.port_map.get_mut(&port)
+	pub fn get_mut_socket(&mut self, id: &ConnectionId) -> Option<&mut RawSocket> {
+		self.connections.get_mut(id)
 	}
 
-	pub fn remove_socket(&mut self, port: u32) {
-		let _ = self.port_map.remove(&port);
+	pub fn remove_socket(&mut self, id: &ConnectionId) -> Option<RawSocket> {
+		self.connections.remove(id)
 	}
 }
 
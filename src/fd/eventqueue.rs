@@ -0,0 +1,395 @@
+//! Event queues, which report the readiness of many descriptors, similar to `epoll`.
+//!
+//! Descriptors are registered with the events, in which the application is
+//! interested. A wait does not poll every registered descriptor. Instead,
+//! each registration passes its own waker to the object of the descriptor,
+//! and only registrations, whose waker has been woken, are polled again.
+//!
+//! Registrations are level-triggered: a descriptor, which has been reported
+//! as ready, is polled again by the next wait. `EPOLLET` is accepted, but
+//! behaves like a level-triggered registration, which may report more events
+//! than the application expects. Applications like mio handle these events
+//! like spurious wakeups. With `EPOLLONESHOT`, a registration is disabled
+//! after its first event, until it is modified again.
+//!
+//! A registration is removed, when the object of its descriptor is closed.
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::{Arc, Weak};
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::future::{self, Future};
+use core::pin::pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use async_trait::async_trait;
+use hermit_sync::InterruptTicketMutex;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use crate::errno::Errno;
+use crate::executor::block_on;
+use crate::fd::{self, FileDescriptor, ObjectInterface, PollEvent};
+use crate::io;
+
+bitflags! {
+	/// Events of a registration
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub struct EventMask: u32 {
+		const EPOLLIN = 0x001;
+		const EPOLLPRI = 0x002;
+		const EPOLLOUT = 0x004;
+		const EPOLLERR = 0x008;
+		const EPOLLHUP = 0x010;
+		const EPOLLRDNORM = 0x040;
+		const EPOLLRDBAND = 0x080;
+		const EPOLLWRNORM = 0x100;
+		const EPOLLWRBAND = 0x200;
+		const EPOLLRDHUP = 0x2000;
+		const EPOLLONESHOT = 1 << 30;
+		const EPOLLET = 1 << 31;
+	}
+}
+
+impl EventMask {
+	/// Events, which are always reported, even if they have not been requested
+	const ALWAYS: Self = Self::EPOLLERR.union(Self::EPOLLHUP);
+
+	/// Converts the events to the events of `poll`, which have the same values.
+	fn to_poll_event(self) -> PollEvent {
+		let events = self.difference(Self::EPOLLONESHOT | Self::EPOLLET) | Self::ALWAYS;
+		PollEvent::from_bits_truncate(events.bits().try_into().unwrap())
+	}
+
+	fn from_poll_event(events: PollEvent) -> Self {
+		Self::from_bits_truncate(u32::from(events.bits().cast_unsigned()))
+	}
+}
+
+/// Event, which is registered with and reported by an event queue (`struct epoll_event`)
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct EventQueueEvent {
+	pub events: u32,
+	/// Data of the registration, which identifies the descriptor for the application
+	pub data: u64,
+}
+
+/// Operations of `sys_eventqueue_ctl`
+#[derive(TryFromPrimitive, IntoPrimitive, PartialEq, Eq, Clone, Copy, Debug)]
+#[repr(i32)]
+pub enum EventQueueOp {
+	Add = 1,
+	Del = 2,
+	Mod = 3,
+}
+
+type Object = Arc<async_lock::RwLock<dyn ObjectInterface>>;
+
+/// Waker of a registration, which queues the registration as ready.
+#[derive(Debug)]
+struct RegistrationWaker {
+	fd: FileDescriptor,
+	/// The registration is in the ready list.
+	queued: AtomicBool,
+	instance: Weak<Instance>,
+}
+
+impl Wake for RegistrationWaker {
+	fn wake(self: Arc<Self>) {
+		self.wake_by_ref();
+	}
+
+	fn wake_by_ref(self: &Arc<Self>) {
+		if self.queued.swap(true, Ordering::AcqRel) {
+			return;
+		}
+		if let Some(instance) = self.instance.upgrade() {
+			instance.push_ready(self.fd);
+		}
+	}
+}
+
+#[derive(Debug)]
+struct Registration {
+	object: Weak<async_lock::RwLock<dyn ObjectInterface>>,
+	events: EventMask,
+	data: u64,
+	/// A oneshot registration is disabled after its first event.
+	enabled: bool,
+	waker: Arc<RegistrationWaker>,
+}
+
+#[derive(Debug, Default)]
+struct ReadyList {
+	fds: VecDeque<FileDescriptor>,
+	/// Wakers of the tasks, which wait for the queue
+	waiters: Vec<Waker>,
+}
+
+#[derive(Debug, Default)]
+struct Instance {
+	registrations: InterruptTicketMutex<BTreeMap<FileDescriptor, Registration>>,
+	ready: InterruptTicketMutex<ReadyList>,
+}
+
+impl Instance {
+	fn push_ready(&self, fd: FileDescriptor) {
+		let waiters = {
+			let mut ready = self.ready.lock();
+			ready.fds.push_back(fd);
+			core::mem::take(&mut ready.waiters)
+		};
+		for waiter in waiters {
+			waiter.wake();
+		}
+	}
+
+	/// Returns the next ready registration or registers `waker` to be woken by the next one.
+	fn pop_ready(&self, waker: &Waker) -> Option<FileDescriptor> {
+		let mut ready = self.ready.lock();
+		let fd = ready.fds.pop_front();
+		if fd.is_none() && !ready.waiters.iter().any(|w| w.will_wake(waker)) {
+			ready.waiters.push(waker.clone());
+		}
+		fd
+	}
+
+	/// Polls the registration of `fd` and returns its events.
+	///
+	/// If the registration is ready and not oneshot, its waker is returned as
+	/// well, so that it can be queued again after the current wait.
+	fn poll_registration(
+		&self,
+		fd: FileDescriptor,
+	) -> Option<(EventQueueEvent, Option<Arc<RegistrationWaker>>)> {
+		let (object, interest, data, waker) = {
+			let mut registrations = self.registrations.lock();
+			let registration = registrations.get(&fd)?;
+			let Some(object) = registration.object.upgrade() else {
+				registrations.remove(&fd);
+				return None;
+			};
+			if !registration.enabled {
+				return None;
+			}
+			registration.waker.queued.store(false, Ordering::Release);
+			(
+				object,
+				registration.events,
+				registration.data,
+				registration.waker.clone(),
+			)
+		};
+
+		let events = poll_object(&object, interest.to_poll_event(), &waker)?;
+		let events = EventMask::from_poll_event(events);
+
+		let event = EventQueueEvent {
+			events: events.bits(),
+			data,
+		};
+		if interest.contains(EventMask::EPOLLONESHOT) {
+			if let Some(registration) = self.registrations.lock().get_mut(&fd) {
+				registration.enabled = false;
+			}
+			Some((event, None))
+		} else {
+			Some((event, Some(waker)))
+		}
+	}
+}
+
+/// Polls `object` once with the waker of its registration.
+///
+/// Returns `None`, if the object is not ready. Objects, which do not support
+/// polling, report no events and are not polled again.
+fn poll_object(
+	object: &Object,
+	events: PollEvent,
+	waker: &Arc<RegistrationWaker>,
+) -> Option<PollEvent> {
+	let waker = Waker::from(waker.clone());
+	let mut cx = Context::from_waker(&waker);
+	let mut future = pin!(async { object.read().await.poll(events).await });
+	match future.as_mut().poll(&mut cx) {
+		Poll::Ready(Ok(events)) if !events.is_empty() => Some(events),
+		Poll::Ready(Ok(_)) | Poll::Pending => None,
+		Poll::Ready(Err(_)) => Some(PollEvent::POLLERR),
+	}
+}
+
+/// Descriptor of an event queue
+#[derive(Debug)]
+struct EventQueue {
+	instance: Arc<Instance>,
+}
+
+#[async_trait]
+impl ObjectInterface for EventQueue {
+	/// An event queue is readable, if one of its registrations may be ready.
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		future::poll_fn(|cx| {
+			let mut ready = self.instance.ready.lock();
+			let available = if ready.fds.is_empty() {
+				PollEvent::empty()
+			} else {
+				PollEvent::POLLIN | PollEvent::POLLRDNORM
+			};
+
+			let ret = event & available;
+			if ret.is_empty() {
+				if !ready.waiters.iter().any(|w| w.will_wake(cx.waker())) {
+					ready.waiters.push(cx.waker().clone());
+				}
+				Poll::Pending
+			} else {
+				Poll::Ready(Ok(ret))
+			}
+		})
+		.await
+	}
+}
+
+/// Event queues, identified by the address of their object
+static INSTANCES: InterruptTicketMutex<Vec<(usize, Weak<Instance>)>> =
+	InterruptTicketMutex::new(Vec::new());
+
+fn owner(object: &Object) -> usize {
+	Arc::as_ptr(object).cast::<()>() as usize
+}
+
+fn instance(fd: FileDescriptor) -> io::Result<Arc<Instance>> {
+	let object = fd::get_object(fd)?;
+	let owner = owner(&object);
+	INSTANCES
+		.lock()
+		.iter()
+		.find(|(addr, _)| *addr == owner)
+		.and_then(|(_, instance)| instance.upgrade())
+		.ok_or(Errno::Inval)
+}
+
+/// Creates a new event queue.
+pub(crate) fn create() -> io::Result<FileDescriptor> {
+	let instance = Arc::new(Instance::default());
+	let object: Object = Arc::new(async_lock::RwLock::new(EventQueue {
+		instance: instance.clone(),
+	}));
+
+	let mut instances = INSTANCES.lock();
+	instances.retain(|(_, instance)| instance.strong_count() > 0);
+	instances.push((owner(&object), Arc::downgrade(&instance)));
+	drop(instances);
+
+	fd::insert_object(object)
+}
+
+/// Adds, modifies, or removes the registration of `fd` in the event queue `epfd`.
+pub(crate) fn ctl(
+	epfd: FileDescriptor,
+	op: EventQueueOp,
+	fd: FileDescriptor,
+	event: Option<EventQueueEvent>,
+) -> io::Result<()> {
+	let instance = instance(epfd)?;
+	let object = fd::get_object(fd)?;
+	if Arc::ptr_eq(&object, &fd::get_object(epfd)?) {
+		return Err(Errno::Inval);
+	}
+
+	let mut registrations = instance.registrations.lock();
+	let registered = registrations
+		.get(&fd)
+		.is_some_and(|registration| registration.object.strong_count() > 0);
+
+	match op {
+		EventQueueOp::Add | EventQueueOp::Mod => {
+			let event = event.ok_or(Errno::Fault)?;
+			match (op, registered) {
+				(EventQueueOp::Add, true) => return Err(Errno::Exist),
+				(EventQueueOp::Mod, false) => return Err(Errno::Noent),
+				_ => {}
+			}
+
+			// A new waker is created, so that wakers of the previous
+			// registration only cause an additional poll.
+			let waker = Arc::new(RegistrationWaker {
+				fd,
+				queued: AtomicBool::new(false),
+				instance: Arc::downgrade(&instance),
+			});
+			registrations.insert(
+				fd,
+				Registration {
+					object: Arc::downgrade(&object),
+					events: EventMask::from_bits_truncate(event.events),
+					data: event.data,
+					enabled: true,
+					waker: waker.clone(),
+				},
+			);
+			drop(registrations);
+
+			// The descriptor is polled by the next wait, which passes the waker to it.
+			waker.wake();
+		}
+		EventQueueOp::Del => {
+			if !registered {
+				return Err(Errno::Noent);
+			}
+			registrations.remove(&fd);
+		}
+	}
+
+	Ok(())
+}
+
+async fn wait_events(instance: &Instance, events: &mut [EventQueueEvent]) -> io::Result<usize> {
+	future::poll_fn(|cx| {
+		let mut count = 0;
+		let mut requeue = Vec::new();
+		while count < events.len()
+			&& let Some(fd) = instance.pop_ready(cx.waker())
+		{
+			if let Some((event, waker)) = instance.poll_registration(fd) {
+				events[count] = event;
+				count += 1;
+				requeue.extend(waker);
+			}
+		}
+
+		// Level-triggered registrations, which have been ready, are polled
+		// again by the next wait.
+		for waker in requeue {
+			waker.wake();
+		}
+
+		if count > 0 {
+			Poll::Ready(Ok(count))
+		} else {
+			Poll::Pending
+		}
+	})
+	.await
+}
+
+/// Waits for events of the registrations of the event queue `epfd`.
+///
+/// Returns zero, if no event has been reported within `timeout`.
+pub(crate) fn wait(
+	epfd: FileDescriptor,
+	events: &mut [EventQueueEvent],
+	timeout: Option<core::time::Duration>,
+) -> io::Result<usize> {
+	let instance = instance(epfd)?;
+	if events.is_empty() {
+		return Err(Errno::Inval);
+	}
+
+	match block_on(wait_events(&instance, events), timeout) {
+		Err(Errno::Time) => Ok(0),
+		result => result,
+	}
+}
@@ -0,0 +1,77 @@
+use alloc::sync::Arc;
+use core::task::Poll;
+use core::{future, mem};
+
+use async_trait::async_trait;
+
+use crate::errno::Errno;
+use crate::fd::{ObjectInterface, PollEvent};
+use crate::io;
+use crate::mm::pressure::{self, Level, Trigger};
+
+/// Descriptor, which becomes readable, when the memory pressure rises to or
+/// above its level.
+///
+/// A read returns the number of notifications since the last read as `u64`.
+#[derive(Debug)]
+pub(crate) struct MemoryPressureFd {
+	trigger: Arc<Trigger>,
+	nonblocking: bool,
+}
+
+impl MemoryPressureFd {
+	pub fn new(level: Level, nonblocking: bool) -> Self {
+		debug!("Create MemoryPressureFd {level:?}");
+		Self {
+			trigger: pressure::register(level),
+			nonblocking,
+		}
+	}
+}
+
+#[async_trait]
+impl ObjectInterface for MemoryPressureFd {
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let len = mem::size_of::<u64>();
+
+		if buf.len() < len {
+			return Err(Errno::Inval);
+		}
+
+		future::poll_fn(|cx| {
+			// register before checking, so that no notification is missed
+			if !self.nonblocking {
+				self.trigger.register(cx.waker());
+			}
+
+			let events = self.trigger.take_events();
+			if events > 0 {
+				buf[..len].copy_from_slice(&u64::to_ne_bytes(events));
+				Poll::Ready(Ok(len))
+			} else if self.nonblocking {
+				Poll::Ready(Err(Errno::Again))
+			} else {
+				Poll::Pending
+			}
+		})
+		.await
+	}
+
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		let readable = PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLPRI;
+
+		future::poll_fn(|cx| {
+			if !event.intersects(readable) {
+				return Poll::Ready(Ok(PollEvent::empty()));
+			}
+
+			self.trigger.register(cx.waker());
+			if self.trigger.has_events() {
+				Poll::Ready(Ok(event & readable))
+			} else {
+				Poll::Pending
+			}
+		})
+		.await
+	}
+}
@@ -1,51 +1,145 @@
 use alloc::boxed::Box;
 use alloc::sync::Arc;
+use alloc::vec;
 use core::future::{self, Future};
 use core::mem::MaybeUninit;
 use core::task::Poll::{Pending, Ready};
 use core::time::Duration;
 
 use async_trait::async_trait;
+#[cfg(feature = "udp")]
+use smoltcp::wire::IpAddress;
 #[cfg(feature = "net")]
 use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 
 use crate::arch::kernel::core_local::core_scheduler;
 use crate::errno::Errno;
 use crate::executor::block_on;
-use crate::fs::{FileAttr, SeekWhence};
+use crate::fs::{FileAttr, FileTimes, SeekWhence};
 use crate::io;
+#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+use crate::time::timeval;
 
 mod eventfd;
-#[cfg(any(feature = "net", feature = "vsock"))]
+pub mod eventqueue;
+mod mempressure;
+#[cfg(feature = "pcap")]
+mod pcap;
+#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 pub(crate) mod socket;
 pub(crate) mod stdio;
+pub(crate) mod table;
 
 pub(crate) const STDIN_FILENO: FileDescriptor = 0;
 pub(crate) const STDOUT_FILENO: FileDescriptor = 1;
 pub(crate) const STDERR_FILENO: FileDescriptor = 2;
 
-#[cfg(any(feature = "net", feature = "vsock"))]
+#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 #[derive(Debug)]
 pub(crate) enum Endpoint {
 	#[cfg(feature = "net")]
 	Ip(IpEndpoint),
 	#[cfg(feature = "vsock")]
 	Vsock(socket::vsock::VsockEndpoint),
+	#[cfg(feature = "unix")]
+	Unix(socket::unix::UnixAddress),
 }
 
-#[cfg(any(feature = "net", feature = "vsock"))]
+#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 #[derive(Debug)]
 pub(crate) enum ListenEndpoint {
 	#[cfg(feature = "net")]
 	Ip(IpListenEndpoint),
 	#[cfg(feature = "vsock")]
 	Vsock(socket::vsock::VsockListenEndpoint),
+	#[cfg(feature = "unix")]
+	Unix(socket::unix::UnixAddress),
 }
 
-#[allow(dead_code)]
-#[derive(Debug, PartialEq)]
+/// Occupancy of the queue of a listening socket
+#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct ListenQueue {
+	/// Connections, which wait to be accepted
+	pub depth: usize,
+	/// Maximum number of connections, which can wait
+	pub backlog: usize,
+	/// Connection attempts, which have found the queue full
+	pub overflows: u64,
+}
+
+/// Options of sockets, which are set and read with `setsockopt` and `getsockopt`
+#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub(crate) enum SocketOption {
+	/// Disables the Nagle algorithm (`TCP_NODELAY`)
 	TcpNoDelay,
+	/// Idle time in seconds before the first keep-alive probe (`TCP_KEEPIDLE`)
+	TcpKeepIdle,
+	/// Time in seconds between keep-alive probes (`TCP_KEEPINTVL`)
+	TcpKeepInterval,
+	/// Unanswered keep-alive probes, before the connection is dropped (`TCP_KEEPCNT`)
+	TcpKeepCount,
+	/// Allows binding to an address in use (`SO_REUSEADDR`)
+	ReuseAddr,
+	/// Sends keep-alive probes (`SO_KEEPALIVE`)
+	KeepAlive,
+	/// Time, which a close waits for unsent data (`SO_LINGER`)
+	Linger,
+	/// Capacity of the send buffer in bytes (`SO_SNDBUF`)
+	SendBuffer,
+	/// Capacity of the receive buffer in bytes (`SO_RCVBUF`)
+	RecvBuffer,
+	/// Timeout of blocking sends (`SO_SNDTIMEO`)
+	SendTimeout,
+	/// Timeout of blocking receives (`SO_RCVTIMEO`)
+	RecvTimeout,
+	/// Pending error of the socket (`SO_ERROR`)
+	Error,
+	/// Reports the time of reception of datagrams (`SO_TIMESTAMP`)
+	Timestamp,
+	/// Reports the destination address of datagrams (`IP_PKTINFO`, `IPV6_RECVPKTINFO`)
+	PacketInfo,
+}
+
+/// Value of a [`SocketOption`]
+#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum SocketOptionValue {
+	Flag(bool),
+	Int(i32),
+	/// A timeout, or no timeout if `None`
+	Duration(Option<Duration>),
+}
+
+/// Data, which `recvmsg` has received, and its ancillary data
+#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+#[derive(Debug, Default)]
+pub(crate) struct ReceivedMessage {
+	/// Number of bytes in the buffer
+	pub len: usize,
+	/// Sender, if the socket knows it
+	pub endpoint: Option<Endpoint>,
+	/// The remainder of the datagram did not fit into the buffer and has been discarded.
+	pub truncated: bool,
+	/// Time of reception, if `SO_TIMESTAMP` is enabled
+	pub timestamp: Option<timeval>,
+	/// Destination address and the index of its interface, if `IP_PKTINFO`
+	/// or `IPV6_RECVPKTINFO` is enabled
+	#[cfg(feature = "udp")]
+	pub packet_info: Option<(IpAddress, usize)>,
+}
+
+/// Multicast options of datagram sockets
+#[cfg(feature = "udp")]
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum MulticastOption {
+	/// Joins the multicast group
+	AddMembership(IpAddress),
+	/// Leaves the multicast group
+	DropMembership(IpAddress),
+	/// Hop limit of outgoing multicast datagrams
+	HopLimit(u8),
 }
 
 pub(crate) type FileDescriptor = i32;
@@ -62,6 +156,13 @@ bitflags! {
 		const O_TRUNC = 0o1000;
 		const O_APPEND = StatusFlags::O_APPEND.bits();
 		const O_NONBLOCK = StatusFlags::O_NONBLOCK.bits();
+		/// Writes return after the data has reached stable storage.
+		const O_DSYNC = 0o10000;
+		/// Like `O_DSYNC`, which also covers the metadata of the file
+		const O_SYNC = 0o4_010_000;
+		/// Transfers go straight to the block device and have to be aligned to its block size.
+		///
+		/// `O_DIRECT` is ignored by file systems, which are not backed by a block device.
 		const O_DIRECT = 0o40000;
 		const O_DIRECTORY = 0o200_000;
 		/// `O_CLOEXEC` has no functionality in Hermit and will be silently ignored
@@ -124,6 +225,18 @@ bitflags! {
 	}
 }
 
+bitflags! {
+	/// Modes of `fallocate`
+	#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+	pub struct FallocateMode: i32 {
+		/// The file size is not changed, even if the range extends beyond the end of the file.
+		const FALLOC_FL_KEEP_SIZE = 0x01;
+		/// Deallocates the range, which reads as zeros afterwards.
+		/// Requires `FALLOC_FL_KEEP_SIZE`.
+		const FALLOC_FL_PUNCH_HOLE = 0x02;
+	}
+}
+
 bitflags! {
 	#[derive(Debug, Copy, Clone, Default)]
 	pub struct PollEvent: i16 {
@@ -219,6 +332,55 @@ pub(crate) trait ObjectInterface: Sync + Send + core::fmt::Debug {
 		Err(Errno::Inval)
 	}
 
+	/// `pread` reads up to `buf.len()` bytes at `offset` without changing
+	/// the position of the file descriptor
+	async fn pread(&self, _buf: &mut [u8], _offset: usize) -> io::Result<usize> {
+		Err(Errno::Spipe)
+	}
+
+	/// `pwrite` writes `buf` at `offset` without changing the position of
+	/// the file descriptor
+	async fn pwrite(&self, _buf: &[u8], _offset: usize) -> io::Result<usize> {
+		Err(Errno::Spipe)
+	}
+
+	/// `send_to` writes up to `count` bytes at `offset` to `sink` without
+	/// copying them to the user space and returns the number of written bytes
+	///
+	/// Files, whose content is kept in memory, pass it directly to `sink`.
+	/// Otherwise, the data is copied through a buffer of the kernel.
+	async fn send_to(
+		&self,
+		sink: &dyn ObjectInterface,
+		offset: usize,
+		count: usize,
+	) -> io::Result<usize> {
+		const BUFFER_SIZE: usize = 64 * 1024;
+
+		let mut buf = vec![0; count.min(BUFFER_SIZE)];
+		let mut pos = 0;
+		while pos < count {
+			let len = (count - pos).min(buf.len());
+			let len = self.pread(&mut buf[..len], offset + pos).await?;
+			if len == 0 {
+				break;
+			}
+
+			match sink.write(&buf[..len]).await {
+				Ok(written) => {
+					pos += written;
+					if written < len {
+						break;
+					}
+				}
+				Err(e) if pos == 0 => return Err(e),
+				Err(_) => break,
+			}
+		}
+
+		Ok(pos)
+	}
+
 	/// `fstat`
 	async fn fstat(&self) -> io::Result<FileAttr> {
 		Err(Errno::Inval)
@@ -232,7 +394,7 @@ pub(crate) trait ObjectInterface: Sync + Send + core::fmt::Debug {
 	}
 
 	/// `accept` a connection on a socket
-	#[cfg(any(feature = "net", feature = "vsock"))]
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 	async fn accept(
 		&mut self,
 	) -> io::Result<(Arc<async_lock::RwLock<dyn ObjectInterface>>, Endpoint)> {
@@ -240,54 +402,97 @@ pub(crate) trait ObjectInterface: Sync + Send + core::fmt::Debug {
 	}
 
 	/// initiate a connection on a socket
-	#[cfg(any(feature = "net", feature = "vsock"))]
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 	async fn connect(&mut self, _endpoint: Endpoint) -> io::Result<()> {
 		Err(Errno::Inval)
 	}
 
 	/// `bind` a name to a socket
-	#[cfg(any(feature = "net", feature = "vsock"))]
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 	async fn bind(&mut self, _name: ListenEndpoint) -> io::Result<()> {
 		Err(Errno::Inval)
 	}
 
 	/// `listen` for connections on a socket
-	#[cfg(any(feature = "net", feature = "vsock"))]
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 	async fn listen(&mut self, _backlog: i32) -> io::Result<()> {
 		Err(Errno::Inval)
 	}
 
+	/// Returns the occupancy of the queue of a listening socket.
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+	async fn listen_queue(&self) -> io::Result<ListenQueue> {
+		Err(Errno::Inval)
+	}
+
 	/// `setsockopt` sets options on sockets
-	#[cfg(any(feature = "net", feature = "vsock"))]
-	async fn setsockopt(&self, _opt: SocketOption, _optval: bool) -> io::Result<()> {
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+	async fn setsockopt(
+		&mut self,
+		_opt: SocketOption,
+		_optval: SocketOptionValue,
+	) -> io::Result<()> {
 		Err(Errno::Notsock)
 	}
 
 	/// `getsockopt` gets options on sockets
-	#[cfg(any(feature = "net", feature = "vsock"))]
-	async fn getsockopt(&self, _opt: SocketOption) -> io::Result<bool> {
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+	async fn getsockopt(&self, _opt: SocketOption) -> io::Result<SocketOptionValue> {
 		Err(Errno::Notsock)
 	}
 
+	/// `set_multicast_option` sets multicast options on datagram sockets
+	#[cfg(feature = "udp")]
+	async fn set_multicast_option(&mut self, _opt: MulticastOption) -> io::Result<()> {
+		Err(Errno::Noprotoopt)
+	}
+
+	/// `set_hop_limit` sets the hop limit of outgoing unicast packets
+	#[cfg(feature = "raw")]
+	async fn set_hop_limit(&mut self, _hop_limit: u8) -> io::Result<()> {
+		Err(Errno::Noprotoopt)
+	}
+
 	/// `getsockname` gets socket name
-	#[cfg(any(feature = "net", feature = "vsock"))]
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 	async fn getsockname(&self) -> io::Result<Option<Endpoint>> {
 		Ok(None)
 	}
 
 	/// `getpeername` get address of connected peer
-	#[cfg(any(feature = "net", feature = "vsock"))]
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 	#[allow(dead_code)]
 	async fn getpeername(&self) -> io::Result<Option<Endpoint>> {
 		Ok(None)
 	}
 
 	/// receive a message from a socket
-	#[cfg(any(feature = "net", feature = "vsock"))]
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 	async fn recvfrom(&self, _buffer: &mut [MaybeUninit<u8>]) -> io::Result<(usize, Endpoint)> {
 		Err(Errno::Nosys)
 	}
 
+	/// receive a message and its ancillary data from a socket
+	///
+	/// Sockets, which do not report their senders, receive through `read`.
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+	async fn recvmsg(&self, buffer: &mut [u8]) -> io::Result<ReceivedMessage> {
+		let uninit =
+			unsafe { core::slice::from_raw_parts_mut(buffer.as_mut_ptr().cast(), buffer.len()) };
+		match self.recvfrom(uninit).await {
+			Ok((len, endpoint)) => Ok(ReceivedMessage {
+				len,
+				endpoint: Some(endpoint),
+				..Default::default()
+			}),
+			Err(Errno::Nosys) => Ok(ReceivedMessage {
+				len: self.read(buffer).await?,
+				..Default::default()
+			}),
+			Err(e) => Err(e),
+		}
+	}
+
 	/// send a message from a socket
 	///
 	/// The sendto() function shall send a message.
@@ -295,13 +500,13 @@ pub(crate) trait ObjectInterface: Sync + Send + core::fmt::Debug {
 	/// If a peer address has been prespecified, either the message shall
 	/// be sent to the address specified by dest_addr (overriding the pre-specified peer
 	/// address).
-	#[cfg(any(feature = "net", feature = "vsock"))]
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 	async fn sendto(&self, _buffer: &[u8], _endpoint: Endpoint) -> io::Result<usize> {
 		Err(Errno::Nosys)
 	}
 
 	/// shut down part of a full-duplex connection
-	#[cfg(any(feature = "net", feature = "vsock"))]
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 	async fn shutdown(&self, _how: i32) -> io::Result<()> {
 		Err(Errno::Nosys)
 	}
@@ -321,17 +526,60 @@ pub(crate) trait ObjectInterface: Sync + Send + core::fmt::Debug {
 		Err(Errno::Nosys)
 	}
 
+	/// Allocates or deallocates `len` bytes at `offset` of the file
+	async fn allocate(&self, _mode: FallocateMode, _offset: usize, _len: usize) -> io::Result<()> {
+		Err(Errno::Opnotsupp)
+	}
+
+	/// Ensures that all written data reached stable storage
+	async fn fsync(&self) -> io::Result<()> {
+		Err(Errno::Inval)
+	}
+
 	/// Changes access permissions to the file
 	async fn chmod(&self, _access_permission: AccessPermission) -> io::Result<()> {
 		Err(Errno::Nosys)
 	}
 
+	/// Sets the access and modification times of the file
+	async fn utimens(&self, _times: FileTimes) -> io::Result<()> {
+		Err(Errno::Nosys)
+	}
+
 	/// `isatty` returns `true` for a terminal device
 	async fn isatty(&self) -> io::Result<bool> {
 		Ok(false)
 	}
 }
 
+/// Returns the timeout of blocking receives or sends on a socket given by
+/// `SO_RCVTIMEO` or `SO_SNDTIMEO`.
+#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+pub(crate) fn socket_timeout(
+	obj: &Arc<async_lock::RwLock<dyn ObjectInterface>>,
+	opt: SocketOption,
+) -> Option<Duration> {
+	match block_on(async { obj.read().await.getsockopt(opt).await }, None) {
+		Ok(SocketOptionValue::Duration(timeout)) => timeout,
+		_ => None,
+	}
+}
+
+/// Blocks on a receive or send, which fails with `EAGAIN` after `timeout`
+/// like a socket with `SO_RCVTIMEO` or `SO_SNDTIMEO`.
+pub(crate) fn block_on_io<F, T>(future: F, timeout: Option<Duration>) -> io::Result<T>
+where
+	F: Future<Output = io::Result<T>>,
+{
+	block_on(future, timeout).map_err(|e| {
+		if timeout.is_some() && e == Errno::Time {
+			Errno::Again
+		} else {
+			e
+		}
+	})
+}
+
 pub(crate) fn read(fd: FileDescriptor, buf: &mut [u8]) -> io::Result<usize> {
 	let obj = get_object(fd)?;
 
@@ -339,7 +587,11 @@ pub(crate) fn read(fd: FileDescriptor, buf: &mut [u8]) -> io::Result<usize> {
 		return Ok(0);
 	}
 
-	block_on(async { obj.read().await.read(buf).await }, None)
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+	let timeout = socket_timeout(&obj, SocketOption::RecvTimeout);
+	#[cfg(not(any(feature = "net", feature = "vsock", feature = "unix")))]
+	let timeout = None;
+	block_on_io(async { obj.read().await.read(buf).await }, timeout)
 }
 
 pub(crate) fn lseek(fd: FileDescriptor, offset: isize, whence: SeekWhence) -> io::Result<isize> {
@@ -354,6 +606,12 @@ pub(crate) fn chmod(fd: FileDescriptor, mode: AccessPermission) -> io::Result<()
 	block_on(async { obj.read().await.chmod(mode).await }, None)
 }
 
+pub(crate) fn utimens(fd: FileDescriptor, times: FileTimes) -> io::Result<()> {
+	let obj = get_object(fd)?;
+
+	block_on(async { obj.read().await.utimens(times).await }, None)
+}
+
 pub(crate) fn write(fd: FileDescriptor, buf: &[u8]) -> io::Result<usize> {
 	let obj = get_object(fd)?;
 
@@ -361,7 +619,55 @@ pub(crate) fn write(fd: FileDescriptor, buf: &[u8]) -> io::Result<usize> {
 		return Ok(0);
 	}
 
-	block_on(async { obj.read().await.write(buf).await }, None)
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+	let timeout = socket_timeout(&obj, SocketOption::SendTimeout);
+	#[cfg(not(any(feature = "net", feature = "vsock", feature = "unix")))]
+	let timeout = None;
+	block_on_io(async { obj.read().await.write(buf).await }, timeout)
+}
+
+/// Writes up to `count` bytes of the file `fd` to `socket`.
+///
+/// Reads at `offset`, if it is given. Otherwise, reads at the position of
+/// `fd` and advances it.
+pub(crate) fn sendfile(
+	fd: FileDescriptor,
+	socket: FileDescriptor,
+	offset: Option<usize>,
+	count: usize,
+) -> io::Result<usize> {
+	let obj = get_object(fd)?;
+	let sink = get_object(socket)?;
+
+	#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
+	let timeout = socket_timeout(&sink, SocketOption::SendTimeout);
+	#[cfg(not(any(feature = "net", feature = "vsock", feature = "unix")))]
+	let timeout = None;
+	block_on_io(
+		async {
+			let file = obj.read().await;
+			let sink = sink.read().await;
+			match offset {
+				Some(offset) => file.send_to(&*sink, offset, count).await,
+				None => {
+					let pos = file.lseek(0, SeekWhence::Cur).await?;
+					let len = file.send_to(&*sink, pos.try_into().unwrap(), count).await?;
+					file.lseek(pos + isize::try_from(len).unwrap(), SeekWhence::Set)
+						.await?;
+					Ok(len)
+				}
+			}
+		},
+		timeout,
+	)
+}
+
+pub(crate) fn fsync(fd: FileDescriptor) -> io::Result<()> {
+	let obj = get_object(fd)?;
+	// Shared mappings are the only data, which is buffered by the kernel.
+	#[cfg(feature = "mman")]
+	crate::mm::file_mapping::sync_object(&obj)?;
+	block_on(async { obj.read().await.fsync().await }, None)
 }
 
 pub(crate) fn truncate(fd: FileDescriptor, length: usize) -> io::Result<()> {
@@ -369,6 +675,26 @@ pub(crate) fn truncate(fd: FileDescriptor, length: usize) -> io::Result<()> {
 	block_on(async { obj.read().await.truncate(length).await }, None)
 }
 
+pub(crate) fn allocate(
+	fd: FileDescriptor,
+	mode: FallocateMode,
+	offset: usize,
+	len: usize,
+) -> io::Result<()> {
+	if len == 0
+		|| (mode.contains(FallocateMode::FALLOC_FL_PUNCH_HOLE)
+			&& !mode.contains(FallocateMode::FALLOC_FL_KEEP_SIZE))
+	{
+		return Err(Errno::Inval);
+	}
+
+	let obj = get_object(fd)?;
+	block_on(
+		async { obj.read().await.allocate(mode, offset, len).await },
+		None,
+	)
+}
+
 async fn poll_fds(fds: &mut [PollFd]) -> io::Result<u64> {
 	future::poll_fn(|cx| {
 		let mut counter: u64 = 0;
@@ -447,6 +773,33 @@ pub fn eventfd(initval: u64, flags: EventFlags) -> io::Result<FileDescriptor> {
 	Ok(fd)
 }
 
+/// Creates a descriptor, which becomes readable, when the memory pressure
+/// rises to or above `level`.
+pub(crate) fn mempressure(
+	level: crate::mm::pressure::Level,
+	nonblocking: bool,
+) -> io::Result<FileDescriptor> {
+	let obj = self::mempressure::MemoryPressureFd::new(level, nonblocking);
+
+	let fd = core_scheduler().insert_object(Arc::new(async_lock::RwLock::new(obj)))?;
+
+	Ok(fd)
+}
+
+/// Creates a descriptor, from which the frames of a packet capture are read.
+#[cfg(feature = "pcap")]
+pub(crate) fn pcap(
+	filter: crate::executor::capture::Filter,
+	snaplen: usize,
+	nonblocking: bool,
+) -> io::Result<FileDescriptor> {
+	let obj = self::pcap::PcapFd::new(filter, snaplen, nonblocking);
+
+	let fd = core_scheduler().insert_object(Arc::new(async_lock::RwLock::new(obj)))?;
+
+	Ok(fd)
+}
+
 pub(crate) fn get_object(
 	fd: FileDescriptor,
 ) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
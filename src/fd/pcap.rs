@@ -0,0 +1,72 @@
+use alloc::sync::Arc;
+use core::future;
+use core::task::Poll;
+
+use async_trait::async_trait;
+
+use crate::errno::Errno;
+use crate::executor::capture::{self, Capture, Filter};
+use crate::fd::{ObjectInterface, PollEvent};
+use crate::io;
+
+/// Descriptor, from which the frames of a packet capture are read in the
+/// format of libpcap.
+#[derive(Debug)]
+pub(crate) struct PcapFd {
+	capture: Arc<Capture>,
+	nonblocking: bool,
+}
+
+impl PcapFd {
+	pub fn new(filter: Filter, snaplen: usize, nonblocking: bool) -> Self {
+		debug!("Create PcapFd {filter:?}");
+		Self {
+			capture: capture::open(filter, snaplen),
+			nonblocking,
+		}
+	}
+}
+
+#[async_trait]
+impl ObjectInterface for PcapFd {
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		if buf.is_empty() {
+			return Ok(0);
+		}
+
+		future::poll_fn(|cx| {
+			// register before reading, so that no frame is missed
+			if !self.nonblocking {
+				self.capture.register(cx.waker());
+			}
+
+			let len = self.capture.read(buf);
+			if len > 0 {
+				Poll::Ready(Ok(len))
+			} else if self.nonblocking {
+				Poll::Ready(Err(Errno::Again))
+			} else {
+				Poll::Pending
+			}
+		})
+		.await
+	}
+
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		let readable = PollEvent::POLLIN | PollEvent::POLLRDNORM;
+
+		future::poll_fn(|cx| {
+			if !event.intersects(readable) {
+				return Poll::Ready(Ok(PollEvent::empty()));
+			}
+
+			self.capture.register(cx.waker());
+			if self.capture.is_empty() {
+				Poll::Pending
+			} else {
+				Poll::Ready(Ok(event & readable))
+			}
+		})
+		.await
+	}
+}
@@ -1,6 +1,10 @@
+#[cfg(feature = "raw")]
+pub(crate) mod raw;
 #[cfg(feature = "tcp")]
 pub(crate) mod tcp;
 #[cfg(feature = "udp")]
 pub(crate) mod udp;
+#[cfg(feature = "unix")]
+pub(crate) mod unix;
 #[cfg(feature = "vsock")]
 pub(crate) mod vsock;
@@ -0,0 +1,406 @@
+//! Raw sockets of the protocols ICMP and ICMPv6.
+//!
+//! As on Linux, an ICMP socket receives the whole IPv4 packet including its
+//! header, while an ICMPv6 socket only receives the ICMPv6 message. Messages
+//! are sent without an IP header, which is built by the kernel. The kernel
+//! also computes the checksum of outgoing messages, so that applications may
+//! leave it empty, e.g., in echo requests. Echo requests to the interface are
+//! still answered by the network stack itself.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::future;
+use core::mem::MaybeUninit;
+use core::task::Poll;
+use core::time::Duration;
+
+use async_trait::async_trait;
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::socket::raw;
+use smoltcp::wire::{
+	IPV6_HEADER_LEN, Icmpv4Packet, Icmpv6Packet, IpAddress, IpCidr, IpEndpoint, IpProtocol,
+	IpVersion, Ipv4Address, Ipv4Packet, Ipv4Repr, Ipv6Address, Ipv6Packet, Ipv6Repr,
+};
+
+use crate::errno::Errno;
+use crate::executor::network::{Handle, NIC};
+use crate::fd::{
+	self, Endpoint, ListenEndpoint, ObjectInterface, PollEvent, SocketOption, SocketOptionValue,
+};
+use crate::io;
+use crate::syscalls::socket::Af;
+
+/// Default hop limit of outgoing packets
+pub(crate) const DEFAULT_HOP_LIMIT: u8 = 64;
+
+/// Maximum size of an outgoing packet including the IP header
+const MAX_PACKET_SIZE: usize = 0x10000;
+
+/// Selects the address of the interface, from which `dst_addr` is reached.
+///
+/// Link-local destinations are reached from the link-local address and all
+/// other IPv6 destinations preferably from a global address.
+fn source_address(addrs: &[IpCidr], dst_addr: IpAddress) -> Option<IpAddress> {
+	match dst_addr {
+		IpAddress::Ipv4(_) => addrs.iter().find_map(|cidr| match cidr.address() {
+			IpAddress::Ipv4(addr) => Some(IpAddress::Ipv4(addr)),
+			IpAddress::Ipv6(_) => None,
+		}),
+		IpAddress::Ipv6(dst_addr) => {
+			let link_local = dst_addr.is_unicast_link_local()
+				|| (dst_addr.is_multicast() && dst_addr.octets()[1] & 0xf <= 2);
+			let mut candidates = addrs.iter().filter_map(|cidr| match cidr.address() {
+				IpAddress::Ipv4(_) => None,
+				IpAddress::Ipv6(addr) => Some(addr),
+			});
+			candidates
+				.clone()
+				.find(|addr| addr.is_unicast_link_local() == link_local)
+				.or_else(|| candidates.next())
+				.map(IpAddress::Ipv6)
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct Socket {
+	handle: Handle,
+	nonblocking: bool,
+	ip_version: IpVersion,
+	/// Source address, which has been selected by `bind`
+	local_addr: Option<IpAddress>,
+	/// Default destination, which has been selected by `connect`
+	remote_addr: Option<IpAddress>,
+	hop_limit: u8,
+	send_timeout: Option<Duration>,
+	recv_timeout: Option<Duration>,
+}
+
+impl Socket {
+	pub fn new(handle: Handle, domain: Af) -> Self {
+		let ip_version = match domain {
+			Af::Inet => IpVersion::Ipv4,
+			Af::Inet6 => IpVersion::Ipv6,
+			_ => panic!("Unsupported domain for raw socket: {domain:?}"),
+		};
+
+		Self {
+			handle,
+			nonblocking: false,
+			ip_version,
+			local_addr: None,
+			remote_addr: None,
+			hop_limit: DEFAULT_HOP_LIMIT,
+			send_timeout: None,
+			recv_timeout: None,
+		}
+	}
+
+	fn with<R>(&self, f: impl FnOnce(&mut raw::Socket<'_>) -> R) -> R {
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().unwrap();
+		f(nic.get_mut_socket::<raw::Socket<'_>>(self.handle))
+	}
+
+	fn check_version(&self, addr: IpAddress) -> io::Result<()> {
+		if addr.version() == self.ip_version {
+			Ok(())
+		} else {
+			Err(Errno::Afnosupport)
+		}
+	}
+
+	/// Builds the IP packet, which carries `message` to `dst_addr`.
+	fn build_packet(&self, message: &[u8], dst_addr: IpAddress) -> io::Result<Vec<u8>> {
+		self.check_version(dst_addr)?;
+		let src_addr = match self.local_addr {
+			Some(addr) => addr,
+			None => {
+				let mut guard = NIC.lock();
+				let nic = guard.as_nic_mut().map_err(|_| Errno::Netdown)?;
+				source_address(nic.source_addrs(dst_addr), dst_addr).ok_or(Errno::Netunreach)?
+			}
+		};
+
+		let packet = match (src_addr, dst_addr) {
+			(IpAddress::Ipv4(src_addr), IpAddress::Ipv4(dst_addr)) => {
+				let repr = Ipv4Repr {
+					src_addr,
+					dst_addr,
+					next_header: IpProtocol::Icmp,
+					payload_len: message.len(),
+					hop_limit: self.hop_limit,
+				};
+				let mut packet = vec![0; repr.buffer_len() + message.len()];
+				let mut ipv4 = Ipv4Packet::new_unchecked(&mut packet[..]);
+				repr.emit(&mut ipv4, &ChecksumCapabilities::default());
+				let payload = ipv4.payload_mut();
+				payload.copy_from_slice(message);
+				Icmpv4Packet::new_checked(payload)
+					.map_err(|_| Errno::Inval)?
+					.fill_checksum();
+				packet
+			}
+			(IpAddress::Ipv6(src_addr), IpAddress::Ipv6(dst_addr)) => {
+				let repr = Ipv6Repr {
+					src_addr,
+					dst_addr,
+					next_header: IpProtocol::Icmpv6,
+					payload_len: message.len(),
+					hop_limit: self.hop_limit,
+				};
+				let mut packet = vec![0; repr.buffer_len() + message.len()];
+				let mut ipv6 = Ipv6Packet::new_unchecked(&mut packet[..]);
+				repr.emit(&mut ipv6);
+				let payload = ipv6.payload_mut();
+				payload.copy_from_slice(message);
+				Icmpv6Packet::new_checked(payload)
+					.map_err(|_| Errno::Inval)?
+					.fill_checksum(&src_addr, &dst_addr);
+				packet
+			}
+			_ => return Err(Errno::Afnosupport),
+		};
+
+		if packet.len() > MAX_PACKET_SIZE {
+			return Err(Errno::Msgsize);
+		}
+		Ok(packet)
+	}
+
+	async fn send_to(&self, message: &[u8], dst_addr: IpAddress) -> io::Result<usize> {
+		let packet = self.build_packet(message, dst_addr)?;
+		future::poll_fn(|cx| {
+			self.with(|socket| {
+				if !socket.is_open() {
+					Poll::Ready(Err(Errno::Io))
+				} else if socket.can_send() {
+					Poll::Ready(
+						socket
+							.send_slice(&packet)
+							.map(|()| message.len())
+							.map_err(|_| Errno::Io),
+					)
+				} else if self.nonblocking {
+					Poll::Ready(Err(Errno::Again))
+				} else {
+					socket.register_send_waker(cx.waker());
+					Poll::Pending
+				}
+			})
+		})
+		.await
+	}
+
+	/// Receives the next packet from the peer of `connect` or from any host.
+	///
+	/// The packet is truncated, if it does not fit into `buffer`.
+	async fn recv_from(&self, buffer: &mut [MaybeUninit<u8>]) -> io::Result<(usize, IpAddress)> {
+		future::poll_fn(|cx| {
+			self.with(|socket| {
+				loop {
+					if !socket.is_open() {
+						return Poll::Ready(Err(Errno::Io));
+					}
+					if !socket.can_recv() {
+						if self.nonblocking {
+							return Poll::Ready(Err(Errno::Again));
+						}
+						socket.register_recv_waker(cx.waker());
+						return Poll::Pending;
+					}
+
+					let packet = socket.recv().map_err(|_| Errno::Io)?;
+					let (src_addr, data) = match self.ip_version {
+						IpVersion::Ipv4 => {
+							let Ok(ipv4) = Ipv4Packet::new_checked(packet) else {
+								continue;
+							};
+							(IpAddress::Ipv4(ipv4.src_addr()), packet)
+						}
+						IpVersion::Ipv6 => {
+							let Ok(ipv6) = Ipv6Packet::new_checked(packet) else {
+								continue;
+							};
+							(IpAddress::Ipv6(ipv6.src_addr()), &packet[IPV6_HEADER_LEN..])
+						}
+					};
+
+					if self.remote_addr.is_none_or(|addr| addr == src_addr) {
+						let len = data.len().min(buffer.len());
+						buffer[..len].write_copy_of_slice(&data[..len]);
+						return Poll::Ready(Ok((len, src_addr)));
+					}
+				}
+			})
+		})
+		.await
+	}
+}
+
+#[async_trait]
+impl ObjectInterface for Socket {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		future::poll_fn(|cx| {
+			self.with(|socket| {
+				let ret = if socket.is_open() {
+					let mut avail = PollEvent::empty();
+
+					if socket.can_send() {
+						avail.insert(
+							PollEvent::POLLOUT | PollEvent::POLLWRNORM | PollEvent::POLLWRBAND,
+						);
+					}
+
+					if socket.can_recv() {
+						avail.insert(
+							PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND,
+						);
+					}
+
+					event & avail
+				} else {
+					PollEvent::POLLNVAL
+				};
+
+				if ret.is_empty() {
+					if event.intersects(
+						PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND,
+					) {
+						socket.register_recv_waker(cx.waker());
+					}
+
+					if event.intersects(
+						PollEvent::POLLOUT | PollEvent::POLLWRNORM | PollEvent::POLLWRBAND,
+					) {
+						socket.register_send_waker(cx.waker());
+					}
+
+					Poll::Pending
+				} else {
+					Poll::Ready(Ok(ret))
+				}
+			})
+		})
+		.await
+	}
+
+	async fn bind(&mut self, endpoint: ListenEndpoint) -> io::Result<()> {
+		#[allow(irrefutable_let_patterns)]
+		if let ListenEndpoint::Ip(endpoint) = endpoint {
+			if let Some(addr) = endpoint.addr {
+				self.check_version(addr)?;
+				if !addr.is_unspecified() {
+					self.local_addr = Some(addr);
+				}
+			}
+			Ok(())
+		} else {
+			Err(Errno::Io)
+		}
+	}
+
+	async fn connect(&mut self, endpoint: Endpoint) -> io::Result<()> {
+		#[allow(irrefutable_let_patterns)]
+		if let Endpoint::Ip(endpoint) = endpoint {
+			self.check_version(endpoint.addr)?;
+			self.remote_addr = Some(endpoint.addr);
+			Ok(())
+		} else {
+			Err(Errno::Io)
+		}
+	}
+
+	async fn sendto(&self, buf: &[u8], endpoint: Endpoint) -> io::Result<usize> {
+		#[allow(irrefutable_let_patterns)]
+		if let Endpoint::Ip(endpoint) = endpoint {
+			self.send_to(buf, endpoint.addr).await
+		} else {
+			Err(Errno::Io)
+		}
+	}
+
+	async fn recvfrom(&self, buffer: &mut [MaybeUninit<u8>]) -> io::Result<(usize, Endpoint)> {
+		let (len, src_addr) = self.recv_from(buffer).await?;
+		Ok((len, Endpoint::Ip(IpEndpoint::new(src_addr, 0))))
+	}
+
+	async fn read(&self, buffer: &mut [u8]) -> io::Result<usize> {
+		let buffer =
+			unsafe { core::slice::from_raw_parts_mut(buffer.as_mut_ptr().cast(), buffer.len()) };
+		let (len, _) = self.recv_from(buffer).await?;
+		Ok(len)
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		let dst_addr = self.remote_addr.ok_or(Errno::Destaddrreq)?;
+		self.send_to(buf, dst_addr).await
+	}
+
+	async fn setsockopt(&mut self, opt: SocketOption, optval: SocketOptionValue) -> io::Result<()> {
+		match (opt, optval) {
+			// Like on Linux, `SO_REUSEADDR` is accepted, but has no effect.
+			(SocketOption::ReuseAddr, _) => Ok(()),
+			(SocketOption::SendTimeout, SocketOptionValue::Duration(timeout)) => {
+				self.send_timeout = timeout;
+				Ok(())
+			}
+			(SocketOption::RecvTimeout, SocketOptionValue::Duration(timeout)) => {
+				self.recv_timeout = timeout;
+				Ok(())
+			}
+			_ => Err(Errno::Noprotoopt),
+		}
+	}
+
+	async fn getsockopt(&self, opt: SocketOption) -> io::Result<SocketOptionValue> {
+		match opt {
+			SocketOption::SendTimeout => Ok(SocketOptionValue::Duration(self.send_timeout)),
+			SocketOption::RecvTimeout => Ok(SocketOptionValue::Duration(self.recv_timeout)),
+			SocketOption::Error => Ok(SocketOptionValue::Int(0)),
+			_ => Err(Errno::Noprotoopt),
+		}
+	}
+
+	async fn status_flags(&self) -> io::Result<fd::StatusFlags> {
+		let status_flags = if self.nonblocking {
+			fd::StatusFlags::O_NONBLOCK
+		} else {
+			fd::StatusFlags::empty()
+		};
+
+		Ok(status_flags)
+	}
+
+	async fn set_status_flags(&mut self, status_flags: fd::StatusFlags) -> io::Result<()> {
+		self.nonblocking = status_flags.contains(fd::StatusFlags::O_NONBLOCK);
+		Ok(())
+	}
+
+	async fn getsockname(&self) -> io::Result<Option<Endpoint>> {
+		let addr = self.local_addr.unwrap_or(match self.ip_version {
+			IpVersion::Ipv4 => IpAddress::Ipv4(Ipv4Address::UNSPECIFIED),
+			IpVersion::Ipv6 => IpAddress::Ipv6(Ipv6Address::UNSPECIFIED),
+		});
+		Ok(Some(Endpoint::Ip(IpEndpoint::new(addr, 0))))
+	}
+
+	async fn getpeername(&self) -> io::Result<Option<Endpoint>> {
+		Ok(self
+			.remote_addr
+			.map(|addr| Endpoint::Ip(IpEndpoint::new(addr, 0))))
+	}
+
+	async fn set_hop_limit(&mut self, hop_limit: u8) -> io::Result<()> {
+		self.hop_limit = hop_limit;
+		Ok(())
+	}
+}
+
+impl Drop for Socket {
+	fn drop(&mut self) {
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().unwrap();
+		nic.destroy_socket(self.handle);
+	}
+}
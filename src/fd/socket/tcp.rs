@@ -2,21 +2,24 @@ use alloc::boxed::Box;
 use alloc::collections::BTreeSet;
 use alloc::sync::Arc;
 use core::future;
-use core::sync::atomic::{AtomicU16, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
 use core::task::Poll;
 
 use async_trait::async_trait;
 use smoltcp::iface;
 use smoltcp::socket::tcp;
 use smoltcp::time::Duration;
-use smoltcp::wire::{IpEndpoint, Ipv4Address, Ipv6Address};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address};
 
 use crate::errno::Errno;
-use crate::executor::block_on;
-use crate::executor::network::{Handle, NIC};
-use crate::fd::{self, Endpoint, ListenEndpoint, ObjectInterface, PollEvent, SocketOption};
+use crate::executor::network::{Handle, NIC, now};
+use crate::executor::{block_on, socket_buffer};
+use crate::fd::{
+	self, Endpoint, ListenEndpoint, ListenQueue, ObjectInterface, PollEvent, SocketOption,
+	SocketOptionValue,
+};
 use crate::syscalls::socket::Af;
-use crate::{DEFAULT_KEEP_ALIVE_INTERVAL, io};
+use crate::{DEFAULT_KEEP_ALIVE_COUNT, DEFAULT_KEEP_ALIVE_INTERVAL, io};
 
 /// further receives will be disallowed
 pub const SHUT_RD: i32 = 0;
@@ -27,18 +30,73 @@ pub const SHUT_RDWR: i32 = 2;
 /// The default queue size for incoming connections
 pub const DEFAULT_BACKLOG: i32 = 128;
 
-fn get_ephemeral_port() -> u16 {
+pub(crate) fn get_ephemeral_port() -> u16 {
 	static LOCAL_ENDPOINT: AtomicU16 = AtomicU16::new(49152);
 
 	LOCAL_ENDPOINT.fetch_add(1, Ordering::SeqCst)
 }
 
+/// Parameters of the keep-alive probes
+///
+/// smoltcp sends a probe, whenever the connection has been idle for a given
+/// time, and drops a connection, from which nothing has been received for a
+/// given time. Therefore, the first probe and every further, unanswered
+/// probe is sent after the idle time. A connection, which stays silent for
+/// the idle time and `count` intervals, is dropped.
+#[derive(Debug, Copy, Clone)]
+struct KeepAlive {
+	/// Idle time of the connection before the first probe (`TCP_KEEPIDLE`)
+	idle: Duration,
+	/// Time between unanswered probes (`TCP_KEEPINTVL`)
+	interval: Duration,
+	/// Unanswered probes, before the connection is dropped (`TCP_KEEPCNT`)
+	count: u32,
+}
+
+impl KeepAlive {
+	/// Maximum idle time and interval in seconds, like on Linux
+	const MAX_SECS: i32 = 32767;
+	/// Maximum number of probes, like on Linux
+	const MAX_COUNT: i32 = 127;
+
+	const fn new() -> Self {
+		Self {
+			idle: Duration::from_millis(DEFAULT_KEEP_ALIVE_INTERVAL),
+			interval: Duration::from_millis(DEFAULT_KEEP_ALIVE_INTERVAL),
+			count: DEFAULT_KEEP_ALIVE_COUNT,
+		}
+	}
+
+	/// Enables or disables the probes of `socket`.
+	fn apply(&self, socket: &mut tcp::Socket<'_>, enabled: bool) {
+		if enabled {
+			socket.set_keep_alive(Some(self.idle));
+			socket.set_timeout(Some(self.idle + self.interval * self.count));
+		} else {
+			socket.set_keep_alive(None);
+			socket.set_timeout(None);
+		}
+	}
+}
+
 #[derive(Debug)]
 pub struct Socket {
 	handle: BTreeSet<Handle>,
 	endpoint: IpEndpoint,
 	is_nonblocking: bool,
 	is_listen: bool,
+	/// Binding to an address in use has been allowed. smoltcp does not
+	/// reject such bindings, so the flag is only reported.
+	reuse_addr: bool,
+	/// Time, which dropping the socket waits for the connection to close,
+	/// before it is reset
+	linger: Option<core::time::Duration>,
+	send_timeout: Option<core::time::Duration>,
+	recv_timeout: Option<core::time::Duration>,
+	keep_alive: KeepAlive,
+	/// A non-blocking connect has been started, whose result has not been
+	/// reported by `SO_ERROR` or a further connect yet
+	connecting: AtomicBool,
 }
 
 impl Socket {
@@ -59,6 +117,12 @@ impl Socket {
 			endpoint,
 			is_nonblocking: false,
 			is_listen: false,
+			reuse_addr: false,
+			linger: None,
+			send_timeout: None,
+			recv_timeout: None,
+			keep_alive: KeepAlive::new(),
+			connecting: AtomicBool::new(false),
 		}
 	}
 
@@ -68,10 +132,16 @@ impl Socket {
 		f(nic.get_mut_socket::<tcp::Socket<'_>>(*self.handle.first().unwrap()))
 	}
 
-	fn with_context<R>(&self, f: impl FnOnce(&mut tcp::Socket<'_>, &mut iface::Context) -> R) -> R {
+	/// Calls `f` with the context of the interface, which reaches `remote`.
+	fn with_context<R>(
+		&self,
+		remote: IpAddress,
+		f: impl FnOnce(&mut tcp::Socket<'_>, &mut iface::Context) -> R,
+	) -> R {
 		let mut guard = NIC.lock();
 		let nic = guard.as_nic_mut().unwrap();
-		let (s, cx) = nic.get_socket_and_context::<tcp::Socket<'_>>(*self.handle.first().unwrap());
+		let (s, cx) =
+			nic.get_socket_and_context::<tcp::Socket<'_>>(*self.handle.first().unwrap(), remote);
 		f(s, cx)
 	}
 
@@ -110,6 +180,102 @@ impl Socket {
 		})
 		.await
 	}
+
+	/// Resets the connections and sends the resets, before the handles are destroyed.
+	fn abort(&self) {
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().unwrap();
+
+		for handle in self.handle.iter() {
+			nic.get_mut_socket::<tcp::Socket<'_>>(*handle).abort();
+		}
+		nic.poll_common(now());
+	}
+
+	/// Replaces the unconnected socket by one with other buffer capacities.
+	fn set_capacity(&mut self, opt: SocketOption, size: i32) -> io::Result<()> {
+		let size = usize::try_from(size).map_err(|_| Errno::Inval)?;
+		let limits = socket_buffer::limits();
+		if self.is_listen {
+			return Err(Errno::Isconn);
+		}
+
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().unwrap();
+		let old_handle = *self.handle.first().unwrap();
+		let socket = nic.get_mut_socket::<tcp::Socket<'_>>(old_handle);
+		if socket.state() != tcp::State::Closed {
+			return Err(Errno::Isconn);
+		}
+
+		let (rx_capacity, tx_capacity) = if opt == SocketOption::RecvBuffer {
+			(limits.recv_capacity(size), socket.send_capacity())
+		} else {
+			(socket.recv_capacity(), limits.send_capacity(size))
+		};
+		let nagle_enabled = socket.nagle_enabled();
+		let keep_alive = socket.keep_alive().is_some();
+
+		let handle = nic
+			.create_tcp_handle_with_capacity(rx_capacity, tx_capacity)
+			.map_err(|()| Errno::Nobufs)?;
+		let socket = nic.get_mut_socket::<tcp::Socket<'_>>(handle);
+		socket.set_nagle_enabled(nagle_enabled);
+		self.keep_alive.apply(socket, keep_alive);
+		nic.destroy_socket(old_handle);
+
+		self.handle.clear();
+		self.handle.insert(handle);
+		Ok(())
+	}
+
+	/// Returns the error of a non-blocking connect, which has failed, and
+	/// clears it. A connect, which is still in progress, has no error yet.
+	///
+	/// smoltcp closes the socket on a reset as well as on a timeout, so that
+	/// both are reported as `ECONNREFUSED`.
+	fn take_connect_error(&self) -> Option<Errno> {
+		if !self.connecting.load(Ordering::Relaxed) {
+			return None;
+		}
+
+		match self.with(|socket| socket.state()) {
+			tcp::State::SynSent | tcp::State::SynReceived => None,
+			tcp::State::Closed => {
+				self.connecting.store(false, Ordering::Relaxed);
+				Some(Errno::Connrefused)
+			}
+			_ => {
+				self.connecting.store(false, Ordering::Relaxed);
+				None
+			}
+		}
+	}
+
+	/// Changes a parameter of the keep-alive probes by `f` and applies it to
+	/// the handles, whose probes are enabled.
+	fn set_keep_alive_param(
+		&mut self,
+		value: i32,
+		max: i32,
+		f: impl FnOnce(&mut KeepAlive, u32),
+	) -> io::Result<()> {
+		if !(1..=max).contains(&value) {
+			return Err(Errno::Inval);
+		}
+		f(&mut self.keep_alive, value.try_into().unwrap());
+
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().unwrap();
+		for i in self.handle.iter() {
+			let socket = nic.get_mut_socket::<tcp::Socket<'_>>(*i);
+			if socket.keep_alive().is_some() {
+				self.keep_alive.apply(socket, true);
+			}
+		}
+
+		Ok(())
+	}
 }
 
 #[async_trait]
@@ -127,7 +293,12 @@ impl ObjectInterface for Socket {
 
 					let ret = event & available;
 
-					if ret.is_empty() {
+					if socket.state() == tcp::State::Closed
+						&& self.connecting.load(Ordering::Relaxed)
+					{
+						// A non-blocking connect has failed.
+						Poll::Ready(Ok(ret | PollEvent::POLLERR | PollEvent::POLLHUP))
+					} else if ret.is_empty() {
 						Poll::Ready(Ok(PollEvent::POLLHUP))
 					} else {
 						Poll::Ready(Ok(ret))
@@ -281,12 +452,34 @@ impl ObjectInterface for Socket {
 	async fn connect(&mut self, endpoint: Endpoint) -> io::Result<()> {
 		#[allow(irrefutable_let_patterns)]
 		if let Endpoint::Ip(endpoint) = endpoint {
-			self.with_context(|socket, cx| socket.connect(cx, endpoint, get_ephemeral_port()))
-				.map_err(|_| Errno::Io)?;
+			if let Some(err) = self.take_connect_error() {
+				return Err(err);
+			}
+			match self.with(|socket| socket.state()) {
+				tcp::State::SynSent | tcp::State::SynReceived => {
+					return Err(Errno::Already);
+				}
+				tcp::State::Closed | tcp::State::Listen => {}
+				_ => return Err(Errno::Isconn),
+			}
+
+			self.with_context(endpoint.addr, |socket, cx| {
+				socket.connect(cx, endpoint, get_ephemeral_port())
+			})
+			.map_err(|_| Errno::Io)?;
+
+			// The completion is reported by the writability of the socket and
+			// the result by `SO_ERROR`.
+			if self.is_nonblocking {
+				self.connecting.store(true, Ordering::Relaxed);
+				return Err(Errno::Inprogress);
+			}
 
 			future::poll_fn(|cx| {
 				self.with(|socket| match socket.state() {
-					tcp::State::Closed | tcp::State::TimeWait => Poll::Ready(Err(Errno::Fault)),
+					tcp::State::Closed | tcp::State::TimeWait => {
+						Poll::Ready(Err(Errno::Connrefused))
+					}
 					tcp::State::Listen => Poll::Ready(Err(Errno::Io)),
 					tcp::State::SynSent | tcp::State::SynReceived => {
 						socket.register_send_waker(cx.waker());
@@ -308,6 +501,18 @@ impl ObjectInterface for Socket {
 			self.listen(DEFAULT_BACKLOG).await?;
 		}
 
+		if self.handle.is_empty() {
+			// The queue could not be refilled, because the socket buffers
+			// have exceeded their budget.
+			let mut guard = NIC.lock();
+			let nic = guard.as_nic_mut().map_err(|_| Errno::Io)?;
+			let handle = nic.create_tcp_handle().map_err(|()| Errno::Nobufs)?;
+			self.handle.insert(handle);
+			nic.get_mut_socket::<tcp::Socket<'_>>(handle)
+				.listen(self.endpoint.port)
+				.map_err(|_| Errno::Io)?;
+		}
+
 		let connection_handle = future::poll_fn(|cx| {
 			let mut guard = NIC.lock();
 			let nic = guard.as_nic_mut().unwrap();
@@ -341,16 +546,20 @@ impl ObjectInterface for Socket {
 		let mut guard = NIC.lock();
 		let nic = guard.as_nic_mut().map_err(|_| Errno::Io)?;
 		let socket = nic.get_mut_socket::<tcp::Socket<'_>>(connection_handle);
-		socket.set_keep_alive(Some(Duration::from_millis(DEFAULT_KEEP_ALIVE_INTERVAL)));
+		self.keep_alive.apply(socket, true);
 		let endpoint = Endpoint::Ip(socket.remote_endpoint().unwrap());
 		let nagle_enabled = socket.nagle_enabled();
-
-		// fill up queue for pending connections
-		let new_handle = nic.create_tcp_handle().unwrap();
-		self.handle.insert(new_handle);
-		let socket = nic.get_mut_socket::<tcp::Socket<'_>>(new_handle);
-		socket.set_nagle_enabled(nagle_enabled);
-		socket.listen(self.endpoint.port).map_err(|_| Errno::Io)?;
+		let rx_capacity = socket.recv_capacity();
+		let tx_capacity = socket.send_capacity();
+
+		// fill up queue for pending connections, as far as the budget of the
+		// socket buffers allows
+		if let Ok(new_handle) = nic.create_tcp_handle_with_capacity(rx_capacity, tx_capacity) {
+			self.handle.insert(new_handle);
+			let socket = nic.get_mut_socket::<tcp::Socket<'_>>(new_handle);
+			socket.set_nagle_enabled(nagle_enabled);
+			socket.listen(self.endpoint.port).map_err(|_| Errno::Io)?;
+		}
 
 		let mut handle = BTreeSet::new();
 		handle.insert(connection_handle);
@@ -360,6 +569,12 @@ impl ObjectInterface for Socket {
 			endpoint: self.endpoint,
 			is_nonblocking: self.is_nonblocking,
 			is_listen: false,
+			reuse_addr: self.reuse_addr,
+			linger: self.linger,
+			send_timeout: self.send_timeout,
+			recv_timeout: self.recv_timeout,
+			keep_alive: self.keep_alive,
+			connecting: AtomicBool::new(false),
 		};
 
 		Ok((Arc::new(async_lock::RwLock::new(socket)), endpoint))
@@ -384,7 +599,13 @@ impl ObjectInterface for Socket {
 	}
 
 	async fn listen(&mut self, backlog: i32) -> io::Result<()> {
-		let nagle_enabled = self.with(|socket| socket.nagle_enabled());
+		let (nagle_enabled, rx_capacity, tx_capacity) = self.with(|socket| {
+			(
+				socket.nagle_enabled(),
+				socket.recv_capacity(),
+				socket.send_capacity(),
+			)
+		});
 		let mut guard = NIC.lock();
 		let nic = guard.as_nic_mut().unwrap();
 
@@ -403,7 +624,9 @@ impl ObjectInterface for Socket {
 		self.is_listen = true;
 
 		for _ in 1..backlog {
-			let handle = nic.create_tcp_handle().unwrap();
+			let handle = nic
+				.create_tcp_handle_with_capacity(rx_capacity, tx_capacity)
+				.map_err(|()| Errno::Nobufs)?;
 
 			let s = nic.get_mut_socket::<tcp::Socket<'_>>(handle);
 			s.set_nagle_enabled(nagle_enabled);
@@ -415,32 +638,128 @@ impl ObjectInterface for Socket {
 		Ok(())
 	}
 
-	async fn setsockopt(&self, opt: SocketOption, optval: bool) -> io::Result<()> {
-		if opt == SocketOption::TcpNoDelay {
-			let mut guard = NIC.lock();
-			let nic = guard.as_nic_mut().unwrap();
+	/// Each handle of a listening socket either listens or holds a connection,
+	/// which waits to be accepted. smoltcp answers connection attempts to a
+	/// full queue with a reset, which the socket does not notice. Therefore,
+	/// no overflows are counted.
+	async fn listen_queue(&self) -> io::Result<ListenQueue> {
+		if !self.is_listen {
+			return Err(Errno::Inval);
+		}
+
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().map_err(|_| Errno::Io)?;
+		let depth = self
+			.handle
+			.iter()
+			.filter(|handle| nic.get_mut_socket::<tcp::Socket<'_>>(**handle).is_active())
+			.count();
+
+		Ok(ListenQueue {
+			depth,
+			backlog: self.handle.len(),
+			overflows: 0,
+		})
+	}
+
+	async fn setsockopt(&mut self, opt: SocketOption, optval: SocketOptionValue) -> io::Result<()> {
+		match (opt, optval) {
+			(SocketOption::TcpNoDelay, SocketOptionValue::Flag(nodelay)) => {
+				let mut guard = NIC.lock();
+				let nic = guard.as_nic_mut().unwrap();
+
+				for i in self.handle.iter() {
+					let socket = nic.get_mut_socket::<tcp::Socket<'_>>(*i);
+					socket.set_nagle_enabled(!nodelay);
+				}
 
-			for i in self.handle.iter() {
-				let socket = nic.get_mut_socket::<tcp::Socket<'_>>(*i);
-				socket.set_nagle_enabled(optval);
+				Ok(())
 			}
+			(SocketOption::KeepAlive, SocketOptionValue::Flag(keep_alive)) => {
+				let mut guard = NIC.lock();
+				let nic = guard.as_nic_mut().unwrap();
 
-			Ok(())
-		} else {
-			Err(Errno::Inval)
+				for i in self.handle.iter() {
+					let socket = nic.get_mut_socket::<tcp::Socket<'_>>(*i);
+					self.keep_alive.apply(socket, keep_alive);
+				}
+
+				Ok(())
+			}
+			(SocketOption::TcpKeepIdle, SocketOptionValue::Int(secs)) => {
+				self.set_keep_alive_param(secs, KeepAlive::MAX_SECS, |keep_alive, secs| {
+					keep_alive.idle = Duration::from_secs(secs.into());
+				})
+			}
+			(SocketOption::TcpKeepInterval, SocketOptionValue::Int(secs)) => self
+				.set_keep_alive_param(secs, KeepAlive::MAX_SECS, |keep_alive, secs| {
+					keep_alive.interval = Duration::from_secs(secs.into());
+				}),
+			(SocketOption::TcpKeepCount, SocketOptionValue::Int(count)) => self
+				.set_keep_alive_param(count, KeepAlive::MAX_COUNT, |keep_alive, count| {
+					keep_alive.count = count;
+				}),
+			(SocketOption::ReuseAddr, SocketOptionValue::Flag(reuse_addr)) => {
+				self.reuse_addr = reuse_addr;
+				Ok(())
+			}
+			(SocketOption::Linger, SocketOptionValue::Duration(linger)) => {
+				self.linger = linger;
+				Ok(())
+			}
+			(SocketOption::SendBuffer | SocketOption::RecvBuffer, SocketOptionValue::Int(size)) => {
+				self.set_capacity(opt, size)
+			}
+			(SocketOption::SendTimeout, SocketOptionValue::Duration(timeout)) => {
+				self.send_timeout = timeout;
+				Ok(())
+			}
+			(SocketOption::RecvTimeout, SocketOptionValue::Duration(timeout)) => {
+				self.recv_timeout = timeout;
+				Ok(())
+			}
+			_ => Err(Errno::Noprotoopt),
 		}
 	}
 
-	async fn getsockopt(&self, opt: SocketOption) -> io::Result<bool> {
-		if opt == SocketOption::TcpNoDelay {
-			let mut guard = NIC.lock();
-			let nic = guard.as_nic_mut().unwrap();
-			let socket = nic.get_mut_socket::<tcp::Socket<'_>>(*self.handle.first().unwrap());
+	async fn getsockopt(&self, opt: SocketOption) -> io::Result<SocketOptionValue> {
+		let value = match opt {
+			SocketOption::TcpNoDelay => {
+				SocketOptionValue::Flag(!self.with(|socket| socket.nagle_enabled()))
+			}
+			SocketOption::KeepAlive => {
+				SocketOptionValue::Flag(self.with(|socket| socket.keep_alive().is_some()))
+			}
+			SocketOption::TcpKeepIdle => {
+				SocketOptionValue::Int(self.keep_alive.idle.secs().try_into().unwrap())
+			}
+			SocketOption::TcpKeepInterval => {
+				SocketOptionValue::Int(self.keep_alive.interval.secs().try_into().unwrap())
+			}
+			SocketOption::TcpKeepCount => {
+				SocketOptionValue::Int(self.keep_alive.count.try_into().unwrap())
+			}
+			SocketOption::ReuseAddr => SocketOptionValue::Flag(self.reuse_addr),
+			SocketOption::Linger => SocketOptionValue::Duration(self.linger),
+			SocketOption::SendBuffer => SocketOptionValue::Int(
+				self.with(|socket| socket.send_capacity())
+					.try_into()
+					.unwrap(),
+			),
+			SocketOption::RecvBuffer => SocketOptionValue::Int(
+				self.with(|socket| socket.recv_capacity())
+					.try_into()
+					.unwrap(),
+			),
+			SocketOption::SendTimeout => SocketOptionValue::Duration(self.send_timeout),
+			SocketOption::RecvTimeout => SocketOptionValue::Duration(self.recv_timeout),
+			SocketOption::Error => {
+				SocketOptionValue::Int(self.take_connect_error().map_or(0, i32::from))
+			}
+			SocketOption::Timestamp | SocketOption::PacketInfo => return Err(Errno::Noprotoopt),
+		};
 
-			Ok(socket.nagle_enabled())
-		} else {
-			Err(Errno::Inval)
-		}
+		Ok(value)
 	}
 
 	async fn shutdown(&self, how: i32) -> io::Result<()> {
@@ -470,7 +789,17 @@ impl ObjectInterface for Socket {
 
 impl Drop for Socket {
 	fn drop(&mut self) {
-		let _ = block_on(self.close(), None);
+		match self.linger {
+			Some(linger) if linger.is_zero() => self.abort(),
+			Some(linger) => {
+				if block_on(self.close(), Some(linger)) == Err(Errno::Time) {
+					self.abort();
+				}
+			}
+			None => {
+				let _ = block_on(self.close(), None);
+			}
+		}
 
 		let mut guard = NIC.lock();
 		for h in self.handle.iter() {
@@ -1,19 +1,28 @@
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 use core::future;
 use core::mem::MaybeUninit;
 use core::task::Poll;
+use core::time::Duration;
 
 use async_trait::async_trait;
 use smoltcp::socket::udp;
 use smoltcp::socket::udp::UdpMetadata;
-use smoltcp::wire::{IpEndpoint, Ipv4Address, Ipv6Address};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address};
 
 use crate::errno::Errno;
-use crate::executor::block_on;
 use crate::executor::network::{Handle, NIC};
-use crate::fd::{self, Endpoint, ListenEndpoint, ObjectInterface, PollEvent};
+use crate::executor::{block_on, socket_buffer};
+use crate::fd::{
+	self, Endpoint, ListenEndpoint, MulticastOption, ObjectInterface, PollEvent, ReceivedMessage,
+	SocketOption, SocketOptionValue,
+};
 use crate::io;
 use crate::syscalls::socket::Af;
+use crate::time::timeval;
+
+/// Default hop limit of multicast datagrams, which keeps them on the link
+const DEFAULT_MULTICAST_HOP_LIMIT: u8 = 1;
 
 #[derive(Debug)]
 pub struct Socket {
@@ -21,6 +30,18 @@ pub struct Socket {
 	nonblocking: bool,
 	local_endpoint: IpEndpoint,
 	remote_endpoint: Option<IpEndpoint>,
+	/// Multicast groups, which have been joined through this socket
+	multicast_groups: Vec<IpAddress>,
+	multicast_hop_limit: u8,
+	/// Binding to an address in use has been allowed. smoltcp does not
+	/// reject such bindings, so the flag is only reported.
+	reuse_addr: bool,
+	send_timeout: Option<Duration>,
+	recv_timeout: Option<Duration>,
+	/// `recvmsg` reports the time of reception (`SO_TIMESTAMP`).
+	timestamp: bool,
+	/// `recvmsg` reports the destination address (`IP_PKTINFO`).
+	packet_info: bool,
 }
 
 impl Socket {
@@ -38,6 +59,13 @@ impl Socket {
 			nonblocking: false,
 			local_endpoint,
 			remote_endpoint: None,
+			multicast_groups: Vec::new(),
+			multicast_hop_limit: DEFAULT_MULTICAST_HOP_LIMIT,
+			reuse_addr: false,
+			send_timeout: None,
+			recv_timeout: None,
+			timestamp: false,
+			packet_info: false,
 		}
 	}
 
@@ -52,11 +80,44 @@ impl Socket {
 		Ok(())
 	}
 
+	/// Replaces the unbound socket by one with other buffer capacities.
+	fn set_capacity(&mut self, opt: SocketOption, size: i32) -> io::Result<()> {
+		let size = usize::try_from(size).map_err(|_| Errno::Inval)?;
+		let limits = socket_buffer::limits();
+
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().unwrap();
+		let socket = nic.get_mut_socket::<udp::Socket<'_>>(self.handle);
+		if socket.is_open() {
+			return Err(Errno::Isconn);
+		}
+
+		let (rx_capacity, tx_capacity) = if opt == SocketOption::RecvBuffer {
+			(limits.recv_capacity(size), socket.payload_send_capacity())
+		} else {
+			(socket.payload_recv_capacity(), limits.send_capacity(size))
+		};
+		let handle = nic
+			.create_udp_handle_with_capacity(rx_capacity, tx_capacity)
+			.map_err(|()| Errno::Nobufs)?;
+		nic.destroy_socket(self.handle);
+		self.handle = handle;
+		Ok(())
+	}
+
 	async fn write_with_meta(&self, buffer: &[u8], meta: &UdpMetadata) -> io::Result<usize> {
 		future::poll_fn(|cx| {
 			self.with(|socket| {
 				if socket.is_open() {
 					if socket.can_send() {
+						// The hop limit is applied, when the datagram is
+						// dispatched, which normally happens right away.
+						let hop_limit = meta
+							.endpoint
+							.addr
+							.is_multicast()
+							.then_some(self.multicast_hop_limit);
+						socket.set_hop_limit(hop_limit);
 						Poll::Ready(
 							socket
 								.send_slice(buffer, *meta)
@@ -188,6 +249,58 @@ impl ObjectInterface for Socket {
 		.map(|(len, endpoint)| (len, Endpoint::Ip(endpoint)))
 	}
 
+	async fn recvmsg(&self, buffer: &mut [u8]) -> io::Result<ReceivedMessage> {
+		let (mut message, local_address) = future::poll_fn(|cx| {
+			self.with(|socket| {
+				if !socket.is_open() {
+					return Poll::Ready(Err(Errno::Io));
+				}
+
+				while socket.can_recv() {
+					let Ok((data, meta)) = socket.recv() else {
+						return Poll::Ready(Err(Errno::Io));
+					};
+					// Drop the datagrams of other senders than the connected peer.
+					if self.remote_endpoint.is_some_and(|ep| meta.endpoint != ep) {
+						continue;
+					}
+
+					// Unlike `recvfrom`, truncate the datagram, if it does not fit.
+					let len = data.len().min(buffer.len());
+					buffer[..len].copy_from_slice(&data[..len]);
+					let message = ReceivedMessage {
+						len,
+						endpoint: Some(Endpoint::Ip(meta.endpoint)),
+						truncated: len < data.len(),
+						timestamp: self.timestamp.then(|| {
+							timeval::from_usec(
+								crate::arch::kernel::systemtime::now_micros()
+									.try_into()
+									.unwrap(),
+							)
+						}),
+						packet_info: None,
+					};
+					let local_address = meta.local_address.unwrap_or(self.local_endpoint.addr);
+					return Poll::Ready(Ok((message, local_address)));
+				}
+
+				socket.register_recv_waker(cx.waker());
+				Poll::Pending
+			})
+		})
+		.await?;
+
+		if self.packet_info {
+			let interface = NIC
+				.lock()
+				.as_nic_mut()
+				.map_or(0, |nic| nic.interface_of(local_address));
+			message.packet_info = Some((local_address, interface));
+		}
+		Ok(message)
+	}
+
 	async fn read(&self, buffer: &mut [u8]) -> io::Result<usize> {
 		future::poll_fn(|cx| {
 			self.with(|socket| {
@@ -246,11 +359,99 @@ impl ObjectInterface for Socket {
 	async fn getsockname(&self) -> io::Result<Option<Endpoint>> {
 		Ok(Some(Endpoint::Ip(self.local_endpoint)))
 	}
+
+	async fn set_multicast_option(&mut self, opt: MulticastOption) -> io::Result<()> {
+		match opt {
+			MulticastOption::AddMembership(group) => {
+				if self.multicast_groups.contains(&group) {
+					return Err(Errno::Addrinuse);
+				}
+				NIC.lock()
+					.as_nic_mut()
+					.map_err(|_| Errno::Netdown)?
+					.join_multicast_group(group)?;
+				self.multicast_groups.push(group);
+			}
+			MulticastOption::DropMembership(group) => {
+				let index = self
+					.multicast_groups
+					.iter()
+					.position(|addr| *addr == group)
+					.ok_or(Errno::Addrnotavail)?;
+				self.multicast_groups.swap_remove(index);
+				NIC.lock()
+					.as_nic_mut()
+					.map_err(|_| Errno::Netdown)?
+					.leave_multicast_group(group)?;
+			}
+			MulticastOption::HopLimit(hop_limit) => self.multicast_hop_limit = hop_limit,
+		}
+		Ok(())
+	}
+
+	async fn setsockopt(&mut self, opt: SocketOption, optval: SocketOptionValue) -> io::Result<()> {
+		match (opt, optval) {
+			(SocketOption::ReuseAddr, SocketOptionValue::Flag(reuse_addr)) => {
+				self.reuse_addr = reuse_addr;
+				Ok(())
+			}
+			(SocketOption::SendBuffer | SocketOption::RecvBuffer, SocketOptionValue::Int(size)) => {
+				self.set_capacity(opt, size)
+			}
+			(SocketOption::SendTimeout, SocketOptionValue::Duration(timeout)) => {
+				self.send_timeout = timeout;
+				Ok(())
+			}
+			(SocketOption::RecvTimeout, SocketOptionValue::Duration(timeout)) => {
+				self.recv_timeout = timeout;
+				Ok(())
+			}
+			(SocketOption::Timestamp, SocketOptionValue::Flag(timestamp)) => {
+				self.timestamp = timestamp;
+				Ok(())
+			}
+			(SocketOption::PacketInfo, SocketOptionValue::Flag(packet_info)) => {
+				self.packet_info = packet_info;
+				Ok(())
+			}
+			_ => Err(Errno::Noprotoopt),
+		}
+	}
+
+	async fn getsockopt(&self, opt: SocketOption) -> io::Result<SocketOptionValue> {
+		let value = match opt {
+			SocketOption::ReuseAddr => SocketOptionValue::Flag(self.reuse_addr),
+			SocketOption::SendBuffer => SocketOptionValue::Int(
+				self.with(|socket| socket.payload_send_capacity())
+					.try_into()
+					.unwrap(),
+			),
+			SocketOption::RecvBuffer => SocketOptionValue::Int(
+				self.with(|socket| socket.payload_recv_capacity())
+					.try_into()
+					.unwrap(),
+			),
+			SocketOption::SendTimeout => SocketOptionValue::Duration(self.send_timeout),
+			SocketOption::RecvTimeout => SocketOptionValue::Duration(self.recv_timeout),
+			SocketOption::Error => SocketOptionValue::Int(0),
+			SocketOption::Timestamp => SocketOptionValue::Flag(self.timestamp),
+			SocketOption::PacketInfo => SocketOptionValue::Flag(self.packet_info),
+			_ => return Err(Errno::Noprotoopt),
+		};
+
+		Ok(value)
+	}
 }
 
 impl Drop for Socket {
 	fn drop(&mut self) {
 		let _ = block_on(self.close(), None);
-		NIC.lock().as_nic_mut().unwrap().destroy_socket(self.handle);
+
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().unwrap();
+		for group in self.multicast_groups.drain(..) {
+			let _ = nic.leave_multicast_group(group);
+		}
+		nic.destroy_socket(self.handle);
 	}
 }
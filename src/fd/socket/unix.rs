@@ -0,0 +1,915 @@
+//! Sockets of the domain `AF_UNIX` for the communication within the kernel.
+//!
+//! Stream sockets are connected through a pair of byte buffers, one for each
+//! direction. Datagram sockets own a queue of messages, into which the other
+//! datagram sockets send.
+//!
+//! A socket is bound to a path of the file system or to an abstract name,
+//! which starts with a null byte. Binding to a path creates an empty file at
+//! the path, which keeps the path from being bound twice. As on Linux, the
+//! file remains after the socket has been closed and has to be removed, before
+//! the path can be bound again. Abstract names are released, when the socket
+//! is closed.
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::future;
+use core::mem::MaybeUninit;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+use async_trait::async_trait;
+use hermit_sync::InterruptTicketMutex;
+
+use crate::errno::Errno;
+use crate::fd::{
+	self, AccessPermission, Endpoint, ListenEndpoint, ListenQueue, ObjectInterface, OpenOption,
+	PollEvent, SocketOption, SocketOptionValue,
+};
+use crate::{fs, io};
+
+/// Capacity of each direction of a stream connection in bytes
+const STREAM_CAPACITY: usize = 0x40000;
+
+/// Maximum number of datagrams in the queue of a socket
+const DATAGRAM_QUEUE_LEN: usize = 128;
+
+/// Maximum size of a datagram in bytes
+const MAX_DATAGRAM_SIZE: usize = 0x10000;
+
+/// Maximum number of pending connections of a listening socket
+const MAX_BACKLOG: usize = 4096;
+
+/// Address of a socket of the domain `AF_UNIX`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum UnixAddress {
+	/// The socket is not bound to a name.
+	Unnamed,
+	/// Path of the file system, which is absolute for bound sockets
+	Path(String),
+	/// Abstract name without the leading null byte
+	Abstract(Vec<u8>),
+}
+
+/// Socket, to which a name is bound
+#[derive(Debug)]
+enum Binding {
+	Stream(Weak<Listener>),
+	Datagram(Weak<DatagramQueue>),
+}
+
+impl Binding {
+	fn as_ptr(&self) -> *const () {
+		match self {
+			Self::Stream(listener) => listener.as_ptr().cast(),
+			Self::Datagram(queue) => queue.as_ptr().cast(),
+		}
+	}
+
+	fn is_alive(&self) -> bool {
+		match self {
+			Self::Stream(listener) => listener.strong_count() > 0,
+			Self::Datagram(queue) => queue.strong_count() > 0,
+		}
+	}
+}
+
+/// Names of the bound sockets
+static NAMES: InterruptTicketMutex<BTreeMap<UnixAddress, Binding>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+
+/// Binds `address` and returns the name, under which the socket is registered.
+fn bind(address: UnixAddress, binding: Binding) -> io::Result<UnixAddress> {
+	let name = match address {
+		UnixAddress::Unnamed => return Err(Errno::Inval),
+		UnixAddress::Abstract(_) => address,
+		UnixAddress::Path(path) => {
+			let fd = fs::open(
+				&path,
+				OpenOption::O_CREAT | OpenOption::O_EXCL | OpenOption::O_WRONLY,
+				AccessPermission::S_IRWXU | AccessPermission::S_IRWXG | AccessPermission::S_IRWXO,
+			)
+			.map_err(|err| match err {
+				Errno::Exist => Errno::Addrinuse,
+				err => err,
+			})?;
+			fd::remove_object(fd)?;
+			UnixAddress::Path(fs::realpath(&path)?)
+		}
+	};
+
+	// A path, whose file has been removed, is taken over from the previous socket.
+	let mut names = NAMES.lock();
+	if matches!(name, UnixAddress::Abstract(_)) && names.get(&name).is_some_and(Binding::is_alive) {
+		return Err(Errno::Addrinuse);
+	}
+	names.insert(name.clone(), binding);
+	Ok(name)
+}
+
+/// Releases `name`, if it is still bound to the socket at `ptr`.
+fn unbind(name: &UnixAddress, ptr: *const ()) {
+	let mut names = NAMES.lock();
+	if names
+		.get(name)
+		.is_some_and(|binding| binding.as_ptr() == ptr)
+	{
+		names.remove(name);
+	}
+}
+
+/// Resolves `address` to the name, under which a socket may be registered.
+fn resolve(address: &UnixAddress) -> io::Result<UnixAddress> {
+	match address {
+		UnixAddress::Unnamed => Err(Errno::Inval),
+		UnixAddress::Path(path) => Ok(UnixAddress::Path(fs::realpath(path)?)),
+		UnixAddress::Abstract(_) => Ok(address.clone()),
+	}
+}
+
+fn lookup_listener(address: &UnixAddress) -> io::Result<(UnixAddress, Arc<Listener>)> {
+	let name = resolve(address)?;
+	let listener = match NAMES.lock().get(&name) {
+		Some(Binding::Stream(listener)) => listener.upgrade(),
+		Some(Binding::Datagram(_)) => return Err(Errno::Prototype),
+		None => None,
+	};
+	Ok((name, listener.ok_or(Errno::Connrefused)?))
+}
+
+fn lookup_datagram(address: &UnixAddress) -> io::Result<(UnixAddress, Weak<DatagramQueue>)> {
+	let name = resolve(address)?;
+	match NAMES.lock().get(&name) {
+		Some(Binding::Datagram(queue)) => Ok((name, queue.clone())),
+		Some(Binding::Stream(_)) => Err(Errno::Prototype),
+		None => Err(Errno::Connrefused),
+	}
+}
+
+/// Tasks, which wait for a change of state
+#[derive(Debug, Default)]
+struct WaitQueue(Vec<Waker>);
+
+impl WaitQueue {
+	fn register(&mut self, waker: &Waker) {
+		if !self.0.iter().any(|registered| registered.will_wake(waker)) {
+			self.0.push(waker.clone());
+		}
+	}
+
+	fn wake_all(&mut self) {
+		for waker in self.0.drain(..) {
+			waker.wake();
+		}
+	}
+}
+
+/// Copies the first bytes of `data` into `buf` and returns their number.
+fn copy_front(data: &VecDeque<u8>, buf: &mut [MaybeUninit<u8>]) -> usize {
+	let len = buf.len().min(data.len());
+	let (front, back) = data.as_slices();
+	let split = len.min(front.len());
+	buf[..split].write_copy_of_slice(&front[..split]);
+	buf[split..len].write_copy_of_slice(&back[..len - split]);
+	len
+}
+
+#[derive(Debug, Default)]
+struct ChannelState {
+	buffer: VecDeque<u8>,
+	/// The reading end has been closed or shut down.
+	read_closed: bool,
+	/// The writing end has been closed or shut down.
+	write_closed: bool,
+	readers: WaitQueue,
+	writers: WaitQueue,
+}
+
+/// One direction of a stream connection
+#[derive(Debug, Default)]
+struct Channel {
+	state: InterruptTicketMutex<ChannelState>,
+}
+
+impl Channel {
+	fn poll_read(
+		&self,
+		cx: &mut Context<'_>,
+		buf: &mut [MaybeUninit<u8>],
+		is_nonblocking: bool,
+	) -> Poll<io::Result<usize>> {
+		let mut state = self.state.lock();
+		if state.buffer.is_empty() {
+			if state.write_closed || state.read_closed || buf.is_empty() {
+				Poll::Ready(Ok(0))
+			} else if is_nonblocking {
+				Poll::Ready(Err(Errno::Again))
+			} else {
+				state.readers.register(cx.waker());
+				Poll::Pending
+			}
+		} else {
+			let len = copy_front(&state.buffer, buf);
+			state.buffer.drain(..len);
+			state.writers.wake_all();
+			Poll::Ready(Ok(len))
+		}
+	}
+
+	fn poll_write(
+		&self,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+		is_nonblocking: bool,
+	) -> Poll<io::Result<usize>> {
+		let mut state = self.state.lock();
+		if state.read_closed || state.write_closed {
+			return Poll::Ready(Err(Errno::Pipe));
+		}
+
+		let len = buf.len().min(STREAM_CAPACITY - state.buffer.len());
+		if len == 0 && !buf.is_empty() {
+			if is_nonblocking {
+				Poll::Ready(Err(Errno::Again))
+			} else {
+				state.writers.register(cx.waker());
+				Poll::Pending
+			}
+		} else {
+			state.buffer.extend(&buf[..len]);
+			state.readers.wake_all();
+			Poll::Ready(Ok(len))
+		}
+	}
+
+	fn shutdown_read(&self) {
+		let mut state = self.state.lock();
+		state.read_closed = true;
+		state.buffer.clear();
+		state.readers.wake_all();
+		state.writers.wake_all();
+	}
+
+	fn shutdown_write(&self) {
+		let mut state = self.state.lock();
+		state.write_closed = true;
+		state.readers.wake_all();
+		state.writers.wake_all();
+	}
+}
+
+/// One end of a stream connection
+#[derive(Debug)]
+struct Connection {
+	rx: Arc<Channel>,
+	tx: Arc<Channel>,
+	peer: UnixAddress,
+}
+
+impl Connection {
+	/// Creates both ends of a connection. The peer of the first end is `first_peer`.
+	fn pair(first_peer: UnixAddress, second_peer: UnixAddress) -> (Self, Self) {
+		let first = Arc::new(Channel::default());
+		let second = Arc::new(Channel::default());
+		(
+			Self {
+				rx: first.clone(),
+				tx: second.clone(),
+				peer: first_peer,
+			},
+			Self {
+				rx: second,
+				tx: first,
+				peer: second_peer,
+			},
+		)
+	}
+
+	fn poll(&self, cx: &mut Context<'_>) -> PollEvent {
+		let mut available = PollEvent::empty();
+
+		let mut rx = self.rx.state.lock();
+		if !rx.buffer.is_empty() || rx.write_closed {
+			available.insert(PollEvent::POLLIN | PollEvent::POLLRDNORM);
+		}
+		if rx.write_closed {
+			available.insert(PollEvent::POLLRDHUP);
+		}
+		rx.readers.register(cx.waker());
+		let read_closed = rx.write_closed;
+		drop(rx);
+
+		let mut tx = self.tx.state.lock();
+		if tx.buffer.len() < STREAM_CAPACITY || tx.read_closed {
+			available.insert(PollEvent::POLLOUT | PollEvent::POLLWRNORM);
+		}
+		if tx.read_closed && read_closed {
+			available.insert(PollEvent::POLLHUP);
+		}
+		tx.writers.register(cx.waker());
+
+		available
+	}
+}
+
+impl Drop for Connection {
+	fn drop(&mut self) {
+		self.rx.shutdown_read();
+		self.tx.shutdown_write();
+	}
+}
+
+#[derive(Debug, Default)]
+struct ListenerState {
+	/// Maximum number of pending connections or `None`, if the socket does not listen
+	backlog: Option<usize>,
+	/// Connections, which wait to be accepted
+	pending: VecDeque<Connection>,
+	/// Connection attempts, which have found the queue full
+	overflows: u64,
+	closed: bool,
+	acceptors: WaitQueue,
+	connectors: WaitQueue,
+}
+
+/// Bound stream socket, to which other sockets connect
+#[derive(Debug, Default)]
+struct Listener {
+	state: InterruptTicketMutex<ListenerState>,
+}
+
+#[derive(Debug)]
+enum StreamState {
+	Unconnected,
+	/// The socket is bound and possibly listens.
+	Bound(Arc<Listener>),
+	Connected(Connection),
+}
+
+/// Stream socket of the domain `AF_UNIX`
+#[derive(Debug)]
+pub(crate) struct StreamSocket {
+	local: UnixAddress,
+	state: StreamState,
+	is_nonblocking: bool,
+	send_timeout: Option<Duration>,
+	recv_timeout: Option<Duration>,
+}
+
+impl StreamSocket {
+	pub fn new() -> Self {
+		Self {
+			local: UnixAddress::Unnamed,
+			state: StreamState::Unconnected,
+			is_nonblocking: false,
+			send_timeout: None,
+			recv_timeout: None,
+		}
+	}
+
+	/// Creates a pair of connected sockets.
+	pub fn pair() -> (Self, Self) {
+		let (first, second) = Connection::pair(UnixAddress::Unnamed, UnixAddress::Unnamed);
+		let mut sockets = (Self::new(), Self::new());
+		sockets.0.state = StreamState::Connected(first);
+		sockets.1.state = StreamState::Connected(second);
+		sockets
+	}
+
+	fn connection(&self) -> io::Result<&Connection> {
+		match &self.state {
+			StreamState::Connected(connection) => Ok(connection),
+			_ => Err(Errno::Notconn),
+		}
+	}
+}
+
+#[async_trait]
+impl ObjectInterface for StreamSocket {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		future::poll_fn(|cx| {
+			let available = match &self.state {
+				StreamState::Unconnected => PollEvent::POLLOUT | PollEvent::POLLHUP,
+				StreamState::Bound(listener) => {
+					let mut state = listener.state.lock();
+					state.acceptors.register(cx.waker());
+					if state.pending.is_empty() {
+						PollEvent::empty()
+					} else {
+						PollEvent::POLLIN | PollEvent::POLLRDNORM
+					}
+				}
+				StreamState::Connected(connection) => connection.poll(cx),
+			};
+
+			let ret = available & (event | PollEvent::POLLHUP | PollEvent::POLLERR);
+			if ret.is_empty() {
+				Poll::Pending
+			} else {
+				Poll::Ready(Ok(ret))
+			}
+		})
+		.await
+	}
+
+	async fn bind(&mut self, endpoint: ListenEndpoint) -> io::Result<()> {
+		match endpoint {
+			ListenEndpoint::Unix(address) => {
+				if !matches!(self.state, StreamState::Unconnected) {
+					return Err(Errno::Inval);
+				}
+
+				let listener = Arc::new(Listener::default());
+				self.local = bind(address, Binding::Stream(Arc::downgrade(&listener)))?;
+				self.state = StreamState::Bound(listener);
+				Ok(())
+			}
+			#[cfg(any(feature = "net", feature = "vsock"))]
+			_ => Err(Errno::Inval),
+		}
+	}
+
+	async fn connect(&mut self, endpoint: Endpoint) -> io::Result<()> {
+		let address = match endpoint {
+			Endpoint::Unix(address) => address,
+			#[cfg(any(feature = "net", feature = "vsock"))]
+			_ => return Err(Errno::Inval),
+		};
+
+		match &self.state {
+			StreamState::Connected(_) => return Err(Errno::Isconn),
+			StreamState::Bound(listener) if listener.state.lock().backlog.is_some() => {
+				return Err(Errno::Inval);
+			}
+			_ => {}
+		}
+
+		let (name, listener) = lookup_listener(&address)?;
+		let (client, server) = Connection::pair(name, self.local.clone());
+		let mut server = Some(server);
+		let is_nonblocking = self.is_nonblocking;
+		let mut has_overflowed = false;
+		future::poll_fn(|cx| {
+			let mut state = listener.state.lock();
+			let Some(backlog) = state.backlog.filter(|_| !state.closed) else {
+				return Poll::Ready(Err(Errno::Connrefused));
+			};
+
+			if state.pending.len() >= backlog {
+				if !has_overflowed {
+					has_overflowed = true;
+					state.overflows += 1;
+				}
+
+				if is_nonblocking {
+					Poll::Ready(Err(Errno::Again))
+				} else {
+					state.connectors.register(cx.waker());
+					Poll::Pending
+				}
+			} else {
+				state.pending.push_back(server.take().unwrap());
+				state.acceptors.wake_all();
+				Poll::Ready(Ok(()))
+			}
+		})
+		.await?;
+
+		self.state = StreamState::Connected(client);
+		Ok(())
+	}
+
+	async fn listen(&mut self, backlog: i32) -> io::Result<()> {
+		let StreamState::Bound(listener) = &self.state else {
+			return Err(Errno::Inval);
+		};
+
+		let backlog = usize::try_from(backlog).unwrap_or(0).clamp(1, MAX_BACKLOG);
+		let mut state = listener.state.lock();
+		state.backlog = Some(backlog);
+		state.connectors.wake_all();
+		Ok(())
+	}
+
+	async fn listen_queue(&self) -> io::Result<ListenQueue> {
+		let StreamState::Bound(listener) = &self.state else {
+			return Err(Errno::Inval);
+		};
+
+		let state = listener.state.lock();
+		let backlog = state.backlog.ok_or(Errno::Inval)?;
+		Ok(ListenQueue {
+			depth: state.pending.len(),
+			backlog,
+			overflows: state.overflows,
+		})
+	}
+
+	async fn accept(
+		&mut self,
+	) -> io::Result<(Arc<async_lock::RwLock<dyn ObjectInterface>>, Endpoint)> {
+		let StreamState::Bound(listener) = &self.state else {
+			return Err(Errno::Inval);
+		};
+
+		let connection = future::poll_fn(|cx| {
+			let mut state = listener.state.lock();
+			if state.backlog.is_none() {
+				return Poll::Ready(Err(Errno::Inval));
+			}
+
+			if let Some(connection) = state.pending.pop_front() {
+				state.connectors.wake_all();
+				Poll::Ready(Ok(connection))
+			} else if self.is_nonblocking {
+				Poll::Ready(Err(Errno::Again))
+			} else {
+				state.acceptors.register(cx.waker());
+				Poll::Pending
+			}
+		})
+		.await?;
+
+		let peer = connection.peer.clone();
+		let socket = Self {
+			local: self.local.clone(),
+			state: StreamState::Connected(connection),
+			is_nonblocking: false,
+			send_timeout: self.send_timeout,
+			recv_timeout: self.recv_timeout,
+		};
+		Ok((
+			Arc::new(async_lock::RwLock::new(socket)),
+			Endpoint::Unix(peer),
+		))
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let buf = unsafe { core::slice::from_raw_parts_mut(buf.as_mut_ptr().cast(), buf.len()) };
+		let connection = self.connection()?;
+		future::poll_fn(|cx| connection.rx.poll_read(cx, buf, self.is_nonblocking)).await
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		let connection = self.connection()?;
+		future::poll_fn(|cx| connection.tx.poll_write(cx, buf, self.is_nonblocking)).await
+	}
+
+	async fn recvfrom(&self, buffer: &mut [MaybeUninit<u8>]) -> io::Result<(usize, Endpoint)> {
+		let connection = self.connection()?;
+		let len =
+			future::poll_fn(|cx| connection.rx.poll_read(cx, buffer, self.is_nonblocking)).await?;
+		Ok((len, Endpoint::Unix(connection.peer.clone())))
+	}
+
+	async fn sendto(&self, _buffer: &[u8], _endpoint: Endpoint) -> io::Result<usize> {
+		match self.state {
+			StreamState::Connected(_) => Err(Errno::Isconn),
+			_ => Err(Errno::Notconn),
+		}
+	}
+
+	async fn shutdown(&self, how: i32) -> io::Result<()> {
+		let connection = self.connection()?;
+		match how {
+			0 => connection.rx.shutdown_read(),
+			1 => connection.tx.shutdown_write(),
+			2 => {
+				connection.rx.shutdown_read();
+				connection.tx.shutdown_write();
+			}
+			_ => return Err(Errno::Inval),
+		}
+		Ok(())
+	}
+
+	async fn getsockname(&self) -> io::Result<Option<Endpoint>> {
+		Ok(Some(Endpoint::Unix(self.local.clone())))
+	}
+
+	async fn getpeername(&self) -> io::Result<Option<Endpoint>> {
+		let connection = self.connection()?;
+		Ok(Some(Endpoint::Unix(connection.peer.clone())))
+	}
+
+	async fn setsockopt(&mut self, opt: SocketOption, optval: SocketOptionValue) -> io::Result<()> {
+		match (opt, optval) {
+			// Like on Linux, `SO_REUSEADDR` is accepted, but has no effect.
+			(SocketOption::ReuseAddr, _) => Ok(()),
+			(SocketOption::SendTimeout, SocketOptionValue::Duration(timeout)) => {
+				self.send_timeout = timeout;
+				Ok(())
+			}
+			(SocketOption::RecvTimeout, SocketOptionValue::Duration(timeout)) => {
+				self.recv_timeout = timeout;
+				Ok(())
+			}
+			_ => Err(Errno::Noprotoopt),
+		}
+	}
+
+	async fn getsockopt(&self, opt: SocketOption) -> io::Result<SocketOptionValue> {
+		match opt {
+			SocketOption::SendTimeout => Ok(SocketOptionValue::Duration(self.send_timeout)),
+			SocketOption::RecvTimeout => Ok(SocketOptionValue::Duration(self.recv_timeout)),
+			SocketOption::Error => Ok(SocketOptionValue::Int(0)),
+			_ => Err(Errno::Noprotoopt),
+		}
+	}
+
+	async fn status_flags(&self) -> io::Result<fd::StatusFlags> {
+		let status_flags = if self.is_nonblocking {
+			fd::StatusFlags::O_NONBLOCK
+		} else {
+			fd::StatusFlags::empty()
+		};
+
+		Ok(status_flags)
+	}
+
+	async fn set_status_flags(&mut self, status_flags: fd::StatusFlags) -> io::Result<()> {
+		self.is_nonblocking = status_flags.contains(fd::StatusFlags::O_NONBLOCK);
+		Ok(())
+	}
+}
+
+impl Drop for StreamSocket {
+	fn drop(&mut self) {
+		if let StreamState::Bound(listener) = &self.state {
+			// The pending connections are dropped, which closes them.
+			let mut state = listener.state.lock();
+			state.closed = true;
+			state.pending.clear();
+			state.connectors.wake_all();
+			state.acceptors.wake_all();
+			drop(state);
+
+			unbind(&self.local, Arc::as_ptr(listener).cast());
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+struct DatagramQueueState {
+	/// Received datagrams and their senders
+	messages: VecDeque<(Vec<u8>, UnixAddress)>,
+	closed: bool,
+	readers: WaitQueue,
+	writers: WaitQueue,
+}
+
+/// Queue of the datagrams, which have been sent to a socket
+#[derive(Debug, Default)]
+struct DatagramQueue {
+	state: InterruptTicketMutex<DatagramQueueState>,
+}
+
+impl DatagramQueue {
+	fn poll_send(
+		&self,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+		sender: &UnixAddress,
+		is_nonblocking: bool,
+	) -> Poll<io::Result<usize>> {
+		let mut state = self.state.lock();
+		if state.closed {
+			Poll::Ready(Err(Errno::Connrefused))
+		} else if state.messages.len() >= DATAGRAM_QUEUE_LEN {
+			if is_nonblocking {
+				Poll::Ready(Err(Errno::Again))
+			} else {
+				state.writers.register(cx.waker());
+				Poll::Pending
+			}
+		} else {
+			state.messages.push_back((buf.to_vec(), sender.clone()));
+			state.readers.wake_all();
+			Poll::Ready(Ok(buf.len()))
+		}
+	}
+
+	/// Receives a datagram, whose remainder is discarded, if it does not fit into `buf`.
+	fn poll_recv(
+		&self,
+		cx: &mut Context<'_>,
+		buf: &mut [MaybeUninit<u8>],
+		is_nonblocking: bool,
+	) -> Poll<io::Result<(usize, UnixAddress)>> {
+		let mut state = self.state.lock();
+		if let Some((data, sender)) = state.messages.pop_front() {
+			let len = buf.len().min(data.len());
+			buf[..len].write_copy_of_slice(&data[..len]);
+			state.writers.wake_all();
+			Poll::Ready(Ok((len, sender)))
+		} else if is_nonblocking {
+			Poll::Ready(Err(Errno::Again))
+		} else {
+			state.readers.register(cx.waker());
+			Poll::Pending
+		}
+	}
+
+	fn close(&self) {
+		let mut state = self.state.lock();
+		state.closed = true;
+		state.messages.clear();
+		state.readers.wake_all();
+		state.writers.wake_all();
+	}
+}
+
+/// Datagram socket of the domain `AF_UNIX`
+#[derive(Debug)]
+pub(crate) struct DatagramSocket {
+	queue: Arc<DatagramQueue>,
+	local: UnixAddress,
+	/// Default destination, which has been selected by `connect`
+	peer: Option<(UnixAddress, Weak<DatagramQueue>)>,
+	is_nonblocking: bool,
+	send_timeout: Option<Duration>,
+	recv_timeout: Option<Duration>,
+}
+
+impl DatagramSocket {
+	pub fn new() -> Self {
+		Self {
+			queue: Arc::new(DatagramQueue::default()),
+			local: UnixAddress::Unnamed,
+			peer: None,
+			is_nonblocking: false,
+			send_timeout: None,
+			recv_timeout: None,
+		}
+	}
+
+	/// Creates a pair of sockets, which are connected to each other.
+	pub fn pair() -> (Self, Self) {
+		let mut first = Self::new();
+		let mut second = Self::new();
+		first.peer = Some((UnixAddress::Unnamed, Arc::downgrade(&second.queue)));
+		second.peer = Some((UnixAddress::Unnamed, Arc::downgrade(&first.queue)));
+		(first, second)
+	}
+
+	async fn send(&self, buf: &[u8], queue: &Weak<DatagramQueue>) -> io::Result<usize> {
+		if buf.len() > MAX_DATAGRAM_SIZE {
+			return Err(Errno::Msgsize);
+		}
+
+		let queue = queue.upgrade().ok_or(Errno::Connrefused)?;
+		future::poll_fn(|cx| queue.poll_send(cx, buf, &self.local, self.is_nonblocking)).await
+	}
+}
+
+#[async_trait]
+impl ObjectInterface for DatagramSocket {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		future::poll_fn(|cx| {
+			let mut available = PollEvent::empty();
+
+			let mut state = self.queue.state.lock();
+			if !state.messages.is_empty() {
+				available.insert(PollEvent::POLLIN | PollEvent::POLLRDNORM);
+			}
+			state.readers.register(cx.waker());
+			drop(state);
+
+			match self.peer.as_ref().and_then(|(_, queue)| queue.upgrade()) {
+				Some(queue) => {
+					let mut state = queue.state.lock();
+					if state.messages.len() < DATAGRAM_QUEUE_LEN || state.closed {
+						available.insert(PollEvent::POLLOUT | PollEvent::POLLWRNORM);
+					}
+					state.writers.register(cx.waker());
+				}
+				None => available.insert(PollEvent::POLLOUT | PollEvent::POLLWRNORM),
+			}
+
+			let ret = available & event;
+			if ret.is_empty() {
+				Poll::Pending
+			} else {
+				Poll::Ready(Ok(ret))
+			}
+		})
+		.await
+	}
+
+	async fn bind(&mut self, endpoint: ListenEndpoint) -> io::Result<()> {
+		match endpoint {
+			ListenEndpoint::Unix(address) => {
+				if self.local != UnixAddress::Unnamed {
+					return Err(Errno::Inval);
+				}
+
+				self.local = bind(address, Binding::Datagram(Arc::downgrade(&self.queue)))?;
+				Ok(())
+			}
+			#[cfg(any(feature = "net", feature = "vsock"))]
+			_ => Err(Errno::Inval),
+		}
+	}
+
+	async fn connect(&mut self, endpoint: Endpoint) -> io::Result<()> {
+		match endpoint {
+			Endpoint::Unix(address) => {
+				self.peer = Some(lookup_datagram(&address)?);
+				Ok(())
+			}
+			#[cfg(any(feature = "net", feature = "vsock"))]
+			_ => Err(Errno::Inval),
+		}
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let buf = unsafe { core::slice::from_raw_parts_mut(buf.as_mut_ptr().cast(), buf.len()) };
+		let (len, _) = self.recvfrom(buf).await?;
+		Ok(len)
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		let (_, queue) = self.peer.as_ref().ok_or(Errno::Destaddrreq)?;
+		self.send(buf, queue).await
+	}
+
+	async fn recvfrom(&self, buffer: &mut [MaybeUninit<u8>]) -> io::Result<(usize, Endpoint)> {
+		let (len, sender) =
+			future::poll_fn(|cx| self.queue.poll_recv(cx, buffer, self.is_nonblocking)).await?;
+		Ok((len, Endpoint::Unix(sender)))
+	}
+
+	async fn sendto(&self, buffer: &[u8], endpoint: Endpoint) -> io::Result<usize> {
+		match endpoint {
+			Endpoint::Unix(address) => {
+				let (_, queue) = lookup_datagram(&address)?;
+				self.send(buffer, &queue).await
+			}
+			#[cfg(any(feature = "net", feature = "vsock"))]
+			_ => Err(Errno::Inval),
+		}
+	}
+
+	async fn shutdown(&self, _how: i32) -> io::Result<()> {
+		Ok(())
+	}
+
+	async fn getsockname(&self) -> io::Result<Option<Endpoint>> {
+		Ok(Some(Endpoint::Unix(self.local.clone())))
+	}
+
+	async fn getpeername(&self) -> io::Result<Option<Endpoint>> {
+		let (peer, _) = self.peer.as_ref().ok_or(Errno::Notconn)?;
+		Ok(Some(Endpoint::Unix(peer.clone())))
+	}
+
+	async fn setsockopt(&mut self, opt: SocketOption, optval: SocketOptionValue) -> io::Result<()> {
+		match (opt, optval) {
+			// Like on Linux, `SO_REUSEADDR` is accepted, but has no effect.
+			(SocketOption::ReuseAddr, _) => Ok(()),
+			(SocketOption::SendTimeout, SocketOptionValue::Duration(timeout)) => {
+				self.send_timeout = timeout;
+				Ok(())
+			}
+			(SocketOption::RecvTimeout, SocketOptionValue::Duration(timeout)) => {
+				self.recv_timeout = timeout;
+				Ok(())
+			}
+			_ => Err(Errno::Noprotoopt),
+		}
+	}
+
+	async fn getsockopt(&self, opt: SocketOption) -> io::Result<SocketOptionValue> {
+		match opt {
+			SocketOption::SendTimeout => Ok(SocketOptionValue::Duration(self.send_timeout)),
+			SocketOption::RecvTimeout => Ok(SocketOptionValue::Duration(self.recv_timeout)),
+			SocketOption::Error => Ok(SocketOptionValue::Int(0)),
+			_ => Err(Errno::Noprotoopt),
+		}
+	}
+
+	async fn status_flags(&self) -> io::Result<fd::StatusFlags> {
+		let status_flags = if self.is_nonblocking {
+			fd::StatusFlags::O_NONBLOCK
+		} else {
+			fd::StatusFlags::empty()
+		};
+
+		Ok(status_flags)
+	}
+
+	async fn set_status_flags(&mut self, status_flags: fd::StatusFlags) -> io::Result<()> {
+		self.is_nonblocking = status_flags.contains(fd::StatusFlags::O_NONBLOCK);
+		Ok(())
+	}
+}
+
+impl Drop for DatagramSocket {
+	fn drop(&mut self) {
+		self.queue.close();
+		unbind(&self.local, Arc::as_ptr(&self.queue).cast());
+	}
+}
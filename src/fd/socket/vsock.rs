@@ -1,20 +1,27 @@
 use alloc::boxed::Box;
+use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::future;
 use core::task::Poll;
+use core::time::Duration;
 
 use async_trait::async_trait;
-use virtio::vsock::{Hdr, Op, Type};
-use virtio::{le16, le32, le64};
+use virtio::le32;
+use virtio::vsock::{Hdr, Op};
 
 #[cfg(not(feature = "pci"))]
 use crate::arch::kernel::mmio as hardware;
 #[cfg(feature = "pci")]
 use crate::drivers::pci as hardware;
 use crate::errno::Errno;
-use crate::executor::vsock::{VSOCK_MAP, VsockState};
-use crate::fd::{self, Endpoint, ListenEndpoint, ObjectInterface, PollEvent};
+use crate::executor::vsock::{
+	ConnectionId, SHUTDOWN_RCV, SHUTDOWN_SEND, VMADDR_CID_ANY, VSOCK_MAP, VsockState, send_header,
+};
+use crate::fd::{
+	self, Endpoint, ListenEndpoint, ListenQueue, ObjectInterface, PollEvent, SocketOption,
+	SocketOptionValue,
+};
 use crate::io;
 
 #[derive(Debug)]
@@ -41,118 +48,140 @@ impl VsockEndpoint {
 	}
 }
 
+/// State of a vsock stream socket
 #[derive(Debug)]
-pub struct NullSocket;
-
-impl NullSocket {
-	pub const fn new() -> Self {
-		Self {}
-	}
+enum SocketState {
+	Unbound,
+	/// The port is reserved by `bind` and accepts connections after `listen`.
+	Listener {
+		port: u32,
+	},
+	Connection(ConnectionId),
 }
 
-#[async_trait]
-impl ObjectInterface for NullSocket {}
-
 #[derive(Debug)]
 pub struct Socket {
-	port: u32,
-	cid: u32,
+	state: SocketState,
 	is_nonblocking: bool,
+	send_timeout: Option<Duration>,
+	recv_timeout: Option<Duration>,
 }
 
 impl Socket {
 	pub fn new() -> Self {
 		Self {
-			port: 0,
-			cid: u32::MAX,
+			state: SocketState::Unbound,
 			is_nonblocking: false,
+			send_timeout: None,
+			recv_timeout: None,
 		}
 	}
+
+	fn connection(&self) -> io::Result<ConnectionId> {
+		match self.state {
+			SocketState::Connection(id) => Ok(id),
+			_ => Err(Errno::Notconn),
+		}
+	}
+}
+
+fn local_cid() -> u64 {
+	hardware::get_vsock_driver().unwrap().lock().get_cid()
 }
 
 #[async_trait]
 impl ObjectInterface for Socket {
 	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
-		future::poll_fn(|cx| {
-			let mut guard = VSOCK_MAP.lock();
-			let raw = guard.get_mut_socket(self.port).ok_or(Errno::Inval)?;
-
-			match raw.state {
-				VsockState::Shutdown | VsockState::ReceiveRequest => {
-					let available = PollEvent::POLLOUT
-						| PollEvent::POLLWRNORM
-						| PollEvent::POLLWRBAND
-						| PollEvent::POLLIN
-						| PollEvent::POLLRDNORM
-						| PollEvent::POLLRDBAND;
-
-					let ret = event & available;
-
-					if ret.is_empty() {
-						Poll::Ready(Ok(PollEvent::POLLHUP))
-					} else {
-						Poll::Ready(Ok(ret))
-					}
-				}
-				VsockState::Listen | VsockState::Connecting => {
-					raw.rx_waker.register(cx.waker());
-					raw.tx_waker.register(cx.waker());
-					Poll::Pending
-				}
-				VsockState::Connected => {
-					let mut available = PollEvent::empty();
+		let readable = PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND;
+		let writable = PollEvent::POLLOUT | PollEvent::POLLWRNORM | PollEvent::POLLWRBAND;
 
-					if !raw.buffer.is_empty() {
-						// In case, we just establish a fresh connection in non-blocking mode, we try to read data.
-						available.insert(
-							PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND,
-						);
-					}
+		match self.state {
+			SocketState::Unbound => Ok(PollEvent::POLLHUP),
+			SocketState::Listener { port } => {
+				future::poll_fn(|cx| {
+					let mut guard = VSOCK_MAP.lock();
+					let listener = guard.get_listener_mut(port).ok_or(Errno::Inval)?;
 
-					let diff = raw.tx_cnt.abs_diff(raw.peer_fwd_cnt);
-					if diff < raw.peer_buf_alloc {
-						available.insert(
-							PollEvent::POLLOUT | PollEvent::POLLWRNORM | PollEvent::POLLWRBAND,
-						);
+					if listener.pending.is_empty() {
+						listener.waker.register(cx.waker());
+						Poll::Pending
+					} else {
+						Poll::Ready(Ok(event & readable))
 					}
+				})
+				.await
+			}
+			SocketState::Connection(id) => {
+				future::poll_fn(|cx| {
+					let mut guard = VSOCK_MAP.lock();
+					let raw = guard.get_mut_socket(&id).ok_or(Errno::Inval)?;
 
-					let ret = event & available;
-
-					if ret.is_empty() {
-						if event.intersects(
-							PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND,
-						) {
+					match raw.state {
+						VsockState::Connecting => {
 							raw.rx_waker.register(cx.waker());
+							raw.tx_waker.register(cx.waker());
+							Poll::Pending
 						}
+						VsockState::Refused => Poll::Ready(Ok(PollEvent::POLLERR)),
+						VsockState::Shutdown => {
+							let ret = event & (readable | writable);
 
-						if event.intersects(
-							PollEvent::POLLOUT | PollEvent::POLLWRNORM | PollEvent::POLLWRBAND,
-						) {
-							raw.tx_waker.register(cx.waker());
+							if ret.is_empty() {
+								Poll::Ready(Ok(PollEvent::POLLHUP))
+							} else {
+								Poll::Ready(Ok(ret))
+							}
 						}
+						VsockState::Connected => {
+							let mut available = PollEvent::empty();
 
-						Poll::Pending
-					} else {
-						Poll::Ready(Ok(ret))
+							if !raw.buffer.is_empty() {
+								// In case, we just establish a fresh connection in non-blocking mode, we try to read data.
+								available.insert(readable);
+							}
+
+							let diff = raw.tx_cnt.abs_diff(raw.peer_fwd_cnt);
+							if diff < raw.peer_buf_alloc || raw.peer_shutdown & SHUTDOWN_RCV != 0 {
+								available.insert(writable);
+							}
+
+							let ret = event & available;
+
+							if ret.is_empty() {
+								if event.intersects(readable) {
+									raw.rx_waker.register(cx.waker());
+								}
+
+								if event.intersects(writable) {
+									raw.tx_waker.register(cx.waker());
+								}
+
+								Poll::Pending
+							} else {
+								Poll::Ready(Ok(ret))
+							}
+						}
 					}
-				}
+				})
+				.await
 			}
-		})
-		.await
+		}
 	}
 
 	async fn bind(&mut self, endpoint: ListenEndpoint) -> io::Result<()> {
 		match endpoint {
 			ListenEndpoint::Vsock(ep) => {
-				self.port = ep.port;
-				if let Some(cid) = ep.cid {
-					self.cid = cid;
-				} else {
-					self.cid = u32::MAX;
+				if !matches!(self.state, SocketState::Unbound) {
+					return Err(Errno::Inval);
 				}
-				VSOCK_MAP.lock().bind(ep.port)
+
+				VSOCK_MAP
+					.lock()
+					.bind(ep.port, ep.cid.unwrap_or(VMADDR_CID_ANY))?;
+				self.state = SocketState::Listener { port: ep.port };
+				Ok(())
 			}
-			#[cfg(feature = "net")]
+			#[cfg(any(feature = "net", feature = "unix"))]
 			_ => Err(Errno::Inval),
 		}
 	}
@@ -160,32 +189,19 @@ impl ObjectInterface for Socket {
 	async fn connect(&mut self, endpoint: Endpoint) -> io::Result<()> {
 		match endpoint {
 			Endpoint::Vsock(ep) => {
-				const HEADER_SIZE: usize = core::mem::size_of::<Hdr>();
-				let port = VSOCK_MAP.lock().connect(ep.port, ep.cid)?;
-				self.port = port;
-				self.port = ep.cid;
+				let local_port = match self.state {
+					SocketState::Unbound => None,
+					SocketState::Listener { port } => Some(port),
+					SocketState::Connection(_) => return Err(Errno::Isconn),
+				};
+				let id = VSOCK_MAP.lock().connect(ep.port, ep.cid, local_port)?;
+				self.state = SocketState::Connection(id);
 
 				future::poll_fn(|cx| {
 					if let Some(mut driver_guard) = hardware::get_vsock_driver().unwrap().try_lock()
 					{
 						let local_cid = driver_guard.get_cid();
-
-						driver_guard.send_packet(HEADER_SIZE, |buffer| {
-							let response = unsafe { &mut *buffer.as_mut_ptr().cast::<Hdr>() };
-
-							response.src_cid = le64::from_ne(local_cid);
-							response.dst_cid = le64::from_ne(ep.cid.into());
-							response.src_port = le32::from_ne(port);
-							response.dst_port = le32::from_ne(ep.port);
-							response.len = le32::from_ne(0);
-							response.type_ = le16::from_ne(Type::Stream.into());
-							response.op = le16::from_ne(Op::Request.into());
-							response.flags = le32::from_ne(0);
-							response.buf_alloc = le32::from_ne(
-								crate::executor::vsock::RAW_SOCKET_BUFFER_SIZE as u32,
-							);
-							response.fwd_cnt = le32::from_ne(0);
-						});
+						send_header(&mut driver_guard, id.header(local_cid, Op::Request, 0, 0));
 
 						Poll::Ready(())
 					} else {
@@ -198,7 +214,7 @@ impl ObjectInterface for Socket {
 
 				future::poll_fn(|cx| {
 					let mut guard = VSOCK_MAP.lock();
-					let raw = guard.get_mut_socket(port).ok_or(Errno::Inval)?;
+					let raw = guard.get_mut_socket(&id).ok_or(Errno::Inval)?;
 
 					match raw.state {
 						VsockState::Connected => Poll::Ready(Ok(())),
@@ -206,107 +222,155 @@ impl ObjectInterface for Socket {
 							raw.rx_waker.register(cx.waker());
 							Poll::Pending
 						}
-						_ => Poll::Ready(Err(Errno::Badf)),
+						VsockState::Refused => Poll::Ready(Err(Errno::Connrefused)),
+						VsockState::Shutdown => Poll::Ready(Err(Errno::Connreset)),
 					}
 				})
 				.await
 			}
-			#[cfg(feature = "net")]
+			#[cfg(any(feature = "net", feature = "unix"))]
 			_ => Err(Errno::Inval),
 		}
 	}
 
 	async fn getpeername(&self) -> io::Result<Option<Endpoint>> {
-		let guard = VSOCK_MAP.lock();
-		let raw = guard.get_socket(self.port).ok_or(Errno::Inval)?;
+		let id = self.connection()?;
 
 		Ok(Some(Endpoint::Vsock(VsockEndpoint::new(
-			raw.remote_port,
-			raw.remote_cid,
+			id.remote_port,
+			id.remote_cid,
 		))))
 	}
 
 	async fn getsockname(&self) -> io::Result<Option<Endpoint>> {
-		let local_cid = hardware::get_vsock_driver().unwrap().lock().get_cid();
+		let port = match self.state {
+			SocketState::Unbound => 0,
+			SocketState::Listener { port } => port,
+			SocketState::Connection(id) => id.local_port,
+		};
 
 		Ok(Some(Endpoint::Vsock(VsockEndpoint::new(
-			self.port,
-			local_cid.try_into().unwrap(),
+			port,
+			local_cid().try_into().unwrap(),
 		))))
 	}
 
-	async fn listen(&mut self, _backlog: i32) -> io::Result<()> {
-		Ok(())
+	async fn listen(&mut self, backlog: i32) -> io::Result<()> {
+		match self.state {
+			SocketState::Listener { port } => VSOCK_MAP
+				.lock()
+				.listen(port, backlog.try_into().unwrap_or(0)),
+			_ => Err(Errno::Inval),
+		}
+	}
+
+	async fn listen_queue(&self) -> io::Result<ListenQueue> {
+		let SocketState::Listener { port } = self.state else {
+			return Err(Errno::Inval);
+		};
+
+		let mut guard = VSOCK_MAP.lock();
+		let listener = guard.get_listener_mut(port).ok_or(Errno::Inval)?;
+		if listener.backlog == 0 {
+			return Err(Errno::Inval);
+		}
+
+		Ok(ListenQueue {
+			depth: listener.pending.len(),
+			backlog: listener.backlog,
+			overflows: listener.overflows,
+		})
 	}
 
 	async fn accept(
 		&mut self,
 	) -> io::Result<(Arc<async_lock::RwLock<dyn ObjectInterface>>, Endpoint)> {
-		let port = self.port;
-		let cid = self.cid;
+		let SocketState::Listener { port } = self.state else {
+			return Err(Errno::Inval);
+		};
 
-		let endpoint = future::poll_fn(|cx| {
+		let id = future::poll_fn(|cx| {
 			let mut guard = VSOCK_MAP.lock();
-			let raw = guard.get_mut_socket(port).ok_or(Errno::Inval)?;
-
-			match raw.state {
-				VsockState::Listen => {
-					if self.is_nonblocking {
-						Poll::Ready(Err(Errno::Again))
-					} else {
-						raw.rx_waker.register(cx.waker());
-						Poll::Pending
-					}
-				}
-				VsockState::ReceiveRequest => {
-					let result = {
-						const HEADER_SIZE: usize = core::mem::size_of::<Hdr>();
-						let mut driver_guard = hardware::get_vsock_driver().unwrap().lock();
-						let local_cid = driver_guard.get_cid();
-
-						driver_guard.send_packet(HEADER_SIZE, |buffer| {
-							let response = unsafe { &mut *buffer.as_mut_ptr().cast::<Hdr>() };
-
-							response.src_cid = le64::from_ne(local_cid);
-							response.dst_cid = le64::from_ne(raw.remote_cid.into());
-							response.src_port = le32::from_ne(port);
-							response.dst_port = le32::from_ne(raw.remote_port);
-							response.len = le32::from_ne(0);
-							response.type_ = le16::from_ne(Type::Stream.into());
-							if local_cid != u64::from(cid) && cid != u32::MAX {
-								response.op = le16::from_ne(Op::Rst.into());
-							} else {
-								response.op = le16::from_ne(Op::Response.into());
-							}
-							response.flags = le32::from_ne(0);
-							response.buf_alloc = le32::from_ne(
-								crate::executor::vsock::RAW_SOCKET_BUFFER_SIZE as u32,
-							);
-							response.fwd_cnt = le32::from_ne(raw.fwd_cnt);
-						});
-
-						raw.state = VsockState::Connected;
-
-						Ok(VsockEndpoint::new(raw.remote_port, raw.remote_cid))
-					};
-
-					Poll::Ready(result)
-				}
-				_ => Poll::Ready(Err(Errno::Badf)),
+			let listener = guard.get_listener_mut(port).ok_or(Errno::Inval)?;
+
+			if listener.backlog == 0 {
+				Poll::Ready(Err(Errno::Inval))
+			} else if let Some(id) = listener.pending.pop_front() {
+				Poll::Ready(Ok(id))
+			} else if self.is_nonblocking {
+				Poll::Ready(Err(Errno::Again))
+			} else {
+				listener.waker.register(cx.waker());
+				Poll::Pending
 			}
 		})
 		.await?;
 
+		let socket = Socket {
+			state: SocketState::Connection(id),
+			is_nonblocking: false,
+			send_timeout: self.send_timeout,
+			recv_timeout: self.recv_timeout,
+		};
+
 		Ok((
-			Arc::new(async_lock::RwLock::new(NullSocket::new())),
-			Endpoint::Vsock(endpoint),
+			Arc::new(async_lock::RwLock::new(socket)),
+			Endpoint::Vsock(VsockEndpoint::new(id.remote_port, id.remote_cid)),
 		))
 	}
 
-	async fn shutdown(&self, _how: i32) -> io::Result<()> {
+	async fn shutdown(&self, how: i32) -> io::Result<()> {
+		let id = self.connection()?;
+		let flags = match how {
+			0 => SHUTDOWN_RCV,
+			1 => SHUTDOWN_SEND,
+			2 => SHUTDOWN_RCV | SHUTDOWN_SEND,
+			_ => return Err(Errno::Inval),
+		};
+
+		let mut guard = VSOCK_MAP.lock();
+		let raw = guard.get_mut_socket(&id).ok_or(Errno::Notconn)?;
+		raw.local_shutdown |= flags;
+		if flags & SHUTDOWN_RCV != 0 {
+			raw.buffer.clear();
+		}
+		raw.rx_waker.wake();
+		raw.tx_waker.wake();
+
+		let mut driver_guard = hardware::get_vsock_driver().unwrap().lock();
+		let local_cid = driver_guard.get_cid();
+		send_header(
+			&mut driver_guard,
+			id.header(local_cid, Op::Shutdown, raw.local_shutdown, raw.fwd_cnt),
+		);
 		Ok(())
 	}
 
+	async fn setsockopt(&mut self, opt: SocketOption, optval: SocketOptionValue) -> io::Result<()> {
+		match (opt, optval) {
+			// Like on Linux, `SO_REUSEADDR` is accepted, but has no effect.
+			(SocketOption::ReuseAddr, _) => Ok(()),
+			(SocketOption::SendTimeout, SocketOptionValue::Duration(timeout)) => {
+				self.send_timeout = timeout;
+				Ok(())
+			}
+			(SocketOption::RecvTimeout, SocketOptionValue::Duration(timeout)) => {
+				self.recv_timeout = timeout;
+				Ok(())
+			}
+			_ => Err(Errno::Noprotoopt),
+		}
+	}
+
+	async fn getsockopt(&self, opt: SocketOption) -> io::Result<SocketOptionValue> {
+		match opt {
+			SocketOption::SendTimeout => Ok(SocketOptionValue::Duration(self.send_timeout)),
+			SocketOption::RecvTimeout => Ok(SocketOptionValue::Duration(self.recv_timeout)),
+			SocketOption::Error => Ok(SocketOptionValue::Int(0)),
+			_ => Err(Errno::Noprotoopt),
+		}
+	}
+
 	async fn status_flags(&self) -> io::Result<fd::StatusFlags> {
 		let status_flags = if self.is_nonblocking {
 			fd::StatusFlags::O_NONBLOCK
@@ -323,54 +387,47 @@ impl ObjectInterface for Socket {
 	}
 
 	async fn read(&self, buffer: &mut [u8]) -> io::Result<usize> {
-		let port = self.port;
+		let id = self.connection()?;
 		future::poll_fn(|cx| {
 			let mut guard = VSOCK_MAP.lock();
-			let raw = guard.get_mut_socket(port).ok_or(Errno::Inval)?;
+			let raw = guard.get_mut_socket(&id).ok_or(Errno::Inval)?;
 
-			match raw.state {
-				VsockState::Connected => {
-					let len = core::cmp::min(buffer.len(), raw.buffer.len());
+			let len = core::cmp::min(buffer.len(), raw.buffer.len());
+			if len > 0 {
+				let tmp: Vec<_> = raw.buffer.drain(..len).collect();
+				buffer[..len].copy_from_slice(tmp.as_slice());
 
-					if len == 0 {
-						if self.is_nonblocking {
-							Poll::Ready(Err(Errno::Again))
-						} else {
-							raw.rx_waker.register(cx.waker());
-							Poll::Pending
-						}
-					} else {
-						let tmp: Vec<_> = raw.buffer.drain(..len).collect();
-						buffer[..len].copy_from_slice(tmp.as_slice());
-
-						Poll::Ready(Ok(len))
-					}
-				}
-				VsockState::Shutdown => {
-					let len = core::cmp::min(buffer.len(), raw.buffer.len());
+				return Poll::Ready(Ok(len));
+			}
 
-					if len == 0 {
-						Poll::Ready(Ok(0))
+			match raw.state {
+				VsockState::Connected if raw.local_shutdown & SHUTDOWN_RCV == 0 => {
+					if self.is_nonblocking {
+						Poll::Ready(Err(Errno::Again))
 					} else {
-						let tmp: Vec<_> = raw.buffer.drain(..len).collect();
-						buffer[..len].copy_from_slice(tmp.as_slice());
-
-						Poll::Ready(Ok(len))
+						raw.rx_waker.register(cx.waker());
+						Poll::Pending
 					}
 				}
-				_ => Poll::Ready(Err(Errno::Io)),
+				VsockState::Connected | VsockState::Shutdown => Poll::Ready(Ok(0)),
+				VsockState::Connecting => Poll::Ready(Err(Errno::Notconn)),
+				VsockState::Refused => Poll::Ready(Err(Errno::Connrefused)),
 			}
 		})
 		.await
 	}
 
 	async fn write(&self, buffer: &[u8]) -> io::Result<usize> {
-		let port = self.port;
+		let id = self.connection()?;
 		future::poll_fn(|cx| {
 			let mut guard = VSOCK_MAP.lock();
-			let raw = guard.get_mut_socket(port).ok_or(Errno::Inval)?;
+			let raw = guard.get_mut_socket(&id).ok_or(Errno::Inval)?;
 			let diff = raw.tx_cnt.abs_diff(raw.peer_fwd_cnt);
 
+			if raw.local_shutdown & SHUTDOWN_SEND != 0 || raw.peer_shutdown & SHUTDOWN_RCV != 0 {
+				return Poll::Ready(Err(Errno::Pipe));
+			}
+
 			match raw.state {
 				VsockState::Connected => {
 					if diff >= raw.peer_buf_alloc {
@@ -394,18 +451,8 @@ impl ObjectInterface for Socket {
 								unsafe { &mut *virtio_buffer.as_mut_ptr().cast::<Hdr>() };
 
 							raw.tx_cnt = raw.tx_cnt.wrapping_add(len.try_into().unwrap());
-							response.src_cid = le64::from_ne(local_cid);
-							response.dst_cid = le64::from_ne(raw.remote_cid.into());
-							response.src_port = le32::from_ne(port);
-							response.dst_port = le32::from_ne(raw.remote_port);
+							*response = id.header(local_cid, Op::Rw, 0, raw.fwd_cnt);
 							response.len = le32::from_ne(len.try_into().unwrap());
-							response.type_ = le16::from_ne(Type::Stream.into());
-							response.op = le16::from_ne(Op::Rw.into());
-							response.flags = le32::from_ne(0);
-							response.buf_alloc = le32::from_ne(
-								crate::executor::vsock::RAW_SOCKET_BUFFER_SIZE as u32,
-							);
-							response.fwd_cnt = le32::from_ne(raw.fwd_cnt);
 
 							virtio_buffer[HEADER_SIZE..HEADER_SIZE + len]
 								.copy_from_slice(&buffer[..len]);
@@ -414,7 +461,9 @@ impl ObjectInterface for Socket {
 						Poll::Ready(Ok(len))
 					}
 				}
-				_ => Poll::Ready(Err(Errno::Io)),
+				VsockState::Shutdown => Poll::Ready(Err(Errno::Pipe)),
+				VsockState::Connecting => Poll::Ready(Err(Errno::Notconn)),
+				VsockState::Refused => Poll::Ready(Err(Errno::Connrefused)),
 			}
 		})
 		.await
@@ -424,6 +473,37 @@ impl ObjectInterface for Socket {
 impl Drop for Socket {
 	fn drop(&mut self) {
 		let mut guard = VSOCK_MAP.lock();
-		guard.remove_socket(self.port);
+		let resets: VecDeque<ConnectionId> = match self.state {
+			SocketState::Unbound => return,
+			// The connections, which have not been accepted, are reset.
+			SocketState::Listener { port } => guard.remove_listener(port),
+			SocketState::Connection(id) => match guard.remove_socket(&id) {
+				// The peer is told, that the connection is closed. It answers with a reset.
+				Some(raw) if raw.state == VsockState::Connected => {
+					let mut driver_guard = hardware::get_vsock_driver().unwrap().lock();
+					let local_cid = driver_guard.get_cid();
+					send_header(
+						&mut driver_guard,
+						id.header(
+							local_cid,
+							Op::Shutdown,
+							SHUTDOWN_RCV | SHUTDOWN_SEND,
+							raw.fwd_cnt,
+						),
+					);
+					return;
+				}
+				Some(raw) if raw.state == VsockState::Connecting => VecDeque::from([id]),
+				_ => return,
+			},
+		};
+
+		if !resets.is_empty() {
+			let mut driver_guard = hardware::get_vsock_driver().unwrap().lock();
+			let local_cid = driver_guard.get_cid();
+			for id in resets {
+				send_header(&mut driver_guard, id.header(local_cid, Op::Rst, 0, 0));
+			}
+		}
 	}
 }
@@ -7,11 +7,15 @@ use embedded_io::{Read, ReadReady, Write};
 use uhyve_interface::parameters::WriteParams;
 use uhyve_interface::{GuestVirtAddr, Hypercall};
 
-use crate::console::{CONSOLE, CONSOLE_WAKER};
+use crate::console::CONSOLE;
+#[cfg(not(feature = "shell"))]
+use crate::console::CONSOLE_WAKER as INPUT_WAKER;
 use crate::fd::{
 	AccessPermission, FileAttr, ObjectInterface, PollEvent, STDERR_FILENO, STDOUT_FILENO,
 };
 use crate::io;
+#[cfg(feature = "shell")]
+use crate::shell::INPUT_WAKER;
 use crate::syscalls::interfaces::uhyve_hypercall;
 
 #[derive(Debug)]
@@ -37,7 +41,7 @@ impl ObjectInterface for GenericStdin {
 				CONSOLE.lock().flush()?;
 				Poll::Ready(Ok(read_bytes))
 			} else {
-				CONSOLE_WAKER.lock().register(cx.waker());
+				INPUT_WAKER.lock().register(cx.waker());
 				Poll::Pending
 			}
 		})
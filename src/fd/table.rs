@@ -0,0 +1,279 @@
+//! Table of the file descriptors.
+//!
+//! The objects are stored in slots of fixed-size chunks, which are allocated
+//! on first use and are not moved afterwards. A lookup locks only the slot
+//! of its descriptor, so that lookups neither wait for each other nor for
+//! allocations of other descriptors. Lookups are not lock-free like RCU,
+//! though: a lookup holds the spin lock of its slot, while it clones the
+//! object, and waits for a concurrent insertion or removal of the same
+//! descriptor.
+//!
+//! The free descriptors are tracked by a tree of bitmaps. A bit of the lowest
+//! level is set, if its descriptor is in use. A bit of the higher levels is
+//! set, if the word below it is full. Thereby, the lowest free descriptor is
+//! found by descending the tree, which takes one step per level.
+//!
+//! The number of descriptors is limited by `nofile=<count>` (default 65536).
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use hermit_sync::{OnceCell, SpinMutex};
+
+use crate::errno::Errno;
+use crate::fd::{FileDescriptor, ObjectInterface};
+use crate::io;
+
+type Object = Arc<async_lock::RwLock<dyn ObjectInterface>>;
+
+/// Default maximum number of file descriptors
+const DEFAULT_LIMIT: usize = 65536;
+
+/// Maximum number of file descriptors, which can be configured
+const MAX_LIMIT: usize = 1 << 24;
+
+/// Number of slots per chunk
+const CHUNK_SIZE: usize = 256;
+
+type Chunk = [SpinMutex<Option<Object>>; CHUNK_SIZE];
+
+/// Tree of bitmaps of the used descriptors
+struct Bitmap {
+	/// The first level contains one bit per descriptor, the last level
+	/// consists of a single word.
+	levels: Vec<Vec<u64>>,
+}
+
+impl Bitmap {
+	fn new(limit: usize) -> Self {
+		let mut len = limit.div_ceil(64);
+		let mut levels = vec![vec![0; len]];
+		while len > 1 {
+			len = len.div_ceil(64);
+			levels.push(vec![0; len]);
+		}
+		Self { levels }
+	}
+
+	/// Returns the lowest index, whose bit is not set.
+	fn first_free(&self) -> Option<usize> {
+		let mut index = 0;
+		for level in self.levels.iter().rev() {
+			let word = *level.get(index)?;
+			let bit = word.trailing_ones() as usize;
+			if bit == 64 {
+				return None;
+			}
+			index = index * 64 + bit;
+		}
+		Some(index)
+	}
+
+	fn is_set(&self, index: usize) -> bool {
+		self.levels[0][index / 64] & (1 << (index % 64)) != 0
+	}
+
+	fn set(&mut self, mut index: usize) {
+		for level in &mut self.levels {
+			let word = &mut level[index / 64];
+			*word |= 1 << (index % 64);
+			if *word != u64::MAX {
+				break;
+			}
+			index /= 64;
+		}
+	}
+
+	fn clear(&mut self, mut index: usize) {
+		for level in &mut self.levels {
+			let word = &mut level[index / 64];
+			let was_full = *word == u64::MAX;
+			*word &= !(1 << (index % 64));
+			if !was_full {
+				break;
+			}
+			index /= 64;
+		}
+	}
+}
+
+/// Mapping between file descriptors and the referenced IO interfaces
+pub(crate) struct FdTable {
+	limit: usize,
+	chunks: Vec<OnceCell<Box<Chunk>>>,
+	/// Serializes the allocation and release of descriptors
+	used: SpinMutex<Bitmap>,
+}
+
+impl FdTable {
+	pub fn new() -> Self {
+		Self::with_limit(crate::env::nofile().unwrap_or(DEFAULT_LIMIT))
+	}
+
+	/// Creates a table with at most `limit` descriptors.
+	fn with_limit(limit: usize) -> Self {
+		let limit = limit.clamp(3, MAX_LIMIT);
+		let num_chunks = limit.div_ceil(CHUNK_SIZE);
+
+		Self {
+			limit,
+			chunks: (0..num_chunks).map(|_| OnceCell::new()).collect(),
+			used: SpinMutex::new(Bitmap::new(limit)),
+		}
+	}
+
+	fn index(&self, fd: FileDescriptor) -> Option<usize> {
+		usize::try_from(fd).ok().filter(|&index| index < self.limit)
+	}
+
+	/// Returns the slot of `index`, if its chunk has been allocated.
+	fn slot(&self, index: usize) -> Option<&SpinMutex<Option<Object>>> {
+		let chunk = self.chunks[index / CHUNK_SIZE].get()?;
+		Some(&chunk[index % CHUNK_SIZE])
+	}
+
+	fn slot_or_init(&self, index: usize) -> &SpinMutex<Option<Object>> {
+		let chunk = self.chunks[index / CHUNK_SIZE]
+			.get_or_init(|| Box::new([const { SpinMutex::new(None) }; CHUNK_SIZE]));
+		&chunk[index % CHUNK_SIZE]
+	}
+
+	pub fn get(&self, fd: FileDescriptor) -> io::Result<Object> {
+		let index = self.index(fd).ok_or(Errno::Badf)?;
+		let slot = self.slot(index).ok_or(Errno::Badf)?;
+		slot.lock().clone().ok_or(Errno::Badf)
+	}
+
+	/// Stores `obj` with the lowest free file descriptor and returns it.
+	pub fn insert(&self, obj: Object) -> io::Result<FileDescriptor> {
+		let mut used = self.used.lock();
+		let index = used
+			.first_free()
+			.filter(|&index| index < self.limit)
+			.ok_or(Errno::Mfile)?;
+
+		used.set(index);
+		*self.slot_or_init(index).lock() = Some(obj);
+		Ok(index.try_into().unwrap())
+	}
+
+	/// Stores `obj` with the file descriptor `fd`, which has to be free.
+	pub fn insert_at(&self, fd: FileDescriptor, obj: Object) -> io::Result<()> {
+		let index = self.index(fd).ok_or(Errno::Badf)?;
+		let mut used = self.used.lock();
+		if used.is_set(index) {
+			return Err(Errno::Mfile);
+		}
+
+		used.set(index);
+		*self.slot_or_init(index).lock() = Some(obj);
+		Ok(())
+	}
+
+	pub fn remove(&self, fd: FileDescriptor) -> io::Result<Object> {
+		let index = self.index(fd).ok_or(Errno::Badf)?;
+		let mut used = self.used.lock();
+		let obj = self
+			.slot(index)
+			.and_then(|slot| slot.lock().take())
+			.ok_or(Errno::Badf)?;
+
+		used.clear(index);
+		Ok(obj)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use async_trait::async_trait;
+
+	use super::*;
+
+	#[derive(Debug)]
+	struct Dummy;
+
+	#[async_trait]
+	impl ObjectInterface for Dummy {}
+
+	fn dummy() -> Object {
+		Arc::new(async_lock::RwLock::new(Dummy))
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_bitmap_word_boundary() {
+		let mut bitmap = Bitmap::new(256);
+		for index in 0..64 {
+			assert_eq!(bitmap.first_free(), Some(index));
+			bitmap.set(index);
+		}
+		// The full first word is marked in the second level.
+		assert_eq!(bitmap.levels[1][0], 1);
+		assert_eq!(bitmap.first_free(), Some(64));
+
+		bitmap.clear(63);
+		assert_eq!(bitmap.levels[1][0], 0);
+		assert!(!bitmap.is_set(63));
+		assert_eq!(bitmap.first_free(), Some(63));
+
+		bitmap.set(63);
+		bitmap.clear(5);
+		assert_eq!(bitmap.first_free(), Some(5));
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_bitmap_level_boundary() {
+		// Three levels, the second level is full after 64 * 64 descriptors.
+		let mut bitmap = Bitmap::new(2 * 64 * 64);
+		assert_eq!(bitmap.levels.len(), 3);
+		for index in 0..64 * 64 {
+			bitmap.set(index);
+		}
+		assert_eq!(bitmap.levels[1][0], u64::MAX);
+		assert_eq!(bitmap.levels[2][0], 1);
+		assert_eq!(bitmap.first_free(), Some(64 * 64));
+
+		bitmap.clear(64 * 64 - 1);
+		assert_eq!(bitmap.levels[2][0], 0);
+		assert_eq!(bitmap.levels[1][0], u64::MAX >> 1);
+		assert_eq!(bitmap.first_free(), Some(64 * 64 - 1));
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_bitmap_full() {
+		let mut bitmap = Bitmap::new(64);
+		assert_eq!(bitmap.levels.len(), 1);
+		for index in 0..64 {
+			bitmap.set(index);
+		}
+		assert_eq!(bitmap.first_free(), None);
+		bitmap.clear(0);
+		assert_eq!(bitmap.first_free(), Some(0));
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_fd_table_insert_at_limit() {
+		// 65 descriptors span two words of the bitmap.
+		let table = FdTable::with_limit(65);
+		for fd in 0..65 {
+			assert_eq!(table.insert(dummy()), Ok(fd));
+		}
+		assert_eq!(table.insert(dummy()).err(), Some(Errno::Mfile));
+		assert_eq!(table.insert_at(65, dummy()).err(), Some(Errno::Badf));
+
+		assert!(table.remove(64).is_ok());
+		assert_eq!(table.get(64).err(), Some(Errno::Badf));
+		assert_eq!(table.insert(dummy()), Ok(64));
+		assert_eq!(table.insert(dummy()).err(), Some(Errno::Mfile));
+
+		assert!(table.remove(3).is_ok());
+		assert_eq!(table.insert_at(4, dummy()).err(), Some(Errno::Mfile));
+		assert!(table.insert_at(3, dummy()).is_ok());
+		assert!(table.get(3).is_ok());
+	}
+}
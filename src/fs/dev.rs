@@ -0,0 +1,523 @@
+//! Device files in `/dev`.
+//!
+//! The directory is generated on every access, so that block devices, which
+//! are registered by drivers, appear without further setup.
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::MaybeUninit;
+#[cfg(feature = "block")]
+use core::ops::Range;
+
+use async_lock::Mutex;
+use async_trait::async_trait;
+
+#[cfg(feature = "block")]
+use crate::drivers::block::{self, BlockDeviceRef};
+use crate::entropy::{self, Flags};
+use crate::errno::Errno;
+use crate::fd::stdio::{GenericStdin, GenericStdout};
+use crate::fd::{AccessPermission, ObjectInterface, OpenOption, PollEvent};
+use crate::fs::{
+	DirectoryEntry, FileAttr, FileType, NodeKind, RenameFlags, SeekWhence, StatFs, TMPFS_MAGIC,
+	VfsNode, seek_dir, write_dirents,
+};
+use crate::io;
+
+/// Character devices, which are always available
+const CHARACTER_DEVICES: &[&str] = &["console", "null", "random", "urandom", "zero"];
+
+#[derive(Debug)]
+enum Device {
+	Console,
+	Null,
+	Zero,
+	Random,
+	#[cfg(feature = "block")]
+	Block(BlockDeviceRef),
+}
+
+impl Device {
+	fn lookup(name: &str) -> io::Result<Self> {
+		match name {
+			"console" => Ok(Self::Console),
+			"null" => Ok(Self::Null),
+			"zero" => Ok(Self::Zero),
+			"random" | "urandom" => Ok(Self::Random),
+			#[cfg(feature = "block")]
+			name => Ok(Self::Block(block::get(name).map_err(|_| Errno::Noent)?)),
+			#[cfg(not(feature = "block"))]
+			_ => Err(Errno::Noent),
+		}
+	}
+
+	fn file_attributes(&self) -> FileAttr {
+		match self {
+			#[cfg(feature = "block")]
+			Self::Block(device) => {
+				let device = device.lock();
+				let block_size = device.block_size();
+				let size = device.num_blocks() * u64::try_from(block_size).unwrap();
+				FileAttr {
+					st_mode: AccessPermission::S_IFBLK
+						| AccessPermission::from_bits(0o660).unwrap(),
+					st_size: size.try_into().unwrap(),
+					st_blksize: block_size.try_into().unwrap(),
+					st_blocks: (size / 512).try_into().unwrap(),
+					..Default::default()
+				}
+			}
+			_ => FileAttr {
+				st_mode: AccessPermission::S_IFCHR | AccessPermission::from_bits(0o666).unwrap(),
+				..Default::default()
+			},
+		}
+	}
+
+	fn file_type(&self) -> FileType {
+		match self {
+			#[cfg(feature = "block")]
+			Self::Block(_) => FileType::BlockDevice,
+			_ => FileType::CharacterDevice,
+		}
+	}
+
+	fn into_object(self, opt: OpenOption) -> Arc<async_lock::RwLock<dyn ObjectInterface>> {
+		match self {
+			Self::Console => Arc::new(async_lock::RwLock::new(ConsoleInterface)),
+			Self::Null => Arc::new(async_lock::RwLock::new(NullInterface)),
+			Self::Zero => Arc::new(async_lock::RwLock::new(ZeroInterface)),
+			Self::Random => Arc::new(async_lock::RwLock::new(RandomInterface)),
+			#[cfg(feature = "block")]
+			Self::Block(device) => Arc::new(async_lock::RwLock::new(BlockDeviceInterface {
+				device,
+				pos: Mutex::new(0),
+				direct: opt.contains(OpenOption::O_DIRECT),
+				sync: opt.intersects(OpenOption::O_DSYNC),
+			})),
+		}
+	}
+}
+
+/// Returns the names of all devices.
+fn names() -> Vec<String> {
+	let names = CHARACTER_DEVICES.iter().map(|name| name.to_string());
+	#[cfg(feature = "block")]
+	let names = names.chain(block::names());
+	names.collect()
+}
+
+const READABLE: PollEvent = PollEvent::POLLIN
+	.union(PollEvent::POLLRDNORM)
+	.union(PollEvent::POLLRDBAND);
+const WRITABLE: PollEvent = PollEvent::POLLOUT
+	.union(PollEvent::POLLWRNORM)
+	.union(PollEvent::POLLWRBAND);
+
+/// `/dev/console`, which reads from and writes to the kernel console
+#[derive(Debug)]
+struct ConsoleInterface;
+
+#[async_trait]
+impl ObjectInterface for ConsoleInterface {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		let readable = GenericStdin::new().poll(event).await?;
+		Ok(readable | (event & WRITABLE))
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		GenericStdin::new().read(buf).await
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		GenericStdout::new().write(buf).await
+	}
+
+	async fn isatty(&self) -> io::Result<bool> {
+		Ok(true)
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		Ok(Device::Console.file_attributes())
+	}
+}
+
+/// `/dev/null`, which discards all writes and is always at the end of file
+#[derive(Debug)]
+struct NullInterface;
+
+#[async_trait]
+impl ObjectInterface for NullInterface {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		Ok(event & (READABLE | WRITABLE))
+	}
+
+	async fn read(&self, _buf: &mut [u8]) -> io::Result<usize> {
+		Ok(0)
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		Ok(buf.len())
+	}
+
+	async fn lseek(&self, _offset: isize, _whence: SeekWhence) -> io::Result<isize> {
+		Ok(0)
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		Ok(Device::Null.file_attributes())
+	}
+}
+
+/// `/dev/zero`, which discards all writes and reads as zeros
+#[derive(Debug)]
+struct ZeroInterface;
+
+#[async_trait]
+impl ObjectInterface for ZeroInterface {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		Ok(event & (READABLE | WRITABLE))
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		buf.fill(0);
+		Ok(buf.len())
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		Ok(buf.len())
+	}
+
+	async fn lseek(&self, _offset: isize, _whence: SeekWhence) -> io::Result<isize> {
+		Ok(0)
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		Ok(Device::Zero.file_attributes())
+	}
+}
+
+/// `/dev/random` and `/dev/urandom`, which read from the kernel's entropy pool
+///
+/// Writes are accepted, but do not add entropy to the pool.
+#[derive(Debug)]
+struct RandomInterface;
+
+#[async_trait]
+impl ObjectInterface for RandomInterface {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		Ok(event & (READABLE | WRITABLE))
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let ret = entropy::read(buf, Flags::empty());
+		usize::try_from(ret).map_err(|_| Errno::Nosys)
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		Ok(buf.len())
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		Ok(Device::Random.file_attributes())
+	}
+}
+
+/// A registered block device, which is accessed at byte granularity
+#[cfg(feature = "block")]
+#[derive(Debug)]
+struct BlockDeviceInterface {
+	device: BlockDeviceRef,
+	/// Position within the device
+	pos: Mutex<u64>,
+	/// Transfers go straight to the device without bounce buffers (`O_DIRECT`).
+	direct: bool,
+	/// Writes are flushed to stable storage (`O_SYNC`).
+	sync: bool,
+}
+
+#[cfg(feature = "block")]
+impl BlockDeviceInterface {
+	/// Reads the blocks covering `len` bytes at `pos`.
+	///
+	/// Returns the first block, the blocks, and the range of the requested
+	/// bytes within them, which is clipped to the capacity of the device.
+	fn read_covering_blocks(
+		&self,
+		pos: u64,
+		len: usize,
+	) -> io::Result<(u64, Vec<u8>, Range<usize>)> {
+		let mut device = self.device.lock();
+		let block_size = u64::try_from(device.block_size()).unwrap();
+		let capacity = device.num_blocks() * block_size;
+
+		let end = pos
+			.saturating_add(u64::try_from(len).unwrap())
+			.min(capacity);
+		if pos >= end {
+			return Ok((0, Vec::new(), 0..0));
+		}
+
+		let lba = pos / block_size;
+		let blocks = end.div_ceil(block_size) - lba;
+		let mut buf = vec![0; usize::try_from(blocks * block_size).unwrap()];
+		device.read_blocks(lba, &mut buf)?;
+
+		let start = usize::try_from(pos - lba * block_size).unwrap();
+		let range = start..start + usize::try_from(end - pos).unwrap();
+		Ok((lba, buf, range))
+	}
+
+	/// Checks the alignment of a direct transfer of `len` bytes from or to `addr`
+	/// at `pos` and returns the first block and the number of bytes, which are
+	/// clipped to the capacity of the device.
+	fn direct_request(&self, pos: u64, addr: usize, len: usize) -> io::Result<(u64, usize)> {
+		let device = self.device.lock();
+		block::check_direct(&*device, pos, addr, len)?;
+
+		let block_size = u64::try_from(device.block_size()).unwrap();
+		let capacity = device.num_blocks() * block_size;
+		let len = capacity
+			.saturating_sub(pos)
+			.min(u64::try_from(len).unwrap());
+		Ok((pos / block_size, usize::try_from(len).unwrap()))
+	}
+}
+
+#[cfg(feature = "block")]
+#[async_trait]
+impl ObjectInterface for BlockDeviceInterface {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		Ok(event & (READABLE | WRITABLE))
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let mut pos = self.pos.lock().await;
+
+		if self.direct {
+			let (lba, len) = self.direct_request(*pos, buf.as_ptr().addr(), buf.len())?;
+			if len > 0 {
+				self.device.lock().read_blocks(lba, &mut buf[..len])?;
+			}
+			*pos += u64::try_from(len).unwrap();
+			return Ok(len);
+		}
+
+		let (_, blocks, range) = self.read_covering_blocks(*pos, buf.len())?;
+		let data = &blocks[range];
+		buf[..data.len()].copy_from_slice(data);
+
+		*pos += u64::try_from(data.len()).unwrap();
+		Ok(data.len())
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		let mut pos = self.pos.lock().await;
+
+		let len = if self.direct {
+			let (lba, len) = self.direct_request(*pos, buf.as_ptr().addr(), buf.len())?;
+			if len > 0 {
+				self.device.lock().write_blocks(lba, &buf[..len])?;
+			}
+			len
+		} else {
+			let (lba, mut blocks, range) = self.read_covering_blocks(*pos, buf.len())?;
+			let len = range.len();
+			if len > 0 {
+				// Partially written blocks keep the rest of their content.
+				blocks[range].copy_from_slice(&buf[..len]);
+				self.device.lock().write_blocks(lba, &blocks)?;
+			}
+			len
+		};
+		if len == 0 {
+			return if buf.is_empty() {
+				Ok(0)
+			} else {
+				Err(Errno::Nospc)
+			};
+		}
+		if self.sync {
+			self.device.lock().flush()?;
+		}
+
+		*pos += u64::try_from(len).unwrap();
+		Ok(len)
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		let mut pos = self.pos.lock().await;
+
+		let capacity = {
+			let device = self.device.lock();
+			device.num_blocks() * u64::try_from(device.block_size()).unwrap()
+		};
+		let new_pos = match whence {
+			SeekWhence::Set => offset,
+			SeekWhence::Cur => isize::try_from(*pos).unwrap() + offset,
+			SeekWhence::End => isize::try_from(capacity).unwrap() + offset,
+			_ => return Err(Errno::Inval),
+		};
+		if new_pos < 0 {
+			return Err(Errno::Inval);
+		}
+
+		*pos = new_pos.try_into().unwrap();
+		Ok(new_pos)
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		Ok(Device::Block(self.device.clone()).file_attributes())
+	}
+
+	async fn fsync(&self) -> io::Result<()> {
+		Ok(self.device.lock().flush()?)
+	}
+}
+
+#[derive(Debug)]
+struct DevDirectoryInterface {
+	entries: Vec<(String, FileType)>,
+	read_idx: Mutex<usize>,
+}
+
+#[async_trait]
+impl ObjectInterface for DevDirectoryInterface {
+	async fn getdents(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+		let mut read_idx = self.read_idx.lock().await;
+		write_dirents(
+			buf,
+			&mut read_idx,
+			self.entries
+				.iter()
+				.map(|(name, file_type)| (name.as_str(), 1, *file_type)),
+		)
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		seek_dir(&mut *self.read_idx.lock().await, offset, whence)
+	}
+}
+
+/// Mount point of the device files
+#[derive(Debug)]
+pub(crate) struct DevDirectory;
+
+impl DevDirectory {
+	/// Returns the device, which is referenced by the reversed path components.
+	fn device(components: &[&str]) -> io::Result<Device> {
+		match components {
+			[name] => Device::lookup(name),
+			_ => Err(Errno::Noent),
+		}
+	}
+
+	fn stat(components: &[&str]) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			return Ok(FileAttr {
+				st_mode: AccessPermission::S_IFDIR | AccessPermission::from_bits(0o755).unwrap(),
+				..Default::default()
+			});
+		}
+		Ok(Self::device(components)?.file_attributes())
+	}
+}
+
+impl VfsNode for DevDirectory {
+	fn get_kind(&self) -> NodeKind {
+		NodeKind::Directory
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		Self::stat(&[])
+	}
+
+	fn get_object(&self) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		let entries = names()
+			.into_iter()
+			.filter_map(|name| {
+				let file_type = Device::lookup(&name).ok()?.file_type();
+				Some((name, file_type))
+			})
+			.collect();
+
+		Ok(Arc::new(async_lock::RwLock::new(DevDirectoryInterface {
+			entries,
+			read_idx: Mutex::new(0),
+		})))
+	}
+
+	fn traverse_mkdir(
+		&self,
+		_components: &mut Vec<&str>,
+		_mode: AccessPermission,
+	) -> io::Result<()> {
+		Err(Errno::Perm)
+	}
+
+	fn traverse_rmdir(&self, _components: &mut Vec<&str>) -> io::Result<()> {
+		Err(Errno::Perm)
+	}
+
+	fn traverse_unlink(&self, _components: &mut Vec<&str>) -> io::Result<()> {
+		Err(Errno::Perm)
+	}
+
+	fn traverse_rename(
+		&self,
+		_old: &mut Vec<&str>,
+		_new: &mut Vec<&str>,
+		_flags: RenameFlags,
+	) -> io::Result<()> {
+		Err(Errno::Perm)
+	}
+
+	fn traverse_readdir(&self, components: &mut Vec<&str>) -> io::Result<Vec<DirectoryEntry>> {
+		if !components.is_empty() {
+			Self::device(components)?;
+			return Err(Errno::Notdir);
+		}
+
+		Ok(names().into_iter().map(DirectoryEntry::new).collect())
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		Self::stat(components)
+	}
+
+	/// The device nodes do not occupy any space.
+	fn traverse_statfs(&self, _components: &mut Vec<&str>) -> io::Result<StatFs> {
+		Ok(StatFs {
+			f_type: TMPFS_MAGIC,
+			f_bsize: 4096,
+			f_namelen: 255,
+			..Default::default()
+		})
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		Self::stat(components)
+	}
+
+	fn traverse_open(
+		&self,
+		components: &mut Vec<&str>,
+		opt: OpenOption,
+		_mode: AccessPermission,
+	) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		if components.is_empty() {
+			return self.get_object();
+		}
+
+		match Self::device(components) {
+			Ok(_) if opt.contains(OpenOption::O_CREAT | OpenOption::O_EXCL) => Err(Errno::Exist),
+			Ok(_) if opt.contains(OpenOption::O_DIRECTORY) => Err(Errno::Notdir),
+			Ok(device) => Ok(device.into_object(opt)),
+			// Device files cannot be created by applications.
+			Err(Errno::Noent) if opt.contains(OpenOption::O_CREAT) => Err(Errno::Perm),
+			Err(err) => Err(err),
+		}
+	}
+}
@@ -0,0 +1,1018 @@
+//! Read-only ext2 file system on top of a block device.
+//!
+//! Only the features of the original ext2 format and file types in directory
+//! entries are supported. Thus, images created by `mke2fs -t ext2` can be
+//! mounted, while images that use ext4 features (extents, 64-bit block
+//! numbers, ...) are rejected.
+//!
+//! Symbolic links are followed within the file system. Absolute targets are
+//! resolved relative to the root directory of the file system, as the image
+//! is usually built as the root of a Linux system. Since the file system is
+//! never modified, a file handle keeps a copy of the inode.
+
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::MaybeUninit;
+
+use align_address::Align;
+use async_lock::Mutex;
+use async_trait::async_trait;
+
+use crate::drivers::block::{self, BlockDeviceRef};
+use crate::errno::Errno;
+use crate::executor::block_on;
+use crate::fd::{AccessPermission, ObjectInterface, OpenOption, PollEvent};
+use crate::fs::{
+	DirectoryEntry, EXT2_SUPER_MAGIC, FileAttr, FileType, NodeKind, RenameFlags, ST_RDONLY,
+	SeekWhence, StatFs, VfsNode, seek_dir, write_dirents,
+};
+use crate::io;
+use crate::time::timespec;
+
+/// Byte offset of the superblock on the block device
+const SUPERBLOCK_OFFSET: usize = 1024;
+const SUPERBLOCK_SIZE: usize = 1024;
+const EXT2_MAGIC: u16 = 0xef53;
+const ROOT_INODE: u32 = 2;
+
+/// Inode size of revision 0 file systems
+const GOOD_OLD_INODE_SIZE: usize = 128;
+const GROUP_DESC_SIZE: usize = 32;
+
+/// Directory entries contain the file type.
+const INCOMPAT_FILETYPE: u32 = 0x0002;
+
+const NUM_DIRECT_BLOCKS: u64 = 12;
+const INDIRECT_BLOCK: usize = 12;
+const DOUBLE_INDIRECT_BLOCK: usize = 13;
+const TRIPLE_INDIRECT_BLOCK: usize = 14;
+
+/// Maximum number of symbolic links, which are followed during a lookup
+const MAX_SYMLINKS: usize = 8;
+
+const S_IFMT: u16 = 0o170_000;
+const S_IFDIR: u16 = 0o040_000;
+const S_IFLNK: u16 = 0o120_000;
+
+/// File types of directory entries
+const FT_REG_FILE: u8 = 1;
+const FT_DIR: u8 = 2;
+const FT_SYMLINK: u8 = 7;
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+	u16::from_le_bytes(buf[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+	u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn to_timespec(seconds: u32) -> timespec {
+	timespec {
+		tv_sec: seconds.into(),
+		tv_nsec: 0,
+	}
+}
+
+#[derive(Debug, Clone)]
+struct Inode {
+	number: u32,
+	mode: u16,
+	uid: u32,
+	gid: u32,
+	size: u64,
+	links: u16,
+	/// Number of 512-byte sectors
+	sectors: u32,
+	atime: u32,
+	ctime: u32,
+	mtime: u32,
+	/// Direct blocks followed by the single, double and triple indirect block
+	block: [u32; 15],
+}
+
+impl Inode {
+	fn from_raw(number: u32, raw: &[u8]) -> Self {
+		let mode = read_u16(raw, 0);
+		let mut size = u64::from(read_u32(raw, 4));
+		if mode & S_IFMT != S_IFDIR {
+			size |= u64::from(read_u32(raw, 108)) << 32;
+		}
+
+		Self {
+			number,
+			mode,
+			uid: u32::from(read_u16(raw, 2)) | (u32::from(read_u16(raw, 120)) << 16),
+			gid: u32::from(read_u16(raw, 24)) | (u32::from(read_u16(raw, 122)) << 16),
+			size,
+			links: read_u16(raw, 26),
+			sectors: read_u32(raw, 28),
+			atime: read_u32(raw, 8),
+			ctime: read_u32(raw, 12),
+			mtime: read_u32(raw, 16),
+			block: core::array::from_fn(|i| read_u32(raw, 40 + i * 4)),
+		}
+	}
+
+	fn is_dir(&self) -> bool {
+		self.mode & S_IFMT == S_IFDIR
+	}
+
+	fn is_symlink(&self) -> bool {
+		self.mode & S_IFMT == S_IFLNK
+	}
+
+	/// Short targets of symbolic links are stored in the block array.
+	fn is_fast_symlink(&self) -> bool {
+		self.is_symlink() && self.sectors == 0
+	}
+}
+
+#[derive(Debug, Clone)]
+struct DirEntry {
+	name: String,
+	inode: u32,
+	file_type: FileType,
+}
+
+/// State of a mounted ext2 file system
+#[derive(Debug)]
+struct Ext2Volume {
+	device: BlockDeviceRef,
+	block_size: usize,
+	/// Number of device blocks per file system block
+	device_blocks_per_block: u64,
+	inodes_count: u32,
+	/// Capacity as recorded in the superblock
+	statfs: StatFs,
+	inodes_per_group: u32,
+	inode_size: usize,
+	/// First block of the inode table of each block group
+	inode_tables: Vec<u32>,
+	/// Directory entries contain the file type.
+	filetype: bool,
+	/// The indirect block, which was accessed last
+	indirect_cache: Option<(u32, Vec<u32>)>,
+}
+
+impl Ext2Volume {
+	fn new(device: BlockDeviceRef) -> Result<Self, Errno> {
+		let device_block_size = device.lock().block_size();
+		let start = SUPERBLOCK_OFFSET.align_down(device_block_size);
+		let end = (SUPERBLOCK_OFFSET + SUPERBLOCK_SIZE).align_up(device_block_size);
+		let mut buf = vec![0u8; end - start];
+		device
+			.lock()
+			.read_blocks(u64::try_from(start / device_block_size).unwrap(), &mut buf)?;
+		let sb = &buf[SUPERBLOCK_OFFSET - start..][..SUPERBLOCK_SIZE];
+
+		if read_u16(sb, 56) != EXT2_MAGIC {
+			error!("Block device does not contain an ext2 file system");
+			return Err(Errno::Inval);
+		}
+
+		let inodes_count = read_u32(sb, 0);
+		let blocks_count = read_u32(sb, 4);
+		let r_blocks_count = read_u32(sb, 8);
+		let free_blocks_count = read_u32(sb, 12);
+		let free_inodes_count = read_u32(sb, 16);
+		let first_data_block = read_u32(sb, 20);
+		let log_block_size = read_u32(sb, 24);
+		let blocks_per_group = read_u32(sb, 32);
+		let inodes_per_group = read_u32(sb, 40);
+		let rev_level = read_u32(sb, 76);
+		let (inode_size, feature_incompat) = if rev_level == 0 {
+			(GOOD_OLD_INODE_SIZE, 0)
+		} else {
+			(usize::from(read_u16(sb, 88)), read_u32(sb, 96))
+		};
+
+		if feature_incompat & !INCOMPAT_FILETYPE != 0 {
+			error!("Unsupported ext2 features {feature_incompat:#x}");
+			return Err(Errno::Inval);
+		}
+
+		if log_block_size > 6
+			|| blocks_per_group == 0
+			|| inodes_per_group == 0
+			|| inode_size < GOOD_OLD_INODE_SIZE
+			|| !inode_size.is_power_of_two()
+		{
+			error!("Invalid ext2 superblock");
+			return Err(Errno::Inval);
+		}
+
+		let block_size = 1024 << log_block_size;
+		if inode_size > block_size {
+			error!("Invalid ext2 inode size {inode_size}");
+			return Err(Errno::Inval);
+		}
+		if block_size % device_block_size != 0 {
+			error!(
+				"ext2 block size {block_size} is not a multiple of the block size {device_block_size}"
+			);
+			return Err(Errno::Inval);
+		}
+
+		let mut volume = Self {
+			device,
+			block_size,
+			device_blocks_per_block: u64::try_from(block_size / device_block_size).unwrap(),
+			inodes_count,
+			statfs: StatFs {
+				f_type: EXT2_SUPER_MAGIC,
+				f_bsize: block_size.try_into().unwrap(),
+				f_blocks: blocks_count.into(),
+				f_bfree: free_blocks_count.into(),
+				f_bavail: free_blocks_count.saturating_sub(r_blocks_count).into(),
+				f_files: inodes_count.into(),
+				f_ffree: free_inodes_count.into(),
+				f_namelen: 255,
+				f_flags: ST_RDONLY,
+			},
+			inodes_per_group,
+			inode_size,
+			inode_tables: Vec::new(),
+			filetype: feature_incompat & INCOMPAT_FILETYPE != 0,
+			indirect_cache: None,
+		};
+
+		let num_groups = blocks_count
+			.saturating_sub(first_data_block)
+			.div_ceil(blocks_per_group);
+		let num_groups = usize::try_from(num_groups).unwrap();
+		let table_blocks = (num_groups * GROUP_DESC_SIZE).div_ceil(block_size);
+		let mut table = vec![0u8; table_blocks * block_size];
+		for (i, chunk) in table.chunks_exact_mut(block_size).enumerate() {
+			volume.read_block(first_data_block + 1 + u32::try_from(i).unwrap(), chunk)?;
+		}
+		volume.inode_tables = table
+			.chunks_exact(GROUP_DESC_SIZE)
+			.take(num_groups)
+			.map(|desc| read_u32(desc, 8))
+			.collect();
+
+		Ok(volume)
+	}
+
+	/// Reads the file system block `block`. Block 0 is a hole and reads as zeros.
+	fn read_block(&self, block: u32, buf: &mut [u8]) -> Result<(), Errno> {
+		if block == 0 {
+			buf.fill(0);
+			return Ok(());
+		}
+
+		self.device
+			.lock()
+			.read_blocks(u64::from(block) * self.device_blocks_per_block, buf)
+			.map_err(Errno::from)
+	}
+
+	fn read_inode(&self, number: u32) -> Result<Inode, Errno> {
+		if number == 0 || number > self.inodes_count {
+			return Err(Errno::Io);
+		}
+
+		let group = usize::try_from((number - 1) / self.inodes_per_group).unwrap();
+		let index = usize::try_from((number - 1) % self.inodes_per_group).unwrap();
+		let table = *self.inode_tables.get(group).ok_or(Errno::Io)?;
+		let offset = index * self.inode_size;
+
+		let mut buf = vec![0u8; self.block_size];
+		self.read_block(
+			table + u32::try_from(offset / self.block_size).unwrap(),
+			&mut buf,
+		)?;
+		let offset = offset % self.block_size;
+		Ok(Inode::from_raw(
+			number,
+			&buf[offset..offset + GOOD_OLD_INODE_SIZE],
+		))
+	}
+
+	/// Returns entry `index` of the indirect block `block`.
+	fn indirect_entry(&mut self, block: u32, index: u64) -> Result<u32, Errno> {
+		if block == 0 {
+			return Ok(0);
+		}
+
+		if self
+			.indirect_cache
+			.as_ref()
+			.is_none_or(|(cached, _)| *cached != block)
+		{
+			let mut buf = vec![0u8; self.block_size];
+			self.read_block(block, &mut buf)?;
+			let entries = buf.chunks_exact(4).map(|b| read_u32(b, 0)).collect();
+			self.indirect_cache = Some((block, entries));
+		}
+
+		let (_, entries) = self.indirect_cache.as_ref().unwrap();
+		Ok(entries[usize::try_from(index).unwrap()])
+	}
+
+	/// Returns the file system block, which contains block `index` of the file.
+	fn file_block(&mut self, inode: &Inode, index: u64) -> Result<u32, Errno> {
+		let per_block = u64::try_from(self.block_size / 4).unwrap();
+
+		if index < NUM_DIRECT_BLOCKS {
+			return Ok(inode.block[usize::try_from(index).unwrap()]);
+		}
+
+		let index = index - NUM_DIRECT_BLOCKS;
+		if index < per_block {
+			return self.indirect_entry(inode.block[INDIRECT_BLOCK], index);
+		}
+
+		let index = index - per_block;
+		if index < per_block * per_block {
+			let block =
+				self.indirect_entry(inode.block[DOUBLE_INDIRECT_BLOCK], index / per_block)?;
+			return self.indirect_entry(block, index % per_block);
+		}
+
+		let index = index - per_block * per_block;
+		if index < per_block * per_block * per_block {
+			let block = self.indirect_entry(
+				inode.block[TRIPLE_INDIRECT_BLOCK],
+				index / (per_block * per_block),
+			)?;
+			let block = self.indirect_entry(block, index / per_block % per_block)?;
+			return self.indirect_entry(block, index % per_block);
+		}
+
+		Err(Errno::Fbig)
+	}
+
+	/// Reads from the file `inode` starting at `pos` and returns the number of bytes read.
+	fn read_file(&mut self, inode: &Inode, pos: u64, buf: &mut [u8]) -> Result<usize, Errno> {
+		if pos >= inode.size {
+			return Ok(0);
+		}
+
+		let len = buf
+			.len()
+			.min(usize::try_from(inode.size - pos).unwrap_or(usize::MAX));
+		let block_size = u64::try_from(self.block_size).unwrap();
+		let mut block_buf = vec![0u8; self.block_size];
+		let mut done = 0;
+		while done < len {
+			let offset = pos + u64::try_from(done).unwrap();
+			let block = self.file_block(inode, offset / block_size)?;
+			let start = usize::try_from(offset % block_size).unwrap();
+			let count = (self.block_size - start).min(len - done);
+
+			self.read_block(block, &mut block_buf)?;
+			buf[done..done + count].copy_from_slice(&block_buf[start..start + count]);
+			done += count;
+		}
+
+		Ok(len)
+	}
+
+	/// Returns the entries of the directory `inode` without `.` and `..`.
+	fn read_dir(&mut self, inode: &Inode) -> Result<Vec<DirEntry>, Errno> {
+		if !inode.is_dir() {
+			return Err(Errno::Notdir);
+		}
+
+		let mut entries = Vec::new();
+		let mut buf = vec![0u8; self.block_size];
+		let num_blocks = inode.size.div_ceil(u64::try_from(self.block_size).unwrap());
+		for index in 0..num_blocks {
+			let block = self.file_block(inode, index)?;
+			self.read_block(block, &mut buf)?;
+
+			let mut offset = 0;
+			while offset + 8 <= self.block_size {
+				let number = read_u32(&buf, offset);
+				let rec_len = usize::from(read_u16(&buf, offset + 4));
+				let (name_len, file_type) = if self.filetype {
+					(usize::from(buf[offset + 6]), buf[offset + 7])
+				} else {
+					(usize::from(read_u16(&buf, offset + 6)), 0)
+				};
+
+				if rec_len < 8 || offset + rec_len > self.block_size || 8 + name_len > rec_len {
+					error!("Corrupted ext2 directory (inode {})", inode.number);
+					return Err(Errno::Io);
+				}
+
+				let name = &buf[offset + 8..offset + 8 + name_len];
+				if number != 0 && name != b"." && name != b".." {
+					let file_type = match file_type {
+						FT_REG_FILE => FileType::RegularFile,
+						FT_DIR => FileType::Directory,
+						FT_SYMLINK => FileType::SymbolicLink,
+						_ => FileType::Unknown,
+					};
+					entries.push(DirEntry {
+						name: String::from_utf8_lossy(name).into_owned(),
+						inode: number,
+						file_type,
+					});
+				}
+
+				offset += rec_len;
+			}
+		}
+
+		Ok(entries)
+	}
+
+	fn find_entry(&mut self, dir: &Inode, name: &str) -> Result<Option<u32>, Errno> {
+		Ok(self
+			.read_dir(dir)?
+			.into_iter()
+			.find(|entry| entry.name == name)
+			.map(|entry| entry.inode))
+	}
+
+	fn read_link(&mut self, inode: &Inode) -> Result<String, Errno> {
+		let target = if inode.is_fast_symlink() {
+			let mut raw = inode
+				.block
+				.iter()
+				.flat_map(|block| block.to_le_bytes())
+				.collect::<Vec<_>>();
+			raw.truncate(usize::try_from(inode.size).unwrap().min(raw.len()));
+			raw
+		} else {
+			let mut raw = vec![0u8; usize::try_from(inode.size).map_err(|_| Errno::Io)?];
+			let len = self.read_file(inode, 0, &mut raw)?;
+			raw.truncate(len);
+			raw
+		};
+
+		String::from_utf8(target).map_err(|_| Errno::Io)
+	}
+
+	/// Returns the inode of `path`. If `follow` is set, a symbolic link at
+	/// the end of the path is resolved as well.
+	fn lookup(&mut self, path: &[&str], follow: bool) -> Result<Inode, Errno> {
+		let mut pending = path
+			.iter()
+			.rev()
+			.map(|component| (*component).to_owned())
+			.collect::<Vec<_>>();
+		// The directories from the root to the current one
+		let mut dirs = vec![self.read_inode(ROOT_INODE)?];
+		let mut symlinks = 0;
+
+		while let Some(component) = pending.pop() {
+			let dir = dirs.last().unwrap();
+			if !dir.is_dir() {
+				return Err(Errno::Notdir);
+			}
+
+			match component.as_str() {
+				"" | "." => continue,
+				".." => {
+					if dirs.len() > 1 {
+						dirs.pop();
+					}
+					continue;
+				}
+				_ => {}
+			}
+
+			let number = self.find_entry(dir, &component)?.ok_or(Errno::Noent)?;
+			let inode = self.read_inode(number)?;
+			if inode.is_symlink() && (follow || !pending.is_empty()) {
+				symlinks += 1;
+				if symlinks > MAX_SYMLINKS {
+					return Err(Errno::Loop);
+				}
+
+				let target = self.read_link(&inode)?;
+				if target.starts_with('/') {
+					dirs.truncate(1);
+				}
+				pending.extend(target.split('/').rev().map(ToOwned::to_owned));
+				continue;
+			}
+
+			dirs.push(inode);
+		}
+
+		Ok(dirs.pop().unwrap())
+	}
+
+	/// Returns the attributes of `inode` or `EOVERFLOW`, if its size on the
+	/// disk does not fit into `st_size`.
+	fn file_attributes(&self, inode: &Inode) -> io::Result<FileAttr> {
+		Ok(FileAttr {
+			st_ino: inode.number.into(),
+			st_nlink: inode.links.into(),
+			st_mode: AccessPermission::from_bits_truncate(inode.mode.into()),
+			st_uid: inode.uid,
+			st_gid: inode.gid,
+			st_size: inode.size.try_into().map_err(|_| Errno::Overflow)?,
+			st_blksize: self.block_size.try_into().unwrap(),
+			st_blocks: inode.sectors.into(),
+			st_atim: to_timespec(inode.atime),
+			st_mtim: to_timespec(inode.mtime),
+			st_ctim: to_timespec(inode.ctime),
+			..Default::default()
+		})
+	}
+}
+
+#[derive(Debug)]
+struct Ext2FileInterface {
+	volume: Arc<Mutex<Ext2Volume>>,
+	inode: Inode,
+	/// Position within the file
+	pos: Mutex<usize>,
+}
+
+#[async_trait]
+impl ObjectInterface for Ext2FileInterface {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		Ok(event & (PollEvent::POLLIN | PollEvent::POLLRDNORM | PollEvent::POLLRDBAND))
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let mut volume = self.volume.lock().await;
+		let mut pos = self.pos.lock().await;
+
+		let len = volume.read_file(&self.inode, u64::try_from(*pos).unwrap(), buf)?;
+		*pos += len;
+		Ok(len)
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		let mut pos = self.pos.lock().await;
+
+		let base = match whence {
+			SeekWhence::Set => 0,
+			SeekWhence::Cur => isize::try_from(*pos).map_err(|_| Errno::Overflow)?,
+			SeekWhence::End => isize::try_from(self.inode.size).map_err(|_| Errno::Overflow)?,
+			_ => return Err(Errno::Inval),
+		};
+		let new_pos = base.checked_add(offset).ok_or(Errno::Overflow)?;
+		if new_pos < 0 {
+			return Err(Errno::Inval);
+		}
+
+		*pos = new_pos.try_into().unwrap();
+		Ok(new_pos)
+	}
+
+	async fn pread(&self, buf: &mut [u8], offset: usize) -> io::Result<usize> {
+		let mut volume = self.volume.lock().await;
+		volume.read_file(&self.inode, u64::try_from(offset).unwrap(), buf)
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		self.volume.lock().await.file_attributes(&self.inode)
+	}
+
+	/// The volume is read-only, so there is nothing to write back.
+	async fn fsync(&self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+#[derive(Debug)]
+struct Ext2DirectoryInterface {
+	entries: Vec<DirEntry>,
+	read_idx: Mutex<usize>,
+}
+
+#[async_trait]
+impl ObjectInterface for Ext2DirectoryInterface {
+	async fn getdents(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+		let mut read_idx = self.read_idx.lock().await;
+		write_dirents(
+			buf,
+			&mut read_idx,
+			self.entries
+				.iter()
+				.map(|entry| (entry.name.as_str(), u64::from(entry.inode), entry.file_type)),
+		)
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		seek_dir(&mut *self.read_idx.lock().await, offset, whence)
+	}
+}
+
+/// Mount point of an ext2 file system
+#[derive(Debug)]
+pub(crate) struct Ext2Directory {
+	volume: Arc<Mutex<Ext2Volume>>,
+}
+
+impl Ext2Directory {
+	/// Converts the reversed path components of the VFS into a path relative to
+	/// the mount point.
+	fn path<'a>(components: &[&'a str]) -> Vec<&'a str> {
+		components.iter().rev().copied().collect()
+	}
+
+	fn directory_object(
+		volume: &mut Ext2Volume,
+		inode: &Inode,
+	) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		Ok(Arc::new(async_lock::RwLock::new(Ext2DirectoryInterface {
+			entries: volume.read_dir(inode)?,
+			read_idx: Mutex::new(0),
+		})))
+	}
+
+	async fn async_traverse_open(
+		&self,
+		path: &[&str],
+		opt: OpenOption,
+	) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		let mut volume = self.volume.lock().await;
+		let inode = match volume.lookup(path, true) {
+			Ok(_) if opt.contains(OpenOption::O_CREAT | OpenOption::O_EXCL) => {
+				return Err(Errno::Exist);
+			}
+			Ok(inode) => inode,
+			Err(Errno::Noent) if opt.contains(OpenOption::O_CREAT) => return Err(Errno::Rofs),
+			Err(err) => return Err(err),
+		};
+
+		if inode.is_dir() {
+			if opt.intersects(OpenOption::O_WRONLY | OpenOption::O_RDWR) {
+				return Err(Errno::Isdir);
+			}
+			return Self::directory_object(&mut volume, &inode);
+		}
+		if opt.contains(OpenOption::O_DIRECTORY) {
+			return Err(Errno::Notdir);
+		}
+		if opt.intersects(OpenOption::O_WRONLY | OpenOption::O_RDWR | OpenOption::O_TRUNC) {
+			return Err(Errno::Rofs);
+		}
+
+		Ok(Arc::new(async_lock::RwLock::new(Ext2FileInterface {
+			volume: self.volume.clone(),
+			inode,
+			pos: Mutex::new(0),
+		})))
+	}
+
+	fn stat(&self, components: &[&str], follow: bool) -> io::Result<FileAttr> {
+		let path = Self::path(components);
+		block_on(
+			async {
+				let mut volume = self.volume.lock().await;
+				let inode = volume.lookup(&path, follow)?;
+				volume.file_attributes(&inode)
+			},
+			None,
+		)
+	}
+}
+
+impl VfsNode for Ext2Directory {
+	fn get_kind(&self) -> NodeKind {
+		NodeKind::Directory
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		self.stat(&[], true)
+	}
+
+	fn get_object(&self) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		block_on(
+			async {
+				let mut volume = self.volume.lock().await;
+				let root = volume.read_inode(ROOT_INODE)?;
+				Self::directory_object(&mut volume, &root)
+			},
+			None,
+		)
+	}
+
+	fn traverse_mkdir(
+		&self,
+		_components: &mut Vec<&str>,
+		_mode: AccessPermission,
+	) -> io::Result<()> {
+		Err(Errno::Rofs)
+	}
+
+	fn traverse_rmdir(&self, _components: &mut Vec<&str>) -> io::Result<()> {
+		Err(Errno::Rofs)
+	}
+
+	fn traverse_unlink(&self, _components: &mut Vec<&str>) -> io::Result<()> {
+		Err(Errno::Rofs)
+	}
+
+	fn traverse_rename(
+		&self,
+		_old: &mut Vec<&str>,
+		_new: &mut Vec<&str>,
+		_flags: RenameFlags,
+	) -> io::Result<()> {
+		Err(Errno::Rofs)
+	}
+
+	fn traverse_readdir(&self, components: &mut Vec<&str>) -> io::Result<Vec<DirectoryEntry>> {
+		let path = Self::path(components);
+		block_on(
+			async {
+				let mut volume = self.volume.lock().await;
+				let inode = volume.lookup(&path, true)?;
+				Ok(volume
+					.read_dir(&inode)?
+					.into_iter()
+					.map(|entry| DirectoryEntry::new(entry.name))
+					.collect())
+			},
+			None,
+		)
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		self.stat(components, false)
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		self.stat(components, true)
+	}
+
+	fn traverse_statfs(&self, _components: &mut Vec<&str>) -> io::Result<StatFs> {
+		block_on(async { Ok(self.volume.lock().await.statfs) }, None)
+	}
+
+	fn traverse_readlink(&self, components: &mut Vec<&str>) -> io::Result<String> {
+		let path = Self::path(components);
+		block_on(
+			async {
+				let mut volume = self.volume.lock().await;
+				let inode = volume.lookup(&path, false)?;
+				if !inode.is_symlink() {
+					return Err(Errno::Inval);
+				}
+				volume.read_link(&inode)
+			},
+			None,
+		)
+	}
+
+	fn traverse_open(
+		&self,
+		components: &mut Vec<&str>,
+		opt: OpenOption,
+		_mode: AccessPermission,
+	) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		let path = Self::path(components);
+		block_on(self.async_traverse_open(&path, opt), None)
+	}
+}
+
+/// Opens the ext2 file system on the block device `name`.
+pub(crate) fn open(name: &str) -> io::Result<Box<dyn VfsNode + Send + Sync>> {
+	let device = block::get(name)?;
+	let volume = Ext2Volume::new(device)?;
+	info!(
+		"ext2 file system on {name}: {} block groups, blocks of {} bytes",
+		volume.inode_tables.len(),
+		volume.block_size
+	);
+
+	Ok(Box::new(Ext2Directory {
+		volume: Arc::new(Mutex::new(volume)),
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use hermit_sync::InterruptTicketMutex;
+
+	use super::*;
+	use crate::drivers::block::RamDisk;
+
+	const BLOCK_SIZE: usize = 1024;
+	const INODE_TABLE: usize = 3;
+	const BIG: u32 = 11;
+	const DIR: u32 = 12;
+	const LINK: u32 = 13;
+	const SLOW_LINK: u32 = 14;
+	/// The file `big` consists of 13 blocks with the hole at index 5, the last
+	/// one is reached through the indirect block.
+	const BIG_SIZE: usize = 13 * BLOCK_SIZE - 100;
+
+	fn write_u16(image: &mut [u8], offset: usize, value: u16) {
+		image[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+	}
+
+	fn write_u32(image: &mut [u8], offset: usize, value: u32) {
+		image[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+	}
+
+	fn write_inode(image: &mut [u8], number: u32, mode: u16, size: usize, blocks: &[u32]) {
+		let offset = INODE_TABLE * BLOCK_SIZE + usize::try_from(number - 1).unwrap() * 128;
+		write_u16(image, offset, mode);
+		write_u32(image, offset + 4, u32::try_from(size).unwrap());
+		write_u16(image, offset + 26, 1);
+		let sectors = blocks.iter().filter(|block| **block != 0).count() * BLOCK_SIZE / 512;
+		write_u32(image, offset + 28, u32::try_from(sectors).unwrap());
+		for (i, block) in blocks.iter().enumerate() {
+			write_u32(image, offset + 40 + i * 4, *block);
+		}
+	}
+
+	fn write_dir(image: &mut [u8], block: usize, entries: &[(u32, u8, &str)]) {
+		let mut offset = block * BLOCK_SIZE;
+		for (i, (inode, file_type, name)) in entries.iter().enumerate() {
+			let rec_len = if i == entries.len() - 1 {
+				(block + 1) * BLOCK_SIZE - offset
+			} else {
+				(8 + name.len()).next_multiple_of(4)
+			};
+			write_u32(image, offset, *inode);
+			write_u16(image, offset + 4, u16::try_from(rec_len).unwrap());
+			image[offset + 6] = u8::try_from(name.len()).unwrap();
+			image[offset + 7] = *file_type;
+			image[offset + 8..offset + 8 + name.len()].copy_from_slice(name.as_bytes());
+			offset += rec_len;
+		}
+	}
+
+	/// Returns an image with the root directory, the directory `dir` with the
+	/// file `big`, the fast symbolic link `link` to `dir/big`, and the symbolic
+	/// link `slow` to `/dir`, whose target is stored in a block.
+	fn image() -> Vec<u8> {
+		let mut image = vec![0u8; 32 * BLOCK_SIZE];
+		let sb = BLOCK_SIZE;
+		write_u32(&mut image, sb, 16);
+		write_u32(&mut image, sb + 4, 32);
+		write_u32(&mut image, sb + 20, 1);
+		write_u32(&mut image, sb + 32, 8192);
+		write_u32(&mut image, sb + 40, 16);
+		write_u16(&mut image, sb + 56, EXT2_MAGIC);
+		write_u32(&mut image, sb + 76, 1);
+		write_u16(&mut image, sb + 88, 128);
+		write_u32(&mut image, sb + 96, INCOMPAT_FILETYPE);
+		// The group descriptor
+		write_u32(
+			&mut image,
+			2 * BLOCK_SIZE + 8,
+			u32::try_from(INODE_TABLE).unwrap(),
+		);
+
+		write_inode(&mut image, ROOT_INODE, S_IFDIR | 0o755, BLOCK_SIZE, &[5]);
+		write_dir(
+			&mut image,
+			5,
+			&[
+				(ROOT_INODE, FT_DIR, "."),
+				(ROOT_INODE, FT_DIR, ".."),
+				(DIR, FT_DIR, "dir"),
+				(LINK, FT_SYMLINK, "link"),
+				(SLOW_LINK, FT_SYMLINK, "slow"),
+			],
+		);
+		write_inode(&mut image, DIR, S_IFDIR | 0o755, BLOCK_SIZE, &[6]);
+		write_dir(
+			&mut image,
+			6,
+			&[
+				(DIR, FT_DIR, "."),
+				(ROOT_INODE, FT_DIR, ".."),
+				(BIG, FT_REG_FILE, "big"),
+			],
+		);
+
+		// The target of a fast symbolic link is stored in the block array.
+		let target = b"dir/big\0";
+		let blocks = [
+			u32::from_le_bytes(target[..4].try_into().unwrap()),
+			u32::from_le_bytes(target[4..].try_into().unwrap()),
+		];
+		let offset = INODE_TABLE * BLOCK_SIZE + usize::try_from(LINK - 1).unwrap() * 128;
+		write_inode(&mut image, LINK, S_IFLNK | 0o777, 7, &[]);
+		write_u32(&mut image, offset + 40, blocks[0]);
+		write_u32(&mut image, offset + 44, blocks[1]);
+
+		write_inode(&mut image, SLOW_LINK, S_IFLNK | 0o777, 4, &[7]);
+		image[7 * BLOCK_SIZE..7 * BLOCK_SIZE + 4].copy_from_slice(b"/dir");
+
+		// The data blocks 10 to 21 and the indirect block 9
+		let mut blocks: Vec<u32> = (10..22).collect();
+		blocks[5] = 0;
+		blocks.extend([9, 0, 0]);
+		write_inode(&mut image, BIG, 0o100_644, BIG_SIZE, &blocks);
+		write_u32(&mut image, 9 * BLOCK_SIZE, 22);
+		for (index, block) in (10..23).enumerate() {
+			if index != 5 {
+				let start = block * BLOCK_SIZE;
+				image[start..start + BLOCK_SIZE].fill(u8::try_from(index + 1).unwrap());
+			}
+		}
+
+		image
+	}
+
+	fn volume(image: Vec<u8>) -> Result<Ext2Volume, Errno> {
+		let device: BlockDeviceRef = Arc::new(InterruptTicketMutex::new(RamDisk::new(512, image)));
+		Ext2Volume::new(device)
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_ext2_read_file() {
+		let mut volume = volume(image()).unwrap();
+		let big = volume.lookup(&["dir", "big"], true).unwrap();
+		assert_eq!(big.number, BIG);
+		assert_eq!(big.size, u64::try_from(BIG_SIZE).unwrap());
+
+		let mut buf = vec![0u8; 2 * BLOCK_SIZE];
+		assert_eq!(volume.read_file(&big, 0, &mut buf), Ok(buf.len()));
+		assert!(buf[..BLOCK_SIZE].iter().all(|byte| *byte == 1));
+		assert!(buf[BLOCK_SIZE..].iter().all(|byte| *byte == 2));
+
+		// The hole reads as zeros.
+		let pos = u64::try_from(5 * BLOCK_SIZE - 2).unwrap();
+		assert_eq!(volume.read_file(&big, pos, &mut buf[..4]), Ok(4));
+		assert_eq!(buf[..4], [5, 5, 0, 0]);
+
+		// The last block is reached through the indirect block.
+		let pos = u64::try_from(12 * BLOCK_SIZE - 2).unwrap();
+		assert_eq!(volume.read_file(&big, pos, &mut buf), Ok(BLOCK_SIZE - 98));
+		assert_eq!(buf[..3], [12, 12, 13]);
+		let pos = u64::try_from(BIG_SIZE).unwrap();
+		assert_eq!(volume.read_file(&big, pos, &mut buf), Ok(0));
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_ext2_lookup() {
+		let mut volume = volume(image()).unwrap();
+		let root = volume.read_inode(ROOT_INODE).unwrap();
+		let names: Vec<String> = volume
+			.read_dir(&root)
+			.unwrap()
+			.into_iter()
+			.map(|entry| entry.name)
+			.collect();
+		assert_eq!(names, ["dir", "link", "slow"]);
+
+		assert!(volume.lookup(&["link"], false).unwrap().is_fast_symlink());
+		assert_eq!(volume.lookup(&["link"], true).unwrap().number, BIG);
+		assert_eq!(
+			volume
+				.read_link(&volume.read_inode(SLOW_LINK).unwrap())
+				.unwrap(),
+			"/dir"
+		);
+		assert_eq!(volume.lookup(&["slow", "big"], false).unwrap().number, BIG);
+		assert_eq!(
+			volume
+				.lookup(&["dir", "..", "dir", ".", "big"], true)
+				.unwrap()
+				.number,
+			BIG
+		);
+		assert_eq!(volume.lookup(&["missing"], true).err(), Some(Errno::Noent));
+		assert_eq!(
+			volume.lookup(&["link", "file"], true).err(),
+			Some(Errno::Notdir)
+		);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_ext2_corrupt_image() {
+		let mut magic = image();
+		write_u16(&mut magic, BLOCK_SIZE + 56, 0);
+		assert_eq!(volume(magic).err(), Some(Errno::Inval));
+
+		// Extents of ext4
+		let mut extents = image();
+		write_u32(&mut extents, BLOCK_SIZE + 96, INCOMPAT_FILETYPE | 0x40);
+		assert_eq!(volume(extents).err(), Some(Errno::Inval));
+
+		let mut directory = image();
+		write_u16(&mut directory, 6 * BLOCK_SIZE + 4, 4);
+		let mut volume = volume(directory).unwrap();
+		assert_eq!(volume.lookup(&["dir", "big"], true).err(), Some(Errno::Io));
+		assert_eq!(volume.read_inode(17).err(), Some(Errno::Io));
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_ext2_oversized_file() {
+		let mut volume = volume(image()).unwrap();
+		let mut big = volume.lookup(&["dir", "big"], true).unwrap();
+		assert_eq!(
+			volume.file_attributes(&big).unwrap().st_size,
+			i64::try_from(BIG_SIZE).unwrap()
+		);
+
+		big.size = u64::MAX;
+		assert_eq!(volume.file_attributes(&big).err(), Some(Errno::Overflow));
+	}
+}
@@ -0,0 +1,1875 @@
+//! FAT32 file system on top of a block device.
+//!
+//! Files and directories are accessed with long file names (VFAT). A short
+//! name is generated for every new entry, so that the volume stays readable
+//! by implementations that only support 8.3 names. Names are compared
+//! case-insensitively for ASCII characters.
+//!
+//! All accesses to a volume are serialized by a single lock. A file handle
+//! only remembers the location of its directory entry and reads the size and
+//! the first cluster from the entry on every access. Thus, all handles of the
+//! same file stay consistent. Unlinking or renaming a file, which is still
+//! open, invalidates its handles.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::mem::MaybeUninit;
+
+use async_lock::Mutex;
+use async_trait::async_trait;
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
+
+use crate::drivers::block::{self, BlockDeviceRef};
+use crate::errno::Errno;
+use crate::executor::block_on;
+use crate::fd::{AccessPermission, FallocateMode, ObjectInterface, OpenOption, PollEvent};
+use crate::fs::{
+	DirectoryEntry, FileAttr, FileType, MSDOS_SUPER_MAGIC, NodeKind, RenameFlags, SeekWhence,
+	StatFs, VfsNode, seek_dir, write_dirents,
+};
+use crate::time::timespec;
+use crate::{arch, io};
+
+const DIR_ENTRY_SIZE: usize = 32;
+
+const ATTR_READ_ONLY: u8 = 0x01;
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_ARCHIVE: u8 = 0x20;
+/// Attributes of an entry, which holds a part of a long file name
+const ATTR_LONG_NAME: u8 = 0x0f;
+const ATTR_LONG_NAME_MASK: u8 = 0x3f;
+
+/// Flag of the ordinal of the last part of a long file name
+const LAST_LONG_ENTRY: u8 = 0x40;
+/// First byte of a deleted directory entry
+const DELETED_ENTRY: u8 = 0xe5;
+/// Flags of the short name, which indicate a lower case base name and extension
+const LOWER_CASE_BASE: u8 = 0x08;
+const LOWER_CASE_EXT: u8 = 0x10;
+
+/// Only the lower 28 bits of a FAT entry are used
+const CLUSTER_MASK: u32 = 0x0fff_ffff;
+const BAD_CLUSTER: u32 = 0x0fff_fff7;
+/// FAT entries starting from this value mark the end of a cluster chain
+const END_OF_CHAIN: u32 = 0x0fff_fff8;
+const FREE_CLUSTER: u32 = 0;
+/// Number of the first data cluster
+const FIRST_CLUSTER: u32 = 2;
+
+/// Largest number of UTF-16 code units of a long file name
+const MAX_NAME_LEN: usize = 255;
+/// Number of UTF-16 code units, which are stored in one long name entry
+const LONG_NAME_CHARS: usize = 13;
+/// Offsets of the UTF-16 code units within a long name entry
+const LONG_NAME_OFFSETS: [usize; LONG_NAME_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+const FSINFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FSINFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+/// Value of the FSInfo hints, if they are unknown
+const FSINFO_UNKNOWN: u32 = 0xffff_ffff;
+
+/// The largest file size, which can be stored in a directory entry
+const MAX_FILE_SIZE: u64 = 0xffff_ffff;
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+	u16::from_le_bytes(buf[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+	u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn write_u16(buf: &mut [u8], offset: usize, value: u16) {
+	buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(buf: &mut [u8], offset: usize, value: u32) {
+	buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn now() -> OffsetDateTime {
+	let microseconds = arch::kernel::systemtime::now_micros();
+	OffsetDateTime::from_unix_timestamp_nanos(i128::from(microseconds) * 1000).unwrap()
+}
+
+/// Converts a point in time into a FAT date and time.
+fn to_fat_time(t: OffsetDateTime) -> (u16, u16) {
+	if t.year() < 1980 {
+		return ((1 << 5) | 1, 0);
+	}
+
+	let year = u16::try_from(t.year() - 1980).unwrap_or(127).min(127);
+	let date = (year << 9) | (u16::from(u8::from(t.month())) << 5) | u16::from(t.day());
+	let time =
+		(u16::from(t.hour()) << 11) | (u16::from(t.minute()) << 5) | u16::from(t.second() / 2);
+	(date, time)
+}
+
+/// Converts a FAT date and time into a timespec.
+fn from_fat_time(date: u16, time: u16) -> timespec {
+	let year = 1980 + i32::from(date >> 9);
+	let Ok(month) = Month::try_from(u8::try_from((date >> 5) & 0xf).unwrap()) else {
+		return timespec::default();
+	};
+	let day = u8::try_from(date & 0x1f).unwrap();
+	let hour = u8::try_from(time >> 11).unwrap();
+	let minute = u8::try_from((time >> 5) & 0x3f).unwrap();
+	let second = u8::try_from((time & 0x1f) * 2).unwrap();
+
+	match (
+		Date::from_calendar_date(year, month, day),
+		Time::from_hms(hour, minute, second),
+	) {
+		(Ok(date), Ok(time)) => timespec {
+			tv_sec: PrimitiveDateTime::new(date, time)
+				.assume_utc()
+				.unix_timestamp(),
+			tv_nsec: 0,
+		},
+		_ => timespec::default(),
+	}
+}
+
+/// Checksum of a short name, which is stored in the corresponding long name entries
+fn checksum(short_name: &[u8; 11]) -> u8 {
+	short_name
+		.iter()
+		.fold(0u8, |sum, c| sum.rotate_right(1).wrapping_add(*c))
+}
+
+/// Characters, which are not allowed in a long file name
+fn is_invalid_char(c: char) -> bool {
+	c < ' ' || "\"*/:<>?\\|".contains(c)
+}
+
+/// Characters, which are allowed in a short name besides upper case letters and digits
+fn is_short_name_char(c: char) -> bool {
+	c.is_ascii_uppercase() || c.is_ascii_digit() || "!#$%&'()-@^_`{}~".contains(c)
+}
+
+/// Returns the short name, if `name` is a valid upper case 8.3 name.
+fn exact_short_name(name: &str) -> Option<[u8; 11]> {
+	let (base, ext) = name.split_once('.').unwrap_or((name, ""));
+	if base.is_empty()
+		|| base.len() > 8
+		|| ext.len() > 3
+		|| !base.chars().chain(ext.chars()).all(is_short_name_char)
+	{
+		return None;
+	}
+
+	let mut short_name = [b' '; 11];
+	short_name[..base.len()].copy_from_slice(base.as_bytes());
+	short_name[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+	Some(short_name)
+}
+
+/// Generates a unique short name for `name`, which is stored next to its long name.
+fn generate_short_name(name: &str, used: &[[u8; 11]]) -> Result<[u8; 11], Errno> {
+	let convert = |s: &str, len: usize| -> Vec<u8> {
+		s.chars()
+			.filter(|c| *c != ' ' && *c != '.')
+			.map(|c| c.to_ascii_uppercase())
+			.map(|c| if is_short_name_char(c) { c as u8 } else { b'_' })
+			.take(len)
+			.collect()
+	};
+
+	let (base, ext) = match name.rsplit_once('.') {
+		Some((base, ext)) if !base.is_empty() => (convert(base, 8), convert(ext, 3)),
+		_ => (convert(name, 8), Vec::new()),
+	};
+
+	let mut short_name = [b' '; 11];
+	short_name[8..8 + ext.len()].copy_from_slice(&ext);
+
+	for n in 1..1_000_000u32 {
+		let tail = format!("~{n}");
+		let base_len = base.len().min(8 - tail.len());
+
+		short_name[..8].fill(b' ');
+		short_name[..base_len].copy_from_slice(&base[..base_len]);
+		short_name[base_len..base_len + tail.len()].copy_from_slice(tail.as_bytes());
+
+		if !used.contains(&short_name) {
+			return Ok(short_name);
+		}
+	}
+
+	Err(Errno::Exist)
+}
+
+/// Converts a short name into a string, taking the lower case flags into account.
+fn short_name_to_string(short_name: &[u8; 11], flags: u8) -> String {
+	// A leading 0x05 stands for the character 0xe5, which marks deleted entries
+	let mut short_name = *short_name;
+	if short_name[0] == 0x05 {
+		short_name[0] = DELETED_ENTRY;
+	}
+
+	let convert = |bytes: &[u8], lower: bool| -> String {
+		bytes
+			.iter()
+			.copied()
+			.map(char::from)
+			.map(|c| if lower { c.to_ascii_lowercase() } else { c })
+			.collect::<String>()
+			.trim_end()
+			.into()
+	};
+
+	let mut name = convert(&short_name[..8], flags & LOWER_CASE_BASE != 0);
+	let ext = convert(&short_name[8..], flags & LOWER_CASE_EXT != 0);
+	if !ext.is_empty() {
+		name.push('.');
+		name.push_str(&ext);
+	}
+	name
+}
+
+/// Location of a directory entry: the cluster of the directory and the index
+/// of the entry within this cluster
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct EntryLocation {
+	cluster: u32,
+	index: usize,
+}
+
+/// Returns the short entry `raw` with the attributes, the timestamps, the
+/// first cluster and the size of `from`. The name is kept.
+fn with_content(
+	mut raw: [u8; DIR_ENTRY_SIZE],
+	from: &[u8; DIR_ENTRY_SIZE],
+) -> [u8; DIR_ENTRY_SIZE] {
+	// Byte 12 contains the case of the short name.
+	raw[11] = from[11];
+	raw[13..].copy_from_slice(&from[13..]);
+	raw
+}
+
+/// A file or directory, which is described by a short directory entry and
+/// its preceding long name entries
+#[derive(Debug, Clone)]
+struct DirEntry {
+	name: String,
+	short_name: [u8; 11],
+	attr: u8,
+	first_cluster: u32,
+	size: u32,
+	raw: [u8; DIR_ENTRY_SIZE],
+	/// Locations of all entries, which belong to this file. The short entry is the last one.
+	slots: Vec<EntryLocation>,
+}
+
+impl DirEntry {
+	fn from_raw(
+		raw: [u8; DIR_ENTRY_SIZE],
+		name: Option<String>,
+		slots: Vec<EntryLocation>,
+	) -> Self {
+		let short_name: [u8; 11] = raw[..11].try_into().unwrap();
+		let first_cluster =
+			((u32::from(read_u16(&raw, 20)) << 16) | u32::from(read_u16(&raw, 26))) & CLUSTER_MASK;
+
+		Self {
+			name: name.unwrap_or_else(|| short_name_to_string(&short_name, raw[12])),
+			short_name,
+			attr: raw[11],
+			first_cluster,
+			size: read_u32(&raw, 28),
+			raw,
+			slots,
+		}
+	}
+
+	fn location(&self) -> EntryLocation {
+		*self.slots.last().unwrap()
+	}
+
+	fn is_dir(&self) -> bool {
+		self.attr & ATTR_DIRECTORY != 0
+	}
+}
+
+/// Position in the cluster chain of an open file, from which the next read or
+/// write continues instead of walking the chain from its start
+#[derive(Debug, Copy, Clone)]
+struct ClusterCursor {
+	/// First cluster of the chain
+	first: u32,
+	/// [`FatVolume::generation`], when the cursor has been set
+	generation: u64,
+	/// Index of `cluster` within the chain
+	index: u64,
+	cluster: u32,
+}
+
+/// State of a mounted FAT32 volume
+#[derive(Debug)]
+struct FatVolume {
+	device: BlockDeviceRef,
+	/// Number of device blocks per FAT sector
+	blocks_per_sector: u64,
+	bytes_per_sector: usize,
+	sectors_per_cluster: u64,
+	/// First sector of the first FAT
+	fat_start: u64,
+	/// Number of sectors of one FAT
+	fat_sectors: u64,
+	num_fats: u64,
+	/// First sector of the data region, which starts with cluster 2
+	data_start: u64,
+	/// Number of data clusters
+	cluster_count: u32,
+	root_cluster: u32,
+	fsinfo_sector: Option<u64>,
+	free_count: Option<u32>,
+	next_free: u32,
+	/// The FAT sector, which was accessed last
+	fat_cache: Option<(u64, Vec<u8>)>,
+	/// Incremented, whenever clusters are freed, so that the cursors of the
+	/// open files become stale
+	generation: u64,
+}
+
+impl FatVolume {
+	fn new(device: BlockDeviceRef) -> Result<Self, Errno> {
+		let block_size = device.lock().block_size();
+		let mut boot_sector = vec![0u8; block_size.max(512).next_multiple_of(block_size)];
+		device.lock().read_blocks(0, &mut boot_sector)?;
+
+		let bytes_per_sector = usize::from(read_u16(&boot_sector, 11));
+		let sectors_per_cluster = u64::from(boot_sector[13]);
+		let reserved_sectors = u64::from(read_u16(&boot_sector, 14));
+		let num_fats = u64::from(boot_sector[16]);
+		let root_entries = read_u16(&boot_sector, 17);
+		let total_sectors_16 = u64::from(read_u16(&boot_sector, 19));
+		let fat_sectors_16 = read_u16(&boot_sector, 22);
+		let total_sectors_32 = u64::from(read_u32(&boot_sector, 32));
+		let fat_sectors = u64::from(read_u32(&boot_sector, 36));
+		let root_cluster = read_u32(&boot_sector, 44);
+		let fsinfo_sector = u64::from(read_u16(&boot_sector, 48));
+
+		if boot_sector[510..512] != [0x55, 0xaa]
+			|| root_entries != 0
+			|| fat_sectors_16 != 0
+			|| fat_sectors == 0
+		{
+			error!("Block device does not contain a FAT32 file system");
+			return Err(Errno::Inval);
+		}
+
+		if ![512, 1024, 2048, 4096].contains(&bytes_per_sector)
+			|| !sectors_per_cluster.is_power_of_two()
+			|| num_fats == 0
+		{
+			error!("Invalid FAT32 boot sector");
+			return Err(Errno::Inval);
+		}
+
+		if bytes_per_sector % block_size != 0 {
+			error!(
+				"FAT sector size {bytes_per_sector} is not a multiple of the block size {block_size}"
+			);
+			return Err(Errno::Inval);
+		}
+
+		let total_sectors = if total_sectors_16 != 0 {
+			total_sectors_16
+		} else {
+			total_sectors_32
+		};
+		let data_start = reserved_sectors + num_fats * fat_sectors;
+		let fat_entries = fat_sectors * u64::try_from(bytes_per_sector / 4).unwrap();
+		let cluster_count = (total_sectors.saturating_sub(data_start) / sectors_per_cluster)
+			.min(fat_entries - u64::from(FIRST_CLUSTER))
+			.min(u64::from(BAD_CLUSTER - FIRST_CLUSTER));
+		let cluster_count = u32::try_from(cluster_count).unwrap();
+
+		if root_cluster < FIRST_CLUSTER || root_cluster >= cluster_count + FIRST_CLUSTER {
+			error!("Invalid root cluster {root_cluster} of FAT32 file system");
+			return Err(Errno::Inval);
+		}
+
+		let mut volume = Self {
+			device,
+			blocks_per_sector: u64::try_from(bytes_per_sector / block_size).unwrap(),
+			bytes_per_sector,
+			sectors_per_cluster,
+			fat_start: reserved_sectors,
+			fat_sectors,
+			num_fats,
+			data_start,
+			cluster_count,
+			root_cluster,
+			fsinfo_sector: None,
+			free_count: None,
+			next_free: FIRST_CLUSTER,
+			fat_cache: None,
+			generation: 0,
+		};
+
+		if fsinfo_sector != 0 && fsinfo_sector < reserved_sectors {
+			let mut fsinfo = vec![0u8; bytes_per_sector];
+			volume.read_sectors(fsinfo_sector, &mut fsinfo)?;
+			if read_u32(&fsinfo, 0) == FSINFO_LEAD_SIGNATURE
+				&& read_u32(&fsinfo, 484) == FSINFO_STRUCT_SIGNATURE
+			{
+				volume.fsinfo_sector = Some(fsinfo_sector);
+				let free_count = read_u32(&fsinfo, 488);
+				if free_count <= cluster_count {
+					volume.free_count = Some(free_count);
+				}
+				let next_free = read_u32(&fsinfo, 492);
+				if next_free != FSINFO_UNKNOWN && volume.is_valid_cluster(next_free) {
+					volume.next_free = next_free;
+				}
+			}
+		}
+
+		Ok(volume)
+	}
+
+	fn cluster_size(&self) -> usize {
+		self.bytes_per_sector * usize::try_from(self.sectors_per_cluster).unwrap()
+	}
+
+	fn entries_per_cluster(&self) -> usize {
+		self.cluster_size() / DIR_ENTRY_SIZE
+	}
+
+	fn is_valid_cluster(&self, cluster: u32) -> bool {
+		(FIRST_CLUSTER..self.cluster_count + FIRST_CLUSTER).contains(&cluster)
+	}
+
+	fn read_sectors(&self, sector: u64, buf: &mut [u8]) -> Result<(), Errno> {
+		self.device
+			.lock()
+			.read_blocks(sector * self.blocks_per_sector, buf)
+			.map_err(Errno::from)
+	}
+
+	fn write_sectors(&self, sector: u64, buf: &[u8]) -> Result<(), Errno> {
+		self.device
+			.lock()
+			.write_blocks(sector * self.blocks_per_sector, buf)
+			.map_err(Errno::from)
+	}
+
+	fn cluster_sector(&self, cluster: u32) -> u64 {
+		self.data_start + u64::from(cluster - FIRST_CLUSTER) * self.sectors_per_cluster
+	}
+
+	/// Reads `buf.len()` bytes starting at byte `offset` of `cluster`.
+	fn read_in_cluster(&self, cluster: u32, offset: usize, buf: &mut [u8]) -> Result<(), Errno> {
+		let first = offset / self.bytes_per_sector;
+		let last = (offset + buf.len()).div_ceil(self.bytes_per_sector);
+		let sector = self.cluster_sector(cluster) + u64::try_from(first).unwrap();
+		let start = offset - first * self.bytes_per_sector;
+
+		if start == 0 && buf.len() % self.bytes_per_sector == 0 {
+			return self.read_sectors(sector, buf);
+		}
+
+		let mut tmp = vec![0u8; (last - first) * self.bytes_per_sector];
+		self.read_sectors(sector, &mut tmp)?;
+		buf.copy_from_slice(&tmp[start..start + buf.len()]);
+		Ok(())
+	}
+
+	/// Writes `buf` starting at byte `offset` of `cluster`.
+	fn write_in_cluster(&self, cluster: u32, offset: usize, buf: &[u8]) -> Result<(), Errno> {
+		let first = offset / self.bytes_per_sector;
+		let last = (offset + buf.len()).div_ceil(self.bytes_per_sector);
+		let sector = self.cluster_sector(cluster) + u64::try_from(first).unwrap();
+		let start = offset - first * self.bytes_per_sector;
+
+		if start == 0 && buf.len() % self.bytes_per_sector == 0 {
+			return self.write_sectors(sector, buf);
+		}
+
+		let mut tmp = vec![0u8; (last - first) * self.bytes_per_sector];
+		self.read_sectors(sector, &mut tmp)?;
+		tmp[start..start + buf.len()].copy_from_slice(buf);
+		self.write_sectors(sector, &tmp)
+	}
+
+	/// Loads the FAT sector, which contains the entry of `cluster`, and returns
+	/// the sector and the offset of the entry within the sector.
+	fn load_fat_sector(&mut self, cluster: u32) -> Result<(u64, usize), Errno> {
+		let offset = u64::from(cluster) * 4;
+		let bytes_per_sector = u64::try_from(self.bytes_per_sector).unwrap();
+		let sector = self.fat_start + offset / bytes_per_sector;
+		let index = usize::try_from(offset % bytes_per_sector).unwrap();
+
+		if !matches!(&self.fat_cache, Some((cached, _)) if *cached == sector) {
+			let mut buf = vec![0u8; self.bytes_per_sector];
+			self.read_sectors(sector, &mut buf)?;
+			self.fat_cache = Some((sector, buf));
+		}
+
+		Ok((sector, index))
+	}
+
+	fn fat_entry(&mut self, cluster: u32) -> Result<u32, Errno> {
+		let (_, index) = self.load_fat_sector(cluster)?;
+		let (_, buf) = self.fat_cache.as_ref().unwrap();
+		Ok(read_u32(buf, index) & CLUSTER_MASK)
+	}
+
+	/// Sets the FAT entry of `cluster` in all copies of the FAT.
+	fn set_fat_entry(&mut self, cluster: u32, value: u32) -> Result<(), Errno> {
+		let (sector, index) = self.load_fat_sector(cluster)?;
+		let (_, buf) = self.fat_cache.as_mut().unwrap();
+		let old = read_u32(buf, index);
+		write_u32(buf, index, (old & !CLUSTER_MASK) | (value & CLUSTER_MASK));
+
+		let (_, buf) = self.fat_cache.as_ref().unwrap();
+		for i in 0..self.num_fats {
+			self.write_sectors(sector + i * self.fat_sectors, buf)?;
+		}
+
+		Ok(())
+	}
+
+	/// Returns the cluster following `cluster` in its chain.
+	fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, Errno> {
+		match self.fat_entry(cluster)? {
+			next if next >= END_OF_CHAIN => Ok(None),
+			next if self.is_valid_cluster(next) => Ok(Some(next)),
+			next => {
+				error!("Invalid FAT entry {next:#x} of cluster {cluster}");
+				Err(Errno::Io)
+			}
+		}
+	}
+
+	/// Returns the cluster, from which a walk to the `index`-th cluster of the
+	/// chain starting at `first` begins, and its index. This is the position of
+	/// `cursor`, if it is still valid and not behind `index`, or else the start
+	/// of the chain.
+	fn walk_start(&self, first: u32, index: u64, cursor: Option<ClusterCursor>) -> (u32, u64) {
+		match cursor {
+			Some(cursor)
+				if cursor.first == first
+					&& cursor.generation == self.generation
+					&& cursor.index <= index =>
+			{
+				(cursor.cluster, cursor.index)
+			}
+			_ => (first, 0),
+		}
+	}
+
+	/// Returns a cursor at the `index`-th cluster `cluster` of the chain
+	/// starting at `first`.
+	fn cursor(&self, first: u32, index: u64, cluster: u32) -> Option<ClusterCursor> {
+		Some(ClusterCursor {
+			first,
+			generation: self.generation,
+			index,
+			cluster,
+		})
+	}
+
+	/// Returns the `index`-th cluster of the chain starting at `first`.
+	fn cluster_at(&mut self, first: u32, index: u64) -> Result<Option<u32>, Errno> {
+		let mut cluster = first;
+		for _ in 0..index {
+			match self.next_cluster(cluster)? {
+				Some(next) => cluster = next,
+				None => return Ok(None),
+			}
+		}
+		Ok(Some(cluster))
+	}
+
+	/// Allocates a zeroed cluster and appends it to the chain ending with `prev`.
+	fn alloc_cluster(&mut self, prev: Option<u32>) -> Result<u32, Errno> {
+		if self.free_count == Some(0) {
+			return Err(Errno::Nospc);
+		}
+
+		let start = self.next_free;
+		let mut cluster = start;
+		loop {
+			if self.fat_entry(cluster)? == FREE_CLUSTER {
+				break;
+			}
+
+			cluster += 1;
+			if cluster >= self.cluster_count + FIRST_CLUSTER {
+				cluster = FIRST_CLUSTER;
+			}
+			if cluster == start {
+				self.free_count = Some(0);
+				return Err(Errno::Nospc);
+			}
+		}
+
+		self.write_in_cluster(cluster, 0, &vec![0u8; self.cluster_size()])?;
+		self.set_fat_entry(cluster, END_OF_CHAIN | 0xf)?;
+		if let Some(prev) = prev {
+			self.set_fat_entry(prev, cluster)?;
+		}
+
+		self.next_free = cluster;
+		self.free_count = self.free_count.map(|count| count - 1);
+		Ok(cluster)
+	}
+
+	/// Returns the number of free clusters. If the FSInfo sector does not
+	/// contain the number, the FAT is scanned once.
+	fn free_clusters(&mut self) -> Result<u32, Errno> {
+		if let Some(count) = self.free_count {
+			return Ok(count);
+		}
+
+		let mut count = 0;
+		for cluster in FIRST_CLUSTER..self.cluster_count + FIRST_CLUSTER {
+			if self.fat_entry(cluster)? == FREE_CLUSTER {
+				count += 1;
+			}
+		}
+		self.free_count = Some(count);
+		Ok(count)
+	}
+
+	fn statfs(&mut self) -> Result<StatFs, Errno> {
+		let free = u64::from(self.free_clusters()?);
+		Ok(StatFs {
+			f_type: MSDOS_SUPER_MAGIC,
+			f_bsize: self.cluster_size().try_into().unwrap(),
+			f_blocks: self.cluster_count.into(),
+			f_bfree: free,
+			f_bavail: free,
+			f_namelen: MAX_NAME_LEN.try_into().unwrap(),
+			..Default::default()
+		})
+	}
+
+	/// Frees all clusters of the chain starting at `first`.
+	fn free_chain(&mut self, first: u32) -> Result<(), Errno> {
+		self.generation += 1;
+		let mut cluster = Some(first);
+		while let Some(current) = cluster {
+			cluster = self.next_cluster(current)?;
+			self.set_fat_entry(current, FREE_CLUSTER)?;
+			self.free_count = self.free_count.map(|count| count + 1);
+		}
+		Ok(())
+	}
+
+	/// Shortens the chain starting at `first` to `len` clusters and returns the
+	/// new first cluster.
+	fn truncate_chain(&mut self, first: u32, len: u64) -> Result<u32, Errno> {
+		if first == 0 {
+			return Ok(0);
+		}
+		if len == 0 {
+			self.free_chain(first)?;
+			return Ok(0);
+		}
+
+		let Some(last) = self.cluster_at(first, len - 1)? else {
+			return Ok(first);
+		};
+		if let Some(next) = self.next_cluster(last)? {
+			self.set_fat_entry(last, END_OF_CHAIN | 0xf)?;
+			self.free_chain(next)?;
+		}
+		Ok(first)
+	}
+
+	/// Writes the allocation hints back to the FSInfo sector.
+	fn sync_fsinfo(&mut self) -> Result<(), Errno> {
+		let Some(sector) = self.fsinfo_sector else {
+			return Ok(());
+		};
+
+		let mut fsinfo = vec![0u8; self.bytes_per_sector];
+		self.read_sectors(sector, &mut fsinfo)?;
+		write_u32(&mut fsinfo, 488, self.free_count.unwrap_or(FSINFO_UNKNOWN));
+		write_u32(&mut fsinfo, 492, self.next_free);
+		self.write_sectors(sector, &fsinfo)
+	}
+
+	/// Reads up to `buf.len()` bytes at `pos` of the file starting at cluster
+	/// `first`. The walk along the chain continues from `cursor`, which is moved
+	/// to the last cluster read.
+	fn read_file(
+		&mut self,
+		first: u32,
+		size: u32,
+		pos: u64,
+		buf: &mut [u8],
+		cursor: &mut Option<ClusterCursor>,
+	) -> Result<usize, Errno> {
+		let size = u64::from(size);
+		if pos >= size || first == 0 {
+			return Ok(0);
+		}
+
+		let len = usize::try_from((size - pos).min(u64::try_from(buf.len()).unwrap())).unwrap();
+		let cluster_size = self.cluster_size();
+		let cluster_size_u64 = u64::try_from(cluster_size).unwrap();
+		let mut index = pos / cluster_size_u64;
+		let (mut cluster, start) = self.walk_start(first, index, *cursor);
+		for _ in start..index {
+			cluster = self.next_cluster(cluster)?.ok_or(Errno::Io)?;
+		}
+		let mut offset = usize::try_from(pos % cluster_size_u64).unwrap();
+		let mut done = 0;
+
+		loop {
+			let n = (len - done).min(cluster_size - offset);
+			self.read_in_cluster(cluster, offset, &mut buf[done..done + n])?;
+			done += n;
+			if done == len {
+				*cursor = self.cursor(first, index, cluster);
+				return Ok(len);
+			}
+
+			offset = 0;
+			cluster = self.next_cluster(cluster)?.ok_or(Errno::Io)?;
+			index += 1;
+		}
+	}
+
+	/// Writes `buf` at `pos` of the file starting at cluster `first`, extending
+	/// the cluster chain as required. Returns the (possibly new) first cluster.
+	///
+	/// Like in [`Self::read_file`], the walk continues from `cursor`.
+	fn write_file(
+		&mut self,
+		first: u32,
+		pos: u64,
+		buf: &[u8],
+		cursor: &mut Option<ClusterCursor>,
+	) -> Result<u32, Errno> {
+		if buf.is_empty() {
+			return Ok(first);
+		}
+		if pos + u64::try_from(buf.len()).unwrap() > MAX_FILE_SIZE {
+			return Err(Errno::Fbig);
+		}
+
+		let first = if first == 0 {
+			self.alloc_cluster(None)?
+		} else {
+			first
+		};
+
+		let cluster_size = self.cluster_size();
+		let cluster_size_u64 = u64::try_from(cluster_size).unwrap();
+		let mut index = pos / cluster_size_u64;
+		let (mut cluster, start) = self.walk_start(first, index, *cursor);
+		for _ in start..index {
+			cluster = match self.next_cluster(cluster)? {
+				Some(next) => next,
+				None => self.alloc_cluster(Some(cluster))?,
+			};
+		}
+
+		let mut offset = usize::try_from(pos % cluster_size_u64).unwrap();
+		let mut done = 0;
+		loop {
+			let n = (buf.len() - done).min(cluster_size - offset);
+			self.write_in_cluster(cluster, offset, &buf[done..done + n])?;
+			done += n;
+			if done == buf.len() {
+				*cursor = self.cursor(first, index, cluster);
+				return Ok(first);
+			}
+
+			offset = 0;
+			cluster = match self.next_cluster(cluster)? {
+				Some(next) => next,
+				None => self.alloc_cluster(Some(cluster))?,
+			};
+			index += 1;
+		}
+	}
+
+	fn read_entry(&self, location: EntryLocation) -> Result<[u8; DIR_ENTRY_SIZE], Errno> {
+		let mut raw = [0u8; DIR_ENTRY_SIZE];
+		self.read_in_cluster(location.cluster, location.index * DIR_ENTRY_SIZE, &mut raw)?;
+		Ok(raw)
+	}
+
+	fn write_entry(
+		&self,
+		location: EntryLocation,
+		raw: &[u8; DIR_ENTRY_SIZE],
+	) -> Result<(), Errno> {
+		self.write_in_cluster(location.cluster, location.index * DIR_ENTRY_SIZE, raw)
+	}
+
+	/// Reads the short entry of an open file, which has to be still present.
+	fn read_file_entry(&self, location: EntryLocation) -> Result<DirEntry, Errno> {
+		let raw = self.read_entry(location)?;
+		if raw[0] == 0 || raw[0] == DELETED_ENTRY {
+			return Err(Errno::Noent);
+		}
+		let entry = DirEntry::from_raw(raw, None, vec![location]);
+		self.check_entry(&entry)?;
+		Ok(entry)
+	}
+
+	/// Checks that the first cluster of a parsed entry lies in the data region.
+	/// Only empty files have none.
+	fn check_entry(&self, entry: &DirEntry) -> Result<(), Errno> {
+		let valid = if entry.first_cluster == 0 {
+			!entry.is_dir() && entry.size == 0
+		} else {
+			self.is_valid_cluster(entry.first_cluster)
+		};
+		if !valid {
+			error!(
+				"Invalid first cluster {:#x} of {}",
+				entry.first_cluster, entry.name
+			);
+			return Err(Errno::Io);
+		}
+		Ok(())
+	}
+
+	/// Updates the first cluster and the size of a directory entry and sets its
+	/// modification time.
+	fn update_entry(
+		&self,
+		location: EntryLocation,
+		first_cluster: u32,
+		size: u32,
+	) -> Result<(), Errno> {
+		let mut raw = self.read_entry(location)?;
+		let (date, time) = to_fat_time(now());
+		write_u16(&mut raw, 20, u16::try_from(first_cluster >> 16).unwrap());
+		write_u16(&mut raw, 26, u16::try_from(first_cluster & 0xffff).unwrap());
+		write_u32(&mut raw, 28, size);
+		write_u16(&mut raw, 22, time);
+		write_u16(&mut raw, 24, date);
+		write_u16(&mut raw, 18, date);
+		self.write_entry(location, &raw)
+	}
+
+	/// Returns all entries of the directory starting at `cluster` except `.` and `..`.
+	fn read_dir(&mut self, cluster: u32) -> Result<Vec<DirEntry>, Errno> {
+		let mut entries = Vec::new();
+		let mut buf = vec![0u8; self.cluster_size()];
+
+		// Parts of the long name, its checksum and the next expected ordinal
+		let mut long_name: Vec<u16> = Vec::new();
+		let mut long_checksum = 0u8;
+		let mut expected = 0u8;
+		let mut slots = Vec::new();
+
+		let mut current = Some(cluster);
+		while let Some(cluster) = current {
+			self.read_in_cluster(cluster, 0, &mut buf)?;
+
+			for (index, raw) in buf.chunks_exact(DIR_ENTRY_SIZE).enumerate() {
+				let location = EntryLocation { cluster, index };
+				let raw: [u8; DIR_ENTRY_SIZE] = raw.try_into().unwrap();
+
+				if raw[0] == 0 {
+					return Ok(entries);
+				}
+				if raw[0] == DELETED_ENTRY {
+					expected = 0;
+					continue;
+				}
+
+				if raw[11] & ATTR_LONG_NAME_MASK == ATTR_LONG_NAME {
+					let ordinal = raw[0] & !LAST_LONG_ENTRY;
+					if raw[0] & LAST_LONG_ENTRY != 0 && ordinal != 0 {
+						long_name = vec![0xffff; usize::from(ordinal) * LONG_NAME_CHARS];
+						long_checksum = raw[13];
+						expected = ordinal;
+						slots.clear();
+					}
+
+					if ordinal == 0 || ordinal != expected || raw[13] != long_checksum {
+						expected = 0;
+						continue;
+					}
+
+					let start = usize::from(ordinal - 1) * LONG_NAME_CHARS;
+					for (i, offset) in LONG_NAME_OFFSETS.iter().enumerate() {
+						long_name[start + i] = read_u16(&raw, *offset);
+					}
+					expected -= 1;
+					slots.push(location);
+					continue;
+				}
+
+				let has_long_name = expected == 0
+					&& !slots.is_empty()
+					&& checksum(raw[..11].try_into().unwrap()) == long_checksum;
+				let mut entry_slots = if has_long_name {
+					core::mem::take(&mut slots)
+				} else {
+					Vec::new()
+				};
+				slots.clear();
+				expected = 0;
+
+				if raw[11] & ATTR_VOLUME_ID != 0 || raw[0] == b'.' {
+					continue;
+				}
+
+				let name = has_long_name.then(|| {
+					let len = long_name
+						.iter()
+						.position(|c| *c == 0 || *c == 0xffff)
+						.unwrap_or(long_name.len());
+					char::decode_utf16(long_name[..len].iter().copied())
+						.map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+						.collect()
+				});
+				entry_slots.push(location);
+				let entry = DirEntry::from_raw(raw, name, entry_slots);
+				self.check_entry(&entry)?;
+				entries.push(entry);
+			}
+
+			current = self.next_cluster(cluster)?;
+		}
+
+		Ok(entries)
+	}
+
+	fn find_entry(&mut self, dir: u32, name: &str) -> Result<Option<DirEntry>, Errno> {
+		Ok(self
+			.read_dir(dir)?
+			.into_iter()
+			.find(|entry| entry.name.eq_ignore_ascii_case(name)))
+	}
+
+	/// Returns the first cluster of a directory entry. Entries of type `..`
+	/// refer to the root directory by cluster 0.
+	fn dir_cluster(&self, entry: &DirEntry) -> u32 {
+		if entry.first_cluster == 0 {
+			self.root_cluster
+		} else {
+			entry.first_cluster
+		}
+	}
+
+	/// Returns the first cluster of the directory `path`.
+	fn lookup_dir(&mut self, path: &[&str]) -> Result<u32, Errno> {
+		let mut cluster = self.root_cluster;
+		for component in path {
+			let entry = self.find_entry(cluster, component)?.ok_or(Errno::Noent)?;
+			if !entry.is_dir() {
+				return Err(Errno::Notdir);
+			}
+			cluster = self.dir_cluster(&entry);
+		}
+		Ok(cluster)
+	}
+
+	/// Returns the first cluster of the parent directory of `path` and the entry of `path`.
+	fn lookup(&mut self, path: &[&str]) -> Result<(u32, Option<DirEntry>), Errno> {
+		let (name, parent) = path.split_last().ok_or(Errno::Inval)?;
+		let dir = self.lookup_dir(parent)?;
+		let entry = self.find_entry(dir, name)?;
+		Ok((dir, entry))
+	}
+
+	/// Returns `count` consecutive free entries of the directory starting at
+	/// cluster `dir`. The directory is extended, if it is full.
+	fn find_free_slots(&mut self, dir: u32, count: usize) -> Result<Vec<EntryLocation>, Errno> {
+		let mut run = Vec::with_capacity(count);
+		let mut buf = vec![0u8; self.cluster_size()];
+		let mut cluster = dir;
+
+		loop {
+			self.read_in_cluster(cluster, 0, &mut buf)?;
+			for (index, raw) in buf.chunks_exact(DIR_ENTRY_SIZE).enumerate() {
+				if raw[0] == 0 || raw[0] == DELETED_ENTRY {
+					run.push(EntryLocation { cluster, index });
+					if run.len() == count {
+						return Ok(run);
+					}
+				} else {
+					run.clear();
+				}
+			}
+
+			cluster = match self.next_cluster(cluster)? {
+				Some(next) => next,
+				None => self.alloc_cluster(Some(cluster))?,
+			};
+		}
+	}
+
+	/// Creates a new entry `name` in the directory starting at cluster `dir`.
+	fn create_entry(
+		&mut self,
+		dir: u32,
+		name: &str,
+		attr: u8,
+		first_cluster: u32,
+	) -> Result<DirEntry, Errno> {
+		if name.is_empty() || name == "." || name == ".." || name.chars().any(is_invalid_char) {
+			return Err(Errno::Inval);
+		}
+		let utf16: Vec<u16> = name.encode_utf16().collect();
+		if utf16.len() > MAX_NAME_LEN {
+			return Err(Errno::Nametoolong);
+		}
+
+		let entries = self.read_dir(dir)?;
+		let used: Vec<[u8; 11]> = entries.iter().map(|entry| entry.short_name).collect();
+		let (short_name, long_entries) = match exact_short_name(name) {
+			Some(short_name) if !used.contains(&short_name) => (short_name, 0),
+			_ => (
+				generate_short_name(name, &used)?,
+				utf16.len().div_ceil(LONG_NAME_CHARS),
+			),
+		};
+
+		let slots = self.find_free_slots(dir, long_entries + 1)?;
+		let sum = checksum(&short_name);
+		for (i, location) in slots[..long_entries].iter().enumerate() {
+			let ordinal = long_entries - i;
+			let mut raw = [0u8; DIR_ENTRY_SIZE];
+			raw[0] = u8::try_from(ordinal).unwrap();
+			if i == 0 {
+				raw[0] |= LAST_LONG_ENTRY;
+			}
+			raw[11] = ATTR_LONG_NAME;
+			raw[13] = sum;
+
+			let start = (ordinal - 1) * LONG_NAME_CHARS;
+			for (j, offset) in LONG_NAME_OFFSETS.iter().enumerate() {
+				let c = match utf16.get(start + j) {
+					Some(c) => *c,
+					None if start + j == utf16.len() => 0,
+					None => 0xffff,
+				};
+				write_u16(&mut raw, *offset, c);
+			}
+
+			self.write_entry(*location, &raw)?;
+		}
+
+		let (date, time) = to_fat_time(now());
+		let mut raw = [0u8; DIR_ENTRY_SIZE];
+		raw[..11].copy_from_slice(&short_name);
+		raw[11] = attr;
+		write_u16(&mut raw, 14, time);
+		write_u16(&mut raw, 16, date);
+		write_u16(&mut raw, 18, date);
+		write_u16(&mut raw, 20, u16::try_from(first_cluster >> 16).unwrap());
+		write_u16(&mut raw, 22, time);
+		write_u16(&mut raw, 24, date);
+		write_u16(&mut raw, 26, u16::try_from(first_cluster & 0xffff).unwrap());
+		let short_location = *slots.last().unwrap();
+		self.write_entry(short_location, &raw)?;
+
+		Ok(DirEntry::from_raw(raw, Some(name.into()), slots))
+	}
+
+	/// Marks all entries of `entry` as deleted.
+	fn remove_entry(&self, entry: &DirEntry) -> Result<(), Errno> {
+		for location in &entry.slots {
+			self.write_in_cluster(
+				location.cluster,
+				location.index * DIR_ENTRY_SIZE,
+				&[DELETED_ENTRY],
+			)?;
+		}
+		Ok(())
+	}
+
+	/// Creates the directory `path`.
+	fn mkdir(&mut self, path: &[&str]) -> Result<(), Errno> {
+		let (dir, entry) = self.lookup(path)?;
+		if entry.is_some() {
+			return Err(Errno::Exist);
+		}
+
+		let cluster = self.alloc_cluster(None)?;
+		let entry = match self.create_entry(dir, path.last().unwrap(), ATTR_DIRECTORY, cluster) {
+			Ok(entry) => entry,
+			Err(err) => {
+				self.free_chain(cluster)?;
+				return Err(err);
+			}
+		};
+
+		// The entries `.` and `..` refer to the directory itself and to its parent
+		let parent = if dir == self.root_cluster { 0 } else { dir };
+		for (index, (name, target)) in [(b".          ", cluster), (b"..         ", parent)]
+			.into_iter()
+			.enumerate()
+		{
+			let mut raw = entry.raw;
+			raw[..11].copy_from_slice(name);
+			write_u16(&mut raw, 20, u16::try_from(target >> 16).unwrap());
+			write_u16(&mut raw, 26, u16::try_from(target & 0xffff).unwrap());
+			self.write_entry(EntryLocation { cluster, index }, &raw)?;
+		}
+
+		self.sync_fsinfo()
+	}
+
+	/// Removes the empty directory `path`.
+	fn rmdir(&mut self, path: &[&str]) -> Result<(), Errno> {
+		let (_, entry) = self.lookup(path)?;
+		let entry = entry.ok_or(Errno::Noent)?;
+		if !entry.is_dir() {
+			return Err(Errno::Notdir);
+		}
+		if !self.read_dir(self.dir_cluster(&entry))?.is_empty() {
+			return Err(Errno::Notempty);
+		}
+
+		self.remove_entry(&entry)?;
+		self.free_chain(entry.first_cluster)?;
+		self.sync_fsinfo()
+	}
+
+	/// Removes the file `path`.
+	fn unlink(&mut self, path: &[&str]) -> Result<(), Errno> {
+		let (_, entry) = self.lookup(path)?;
+		let entry = entry.ok_or(Errno::Noent)?;
+		if entry.is_dir() {
+			return Err(Errno::Isdir);
+		}
+
+		self.remove_entry(&entry)?;
+		if entry.first_cluster != 0 {
+			self.free_chain(entry.first_cluster)?;
+		}
+		self.sync_fsinfo()
+	}
+
+	/// Points the entry `..` of the directory starting at `cluster` to `parent`.
+	fn set_parent(&self, cluster: u32, parent: u32) -> Result<(), Errno> {
+		let parent = if parent == self.root_cluster {
+			0
+		} else {
+			parent
+		};
+		let location = EntryLocation { cluster, index: 1 };
+		let mut raw = self.read_entry(location)?;
+		write_u16(&mut raw, 20, u16::try_from(parent >> 16).unwrap());
+		write_u16(&mut raw, 26, u16::try_from(parent & 0xffff).unwrap());
+		self.write_entry(location, &raw)
+	}
+
+	/// Renames `old` to `new`. Instead of creating a new entry, an existing
+	/// entry of `new` is overwritten, so that `new` exists at any time.
+	fn rename(&mut self, old: &[&str], new: &[&str], flags: RenameFlags) -> Result<(), Errno> {
+		let (old_dir, old_entry) = self.lookup(old)?;
+		let old_entry = old_entry.ok_or(Errno::Noent)?;
+		let (new_dir, new_entry) = self.lookup(new)?;
+
+		// Names are compared case-insensitively, so `new` may be `old` itself.
+		if new_entry
+			.as_ref()
+			.is_some_and(|entry| entry.location() == old_entry.location())
+		{
+			return Ok(());
+		}
+
+		if old_entry.is_dir() {
+			let cluster = self.dir_cluster(&old_entry);
+			for i in 1..new.len() {
+				if self.lookup_dir(&new[..i])? == cluster {
+					return Err(Errno::Inval);
+				}
+			}
+		}
+
+		match new_entry {
+			None if flags.contains(RenameFlags::RENAME_EXCHANGE) => return Err(Errno::Noent),
+			None => {
+				let mut entry =
+					self.create_entry(new_dir, new.last().unwrap(), old_entry.attr, 0)?;
+				entry.raw = with_content(entry.raw, &old_entry.raw);
+				self.write_entry(entry.location(), &entry.raw)?;
+				self.remove_entry(&old_entry)?;
+			}
+			Some(_) if flags.contains(RenameFlags::RENAME_NOREPLACE) => {
+				return Err(Errno::Exist);
+			}
+			Some(new_entry) if flags.contains(RenameFlags::RENAME_EXCHANGE) => {
+				self.write_entry(
+					new_entry.location(),
+					&with_content(new_entry.raw, &old_entry.raw),
+				)?;
+				self.write_entry(
+					old_entry.location(),
+					&with_content(old_entry.raw, &new_entry.raw),
+				)?;
+				if new_entry.is_dir() && old_dir != new_dir {
+					self.set_parent(self.dir_cluster(&new_entry), old_dir)?;
+				}
+			}
+			Some(new_entry) => {
+				match (old_entry.is_dir(), new_entry.is_dir()) {
+					(true, false) => return Err(Errno::Notdir),
+					(false, true) => return Err(Errno::Isdir),
+					(true, true) if !self.read_dir(self.dir_cluster(&new_entry))?.is_empty() => {
+						return Err(Errno::Notempty);
+					}
+					_ => {}
+				}
+
+				self.write_entry(
+					new_entry.location(),
+					&with_content(new_entry.raw, &old_entry.raw),
+				)?;
+				self.remove_entry(&old_entry)?;
+				if new_entry.first_cluster != 0 {
+					self.free_chain(new_entry.first_cluster)?;
+				}
+			}
+		}
+
+		if old_entry.is_dir() && old_dir != new_dir {
+			self.set_parent(self.dir_cluster(&old_entry), new_dir)?;
+		}
+		self.sync_fsinfo()
+	}
+
+	/// Sets the size of the file described by the entry at `location`.
+	fn truncate(&mut self, location: EntryLocation, size: usize) -> Result<(), Errno> {
+		let entry = self.read_file_entry(location)?;
+		let size = u64::try_from(size).unwrap();
+		if size > MAX_FILE_SIZE {
+			return Err(Errno::Fbig);
+		}
+
+		let old_size = u64::from(entry.size);
+		let first = if size < old_size {
+			let clusters = size.div_ceil(u64::try_from(self.cluster_size()).unwrap());
+			let first = self.truncate_chain(entry.first_cluster, clusters)?;
+			// Clear the rest of the last cluster, so that extending the file reads zeros.
+			let cluster_size = u64::try_from(self.cluster_size()).unwrap();
+			if first != 0 && size % cluster_size != 0 {
+				let offset = usize::try_from(size % cluster_size).unwrap();
+				let last = self
+					.cluster_at(first, size / cluster_size)?
+					.ok_or(Errno::Io)?;
+				self.write_in_cluster(last, offset, &vec![0u8; self.cluster_size() - offset])?;
+			}
+			first
+		} else if size > old_size {
+			// Clusters are zeroed on allocation, only the chain has to be extended.
+			self.write_file(entry.first_cluster, size - 1, &[0], &mut None)?
+		} else {
+			entry.first_cluster
+		};
+
+		self.update_entry(location, first, u32::try_from(size).unwrap())?;
+		self.sync_fsinfo()
+	}
+
+	fn file_attributes(&self, entry: &DirEntry) -> FileAttr {
+		let mtim = from_fat_time(read_u16(&entry.raw, 24), read_u16(&entry.raw, 22));
+		let location = entry.location();
+		let mode = if entry.is_dir() {
+			AccessPermission::S_IFDIR | AccessPermission::from_bits(0o777).unwrap()
+		} else if entry.attr & ATTR_READ_ONLY != 0 {
+			AccessPermission::S_IFREG | AccessPermission::from_bits(0o444).unwrap()
+		} else {
+			AccessPermission::S_IFREG | AccessPermission::from_bits(0o666).unwrap()
+		};
+
+		FileAttr {
+			st_ino: (u64::from(location.cluster) << 32) | u64::try_from(location.index).unwrap(),
+			st_nlink: 1,
+			st_mode: mode,
+			st_size: entry.size.into(),
+			st_blksize: self.cluster_size().try_into().unwrap(),
+			st_blocks: i64::from(entry.size).div_ceil(512),
+			st_atim: from_fat_time(read_u16(&entry.raw, 18), 0),
+			st_mtim: mtim,
+			st_ctim: from_fat_time(read_u16(&entry.raw, 16), read_u16(&entry.raw, 14)),
+			..Default::default()
+		}
+	}
+
+	fn root_attributes(&self) -> FileAttr {
+		FileAttr {
+			st_ino: 1,
+			st_nlink: 1,
+			st_mode: AccessPermission::S_IFDIR | AccessPermission::from_bits(0o777).unwrap(),
+			st_blksize: self.cluster_size().try_into().unwrap(),
+			..Default::default()
+		}
+	}
+}
+
+#[derive(Debug)]
+struct FatFileInterface {
+	volume: Arc<Mutex<FatVolume>>,
+	/// Location of the short directory entry of the file
+	location: EntryLocation,
+	/// Position within the file
+	pos: Mutex<usize>,
+	/// Position in the cluster chain, at which the last transfer has ended
+	cursor: Mutex<Option<ClusterCursor>>,
+	append: bool,
+	/// Transfers have to be aligned to the block size of the device (`O_DIRECT`).
+	direct: bool,
+	/// Writes are flushed to stable storage (`O_SYNC`).
+	sync: bool,
+}
+
+impl FatFileInterface {
+	/// Data is always written through to the device. Hence, direct transfers
+	/// only have to fulfill the alignment requirements.
+	fn check_direct(
+		&self,
+		volume: &FatVolume,
+		pos: usize,
+		addr: usize,
+		len: usize,
+	) -> io::Result<()> {
+		if self.direct {
+			let device = volume.device.lock();
+			block::check_direct(&*device, u64::try_from(pos).unwrap(), addr, len)?;
+		}
+		Ok(())
+	}
+}
+
+#[async_trait]
+impl ObjectInterface for FatFileInterface {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		let available = PollEvent::POLLIN
+			| PollEvent::POLLRDNORM
+			| PollEvent::POLLRDBAND
+			| PollEvent::POLLOUT
+			| PollEvent::POLLWRNORM
+			| PollEvent::POLLWRBAND;
+		Ok(event & available)
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let mut volume = self.volume.lock().await;
+		let mut pos = self.pos.lock().await;
+		let entry = volume.read_file_entry(self.location)?;
+		self.check_direct(&volume, *pos, buf.as_ptr().addr(), buf.len())?;
+
+		let len = volume.read_file(
+			entry.first_cluster,
+			entry.size,
+			u64::try_from(*pos).unwrap(),
+			buf,
+			&mut *self.cursor.lock().await,
+		)?;
+		*pos += len;
+		Ok(len)
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		let mut volume = self.volume.lock().await;
+		let mut pos = self.pos.lock().await;
+		let entry = volume.read_file_entry(self.location)?;
+		if self.append {
+			*pos = usize::try_from(entry.size).unwrap();
+		}
+		self.check_direct(&volume, *pos, buf.as_ptr().addr(), buf.len())?;
+
+		let offset = u64::try_from(*pos).unwrap();
+		let first = volume.write_file(
+			entry.first_cluster,
+			offset,
+			buf,
+			&mut *self.cursor.lock().await,
+		)?;
+		let end = offset + u64::try_from(buf.len()).unwrap();
+		let size = u32::try_from(end.max(u64::from(entry.size))).unwrap();
+		volume.update_entry(self.location, first, size)?;
+		volume.sync_fsinfo()?;
+		if self.sync {
+			volume.device.lock().flush()?;
+		}
+
+		*pos += buf.len();
+		Ok(buf.len())
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		let volume = self.volume.lock().await;
+		let mut pos = self.pos.lock().await;
+
+		let new_pos = match whence {
+			SeekWhence::Set => offset,
+			SeekWhence::Cur => isize::try_from(*pos).unwrap() + offset,
+			SeekWhence::End => {
+				let entry = volume.read_file_entry(self.location)?;
+				isize::try_from(entry.size).unwrap() + offset
+			}
+			_ => return Err(Errno::Inval),
+		};
+		if new_pos < 0 {
+			return Err(Errno::Inval);
+		}
+
+		*pos = new_pos.try_into().unwrap();
+		Ok(new_pos)
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		let volume = self.volume.lock().await;
+		let entry = volume.read_file_entry(self.location)?;
+		Ok(volume.file_attributes(&entry))
+	}
+
+	async fn truncate(&self, size: usize) -> io::Result<()> {
+		self.volume.lock().await.truncate(self.location, size)
+	}
+
+	async fn allocate(&self, mode: FallocateMode, offset: usize, len: usize) -> io::Result<()> {
+		let end = offset.checked_add(len).ok_or(Errno::Fbig)?;
+		let mut volume = self.volume.lock().await;
+		let entry = volume.read_file_entry(self.location)?;
+		let size = usize::try_from(entry.size).unwrap();
+
+		if mode.contains(FallocateMode::FALLOC_FL_PUNCH_HOLE) {
+			// FAT has no sparse files. Thus, the range is only overwritten with zeros.
+			let end = end.min(size);
+			let zeros = vec![0u8; volume.cluster_size()];
+			let mut pos = offset;
+			while pos < end {
+				let len = zeros.len().min(end - pos);
+				volume.write_file(
+					entry.first_cluster,
+					u64::try_from(pos).unwrap(),
+					&zeros[..len],
+					&mut None,
+				)?;
+				pos += len;
+			}
+			Ok(())
+		} else if end <= size {
+			Ok(())
+		} else if mode.contains(FallocateMode::FALLOC_FL_KEEP_SIZE) {
+			// Clusters beyond the size of the file would be inconsistent for other implementations.
+			Err(Errno::Opnotsupp)
+		} else {
+			volume.truncate(self.location, end)
+		}
+	}
+
+	async fn fsync(&self) -> io::Result<()> {
+		Ok(self.volume.lock().await.device.lock().flush()?)
+	}
+}
+
+#[derive(Debug)]
+struct FatDirectoryInterface {
+	/// Snapshot of the directory entries
+	entries: Vec<(String, FileType)>,
+	read_idx: Mutex<usize>,
+}
+
+#[async_trait]
+impl ObjectInterface for FatDirectoryInterface {
+	async fn getdents(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+		let mut read_idx = self.read_idx.lock().await;
+		write_dirents(
+			buf,
+			&mut read_idx,
+			self.entries
+				.iter()
+				.map(|(name, file_type)| (name.as_str(), 1, *file_type)),
+		)
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		seek_dir(&mut *self.read_idx.lock().await, offset, whence)
+	}
+}
+
+/// Mount point of a FAT32 volume
+#[derive(Debug)]
+pub(crate) struct FatDirectory {
+	volume: Arc<Mutex<FatVolume>>,
+}
+
+impl FatDirectory {
+	/// Converts the reversed path components of the VFS into a path relative to
+	/// the mount point.
+	fn path<'a>(components: &[&'a str]) -> Vec<&'a str> {
+		components
+			.iter()
+			.rev()
+			.copied()
+			.filter(|component| !component.is_empty() && *component != ".")
+			.collect()
+	}
+
+	fn directory_object(
+		volume: &mut FatVolume,
+		cluster: u32,
+	) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		let entries = volume
+			.read_dir(cluster)?
+			.into_iter()
+			.map(|entry| {
+				let file_type = if entry.is_dir() {
+					FileType::Directory
+				} else {
+					FileType::RegularFile
+				};
+				(entry.name, file_type)
+			})
+			.collect();
+
+		Ok(Arc::new(async_lock::RwLock::new(FatDirectoryInterface {
+			entries,
+			read_idx: Mutex::new(0),
+		})))
+	}
+
+	async fn async_traverse_open(
+		&self,
+		path: &[&str],
+		opt: OpenOption,
+	) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		let mut volume = self.volume.lock().await;
+		if path.is_empty() {
+			let root = volume.root_cluster;
+			return Self::directory_object(&mut volume, root);
+		}
+
+		let writable = opt.intersects(OpenOption::O_WRONLY | OpenOption::O_RDWR);
+		let (dir, entry) = volume.lookup(path)?;
+		let entry = match entry {
+			Some(_) if opt.contains(OpenOption::O_CREAT | OpenOption::O_EXCL) => {
+				return Err(Errno::Exist);
+			}
+			Some(entry) if entry.is_dir() => {
+				if writable {
+					return Err(Errno::Isdir);
+				}
+				let cluster = volume.dir_cluster(&entry);
+				return Self::directory_object(&mut volume, cluster);
+			}
+			Some(_) if opt.contains(OpenOption::O_DIRECTORY) => return Err(Errno::Notdir),
+			Some(entry) => {
+				if writable && entry.attr & ATTR_READ_ONLY != 0 {
+					return Err(Errno::Acces);
+				}
+				if writable && opt.contains(OpenOption::O_TRUNC) {
+					volume.truncate(entry.location(), 0)?;
+				}
+				entry
+			}
+			None if opt.contains(OpenOption::O_CREAT) => {
+				let entry = volume.create_entry(dir, path.last().unwrap(), ATTR_ARCHIVE, 0)?;
+				volume.sync_fsinfo()?;
+				entry
+			}
+			None => return Err(Errno::Noent),
+		};
+
+		Ok(Arc::new(async_lock::RwLock::new(FatFileInterface {
+			volume: self.volume.clone(),
+			location: entry.location(),
+			pos: Mutex::new(0),
+			cursor: Mutex::new(None),
+			append: opt.contains(OpenOption::O_APPEND),
+			direct: opt.contains(OpenOption::O_DIRECT),
+			sync: opt.intersects(OpenOption::O_DSYNC),
+		})))
+	}
+
+	fn stat(&self, components: &[&str]) -> io::Result<FileAttr> {
+		let path = Self::path(components);
+		block_on(
+			async {
+				let mut volume = self.volume.lock().await;
+				if path.is_empty() {
+					return Ok(volume.root_attributes());
+				}
+
+				let (_, entry) = volume.lookup(&path)?;
+				Ok(volume.file_attributes(&entry.ok_or(Errno::Noent)?))
+			},
+			None,
+		)
+	}
+}
+
+impl VfsNode for FatDirectory {
+	fn get_kind(&self) -> NodeKind {
+		NodeKind::Directory
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		block_on(
+			async { Ok(self.volume.lock().await.root_attributes()) },
+			None,
+		)
+	}
+
+	fn get_object(&self) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		block_on(
+			async {
+				let mut volume = self.volume.lock().await;
+				let root = volume.root_cluster;
+				Self::directory_object(&mut volume, root)
+			},
+			None,
+		)
+	}
+
+	fn traverse_mkdir(
+		&self,
+		components: &mut Vec<&str>,
+		_mode: AccessPermission,
+	) -> io::Result<()> {
+		let path = Self::path(components);
+		block_on(async { self.volume.lock().await.mkdir(&path) }, None)
+	}
+
+	fn traverse_rmdir(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		let path = Self::path(components);
+		block_on(async { self.volume.lock().await.rmdir(&path) }, None)
+	}
+
+	fn traverse_unlink(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		let path = Self::path(components);
+		block_on(async { self.volume.lock().await.unlink(&path) }, None)
+	}
+
+	fn traverse_readdir(&self, components: &mut Vec<&str>) -> io::Result<Vec<DirectoryEntry>> {
+		let path = Self::path(components);
+		block_on(
+			async {
+				let mut volume = self.volume.lock().await;
+				let cluster = volume.lookup_dir(&path)?;
+				Ok(volume
+					.read_dir(cluster)?
+					.into_iter()
+					.map(|entry| DirectoryEntry::new(entry.name))
+					.collect())
+			},
+			None,
+		)
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		self.stat(components)
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		self.stat(components)
+	}
+
+	fn traverse_rename(
+		&self,
+		old: &mut Vec<&str>,
+		new: &mut Vec<&str>,
+		flags: RenameFlags,
+	) -> io::Result<()> {
+		let old = Self::path(old);
+		let new = Self::path(new);
+		block_on(
+			async { self.volume.lock().await.rename(&old, &new, flags) },
+			None,
+		)
+	}
+
+	fn traverse_statfs(&self, _components: &mut Vec<&str>) -> io::Result<StatFs> {
+		block_on(async { self.volume.lock().await.statfs() }, None)
+	}
+
+	fn traverse_open(
+		&self,
+		components: &mut Vec<&str>,
+		opt: OpenOption,
+		_mode: AccessPermission,
+	) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		let path = Self::path(components);
+		block_on(self.async_traverse_open(&path, opt), None)
+	}
+}
+
+/// Opens the FAT32 file system on the block device `name`.
+pub(crate) fn open(name: &str) -> io::Result<Box<dyn VfsNode + Send + Sync>> {
+	let device = block::get(name)?;
+	let volume = FatVolume::new(device)?;
+	info!(
+		"FAT32 file system on {name}: {} clusters of {} bytes",
+		volume.cluster_count,
+		volume.cluster_size()
+	);
+
+	Ok(Box::new(FatDirectory {
+		volume: Arc::new(Mutex::new(volume)),
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use hermit_sync::InterruptTicketMutex;
+
+	use super::*;
+	use crate::drivers::block::RamDisk;
+
+	const SECTOR_SIZE: usize = 512;
+
+	/// Returns an empty volume with 32 reserved sectors, one FAT of 8 sectors
+	/// and 256 clusters of one sector.
+	fn volume() -> FatVolume {
+		let mut image = vec![0u8; (32 + 8 + 256) * SECTOR_SIZE];
+		write_u16(&mut image, 11, u16::try_from(SECTOR_SIZE).unwrap());
+		image[13] = 1;
+		write_u16(&mut image, 14, 32);
+		image[16] = 1;
+		image[21] = 0xf8;
+		write_u32(&mut image, 32, 32 + 8 + 256);
+		write_u32(&mut image, 36, 8);
+		write_u32(&mut image, 44, FIRST_CLUSTER);
+		image[510..512].copy_from_slice(&[0x55, 0xaa]);
+
+		let fat = 32 * SECTOR_SIZE;
+		write_u32(&mut image, fat, 0x0fff_fff8);
+		write_u32(&mut image, fat + 4, CLUSTER_MASK);
+		write_u32(&mut image, fat + 8, CLUSTER_MASK);
+
+		let device: BlockDeviceRef =
+			Arc::new(InterruptTicketMutex::new(RamDisk::new(SECTOR_SIZE, image)));
+		FatVolume::new(device).unwrap()
+	}
+
+	fn create(volume: &mut FatVolume, path: &[&str]) {
+		let (dir, _) = volume.lookup(path).unwrap();
+		volume
+			.create_entry(dir, path.last().unwrap(), ATTR_ARCHIVE, 0)
+			.unwrap();
+	}
+
+	fn is_dir(volume: &mut FatVolume, path: &[&str]) -> bool {
+		volume.lookup(path).unwrap().1.unwrap().is_dir()
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_fat_rename_within_and_across_directories() {
+		let mut volume = volume();
+		volume.mkdir(&["a"]).unwrap();
+		volume.mkdir(&["b"]).unwrap();
+		create(&mut volume, &["a", "file"]);
+		create(&mut volume, &["a", "target"]);
+
+		volume
+			.rename(&["a", "file"], &["a", "renamed"], RenameFlags::empty())
+			.unwrap();
+		assert!(volume.lookup(&["a", "file"]).unwrap().1.is_none());
+		volume
+			.rename(&["a", "renamed"], &["a", "target"], RenameFlags::empty())
+			.unwrap();
+		assert!(volume.lookup(&["a", "renamed"]).unwrap().1.is_none());
+
+		volume
+			.rename(&["a", "target"], &["b", "file"], RenameFlags::empty())
+			.unwrap();
+		assert!(!is_dir(&mut volume, &["b", "file"]));
+		assert!(volume.lookup(&["a", "target"]).unwrap().1.is_none());
+
+		volume
+			.rename(&["b"], &["a", "b"], RenameFlags::empty())
+			.unwrap();
+		assert!(!is_dir(&mut volume, &["a", "b", "file"]));
+		assert!(volume.lookup(&["b"]).unwrap().1.is_none());
+		// The entry `..` of the moved directory refers to its new parent.
+		let a = volume.lookup_dir(&["a"]).unwrap();
+		let b = volume.lookup_dir(&["a", "b"]).unwrap();
+		let dotdot = volume
+			.read_entry(EntryLocation {
+				cluster: b,
+				index: 1,
+			})
+			.unwrap();
+		assert_eq!(u32::from(read_u16(&dotdot, 26)), a);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_fat_rename_exchange_file_and_directory() {
+		let mut volume = volume();
+		volume.mkdir(&["a"]).unwrap();
+		create(&mut volume, &["a", "file"]);
+		volume.mkdir(&["dir"]).unwrap();
+		create(&mut volume, &["dir", "inner"]);
+
+		volume
+			.rename(&["a", "file"], &["dir"], RenameFlags::RENAME_EXCHANGE)
+			.unwrap();
+		assert!(is_dir(&mut volume, &["a", "file"]));
+		assert!(!is_dir(&mut volume, &["dir"]));
+		assert!(volume.lookup(&["a", "file", "inner"]).unwrap().1.is_some());
+		let a = volume.lookup_dir(&["a"]).unwrap();
+		let moved = volume.lookup_dir(&["a", "file"]).unwrap();
+		let dotdot = volume
+			.read_entry(EntryLocation {
+				cluster: moved,
+				index: 1,
+			})
+			.unwrap();
+		assert_eq!(u32::from(read_u16(&dotdot, 26)), a);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_fat_rename_replace_directory() {
+		let mut volume = volume();
+		volume.mkdir(&["src"]).unwrap();
+		volume.mkdir(&["empty"]).unwrap();
+		volume.mkdir(&["full"]).unwrap();
+		create(&mut volume, &["full", "file"]);
+		create(&mut volume, &["file"]);
+
+		assert_eq!(
+			volume.rename(&["src"], &["full"], RenameFlags::empty()),
+			Err(Errno::Notempty)
+		);
+		assert_eq!(
+			volume.rename(&["src"], &["file"], RenameFlags::empty()),
+			Err(Errno::Notdir)
+		);
+		assert_eq!(
+			volume.rename(&["file"], &["empty"], RenameFlags::empty()),
+			Err(Errno::Isdir)
+		);
+		volume
+			.rename(&["src"], &["empty"], RenameFlags::empty())
+			.unwrap();
+		assert!(volume.lookup(&["src"]).unwrap().1.is_none());
+		assert!(is_dir(&mut volume, &["empty"]));
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_fat_rename_directory_into_itself() {
+		let mut volume = volume();
+		volume.mkdir(&["dir"]).unwrap();
+		volume.mkdir(&["dir", "sub"]).unwrap();
+
+		assert_eq!(
+			volume.rename(&["dir"], &["dir", "sub", "dir"], RenameFlags::empty()),
+			Err(Errno::Inval)
+		);
+		assert_eq!(
+			volume.rename(&["dir"], &["dir", "new"], RenameFlags::empty()),
+			Err(Errno::Inval)
+		);
+		assert!(is_dir(&mut volume, &["dir", "sub"]));
+	}
+}
@@ -1,34 +1,39 @@
 use alloc::borrow::ToOwned;
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::ffi::CString;
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::ffi::CStr;
 use core::marker::PhantomData;
 use core::mem::{MaybeUninit, align_of, offset_of, size_of};
 use core::sync::atomic::{AtomicU64, Ordering};
-use core::task::Poll;
-use core::{future, mem};
+use core::task::{Poll, Waker};
+use core::{future, mem, slice};
 
 use align_address::Align;
 use async_lock::Mutex;
 use async_trait::async_trait;
-use embedded_io::{ErrorType, Read, Write};
+use crossbeam_utils::Backoff;
 use fuse_abi::linux::*;
+use hermit_sync::{InterruptTicketMutex, OnceCell};
+use memory_addresses::VirtAddr;
 
 use crate::alloc::string::ToString;
 #[cfg(not(feature = "pci"))]
 use crate::arch::kernel::mmio::get_filesystem_driver;
 #[cfg(feature = "pci")]
 use crate::drivers::pci::get_filesystem_driver;
+use crate::drivers::virtio::transport::ShmRegion;
 use crate::drivers::virtio::virtqueue::error::VirtqError;
 use crate::errno::Errno;
-use crate::executor::block_on;
-use crate::fd::PollEvent;
+use crate::executor::{WakerRegistration, block_on, spawn};
+use crate::fd::{PollEvent, StatusFlags};
 use crate::fs::fuse::ops::SetAttrValidFields;
 use crate::fs::{
-	self, AccessPermission, DirectoryEntry, FileAttr, NodeKind, ObjectInterface, OpenOption,
-	SeekWhence, VfsNode,
+	self, AccessPermission, DirectoryEntry, FileAttr, FileTimes, NodeKind, ObjectInterface,
+	OpenOption, RenameFlags, SeekWhence, StatFs, VfsNode, seek_dir,
 };
 use crate::mm::device_alloc::DeviceAlloc;
 use crate::syscalls::Dirent64;
@@ -48,16 +53,38 @@ const S_IFLNK: u32 = 0o120_000;
 const S_IFMT: u32 = 0o170_000;
 
 pub(crate) trait FuseInterface {
-	fn send_command<O: ops::Op + 'static>(
+	/// Returns whether the device has no room for another request.
+	fn is_full(&mut self) -> bool;
+
+	/// Submits `cmd` without waiting for its response and returns the
+	/// `unique` field, which tags the request.
+	///
+	/// Several requests may be outstanding and complete in any order. Their
+	/// responses are collected by [`try_complete`](Self::try_complete).
+	fn submit<O: ops::Op + 'static>(
 		&mut self,
 		cmd: Cmd<O>,
 		rsp_payload_len: u32,
-	) -> Result<Rsp<O>, FuseError>
+	) -> Result<u64, FuseError>
 	where
 		<O as ops::Op>::InStruct: Send,
 		<O as ops::Op>::OutStruct: Send;
 
+	/// Returns the response of the request `unique`, if it has arrived, and
+	/// registers `waker` otherwise.
+	fn try_complete<O: ops::Op + 'static>(
+		&mut self,
+		unique: u64,
+		waker: Option<&Waker>,
+	) -> Option<Result<Rsp<O>, FuseError>>;
+
+	/// Discards the response of the request `unique`.
+	fn abandon(&mut self, unique: u64);
+
 	fn get_mount_point(&self) -> String;
+
+	/// Returns the window, into which the device maps extents of files.
+	fn get_dax_window(&self) -> Option<ShmRegion>;
 }
 
 pub(crate) mod ops {
@@ -100,12 +127,13 @@ pub(crate) mod ops {
 	}
 
 	impl Init {
-		pub(crate) fn create() -> (Cmd<Self>, u32) {
+		pub(crate) fn create(flags: u32) -> (Cmd<Self>, u32) {
 			let cmd = Cmd::new(
 				FUSE_ROOT_ID,
 				fuse_init_in {
 					major: 7,
 					minor: 31,
+					flags,
 					..Default::default()
 				},
 			);
@@ -325,8 +353,8 @@ pub(crate) mod ops {
 					size: attr.st_size as u64,
 					atime: attr.st_atim.tv_sec as u64,
 					atimensec: attr.st_atim.tv_nsec as u32,
-					mtime: attr.st_ctim.tv_sec as u64,
-					mtimensec: attr.st_ctim.tv_nsec as u32,
+					mtime: attr.st_mtim.tv_sec as u64,
+					mtimensec: attr.st_mtim.tv_nsec as u32,
 					ctime: attr.st_ctim.tv_sec as u64,
 					ctimensec: attr.st_ctim.tv_nsec as u32,
 					mode: attr.st_mode.bits(),
@@ -385,6 +413,30 @@ pub(crate) mod ops {
 		}
 	}
 
+	#[derive(Debug)]
+	pub(crate) struct Fsync;
+
+	impl Op for Fsync {
+		const OP_CODE: fuse_opcode = fuse_opcode::FUSE_FSYNC;
+		type InStruct = fuse_fsync_in;
+		type InPayload = ();
+		type OutStruct = ();
+		type OutPayload = ();
+	}
+
+	impl Fsync {
+		pub(crate) fn create(nid: u64, fh: u64) -> (Cmd<Self>, u32) {
+			let cmd = Cmd::new(
+				nid,
+				fuse_fsync_in {
+					fh,
+					..Default::default()
+				},
+			);
+			(cmd, 0)
+		}
+	}
+
 	#[derive(Debug)]
 	pub(crate) struct Poll;
 
@@ -472,6 +524,121 @@ pub(crate) mod ops {
 		}
 	}
 
+	#[derive(Debug)]
+	pub(crate) struct SetupMapping;
+
+	impl Op for SetupMapping {
+		const OP_CODE: fuse_opcode = fuse_opcode::FUSE_SETUPMAPPING;
+		type InStruct = fuse_setupmapping_in;
+		type InPayload = ();
+		type OutStruct = ();
+		type OutPayload = ();
+	}
+
+	impl SetupMapping {
+		pub(crate) fn create(
+			nid: u64,
+			fh: u64,
+			foffset: u64,
+			len: u64,
+			moffset: u64,
+		) -> (Cmd<Self>, u32) {
+			let cmd = Cmd::new(
+				nid,
+				fuse_setupmapping_in {
+					fh,
+					foffset,
+					len,
+					flags: FUSE_SETUPMAPPING_FLAG_READ.into(),
+					moffset,
+				},
+			);
+			(cmd, 0)
+		}
+	}
+
+	#[derive(Debug)]
+	pub(crate) struct RemoveMapping;
+
+	impl Op for RemoveMapping {
+		const OP_CODE: fuse_opcode = fuse_opcode::FUSE_REMOVEMAPPING;
+		type InStruct = fuse_removemapping_in;
+		type InPayload = [u8];
+		type OutStruct = ();
+		type OutPayload = ();
+	}
+
+	impl RemoveMapping {
+		/// Removes the mappings of `len` bytes at each offset of `moffsets` in the DAX window.
+		pub(crate) fn create(nid: u64, moffsets: &[u64], len: u64) -> (Cmd<Self>, u32) {
+			// The payload is an array of `fuse_removemapping_one`.
+			let payload = moffsets
+				.iter()
+				.flat_map(|moffset| [moffset.to_ne_bytes(), len.to_ne_bytes()])
+				.flatten()
+				.collect::<Box<[u8]>>();
+			let cmd = Cmd::with_boxed_slice(
+				nid,
+				fuse_removemapping_in {
+					count: moffsets.len().try_into().unwrap(),
+				},
+				payload,
+			);
+			(cmd, 0)
+		}
+	}
+
+	#[derive(Debug)]
+	pub(crate) struct Rename2;
+
+	impl Op for Rename2 {
+		const OP_CODE: fuse_opcode = fuse_opcode::FUSE_RENAME2;
+		type InStruct = fuse_rename2_in;
+		type InPayload = [u8];
+		type OutStruct = ();
+		type OutPayload = ();
+	}
+
+	impl Rename2 {
+		/// Renames `old` to `new`, which are both relative to the root node.
+		pub(crate) fn create(old: CString, new: CString, flags: u32) -> (Cmd<Self>, u32) {
+			let payload = old
+				.as_bytes_with_nul()
+				.iter()
+				.chain(new.as_bytes_with_nul())
+				.copied()
+				.collect::<Box<[u8]>>();
+			let cmd = Cmd::with_boxed_slice(
+				FUSE_ROOT_ID,
+				fuse_rename2_in {
+					newdir: FUSE_ROOT_ID,
+					flags,
+					..Default::default()
+				},
+				payload,
+			);
+			(cmd, 0)
+		}
+	}
+
+	#[derive(Debug)]
+	pub(crate) struct Statfs;
+
+	impl Op for Statfs {
+		const OP_CODE: fuse_opcode = fuse_opcode::FUSE_STATFS;
+		type InStruct = ();
+		type InPayload = ();
+		type OutStruct = fuse_statfs_out;
+		type OutPayload = ();
+	}
+
+	impl Statfs {
+		pub(crate) fn create() -> (Cmd<Self>, u32) {
+			let cmd = Cmd::new(FUSE_ROOT_ID, ());
+			(cmd, 0)
+		}
+	}
+
 	#[derive(Debug)]
 	pub(crate) struct Lookup;
 
@@ -644,23 +811,235 @@ impl From<FuseError> for Errno {
 	}
 }
 
+/// Sends `cmd` to the device and waits for its response.
+///
+/// The driver is only locked to submit the request and to collect the
+/// responses, so that the requests of other cores are outstanding at the same
+/// time.
+fn send_command<O: ops::Op + 'static>(
+	cmd: Cmd<O>,
+	rsp_payload_len: u32,
+) -> Result<Rsp<O>, FuseError>
+where
+	<O as ops::Op>::InStruct: Send,
+	<O as ops::Op>::OutStruct: Send,
+{
+	let driver = get_filesystem_driver().ok_or(FuseError::IOError(Errno::Nosys))?;
+	let backoff = Backoff::new();
+	let unique = loop {
+		let mut guard = driver.lock();
+		if !guard.is_full() {
+			break guard.submit(cmd, rsp_payload_len)?;
+		}
+		drop(guard);
+		backoff.snooze();
+	};
+
+	backoff.reset();
+	loop {
+		if let Some(result) = driver.lock().try_complete(unique, None) {
+			return result;
+		}
+		backoff.snooze();
+	}
+}
+
+/// Discards the response of the request `unique`, if its future is dropped.
+struct Submitted(u64);
+
+impl Drop for Submitted {
+	fn drop(&mut self) {
+		if let Some(driver) = get_filesystem_driver() {
+			driver.lock().abandon(self.0);
+		}
+	}
+}
+
+/// Sends `cmd` to the device and waits for its response without blocking the
+/// calling task.
+///
+/// The interrupt of the device wakes the waiting future, while the task runs
+/// other futures or sleeps.
+async fn send_command_async<O: ops::Op + 'static>(
+	cmd: Cmd<O>,
+	rsp_payload_len: u32,
+) -> Result<Rsp<O>, FuseError>
+where
+	<O as ops::Op>::InStruct: Send,
+	<O as ops::Op>::OutStruct: Send,
+{
+	let driver = get_filesystem_driver().ok_or(FuseError::IOError(Errno::Nosys))?;
+	let mut cmd = Some(cmd);
+	let unique = future::poll_fn(|cx| {
+		let mut guard = driver.lock();
+		if guard.is_full() {
+			// FIXME: only wake when progress can be made
+			cx.waker().wake_by_ref();
+			Poll::Pending
+		} else {
+			Poll::Ready(guard.submit(cmd.take().unwrap(), rsp_payload_len))
+		}
+	})
+	.await?;
+	let submitted = Submitted(unique);
+
+	future::poll_fn(
+		|cx| match driver.lock().try_complete(submitted.0, Some(cx.waker())) {
+			Some(result) => Poll::Ready(result),
+			None => Poll::Pending,
+		},
+	)
+	.await
+}
+
+/// Returns the time in microseconds since boot, until which an entry is
+/// valid according to the timeout `secs` and `nsecs` of the daemon.
+fn expiry(secs: u64, nsecs: u32) -> u64 {
+	arch::processor::get_timer_ticks()
+		.saturating_add(secs.saturating_mul(1_000_000))
+		.saturating_add(u64::from(nsecs) / 1000)
+}
+
+/// Cache of the lookups and of the attributes
+///
+/// The entries expire after the timeouts, which the daemon returns with them.
+/// Operations, which change a path or a node, invalidate the affected
+/// entries. With `virtiofs.cache=none`, nothing is cached.
+struct Cache {
+	/// Node ids and their expiry by path
+	entries: BTreeMap<CString, (u64, u64)>,
+	/// Attributes and their expiry by node id
+	attrs: BTreeMap<u64, (FileAttr, u64)>,
+}
+
+static CACHE: InterruptTicketMutex<Cache> = InterruptTicketMutex::new(Cache {
+	entries: BTreeMap::new(),
+	attrs: BTreeMap::new(),
+});
+
+impl Cache {
+	fn insert_entry(&mut self, path: &CStr, entry: &fuse_entry_out) {
+		if !crate::env::virtiofs_cache() || entry.nodeid == 0 {
+			return;
+		}
+
+		if entry.entry_valid != 0 || entry.entry_valid_nsec != 0 {
+			let expiry = expiry(entry.entry_valid, entry.entry_valid_nsec);
+			self.entries.insert(path.to_owned(), (entry.nodeid, expiry));
+		}
+		self.insert_attr(
+			entry.nodeid,
+			FileAttr::from(entry.attr),
+			entry.attr_valid,
+			entry.attr_valid_nsec,
+		);
+	}
+
+	fn insert_attr(&mut self, nid: u64, attr: FileAttr, secs: u64, nsecs: u32) {
+		if crate::env::virtiofs_cache() && (secs != 0 || nsecs != 0) {
+			self.attrs.insert(nid, (attr, expiry(secs, nsecs)));
+		}
+	}
+
+	fn nid(&mut self, path: &CStr) -> Option<u64> {
+		let &(nid, expiry) = self.entries.get(path)?;
+		if expiry <= arch::processor::get_timer_ticks() {
+			self.entries.remove(path);
+			return None;
+		}
+		Some(nid)
+	}
+
+	fn attr(&mut self, nid: u64) -> Option<FileAttr> {
+		let &(attr, expiry) = self.attrs.get(&nid)?;
+		if expiry <= arch::processor::get_timer_ticks() {
+			self.attrs.remove(&nid);
+			return None;
+		}
+		Some(attr)
+	}
+
+	fn invalidate_attr(&mut self, nid: u64) {
+		self.attrs.remove(&nid);
+	}
+
+	/// Removes `path`, the paths below it and their attributes. The attributes
+	/// of the parent directory are removed as well, since its times and link
+	/// count change.
+	fn invalidate(&mut self, path: &CStr) {
+		let path = path.to_bytes();
+		let parent = match path.iter().rposition(|&c| c == b'/') {
+			Some(0) => &path[..1],
+			Some(i) => &path[..i],
+			None => &[],
+		};
+
+		let Self { entries, attrs } = self;
+		entries.retain(|cached, &mut (nid, _)| {
+			let cached = cached.to_bytes();
+			if cached == parent {
+				attrs.remove(&nid);
+				return true;
+			}
+
+			let below = cached
+				.strip_prefix(path)
+				.is_some_and(|rest| rest.is_empty() || rest.starts_with(b"/"));
+			if below {
+				attrs.remove(&nid);
+			}
+			!below
+		});
+	}
+}
+
 fn lookup(name: CString) -> Option<u64> {
-	let (cmd, rsp_payload_len) = ops::Lookup::create(name);
-	let rsp = get_filesystem_driver()
-		.unwrap()
-		.lock()
-		.send_command(cmd, rsp_payload_len)
-		.ok()?;
-	Some(rsp.headers.op_header.nodeid)
+	lookup_attr(name).ok().map(|(nid, _)| nid)
+}
+
+/// Returns the node id and the attributes of the path `name`.
+///
+/// A symbolic link in the last component is not followed.
+fn lookup_attr(name: CString) -> io::Result<(u64, FileAttr)> {
+	{
+		let mut cache = CACHE.lock();
+		if let Some(nid) = cache.nid(&name)
+			&& let Some(attr) = cache.attr(nid)
+		{
+			return Ok((nid, attr));
+		}
+	}
+
+	let (cmd, rsp_payload_len) = ops::Lookup::create(name.clone());
+	let rsp = send_command(cmd, rsp_payload_len)?;
+	if rsp.headers.out_header.error != 0 {
+		return Err(Errno::try_from(-rsp.headers.out_header.error).unwrap());
+	}
+
+	let entry = &rsp.headers.op_header;
+	CACHE.lock().insert_entry(&name, entry);
+	Ok((entry.nodeid, FileAttr::from(entry.attr)))
+}
+
+/// Returns the attributes and valid fields of `FUSE_SETATTR`, which set `times`.
+fn times_to_attr(times: FileTimes) -> (FileAttr, SetAttrValidFields) {
+	let mut attr = FileAttr::default();
+	let mut valid = SetAttrValidFields::empty();
+	if let Some(atime) = times.atime {
+		attr.st_atim = atime;
+		valid |= SetAttrValidFields::FATTR_ATIME;
+	}
+	if let Some(mtime) = times.mtime {
+		attr.st_mtim = mtime;
+		valid |= SetAttrValidFields::FATTR_MTIME;
+	}
+	(attr, valid)
 }
 
 fn readlink(nid: u64) -> io::Result<String> {
 	let len = MAX_READ_LEN as u32;
 	let (cmd, rsp_payload_len) = ops::Readlink::create(nid, len);
-	let rsp = get_filesystem_driver()
-		.unwrap()
-		.lock()
-		.send_command(cmd, rsp_payload_len)?;
+	let rsp = send_command(cmd, rsp_payload_len)?;
 	let len: usize = if rsp.headers.out_header.len as usize - mem::size_of::<fuse_out_header>()
 		>= usize::try_from(len).unwrap()
 	{
@@ -672,11 +1051,132 @@ fn readlink(nid: u64) -> io::Result<String> {
 	Ok(String::from_utf8(rsp.payload.unwrap()[..len].to_vec()).unwrap())
 }
 
+/// Size of the chunks, in which extents of files are mapped into the DAX window
+const DAX_CHUNK_SIZE: usize = 2 * 1024 * 1024;
+
+/// DAX window of the virtio-fs device.
+///
+/// The window is divided into chunks of [`DAX_CHUNK_SIZE`] bytes. Reads let
+/// the device map the chunk of the file, which contains the requested data,
+/// into the window and copy the data directly from the host's page cache. If
+/// all chunks are in use, they are reused in round-robin order.
+///
+/// Accesses beyond the end of a file fault on the host. Therefore, reads are
+/// limited to the file size, which is cached per node and updated by writes and
+/// truncations of the guest. Files must not be truncated by the host while
+/// they are opened in the guest.
+struct DaxWindow {
+	addr: VirtAddr,
+	/// Mapped extent of each chunk: node id, file handle, and offset within the file
+	chunks: Vec<Option<(u64, u64, u64)>>,
+	/// Next chunk to be reused
+	next: usize,
+	/// Cached file sizes by node id
+	sizes: BTreeMap<u64, usize>,
+}
+
+static DAX_WINDOW: OnceCell<InterruptTicketMutex<DaxWindow>> = OnceCell::new();
+
+impl DaxWindow {
+	fn new(window: ShmRegion) -> Self {
+		Self {
+			addr: window.addr,
+			chunks: vec![None; window.len / DAX_CHUNK_SIZE],
+			next: 0,
+			sizes: BTreeMap::new(),
+		}
+	}
+
+	/// Returns the address of the chunk at `foffset` of the file and maps the
+	/// chunk, if necessary.
+	fn map(&mut self, nid: u64, fh: u64, foffset: u64) -> io::Result<VirtAddr> {
+		let extent = Some((nid, fh, foffset));
+		let index = if let Some(index) = self.chunks.iter().position(|chunk| *chunk == extent) {
+			index
+		} else {
+			let index = self
+				.chunks
+				.iter()
+				.position(Option::is_none)
+				.unwrap_or_else(|| {
+					let index = self.next;
+					self.next = (index + 1) % self.chunks.len();
+					index
+				});
+
+			// The device replaces an existing mapping of the chunk.
+			self.chunks[index] = None;
+			let (cmd, rsp_payload_len) = ops::SetupMapping::create(
+				nid,
+				fh,
+				foffset,
+				DAX_CHUNK_SIZE as u64,
+				(index * DAX_CHUNK_SIZE) as u64,
+			);
+			send_command(cmd, rsp_payload_len)?;
+			self.chunks[index] = extent;
+			index
+		};
+
+		Ok(self.addr + (index * DAX_CHUNK_SIZE) as u64)
+	}
+
+	/// Removes the mappings of the file handle `fh`.
+	fn unmap(&mut self, nid: u64, fh: u64) -> io::Result<()> {
+		self.sizes.remove(&nid);
+
+		let mut moffsets = Vec::new();
+		for (index, chunk) in self.chunks.iter_mut().enumerate() {
+			if chunk.is_some_and(|(_, chunk_fh, _)| chunk_fh == fh) {
+				*chunk = None;
+				moffsets.push((index * DAX_CHUNK_SIZE) as u64);
+			}
+		}
+		if moffsets.is_empty() {
+			return Ok(());
+		}
+
+		let (cmd, rsp_payload_len) =
+			ops::RemoveMapping::create(nid, &moffsets, DAX_CHUNK_SIZE as u64);
+		send_command(cmd, rsp_payload_len)?;
+		Ok(())
+	}
+}
+
+/// Reads up to `len` bytes at `offset` of the file by `FUSE_READ`.
+async fn read_at(nid: u64, fh: u64, len: usize, offset: usize) -> io::Result<Vec<u8, DeviceAlloc>> {
+	let len = len.min(MAX_READ_LEN);
+	let (cmd, rsp_payload_len) = ops::Read::create(nid, fh, len.try_into().unwrap(), offset as u64);
+	let rsp = send_command_async(cmd, rsp_payload_len).await?;
+	let len = len.min((rsp.headers.out_header.len as usize) - mem::size_of::<fuse_out_header>());
+
+	let mut payload = rsp.payload.unwrap_or_else(|| Vec::new_in(DeviceAlloc));
+	payload.truncate(len);
+	Ok(payload)
+}
+
+/// Read, which runs in the background for a non-blocking file handle
+#[derive(Debug)]
+struct ReadAhead {
+	/// Offset in the file, at which the read starts
+	offset: usize,
+	state: InterruptTicketMutex<ReadAheadState>,
+}
+
+#[derive(Debug)]
+struct ReadAheadState {
+	result: Option<io::Result<Vec<u8, DeviceAlloc>>>,
+	waker: WakerRegistration,
+}
+
 #[derive(Debug)]
 struct FuseFileHandleInner {
 	fuse_nid: Option<u64>,
 	fuse_fh: Option<u64>,
 	offset: usize,
+	/// Reads return `EAGAIN` until their data has been received.
+	nonblocking: bool,
+	read_ahead: Option<Arc<ReadAhead>>,
 }
 
 impl FuseFileHandleInner {
@@ -685,20 +1185,102 @@ impl FuseFileHandleInner {
 			fuse_nid: None,
 			fuse_fh: None,
 			offset: 0,
+			nonblocking: false,
+			read_ahead: None,
 		}
 	}
 
-	async fn poll(&self, events: PollEvent) -> io::Result<PollEvent> {
+	/// Starts to read up to `len` bytes at the current offset in the background.
+	fn start_read_ahead(&mut self, nid: u64, fh: u64, len: usize) -> Arc<ReadAhead> {
+		let read_ahead = Arc::new(ReadAhead {
+			offset: self.offset,
+			state: InterruptTicketMutex::new(ReadAheadState {
+				result: None,
+				waker: WakerRegistration::new(),
+			}),
+		});
+		self.read_ahead = Some(read_ahead.clone());
+
+		let task = read_ahead.clone();
+		spawn(async move {
+			let result = read_at(nid, fh, len, task.offset).await;
+			let mut state = task.state.lock();
+			state.result = Some(result);
+			state.waker.wake();
+		});
+
+		read_ahead
+	}
+
+	/// Returns the read ahead at the current offset, if it has completed.
+	fn take_read_ahead(
+		&mut self,
+		nid: u64,
+		fh: u64,
+		len: usize,
+	) -> io::Result<Vec<u8, DeviceAlloc>> {
+		if let Some(read_ahead) = &self.read_ahead
+			&& read_ahead.offset == self.offset
+		{
+			let result = read_ahead.state.lock().result.take();
+			return match result {
+				Some(result) => {
+					self.read_ahead = None;
+					result
+				}
+				None => Err(Errno::Again),
+			};
+		}
+
+		self.start_read_ahead(nid, fh, len);
+		Err(Errno::Again)
+	}
+
+	/// Waits, until the read ahead at the current offset has completed.
+	async fn poll_read_ahead(&mut self, nid: u64, fh: u64, events: PollEvent) -> PollEvent {
+		let read_ahead = match &self.read_ahead {
+			Some(read_ahead) if read_ahead.offset == self.offset => read_ahead.clone(),
+			_ => self.start_read_ahead(nid, fh, MAX_READ_LEN),
+		};
+
+		// Files are always writable.
+		let writable = events & (PollEvent::POLLOUT | PollEvent::POLLWRNORM);
+		future::poll_fn(|cx| {
+			let mut state = read_ahead.state.lock();
+			let revents = if state.result.is_some() {
+				writable | (events & (PollEvent::POLLIN | PollEvent::POLLRDNORM))
+			} else {
+				writable
+			};
+
+			if revents.is_empty() {
+				state.waker.register(cx.waker());
+				Poll::Pending
+			} else {
+				Poll::Ready(revents)
+			}
+		})
+		.await
+	}
+
+	async fn poll(&mut self, events: PollEvent) -> io::Result<PollEvent> {
 		static KH: AtomicU64 = AtomicU64::new(0);
 		let kh = KH.fetch_add(1, Ordering::SeqCst);
 
+		// The daemon reports regular files as always readable, but a
+		// non-blocking read becomes ready only with its data.
+		if self.nonblocking
+			&& events.intersects(PollEvent::POLLIN | PollEvent::POLLRDNORM)
+			&& DAX_WINDOW.get().is_none()
+			&& let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh)
+		{
+			return Ok(self.poll_read_ahead(nid, fh, events).await);
+		}
+
 		future::poll_fn(|cx| {
 			if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
 				let (cmd, rsp_payload_len) = ops::Poll::create(nid, fh, kh, events);
-				let rsp = get_filesystem_driver()
-					.ok_or(Errno::Nosys)?
-					.lock()
-					.send_command(cmd, rsp_payload_len)?;
+				let rsp = send_command(cmd, rsp_payload_len)?;
 
 				if rsp.headers.out_header.error < 0 {
 					Poll::Ready(Err(Errno::Io))
@@ -728,10 +1310,7 @@ impl FuseFileHandleInner {
 
 		if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
 			let (cmd, rsp_payload_len) = ops::Lseek::create(nid, fh, offset, whence);
-			let rsp = get_filesystem_driver()
-				.ok_or(Errno::Nosys)?
-				.lock()
-				.send_command(cmd, rsp_payload_len)?;
+			let rsp = send_command(cmd, rsp_payload_len)?;
 
 			if rsp.headers.out_header.error < 0 {
 				return Err(Errno::Io);
@@ -739,6 +1318,7 @@ impl FuseFileHandleInner {
 
 			let rsp_offset = rsp.headers.op_header.offset;
 			self.offset = rsp.headers.op_header.offset.try_into().unwrap();
+			self.read_ahead = None;
 
 			Ok(rsp_offset.try_into().unwrap())
 		} else {
@@ -749,15 +1329,38 @@ impl FuseFileHandleInner {
 	fn fstat(&mut self) -> io::Result<FileAttr> {
 		debug!("FUSE getattr");
 		if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
+			if let Some(attr) = CACHE.lock().attr(nid) {
+				return Ok(attr);
+			}
+
 			let (cmd, rsp_payload_len) = ops::Getattr::create(nid, fh, FUSE_GETATTR_FH);
-			let rsp = get_filesystem_driver()
-				.ok_or(Errno::Nosys)?
-				.lock()
-				.send_command(cmd, rsp_payload_len)?;
+			let rsp = send_command(cmd, rsp_payload_len)?;
 			if rsp.headers.out_header.error < 0 {
 				return Err(Errno::Io);
 			}
-			Ok(rsp.headers.op_header.attr.into())
+			let attr_out = &rsp.headers.op_header;
+			let attr = FileAttr::from(attr_out.attr);
+			CACHE
+				.lock()
+				.insert_attr(nid, attr, attr_out.attr_valid, attr_out.attr_valid_nsec);
+			Ok(attr)
+		} else {
+			Err(Errno::Io)
+		}
+	}
+
+	fn fsync(&self) -> io::Result<()> {
+		debug!("FUSE fsync");
+		if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
+			let (cmd, rsp_payload_len) = ops::Fsync::create(nid, fh);
+			let result = send_command(cmd, rsp_payload_len);
+			match result {
+				Ok(_) => Ok(()),
+				// Like Linux, treat a daemon without support for fsync as
+				// one without volatile buffers.
+				Err(FuseError::IOError(Errno::Nosys)) => Ok(()),
+				Err(err) => Err(err.into()),
+			}
 		} else {
 			Err(Errno::Io)
 		}
@@ -767,48 +1370,91 @@ impl FuseFileHandleInner {
 		debug!("FUSE setattr");
 		if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
 			let (cmd, rsp_payload_len) = ops::Setattr::create(nid, fh, attr, valid);
-			let rsp = get_filesystem_driver()
-				.ok_or(Errno::Nosys)?
-				.lock()
-				.send_command(cmd, rsp_payload_len)?;
+			let rsp = send_command(cmd, rsp_payload_len)?;
 			if rsp.headers.out_header.error < 0 {
 				return Err(Errno::Io);
 			}
-			Ok(rsp.headers.op_header.attr.into())
+			let attr_out = &rsp.headers.op_header;
+			let attr = FileAttr::from(attr_out.attr);
+			CACHE
+				.lock()
+				.insert_attr(nid, attr, attr_out.attr_valid, attr_out.attr_valid_nsec);
+			if let Some(window) = DAX_WINDOW.get() {
+				window
+					.lock()
+					.sizes
+					.insert(nid, attr.st_size.try_into().unwrap());
+			}
+			Ok(attr)
 		} else {
 			Err(Errno::Io)
 		}
 	}
-}
 
-impl ErrorType for FuseFileHandleInner {
-	type Error = Errno;
+	/// Reads from the mapping of the file in the DAX window.
+	///
+	/// The read is limited to a single chunk of the window.
+	fn read_dax(
+		&mut self,
+		window: &InterruptTicketMutex<DaxWindow>,
+		nid: u64,
+		fh: u64,
+		buf: &mut [u8],
+	) -> io::Result<usize> {
+		let offset = self.offset;
+		let cached = window.lock().sizes.get(&nid).copied();
+		let size = match cached {
+			Some(size) if offset + buf.len() <= size => size,
+			// The file may have grown since its size has been determined.
+			_ => {
+				let size = self.fstat()?.st_size.try_into().unwrap();
+				window.lock().sizes.insert(nid, size);
+				size
+			}
+		};
+		if offset >= size {
+			return Ok(0);
+		}
+
+		let chunk_offset = offset % DAX_CHUNK_SIZE;
+		let len = buf
+			.len()
+			.min(size - offset)
+			.min(DAX_CHUNK_SIZE - chunk_offset);
+
+		// The lock prevents that the chunk is reused while it is read.
+		let mut window = window.lock();
+		let addr = window.map(nid, fh, (offset - chunk_offset) as u64)? + chunk_offset as u64;
+		// SAFETY: The chunk is mapped and the range lies within the file.
+		let src = unsafe { slice::from_raw_parts(addr.as_ptr::<u8>(), len) };
+		buf[..len].copy_from_slice(src);
+		drop(window);
+
+		self.offset += len;
+		Ok(len)
+	}
 }
 
-impl Read for FuseFileHandleInner {
-	fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
-		let mut len = buf.len();
-		if len > MAX_READ_LEN {
-			debug!("Reading longer than max_read_len: {len}");
-			len = MAX_READ_LEN;
+impl FuseFileHandleInner {
+	async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if let (Some(nid), Some(fh), Some(window)) = (self.fuse_nid, self.fuse_fh, DAX_WINDOW.get())
+		{
+			return self.read_dax(window, nid, fh, buf);
+		}
+
+		if buf.len() > MAX_READ_LEN {
+			debug!("Reading longer than max_read_len: {}", buf.len());
 		}
 		if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
-			let (cmd, rsp_payload_len) =
-				ops::Read::create(nid, fh, len.try_into().unwrap(), self.offset as u64);
-			let rsp = get_filesystem_driver()
-				.ok_or(Errno::Nosys)?
-				.lock()
-				.send_command(cmd, rsp_payload_len)?;
-			let len: usize =
-				if (rsp.headers.out_header.len as usize) - mem::size_of::<fuse_out_header>() >= len
-				{
-					len
-				} else {
-					(rsp.headers.out_header.len as usize) - mem::size_of::<fuse_out_header>()
-				};
+			let data = if self.nonblocking {
+				self.take_read_ahead(nid, fh, buf.len())?
+			} else {
+				read_at(nid, fh, buf.len(), self.offset).await?
+			};
+			let len = data.len().min(buf.len());
 			self.offset += len;
 
-			buf[..len].copy_from_slice(&rsp.payload.unwrap()[..len]);
+			buf[..len].copy_from_slice(&data[..len]);
 
 			Ok(len)
 		} else {
@@ -816,10 +1462,8 @@ impl Read for FuseFileHandleInner {
 			Err(Errno::Noent)
 		}
 	}
-}
 
-impl Write for FuseFileHandleInner {
-	fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+	async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
 		debug!("FUSE write!");
 		let mut truncated_len = buf.len();
 		if truncated_len > MAX_WRITE_LEN {
@@ -831,13 +1475,13 @@ impl Write for FuseFileHandleInner {
 			truncated_len = MAX_WRITE_LEN;
 		}
 		if let (Some(nid), Some(fh)) = (self.fuse_nid, self.fuse_fh) {
+			// Data, which has been read ahead, may be overwritten.
+			self.read_ahead = None;
+
 			let truncated_buf = Box::<[u8]>::from(&buf[..truncated_len]);
 			let (cmd, rsp_payload_len) =
 				ops::Write::create(nid, fh, truncated_buf, self.offset as u64);
-			let rsp = get_filesystem_driver()
-				.ok_or(Errno::Nosys)?
-				.lock()
-				.send_command(cmd, rsp_payload_len)?;
+			let rsp = send_command_async(cmd, rsp_payload_len).await?;
 
 			if rsp.headers.out_header.error < 0 {
 				return Err(Errno::Io);
@@ -850,28 +1494,34 @@ impl Write for FuseFileHandleInner {
 				rsp_size.try_into().unwrap()
 			};
 			self.offset += rsp_len;
+			CACHE.lock().invalidate_attr(nid);
+			if let Some(window) = DAX_WINDOW.get()
+				&& let Some(size) = window.lock().sizes.get_mut(&nid)
+			{
+				*size = (*size).max(self.offset);
+			}
 			Ok(rsp_len)
 		} else {
 			warn!("File not open, cannot read!");
 			Err(Errno::Noent)
 		}
 	}
-
-	fn flush(&mut self) -> Result<(), Self::Error> {
-		Ok(())
-	}
 }
 
 impl Drop for FuseFileHandleInner {
 	fn drop(&mut self) {
 		if self.fuse_nid.is_some() && self.fuse_fh.is_some() {
+			if let Some(window) = DAX_WINDOW.get()
+				&& let Err(err) = window
+					.lock()
+					.unmap(self.fuse_nid.unwrap(), self.fuse_fh.unwrap())
+			{
+				warn!("Unable to remove the DAX mappings of a file: {err:?}");
+			}
+
 			let (cmd, rsp_payload_len) =
 				ops::Release::create(self.fuse_nid.unwrap(), self.fuse_fh.unwrap());
-			get_filesystem_driver()
-				.unwrap()
-				.lock()
-				.send_command(cmd, rsp_payload_len)
-				.unwrap();
+			send_command(cmd, rsp_payload_len).unwrap();
 		}
 	}
 }
@@ -892,11 +1542,11 @@ impl ObjectInterface for FuseFileHandle {
 	}
 
 	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
-		self.0.lock().await.read(buf)
+		self.0.lock().await.read(buf).await
 	}
 
 	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
-		self.0.lock().await.write(buf)
+		self.0.lock().await.write(buf).await
 	}
 
 	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
@@ -920,6 +1570,10 @@ impl ObjectInterface for FuseFileHandle {
 			.map(|_| ())
 	}
 
+	async fn fsync(&self) -> io::Result<()> {
+		self.0.lock().await.fsync()
+	}
+
 	async fn chmod(&self, access_permission: AccessPermission) -> io::Result<()> {
 		let attr = FileAttr {
 			st_mode: access_permission,
@@ -932,6 +1586,26 @@ impl ObjectInterface for FuseFileHandle {
 			.set_attr(attr, SetAttrValidFields::FATTR_MODE)
 			.map(|_| ())
 	}
+
+	async fn utimens(&self, times: FileTimes) -> io::Result<()> {
+		let (attr, valid) = times_to_attr(times);
+		self.0.lock().await.set_attr(attr, valid).map(|_| ())
+	}
+
+	async fn status_flags(&self) -> io::Result<StatusFlags> {
+		let status_flags = if self.0.lock().await.nonblocking {
+			StatusFlags::O_NONBLOCK
+		} else {
+			StatusFlags::empty()
+		};
+
+		Ok(status_flags)
+	}
+
+	async fn set_status_flags(&mut self, status_flags: StatusFlags) -> io::Result<()> {
+		self.0.lock().await.nonblocking = status_flags.contains(StatusFlags::O_NONBLOCK);
+		Ok(())
+	}
 }
 
 impl Clone for FuseFileHandle {
@@ -973,10 +1647,7 @@ impl ObjectInterface for FuseDirectoryHandle {
 		// Flag 0x10000 for O_DIRECTORY might not be necessary
 		let (mut cmd, rsp_payload_len) = ops::Open::create(fuse_nid, 0x10000);
 		cmd.headers.in_header.opcode = fuse_opcode::FUSE_OPENDIR as u32;
-		let rsp = get_filesystem_driver()
-			.ok_or(Errno::Nosys)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 		let fuse_fh = rsp.headers.op_header.fh;
 
 		debug!("FUSE readdir: {path:#?}");
@@ -989,10 +1660,7 @@ impl ObjectInterface for FuseDirectoryHandle {
 		// read content of the directory
 		let (mut cmd, rsp_payload_len) = ops::Read::create(fuse_nid, fuse_fh, len, 0);
 		cmd.headers.in_header.opcode = fuse_opcode::FUSE_READDIR as u32;
-		let rsp = get_filesystem_driver()
-			.ok_or(Errno::Nosys)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 
 		let len = usize::min(
 			MAX_READ_LEN,
@@ -1006,7 +1674,10 @@ impl ObjectInterface for FuseDirectoryHandle {
 
 		let mut ret = 0;
 
-		while (rsp.headers.out_header.len as usize) - *rsp_offset > size_of::<fuse_dirent>() {
+		// The position may have been moved beyond the end by `lseek`.
+		while (rsp.headers.out_header.len as usize).saturating_sub(*rsp_offset)
+			> size_of::<fuse_dirent>()
+		{
 			let dirent = unsafe {
 				&*rsp
 					.payload
@@ -1025,12 +1696,19 @@ impl ObjectInterface for FuseDirectoryHandle {
 				break;
 			}
 
+			// Position of the next entry, aligned to the dirent struct
+			let next_offset = ((*rsp_offset)
+				+ core::mem::size_of::<fuse_dirent>()
+				+ dirent.namelen as usize
+				+ U64_SIZE - 1)
+				& (!(U64_SIZE - 1));
+
 			// could be replaced with slice_as_ptr once maybe_uninit_slice is stabilized.
 			let target_dirent = buf[buf_offset].as_mut_ptr().cast::<Dirent64>();
 			unsafe {
 				target_dirent.write(Dirent64 {
 					d_ino: dirent.ino,
-					d_off: 0,
+					d_off: next_offset.try_into().unwrap(),
 					d_reclen: (dirent_len.align_up(align_of::<Dirent64>()))
 						.try_into()
 						.unwrap(),
@@ -1046,33 +1724,21 @@ impl ObjectInterface for FuseDirectoryHandle {
 				nameptr.add(dirent.namelen as usize).write(0); // zero termination
 			}
 
-			*rsp_offset += core::mem::size_of::<fuse_dirent>() + dirent.namelen as usize;
-			// Align to dirent struct
-			*rsp_offset = ((*rsp_offset) + U64_SIZE - 1) & (!(U64_SIZE - 1));
+			*rsp_offset = next_offset;
 			buf_offset = next_dirent;
 			ret = buf_offset;
 		}
 
 		let (cmd, rsp_payload_len) = ops::Release::create(fuse_nid, fuse_fh);
-		get_filesystem_driver()
-			.unwrap()
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		send_command(cmd, rsp_payload_len)?;
 
 		Ok(ret)
 	}
 
-	/// lseek for a directory entry is the equivalent for seekdir on linux. But on Hermit this is
-	/// logically the same operation, so we can just use the same fn in the backend.
-	/// Any other offset than 0 is not supported. (Mostly because it doesn't make any sense, as
-	/// userspace applications have no way of knowing valid offsets)
+	/// The position is the offset within the response of the host, which is
+	/// reported as `d_off`.
 	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
-		if whence != SeekWhence::Set && offset != 0 {
-			error!("Invalid offset for directory lseek ({offset})");
-			return Err(Errno::Inval);
-		}
-		*self.read_position.lock().await = offset as usize;
-		Ok(offset)
+		seek_dir(&mut *self.read_position.lock().await, offset, whence)
 	}
 }
 
@@ -1140,10 +1806,7 @@ impl VfsNode for FuseDirectory {
 		// Flag 0x10000 for O_DIRECTORY might not be necessary
 		let (mut cmd, rsp_payload_len) = ops::Open::create(fuse_nid, 0x10000);
 		cmd.headers.in_header.opcode = fuse_opcode::FUSE_OPENDIR as u32;
-		let rsp = get_filesystem_driver()
-			.ok_or(Errno::Nosys)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 		let fuse_fh = rsp.headers.op_header.fh;
 
 		debug!("FUSE readdir: {path:#?}");
@@ -1155,10 +1818,7 @@ impl VfsNode for FuseDirectory {
 		// read content of the directory
 		let (mut cmd, rsp_payload_len) = ops::Read::create(fuse_nid, fuse_fh, len, 0);
 		cmd.headers.in_header.opcode = fuse_opcode::FUSE_READDIR as u32;
-		let rsp = get_filesystem_driver()
-			.ok_or(Errno::Nosys)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 
 		let len: usize = if rsp.headers.out_header.len as usize - mem::size_of::<fuse_out_header>()
 			>= usize::try_from(len).unwrap()
@@ -1201,10 +1861,7 @@ impl VfsNode for FuseDirectory {
 		}
 
 		let (cmd, rsp_payload_len) = ops::Release::create(fuse_nid, fuse_fh);
-		get_filesystem_driver()
-			.unwrap()
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		send_command(cmd, rsp_payload_len)?;
 
 		Ok(entries)
 	}
@@ -1214,25 +1871,12 @@ impl VfsNode for FuseDirectory {
 
 		debug!("FUSE stat: {path:#?}");
 
-		// Is there a better way to implement this?
-		let (cmd, rsp_payload_len) = ops::Lookup::create(path);
-		let rsp = get_filesystem_driver()
-			.unwrap()
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
-
-		if rsp.headers.out_header.error != 0 {
-			return Err(Errno::try_from(-rsp.headers.out_header.error).unwrap());
-		}
-
-		let entry_out = rsp.headers.op_header;
-		let attr = entry_out.attr;
-
-		if attr.mode & S_IFMT != S_IFLNK {
-			return Ok(FileAttr::from(attr));
+		let (nid, attr) = lookup_attr(path)?;
+		if attr.st_mode.bits() & S_IFMT != S_IFLNK {
+			return Ok(attr);
 		}
 
-		let path = readlink(entry_out.nodeid)?;
+		let path = readlink(nid)?;
 		let mut components: Vec<&str> = path.split('/').collect();
 		self.traverse_stat(&mut components)
 	}
@@ -1242,12 +1886,8 @@ impl VfsNode for FuseDirectory {
 
 		debug!("FUSE lstat: {path:#?}");
 
-		let (cmd, rsp_payload_len) = ops::Lookup::create(path);
-		let rsp = get_filesystem_driver()
-			.unwrap()
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
-		Ok(FileAttr::from(rsp.headers.op_header.attr))
+		let (_, attr) = lookup_attr(path)?;
+		Ok(attr)
 	}
 
 	fn traverse_open(
@@ -1267,13 +1907,7 @@ impl VfsNode for FuseDirectory {
 				return Err(Errno::Inval);
 			}
 
-			let (cmd, rsp_payload_len) = ops::Lookup::create(path.clone());
-			let rsp = get_filesystem_driver()
-				.unwrap()
-				.lock()
-				.send_command(cmd, rsp_payload_len)?;
-
-			let attr = FileAttr::from(rsp.headers.op_header.attr);
+			let (_, attr) = lookup_attr(path.clone())?;
 			if attr.st_mode.contains(AccessPermission::S_IFDIR) {
 				let mut path = path.into_string().unwrap();
 				path.remove(0);
@@ -1294,13 +1928,14 @@ impl VfsNode for FuseDirectory {
 			if opt.contains(OpenOption::O_CREAT) {
 				// Create file (opens implicitly, returns results from both lookup and open calls)
 				let (cmd, rsp_payload_len) =
-					ops::Create::create(path, opt.bits().try_into().unwrap(), mode.bits());
-				let rsp = get_filesystem_driver()
-					.ok_or(Errno::Nosys)?
-					.lock()
-					.send_command(cmd, rsp_payload_len)?;
+					ops::Create::create(path.clone(), opt.bits().try_into().unwrap(), mode.bits());
+				let rsp = send_command(cmd, rsp_payload_len)?;
 
 				let inner = rsp.headers.op_header;
+				let mut cache = CACHE.lock();
+				cache.invalidate(&path);
+				cache.insert_entry(&path, &inner.entry);
+				drop(cache);
 				file_guard.fuse_nid = Some(inner.entry.nodeid);
 				file_guard.fuse_fh = Some(inner.open.fh);
 			} else {
@@ -1315,27 +1950,81 @@ impl VfsNode for FuseDirectory {
 				// 3.FUSE_OPEN(nodeid, O_RDONLY) -> fh
 				let (cmd, rsp_payload_len) =
 					ops::Open::create(file_guard.fuse_nid.unwrap(), opt.bits().try_into().unwrap());
-				let rsp = get_filesystem_driver()
-					.ok_or(Errno::Nosys)?
-					.lock()
-					.send_command(cmd, rsp_payload_len)?;
+				let rsp = send_command(cmd, rsp_payload_len)?;
 				file_guard.fuse_fh = Some(rsp.headers.op_header.fh);
+				if opt.contains(OpenOption::O_TRUNC) {
+					CACHE.lock().invalidate_attr(file_guard.fuse_nid.unwrap());
+				}
 			}
 
+			file_guard.nonblocking = opt.contains(OpenOption::O_NONBLOCK);
 			drop(file_guard);
 
 			Ok(Arc::new(async_lock::RwLock::new(file)))
 		}
 	}
 
+	fn traverse_rename(
+		&self,
+		old: &mut Vec<&str>,
+		new: &mut Vec<&str>,
+		flags: RenameFlags,
+	) -> io::Result<()> {
+		let old = self.traversal_path(old);
+		let new = self.traversal_path(new);
+
+		let mut cache = CACHE.lock();
+		cache.invalidate(&old);
+		cache.invalidate(&new);
+		drop(cache);
+
+		let (cmd, rsp_payload_len) = ops::Rename2::create(old, new, flags.bits());
+		let rsp = send_command(cmd, rsp_payload_len)?;
+		trace!("rename answer {rsp:?}");
+
+		Ok(())
+	}
+
+	/// Returns the capacity of the exported file system, as reported by the host
+	fn traverse_statfs(&self, _components: &mut Vec<&str>) -> io::Result<StatFs> {
+		let (cmd, rsp_payload_len) = ops::Statfs::create();
+		let rsp = send_command(cmd, rsp_payload_len)?;
+		let st = rsp.headers.op_header.st;
+
+		Ok(StatFs {
+			f_type: fs::FUSE_SUPER_MAGIC,
+			f_bsize: st.bsize.into(),
+			f_blocks: st.blocks,
+			f_bfree: st.bfree,
+			f_bavail: st.bavail,
+			f_files: st.files,
+			f_ffree: st.ffree,
+			f_namelen: st.namelen.into(),
+			..Default::default()
+		})
+	}
+
+	fn traverse_utimens(&self, components: &mut Vec<&str>, times: FileTimes) -> io::Result<()> {
+		let path = self.traversal_path(components);
+
+		debug!("FUSE utimens: {path:#?}");
+
+		// The lookup does not follow a symbolic link in the last component.
+		let fuse_nid = lookup(path).ok_or(Errno::Noent)?;
+		CACHE.lock().invalidate_attr(fuse_nid);
+		let (attr, valid) = times_to_attr(times);
+		let (cmd, rsp_payload_len) = ops::Setattr::create(fuse_nid, 0, attr, valid);
+		send_command(cmd, rsp_payload_len)?;
+
+		Ok(())
+	}
+
 	fn traverse_unlink(&self, components: &mut Vec<&str>) -> io::Result<()> {
 		let path = self.traversal_path(components);
 
+		CACHE.lock().invalidate(&path);
 		let (cmd, rsp_payload_len) = ops::Unlink::create(path);
-		let rsp = get_filesystem_driver()
-			.ok_or(Errno::Nosys)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 		trace!("unlink answer {rsp:?}");
 
 		Ok(())
@@ -1344,11 +2033,9 @@ impl VfsNode for FuseDirectory {
 	fn traverse_rmdir(&self, components: &mut Vec<&str>) -> io::Result<()> {
 		let path = self.traversal_path(components);
 
+		CACHE.lock().invalidate(&path);
 		let (cmd, rsp_payload_len) = ops::Rmdir::create(path);
-		let rsp = get_filesystem_driver()
-			.ok_or(Errno::Nosys)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 		trace!("rmdir answer {rsp:?}");
 
 		Ok(())
@@ -1356,12 +2043,10 @@ impl VfsNode for FuseDirectory {
 
 	fn traverse_mkdir(&self, components: &mut Vec<&str>, mode: AccessPermission) -> io::Result<()> {
 		let path = self.traversal_path(components);
+		CACHE.lock().invalidate(&path);
 		let (cmd, rsp_payload_len) = ops::Mkdir::create(path, mode.bits());
 
-		let rsp = get_filesystem_driver()
-			.ok_or(Errno::Nosys)?
-			.lock()
-			.send_command(cmd, rsp_payload_len)?;
+		let rsp = send_command(cmd, rsp_payload_len)?;
 		if rsp.headers.out_header.error == 0 {
 			Ok(())
 		} else {
@@ -1374,10 +2059,36 @@ pub(crate) fn init() {
 	debug!("Try to initialize fuse filesystem");
 
 	if let Some(driver) = get_filesystem_driver() {
-		let (cmd, rsp_payload_len) = ops::Init::create();
-		let rsp = driver.lock().send_command(cmd, rsp_payload_len).unwrap();
+		let dax_window = driver.lock().get_dax_window();
+		let flags = if dax_window.is_some() {
+			FUSE_MAP_ALIGNMENT
+		} else {
+			0
+		};
+		let (cmd, rsp_payload_len) = ops::Init::create(flags);
+		let rsp = send_command(cmd, rsp_payload_len).unwrap();
 		trace!("fuse init answer: {rsp:?}");
 
+		if let Some(window) = dax_window {
+			let init_out = &rsp.headers.op_header;
+			// Mappings have to be aligned to the page size of the host.
+			let aligned = init_out.flags & FUSE_MAP_ALIGNMENT == 0
+				|| 1usize
+					.checked_shl(init_out.map_alignment.into())
+					.is_some_and(|alignment| alignment <= DAX_CHUNK_SIZE);
+			if aligned && window.len >= DAX_CHUNK_SIZE {
+				info!(
+					"Use DAX window with {} chunks of {DAX_CHUNK_SIZE:#x} bytes",
+					window.len / DAX_CHUNK_SIZE
+				);
+				DAX_WINDOW
+					.set(InterruptTicketMutex::new(DaxWindow::new(window)))
+					.unwrap();
+			} else {
+				warn!("DAX window of virtio-fs cannot be used");
+			}
+		}
+
 		let mount_point = driver.lock().get_mount_point();
 		if mount_point == "/" {
 			let fuse_nid = lookup(c"/".to_owned()).unwrap();
@@ -1385,11 +2096,7 @@ pub(crate) fn init() {
 			// Flag 0x10000 for O_DIRECTORY might not be necessary
 			let (mut cmd, rsp_payload_len) = ops::Open::create(fuse_nid, 0x10000);
 			cmd.headers.in_header.opcode = fuse_opcode::FUSE_OPENDIR as u32;
-			let rsp = get_filesystem_driver()
-				.unwrap()
-				.lock()
-				.send_command(cmd, rsp_payload_len)
-				.unwrap();
+			let rsp = send_command(cmd, rsp_payload_len).unwrap();
 			let fuse_fh = rsp.headers.op_header.fh;
 
 			// Linux seems to allocate a single page to store the dirfile
@@ -1399,11 +2106,7 @@ pub(crate) fn init() {
 			// read content of the directory
 			let (mut cmd, rsp_payload_len) = ops::Read::create(fuse_nid, fuse_fh, len, 0);
 			cmd.headers.in_header.opcode = fuse_opcode::FUSE_READDIR as u32;
-			let rsp = get_filesystem_driver()
-				.unwrap()
-				.lock()
-				.send_command(cmd, rsp_payload_len)
-				.unwrap();
+			let rsp = send_command(cmd, rsp_payload_len).unwrap();
 
 			let len: usize = if rsp.headers.out_header.len as usize
 				- mem::size_of::<fuse_out_header>()
@@ -1447,11 +2150,7 @@ pub(crate) fn init() {
 			}
 
 			let (cmd, rsp_payload_len) = ops::Release::create(fuse_nid, fuse_fh);
-			get_filesystem_driver()
-				.unwrap()
-				.lock()
-				.send_command(cmd, rsp_payload_len)
-				.unwrap();
+			send_command(cmd, rsp_payload_len).unwrap();
 
 			// remove predefined directories
 			entries.retain(|x| x != ".");
@@ -1465,11 +2164,7 @@ pub(crate) fn init() {
 			for i in entries {
 				let i_cstr = CString::new(i.clone()).unwrap();
 				let (cmd, rsp_payload_len) = ops::Lookup::create(i_cstr);
-				let rsp = get_filesystem_driver()
-					.unwrap()
-					.lock()
-					.send_command(cmd, rsp_payload_len)
-					.unwrap();
+				let rsp = send_command(cmd, rsp_payload_len).unwrap();
 
 				let attr = FileAttr::from(rsp.headers.op_header.attr);
 				if attr.st_mode.contains(AccessPermission::S_IFDIR) {
@@ -1502,3 +2197,13 @@ pub(crate) fn init() {
 		}
 	}
 }
+
+/// Opens the root directory of the virtio-fs device with the tag `tag`.
+pub(crate) fn open(tag: &str) -> io::Result<Box<dyn VfsNode + Send + Sync>> {
+	let driver = get_filesystem_driver().ok_or(Errno::Nodev)?;
+	if driver.lock().get_mount_point().trim_start_matches('/') != tag.trim_start_matches('/') {
+		return Err(Errno::Noent);
+	}
+
+	Ok(Box::new(FuseDirectory::new(None)))
+}
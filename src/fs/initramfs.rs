@@ -0,0 +1,312 @@
+//! Unpacking of the initramfs into the root file system.
+//!
+//! The loader passes the initramfs as uncompressed cpio archive in the
+//! "newc" format, as produced by `find . | cpio -o -H newc`. Directories,
+//! regular files, hard links, and symbolic links are created in the in-memory
+//! file system before `main` runs. Other file types are skipped. Owners and
+//! timestamps are not preserved.
+//!
+//! The memory of the archive is returned to the free memory afterwards.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use core::str;
+
+use crate::errno::Errno;
+use crate::executor::block_on;
+use crate::fd::{AccessPermission, OpenOption};
+use crate::fs::Filesystem;
+use crate::io;
+use crate::mm::physicalmem;
+
+/// Magic numbers of the newc format without and with checksum
+const MAGIC: &[&[u8]] = &[b"070701", b"070702"];
+
+/// Length of the header, which consists of the magic number and 13 fields
+const HEADER_LEN: usize = 110;
+
+/// Name of the entry, which terminates the archive
+const TRAILER: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170_000;
+const S_IFDIR: u32 = 0o040_000;
+const S_IFREG: u32 = 0o100_000;
+const S_IFLNK: u32 = 0o120_000;
+
+struct Entry<'a> {
+	ino: u32,
+	mode: u32,
+	nlink: u32,
+	name: &'a str,
+	data: &'a [u8],
+}
+
+/// Iterates over the entries of a cpio archive.
+struct Archive<'a> {
+	data: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> Archive<'a> {
+	fn field(header: &[u8], index: usize) -> io::Result<u32> {
+		let start = 6 + index * 8;
+		let field = str::from_utf8(&header[start..start + 8]).map_err(|_| Errno::Inval)?;
+		u32::from_str_radix(field, 16).map_err(|_| Errno::Inval)
+	}
+
+	fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+		let end = self.pos.checked_add(len).ok_or(Errno::Inval)?;
+		let bytes = self.data.get(self.pos..end).ok_or(Errno::Inval)?;
+		// entries and their content are padded to four bytes
+		self.pos = end.next_multiple_of(4);
+		Ok(bytes)
+	}
+
+	fn next_entry(&mut self) -> io::Result<Option<Entry<'a>>> {
+		let header = self
+			.data
+			.get(self.pos..self.pos + HEADER_LEN)
+			.ok_or(Errno::Inval)?;
+		if !MAGIC.contains(&&header[..6]) {
+			error!("initramfs is not an uncompressed cpio archive in the newc format");
+			return Err(Errno::Inval);
+		}
+
+		let ino = Self::field(header, 0)?;
+		let mode = Self::field(header, 1)?;
+		let nlink = Self::field(header, 4)?;
+		let size = Self::field(header, 6)?;
+		let name_len = Self::field(header, 11)?;
+
+		// the name is padded together with the header
+		self.pos += HEADER_LEN;
+		let name = self.take(name_len.try_into().unwrap())?;
+		let name = name.strip_suffix(b"\0").ok_or(Errno::Inval)?;
+		let name = str::from_utf8(name).map_err(|_| Errno::Inval)?;
+		if name == TRAILER {
+			return Ok(None);
+		}
+		let data = self.take(size.try_into().unwrap())?;
+
+		Ok(Some(Entry {
+			ino,
+			mode,
+			nlink,
+			name,
+			data,
+		}))
+	}
+}
+
+/// Turns the name of an entry into an absolute path.
+fn path(name: &str) -> io::Result<Option<String>> {
+	let mut path = String::new();
+	for component in name.split('/') {
+		match component {
+			"" | "." => {}
+			".." => return Err(Errno::Inval),
+			component => {
+				path.push('/');
+				path.push_str(component);
+			}
+		}
+	}
+
+	Ok((!path.is_empty()).then_some(path))
+}
+
+fn write_file(
+	fs: &Filesystem,
+	path: &str,
+	opt: OpenOption,
+	mode: AccessPermission,
+	data: &[u8],
+) -> io::Result<()> {
+	let file = fs.open(path, opt | OpenOption::O_WRONLY, mode)?;
+	block_on(
+		async {
+			let file = file.read().await;
+			let mut data = data;
+			while !data.is_empty() {
+				let len = file.write(data).await?;
+				data = &data[len..];
+			}
+			Ok(())
+		},
+		None,
+	)
+}
+
+fn unpack(fs: &Filesystem, data: &[u8]) -> io::Result<usize> {
+	let mut archive = Archive { data, pos: 0 };
+	// paths of the files with several links by their inode number
+	let mut links = BTreeMap::new();
+	let mut count = 0;
+
+	while let Some(entry) = archive.next_entry()? {
+		let Some(path) = path(entry.name)? else {
+			continue;
+		};
+		let mode = AccessPermission::from_bits_truncate(entry.mode & 0o7777);
+
+		match entry.mode & S_IFMT {
+			S_IFDIR => match fs.mkdir(&path, mode) {
+				Ok(()) | Err(Errno::Exist) => {}
+				Err(err) => return Err(err),
+			},
+			S_IFREG => {
+				// In the newc format, only the last link carries the content.
+				if let Some(original) = links.get(&entry.ino) {
+					fs.link(original, &path)?;
+					if !entry.data.is_empty() {
+						write_file(fs, original, OpenOption::O_TRUNC, mode, entry.data)?;
+					}
+				} else {
+					write_file(
+						fs,
+						&path,
+						OpenOption::O_CREAT | OpenOption::O_EXCL,
+						mode,
+						entry.data,
+					)?;
+					if entry.nlink > 1 {
+						links.insert(entry.ino, path.clone());
+					}
+				}
+			}
+			S_IFLNK => {
+				let target = str::from_utf8(entry.data).map_err(|_| Errno::Inval)?;
+				fs.symlink(target, &path)?;
+			}
+			_ => {
+				warn!("Skipping {path} of the initramfs, its file type is not supported");
+				continue;
+			}
+		}
+		count += 1;
+	}
+
+	Ok(count)
+}
+
+/// Unpacks the initramfs, if the loader has passed one.
+pub(crate) fn init(fs: &Filesystem) {
+	let Some(data) = physicalmem::initrd() else {
+		return;
+	};
+
+	info!("Unpacking initramfs of {} bytes", data.len());
+	match unpack(fs, data) {
+		Ok(count) => info!("Unpacked {count} entries of the initramfs"),
+		Err(err) => error!("Unable to unpack the initramfs: {err:?}"),
+	}
+
+	unsafe {
+		physicalmem::release_initrd();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::vec::Vec;
+
+	use super::*;
+
+	/// Appends an entry in the newc format to `archive`.
+	fn push_entry(archive: &mut Vec<u8>, ino: u32, mode: u32, nlink: u32, name: &str, data: &[u8]) {
+		let name_len = u32::try_from(name.len() + 1).unwrap();
+		let size = u32::try_from(data.len()).unwrap();
+		archive.extend_from_slice(b"070701");
+		for field in [ino, mode, 0, 0, nlink, 0, size, 0, 0, 0, 0, name_len, 0] {
+			archive.extend_from_slice(format!("{field:08X}").as_bytes());
+		}
+		archive.extend_from_slice(name.as_bytes());
+		archive.push(0);
+		archive.resize(archive.len().next_multiple_of(4), 0);
+		archive.extend_from_slice(data);
+		archive.resize(archive.len().next_multiple_of(4), 0);
+	}
+
+	fn push_trailer(archive: &mut Vec<u8>) {
+		push_entry(archive, 0, 0, 1, TRAILER, &[]);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_cpio_header() {
+		let mut data = Vec::new();
+		push_entry(&mut data, 7, S_IFREG | 0o644, 2, "dir/file", b"hello");
+		push_trailer(&mut data);
+
+		let mut archive = Archive {
+			data: &data,
+			pos: 0,
+		};
+		let entry = archive.next_entry().unwrap().unwrap();
+		assert_eq!(entry.ino, 7);
+		assert_eq!(entry.mode, S_IFREG | 0o644);
+		assert_eq!(entry.nlink, 2);
+		assert_eq!(entry.name, "dir/file");
+		assert_eq!(entry.data, b"hello");
+		// The name ends at 110 + 9 bytes and is padded to 120 bytes, the content is padded to 4 bytes.
+		assert_eq!(archive.pos, 128);
+		assert!(archive.next_entry().unwrap().is_none());
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_cpio_corrupt_header() {
+		let mut data = Vec::new();
+		push_entry(&mut data, 1, S_IFREG | 0o644, 1, "file", b"content");
+		push_trailer(&mut data);
+
+		let mut magic = data.clone();
+		magic[..6].copy_from_slice(b"070707");
+		// A field, which is not hexadecimal
+		let mut field = data.clone();
+		field[6] = b'g';
+		// The name is not terminated.
+		let mut name = data.clone();
+		name[HEADER_LEN + 4] = b'x';
+		let truncated = &data[..HEADER_LEN + 8 + 4];
+
+		for data in [&magic[..], &field[..], &name[..], truncated, &data[..0]] {
+			let mut archive = Archive { data, pos: 0 };
+			assert_eq!(archive.next_entry().err(), Some(Errno::Inval));
+		}
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_cpio_path() {
+		assert_eq!(path("./dir//file/").unwrap().as_deref(), Some("/dir/file"));
+		assert_eq!(path(".").unwrap(), None);
+		assert_eq!(path("dir/../../etc"), Err(Errno::Inval));
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_cpio_unpack() {
+		let mut data = Vec::new();
+		push_entry(&mut data, 1, S_IFDIR | 0o755, 2, ".", &[]);
+		push_entry(&mut data, 2, S_IFDIR | 0o755, 2, "dir", &[]);
+		push_entry(&mut data, 3, S_IFREG | 0o644, 1, "dir/file", b"hello");
+		push_entry(&mut data, 4, S_IFLNK | 0o777, 1, "link", b"dir/file");
+		// Only the last one of the hard links carries the content.
+		push_entry(&mut data, 5, S_IFREG | 0o644, 2, "a", &[]);
+		push_entry(&mut data, 5, S_IFREG | 0o644, 2, "b", b"linked");
+		push_entry(&mut data, 6, 0o020_644, 1, "dev", &[]);
+		push_trailer(&mut data);
+
+		let fs = Filesystem::new();
+		assert_eq!(unpack(&fs, &data), Ok(5));
+		assert_eq!(fs.lstat("/dir/file").unwrap().st_size, 5);
+		assert_eq!(fs.readlink("/link").unwrap(), "dir/file");
+		assert_eq!(fs.lstat("/a").unwrap().st_size, 6);
+		assert_eq!(
+			fs.lstat("/b").unwrap().st_ino,
+			fs.lstat("/a").unwrap().st_ino
+		);
+		assert_eq!(fs.lstat("/dev").err(), Some(Errno::Noent));
+	}
+}
@@ -0,0 +1,221 @@
+//! Advisory file locks of `fcntl` and `flock`.
+//!
+//! All tasks belong to the same process, so locks associated with the process
+//! would never conflict. Instead, locks are owned by the open file description,
+//! which has been used to acquire them, like the open file description locks of
+//! Linux (`F_OFD_SETLK`). Therefore, duplicated file descriptors share their locks.
+//! Locks of `fcntl` and `flock` are independent of each other. Deadlocks are
+//! not detected.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::errno::Errno;
+use crate::executor::block_on;
+use crate::fd::ObjectInterface;
+use crate::io;
+use crate::synch::futex::{self, Flags};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum LockType {
+	/// Shared lock
+	Read,
+	/// Exclusive lock
+	Write,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum LockKind {
+	/// Byte-range lock of `fcntl`
+	Record,
+	/// Whole-file lock of `flock`
+	File,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Lock {
+	pub kind: LockKind,
+	pub ty: LockType,
+	/// First byte of the locked range
+	pub start: u64,
+	/// End of the locked range (exclusive), `u64::MAX` locks up to the end of the file
+	pub end: u64,
+	owner: usize,
+}
+
+impl Lock {
+	fn overlaps(&self, kind: LockKind, start: u64, end: u64) -> bool {
+		self.kind == kind && self.start < end && start < self.end
+	}
+
+	fn conflicts(&self, other: &Lock) -> bool {
+		self.owner != other.owner
+			&& (self.ty == LockType::Write || other.ty == LockType::Write)
+			&& self.overlaps(other.kind, other.start, other.end)
+	}
+}
+
+/// Identifies a file by its device and inode number.
+type Inode = (u64, u64);
+
+/// Locks of all files
+static LOCKS: InterruptTicketMutex<BTreeMap<Inode, Vec<Lock>>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+
+/// Futex, which is incremented whenever locks are released, so that waiters
+/// check for conflicts again.
+static LOCK_EVENTS: AtomicU32 = AtomicU32::new(0);
+
+type Object = Arc<async_lock::RwLock<dyn ObjectInterface>>;
+
+fn owner(object: &Object) -> usize {
+	Arc::as_ptr(object).cast::<()>() as usize
+}
+
+fn inode(object: &Object) -> io::Result<Inode> {
+	let attr = block_on(async { object.read().await.fstat().await }, None)?;
+	if attr.st_ino == 0 {
+		// The file system does not provide inode numbers. Thus, the file cannot
+		// be identified and the locks only conflict within the same open file description.
+		Ok((u64::MAX, owner(object) as u64))
+	} else {
+		Ok((attr.st_dev, attr.st_ino))
+	}
+}
+
+fn notify_waiters() {
+	LOCK_EVENTS.fetch_add(1, Ordering::SeqCst);
+	futex::futex_wake(&LOCK_EVENTS, i32::MAX);
+}
+
+/// Removes the locks of `owner` in the range from `start` to `end` and returns
+/// whether a lock has been removed.
+///
+/// Locks, which are only partially covered by the range, are kept for the remaining part.
+fn remove(locks: &mut Vec<Lock>, kind: LockKind, owner: usize, start: u64, end: u64) -> bool {
+	let len = locks.len();
+	let mut removed = false;
+	for i in (0..len).rev() {
+		let lock = locks[i];
+		if lock.owner != owner || !lock.overlaps(kind, start, end) {
+			continue;
+		}
+
+		locks.swap_remove(i);
+		removed = true;
+		if lock.start < start {
+			locks.push(Lock { end: start, ..lock });
+		}
+		if end < lock.end {
+			locks.push(Lock { start: end, ..lock });
+		}
+	}
+	removed
+}
+
+/// Acquires a lock of type `ty` in the range from `start` to `end` of the file
+/// or releases the locks in this range, if `ty` is `None`.
+///
+/// Existing locks of the open file description in the range are replaced.
+/// If the lock conflicts with a lock of another open file description, the
+/// function waits until the lock has been released, if `wait` is set, and
+/// fails with [`Errno::Again`] otherwise.
+pub(crate) fn lock(
+	object: &Object,
+	kind: LockKind,
+	ty: Option<LockType>,
+	start: u64,
+	end: u64,
+	wait: bool,
+) -> io::Result<()> {
+	let inode = inode(object)?;
+	let owner = owner(object);
+
+	loop {
+		let events = LOCK_EVENTS.load(Ordering::SeqCst);
+
+		{
+			let mut guard = LOCKS.lock();
+			let locks = guard.entry(inode).or_default();
+			let Some(ty) = ty else {
+				let removed = remove(locks, kind, owner, start, end);
+				if locks.is_empty() {
+					guard.remove(&inode);
+				}
+				drop(guard);
+				if removed {
+					notify_waiters();
+				}
+				return Ok(());
+			};
+
+			let new = Lock {
+				kind,
+				ty,
+				start,
+				end,
+				owner,
+			};
+			if !locks.iter().any(|lock| lock.conflicts(&new)) {
+				// Replacing a write lock by a read lock may unblock waiters.
+				let removed = remove(locks, kind, owner, start, end);
+				locks.push(new);
+				drop(guard);
+				if removed {
+					notify_waiters();
+				}
+				return Ok(());
+			}
+		}
+
+		if !wait {
+			return Err(Errno::Again);
+		}
+		futex::futex_wait(&LOCK_EVENTS, events, None, Flags::empty());
+	}
+}
+
+/// Returns a lock of another open file description, which prevents acquiring a
+/// lock of type `ty` in the range from `start` to `end`.
+pub(crate) fn test(
+	object: &Object,
+	kind: LockKind,
+	ty: LockType,
+	start: u64,
+	end: u64,
+) -> io::Result<Option<Lock>> {
+	let new = Lock {
+		kind,
+		ty,
+		start,
+		end,
+		owner: owner(object),
+	};
+
+	Ok(LOCKS
+		.lock()
+		.get(&inode(object)?)
+		.and_then(|locks| locks.iter().find(|lock| lock.conflicts(&new)).copied()))
+}
+
+/// Releases all locks of the open file description.
+///
+/// Has to be called, when the last file descriptor of the open file description is closed.
+pub(crate) fn release(object: &Object) {
+	let owner = owner(object);
+	let mut removed = false;
+	LOCKS.lock().retain(|_, locks| {
+		let len = locks.len();
+		locks.retain(|lock| lock.owner != owner);
+		removed |= locks.len() != len;
+		!locks.is_empty()
+	});
+
+	if removed {
+		notify_waiters();
+	}
+}
@@ -9,35 +9,87 @@
 
 #![allow(dead_code)]
 
+use alloc::borrow::Cow;
 use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
-use core::marker::PhantomData;
-use core::mem::{MaybeUninit, offset_of};
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU64, Ordering};
 
-use align_address::Align;
 use async_lock::{Mutex, RwLock};
 use async_trait::async_trait;
+use hermit_sync::InterruptSpinMutex;
 
+use crate::arch::mm::paging::{BasePageSize, PageSize};
 use crate::errno::Errno;
 use crate::executor::block_on;
-use crate::fd::{AccessPermission, ObjectInterface, OpenOption, PollEvent};
-use crate::fs::{DirectoryEntry, FileAttr, FileType, NodeKind, SeekWhence, VfsNode};
-use crate::syscalls::Dirent64;
+use crate::fd::{AccessPermission, FallocateMode, ObjectInterface, OpenOption, PollEvent};
+use crate::fs::{
+	DirectoryEntry, FileAttr, FileTimes, FileType, NodeKind, RenameFlags, SeekWhence, StatFs,
+	TMPFS_MAGIC, VfsNode, seek_dir, write_dirents,
+};
+use crate::mm::physicalmem;
 use crate::time::timespec;
 use crate::{arch, io};
 
+/// Interval in seconds, after which a read updates the access time in any case
+const RELATIME_INTERVAL: i64 = 24 * 60 * 60;
+
+fn current_time() -> timespec {
+	timespec::from_usec(arch::kernel::systemtime::now_micros() as i64)
+}
+
+/// Returns whether a read at `now` updates the access time.
+///
+/// Like Linux' `relatime`, the access time is only updated, if it is not
+/// newer than the last modification or status change, or if it is older than
+/// [`RELATIME_INTERVAL`]. This avoids locking the file exclusively for most reads.
+fn atime_outdated(attr: &FileAttr, now: timespec) -> bool {
+	let key = |time: timespec| (time.tv_sec, time.tv_nsec);
+	key(attr.st_atim) <= key(attr.st_mtim)
+		|| key(attr.st_atim) <= key(attr.st_ctim)
+		|| now.tv_sec - attr.st_atim.tv_sec >= RELATIME_INTERVAL
+}
+
+/// Returns a new inode number, which identifies a node of the in-memory file system.
+fn next_inode() -> u64 {
+	static NEXT_INODE: AtomicU64 = AtomicU64::new(1);
+	NEXT_INODE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Returns the capacity of the in-memory file system.
+///
+/// The files are stored in the physical memory of the kernel, so the capacity
+/// is the size of the physical memory. The number of inodes is not limited.
+pub(crate) fn statfs() -> StatFs {
+	let page_size = BasePageSize::SIZE;
+	let free = u64::try_from(physicalmem::free_memory_size()).unwrap() / page_size;
+
+	StatFs {
+		f_type: TMPFS_MAGIC,
+		f_bsize: page_size,
+		f_blocks: u64::try_from(physicalmem::total_memory_size()).unwrap() / page_size,
+		f_bfree: free,
+		f_bavail: free,
+		f_namelen: 255,
+		..Default::default()
+	}
+}
+
 #[derive(Debug)]
 pub(crate) struct RomFileInner {
-	pub data: &'static [u8],
+	pub data: Cow<'static, [u8]>,
 	pub attr: FileAttr,
 }
 
 impl RomFileInner {
-	pub fn new(data: &'static [u8], attr: FileAttr) -> Self {
-		Self { data, attr }
+	pub fn new(data: impl Into<Cow<'static, [u8]>>, attr: FileAttr) -> Self {
+		Self {
+			data: data.into(),
+			attr,
+		}
 	}
 }
 
@@ -65,14 +117,13 @@ impl ObjectInterface for RomFileInterface {
 	}
 
 	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
-		{
-			let microseconds = arch::kernel::systemtime::now_micros();
-			let t = timespec::from_usec(microseconds as i64);
-			let mut guard = self.inner.write().await;
-			guard.attr.st_atim = t;
+		let now = current_time();
+		if atime_outdated(&self.inner.read().await.attr, now) {
+			self.inner.write().await.attr.st_atim = now;
 		}
 
-		let vec = self.inner.read().await.data;
+		let guard = self.inner.read().await;
+		let vec = &guard.data;
 		let mut pos_guard = self.pos.lock().await;
 		let pos = *pos_guard;
 
@@ -118,10 +169,38 @@ impl ObjectInterface for RomFileInterface {
 		}
 	}
 
+	async fn pread(&self, buf: &mut [u8], offset: usize) -> io::Result<usize> {
+		let guard = self.inner.read().await;
+		let data = guard.data.get(offset..).unwrap_or_default();
+		let len = data.len().min(buf.len());
+		buf[..len].copy_from_slice(&data[..len]);
+		Ok(len)
+	}
+
+	async fn send_to(
+		&self,
+		sink: &dyn ObjectInterface,
+		offset: usize,
+		count: usize,
+	) -> io::Result<usize> {
+		let guard = self.inner.read().await;
+		let data = guard.data.get(offset..).unwrap_or_default();
+		sink.write(&data[..data.len().min(count)]).await
+	}
+
 	async fn fstat(&self) -> io::Result<FileAttr> {
 		let guard = self.inner.read().await;
 		Ok(guard.attr)
 	}
+
+	async fn fsync(&self) -> io::Result<()> {
+		Ok(())
+	}
+
+	async fn utimens(&self, times: FileTimes) -> io::Result<()> {
+		times.apply(&mut self.inner.write().await.attr, current_time());
+		Ok(())
+	}
 }
 
 impl RomFileInterface {
@@ -176,11 +255,9 @@ impl ObjectInterface for RamFileInterface {
 	}
 
 	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
-		{
-			let microseconds = arch::kernel::systemtime::now_micros();
-			let t = timespec::from_usec(microseconds as i64);
-			let mut guard = self.inner.write().await;
-			guard.attr.st_atim = t;
+		let now = current_time();
+		if atime_outdated(&self.inner.read().await.attr, now) {
+			self.inner.write().await.attr.st_atim = now;
 		}
 
 		let guard = self.inner.read().await;
@@ -204,8 +281,7 @@ impl ObjectInterface for RamFileInterface {
 	}
 
 	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
-		let microseconds = arch::kernel::systemtime::now_micros();
-		let t = timespec::from_usec(microseconds as i64);
+		let t = current_time();
 		let mut guard = self.inner.write().await;
 		let mut pos_guard = self.pos.lock().await;
 		let pos = *pos_guard;
@@ -215,7 +291,6 @@ impl ObjectInterface for RamFileInterface {
 			guard.attr.st_size = guard.data.len().try_into().unwrap();
 		}
 
-		guard.attr.st_atim = t;
 		guard.attr.st_mtim = t;
 		guard.attr.st_ctim = t;
 
@@ -252,21 +327,93 @@ impl ObjectInterface for RamFileInterface {
 		Ok(new_pos)
 	}
 
+	async fn pread(&self, buf: &mut [u8], offset: usize) -> io::Result<usize> {
+		let guard = self.inner.read().await;
+		let data = guard.data.get(offset..).unwrap_or_default();
+		let len = data.len().min(buf.len());
+		buf[..len].copy_from_slice(&data[..len]);
+		Ok(len)
+	}
+
+	async fn send_to(
+		&self,
+		sink: &dyn ObjectInterface,
+		offset: usize,
+		count: usize,
+	) -> io::Result<usize> {
+		let guard = self.inner.read().await;
+		let data = guard.data.get(offset..).unwrap_or_default();
+		sink.write(&data[..data.len().min(count)]).await
+	}
+
+	async fn pwrite(&self, buf: &[u8], offset: usize) -> io::Result<usize> {
+		let t = current_time();
+		let mut guard = self.inner.write().await;
+
+		if offset + buf.len() > guard.data.len() {
+			guard.data.resize(offset + buf.len(), 0);
+			guard.attr.st_size = guard.data.len().try_into().unwrap();
+		}
+
+		guard.attr.st_mtim = t;
+		guard.attr.st_ctim = t;
+
+		guard.data[offset..offset + buf.len()].copy_from_slice(buf);
+
+		Ok(buf.len())
+	}
+
 	async fn fstat(&self) -> io::Result<FileAttr> {
 		let guard = self.inner.read().await;
 		Ok(guard.attr)
 	}
 
+	async fn fsync(&self) -> io::Result<()> {
+		Ok(())
+	}
+
 	async fn truncate(&self, size: usize) -> io::Result<()> {
+		let t = current_time();
 		let mut guard = self.inner.write().await;
 		guard.data.resize(size, 0);
 		guard.attr.st_size = size as i64;
+		guard.attr.st_mtim = t;
+		guard.attr.st_ctim = t;
+		Ok(())
+	}
+
+	async fn allocate(&self, mode: FallocateMode, offset: usize, len: usize) -> io::Result<()> {
+		let end = offset.checked_add(len).ok_or(Errno::Fbig)?;
+		let mut guard = self.inner.write().await;
+		let size = guard.data.len();
+
+		if mode.contains(FallocateMode::FALLOC_FL_PUNCH_HOLE) {
+			guard.data[offset.min(size)..end.min(size)].fill(0);
+		} else if end <= size {
+			return Ok(());
+		} else if mode.contains(FallocateMode::FALLOC_FL_KEEP_SIZE) {
+			guard.data.reserve(end - size);
+			return Ok(());
+		} else {
+			guard.data.resize(end, 0);
+			guard.attr.st_size = end.try_into().unwrap();
+		}
+
+		let t = current_time();
+		guard.attr.st_mtim = t;
+		guard.attr.st_ctim = t;
 		Ok(())
 	}
 
 	async fn chmod(&self, access_permission: AccessPermission) -> io::Result<()> {
 		let mut guard = self.inner.write().await;
 		guard.attr.st_mode = access_permission;
+		guard.attr.st_ctim = current_time();
+		Ok(())
+	}
+
+	async fn utimens(&self, times: FileTimes) -> io::Result<()> {
+		times.apply(&mut self.inner.write().await.attr, current_time());
 		Ok(())
 	}
 }
@@ -284,7 +431,7 @@ impl RamFileInterface {
 	}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct RomFile {
 	data: Arc<RwLock<RomFileInner>>,
 }
@@ -294,12 +441,26 @@ impl VfsNode for RomFile {
 		NodeKind::File
 	}
 
+	fn set_times(&self, times: FileTimes) -> io::Result<()> {
+		block_on(
+			async {
+				times.apply(&mut self.data.write().await.attr, current_time());
+				Ok(())
+			},
+			None,
+		)
+	}
+
 	fn get_object(&self) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
 		Ok(Arc::new(async_lock::RwLock::new(RomFileInterface::new(
 			self.data.clone(),
 		))))
 	}
 
+	fn hard_link(&self) -> io::Result<Box<dyn VfsNode + core::marker::Send + core::marker::Sync>> {
+		Ok(Box::new(self.clone()))
+	}
+
 	fn get_file_attributes(&self) -> io::Result<FileAttr> {
 		block_on(async { Ok(self.data.read().await.attr) }, None)
 	}
@@ -323,9 +484,9 @@ impl VfsNode for RomFile {
 
 impl RomFile {
 	pub fn new(data: &'static [u8], mode: AccessPermission) -> Self {
-		let microseconds = arch::kernel::systemtime::now_micros();
-		let t = timespec::from_usec(microseconds as i64);
+		let t = current_time();
 		let attr = FileAttr {
+			st_ino: next_inode(),
 			st_size: data.len().try_into().unwrap(),
 			st_mode: mode | AccessPermission::S_IFREG,
 			st_atim: t,
@@ -340,6 +501,67 @@ impl RomFile {
 	}
 }
 
+/// A read-only file, whose content is generated whenever the file is opened
+#[derive(Debug)]
+pub(crate) struct GeneratedFile {
+	generate: fn() -> String,
+	attr: FileAttr,
+}
+
+impl VfsNode for GeneratedFile {
+	fn get_kind(&self) -> NodeKind {
+		NodeKind::File
+	}
+
+	fn get_object(&self) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		let data = (self.generate)().into_bytes();
+		let attr = FileAttr {
+			st_size: data.len().try_into().unwrap(),
+			..self.attr
+		};
+
+		Ok(Arc::new(async_lock::RwLock::new(RomFileInterface::new(
+			Arc::new(RwLock::new(RomFileInner::new(data, attr))),
+		))))
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		Ok(self.attr)
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			self.get_file_attributes()
+		} else {
+			Err(Errno::Badf)
+		}
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			self.get_file_attributes()
+		} else {
+			Err(Errno::Badf)
+		}
+	}
+}
+
+impl GeneratedFile {
+	pub fn new(generate: fn() -> String, mode: AccessPermission) -> Self {
+		let t = current_time();
+		let attr = FileAttr {
+			st_ino: next_inode(),
+			st_mode: mode | AccessPermission::S_IFREG,
+			st_atim: t,
+			st_mtim: t,
+			st_ctim: t,
+			..Default::default()
+		};
+
+		Self { generate, attr }
+	}
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct RamFile {
 	data: Arc<RwLock<RamFileInner>>,
@@ -350,12 +572,26 @@ impl VfsNode for RamFile {
 		NodeKind::File
 	}
 
+	fn set_times(&self, times: FileTimes) -> io::Result<()> {
+		block_on(
+			async {
+				times.apply(&mut self.data.write().await.attr, current_time());
+				Ok(())
+			},
+			None,
+		)
+	}
+
 	fn get_object(&self) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
 		Ok(Arc::new(async_lock::RwLock::new(RamFileInterface::new(
 			self.data.clone(),
 		))))
 	}
 
+	fn hard_link(&self) -> io::Result<Box<dyn VfsNode + core::marker::Send + core::marker::Sync>> {
+		Ok(Box::new(self.clone()))
+	}
+
 	fn get_file_attributes(&self) -> io::Result<FileAttr> {
 		block_on(async { Ok(self.data.read().await.attr) }, None)
 	}
@@ -379,9 +615,9 @@ impl VfsNode for RamFile {
 
 impl RamFile {
 	pub fn new(mode: AccessPermission) -> Self {
-		let microseconds = arch::kernel::systemtime::now_micros();
-		let t = timespec::from_usec(microseconds as i64);
+		let t = current_time();
 		let attr = FileAttr {
+			st_ino: next_inode(),
 			st_mode: mode | AccessPermission::S_IFREG,
 			st_atim: t,
 			st_mtim: t,
@@ -395,6 +631,73 @@ impl RamFile {
 	}
 }
 
+/// A symbolic link
+#[derive(Debug, Clone)]
+pub(crate) struct MemSymlink {
+	target: String,
+	/// Attributes, which are shared with the hard links
+	attr: Arc<InterruptSpinMutex<FileAttr>>,
+}
+
+impl VfsNode for MemSymlink {
+	fn get_kind(&self) -> NodeKind {
+		NodeKind::Symlink
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		Ok(*self.attr.lock())
+	}
+
+	fn set_times(&self, times: FileTimes) -> io::Result<()> {
+		times.apply(&mut self.attr.lock(), current_time());
+		Ok(())
+	}
+
+	fn readlink(&self) -> io::Result<String> {
+		Ok(self.target.clone())
+	}
+
+	fn hard_link(&self) -> io::Result<Box<dyn VfsNode + core::marker::Send + core::marker::Sync>> {
+		Ok(Box::new(self.clone()))
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			self.get_file_attributes()
+		} else {
+			Err(Errno::Badf)
+		}
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		if components.is_empty() {
+			self.get_file_attributes()
+		} else {
+			Err(Errno::Badf)
+		}
+	}
+}
+
+impl MemSymlink {
+	pub fn new(target: &str) -> Self {
+		let t = current_time();
+		let attr = FileAttr {
+			st_ino: next_inode(),
+			st_size: target.len().try_into().unwrap(),
+			st_mode: AccessPermission::from_bits(0o777).unwrap() | AccessPermission::S_IFLNK,
+			st_atim: t,
+			st_mtim: t,
+			st_ctim: t,
+			..Default::default()
+		};
+
+		Self {
+			target: String::from(target),
+			attr: Arc::new(InterruptSpinMutex::new(attr)),
+		}
+	}
+}
+
 #[derive(Debug)]
 pub struct MemDirectoryInterface {
 	/// Directory entries
@@ -419,88 +722,126 @@ impl MemDirectoryInterface {
 #[async_trait]
 impl ObjectInterface for MemDirectoryInterface {
 	async fn getdents(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
-		let mut buf_offset: usize = 0;
-		let mut ret = 0;
 		let mut read_idx = self.read_idx.lock().await;
-		for name in self.inner.read().await.keys().skip(*read_idx) {
-			let namelen = name.len();
+		let entries = self.inner.read().await;
+		// Inode numbers are not reported, because looking them up would block.
+		write_dirents(
+			buf,
+			&mut read_idx,
+			entries
+				.iter()
+				.map(|(name, node)| (name.as_str(), 1, FileType::from(node.get_kind()))),
+		)
+	}
 
-			let dirent_len = offset_of!(Dirent64, d_name) + namelen + 1;
-			let next_dirent = (buf_offset + dirent_len).align_up(align_of::<Dirent64>());
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		seek_dir(&mut *self.read_idx.lock().await, offset, whence)
+	}
+}
 
-			if next_dirent > buf.len() {
-				// target buffer full -> we return the nr. of bytes written (like linux does)
-				break;
-			}
+/// Entries of a directory, indexed by their name
+pub(crate) type Entries =
+	RwLock<BTreeMap<String, Box<dyn VfsNode + core::marker::Send + core::marker::Sync>>>;
+
+/// Serializes renames between directories, which lock two directories at once
+static RENAME_LOCK: Mutex<()> = Mutex::new(());
+
+/// Checks, whether `node` may be renamed to `target`.
+fn check_rename(
+	node: &dyn VfsNode,
+	target: Option<&(dyn VfsNode + core::marker::Send + core::marker::Sync)>,
+	flags: RenameFlags,
+) -> io::Result<()> {
+	let Some(target) = target else {
+		return if flags.contains(RenameFlags::RENAME_EXCHANGE) {
+			Err(Errno::Noent)
+		} else {
+			Ok(())
+		};
+	};
 
-			*read_idx += 1;
-
-			// could be replaced with slice_as_ptr once maybe_uninit_slice is stabilized.
-			let target_dirent = buf[buf_offset].as_mut_ptr().cast::<Dirent64>();
-
-			unsafe {
-				target_dirent.write(Dirent64 {
-					d_ino: 1, // TODO: we don't have inodes in the mem filesystem. Maybe this could lead to problems
-					d_off: 0,
-					d_reclen: (dirent_len.align_up(align_of::<Dirent64>()))
-						.try_into()
-						.unwrap(),
-					d_type: FileType::Unknown, // TODO: Proper filetype
-					d_name: PhantomData {},
-				});
-				let nameptr = core::ptr::from_mut(&mut (*(target_dirent)).d_name).cast::<u8>();
-				core::ptr::copy_nonoverlapping(
-					name.as_bytes().as_ptr().cast::<u8>(),
-					nameptr,
-					namelen,
-				);
-				nameptr.add(namelen).write(0); // zero termination
-			}
+	if flags.contains(RenameFlags::RENAME_NOREPLACE) {
+		return Err(Errno::Exist);
+	}
+	if flags.contains(RenameFlags::RENAME_EXCHANGE) {
+		return Ok(());
+	}
 
-			buf_offset = next_dirent;
-			ret = buf_offset;
+	match (node.get_kind(), target.get_kind()) {
+		(NodeKind::Directory, NodeKind::Directory) => {
+			if target.traverse_readdir(&mut Vec::new())?.is_empty() {
+				Ok(())
+			} else {
+				Err(Errno::Notempty)
+			}
 		}
-		Ok(ret)
+		(NodeKind::Directory, _) => Err(Errno::Notdir),
+		(_, NodeKind::Directory) => Err(Errno::Isdir),
+		_ => Ok(()),
 	}
+}
 
-	/// lseek for a directory entry is the equivalent for seekdir on linux. But on Hermit this is
-	/// logically the same operation, so we can just use the same fn in the backend.
-	/// Any other offset than 0 is not supported. (Mostly because it doesn't make any sense, as
-	/// userspace applications have no way of knowing valid offsets)
-	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
-		if whence != SeekWhence::Set && offset != 0 {
-			error!("Invalid offset for directory lseek ({offset})");
-			return Err(Errno::Inval);
-		}
-		*self.read_idx.lock().await = offset as usize;
-		Ok(offset)
+/// Moves the entry `old_name` of `old_dir` to `new_name` of `new_dir`, which
+/// is the same directory, if it is `None`.
+fn rename_entry(
+	old_dir: &mut BTreeMap<String, Box<dyn VfsNode + core::marker::Send + core::marker::Sync>>,
+	old_name: &str,
+	mut new_dir: Option<
+		&mut BTreeMap<String, Box<dyn VfsNode + core::marker::Send + core::marker::Sync>>,
+	>,
+	new_name: &str,
+	flags: RenameFlags,
+) -> io::Result<()> {
+	let node = old_dir.get(old_name).ok_or(Errno::Noent)?;
+	let target = new_dir.as_deref().unwrap_or(&*old_dir).get(new_name);
+	check_rename(&**node, target.map(|target| &**target), flags)?;
+
+	let node = old_dir.remove(old_name).unwrap();
+	let target = match new_dir.as_deref_mut() {
+		Some(new_dir) => new_dir.insert(new_name.into(), node),
+		None => old_dir.insert(new_name.into(), node),
+	};
+	if let Some(target) = target
+		&& flags.contains(RenameFlags::RENAME_EXCHANGE)
+	{
+		old_dir.insert(old_name.into(), target);
 	}
+
+	Ok(())
 }
 
 #[derive(Debug)]
 pub(crate) struct MemDirectory {
 	inner:
 		Arc<RwLock<BTreeMap<String, Box<dyn VfsNode + core::marker::Send + core::marker::Sync>>>>,
-	attr: FileAttr,
+	attr: InterruptSpinMutex<FileAttr>,
 }
 
 impl MemDirectory {
 	pub fn new(mode: AccessPermission) -> Self {
-		let microseconds = arch::kernel::systemtime::now_micros();
-		let t = timespec::from_usec(microseconds as i64);
+		let t = current_time();
 
 		Self {
 			inner: Arc::new(RwLock::new(BTreeMap::new())),
-			attr: FileAttr {
+			attr: InterruptSpinMutex::new(FileAttr {
+				st_ino: next_inode(),
 				st_mode: mode | AccessPermission::S_IFDIR,
 				st_atim: t,
 				st_mtim: t,
 				st_ctim: t,
 				..Default::default()
-			},
+			}),
 		}
 	}
 
+	/// Updates the modification time after an entry has been added or removed.
+	fn modified(&self) {
+		let t = current_time();
+		let mut attr = self.attr.lock();
+		attr.st_mtim = t;
+		attr.st_ctim = t;
+	}
+
 	async fn async_traverse_open(
 		&self,
 		components: &mut Vec<&str>,
@@ -527,6 +868,7 @@ impl MemDirectory {
 				} else if opt.contains(OpenOption::O_CREAT) {
 					let file = Box::new(RamFile::new(mode));
 					guard.insert(node_name, file.clone());
+					self.modified();
 					return Ok(Arc::new(async_lock::RwLock::new(RamFileInterface::new(
 						file.data.clone(),
 					))));
@@ -556,7 +898,12 @@ impl VfsNode for MemDirectory {
 	}
 
 	fn get_file_attributes(&self) -> io::Result<FileAttr> {
-		Ok(self.attr)
+		Ok(*self.attr.lock())
+	}
+
+	fn set_times(&self, times: FileTimes) -> io::Result<()> {
+		times.apply(&mut self.attr.lock(), current_time());
+		Ok(())
 	}
 
 	fn traverse_mkdir(&self, components: &mut Vec<&str>, mode: AccessPermission) -> io::Result<()> {
@@ -574,6 +921,7 @@ impl VfsNode for MemDirectory {
 							.write()
 							.await
 							.insert(node_name, Box::new(MemDirectory::new(mode)));
+						self.modified();
 						return Ok(());
 					}
 				}
@@ -595,6 +943,7 @@ impl VfsNode for MemDirectory {
 
 						let obj = guard.remove(&node_name).ok_or(Errno::Noent)?;
 						if obj.get_kind() == NodeKind::Directory {
+							self.modified();
 							return Ok(());
 						} else {
 							guard.insert(node_name, obj);
@@ -621,7 +970,8 @@ impl VfsNode for MemDirectory {
 						let mut guard = self.inner.write().await;
 
 						let obj = guard.remove(&node_name).ok_or(Errno::Noent)?;
-						if obj.get_kind() == NodeKind::File {
+						if obj.get_kind() != NodeKind::Directory {
+							self.modified();
 							return Ok(());
 						} else {
 							guard.insert(node_name, obj);
@@ -677,7 +1027,7 @@ impl VfsNode for MemDirectory {
 					if let Some(directory) = self.inner.read().await.get(&node_name) {
 						directory.traverse_lstat(components)
 					} else {
-						Err(Errno::Badf)
+						Err(Errno::Noent)
 					}
 				} else {
 					Err(Errno::Nosys)
@@ -702,7 +1052,7 @@ impl VfsNode for MemDirectory {
 					if let Some(directory) = self.inner.read().await.get(&node_name) {
 						directory.traverse_stat(components)
 					} else {
-						Err(Errno::Badf)
+						Err(Errno::Noent)
 					}
 				} else {
 					Err(Errno::Nosys)
@@ -722,13 +1072,51 @@ impl VfsNode for MemDirectory {
 				if let Some(component) = components.pop() {
 					let node_name = String::from(component);
 
+					if components.is_empty() {
+						let mut guard = self.inner.write().await;
+
+						// only empty directories can be used as mount points
+						if let Some(node) = guard.get(&node_name) {
+							if node.get_kind() != NodeKind::Directory {
+								return Err(Errno::Notdir);
+							}
+							if !node.traverse_readdir(&mut Vec::new())?.is_empty() {
+								return Err(Errno::Notempty);
+							}
+						}
+
+						guard.insert(node_name, obj);
+						return Ok(());
+					}
+
 					if let Some(directory) = self.inner.read().await.get(&node_name) {
 						return directory.traverse_mount(components, obj);
 					}
+				}
+
+				Err(Errno::Badf)
+			},
+			None,
+		)
+	}
+
+	fn traverse_umount(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		block_on(
+			async {
+				if let Some(component) = components.pop() {
+					let node_name = String::from(component);
 
 					if components.is_empty() {
-						self.inner.write().await.insert(node_name, obj);
+						let mut guard = self.inner.write().await;
+
+						// the mount point remains as empty directory
+						let node = guard.get_mut(&node_name).ok_or(Errno::Noent)?;
+						*node = Box::new(MemDirectory::new(
+							AccessPermission::from_bits(0o777).unwrap(),
+						));
 						return Ok(());
+					} else if let Some(directory) = self.inner.read().await.get(&node_name) {
+						return directory.traverse_umount(components);
 					}
 				}
 
@@ -738,6 +1126,224 @@ impl VfsNode for MemDirectory {
 		)
 	}
 
+	fn traverse_symlink(&self, components: &mut Vec<&str>, target: &str) -> io::Result<()> {
+		self.traverse_link(components, Box::new(MemSymlink::new(target)))
+	}
+
+	fn traverse_readlink(&self, components: &mut Vec<&str>) -> io::Result<String> {
+		block_on(
+			async {
+				if let Some(component) = components.pop() {
+					let node_name = String::from(component);
+
+					if let Some(node) = self.inner.read().await.get(&node_name) {
+						if components.is_empty() {
+							return node.readlink();
+						}
+
+						return node.traverse_readlink(components);
+					}
+				}
+
+				Err(Errno::Noent)
+			},
+			None,
+		)
+	}
+
+	fn traverse_rename(
+		&self,
+		old: &mut Vec<&str>,
+		new: &mut Vec<&str>,
+		flags: RenameFlags,
+	) -> io::Result<()> {
+		block_on(
+			async {
+				let (Some(&old_name), Some(&new_name)) = (old.first(), new.first()) else {
+					return Err(Errno::Inval);
+				};
+
+				// Both paths continue in the same entry, which may be a mounted file system.
+				if old.len() > 1 && new.len() > 1 && old.last() == new.last() {
+					let component = old.pop().unwrap();
+					new.pop();
+					if let Some(node) = self.inner.read().await.get(component) {
+						return node.traverse_rename(old, new, flags);
+					}
+					return Err(Errno::Noent);
+				}
+
+				if old.len() == 1 && new.len() == 1 {
+					let mut entries = self.inner.write().await;
+					rename_entry(&mut entries, old_name, None, new_name, flags)?;
+					self.modified();
+					return Ok(());
+				}
+
+				// This directory is the closest common parent of both paths.
+				let _guard = RENAME_LOCK.lock().await;
+				let mut old_parent = old[1..].to_vec();
+				let mut new_parent = new[1..].to_vec();
+				let old_entries = self.traverse_entries(&mut old_parent).map_err(|err| {
+					if err == Errno::Nosys {
+						Errno::Notdir
+					} else {
+						err
+					}
+				})?;
+				let new_entries = self.traverse_entries(&mut new_parent).map_err(|err| {
+					if err == Errno::Nosys {
+						Errno::Notdir
+					} else {
+						err
+					}
+				})?;
+
+				// Parents are locked before their subdirectories.
+				if old.len() <= new.len() {
+					let mut old_entries = old_entries.write().await;
+					let mut new_entries = new_entries.write().await;
+					rename_entry(
+						&mut old_entries,
+						old_name,
+						Some(&mut new_entries),
+						new_name,
+						flags,
+					)?;
+				} else {
+					let mut new_entries = new_entries.write().await;
+					let mut old_entries = old_entries.write().await;
+					rename_entry(
+						&mut old_entries,
+						old_name,
+						Some(&mut new_entries),
+						new_name,
+						flags,
+					)?;
+				}
+
+				let times = FileTimes {
+					atime: None,
+					mtime: Some(current_time()),
+				};
+				self.traverse_utimens(&mut old[1..].to_vec(), times)?;
+				self.traverse_utimens(&mut new[1..].to_vec(), times)
+			},
+			None,
+		)
+	}
+
+	fn traverse_entries(&self, components: &mut Vec<&str>) -> io::Result<Arc<Entries>> {
+		block_on(
+			async {
+				let Some(component) = components.pop() else {
+					return Ok(self.inner.clone());
+				};
+
+				if let Some(node) = self.inner.read().await.get(component) {
+					return node.traverse_entries(components);
+				}
+
+				Err(Errno::Noent)
+			},
+			None,
+		)
+	}
+
+	fn traverse_utimens(&self, components: &mut Vec<&str>, times: FileTimes) -> io::Result<()> {
+		block_on(
+			async {
+				let Some(component) = components.pop() else {
+					return self.set_times(times);
+				};
+
+				if let Some(node) = self.inner.read().await.get(component) {
+					if components.is_empty() {
+						return node.set_times(times);
+					}
+
+					return node.traverse_utimens(components, times);
+				}
+
+				Err(Errno::Noent)
+			},
+			None,
+		)
+	}
+
+	fn traverse_statfs(&self, components: &mut Vec<&str>) -> io::Result<StatFs> {
+		block_on(
+			async {
+				if let Some(component) = components.pop() {
+					let node_name = String::from(component);
+
+					if let Some(node) = self.inner.read().await.get(&node_name) {
+						return node.traverse_statfs(components);
+					}
+				}
+
+				Err(Errno::Noent)
+			},
+			None,
+		)
+	}
+
+	fn traverse_link_source(
+		&self,
+		components: &mut Vec<&str>,
+	) -> io::Result<Box<dyn VfsNode + core::marker::Send + core::marker::Sync>> {
+		block_on(
+			async {
+				if let Some(component) = components.pop() {
+					let node_name = String::from(component);
+
+					if let Some(node) = self.inner.read().await.get(&node_name) {
+						if components.is_empty() {
+							return node.hard_link();
+						}
+
+						return node.traverse_link_source(components);
+					}
+				}
+
+				Err(Errno::Noent)
+			},
+			None,
+		)
+	}
+
+	fn traverse_link(
+		&self,
+		components: &mut Vec<&str>,
+		node: Box<dyn VfsNode + core::marker::Send + core::marker::Sync>,
+	) -> io::Result<()> {
+		block_on(
+			async {
+				if let Some(component) = components.pop() {
+					let node_name = String::from(component);
+
+					if components.is_empty() {
+						let mut guard = self.inner.write().await;
+						if guard.contains_key(&node_name) {
+							return Err(Errno::Exist);
+						}
+
+						guard.insert(node_name, node);
+						self.modified();
+						return Ok(());
+					}
+
+					if let Some(directory) = self.inner.read().await.get(&node_name) {
+						return directory.traverse_link(components, node);
+					}
+				}
+
+				Err(Errno::Noent)
+			},
+			None,
+		)
+	}
+
 	fn traverse_open(
 		&self,
 		components: &mut Vec<&str>,
@@ -761,6 +1367,7 @@ impl VfsNode for MemDirectory {
 					if components.is_empty() {
 						let file = RomFile::new(data, mode);
 						self.inner.write().await.insert(name, Box::new(file));
+						self.modified();
 						return Ok(());
 					}
 
@@ -1,24 +1,41 @@
-#[cfg(all(feature = "fuse", feature = "pci"))]
+mod dev;
+#[cfg(feature = "ext2")]
+mod ext2;
+#[cfg(feature = "fat")]
+mod fat;
+#[cfg(feature = "fuse")]
 pub(crate) mod fuse;
+mod initramfs;
+pub(crate) mod lock;
 mod mem;
+#[cfg(feature = "virtio-9p")]
+mod p9;
+pub(crate) mod proc;
 mod uhyve;
+pub(crate) mod watch;
 
 use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::{MaybeUninit, offset_of};
 use core::ops::BitAnd;
 
+use align_address::Align;
 use async_trait::async_trait;
 use embedded_io::{Read, Write};
 use hermit_sync::{InterruptSpinMutex, OnceCell};
-use mem::MemDirectory;
+use mem::{GeneratedFile, MemDirectory};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
+use self::watch::{WatchMask, WatchedFile};
 use crate::errno::Errno;
 use crate::executor::block_on;
 use crate::fd::{AccessPermission, ObjectInterface, OpenOption, insert_object, remove_object};
 use crate::io;
+use crate::syscalls::Dirent64;
 use crate::time::{SystemTime, timespec};
 
 static FILESYSTEM: OnceCell<Filesystem> = OnceCell::new();
@@ -46,6 +63,8 @@ pub(crate) enum NodeKind {
 	File,
 	/// Node represent a directory
 	Directory,
+	/// Node represent a symbolic link
+	Symlink,
 }
 
 /// VfsNode represents an internal node of the ramdisk.
@@ -63,6 +82,21 @@ pub(crate) trait VfsNode: core::fmt::Debug {
 		Err(Errno::Nosys)
 	}
 
+	/// Determines the target of a symbolic link
+	fn readlink(&self) -> io::Result<String> {
+		Err(Errno::Inval)
+	}
+
+	/// Sets the access and modification times of the node
+	fn set_times(&self, _times: FileTimes) -> io::Result<()> {
+		Err(Errno::Perm)
+	}
+
+	/// Creates a new reference to the node, which is used as hard link
+	fn hard_link(&self) -> io::Result<Box<dyn VfsNode + core::marker::Send + core::marker::Sync>> {
+		Err(Errno::Perm)
+	}
+
 	/// Helper function to create a new directory node
 	fn traverse_mkdir(
 		&self,
@@ -97,6 +131,31 @@ pub(crate) trait VfsNode: core::fmt::Debug {
 		Err(Errno::Nosys)
 	}
 
+	/// Helper function to set the access and modification times
+	fn traverse_utimens(&self, _components: &mut Vec<&str>, _times: FileTimes) -> io::Result<()> {
+		Err(Errno::Nosys)
+	}
+
+	/// Helper function to get the capacity of the file system, which contains the node
+	fn traverse_statfs(&self, _components: &mut Vec<&str>) -> io::Result<StatFs> {
+		Err(Errno::Nosys)
+	}
+
+	/// Helper function to rename the node `old` to `new` within the same file system
+	fn traverse_rename(
+		&self,
+		_old: &mut Vec<&str>,
+		_new: &mut Vec<&str>,
+		_flags: RenameFlags,
+	) -> io::Result<()> {
+		Err(Errno::Nosys)
+	}
+
+	/// Helper function to get the entries of an in-memory directory
+	fn traverse_entries(&self, _components: &mut Vec<&str>) -> io::Result<Arc<mem::Entries>> {
+		Err(Errno::Nosys)
+	}
+
 	/// Helper function to mount a file system
 	fn traverse_mount(
 		&self,
@@ -106,6 +165,11 @@ pub(crate) trait VfsNode: core::fmt::Debug {
 		Err(Errno::Nosys)
 	}
 
+	/// Helper function to unmount a file system
+	fn traverse_umount(&self, _components: &mut Vec<&str>) -> io::Result<()> {
+		Err(Errno::Nosys)
+	}
+
 	/// Helper function to open a file
 	fn traverse_open(
 		&self,
@@ -125,6 +189,33 @@ pub(crate) trait VfsNode: core::fmt::Debug {
 	) -> io::Result<()> {
 		Err(Errno::Nosys)
 	}
+
+	/// Helper function to create a symbolic link
+	fn traverse_symlink(&self, _components: &mut Vec<&str>, _target: &str) -> io::Result<()> {
+		Err(Errno::Nosys)
+	}
+
+	/// Helper function to read the target of a symbolic link
+	fn traverse_readlink(&self, _components: &mut Vec<&str>) -> io::Result<String> {
+		Err(Errno::Nosys)
+	}
+
+	/// Helper function to get a new reference to a node, which is used as hard link
+	fn traverse_link_source(
+		&self,
+		_components: &mut Vec<&str>,
+	) -> io::Result<Box<dyn VfsNode + core::marker::Send + core::marker::Sync>> {
+		Err(Errno::Nosys)
+	}
+
+	/// Helper function to create a hard link to `node`
+	fn traverse_link(
+		&self,
+		_components: &mut Vec<&str>,
+		_node: Box<dyn VfsNode + core::marker::Send + core::marker::Sync>,
+	) -> io::Result<()> {
+		Err(Errno::Nosys)
+	}
 }
 
 #[derive(Debug, Clone)]
@@ -144,15 +235,27 @@ impl ObjectInterface for DirectoryReader {
 	}
 }
 
+/// Returns whether the resolved path `path` is `dir` or located below `dir`
+fn is_within(dir: &str, path: &str) -> bool {
+	path.strip_prefix(dir)
+		.is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Maximum number of symbolic links, which are followed while resolving a path
+const MAX_SYMLINKS: usize = 40;
+
 #[derive(Debug)]
 pub(crate) struct Filesystem {
 	root: MemDirectory,
+	/// Paths of all mount points
+	mounts: InterruptSpinMutex<BTreeSet<String>>,
 }
 
 impl Filesystem {
 	pub fn new() -> Self {
 		Self {
 			root: MemDirectory::new(AccessPermission::from_bits(0o777).unwrap()),
+			mounts: InterruptSpinMutex::new(BTreeSet::new()),
 		}
 	}
 
@@ -164,45 +267,72 @@ impl Filesystem {
 		mode: AccessPermission,
 	) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
 		debug!("Open file {path} with {opt:?}");
+		let path = self.resolve(path, true)?;
 		let mut components: Vec<&str> = path.split('/').collect();
 
 		components.reverse();
 		components.pop();
 
-		self.root.traverse_open(&mut components, opt, mode)
+		if !watch::is_watched(&path) {
+			return self.root.traverse_open(&mut components, opt, mode);
+		}
+
+		let existed = opt.contains(OpenOption::O_CREAT)
+			&& self.root.traverse_lstat(&mut components.clone()).is_ok();
+		let object = self.root.traverse_open(&mut components, opt, mode)?;
+		if opt.contains(OpenOption::O_CREAT) && !existed {
+			watch::notify(&path, WatchMask::IN_CREATE);
+		} else if opt.contains(OpenOption::O_TRUNC) {
+			watch::notify(&path, WatchMask::IN_MODIFY);
+		}
+
+		if opt.intersects(OpenOption::O_WRONLY | OpenOption::O_RDWR) {
+			Ok(WatchedFile::wrap(object, path.clone()))
+		} else {
+			Ok(object)
+		}
 	}
 
 	/// Unlinks a file given by path
 	pub fn unlink(&self, path: &str) -> io::Result<()> {
 		debug!("Unlinking file {path}");
+		let path = self.resolve(path, false)?;
 		let mut components: Vec<&str> = path.split('/').collect();
 
 		components.reverse();
 		components.pop();
 
-		self.root.traverse_unlink(&mut components)
+		self.root.traverse_unlink(&mut components)?;
+		watch::notify(&path, WatchMask::IN_DELETE);
+		Ok(())
 	}
 
 	/// Remove directory given by path
 	pub fn rmdir(&self, path: &str) -> io::Result<()> {
 		debug!("Removing directory {path}");
+		let path = self.resolve(path, false)?;
 		let mut components: Vec<&str> = path.split('/').collect();
 
 		components.reverse();
 		components.pop();
 
-		self.root.traverse_rmdir(&mut components)
+		self.root.traverse_rmdir(&mut components)?;
+		watch::notify(&path, WatchMask::IN_DELETE | WatchMask::IN_ISDIR);
+		Ok(())
 	}
 
 	/// Create directory given by path
 	pub fn mkdir(&self, path: &str, mode: AccessPermission) -> io::Result<()> {
 		debug!("Create directory {path}");
+		let path = self.resolve(path, false)?;
 		let mut components: Vec<&str> = path.split('/').collect();
 
 		components.reverse();
 		components.pop();
 
-		self.root.traverse_mkdir(&mut components, mode)
+		self.root.traverse_mkdir(&mut components, mode)?;
+		watch::notify(&path, WatchMask::IN_CREATE | WatchMask::IN_ISDIR);
+		Ok(())
 	}
 
 	pub fn opendir(&self, path: &str) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
@@ -214,6 +344,7 @@ impl Filesystem {
 
 	/// List given directory
 	pub fn readdir(&self, path: &str) -> io::Result<Vec<DirectoryEntry>> {
+		let path = self.resolve(path, true)?;
 		if path.trim() == "/" {
 			let mut components: Vec<&str> = Vec::new();
 			self.root.traverse_readdir(&mut components)
@@ -231,6 +362,7 @@ impl Filesystem {
 	pub fn stat(&self, path: &str) -> io::Result<FileAttr> {
 		debug!("Getting stats {path}");
 
+		let path = self.resolve(path, true)?;
 		let mut components: Vec<&str> = path.split('/').collect();
 		components.reverse();
 		components.pop();
@@ -242,6 +374,7 @@ impl Filesystem {
 	pub fn lstat(&self, path: &str) -> io::Result<FileAttr> {
 		debug!("Getting lstats {path}");
 
+		let path = self.resolve(path, false)?;
 		let mut components: Vec<&str> = path.split('/').collect();
 		components.reverse();
 		components.pop();
@@ -249,6 +382,120 @@ impl Filesystem {
 		self.root.traverse_lstat(&mut components)
 	}
 
+	/// Sets the access and modification times of `path`
+	pub fn utimens(&self, path: &str, times: FileTimes, follow: bool) -> io::Result<()> {
+		debug!("Set the timestamps of {path} to {times:?}");
+
+		let path = self.resolve(path, follow)?;
+		let mut components: Vec<&str> = path.split('/').collect();
+		components.reverse();
+		components.pop();
+
+		self.root.traverse_utimens(&mut components, times)
+	}
+
+	/// Returns the capacity of the file system, which contains `path`
+	pub fn statfs(&self, path: &str) -> io::Result<StatFs> {
+		debug!("Getting file system stats {path}");
+
+		let path = self.resolve(path, true)?;
+		self.stat(&path)?;
+
+		let Some(mount) = self.mount_point(&path) else {
+			return Ok(mem::statfs());
+		};
+
+		let mut components: Vec<&str> = mount.split('/').collect();
+		components.reverse();
+		components.pop();
+
+		self.root.traverse_statfs(&mut components)
+	}
+
+	/// Renames `old` to `new`, which have to be located in the same file system.
+	///
+	/// The rename is atomic. Other tasks either see the old or the new entry,
+	/// and an existing `new` is replaced without being removed first.
+	pub fn rename(&self, old: &str, new: &str, flags: RenameFlags) -> io::Result<()> {
+		debug!("Rename {old} to {new} with {flags:?}");
+
+		if flags.contains(RenameFlags::RENAME_NOREPLACE | RenameFlags::RENAME_EXCHANGE) {
+			return Err(Errno::Inval);
+		}
+
+		let old = self.resolve(old, false)?;
+		let new = self.resolve(new, false)?;
+		let attr = self.lstat(&old)?;
+		if old == new {
+			return Ok(());
+		}
+
+		if self
+			.mounts
+			.lock()
+			.iter()
+			.any(|mount| is_within(&old, mount) || is_within(&new, mount))
+		{
+			return Err(Errno::Busy);
+		}
+		if self.mount_point(&old) != self.mount_point(&new) {
+			return Err(Errno::Xdev);
+		}
+		// A directory cannot become its own subdirectory or replace one of its parents.
+		if is_within(&old, &new) {
+			return Err(Errno::Inval);
+		}
+		if is_within(&new, &old) {
+			return Err(if flags.contains(RenameFlags::RENAME_EXCHANGE) {
+				Errno::Inval
+			} else {
+				Errno::Notempty
+			});
+		}
+
+		let target = if flags.contains(RenameFlags::RENAME_EXCHANGE) {
+			Some(self.lstat(&new)?)
+		} else {
+			None
+		};
+
+		let mut old_components: Vec<&str> = old.split('/').collect();
+		old_components.reverse();
+		old_components.pop();
+		let mut new_components: Vec<&str> = new.split('/').collect();
+		new_components.reverse();
+		new_components.pop();
+
+		self.root
+			.traverse_rename(&mut old_components, &mut new_components, flags)?;
+
+		let dir_flag = |attr: FileAttr| {
+			if (attr.st_mode & AccessPermission::S_IFMT).bits() == AccessPermission::S_IFDIR.bits()
+			{
+				WatchMask::IN_ISDIR
+			} else {
+				WatchMask::empty()
+			}
+		};
+		watch::notify(&old, WatchMask::IN_DELETE | dir_flag(attr));
+		watch::notify(&new, WatchMask::IN_CREATE | dir_flag(attr));
+		if let Some(target) = target {
+			watch::notify(&new, WatchMask::IN_DELETE | dir_flag(target));
+			watch::notify(&old, WatchMask::IN_CREATE | dir_flag(target));
+		}
+		Ok(())
+	}
+
+	/// Returns the innermost mount point, which contains the resolved path `path`
+	fn mount_point(&self, path: &str) -> Option<String> {
+		self.mounts
+			.lock()
+			.iter()
+			.filter(|mount| is_within(mount, path))
+			.max_by_key(|mount| mount.len())
+			.cloned()
+	}
+
 	/// Create new backing-fs at mountpoint mntpath
 	pub fn mount(
 		&self,
@@ -257,12 +504,45 @@ impl Filesystem {
 	) -> io::Result<()> {
 		debug!("Mounting {path}");
 
+		let path = self.resolve(path, true)?;
+		let path = path.as_str();
+		if path == "/" || !self.mounts.lock().insert(path.to_string()) {
+			return Err(Errno::Busy);
+		}
+
 		let mut components: Vec<&str> = path.split('/').collect();
 
 		components.reverse();
 		components.pop();
 
-		self.root.traverse_mount(&mut components, obj)
+		let result = self.root.traverse_mount(&mut components, obj);
+		if result.is_err() {
+			self.mounts.lock().remove(path);
+		}
+		result
+	}
+
+	/// Detaches the file system at mountpoint `path`, which is replaced by
+	/// an empty directory
+	pub fn umount(&self, path: &str) -> io::Result<()> {
+		debug!("Unmounting {path}");
+
+		let path = self.resolve(path, true)?;
+		let path = path.as_str();
+		if !self.mounts.lock().remove(path) {
+			return Err(Errno::Inval);
+		}
+
+		let mut components: Vec<&str> = path.split('/').collect();
+
+		components.reverse();
+		components.pop();
+
+		let result = self.root.traverse_umount(&mut components);
+		if result.is_err() {
+			self.mounts.lock().insert(path.to_string());
+		}
+		result
 	}
 
 	/// Create read-only file
@@ -274,12 +554,209 @@ impl Filesystem {
 	) -> io::Result<()> {
 		debug!("Create read-only file {path}");
 
+		let path = self.resolve(path, false)?;
 		let mut components: Vec<&str> = path.split('/').collect();
 
 		components.reverse();
 		components.pop();
 
-		self.root.traverse_create_file(&mut components, data, mode)
+		self.root
+			.traverse_create_file(&mut components, data, mode)?;
+		watch::notify(&path, WatchMask::IN_CREATE);
+		Ok(())
+	}
+
+	/// Create read-only file, whose content is generated by `generate` on every open
+	pub fn create_generated_file(
+		&self,
+		path: &str,
+		generate: fn() -> String,
+		mode: AccessPermission,
+	) -> io::Result<()> {
+		debug!("Create generated file {path}");
+
+		let mut components: Vec<&str> = path.split('/').collect();
+
+		components.reverse();
+		components.pop();
+
+		// The file is not a mount point, but it is attached in the same way.
+		self.root.traverse_mount(
+			&mut components,
+			Box::new(GeneratedFile::new(generate, mode)),
+		)
+	}
+
+	/// Create symbolic link at path, which points to target
+	pub fn symlink(&self, target: &str, path: &str) -> io::Result<()> {
+		debug!("Create symbolic link {path} -> {target}");
+
+		let path = self.resolve(path, false)?;
+		let mut components: Vec<&str> = path.split('/').collect();
+
+		components.reverse();
+		components.pop();
+
+		self.root.traverse_symlink(&mut components, target)?;
+		watch::notify(&path, WatchMask::IN_CREATE);
+		Ok(())
+	}
+
+	/// Read the target of the symbolic link given by path
+	pub fn readlink(&self, path: &str) -> io::Result<String> {
+		debug!("Read symbolic link {path}");
+
+		let path = self.resolve(path, false)?;
+		let mut components: Vec<&str> = path.split('/').collect();
+
+		components.reverse();
+		components.pop();
+
+		self.root.traverse_readlink(&mut components)
+	}
+
+	/// Create hard link at path to the file given by original
+	pub fn link(&self, original: &str, path: &str) -> io::Result<()> {
+		debug!("Create hard link {path} to {original}");
+
+		let original = self.resolve(original, false)?;
+		let mut components: Vec<&str> = original.split('/').collect();
+
+		components.reverse();
+		components.pop();
+
+		// Hard links are only supported within the in-memory file system.
+		let node = self
+			.root
+			.traverse_link_source(&mut components)
+			.map_err(|err| {
+				if err == Errno::Nosys {
+					Errno::Xdev
+				} else {
+					err
+				}
+			})?;
+
+		let path = self.resolve(path, false)?;
+		let mut components: Vec<&str> = path.split('/').collect();
+
+		components.reverse();
+		components.pop();
+
+		self.root
+			.traverse_link(&mut components, node)
+			.map_err(|err| {
+				if err == Errno::Nosys {
+					Errno::Xdev
+				} else {
+					err
+				}
+			})?;
+		watch::notify(&path, WatchMask::IN_CREATE);
+		Ok(())
+	}
+
+	/// Returns the canonical form of the absolute path `path`, which has to exist.
+	///
+	/// Symbolic links below a mount point are resolved by the mounted file
+	/// system and remain in the path.
+	pub fn realpath(&self, path: &str) -> io::Result<String> {
+		let path = self.resolve(path, true)?;
+		self.lstat(&path)?;
+		Ok(path)
+	}
+
+	/// Resolves the absolute path `path` component by component.
+	///
+	/// Repeated slashes and `.` are skipped and `..` moves to the parent of the
+	/// path resolved so far. All components except the last one have to exist and
+	/// have to be directories or symbolic links, which are resolved. The last
+	/// component is only resolved, if `follow` is set or the path ends with a
+	/// slash. In the latter case, it has to be a directory, if it exists.
+	/// Mounted file systems resolve their own symbolic links, so the remaining
+	/// path below a mount point is only normalized.
+	fn resolve(&self, path: &str, follow: bool) -> io::Result<String> {
+		let trailing_slash = matches!(path.rsplit('/').next(), Some("" | "." | ".."));
+		let follow = follow || trailing_slash;
+		let mut resolved = String::with_capacity(path.len());
+		let mut pending: Vec<String> = path.rsplit('/').map(String::from).collect();
+		let mut symlinks = 0;
+		// Length of the mount point, while the resolved path is below it
+		let mut mount_point = None;
+
+		while let Some(component) = pending.pop() {
+			match component.as_str() {
+				"" | "." => continue,
+				".." => {
+					let len = resolved.rfind('/').unwrap_or(0);
+					resolved.truncate(len);
+					if mount_point.is_some_and(|mount_point| len < mount_point) {
+						mount_point = None;
+					}
+					continue;
+				}
+				_ => {}
+			}
+
+			let len = resolved.len();
+			resolved.push('/');
+			resolved.push_str(&component);
+
+			if mount_point.is_some() {
+				continue;
+			}
+			if self.mounts.lock().contains(&resolved) {
+				mount_point = Some(resolved.len());
+				continue;
+			}
+
+			let last = pending
+				.iter()
+				.all(|component| component.is_empty() || component == ".");
+			if last && !follow {
+				break;
+			}
+
+			let mut components: Vec<&str> = resolved.split('/').collect();
+			components.reverse();
+			components.pop();
+
+			let file_type = match self.root.traverse_lstat(&mut components) {
+				Ok(attr) => attr.st_mode & AccessPermission::S_IFMT,
+				// The last component may be created.
+				Err(Errno::Noent) if last => break,
+				Err(err) => return Err(err),
+			};
+
+			if file_type.bits() == AccessPermission::S_IFLNK.bits() {
+				symlinks += 1;
+				if symlinks > MAX_SYMLINKS {
+					return Err(Errno::Loop);
+				}
+
+				let mut components: Vec<&str> = resolved.split('/').collect();
+				components.reverse();
+				components.pop();
+				let target = self.root.traverse_readlink(&mut components)?;
+
+				if target.starts_with('/') {
+					resolved.clear();
+				} else {
+					resolved.truncate(len);
+				}
+				pending.extend(target.rsplit('/').map(String::from));
+			} else if file_type.bits() != AccessPermission::S_IFDIR.bits()
+				&& (!last || trailing_slash)
+			{
+				return Err(Errno::Notdir);
+			}
+		}
+
+		if resolved.is_empty() {
+			resolved.push('/');
+		}
+
+		Ok(resolved)
 	}
 }
 
@@ -311,6 +788,76 @@ pub struct FileAttr {
 	pub st_ctim: timespec,
 }
 
+const _: () = assert!(size_of::<FileAttr>() == 120);
+
+bitflags! {
+	/// Flags of `rename`
+	#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+	pub struct RenameFlags: u32 {
+		/// The target must not exist.
+		const RENAME_NOREPLACE = 0x1;
+		/// The source and the target, which must exist, are exchanged.
+		const RENAME_EXCHANGE = 0x2;
+	}
+}
+
+/// Timestamps, which are set by `utimensat`
+///
+/// Timestamps, which are `None`, are left unchanged. The status change time is
+/// always set to the current time.
+#[derive(Debug, Copy, Clone, Default)]
+pub(crate) struct FileTimes {
+	pub atime: Option<timespec>,
+	pub mtime: Option<timespec>,
+}
+
+impl FileTimes {
+	/// Applies the timestamps to `attr` at the time `now`.
+	pub fn apply(&self, attr: &mut FileAttr, now: timespec) {
+		if let Some(atime) = self.atime {
+			attr.st_atim = atime;
+		}
+		if let Some(mtime) = self.mtime {
+			attr.st_mtim = mtime;
+		}
+		attr.st_ctim = now;
+	}
+}
+
+/// Magic numbers of the file systems, as reported by `statfs`
+pub(crate) const TMPFS_MAGIC: u64 = 0x0102_1994;
+pub(crate) const MSDOS_SUPER_MAGIC: u64 = 0x4d44;
+pub(crate) const EXT2_SUPER_MAGIC: u64 = 0xef53;
+pub(crate) const FUSE_SUPER_MAGIC: u64 = 0x6573_5546;
+pub(crate) const V9FS_MAGIC: u64 = 0x0102_1997;
+
+/// The file system is mounted read-only.
+pub(crate) const ST_RDONLY: u64 = 1;
+
+/// Capacity of a mounted file system
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct StatFs {
+	/// type of the file system
+	pub f_type: u64,
+	/// block size in bytes
+	pub f_bsize: u64,
+	/// size of the file system in blocks
+	pub f_blocks: u64,
+	/// number of free blocks
+	pub f_bfree: u64,
+	/// number of free blocks, which are available to the application
+	pub f_bavail: u64,
+	/// number of inodes, 0 if the number is not limited
+	pub f_files: u64,
+	/// number of free inodes
+	pub f_ffree: u64,
+	/// maximum length of a file name
+	pub f_namelen: u64,
+	/// mount flags, e.g., `ST_RDONLY`
+	pub f_flags: u64,
+}
+
 #[derive(TryFromPrimitive, IntoPrimitive, PartialEq, Eq, Clone, Copy, Debug)]
 #[repr(u8)]
 pub enum FileType {
@@ -335,37 +882,171 @@ pub enum SeekWhence {
 	Hole = 4,
 }
 
-pub(crate) fn init() {
-	const VERSION: &str = env!("CARGO_PKG_VERSION");
-	const UTC_BUILT_TIME: &str = build_time::build_time_utc!();
+impl From<NodeKind> for FileType {
+	fn from(value: NodeKind) -> Self {
+		match value {
+			NodeKind::File => Self::RegularFile,
+			NodeKind::Directory => Self::Directory,
+			NodeKind::Symlink => Self::SymbolicLink,
+		}
+	}
+}
+
+/// Writes the directory entries from the position `*pos` on as [`Dirent64`]
+/// records to `buf` and advances the position.
+///
+/// `entries` yields the name, the inode number, and the type of all entries
+/// of the directory. The position counts the entries, which have been read.
+/// `d_off` of a record is the position after the entry, which can be passed
+/// to `lseek` to continue after the entry.
+pub(crate) fn write_dirents<'a>(
+	buf: &mut [MaybeUninit<u8>],
+	pos: &mut usize,
+	entries: impl Iterator<Item = (&'a str, u64, FileType)>,
+) -> io::Result<usize> {
+	let mut buf_offset: usize = 0;
+	for (name, ino, file_type) in entries.skip(*pos) {
+		let namelen = name.len();
+
+		let dirent_len = offset_of!(Dirent64, d_name) + namelen + 1;
+		let next_dirent = (buf_offset + dirent_len).align_up(align_of::<Dirent64>());
+
+		if next_dirent > buf.len() {
+			if buf_offset == 0 {
+				// The buffer is too small for a single entry.
+				return Err(Errno::Inval);
+			}
+			// target buffer full -> we return the nr. of bytes written (like linux does)
+			break;
+		}
+
+		*pos += 1;
+
+		// could be replaced with slice_as_ptr once maybe_uninit_slice is stabilized.
+		let target_dirent = buf[buf_offset].as_mut_ptr().cast::<Dirent64>();
+
+		unsafe {
+			target_dirent.write(Dirent64 {
+				d_ino: ino,
+				d_off: (*pos).try_into().unwrap(),
+				d_reclen: (next_dirent - buf_offset).try_into().unwrap(),
+				d_type: file_type,
+				d_name: PhantomData {},
+			});
+			let nameptr = core::ptr::from_mut(&mut (*(target_dirent)).d_name).cast::<u8>();
+			core::ptr::copy_nonoverlapping(name.as_bytes().as_ptr(), nameptr, namelen);
+			nameptr.add(namelen).write(0); // zero termination
+		}
+
+		buf_offset = next_dirent;
+	}
+	Ok(buf_offset)
+}
+
+/// Moves the position of a directory stream.
+///
+/// `SeekWhence::Cur` with an offset of 0 returns the current position
+/// (`telldir`) and `SeekWhence::Set` restores a position (`seekdir`). The
+/// directories of all file systems implement `lseek` with it.
+pub(crate) fn seek_dir(pos: &mut usize, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+	let new_pos = match whence {
+		SeekWhence::Set => offset,
+		SeekWhence::Cur => isize::try_from(*pos).unwrap() + offset,
+		_ => return Err(Errno::Inval),
+	};
+
+	*pos = usize::try_from(new_pos).map_err(|_| Errno::Inval)?;
+	Ok(new_pos)
+}
 
+pub(crate) fn init() {
 	FILESYSTEM.set(Filesystem::new()).unwrap();
 	FILESYSTEM
 		.get()
 		.unwrap()
 		.mkdir("/tmp", AccessPermission::from_bits(0o777).unwrap())
 		.expect("Unable to create /tmp");
+	proc::init(FILESYSTEM.get().unwrap());
 	FILESYSTEM
 		.get()
 		.unwrap()
-		.mkdir("/proc", AccessPermission::from_bits(0o777).unwrap())
-		.expect("Unable to create /proc");
-
-	if let Ok(mut file) = File::create("/proc/version") {
-		if write!(file, "HermitOS version {VERSION} # UTC {UTC_BUILT_TIME}").is_err() {
-			error!("Unable to write in /proc/version");
-		}
-	} else {
-		error!("Unable to create /proc/version");
-	}
+		.mount("/dev", Box::new(dev::DevDirectory))
+		.expect("Unable to mount /dev");
+	initramfs::init(FILESYSTEM.get().unwrap());
 
 	let mut cwd = WORKING_DIRECTORY.lock();
 	*cwd = Some("/tmp".to_string());
 	drop(cwd);
 
-	#[cfg(all(feature = "fuse", feature = "pci"))]
+	#[cfg(feature = "fuse")]
 	fuse::init();
 	uhyve::init();
+	#[cfg(any(feature = "fat", feature = "ext2"))]
+	mount_root();
+}
+
+/// Opens the file system `fs_type` on `source`, which is either a block
+/// device (e.g. `/dev/nvme0n1p1`) or the tag of a virtio-fs or virtio-9p device.
+fn open_filesystem(source: &str, fs_type: &str) -> io::Result<Box<dyn VfsNode + Send + Sync>> {
+	match fs_type {
+		#[cfg(feature = "fat")]
+		"fat32" | "vfat" => fat::open(block_device_name(source)),
+		#[cfg(feature = "ext2")]
+		"ext2" => ext2::open(block_device_name(source)),
+		#[cfg(feature = "fuse")]
+		"virtiofs" => fuse::open(source),
+		#[cfg(feature = "virtio-9p")]
+		"9p" => p9::open(source),
+		_ => {
+			error!("Unsupported file system type {fs_type} for {source}");
+			Err(Errno::Nodev)
+		}
+	}
+}
+
+#[cfg(any(feature = "fat", feature = "ext2"))]
+fn block_device_name(source: &str) -> &str {
+	source.strip_prefix("/dev/").unwrap_or(source)
+}
+
+/// Mounts the file system given by `root=<device>,<type>` (e.g.
+/// `root=/dev/nvme0n1p1,fat32`) at `/root`.
+#[cfg(any(feature = "fat", feature = "ext2"))]
+fn mount_root() {
+	let Some(root) = crate::env::root() else {
+		return;
+	};
+	let Some((device, fs_type)) = root.split_once(',') else {
+		error!("Invalid root file system {root}, expected root=<device>,<type>");
+		return;
+	};
+
+	match open_filesystem(device, fs_type) {
+		Ok(node) => {
+			info!("Mounting {fs_type} file system of {device} at /root");
+			if let Err(err) = FILESYSTEM.get().unwrap().mount("/root", node) {
+				error!("Could not mount root file system: {err:?}");
+			}
+		}
+		Err(err) => error!("Could not open {fs_type} file system of {device}: {err:?}"),
+	}
+}
+
+/// Mounts the file system `fs_type` on `source` at `target`.
+pub fn mount(source: &str, target: &str, fs_type: &str) -> io::Result<()> {
+	with_relative_filename(target, |target| {
+		let fs = FILESYSTEM.get().ok_or(Errno::Inval)?;
+		let node = open_filesystem(source, fs_type)?;
+		info!("Mounting {fs_type} file system of {source} at {target}");
+		fs.mount(target, node)
+	})
+}
+
+/// Unmounts the file system at `target`.
+pub fn umount(target: &str) -> io::Result<()> {
+	with_relative_filename(target, |target| {
+		FILESYSTEM.get().ok_or(Errno::Inval)?.umount(target)
+	})
 }
 
 pub fn create_file(name: &str, data: &'static [u8], mode: AccessPermission) -> io::Result<()> {
@@ -423,6 +1104,58 @@ pub fn read_lstat(name: &str) -> io::Result<FileAttr> {
 	})
 }
 
+/// Sets the access and modification times of the file `name`.
+///
+/// Symbolic links are only followed, if `follow` is set.
+pub(crate) fn utimens(name: &str, times: FileTimes, follow: bool) -> io::Result<()> {
+	with_relative_filename(name, |name| {
+		FILESYSTEM
+			.get()
+			.ok_or(Errno::Inval)?
+			.utimens(name, times, follow)
+	})
+}
+
+/// Returns the capacity of the file system, which contains the file `name`
+pub fn statfs(name: &str) -> io::Result<StatFs> {
+	with_relative_filename(name, |name| {
+		FILESYSTEM.get().ok_or(Errno::Inval)?.statfs(name)
+	})
+}
+
+/// Creates the symbolic link `path`, which points to `target`.
+pub fn symlink(target: &str, path: &str) -> io::Result<()> {
+	with_relative_filename(path, |path| {
+		FILESYSTEM.get().ok_or(Errno::Inval)?.symlink(target, path)
+	})
+}
+
+/// Returns the target of the symbolic link `path`.
+pub fn readlink(path: &str) -> io::Result<String> {
+	with_relative_filename(path, |path| {
+		FILESYSTEM.get().ok_or(Errno::Inval)?.readlink(path)
+	})
+}
+
+/// Renames the file `old` to `new`.
+pub fn rename(old: &str, new: &str, flags: RenameFlags) -> io::Result<()> {
+	let old = with_relative_filename(old, |old| Ok(old.to_string()))?;
+	with_relative_filename(new, |new| {
+		FILESYSTEM
+			.get()
+			.ok_or(Errno::Inval)?
+			.rename(&old, new, flags)
+	})
+}
+
+/// Creates the hard link `path` to the file `original`.
+pub fn link(original: &str, path: &str) -> io::Result<()> {
+	let original = with_relative_filename(original, |original| Ok(original.to_string()))?;
+	with_relative_filename(path, |path| {
+		FILESYSTEM.get().ok_or(Errno::Inval)?.link(&original, path)
+	})
+}
+
 fn with_relative_filename<F, T>(name: &str, callback: F) -> io::Result<T>
 where
 	F: FnOnce(&str) -> io::Result<T>,
@@ -448,11 +1181,8 @@ where
 pub fn truncate(name: &str, size: usize) -> io::Result<()> {
 	with_relative_filename(name, |name| {
 		let fs = FILESYSTEM.get().ok_or(Errno::Inval)?;
-		if let Ok(file) = fs.open(name, OpenOption::O_TRUNC, AccessPermission::empty()) {
-			block_on(async { file.read().await.truncate(size).await }, None)
-		} else {
-			Err(Errno::Badf)
-		}
+		let file = fs.open(name, OpenOption::O_WRONLY, AccessPermission::empty())?;
+		block_on(async { file.read().await.truncate(size).await }, None)
 	})
 }
 
@@ -482,22 +1212,24 @@ pub fn get_cwd() -> io::Result<String> {
 }
 
 pub fn set_cwd(cwd: &str) -> io::Result<()> {
-	// TODO: check that the directory exists and that permission flags are correct
+	// TODO: check that permission flags are correct
 
-	let mut working_dir = WORKING_DIRECTORY.lock();
-	if cwd.starts_with("/") {
-		*working_dir = Some(cwd.to_string());
-	} else {
-		let Some(working_dir) = working_dir.as_mut() else {
-			return Err(Errno::Badf);
-		};
-		working_dir.push('/');
-		working_dir.push_str(cwd);
+	let cwd = realpath(cwd)?;
+	if !Metadata(read_stat(&cwd)?).is_dir() {
+		return Err(Errno::Notdir);
 	}
 
+	*WORKING_DIRECTORY.lock() = Some(cwd);
 	Ok(())
 }
 
+/// Returns the canonical absolute form of `path`.
+pub fn realpath(path: &str) -> io::Result<String> {
+	with_relative_filename(path, |path| {
+		FILESYSTEM.get().ok_or(Errno::Inval)?.realpath(path)
+	})
+}
+
 pub fn umask(new_mask: AccessPermission) -> AccessPermission {
 	let mut lock = UMASK.lock();
 	let old = *lock;
@@ -623,3 +1355,174 @@ impl Drop for File {
 		let _ = remove_object(self.fd);
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn mode() -> AccessPermission {
+		AccessPermission::from_bits(0o777).unwrap()
+	}
+
+	fn is_dir(fs: &Filesystem, path: &str) -> bool {
+		let attr = fs.lstat(path).unwrap();
+		(attr.st_mode & AccessPermission::S_IFMT).bits() == AccessPermission::S_IFDIR.bits()
+	}
+
+	fn create(fs: &Filesystem, path: &str) {
+		fs.open(path, OpenOption::O_CREAT | OpenOption::O_RDWR, mode())
+			.unwrap();
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_resolve_dotdot_across_mount_point() {
+		let fs = Filesystem::new();
+		fs.mkdir("/data", mode()).unwrap();
+		fs.mount("/mnt", Box::new(MemDirectory::new(mode())))
+			.unwrap();
+		fs.mkdir("/mnt/dir", mode()).unwrap();
+
+		assert_eq!(fs.resolve("/mnt/dir/../../data", true).unwrap(), "/data");
+		assert_eq!(fs.realpath("/mnt/dir/..").unwrap(), "/mnt");
+		assert_eq!(fs.realpath("/mnt/..").unwrap(), "/");
+		assert_eq!(fs.realpath("/mnt/../../..").unwrap(), "/");
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_resolve_trailing_slash_on_file() {
+		let fs = Filesystem::new();
+		fs.open("/file", OpenOption::O_CREAT | OpenOption::O_RDWR, mode())
+			.unwrap();
+		fs.mkdir("/dir", mode()).unwrap();
+
+		assert_eq!(fs.resolve("/file/", false), Err(Errno::Notdir));
+		assert_eq!(fs.resolve("/file/.", false), Err(Errno::Notdir));
+		assert_eq!(fs.realpath("/file/"), Err(Errno::Notdir));
+		assert_eq!(fs.realpath("/dir/").unwrap(), "/dir");
+		assert_eq!(fs.realpath("/file").unwrap(), "/file");
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_resolve_symlink_loop() {
+		let fs = Filesystem::new();
+		fs.symlink("/b", "/a").unwrap();
+		fs.symlink("a", "/b").unwrap();
+		fs.symlink("self", "/self").unwrap();
+
+		assert_eq!(fs.realpath("/a"), Err(Errno::Loop));
+		assert_eq!(fs.resolve("/a/file", false), Err(Errno::Loop));
+		assert_eq!(fs.realpath("/self/"), Err(Errno::Loop));
+		// The last component is not followed.
+		assert_eq!(fs.resolve("/a", false).unwrap(), "/a");
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_rename_within_and_across_directories() {
+		let fs = Filesystem::new();
+		fs.mkdir("/a", mode()).unwrap();
+		fs.mkdir("/b", mode()).unwrap();
+		create(&fs, "/a/file");
+		create(&fs, "/a/target");
+
+		fs.rename("/a/file", "/a/renamed", RenameFlags::empty())
+			.unwrap();
+		assert_eq!(fs.lstat("/a/file").err(), Some(Errno::Noent));
+		// An existing file is replaced.
+		fs.rename("/a/renamed", "/a/target", RenameFlags::empty())
+			.unwrap();
+		assert_eq!(fs.lstat("/a/renamed").err(), Some(Errno::Noent));
+
+		fs.rename("/a/target", "/b/file", RenameFlags::empty())
+			.unwrap();
+		assert!(!is_dir(&fs, "/b/file"));
+		assert_eq!(fs.lstat("/a/target").err(), Some(Errno::Noent));
+		fs.rename("/b", "/a/b", RenameFlags::empty()).unwrap();
+		assert!(!is_dir(&fs, "/a/b/file"));
+		assert_eq!(fs.lstat("/b").err(), Some(Errno::Noent));
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_rename_exchange_file_and_directory() {
+		let fs = Filesystem::new();
+		create(&fs, "/file");
+		fs.mkdir("/dir", mode()).unwrap();
+		create(&fs, "/dir/inner");
+
+		fs.rename("/file", "/dir", RenameFlags::RENAME_EXCHANGE)
+			.unwrap();
+		assert!(is_dir(&fs, "/file"));
+		assert!(!is_dir(&fs, "/dir"));
+		assert!(fs.lstat("/file/inner").is_ok());
+		assert_eq!(
+			fs.rename("/file", "/missing", RenameFlags::RENAME_EXCHANGE),
+			Err(Errno::Noent)
+		);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_rename_replace_directory() {
+		let fs = Filesystem::new();
+		fs.mkdir("/src", mode()).unwrap();
+		fs.mkdir("/empty", mode()).unwrap();
+		fs.mkdir("/full", mode()).unwrap();
+		create(&fs, "/full/file");
+		create(&fs, "/file");
+
+		assert_eq!(
+			fs.rename("/src", "/full", RenameFlags::empty()),
+			Err(Errno::Notempty)
+		);
+		assert_eq!(
+			fs.rename("/src", "/file", RenameFlags::empty()),
+			Err(Errno::Notdir)
+		);
+		assert_eq!(
+			fs.rename("/file", "/empty", RenameFlags::empty()),
+			Err(Errno::Isdir)
+		);
+		fs.rename("/src", "/empty", RenameFlags::empty()).unwrap();
+		assert_eq!(fs.lstat("/src").err(), Some(Errno::Noent));
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_rename_directory_into_itself() {
+		let fs = Filesystem::new();
+		fs.mkdir("/dir", mode()).unwrap();
+		fs.mkdir("/dir/sub", mode()).unwrap();
+
+		assert_eq!(
+			fs.rename("/dir", "/dir/sub/dir", RenameFlags::empty()),
+			Err(Errno::Inval)
+		);
+		assert_eq!(
+			fs.rename("/dir", "/dir/new", RenameFlags::empty()),
+			Err(Errno::Inval)
+		);
+		// A parent cannot be replaced by one of its subdirectories.
+		assert_eq!(
+			fs.rename("/dir/sub", "/dir", RenameFlags::empty()),
+			Err(Errno::Notempty)
+		);
+		assert!(is_dir(&fs, "/dir/sub"));
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_sys_realpath() {
+		let mut buf = [0u8; 8];
+		let path = c"/tmp/../tmp/.".as_ptr();
+		let len = unsafe { crate::syscalls::sys_realpath(path, buf.as_mut_ptr(), buf.len()) };
+		assert_eq!(len, 4);
+		assert_eq!(&buf[..5], b"/tmp\0");
+
+		let len = unsafe { crate::syscalls::sys_realpath(path, buf.as_mut_ptr(), 4) };
+		assert_eq!(len, -isize::try_from(i32::from(Errno::Range)).unwrap());
+	}
+}
@@ -0,0 +1,900 @@
+//! 9P2000.L client on top of virtio-9p devices.
+//!
+//! A device exports a directory of the host, which is mounted by its tag
+//! (e.g. `mount("hostshare", "/host", "9p")`). All mounts of a device share
+//! a session with the server. Open files and directories own a fid of the
+//! server, which is clunked, when the object is dropped. Requests are sent one
+//! at a time, so that all requests use the same tag.
+//!
+//! Symbolic links are followed, if they are the last component of a path and
+//! their target is relative. Absolute targets refer to the file system of the
+//! host and cannot be resolved.
+
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use async_lock::Mutex;
+use async_trait::async_trait;
+use hermit_sync::InterruptTicketMutex;
+
+use crate::drivers::fs::virtio_9p::{self, Virtio9pDriver};
+use crate::errno::Errno;
+use crate::fd::{AccessPermission, ObjectInterface, OpenOption, PollEvent};
+use crate::fs::{
+	DirectoryEntry, FileAttr, FileType, NodeKind, RenameFlags, SeekWhence, StatFs, V9FS_MAGIC,
+	VfsNode, seek_dir, write_dirents,
+};
+use crate::io;
+use crate::mm::device_alloc::DeviceAlloc;
+use crate::time::timespec;
+
+const VERSION: &str = "9P2000.L";
+/// Maximal size of a message, which is proposed to the server
+const MSIZE: u32 = 128 * 1024;
+/// Size of the header of `Tread` and `Rwrite`, which precedes the data
+const IOHDRSZ: u32 = 24;
+const NOTAG: u16 = !0;
+const NOFID: u32 = !0;
+/// Maximal number of names in a single `Twalk`
+const MAXWELEM: usize = 16;
+/// Maximal number of symbolic links, which are followed during a lookup
+const MAX_SYMLINKS: usize = 8;
+
+/// Message types, each response has the type of its request plus one
+const RLERROR: u8 = 7;
+const TSTATFS: u8 = 8;
+const TLOPEN: u8 = 12;
+const TLCREATE: u8 = 14;
+const TSYMLINK: u8 = 16;
+const TREADLINK: u8 = 22;
+const TGETATTR: u8 = 24;
+const TSETATTR: u8 = 26;
+const TREADDIR: u8 = 40;
+const TFSYNC: u8 = 50;
+const TMKDIR: u8 = 72;
+const TRENAMEAT: u8 = 74;
+const TUNLINKAT: u8 = 76;
+const TVERSION: u8 = 100;
+const TATTACH: u8 = 104;
+const TWALK: u8 = 110;
+const TREAD: u8 = 116;
+const TWRITE: u8 = 118;
+const TCLUNK: u8 = 120;
+
+/// Requests the fields of `stat` in `Tgetattr`
+const GETATTR_BASIC: u64 = 0x7ff;
+const SETATTR_MODE: u32 = 0x1;
+const SETATTR_SIZE: u32 = 0x8;
+const AT_REMOVEDIR: u32 = 0x200;
+
+const S_IFMT: u32 = 0o170_000;
+const S_IFDIR: u32 = 0o040_000;
+const S_IFLNK: u32 = 0o120_000;
+
+/// Unique identification of a file on the server
+#[derive(Debug, Copy, Clone)]
+struct Qid {
+	#[allow(dead_code)]
+	ty: u8,
+	#[allow(dead_code)]
+	version: u32,
+	path: u64,
+}
+
+/// A request, which is built in memory that is accessible by the device
+struct Message {
+	buf: Vec<u8, DeviceAlloc>,
+	/// A string does not fit into the message.
+	overflow: bool,
+}
+
+impl Message {
+	fn new(ty: u8) -> Self {
+		let tag = if ty == TVERSION { NOTAG } else { 0 };
+		let mut buf = Vec::new_in(DeviceAlloc);
+		buf.extend_from_slice(&[0; 4]);
+		buf.push(ty);
+		buf.extend_from_slice(&tag.to_le_bytes());
+		Self {
+			buf,
+			overflow: false,
+		}
+	}
+
+	fn ty(&self) -> u8 {
+		self.buf[4]
+	}
+
+	fn u16(mut self, value: u16) -> Self {
+		self.buf.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	fn u32(mut self, value: u32) -> Self {
+		self.buf.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	fn u64(mut self, value: u64) -> Self {
+		self.buf.extend_from_slice(&value.to_le_bytes());
+		self
+	}
+
+	fn bytes(mut self, data: &[u8]) -> Self {
+		self.buf.extend_from_slice(data);
+		self
+	}
+
+	fn str(mut self, s: &str) -> Self {
+		match u16::try_from(s.len()) {
+			Ok(len) => self.u16(len).bytes(s.as_bytes()),
+			Err(_) => {
+				self.overflow = true;
+				self
+			}
+		}
+	}
+
+	fn finish(mut self, msize: u32) -> io::Result<Vec<u8, DeviceAlloc>> {
+		let size = u32::try_from(self.buf.len()).map_err(|_| Errno::Nametoolong)?;
+		if self.overflow || size > msize {
+			return Err(Errno::Nametoolong);
+		}
+		self.buf[..4].copy_from_slice(&size.to_le_bytes());
+		Ok(self.buf)
+	}
+}
+
+/// A response of the server
+struct Reader {
+	buf: Vec<u8, DeviceAlloc>,
+	pos: usize,
+}
+
+impl Reader {
+	fn bytes(&mut self, len: usize) -> io::Result<&[u8]> {
+		let data = self.buf.get(self.pos..self.pos + len).ok_or(Errno::Io)?;
+		self.pos += len;
+		Ok(data)
+	}
+
+	fn u8(&mut self) -> io::Result<u8> {
+		Ok(self.bytes(1)?[0])
+	}
+
+	fn u16(&mut self) -> io::Result<u16> {
+		Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+	}
+
+	fn u32(&mut self) -> io::Result<u32> {
+		Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+	}
+
+	fn u64(&mut self) -> io::Result<u64> {
+		Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+	}
+
+	fn str(&mut self) -> io::Result<String> {
+		let len = self.u16()?.into();
+		Ok(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+	}
+
+	fn qid(&mut self) -> io::Result<Qid> {
+		Ok(Qid {
+			ty: self.u8()?,
+			version: self.u32()?,
+			path: self.u64()?,
+		})
+	}
+
+	fn timespec(&mut self) -> io::Result<timespec> {
+		let tv_sec = self.u64()?.cast_signed();
+		let tv_nsec = self.u64()?.try_into().map_err(|_| Errno::Io)?;
+		Ok(timespec { tv_sec, tv_nsec })
+	}
+}
+
+/// Session with the server of a device
+struct Client {
+	driver: &'static InterruptTicketMutex<Virtio9pDriver>,
+	/// Negotiated maximal size of a message
+	msize: u32,
+	next_fid: AtomicU32,
+}
+
+impl fmt::Debug for Client {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Client")
+			.field("msize", &self.msize)
+			.finish_non_exhaustive()
+	}
+}
+
+impl Client {
+	fn new(driver: &'static InterruptTicketMutex<Virtio9pDriver>) -> io::Result<Self> {
+		let mut client = Self {
+			driver,
+			msize: MSIZE,
+			next_fid: AtomicU32::new(0),
+		};
+
+		let mut rsp = client.rpc(Message::new(TVERSION).u32(MSIZE).str(VERSION))?;
+		let msize = rsp.u32()?;
+		let version = rsp.str()?;
+		if version != VERSION || msize <= IOHDRSZ {
+			error!("9P server does not support {VERSION}, but {version}");
+			return Err(Errno::Proto);
+		}
+		client.msize = msize.min(MSIZE);
+
+		Ok(client)
+	}
+
+	/// Sends `msg` to the server and returns the response, if the request succeeded.
+	fn rpc(&self, msg: Message) -> io::Result<Reader> {
+		let ty = msg.ty();
+		let request = msg.finish(self.msize)?;
+		let buf = self.driver.lock().request(request, self.msize)?;
+
+		let mut rsp = Reader { buf, pos: 0 };
+		let size = rsp.u32()?;
+		let rsp_ty = rsp.u8()?;
+		let _tag = rsp.u16()?;
+		if usize::try_from(size).unwrap() > rsp.buf.len() {
+			return Err(Errno::Io);
+		}
+
+		match rsp_ty {
+			RLERROR => {
+				let ecode = rsp.u32()?;
+				Err(i32::try_from(ecode)
+					.ok()
+					.and_then(|ecode| Errno::try_from(ecode).ok())
+					.unwrap_or(Errno::Io))
+			}
+			_ if rsp_ty == ty + 1 => Ok(rsp),
+			_ => Err(Errno::Io),
+		}
+	}
+
+	fn alloc_fid(&self) -> u32 {
+		self.next_fid.fetch_add(1, Ordering::Relaxed)
+	}
+
+	/// Maximal number of bytes, which are transferred by a single `Tread` or `Twrite`
+	fn max_io(&self) -> usize {
+		(self.msize - IOHDRSZ).try_into().unwrap()
+	}
+
+	fn attach(self: &Arc<Self>) -> io::Result<Fid> {
+		let fid = self.alloc_fid();
+		self.rpc(
+			Message::new(TATTACH)
+				.u32(fid)
+				.u32(NOFID)
+				.str("root")
+				.str("")
+				.u32(0),
+		)?;
+		Ok(Fid {
+			client: self.clone(),
+			fid,
+		})
+	}
+
+	fn walk(&self, fid: u32, newfid: u32, names: &[&str]) -> io::Result<()> {
+		let mut msg = Message::new(TWALK)
+			.u32(fid)
+			.u32(newfid)
+			.u16(names.len().try_into().unwrap());
+		for name in names {
+			msg = msg.str(name);
+		}
+
+		let mut rsp = self.rpc(msg)?;
+		// The walk stops at the first name, which does not exist.
+		if usize::from(rsp.u16()?) != names.len() {
+			return Err(Errno::Noent);
+		}
+		Ok(())
+	}
+}
+
+/// Fid of the server, which is clunked, when it is dropped
+#[derive(Debug)]
+struct Fid {
+	client: Arc<Client>,
+	fid: u32,
+}
+
+impl Fid {
+	/// Returns a new fid for the file, which is reached by walking `names` from this fid.
+	fn walk(&self, names: &[&str]) -> io::Result<Fid> {
+		let newfid = self.client.alloc_fid();
+		let mut chunks = names.chunks(MAXWELEM);
+		self.client
+			.walk(self.fid, newfid, chunks.next().unwrap_or(&[]))?;
+		let fid = Fid {
+			client: self.client.clone(),
+			fid: newfid,
+		};
+
+		for chunk in chunks {
+			self.client.walk(newfid, newfid, chunk)?;
+		}
+		Ok(fid)
+	}
+
+	fn lopen(&self, flags: u32) -> io::Result<()> {
+		self.client
+			.rpc(Message::new(TLOPEN).u32(self.fid).u32(flags))?;
+		Ok(())
+	}
+
+	/// Creates and opens the file `name` in this directory. Afterwards, the fid
+	/// refers to the new file.
+	fn lcreate(&self, name: &str, flags: u32, mode: u32) -> io::Result<()> {
+		self.client.rpc(
+			Message::new(TLCREATE)
+				.u32(self.fid)
+				.str(name)
+				.u32(flags)
+				.u32(mode)
+				.u32(0),
+		)?;
+		Ok(())
+	}
+
+	fn getattr(&self) -> io::Result<FileAttr> {
+		let mut rsp = self
+			.client
+			.rpc(Message::new(TGETATTR).u32(self.fid).u64(GETATTR_BASIC))?;
+		let _valid = rsp.u64()?;
+		let qid = rsp.qid()?;
+		let mode = rsp.u32()?;
+		let uid = rsp.u32()?;
+		let gid = rsp.u32()?;
+		let nlink = rsp.u64()?;
+		let rdev = rsp.u64()?;
+		let size = rsp.u64()?;
+		let blksize = rsp.u64()?;
+		let blocks = rsp.u64()?;
+
+		Ok(FileAttr {
+			st_ino: qid.path,
+			st_nlink: nlink,
+			st_mode: AccessPermission::from_bits_retain(mode),
+			st_uid: uid,
+			st_gid: gid,
+			st_rdev: rdev,
+			st_size: size.cast_signed(),
+			st_blksize: blksize.cast_signed(),
+			st_blocks: blocks.cast_signed(),
+			st_atim: rsp.timespec()?,
+			st_mtim: rsp.timespec()?,
+			st_ctim: rsp.timespec()?,
+			..Default::default()
+		})
+	}
+
+	fn statfs(&self) -> io::Result<StatFs> {
+		let mut rsp = self.client.rpc(Message::new(TSTATFS).u32(self.fid))?;
+		let _type = rsp.u32()?;
+		let bsize = rsp.u32()?;
+		let blocks = rsp.u64()?;
+		let bfree = rsp.u64()?;
+		let bavail = rsp.u64()?;
+		let files = rsp.u64()?;
+		let ffree = rsp.u64()?;
+		let _fsid = rsp.u64()?;
+		let namelen = rsp.u32()?;
+
+		Ok(StatFs {
+			f_type: V9FS_MAGIC,
+			f_bsize: bsize.into(),
+			f_blocks: blocks,
+			f_bfree: bfree,
+			f_bavail: bavail,
+			f_files: files,
+			f_ffree: ffree,
+			f_namelen: namelen.into(),
+			..Default::default()
+		})
+	}
+
+	fn setattr(&self, valid: u32, mode: u32, size: u64) -> io::Result<()> {
+		self.client.rpc(
+			Message::new(TSETATTR)
+				.u32(self.fid)
+				.u32(valid)
+				.u32(mode)
+				.u32(0)
+				.u32(0)
+				.u64(size)
+				.u64(0)
+				.u64(0)
+				.u64(0)
+				.u64(0),
+		)?;
+		Ok(())
+	}
+
+	fn read(&self, buf: &mut [u8], offset: usize) -> io::Result<usize> {
+		let mut filled = 0;
+		while filled < buf.len() {
+			let count = (buf.len() - filled).min(self.client.max_io());
+			let mut rsp = self.client.rpc(
+				Message::new(TREAD)
+					.u32(self.fid)
+					.u64((offset + filled).try_into().unwrap())
+					.u32(count.try_into().unwrap()),
+			)?;
+			let len = usize::try_from(rsp.u32()?).unwrap();
+			if len > count {
+				return Err(Errno::Io);
+			}
+			buf[filled..filled + len].copy_from_slice(rsp.bytes(len)?);
+			filled += len;
+			if len < count {
+				break;
+			}
+		}
+		Ok(filled)
+	}
+
+	fn write(&self, buf: &[u8], offset: usize) -> io::Result<usize> {
+		let mut written = 0;
+		while written < buf.len() {
+			let count = (buf.len() - written).min(self.client.max_io());
+			let mut rsp = self.client.rpc(
+				Message::new(TWRITE)
+					.u32(self.fid)
+					.u64((offset + written).try_into().unwrap())
+					.u32(count.try_into().unwrap())
+					.bytes(&buf[written..written + count]),
+			)?;
+			let len = usize::try_from(rsp.u32()?).unwrap();
+			written += len.min(count);
+			if len < count {
+				break;
+			}
+		}
+		Ok(written)
+	}
+
+	/// Reads the entries of this directory, which has to be opened.
+	fn readdir(&self) -> io::Result<Vec<(String, u64, FileType)>> {
+		let mut entries = Vec::new();
+		let mut offset = 0;
+		loop {
+			let mut rsp = self.client.rpc(
+				Message::new(TREADDIR)
+					.u32(self.fid)
+					.u64(offset)
+					.u32(self.client.max_io().try_into().unwrap()),
+			)?;
+			let count = usize::try_from(rsp.u32()?).unwrap();
+			if count == 0 {
+				return Ok(entries);
+			}
+
+			let end = rsp.pos + count;
+			while rsp.pos < end {
+				let qid = rsp.qid()?;
+				offset = rsp.u64()?;
+				let ty = FileType::try_from(rsp.u8()?).unwrap_or(FileType::Unknown);
+				let name = rsp.str()?;
+				if name != "." && name != ".." {
+					entries.push((name, qid.path, ty));
+				}
+			}
+		}
+	}
+
+	fn readlink(&self) -> io::Result<String> {
+		self.client
+			.rpc(Message::new(TREADLINK).u32(self.fid))?
+			.str()
+	}
+
+	fn fsync(&self) -> io::Result<()> {
+		self.client.rpc(Message::new(TFSYNC).u32(self.fid).u32(0))?;
+		Ok(())
+	}
+
+	fn mkdir(&self, name: &str, mode: u32) -> io::Result<()> {
+		self.client.rpc(
+			Message::new(TMKDIR)
+				.u32(self.fid)
+				.str(name)
+				.u32(mode)
+				.u32(0),
+		)?;
+		Ok(())
+	}
+
+	fn symlink(&self, name: &str, target: &str) -> io::Result<()> {
+		self.client.rpc(
+			Message::new(TSYMLINK)
+				.u32(self.fid)
+				.str(name)
+				.str(target)
+				.u32(0),
+		)?;
+		Ok(())
+	}
+
+	/// Renames the entry `name` of this directory to `newname` of the directory `newdir`.
+	fn renameat(&self, name: &str, newdir: &Fid, newname: &str) -> io::Result<()> {
+		self.client.rpc(
+			Message::new(TRENAMEAT)
+				.u32(self.fid)
+				.str(name)
+				.u32(newdir.fid)
+				.str(newname),
+		)?;
+		Ok(())
+	}
+
+	fn unlinkat(&self, name: &str, flags: u32) -> io::Result<()> {
+		self.client
+			.rpc(Message::new(TUNLINKAT).u32(self.fid).str(name).u32(flags))?;
+		Ok(())
+	}
+}
+
+impl Drop for Fid {
+	fn drop(&mut self) {
+		if let Err(err) = self.client.rpc(Message::new(TCLUNK).u32(self.fid)) {
+			warn!("Unable to clunk 9P fid {}: {err:?}", self.fid);
+		}
+	}
+}
+
+/// Converts the options of `open` into the flags of `Tlopen` and `Tlcreate`.
+fn open_flags(opt: OpenOption) -> u32 {
+	(opt & (OpenOption::O_WRONLY
+		| OpenOption::O_RDWR
+		| OpenOption::O_TRUNC
+		| OpenOption::O_APPEND
+		| OpenOption::O_DSYNC
+		| OpenOption::O_SYNC))
+		.bits()
+		.cast_unsigned()
+}
+
+#[derive(Debug)]
+struct P9FileInterface {
+	fid: Fid,
+	/// Position within the file
+	pos: Mutex<usize>,
+	/// Writes append to the end of the file
+	append: bool,
+}
+
+impl P9FileInterface {
+	fn size(&self) -> io::Result<usize> {
+		usize::try_from(self.fid.getattr()?.st_size).map_err(|_| Errno::Io)
+	}
+}
+
+#[async_trait]
+impl ObjectInterface for P9FileInterface {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		Ok(event
+			& (PollEvent::POLLIN
+				| PollEvent::POLLRDNORM
+				| PollEvent::POLLRDBAND
+				| PollEvent::POLLOUT
+				| PollEvent::POLLWRNORM
+				| PollEvent::POLLWRBAND))
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		let mut pos = self.pos.lock().await;
+		let len = self.fid.read(buf, *pos)?;
+		*pos += len;
+		Ok(len)
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		let mut pos = self.pos.lock().await;
+		if self.append {
+			*pos = self.size()?;
+		}
+		let len = self.fid.write(buf, *pos)?;
+		*pos += len;
+		Ok(len)
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		let mut pos = self.pos.lock().await;
+
+		let new_pos = match whence {
+			SeekWhence::Set => offset,
+			SeekWhence::Cur => isize::try_from(*pos).unwrap() + offset,
+			SeekWhence::End => isize::try_from(self.size()?).unwrap() + offset,
+			_ => return Err(Errno::Inval),
+		};
+		if new_pos < 0 {
+			return Err(Errno::Inval);
+		}
+
+		*pos = new_pos.try_into().unwrap();
+		Ok(new_pos)
+	}
+
+	async fn pread(&self, buf: &mut [u8], offset: usize) -> io::Result<usize> {
+		self.fid.read(buf, offset)
+	}
+
+	async fn pwrite(&self, buf: &[u8], offset: usize) -> io::Result<usize> {
+		self.fid.write(buf, offset)
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		self.fid.getattr()
+	}
+
+	async fn truncate(&self, size: usize) -> io::Result<()> {
+		self.fid.setattr(SETATTR_SIZE, 0, size.try_into().unwrap())
+	}
+
+	async fn chmod(&self, access_permission: AccessPermission) -> io::Result<()> {
+		self.fid
+			.setattr(SETATTR_MODE, access_permission.bits() & 0o7777, 0)
+	}
+
+	async fn fsync(&self) -> io::Result<()> {
+		self.fid.fsync()
+	}
+}
+
+#[derive(Debug)]
+struct P9DirectoryInterface {
+	fid: Fid,
+	entries: Vec<(String, u64, FileType)>,
+	read_idx: Mutex<usize>,
+}
+
+#[async_trait]
+impl ObjectInterface for P9DirectoryInterface {
+	async fn getdents(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+		let mut read_idx = self.read_idx.lock().await;
+		write_dirents(
+			buf,
+			&mut read_idx,
+			self.entries
+				.iter()
+				.map(|(name, ino, ty)| (name.as_str(), *ino, *ty)),
+		)
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		seek_dir(&mut *self.read_idx.lock().await, offset, whence)
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		self.fid.getattr()
+	}
+}
+
+/// Mount point of a 9P file system
+#[derive(Debug)]
+pub(crate) struct P9Directory {
+	root: Fid,
+}
+
+impl P9Directory {
+	/// Returns a fid for the reversed path `components` of the VFS.
+	fn lookup(&self, components: &[&str], follow: bool) -> io::Result<Fid> {
+		let mut path = components
+			.iter()
+			.rev()
+			.map(|&name| name.to_owned())
+			.collect::<Vec<_>>();
+
+		for _ in 0..MAX_SYMLINKS {
+			let names = path.iter().map(String::as_str).collect::<Vec<_>>();
+			let fid = self.root.walk(&names)?;
+			if !follow || path.is_empty() || fid.getattr()?.st_mode.bits() & S_IFMT != S_IFLNK {
+				return Ok(fid);
+			}
+
+			let target = fid.readlink()?;
+			if target.starts_with('/') {
+				return Err(Errno::Noent);
+			}
+			path.pop();
+			for name in target.split('/') {
+				match name {
+					"" | "." => {}
+					".." => {
+						path.pop();
+					}
+					name => path.push(name.to_owned()),
+				}
+			}
+		}
+
+		Err(Errno::Loop)
+	}
+
+	/// Returns the last name of the reversed path `components` and a fid for its directory.
+	fn lookup_parent<'a>(&self, components: &[&'a str]) -> io::Result<(&'a str, Fid)> {
+		let (name, parent) = components.split_first().ok_or(Errno::Inval)?;
+		Ok((name, self.lookup(parent, true)?))
+	}
+
+	fn directory_object(fid: Fid) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		fid.lopen(OpenOption::O_DIRECTORY.bits().cast_unsigned())?;
+		Ok(Arc::new(async_lock::RwLock::new(P9DirectoryInterface {
+			entries: fid.readdir()?,
+			fid,
+			read_idx: Mutex::new(0),
+		})))
+	}
+
+	fn file_object(fid: Fid, opt: OpenOption) -> Arc<async_lock::RwLock<dyn ObjectInterface>> {
+		Arc::new(async_lock::RwLock::new(P9FileInterface {
+			fid,
+			pos: Mutex::new(0),
+			append: opt.contains(OpenOption::O_APPEND),
+		}))
+	}
+}
+
+impl VfsNode for P9Directory {
+	fn get_kind(&self) -> NodeKind {
+		NodeKind::Directory
+	}
+
+	fn get_file_attributes(&self) -> io::Result<FileAttr> {
+		self.root.getattr()
+	}
+
+	fn get_object(&self) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		Self::directory_object(self.root.walk(&[])?)
+	}
+
+	fn traverse_mkdir(&self, components: &mut Vec<&str>, mode: AccessPermission) -> io::Result<()> {
+		let (name, parent) = self.lookup_parent(components)?;
+		parent.mkdir(name, mode.bits() & 0o7777)
+	}
+
+	fn traverse_rmdir(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		let (name, parent) = self.lookup_parent(components)?;
+		parent.unlinkat(name, AT_REMOVEDIR)
+	}
+
+	fn traverse_unlink(&self, components: &mut Vec<&str>) -> io::Result<()> {
+		let (name, parent) = self.lookup_parent(components)?;
+		parent.unlinkat(name, 0)
+	}
+
+	fn traverse_readdir(&self, components: &mut Vec<&str>) -> io::Result<Vec<DirectoryEntry>> {
+		let fid = self.lookup(components, true)?;
+		fid.lopen(OpenOption::O_DIRECTORY.bits().cast_unsigned())?;
+		Ok(fid
+			.readdir()?
+			.into_iter()
+			.map(|(name, _, _)| DirectoryEntry::new(name))
+			.collect())
+	}
+
+	fn traverse_lstat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		self.lookup(components, false)?.getattr()
+	}
+
+	fn traverse_stat(&self, components: &mut Vec<&str>) -> io::Result<FileAttr> {
+		self.lookup(components, true)?.getattr()
+	}
+
+	fn traverse_statfs(&self, _components: &mut Vec<&str>) -> io::Result<StatFs> {
+		self.root.statfs()
+	}
+
+	/// The server replaces `new` atomically. Exchanging two entries is not
+	/// supported by the protocol and `RENAME_NOREPLACE` is checked before the
+	/// rename, so it does not prevent races with the host.
+	fn traverse_rename(
+		&self,
+		old: &mut Vec<&str>,
+		new: &mut Vec<&str>,
+		flags: RenameFlags,
+	) -> io::Result<()> {
+		if flags.contains(RenameFlags::RENAME_EXCHANGE) {
+			return Err(Errno::Inval);
+		}
+
+		let (old_name, old_parent) = self.lookup_parent(old)?;
+		let (new_name, new_parent) = self.lookup_parent(new)?;
+		if flags.contains(RenameFlags::RENAME_NOREPLACE) {
+			match new_parent.walk(&[new_name]) {
+				Ok(_) => return Err(Errno::Exist),
+				Err(Errno::Noent) => {}
+				Err(err) => return Err(err),
+			}
+		}
+
+		old_parent.renameat(old_name, &new_parent, new_name)
+	}
+
+	fn traverse_symlink(&self, components: &mut Vec<&str>, target: &str) -> io::Result<()> {
+		let (name, parent) = self.lookup_parent(components)?;
+		parent.symlink(name, target)
+	}
+
+	fn traverse_readlink(&self, components: &mut Vec<&str>) -> io::Result<String> {
+		self.lookup(components, false)?.readlink()
+	}
+
+	fn traverse_open(
+		&self,
+		components: &mut Vec<&str>,
+		opt: OpenOption,
+		mode: AccessPermission,
+	) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
+		let fid = match self.lookup(components, true) {
+			Ok(_) if opt.contains(OpenOption::O_CREAT | OpenOption::O_EXCL) => {
+				return Err(Errno::Exist);
+			}
+			Ok(fid) => fid,
+			Err(Errno::Noent) if opt.contains(OpenOption::O_CREAT) => {
+				let (name, fid) = self.lookup_parent(components)?;
+				fid.lcreate(name, open_flags(opt), mode.bits() & 0o7777)?;
+				return Ok(Self::file_object(fid, opt));
+			}
+			Err(err) => return Err(err),
+		};
+
+		if fid.getattr()?.st_mode.bits() & S_IFMT == S_IFDIR {
+			if opt.intersects(OpenOption::O_WRONLY | OpenOption::O_RDWR) {
+				return Err(Errno::Isdir);
+			}
+			return Self::directory_object(fid);
+		}
+		if opt.contains(OpenOption::O_DIRECTORY) {
+			return Err(Errno::Notdir);
+		}
+
+		fid.lopen(open_flags(opt))?;
+		Ok(Self::file_object(fid, opt))
+	}
+}
+
+/// Sessions with the servers of all devices, which have been mounted
+static CLIENTS: InterruptTicketMutex<Vec<Arc<Client>>> = InterruptTicketMutex::new(Vec::new());
+
+/// Opens the file system of the virtio-9p device with the mount tag `tag`.
+pub(crate) fn open(tag: &str) -> io::Result<Box<dyn VfsNode + Send + Sync>> {
+	let driver = virtio_9p::get_driver(tag).ok_or(Errno::Nodev)?;
+
+	// `Tversion` aborts all outstanding fids, so there is a single session per device.
+	let mut clients = CLIENTS.lock();
+	let client = if let Some(client) = clients
+		.iter()
+		.find(|client| core::ptr::eq(client.driver, driver))
+	{
+		client.clone()
+	} else {
+		let client = Arc::new(Client::new(driver)?);
+		info!(
+			"9P session with {tag}, messages of up to {} bytes",
+			client.msize
+		);
+		clients.push(client.clone());
+		client
+	};
+	drop(clients);
+
+	Ok(Box::new(P9Directory {
+		root: client.attach()?,
+	}))
+}
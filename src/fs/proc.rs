@@ -0,0 +1,215 @@
+//! Kernel statistics in `/proc`.
+//!
+//! The files follow the format of Linux' procfs where possible, so that
+//! tools and libraries, which parse procfs, work unchanged. Their content is
+//! generated whenever they are opened.
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use embedded_io::Write as _;
+
+use crate::fd::AccessPermission;
+use crate::fs::{File, Filesystem};
+use crate::scheduler;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const UTC_BUILT_TIME: &str = build_time::build_time_utc!();
+
+/// Kernel statistics and the functions, which generate them
+const FILES: &[(&str, fn() -> String)] = &[
+	("/proc/uptime", uptime),
+	("/proc/loadavg", loadavg),
+	("/proc/meminfo", meminfo),
+	("/proc/pressure/memory", crate::mm::pressure::proc_pressure),
+	("/proc/interrupts", crate::interrupts::proc_interrupts),
+	("/proc/tasks", tasks),
+	("/proc/mmio", crate::mm::mmio::proc_mmio),
+	("/proc/console", crate::console::proc_console),
+	#[cfg(feature = "blktrace")]
+	(
+		"/proc/blocktrace",
+		crate::drivers::block::trace::proc_blocktrace,
+	),
+	#[cfg(feature = "net")]
+	("/proc/net/dev", net_dev),
+	#[cfg(feature = "net")]
+	("/proc/net/route", net_route),
+	#[cfg(feature = "net")]
+	(
+		"/proc/net/sockstat",
+		crate::executor::socket_buffer::proc_sockstat,
+	),
+];
+
+/// Seconds since boot and the time, which the cores have spent idle.
+///
+/// Idle time is not accounted and always reported as zero.
+fn uptime() -> String {
+	let micros = crate::arch::processor::get_timer_ticks();
+	format!(
+		"{}.{:02} 0.00\n",
+		micros / 1_000_000,
+		micros % 1_000_000 / 10_000
+	)
+}
+
+/// Load averages, the number of runnable and all tasks, and the last task ID.
+fn loadavg() -> String {
+	use crate::scheduler::load::FSHIFT;
+
+	let (loads, runnable) = scheduler::load::total();
+	let mut s = String::new();
+	for load in loads {
+		// round to two decimal places
+		let load = load + (1 << FSHIFT) / 200;
+		let fraction = ((load & ((1 << FSHIFT) - 1)) * 100) >> FSHIFT;
+		write!(s, "{}.{fraction:02} ", load >> FSHIFT).unwrap();
+	}
+	let last_id = scheduler::last_task_id().map_or(0, |id| id.into());
+	writeln!(s, "{runnable}/{} {last_id}", scheduler::task_count()).unwrap();
+	s
+}
+
+fn meminfo() -> String {
+	let total = crate::mm::physicalmem::total_memory_size() / 1024;
+	let free = crate::mm::physicalmem::free_memory_size() / 1024;
+
+	let mut s = String::new();
+	writeln!(s, "MemTotal:       {total:>8} kB").unwrap();
+	writeln!(s, "MemFree:        {free:>8} kB").unwrap();
+	writeln!(s, "MemAvailable:   {free:>8} kB").unwrap();
+	s
+}
+
+/// Lists all tasks with their priority and the core they have been assigned to.
+fn tasks() -> String {
+	let mut s = String::from("  TID PRIO CORE\n");
+	for task in scheduler::tasks() {
+		#[cfg(feature = "smp")]
+		let core_id = task.get_core_id();
+		#[cfg(not(feature = "smp"))]
+		let core_id = 0;
+		writeln!(
+			s,
+			"{:>5} {:>4} {core_id:>4}",
+			task.get_id(),
+			task.get_priority()
+		)
+		.unwrap();
+	}
+	s
+}
+
+#[cfg(feature = "net")]
+fn net_dev() -> String {
+	use crate::drivers::net::{INTERFACE_NAME, STATISTICS};
+
+	let (rx_packets, rx_bytes) = STATISTICS.rx();
+	let (tx_packets, tx_bytes) = STATISTICS.tx();
+
+	let mut s = String::new();
+	writeln!(
+		s,
+		"Inter-|   Receive                                                |  Transmit"
+	)
+	.unwrap();
+	writeln!(
+		s,
+		" face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed"
+	)
+	.unwrap();
+	writeln!(
+		s,
+		"{INTERFACE_NAME:>6}: {rx_bytes:>7} {rx_packets:>7}    0    0    0     0          0         0 {tx_bytes:>8} {tx_packets:>7}    0    0    0     0       0          0"
+	)
+	.unwrap();
+
+	// Without a network card, the primary interface is the loopback device.
+	#[cfg(any(
+		all(target_arch = "riscv64", feature = "gem-net", not(feature = "pci")),
+		all(target_arch = "x86_64", feature = "rtl8139"),
+		feature = "e1000",
+		feature = "virtio-net",
+	))]
+	{
+		let (rx_packets, rx_bytes) = crate::drivers::net::loopback::STATISTICS.rx();
+		let (tx_packets, tx_bytes) = crate::drivers::net::loopback::STATISTICS.tx();
+		writeln!(
+			s,
+			"    lo: {rx_bytes:>7} {rx_packets:>7}    0    0    0     0          0         0 {tx_bytes:>8} {tx_packets:>7}    0    0    0     0       0          0"
+		)
+		.unwrap();
+	}
+	s
+}
+
+/// Routing table including the subnets of the interfaces, whose gateway is `-`.
+///
+/// Unlike Linux, the addresses are not encoded in hexadecimal, because the
+/// table also contains IPv6 routes.
+#[cfg(feature = "net")]
+fn net_route() -> String {
+	use alloc::string::ToString;
+
+	use crate::executor::network::NIC;
+	use crate::executor::route::interface_name;
+
+	let mut s = String::new();
+	writeln!(s, "{:<43} {:<39} Iface", "Destination", "Gateway").unwrap();
+
+	let mut guard = NIC.lock();
+	let Ok(nic) = guard.as_nic_mut() else {
+		return s;
+	};
+	for route in nic.routes() {
+		let gateway = route
+			.gateway
+			.map_or_else(|| String::from("-"), |gateway| gateway.to_string());
+		writeln!(
+			s,
+			"{:<43} {gateway:<39} {}",
+			route.destination.to_string(),
+			interface_name(route.interface)
+		)
+		.unwrap();
+	}
+	s
+}
+
+/// Generates the content of the file `path`, e.g., for the debug shell.
+#[cfg(any(feature = "shell", feature = "sysrq"))]
+pub(crate) fn generate(path: &str) -> Option<String> {
+	FILES
+		.iter()
+		.find(|(name, _)| *name == path)
+		.map(|(_, generate)| generate())
+}
+
+/// Creates `/proc` and its files.
+pub(crate) fn init(fs: &Filesystem) {
+	let mode = AccessPermission::from_bits(0o777).unwrap();
+	fs.mkdir("/proc", mode).expect("Unable to create /proc");
+	fs.mkdir("/proc/pressure", mode)
+		.expect("Unable to create /proc/pressure");
+	#[cfg(feature = "net")]
+	fs.mkdir("/proc/net", mode)
+		.expect("Unable to create /proc/net");
+
+	if let Ok(mut file) = File::create("/proc/version") {
+		if write!(file, "HermitOS version {VERSION} # UTC {UTC_BUILT_TIME}").is_err() {
+			error!("Unable to write in /proc/version");
+		}
+	} else {
+		error!("Unable to create /proc/version");
+	}
+
+	for (path, generate) in FILES {
+		if fs
+			.create_generated_file(path, *generate, AccessPermission::from_bits(0o444).unwrap())
+			.is_err()
+		{
+			error!("Unable to create {path}");
+		}
+	}
+}
@@ -0,0 +1,483 @@
+//! Notifications about changes of files and directories, similar to `inotify`.
+//!
+//! A watch is added for a path, which is resolved when the watch is added.
+//! If the path is a directory, events of its entries are reported with the
+//! name of the entry. Events of the watched path itself are reported without
+//! a name. The events are read from the descriptor of the watches in the
+//! format of `struct inotify_event`.
+//!
+//! Events are generated by the VFS, so that they are reported for all file
+//! systems. However, only changes made by Hermit are visible. Changes of a
+//! virtio-fs directory by the host are not reported. Modifications through
+//! file descriptors are only reported, if the file has been opened after its
+//! path or its directory has been watched.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::future;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicI32, Ordering};
+use core::task::{Poll, Waker};
+
+use async_trait::async_trait;
+use hermit_sync::InterruptTicketMutex;
+
+use crate::errno::Errno;
+use crate::fd::{
+	self, AccessPermission, FallocateMode, FileDescriptor, ObjectInterface, PollEvent, StatusFlags,
+};
+use crate::fs::{FILESYSTEM, FileAttr, FileTimes, SeekWhence, with_relative_filename};
+use crate::io;
+
+bitflags! {
+	/// Events of a watch
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub struct WatchMask: u32 {
+		/// A file has been written or truncated.
+		const IN_MODIFY = 0x0000_0002;
+		/// An entry has been created in a directory.
+		const IN_CREATE = 0x0000_0100;
+		/// An entry has been removed from a directory.
+		const IN_DELETE = 0x0000_0200;
+		/// The watched path itself has been removed.
+		const IN_DELETE_SELF = 0x0000_0400;
+		/// Events have been dropped, because too many events were queued.
+		const IN_Q_OVERFLOW = 0x0000_4000;
+		/// The watch has been removed.
+		const IN_IGNORED = 0x0000_8000;
+		/// The subject of the event is a directory.
+		const IN_ISDIR = 0x4000_0000;
+	}
+}
+
+impl WatchMask {
+	/// Events, which can be requested by a watch
+	const EVENTS: Self = Self::IN_MODIFY
+		.union(Self::IN_CREATE)
+		.union(Self::IN_DELETE)
+		.union(Self::IN_DELETE_SELF);
+}
+
+/// Maximal number of queued events of a descriptor
+const MAX_QUEUED_EVENTS: usize = 16384;
+/// Size of `struct inotify_event` without the name
+const EVENT_HEADER_SIZE: usize = 16;
+
+#[derive(Debug, PartialEq, Eq)]
+struct Event {
+	wd: i32,
+	mask: WatchMask,
+	name: String,
+}
+
+impl Event {
+	/// Length of the name, including the terminating null byte and padding
+	fn name_len(&self) -> usize {
+		if self.name.is_empty() {
+			0
+		} else {
+			(self.name.len() + 1).next_multiple_of(4)
+		}
+	}
+
+	fn len(&self) -> usize {
+		EVENT_HEADER_SIZE + self.name_len()
+	}
+
+	fn write(&self, buf: &mut [u8]) {
+		let name_len = self.name_len();
+		buf[0..4].copy_from_slice(&self.wd.to_ne_bytes());
+		buf[4..8].copy_from_slice(&self.mask.bits().to_ne_bytes());
+		// Renames are reported as removal and creation, so the cookie is always zero.
+		buf[8..12].copy_from_slice(&0u32.to_ne_bytes());
+		buf[12..16].copy_from_slice(&u32::try_from(name_len).unwrap().to_ne_bytes());
+
+		let name = &mut buf[EVENT_HEADER_SIZE..EVENT_HEADER_SIZE + name_len];
+		name.fill(0);
+		name[..self.name.len()].copy_from_slice(self.name.as_bytes());
+	}
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+	events: VecDeque<Event>,
+	wakers: VecDeque<Waker>,
+}
+
+impl Queue {
+	fn push(&mut self, event: Event) {
+		// Like `inotify`, identical events in a row are merged.
+		if self.events.back() == Some(&event) {
+			return;
+		}
+
+		if self.events.len() >= MAX_QUEUED_EVENTS {
+			if self
+				.events
+				.back()
+				.is_some_and(|event| event.mask == WatchMask::IN_Q_OVERFLOW)
+			{
+				return;
+			}
+			self.events.push_back(Event {
+				wd: -1,
+				mask: WatchMask::IN_Q_OVERFLOW,
+				name: String::new(),
+			});
+		} else {
+			self.events.push_back(event);
+		}
+
+		for waker in self.wakers.drain(..) {
+			waker.wake();
+		}
+	}
+}
+
+/// State of a descriptor of watches
+#[derive(Debug)]
+struct Instance {
+	queue: InterruptTicketMutex<Queue>,
+	next_wd: AtomicI32,
+}
+
+#[derive(Debug)]
+struct Watch {
+	wd: i32,
+	/// Resolved absolute path
+	path: String,
+	mask: WatchMask,
+	instance: Weak<Instance>,
+}
+
+/// All watches, the watches of a closed descriptor are removed lazily.
+static WATCHES: InterruptTicketMutex<Vec<Watch>> = InterruptTicketMutex::new(Vec::new());
+/// Descriptors of watches, identified by the address of their object
+static INSTANCES: InterruptTicketMutex<Vec<(usize, Weak<Instance>)>> =
+	InterruptTicketMutex::new(Vec::new());
+
+type Object = Arc<async_lock::RwLock<dyn ObjectInterface>>;
+
+fn owner(object: &Object) -> usize {
+	Arc::as_ptr(object).cast::<()>() as usize
+}
+
+/// Splits the absolute path `path` into its directory and its name.
+fn split(path: &str) -> (&str, &str) {
+	match path.rsplit_once('/') {
+		Some(("", name)) => ("/", name),
+		Some((parent, name)) => (parent, name),
+		None => ("/", path),
+	}
+}
+
+/// Returns whether events of the absolute path `path` are reported.
+pub(super) fn is_watched(path: &str) -> bool {
+	let watches = WATCHES.lock();
+	if watches.is_empty() {
+		return false;
+	}
+
+	let (parent, _) = split(path);
+	watches
+		.iter()
+		.any(|watch| watch.path == path || watch.path == parent)
+}
+
+/// Reports the event `mask` of the absolute path `path`.
+pub(super) fn notify(path: &str, mask: WatchMask) {
+	let mut watches = WATCHES.lock();
+	if watches.is_empty() {
+		return;
+	}
+
+	let (parent, name) = split(path);
+	let events = mask.difference(WatchMask::IN_ISDIR);
+	watches.retain(|watch| {
+		let Some(instance) = watch.instance.upgrade() else {
+			return false;
+		};
+		let mut queue = instance.queue.lock();
+
+		if watch.path == parent && watch.mask.intersects(events) && path != "/" {
+			queue.push(Event {
+				wd: watch.wd,
+				mask,
+				name: name.into(),
+			});
+		}
+
+		if watch.path == path {
+			if mask.contains(WatchMask::IN_DELETE) {
+				if watch.mask.contains(WatchMask::IN_DELETE_SELF) {
+					queue.push(Event {
+						wd: watch.wd,
+						mask: WatchMask::IN_DELETE_SELF,
+						name: String::new(),
+					});
+				}
+				queue.push(Event {
+					wd: watch.wd,
+					mask: WatchMask::IN_IGNORED,
+					name: String::new(),
+				});
+				return false;
+			}
+
+			if watch.mask.intersects(events & WatchMask::IN_MODIFY) {
+				queue.push(Event {
+					wd: watch.wd,
+					mask,
+					name: String::new(),
+				});
+			}
+		}
+
+		true
+	});
+}
+
+/// Descriptor, from which the events of its watches are read
+#[derive(Debug)]
+struct FsWatch {
+	instance: Arc<Instance>,
+	nonblocking: bool,
+}
+
+#[async_trait]
+impl ObjectInterface for FsWatch {
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		future::poll_fn(|cx| {
+			let mut queue = self.instance.queue.lock();
+			let Some(first) = queue.events.front() else {
+				if self.nonblocking {
+					return Poll::Ready(Err(Errno::Again));
+				}
+				queue.wakers.push_back(cx.waker().clone());
+				return Poll::Pending;
+			};
+			if first.len() > buf.len() {
+				return Poll::Ready(Err(Errno::Inval));
+			}
+
+			let mut pos = 0;
+			while let Some(event) = queue.events.front()
+				&& pos + event.len() <= buf.len()
+			{
+				event.write(&mut buf[pos..]);
+				pos += event.len();
+				queue.events.pop_front();
+			}
+			Poll::Ready(Ok(pos))
+		})
+		.await
+	}
+
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		future::poll_fn(|cx| {
+			let mut queue = self.instance.queue.lock();
+			let available = if queue.events.is_empty() {
+				PollEvent::empty()
+			} else {
+				PollEvent::POLLIN | PollEvent::POLLRDNORM
+			};
+
+			let ret = event & available;
+			if ret.is_empty() {
+				queue.wakers.push_back(cx.waker().clone());
+				Poll::Pending
+			} else {
+				Poll::Ready(Ok(ret))
+			}
+		})
+		.await
+	}
+}
+
+/// Creates a new descriptor for watches.
+pub(crate) fn init(nonblocking: bool) -> io::Result<FileDescriptor> {
+	let instance = Arc::new(Instance {
+		queue: InterruptTicketMutex::new(Queue::default()),
+		next_wd: AtomicI32::new(1),
+	});
+	let object: Object = Arc::new(async_lock::RwLock::new(FsWatch {
+		instance: instance.clone(),
+		nonblocking,
+	}));
+
+	let mut instances = INSTANCES.lock();
+	instances.retain(|(_, instance)| instance.strong_count() > 0);
+	instances.push((owner(&object), Arc::downgrade(&instance)));
+	drop(instances);
+
+	fd::insert_object(object)
+}
+
+fn instance(fd: FileDescriptor) -> io::Result<Arc<Instance>> {
+	let object = fd::get_object(fd)?;
+	let owner = owner(&object);
+	INSTANCES
+		.lock()
+		.iter()
+		.find(|(addr, _)| *addr == owner)
+		.and_then(|(_, instance)| instance.upgrade())
+		.ok_or(Errno::Inval)
+}
+
+/// Watches `path` for the events `mask` and returns the watch descriptor.
+///
+/// If `path` is already watched by the descriptor `fd`, the mask of the
+/// existing watch is replaced.
+pub(crate) fn add(fd: FileDescriptor, path: &str, mask: WatchMask) -> io::Result<i32> {
+	let instance = instance(fd)?;
+	let mask = mask & WatchMask::EVENTS;
+	if mask.is_empty() {
+		return Err(Errno::Inval);
+	}
+
+	let path = with_relative_filename(path, |path| {
+		let fs = FILESYSTEM.get().ok_or(Errno::Inval)?;
+		let path = fs.resolve(path, true)?;
+		fs.stat(&path)?;
+		Ok(path)
+	})?;
+
+	let mut watches = WATCHES.lock();
+	if let Some(watch) = watches
+		.iter_mut()
+		.find(|watch| watch.path == path && Weak::as_ptr(&watch.instance) == Arc::as_ptr(&instance))
+	{
+		watch.mask = mask;
+		return Ok(watch.wd);
+	}
+
+	let wd = instance.next_wd.fetch_add(1, Ordering::Relaxed);
+	debug!("Watch {path} for {mask:?}");
+	watches.push(Watch {
+		wd,
+		path,
+		mask,
+		instance: Arc::downgrade(&instance),
+	});
+	Ok(wd)
+}
+
+/// Removes the watch `wd` of the descriptor `fd`.
+pub(crate) fn remove(fd: FileDescriptor, wd: i32) -> io::Result<()> {
+	let instance = instance(fd)?;
+
+	let mut watches = WATCHES.lock();
+	let index = watches
+		.iter()
+		.position(|watch| watch.wd == wd && Weak::as_ptr(&watch.instance) == Arc::as_ptr(&instance))
+		.ok_or(Errno::Inval)?;
+	watches.swap_remove(index);
+	drop(watches);
+
+	instance.queue.lock().push(Event {
+		wd,
+		mask: WatchMask::IN_IGNORED,
+		name: String::new(),
+	});
+	Ok(())
+}
+
+/// A file, which has been opened for writing, while it has been watched
+#[derive(Debug)]
+pub(super) struct WatchedFile {
+	inner: Object,
+	/// Resolved absolute path
+	path: String,
+}
+
+impl WatchedFile {
+	pub fn wrap(inner: Object, path: String) -> Object {
+		Arc::new(async_lock::RwLock::new(Self { inner, path }))
+	}
+
+	fn modified(&self) {
+		notify(&self.path, WatchMask::IN_MODIFY);
+	}
+}
+
+#[async_trait]
+impl ObjectInterface for WatchedFile {
+	async fn poll(&self, event: PollEvent) -> io::Result<PollEvent> {
+		self.inner.read().await.poll(event).await
+	}
+
+	async fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+		self.inner.read().await.read(buf).await
+	}
+
+	async fn write(&self, buf: &[u8]) -> io::Result<usize> {
+		let len = self.inner.read().await.write(buf).await?;
+		self.modified();
+		Ok(len)
+	}
+
+	async fn lseek(&self, offset: isize, whence: SeekWhence) -> io::Result<isize> {
+		self.inner.read().await.lseek(offset, whence).await
+	}
+
+	async fn pread(&self, buf: &mut [u8], offset: usize) -> io::Result<usize> {
+		self.inner.read().await.pread(buf, offset).await
+	}
+
+	async fn pwrite(&self, buf: &[u8], offset: usize) -> io::Result<usize> {
+		let len = self.inner.read().await.pwrite(buf, offset).await?;
+		self.modified();
+		Ok(len)
+	}
+
+	async fn fstat(&self) -> io::Result<FileAttr> {
+		self.inner.read().await.fstat().await
+	}
+
+	async fn getdents(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<usize> {
+		self.inner.read().await.getdents(buf).await
+	}
+
+	async fn status_flags(&self) -> io::Result<StatusFlags> {
+		self.inner.read().await.status_flags().await
+	}
+
+	async fn set_status_flags(&mut self, status_flags: StatusFlags) -> io::Result<()> {
+		self.inner
+			.write()
+			.await
+			.set_status_flags(status_flags)
+			.await
+	}
+
+	async fn truncate(&self, size: usize) -> io::Result<()> {
+		self.inner.read().await.truncate(size).await?;
+		self.modified();
+		Ok(())
+	}
+
+	async fn allocate(&self, mode: FallocateMode, offset: usize, len: usize) -> io::Result<()> {
+		self.inner.read().await.allocate(mode, offset, len).await?;
+		self.modified();
+		Ok(())
+	}
+
+	async fn fsync(&self) -> io::Result<()> {
+		self.inner.read().await.fsync().await
+	}
+
+	async fn chmod(&self, access_permission: AccessPermission) -> io::Result<()> {
+		self.inner.read().await.chmod(access_permission).await
+	}
+
+	async fn utimens(&self, times: FileTimes) -> io::Result<()> {
+		self.inner.read().await.utimens(times).await
+	}
+
+	async fn isatty(&self) -> io::Result<bool> {
+		self.inner.read().await.isatty().await
+	}
+}
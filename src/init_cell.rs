@@ -2,6 +2,7 @@
 	not(any(
 		feature = "vsock",
 		feature = "fuse",
+		feature = "virtio-9p",
 		feature = "console",
 		feature = "nvme"
 	)),
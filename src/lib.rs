@@ -76,11 +76,16 @@ pub mod fs;
 mod init_cell;
 pub mod io;
 pub mod mm;
+mod panic;
 pub mod scheduler;
 #[cfg(feature = "shell")]
 mod shell;
+mod shutdown;
+mod symbols;
 mod synch;
 pub mod syscalls;
+#[cfg(feature = "sysrq")]
+mod sysrq;
 pub mod time;
 
 mod built_info {
@@ -184,6 +189,7 @@ fn boot_processor_main() -> ! {
 	unsafe {
 		logging::init();
 	}
+	panic::init();
 
 	info!("Welcome to Hermit {}", env!("CARGO_PKG_VERSION"));
 	if let Some(git_version) = built_info::GIT_VERSION {
@@ -248,8 +254,11 @@ fn boot_processor_main() -> ! {
 			0,
 			scheduler::task::NORMAL_PRIO,
 			0,
+			false,
 			USER_STACK_SIZE,
+			scheduler::task::TaskId::MAX,
 		)
+		.expect("Unable to spawn initd")
 	};
 
 	// Run the scheduler loop.
@@ -280,5 +289,5 @@ fn panic(info: &core::panic::PanicInfo<'_>) -> ! {
 	let core_id = crate::arch::core_local::core_id();
 	panic_println!("[{core_id}][PANIC] {info}\n");
 
-	crate::scheduler::shutdown(1);
+	crate::panic::handle()
 }
@@ -1,8 +1,17 @@
+#[cfg(feature = "log-binary")]
+mod binary;
+#[cfg(feature = "klog")]
+pub(crate) mod klog;
+
+#[cfg(not(feature = "log-binary"))]
 use core::fmt;
 use core::sync::atomic::{AtomicBool, Ordering};
 
+#[cfg(not(feature = "log-binary"))]
 use anstyle::AnsiColor;
-use log::{Level, LevelFilter, Metadata, Record};
+#[cfg(not(feature = "log-binary"))]
+use log::Level;
+use log::{LevelFilter, Metadata, Record};
 
 pub static KERNEL_LOGGER: KernelLogger = KernelLogger::new();
 
@@ -41,6 +50,26 @@ impl log::Log for KernelLogger {
 			return;
 		}
 
+		#[cfg(feature = "log-net")]
+		crate::executor::netlog::push(record.level(), record.args());
+
+		#[cfg(feature = "klog")]
+		klog::push(record.level(), record.args());
+
+		#[cfg(feature = "log-binary")]
+		binary::log(
+			record,
+			crate::processor::get_timer_ticks(),
+			crate::arch::core_local::core_id(),
+		);
+		#[cfg(not(feature = "log-binary"))]
+		self.log_text(record);
+	}
+}
+
+impl KernelLogger {
+	#[cfg(not(feature = "log-binary"))]
+	fn log_text(&self, record: &Record<'_>) {
 		// FIXME: Use `super let` once stable
 		let time;
 		let format_time = if self.time() {
@@ -63,8 +92,10 @@ impl log::Log for KernelLogger {
 	}
 }
 
+#[cfg(not(feature = "log-binary"))]
 struct Microseconds(u64);
 
+#[cfg(not(feature = "log-binary"))]
 impl fmt::Display for Microseconds {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		let seconds = self.0 / 1_000_000;
@@ -73,8 +104,10 @@ impl fmt::Display for Microseconds {
 	}
 }
 
+#[cfg(not(feature = "log-binary"))]
 struct ColorLevel(Level);
 
+#[cfg(not(feature = "log-binary"))]
 impl fmt::Display for ColorLevel {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		let level = self.0;
@@ -96,6 +129,7 @@ impl fmt::Display for ColorLevel {
 	}
 }
 
+#[cfg(not(feature = "log-binary"))]
 fn no_color() -> bool {
 	option_env!("NO_COLOR").is_some_and(|val| !val.is_empty())
 }
@@ -0,0 +1,237 @@
+//! Compact binary encoding of kernel messages.
+//!
+//! Each message is written as a frame to the console:
+//!
+//! | Field   | Size | Content                                              |
+//! | ------- | ---- | ---------------------------------------------------- |
+//! | sync    | 2    | `0xff 0xb1`, which cannot occur in UTF-8 text        |
+//! | version | 1    | [`VERSION`]                                          |
+//! | length  | 2    | length of the payload (little endian)                |
+//! | payload | n    | see below                                            |
+//! | crc     | 4    | CRC-32 (IEEE) of version, length, and payload (LE)   |
+//!
+//! The payload consists of the timestamp in microseconds (`u64`), the level
+//! (`u8`, 1 = error to 5 = trace), the core ID (`u8`), the module ID (`u16`),
+//! the message ID (`u32`), and the arguments. All integers are little endian.
+//!
+//! The module ID is the 32-bit FNV-1a hash of the log target folded to 16 bits
+//! and the message ID is the 32-bit FNV-1a hash of `<file>:<line>` of the call
+//! site. A host-side decoder maps the IDs back to the module and the format
+//! string by hashing the call sites of the kernel sources in the same way.
+//! Messages without arguments are not formatted at all and have no arguments
+//! in the payload. `log` does not expose the arguments of a message separately,
+//! so messages with arguments carry the formatted message as UTF-8 text.
+//!
+//! Frames are interleaved with the text output of the application. A decoder
+//! scans for the sync bytes and drops frames with a wrong checksum.
+
+use core::fmt::{self, Write as _};
+
+use embedded_io::Write;
+use log::Record;
+
+/// Version of the frame format
+pub const VERSION: u8 = 1;
+
+const SYNC: [u8; 2] = [0xff, 0xb1];
+
+/// Maximum length of the formatted arguments
+const MAX_ARGS: usize = 512;
+
+/// Length of the fixed fields of the payload
+const HEADER_LEN: usize = 16;
+
+const CRC_TABLE: [u32; 256] = {
+	let mut table = [0; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut crc = i as u32;
+		let mut bit = 0;
+		while bit < 8 {
+			crc = if crc & 1 != 0 {
+				0xedb8_8320 ^ (crc >> 1)
+			} else {
+				crc >> 1
+			};
+			bit += 1;
+		}
+		table[i] = crc;
+		i += 1;
+	}
+	table
+};
+
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+	!data.iter().fold(!crc, |crc, &byte| {
+		CRC_TABLE[usize::from((crc as u8) ^ byte)] ^ (crc >> 8)
+	})
+}
+
+fn fnv1a(parts: &[&[u8]]) -> u32 {
+	parts
+		.iter()
+		.flat_map(|part| part.iter())
+		.fold(0x811c_9dc5, |hash, &byte| {
+			(hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+		})
+}
+
+struct Frame {
+	buf: [u8; SYNC.len() + 3 + HEADER_LEN + MAX_ARGS + 4],
+	len: usize,
+}
+
+impl fmt::Write for Frame {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		// keep room for the checksum
+		let len = s.len().min(self.buf.len() - 4 - self.len);
+		self.buf[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+		self.len += len;
+		Ok(())
+	}
+}
+
+impl Frame {
+	fn put(&mut self, bytes: &[u8]) {
+		self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+		self.len += bytes.len();
+	}
+}
+
+/// Writes `record` as frame to the console.
+pub fn log(record: &Record<'_>, timestamp: u64, core_id: u32) {
+	let frame = encode(record, timestamp, core_id);
+	let mut console = crate::console::CONSOLE.lock();
+	console.write_all(&frame.buf[..frame.len]).ok();
+	console.flush().ok();
+}
+
+/// Encodes `record` as frame.
+fn encode(record: &Record<'_>, timestamp: u64, core_id: u32) -> Frame {
+	let mut frame = Frame {
+		buf: [0; SYNC.len() + 3 + HEADER_LEN + MAX_ARGS + 4],
+		len: 0,
+	};
+	frame.put(&SYNC);
+	frame.put(&[VERSION, 0, 0]);
+
+	let target = record.target().as_bytes();
+	let module_id = fnv1a(&[target]);
+	let file = record.file().unwrap_or_default().as_bytes();
+	let line = record.line().unwrap_or_default();
+	let mut line_buf = [0u8; 10];
+	let line = format_u32(line, &mut line_buf);
+
+	frame.put(&timestamp.to_le_bytes());
+	frame.put(&[record.level() as u8, core_id as u8]);
+	frame.put(&(((module_id >> 16) ^ module_id) as u16).to_le_bytes());
+	frame.put(&fnv1a(&[file, b":", line]).to_le_bytes());
+
+	let args = record.args();
+	if args.as_str().is_none() {
+		let _ = write!(frame, "{args}");
+	}
+
+	let payload_len = u16::try_from(frame.len - SYNC.len() - 3).unwrap();
+	frame.buf[3..5].copy_from_slice(&payload_len.to_le_bytes());
+	let crc = crc32(0, &frame.buf[SYNC.len()..frame.len]);
+	frame.put(&crc.to_le_bytes());
+	frame
+}
+
+/// Formats `value` in decimal without allocating.
+fn format_u32(mut value: u32, buf: &mut [u8; 10]) -> &[u8] {
+	let mut start = buf.len();
+	loop {
+		start -= 1;
+		buf[start] = b'0' + (value % 10) as u8;
+		value /= 10;
+		if value == 0 {
+			break;
+		}
+	}
+	&buf[start..]
+}
+
+#[cfg(test)]
+mod tests {
+	use log::Level;
+
+	use super::*;
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_binlog_checksums() {
+		assert_eq!(crc32(0, b""), 0);
+		assert_eq!(crc32(0, b"123456789"), 0xcbf4_3926);
+		// The checksum can be continued.
+		assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xcbf4_3926);
+
+		assert_eq!(fnv1a(&[]), 0x811c_9dc5);
+		assert_eq!(fnv1a(&[b"a"]), 0xe40c_292c);
+		assert_eq!(fnv1a(&[b"foo", b"bar"]), fnv1a(&[b"foobar"]));
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_binlog_format_u32() {
+		let mut buf = [0; 10];
+		assert_eq!(format_u32(0, &mut buf), b"0");
+		assert_eq!(format_u32(42, &mut buf), b"42");
+		assert_eq!(format_u32(u32::MAX, &mut buf), b"4294967295");
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_binlog_frame() {
+		let frame = encode(
+			&Record::builder()
+				.args(format_args!("value {}", 7))
+				.level(Level::Warn)
+				.target("hermit::test")
+				.file(Some("src/test.rs"))
+				.line(Some(12))
+				.build(),
+			0x0102_0304_0506_0708,
+			3,
+		);
+		let frame = &frame.buf[..frame.len];
+
+		assert_eq!(frame[..3], [0xff, 0xb1, VERSION]);
+		let payload_len = usize::from(u16::from_le_bytes([frame[3], frame[4]]));
+		assert_eq!(payload_len, HEADER_LEN + b"value 7".len());
+		assert_eq!(frame.len(), 5 + payload_len + 4);
+
+		let payload = &frame[5..5 + payload_len];
+		assert_eq!(payload[..8], 0x0102_0304_0506_0708u64.to_le_bytes());
+		assert_eq!(payload[8..10], [Level::Warn as u8, 3]);
+		let module_id = fnv1a(&[b"hermit::test"]);
+		assert_eq!(
+			payload[10..12],
+			(((module_id >> 16) ^ module_id) as u16).to_le_bytes()
+		);
+		assert_eq!(payload[12..16], fnv1a(&[b"src/test.rs:12"]).to_le_bytes());
+		assert_eq!(&payload[16..], b"value 7");
+
+		let crc = crc32(0, &frame[2..5 + payload_len]);
+		assert_eq!(frame[5 + payload_len..], crc.to_le_bytes());
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_binlog_long_message() {
+		let long = "x".repeat(2 * MAX_ARGS);
+		let frame = encode(
+			&Record::builder()
+				.args(format_args!("{long}"))
+				.level(Level::Info)
+				.build(),
+			0,
+			0,
+		);
+		// The arguments are truncated and the checksum still fits.
+		assert_eq!(frame.len, frame.buf.len());
+		let payload_len = usize::from(u16::from_le_bytes([frame.buf[3], frame.buf[4]]));
+		assert_eq!(payload_len, HEADER_LEN + MAX_ARGS);
+	}
+}
@@ -0,0 +1,237 @@
+//! Per-core buffers of the recent kernel messages.
+//!
+//! Each core appends its messages to its own ring buffer, so that logging
+//! cores do not contend for a shared lock. The messages carry the timer ticks
+//! at the time of logging, which are synchronized between the cores (see
+//! `tsc_sync` on x86-64, the counter is global on AArch64 and RISC-V). A
+//! reader merges the buffers by this timestamp into a single stream, in which
+//! the messages of different cores interleave in the order they were logged.
+//!
+//! If a buffer is full, the oldest messages of the core are dropped.
+//!
+//! The merged stream is read through [`sys_klog`](crate::syscalls::sys_klog).
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use hermit_sync::InterruptTicketMutex;
+use log::Level;
+
+/// Size of the buffer of each core in bytes
+pub(crate) const BUFFER_SIZE: usize = 0x2000;
+
+/// Maximum number of cores with a buffer
+const MAX_CORES: usize = 256;
+
+/// Maximum length of the text of a message
+const MAX_TEXT: usize = 512;
+
+/// Length of the timestamp, the level, and the length of the text
+const HEADER_LEN: usize = 11;
+
+/// Buffer of the boot core, which also keeps the messages before the heap is available
+static BOOT_BUFFER: InterruptTicketMutex<Ring> = InterruptTicketMutex::new(Ring::new());
+
+/// Buffers of the application processors, which are allocated on their first message
+static BUFFERS: [AtomicPtr<InterruptTicketMutex<Ring>>; MAX_CORES] =
+	[const { AtomicPtr::new(ptr::null_mut()) }; MAX_CORES];
+
+/// Ring buffer of encoded messages
+///
+/// Each message consists of the timestamp in microseconds (`u64`), the level
+/// (`u8`), the length of the text (`u16`), and the text.
+struct Ring {
+	data: [u8; BUFFER_SIZE],
+	start: usize,
+	len: usize,
+}
+
+impl Ring {
+	const fn new() -> Self {
+		Self {
+			data: [0; BUFFER_SIZE],
+			start: 0,
+			len: 0,
+		}
+	}
+
+	fn byte(&self, i: usize) -> u8 {
+		self.data[(self.start + i) % BUFFER_SIZE]
+	}
+
+	/// Returns the length of the message at `offset` including its header.
+	fn message_len(&self, offset: usize) -> usize {
+		let len = u16::from_le_bytes([self.byte(offset + 9), self.byte(offset + 10)]);
+		HEADER_LEN + usize::from(len)
+	}
+
+	fn push(&mut self, timestamp: u64, level: Level, text: &[u8]) {
+		let len = HEADER_LEN + text.len();
+		while BUFFER_SIZE - self.len < len {
+			let first = self.message_len(0);
+			self.start = (self.start + first) % BUFFER_SIZE;
+			self.len -= first;
+		}
+
+		let mut header = [0; HEADER_LEN];
+		header[..8].copy_from_slice(&timestamp.to_le_bytes());
+		header[8] = level as u8;
+		header[9..].copy_from_slice(&u16::try_from(text.len()).unwrap().to_le_bytes());
+		for &byte in header.iter().chain(text) {
+			self.data[(self.start + self.len) % BUFFER_SIZE] = byte;
+			self.len += 1;
+		}
+	}
+
+	/// Appends the contents of the buffer to `buf`.
+	fn copy_to(&self, buf: &mut Vec<u8>) {
+		buf.extend((0..self.len).map(|i| self.byte(i)));
+	}
+
+	fn clear(&mut self) {
+		self.start = 0;
+		self.len = 0;
+	}
+}
+
+/// Decodes the messages copied from the buffer of the core `core_id`.
+fn decode(mut data: &[u8], core_id: u32, messages: &mut Vec<Message>) {
+	while data.len() >= HEADER_LEN {
+		let (header, rest) = data.split_at(HEADER_LEN);
+		let len = usize::from(u16::from_le_bytes([header[9], header[10]]));
+		let (text, rest) = rest.split_at(len);
+		data = rest;
+
+		messages.push(Message {
+			timestamp: u64::from_le_bytes(header[..8].try_into().unwrap()),
+			core_id,
+			level: level(header[8]),
+			text: String::from_utf8_lossy(text).into_owned(),
+		});
+	}
+}
+
+fn level(value: u8) -> Level {
+	match value {
+		1 => Level::Error,
+		2 => Level::Warn,
+		3 => Level::Info,
+		4 => Level::Debug,
+		_ => Level::Trace,
+	}
+}
+
+/// Returns the buffer of the current core or `None`, if it cannot be allocated.
+fn local_buffer() -> Option<&'static InterruptTicketMutex<Ring>> {
+	let core_id = usize::try_from(crate::arch::core_local::core_id()).unwrap();
+	if core_id == 0 {
+		return Some(&BOOT_BUFFER);
+	}
+
+	let slot = BUFFERS.get(core_id)?;
+	let buffer = slot.load(Ordering::Acquire);
+	if !buffer.is_null() {
+		return Some(unsafe { &*buffer });
+	}
+
+	// Only the core itself installs its buffer. The first message of an
+	// application processor is logged, while it boots on a shallow stack.
+	let buffer = Box::leak(Box::new(InterruptTicketMutex::new(Ring::new())));
+	slot.store(buffer, Ordering::Release);
+	Some(buffer)
+}
+
+fn buffers() -> impl Iterator<Item = (u32, &'static InterruptTicketMutex<Ring>)> {
+	let others = BUFFERS.iter().enumerate().filter_map(|(core_id, slot)| {
+		let buffer = slot.load(Ordering::Acquire);
+		(!buffer.is_null()).then(|| (core_id.try_into().unwrap(), unsafe { &*buffer }))
+	});
+	[(0, &BOOT_BUFFER)].into_iter().chain(others)
+}
+
+/// Formats the text of a message into a fixed buffer and truncates it, if it is too long.
+struct Text {
+	buf: [u8; MAX_TEXT],
+	len: usize,
+}
+
+impl Write for Text {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		let len = s.len().min(MAX_TEXT - self.len);
+		self.buf[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+		self.len += len;
+		Ok(())
+	}
+}
+
+/// Buffers a kernel message of the current core.
+pub(crate) fn push(level: Level, args: &fmt::Arguments<'_>) {
+	let timestamp = crate::processor::get_timer_ticks();
+	let mut text = Text {
+		buf: [0; MAX_TEXT],
+		len: 0,
+	};
+	let _ = write!(text, "{args}");
+
+	if let Some(buffer) = local_buffer() {
+		buffer.lock().push(timestamp, level, &text.buf[..text.len]);
+	}
+}
+
+struct Message {
+	timestamp: u64,
+	core_id: u32,
+	level: Level,
+	text: String,
+}
+
+impl fmt::Display for Message {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let seconds = self.timestamp / 1_000_000;
+		let microseconds = self.timestamp % 1_000_000;
+		writeln!(
+			f,
+			"[{seconds:5}.{microseconds:06}][{}][{}] {}",
+			self.core_id, self.level, self.text
+		)
+	}
+}
+
+/// Returns the buffered messages of all cores as text ordered by their timestamps.
+pub(crate) fn merged() -> String {
+	let mut messages = Vec::new();
+	let mut data = Vec::with_capacity(BUFFER_SIZE);
+	for (core_id, buffer) in buffers() {
+		// Nothing is allocated while the buffer is locked, since the
+		// allocator may log messages itself.
+		data.clear();
+		buffer.lock().copy_to(&mut data);
+		decode(&data, core_id, &mut messages);
+	}
+
+	// The messages of each core are already ordered. The sort is stable, so
+	// that messages of a core with equal timestamps keep their order.
+	messages.sort_by_key(|message| message.timestamp);
+
+	let mut text = String::new();
+	for message in &messages {
+		write!(text, "{message}").unwrap();
+	}
+	text
+}
+
+/// Drops the buffered messages of all cores.
+pub(crate) fn clear() {
+	for (_, buffer) in buffers() {
+		buffer.lock().clear();
+	}
+}
+
+/// Returns the capacity of the buffers of all cores in bytes.
+pub(crate) fn capacity() -> usize {
+	buffers().count() * BUFFER_SIZE
+}
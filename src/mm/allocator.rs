@@ -2,15 +2,26 @@
 //! in the kernel.
 
 use core::alloc::{GlobalAlloc, Layout};
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use hermit_sync::RawInterruptTicketMutex;
 use talc::{ErrOnOom, Span, Talc, Talck};
 
-pub struct LockedAllocator(Talck<RawInterruptTicketMutex, ErrOnOom>);
+pub struct LockedAllocator {
+	talc: Talck<RawInterruptTicketMutex, ErrOnOom>,
+	/// Size of the heap in bytes
+	size: AtomicUsize,
+	/// Number of allocated bytes
+	allocated: AtomicUsize,
+}
 
 impl LockedAllocator {
 	pub const fn new() -> Self {
-		Self(Talc::new(ErrOnOom).lock())
+		Self {
+			talc: Talc::new(ErrOnOom).lock(),
+			size: AtomicUsize::new(0),
+			allocated: AtomicUsize::new(0),
+		}
 	}
 
 	#[inline]
@@ -24,8 +35,27 @@ impl LockedAllocator {
 	pub unsafe fn init(&self, heap_bottom: *mut u8, heap_size: usize) {
 		let arena = Span::from_base_size(heap_bottom, heap_size);
 		unsafe {
-			self.0.lock().claim(arena).unwrap();
+			self.talc.lock().claim(arena).unwrap();
+		}
+		self.size.fetch_add(heap_size, Ordering::Relaxed);
+	}
+
+	/// Returns the size of the heap and the number of allocated bytes.
+	///
+	/// The allocated bytes do not include the padding and the metadata of
+	/// the allocator.
+	pub fn usage(&self) -> (usize, usize) {
+		(
+			self.size.load(Ordering::Relaxed),
+			self.allocated.load(Ordering::Relaxed),
+		)
+	}
+
+	fn count(&self, ptr: *mut u8, size: usize) -> *mut u8 {
+		if !ptr.is_null() {
+			self.allocated.fetch_add(size, Ordering::Relaxed);
 		}
+		ptr
 	}
 }
 
@@ -34,22 +64,28 @@ impl LockedAllocator {
 unsafe impl GlobalAlloc for LockedAllocator {
 	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
 		let layout = Self::align_layout(layout);
-		unsafe { self.0.alloc(layout) }
+		self.count(unsafe { self.talc.alloc(layout) }, layout.size())
 	}
 
 	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
 		let layout = Self::align_layout(layout);
-		unsafe { self.0.dealloc(ptr, layout) }
+		unsafe { self.talc.dealloc(ptr, layout) }
+		self.allocated.fetch_sub(layout.size(), Ordering::Relaxed);
 	}
 
 	unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
 		let layout = Self::align_layout(layout);
-		unsafe { self.0.alloc_zeroed(layout) }
+		self.count(unsafe { self.talc.alloc_zeroed(layout) }, layout.size())
 	}
 
 	unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
 		let layout = Self::align_layout(layout);
-		unsafe { self.0.realloc(ptr, layout, new_size) }
+		let new_ptr = unsafe { self.talc.realloc(ptr, layout, new_size) };
+		if !new_ptr.is_null() {
+			self.allocated.fetch_sub(layout.size(), Ordering::Relaxed);
+			self.allocated.fetch_add(new_size, Ordering::Relaxed);
+		}
+		new_ptr
 	}
 }
 
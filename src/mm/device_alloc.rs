@@ -5,6 +5,7 @@ use align_address::Align;
 use free_list::{PageLayout, PageRange};
 use memory_addresses::{PhysAddr, VirtAddr};
 
+use crate::arch::mm::cache;
 use crate::arch::mm::paging::{BasePageSize, PageSize};
 use crate::mm::physicalmem::PHYSICAL_FREE_LIST;
 use crate::mm::virtualmem;
@@ -12,6 +13,7 @@ use crate::mm::virtualmem;
 /// An [`Allocator`] for memory that is used to communicate with devices.
 ///
 /// Allocations from this allocator always correspond to contiguous physical memory.
+/// If DMA is not cache-coherent, the memory may be mapped non-cacheable.
 pub struct DeviceAlloc;
 
 unsafe impl Allocator for DeviceAlloc {
@@ -27,6 +29,7 @@ unsafe impl Allocator for DeviceAlloc {
 
 		let phys_addr = PhysAddr::from(frame_range.start());
 		let ptr = self.ptr_from(phys_addr);
+		cache::prepare_device_memory(VirtAddr::from_ptr(ptr), size);
 		let slice = ptr::slice_from_raw_parts_mut(ptr, size);
 		Ok(NonNull::new(slice).unwrap())
 	}
@@ -35,6 +38,7 @@ unsafe impl Allocator for DeviceAlloc {
 		assert!(layout.align() <= BasePageSize::SIZE as usize);
 		let size = layout.size().align_up(BasePageSize::SIZE as usize);
 
+		cache::release_device_memory(VirtAddr::from_ptr(ptr.as_ptr()), size);
 		let phys_addr = self.phys_addr_from(ptr.as_ptr());
 		let range = PageRange::from_start_len(phys_addr.as_usize(), size).unwrap();
 
@@ -0,0 +1,327 @@
+//! Streaming DMA mappings.
+//!
+//! Drivers map a buffer with [`map_single`] (or a list of buffers with
+//! [`map_sg`]) before handing it to a device and pass the returned
+//! [`DmaMapping::dma_addr`] to the device. Afterwards, the buffer is owned by
+//! the device until the mapping is synchronized with
+//! [`DmaMapping::sync_for_cpu`] or dropped. To hand the buffer to the device
+//! again, it has to be synchronized with [`DmaMapping::sync_for_device`].
+//!
+//! The mapping hides the cache maintenance, which is required if devices are
+//! not cache-coherent, and bounces buffers, which are not contiguous in
+//! physical memory, through memory from [`DeviceAlloc`]. Buffers, which have
+//! been allocated by [`DeviceAlloc`] themselves, are mapped with
+//! [`map_device`] and [`map_sg_device`] without walking the page tables.
+//!
+//! Memory, which the CPU and a device share, e.g., a virtqueue, is ordered
+//! with [`dma_barrier`]. The compiler and x86-64 keep the order of most
+//! accesses, but aarch64 and riscv64 may reorder them, so that a device
+//! could see a published descriptor before its contents.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::mem::MaybeUninit;
+use core::ptr::{self, NonNull};
+
+use align_address::Align;
+use memory_addresses::{PhysAddr, VirtAddr};
+
+use crate::arch::mm::cache;
+use crate::arch::mm::paging::{BasePageSize, PageSize};
+use crate::mm::device_alloc::DeviceAlloc;
+use crate::mm::virtual_to_physical;
+
+/// Direction of the data transfer of a DMA mapping
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum DmaDirection {
+	/// The device reads from the buffer.
+	ToDevice,
+	/// The device writes to the buffer.
+	FromDevice,
+	/// The device reads from and writes to the buffer.
+	#[allow(dead_code)]
+	Bidirectional,
+}
+
+impl DmaDirection {
+	fn device_reads(self) -> bool {
+		self != Self::FromDevice
+	}
+
+	fn device_writes(self) -> bool {
+		self != Self::ToDevice
+	}
+}
+
+/// Ordering, which [`dma_barrier`] enforces
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum DmaBarrier {
+	/// Earlier reads of memory, which a device writes, happen before later
+	/// reads, e.g., of a used element after the used index.
+	Read,
+	/// Earlier writes of memory, which a device reads, happen before later
+	/// writes, e.g., of a descriptor before the available index.
+	Write,
+	/// Earlier reads and writes happen before later reads and writes, e.g.,
+	/// of the available index before the event suppression of the device.
+	Full,
+	/// Earlier writes of memory happen before a later write of a device
+	/// register, e.g., of a doorbell.
+	Doorbell,
+}
+
+/// Orders the accesses of the CPU to memory and registers of devices.
+#[inline]
+pub(crate) fn dma_barrier(barrier: DmaBarrier) {
+	cache::dma_barrier(barrier);
+}
+
+/// Makes the CPU's view of `len` bytes at `ptr` visible to a device.
+///
+/// This is required for memory that is accessed by a device without a
+/// [`DmaMapping`], e.g. memory of [`DeviceAlloc`].
+pub(crate) fn sync_for_device(ptr: *const u8, len: usize, direction: DmaDirection) {
+	if cache::dma_coherent() || len == 0 {
+		return;
+	}
+
+	let start = VirtAddr::from_ptr(ptr);
+	if direction.device_reads() {
+		cache::clean_dcache_range(start, len);
+	} else {
+		// Dirty cache lines must not be written back while the device writes
+		// to the buffer.
+		cache::flush_dcache_range(start, len);
+	}
+}
+
+/// Makes the data, which a device wrote to `len` bytes at `ptr`, visible to the CPU.
+pub(crate) fn sync_for_cpu(ptr: *const u8, len: usize, direction: DmaDirection) {
+	if cache::dma_coherent() || len == 0 || !direction.device_writes() {
+		return;
+	}
+
+	cache::invalidate_dcache_range(VirtAddr::from_ptr(ptr), len);
+}
+
+/// Returns the physical address of `len` bytes at `ptr`, if they are
+/// contiguous in physical memory.
+fn contiguous_phys_addr(ptr: *const u8, len: usize) -> Option<PhysAddr> {
+	let start = VirtAddr::from_ptr(ptr);
+	if len == 0 {
+		// Empty buffers are never accessed and may be dangling.
+		return Some(virtual_to_physical(start).unwrap_or(PhysAddr::zero()));
+	}
+	let phys_start = virtual_to_physical(start)?;
+
+	let page_size = BasePageSize::SIZE as usize;
+	let end = start.as_usize() + len;
+	let mut page = start.as_usize().align_down(page_size) + page_size;
+	while page < end {
+		let expected = phys_start.as_usize() + (page - start.as_usize());
+		let phys = virtual_to_physical(VirtAddr::new(page as u64))?;
+		if phys.as_usize() != expected {
+			return None;
+		}
+		page += page_size;
+	}
+
+	Some(phys_start)
+}
+
+/// A buffer that is mapped for DMA.
+///
+/// Dropping the mapping synchronizes the buffer for the CPU.
+pub(crate) struct DmaMapping {
+	buffer: NonNull<u8>,
+	len: usize,
+	direction: DmaDirection,
+	dma_addr: PhysAddr,
+	/// Memory, which is accessed by the device instead of the buffer
+	bounce: Option<Box<[MaybeUninit<u8>], DeviceAlloc>>,
+}
+
+// SAFETY: The mapping does not alias the buffer for the CPU. The user of
+// the mapping is responsible for the buffer itself.
+unsafe impl Send for DmaMapping {}
+
+impl DmaMapping {
+	/// Returns the address, which the device uses to access the buffer.
+	pub(crate) fn dma_addr(&self) -> PhysAddr {
+		self.dma_addr
+	}
+
+	pub(crate) fn len(&self) -> usize {
+		self.len
+	}
+
+	pub(crate) fn direction(&self) -> DmaDirection {
+		self.direction
+	}
+
+	fn device_ptr(&self) -> *const u8 {
+		match &self.bounce {
+			Some(bounce) => bounce.as_ptr().cast(),
+			None => self.buffer.as_ptr(),
+		}
+	}
+
+	/// Hands the buffer to the device.
+	pub(crate) fn sync_for_device(&mut self) {
+		if let Some(bounce) = &mut self.bounce
+			&& self.direction.device_reads()
+		{
+			unsafe {
+				ptr::copy_nonoverlapping(
+					self.buffer.as_ptr(),
+					bounce.as_mut_ptr().cast(),
+					self.len,
+				);
+			}
+		}
+
+		sync_for_device(self.device_ptr(), self.len, self.direction);
+	}
+
+	/// Hands the buffer back to the CPU.
+	pub(crate) fn sync_for_cpu(&mut self) {
+		sync_for_cpu(self.device_ptr(), self.len, self.direction);
+
+		if let Some(bounce) = &self.bounce
+			&& self.direction.device_writes()
+		{
+			unsafe {
+				ptr::copy_nonoverlapping(bounce.as_ptr().cast(), self.buffer.as_ptr(), self.len);
+			}
+		}
+	}
+}
+
+impl Drop for DmaMapping {
+	fn drop(&mut self) {
+		self.sync_for_cpu();
+	}
+}
+
+/// Maps `len` bytes at `buffer` for DMA in `direction` and hands them to the device.
+///
+/// # Safety
+///
+/// `buffer` must be valid for reads and writes of `len` bytes for the
+/// lifetime of the mapping and must not be accessed by the CPU while it is
+/// owned by the device.
+pub(crate) unsafe fn map_single(
+	buffer: NonNull<u8>,
+	len: usize,
+	direction: DmaDirection,
+) -> DmaMapping {
+	let (dma_addr, bounce) = match contiguous_phys_addr(buffer.as_ptr(), len) {
+		Some(dma_addr) => (dma_addr, None),
+		None => {
+			let mut bounce = Box::new_uninit_slice_in(len, DeviceAlloc);
+			let dma_addr = DeviceAlloc.phys_addr_from(bounce.as_mut_ptr());
+			(dma_addr, Some(bounce))
+		}
+	};
+
+	let mut mapping = DmaMapping {
+		buffer,
+		len,
+		direction,
+		dma_addr,
+		bounce,
+	};
+	mapping.sync_for_device();
+	mapping
+}
+
+/// Maps `len` bytes at `buffer`, which has been allocated by [`DeviceAlloc`],
+/// for DMA in `direction` and hands them to the device.
+///
+/// The memory of [`DeviceAlloc`] is contiguous in physical memory, so that the
+/// address for the device is derived from `buffer` instead of walking the page
+/// tables, and the buffer is never bounced.
+///
+/// # Safety
+///
+/// See [`map_single`]. Additionally, `buffer` has to be allocated by
+/// [`DeviceAlloc`].
+pub(crate) unsafe fn map_device(
+	buffer: NonNull<u8>,
+	len: usize,
+	direction: DmaDirection,
+) -> DmaMapping {
+	let dma_addr = if len == 0 {
+		// Empty buffers are never accessed and may be dangling.
+		PhysAddr::zero()
+	} else {
+		DeviceAlloc.phys_addr_from(buffer.as_ptr())
+	};
+
+	let mut mapping = DmaMapping {
+		buffer,
+		len,
+		direction,
+		dma_addr,
+		bounce: None,
+	};
+	mapping.sync_for_device();
+	mapping
+}
+
+/// Maps a list of buffers for DMA in `direction` and hands them to the device.
+///
+/// # Safety
+///
+/// See [`map_single`].
+#[allow(dead_code)]
+pub(crate) unsafe fn map_sg(buffers: &[NonNull<[u8]>], direction: DmaDirection) -> Vec<DmaMapping> {
+	buffers
+		.iter()
+		.map(|buffer| unsafe { map_single(buffer.cast(), buffer.len(), direction) })
+		.collect()
+}
+
+/// Maps a list of buffers, which have been allocated by [`DeviceAlloc`], for
+/// DMA in `direction` and hands them to the device.
+///
+/// # Safety
+///
+/// See [`map_device`].
+pub(crate) unsafe fn map_sg_device(
+	buffers: &[NonNull<[u8]>],
+	direction: DmaDirection,
+) -> Vec<DmaMapping> {
+	buffers
+		.iter()
+		.map(|buffer| unsafe { map_device(buffer.cast(), buffer.len(), direction) })
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_map_device() {
+		let len = 2 * BasePageSize::SIZE as usize;
+		let mut buffer = Box::<[u8], _>::new_zeroed_slice_in(len, DeviceAlloc);
+		let ptr = NonNull::new(buffer.as_mut_ptr().cast::<u8>()).unwrap();
+
+		let mapping = unsafe { map_device(ptr, len, DmaDirection::ToDevice) };
+		assert!(mapping.bounce.is_none());
+		assert_eq!(
+			Some(mapping.dma_addr()),
+			virtual_to_physical(VirtAddr::from_ptr(ptr.as_ptr()))
+		);
+
+		// The page table walk yields the same address.
+		let walked = unsafe { map_single(ptr, len, DmaDirection::ToDevice) };
+		assert!(walked.bounce.is_none());
+		assert_eq!(walked.dma_addr(), mapping.dma_addr());
+
+		let empty = unsafe { map_device(NonNull::dangling(), 0, DmaDirection::FromDevice) };
+		assert_eq!(empty.dma_addr(), PhysAddr::zero());
+	}
+}
@@ -0,0 +1,177 @@
+//! Memory mappings of files.
+//!
+//! Hermit has no page cache and does not handle page faults for mappings.
+//! Therefore, the content of a file is copied into the mapping, when it is
+//! created. Shared mappings are written back to the file by [`sync`], by
+//! [`sync_object`] before the file is synchronized to its storage, and when
+//! they are unmapped. Changes to the file or to other mappings of the
+//! same file are not visible in a mapping until it is created again.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::slice;
+
+use hermit_sync::InterruptTicketMutex;
+use memory_addresses::VirtAddr;
+
+use crate::errno::Errno;
+use crate::executor::block_on;
+use crate::fd::ObjectInterface;
+use crate::io;
+
+#[derive(Debug, Clone)]
+struct FileMapping {
+	/// Start of the mapping
+	addr: VirtAddr,
+	/// Number of bytes, which are backed by the file
+	len: usize,
+	object: Arc<async_lock::RwLock<dyn ObjectInterface>>,
+	/// Offset of the mapping within the file
+	offset: usize,
+	/// Changes are written back to the file
+	shared: bool,
+}
+
+impl FileMapping {
+	fn range(&self) -> Range<usize> {
+		self.addr.as_usize()..self.addr.as_usize() + self.len
+	}
+}
+
+static FILE_MAPPINGS: InterruptTicketMutex<Vec<FileMapping>> =
+	InterruptTicketMutex::new(Vec::new());
+
+/// Returns the intersection of two ranges.
+fn intersect(a: &Range<usize>, b: &Range<usize>) -> Range<usize> {
+	a.start.max(b.start)..a.end.min(b.end)
+}
+
+/// Copies up to `len` bytes at `offset` of `object` to `addr` and registers the mapping.
+///
+/// The memory at `addr` has to be mapped and writable. Bytes beyond the end
+/// of the file are left untouched.
+pub(crate) fn map(
+	addr: VirtAddr,
+	len: usize,
+	object: Arc<async_lock::RwLock<dyn ObjectInterface>>,
+	offset: usize,
+	shared: bool,
+) -> io::Result<()> {
+	let buf = unsafe { slice::from_raw_parts_mut(addr.as_mut_ptr::<u8>(), len) };
+	let len = block_on(
+		async {
+			let guard = object.read().await;
+			let mut filled = 0;
+			while filled < buf.len() {
+				let read = guard.pread(&mut buf[filled..], offset + filled).await?;
+				if read == 0 {
+					break;
+				}
+				filled += read;
+			}
+			Ok(filled)
+		},
+		None,
+	)?;
+
+	debug!("Map {len} bytes at offset {offset} of a file to {addr:X}");
+	FILE_MAPPINGS.lock().push(FileMapping {
+		addr,
+		len,
+		object,
+		offset,
+		shared,
+	});
+	Ok(())
+}
+
+/// Writes the shared mappings within `len` bytes at `addr` back to their files.
+pub(crate) fn sync(addr: VirtAddr, len: usize) -> io::Result<()> {
+	let range = addr.as_usize()..addr.as_usize() + len;
+	// The lock must not be held while writing to the files.
+	let mappings = FILE_MAPPINGS
+		.lock()
+		.iter()
+		.filter(|mapping| mapping.shared && !intersect(&mapping.range(), &range).is_empty())
+		.cloned()
+		.collect::<Vec<_>>();
+
+	for mapping in mappings {
+		write_back(&mapping, &range)?;
+	}
+	Ok(())
+}
+
+/// Writes all shared mappings of `object` back to it.
+pub(crate) fn sync_object(object: &Arc<async_lock::RwLock<dyn ObjectInterface>>) -> io::Result<()> {
+	let mappings = FILE_MAPPINGS
+		.lock()
+		.iter()
+		.filter(|mapping| mapping.shared && Arc::ptr_eq(&mapping.object, object))
+		.cloned()
+		.collect::<Vec<_>>();
+
+	for mapping in mappings {
+		write_back(&mapping, &mapping.range())?;
+	}
+	Ok(())
+}
+
+fn write_back(mapping: &FileMapping, range: &Range<usize>) -> io::Result<()> {
+	let dirty = intersect(&mapping.range(), range);
+	let offset = mapping.offset + (dirty.start - mapping.addr.as_usize());
+	let buf = unsafe { slice::from_raw_parts(dirty.start as *const u8, dirty.len()) };
+
+	block_on(
+		async {
+			let guard = mapping.object.read().await;
+			let mut written = 0;
+			while written < buf.len() {
+				match guard.pwrite(&buf[written..], offset + written).await? {
+					0 => return Err(Errno::Io),
+					len => written += len,
+				}
+			}
+			Ok(())
+		},
+		None,
+	)
+}
+
+/// Removes the mappings within `len` bytes at `addr`, after writing shared
+/// mappings back to their files.
+///
+/// Mappings, which are only partially covered, are kept for the remaining part.
+/// The mappings are removed even if they cannot be written back.
+pub(crate) fn unmap(addr: VirtAddr, len: usize) -> io::Result<()> {
+	let result = sync(addr, len);
+
+	let range = addr.as_usize()..addr.as_usize() + len;
+	let mut mappings = FILE_MAPPINGS.lock();
+	let mut remaining = Vec::new();
+	for mapping in mappings.drain(..) {
+		let mapped = mapping.range();
+		if intersect(&mapped, &range).is_empty() {
+			remaining.push(mapping);
+			continue;
+		}
+
+		if mapped.start < range.start {
+			remaining.push(FileMapping {
+				len: range.start - mapped.start,
+				..mapping.clone()
+			});
+		}
+		if range.end < mapped.end {
+			remaining.push(FileMapping {
+				addr: VirtAddr::new(range.end as u64),
+				len: mapped.end - range.end,
+				offset: mapping.offset + (range.end - mapped.start),
+				..mapping
+			});
+		}
+	}
+	*mappings = remaining;
+	result
+}
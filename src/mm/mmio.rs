@@ -0,0 +1,221 @@
+//! Registry of memory-mapped I/O regions.
+//!
+//! Every mapping of device memory is recorded together with the subsystem,
+//! which created it, the device, the BAR (for PCI devices), the virtual
+//! address range, and the page attributes. A region may only be mapped once:
+//! Mapping physical memory, which overlaps a registered region, is rejected,
+//! unless the same subsystem maps the same BAR with the same attributes
+//! again. In this case, the existing mapping is reused.
+//!
+//! The registry is exported as `/proc/mmio`.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::ops::Range;
+
+use bitflags::bitflags;
+use hermit_sync::InterruptTicketMutex;
+use memory_addresses::{PhysAddr, VirtAddr};
+
+bitflags! {
+	/// Page attributes of a MMIO mapping
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub(crate) struct MmioAttributes: u8 {
+		const WRITABLE = 1 << 0;
+		const EXECUTE_DISABLE = 1 << 1;
+		const NO_CACHE = 1 << 2;
+	}
+}
+
+impl MmioAttributes {
+	/// Attributes of device registers
+	pub(crate) const DEVICE: Self = Self::WRITABLE
+		.union(Self::EXECUTE_DISABLE)
+		.union(Self::NO_CACHE);
+}
+
+impl fmt::Display for MmioAttributes {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let writable = if self.contains(Self::WRITABLE) {
+			'w'
+		} else {
+			'-'
+		};
+		let executable = if self.contains(Self::EXECUTE_DISABLE) {
+			'-'
+		} else {
+			'x'
+		};
+		let cache = if self.contains(Self::NO_CACHE) {
+			"uc"
+		} else {
+			"wb"
+		};
+		write!(f, "r{writable}{executable} {cache}")
+	}
+}
+
+/// A region of device memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MmioRegion {
+	/// Subsystem, which maps the region (e.g., `virtio`)
+	pub owner: &'static str,
+	/// Name of the device (e.g., the PCI address)
+	pub device: String,
+	/// Index of the BAR, if the region belongs to a PCI device
+	pub bar: Option<u8>,
+	pub phys_addr: PhysAddr,
+	pub size: usize,
+	pub attributes: MmioAttributes,
+}
+
+impl MmioRegion {
+	/// Creates a region of device registers, which does not belong to a PCI device.
+	pub(crate) fn new(
+		owner: &'static str,
+		device: impl Into<String>,
+		phys_addr: PhysAddr,
+		size: usize,
+	) -> Self {
+		Self {
+			owner,
+			device: device.into(),
+			bar: None,
+			phys_addr,
+			size,
+			attributes: MmioAttributes::DEVICE,
+		}
+	}
+
+	/// Returns the physical address range of the region.
+	fn range(&self) -> Range<u64> {
+		let start = self.phys_addr.as_u64();
+		start..start + u64::try_from(self.size).unwrap()
+	}
+
+	fn overlaps(&self, other: &Self) -> bool {
+		let (range, other) = (self.range(), other.range());
+		range.start < other.end && other.start < range.end
+	}
+
+	/// Returns whether `other` maps the same BAR as `self` in the same way.
+	#[cfg(feature = "pci")]
+	fn is_same_bar(&self, other: &Self) -> bool {
+		self.bar.is_some() && self == other
+	}
+}
+
+impl fmt::Display for MmioRegion {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} ({}", self.device, self.owner)?;
+		if let Some(bar) = self.bar {
+			write!(f, ", BAR {bar}")?;
+		}
+		let range = self.range();
+		write!(f, ") at {:#x}..{:#x}", range.start, range.end)
+	}
+}
+
+#[derive(Debug)]
+struct MmioMapping {
+	region: MmioRegion,
+	virt_addr: VirtAddr,
+}
+
+/// A region, which overlaps an already mapped region
+#[derive(Debug)]
+pub(crate) struct MmioConflict {
+	pub requested: MmioRegion,
+	pub existing: MmioRegion,
+}
+
+impl fmt::Display for MmioConflict {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Cannot map {}, it overlaps {}",
+			self.requested, self.existing
+		)
+	}
+}
+
+static MMIO_MAPPINGS: InterruptTicketMutex<Vec<MmioMapping>> =
+	InterruptTicketMutex::new(Vec::new());
+
+fn check(mappings: &[MmioMapping], region: &MmioRegion) -> Result<(), MmioConflict> {
+	match mappings
+		.iter()
+		.find(|mapping| mapping.region.overlaps(region))
+	{
+		Some(mapping) => Err(MmioConflict {
+			requested: region.clone(),
+			existing: mapping.region.clone(),
+		}),
+		None => Ok(()),
+	}
+}
+
+/// Maps `region` with `map_region`, which returns the virtual address of the mapping.
+///
+/// If the same BAR has already been mapped by the same subsystem with the
+/// same attributes, the existing mapping is returned instead.
+#[cfg(feature = "pci")]
+pub(crate) fn map(
+	region: MmioRegion,
+	map_region: impl FnOnce(&MmioRegion) -> VirtAddr,
+) -> Result<VirtAddr, MmioConflict> {
+	let mut mappings = MMIO_MAPPINGS.lock();
+
+	if let Some(mapping) = mappings
+		.iter()
+		.find(|mapping| mapping.region.is_same_bar(&region))
+	{
+		debug!("Reusing mapping of {region}");
+		return Ok(mapping.virt_addr);
+	}
+	check(&mappings, &region)?;
+
+	let virt_addr = map_region(&region);
+	mappings.push(MmioMapping { region, virt_addr });
+	Ok(virt_addr)
+}
+
+/// Registers `region`, which is about to be mapped at `virt_addr`.
+pub(crate) fn register(region: MmioRegion, virt_addr: VirtAddr) -> Result<(), MmioConflict> {
+	let mut mappings = MMIO_MAPPINGS.lock();
+	check(&mappings, &region)?;
+	mappings.push(MmioMapping { region, virt_addr });
+	Ok(())
+}
+
+/// Returns the registry in the format of `/proc/mmio`.
+pub(crate) fn proc_mmio() -> String {
+	let mut mappings = MMIO_MAPPINGS
+		.lock()
+		.iter()
+		.map(|mapping| (mapping.region.clone(), mapping.virt_addr))
+		.collect::<Vec<_>>();
+	mappings.sort_by_key(|(region, _)| region.phys_addr.as_u64());
+
+	let mut s = String::new();
+	for (region, virt_addr) in mappings {
+		let bar = region
+			.bar
+			.map_or_else(|| String::from("-"), |bar| bar.to_string());
+		let range = region.range();
+		writeln!(
+			s,
+			"{:#018x}-{:#018x} {:#018x} {} {} {} {}",
+			range.start,
+			range.end,
+			virt_addr.as_u64(),
+			region.attributes,
+			region.owner,
+			region.device,
+			bar
+		)
+		.unwrap();
+	}
+	s
+}
@@ -42,7 +42,14 @@
 
 pub(crate) mod allocator;
 pub(crate) mod device_alloc;
+#[cfg_attr(not(any(feature = "nvme", feature = "virtio")), allow(dead_code))]
+pub(crate) mod dma;
+#[cfg(feature = "mman")]
+pub(crate) mod file_mapping;
+pub(crate) mod mmio;
 pub(crate) mod physicalmem;
+pub(crate) mod pool_alloc;
+pub(crate) mod pressure;
 pub(crate) mod virtualmem;
 
 use core::mem;
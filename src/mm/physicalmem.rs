@@ -1,3 +1,5 @@
+use core::ops::Range;
+use core::slice;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 use align_address::Align;
@@ -7,7 +9,7 @@ use memory_addresses::{PhysAddr, VirtAddr};
 
 #[cfg(target_arch = "x86_64")]
 use crate::arch::mm::paging::PageTableEntryFlagsExt;
-use crate::arch::mm::paging::{self, HugePageSize, PageSize, PageTableEntryFlags};
+use crate::arch::mm::paging::{self, BasePageSize, HugePageSize, PageSize, PageTableEntryFlags};
 use crate::env;
 use crate::mm::device_alloc::DeviceAlloc;
 
@@ -15,10 +17,19 @@ pub static PHYSICAL_FREE_LIST: InterruptTicketMutex<FreeList<16>> =
 	InterruptTicketMutex::new(FreeList::new());
 pub static TOTAL_MEMORY: AtomicUsize = AtomicUsize::new(0);
 
+/// Pages and exact range of the initramfs, which are excluded from the free
+/// memory until the initramfs has been unpacked
+static INITRD: InterruptTicketMutex<Option<(PageRange, Range<usize>)>> =
+	InterruptTicketMutex::new(None);
+
 pub fn total_memory_size() -> usize {
 	TOTAL_MEMORY.load(Ordering::Relaxed)
 }
 
+pub fn free_memory_size() -> usize {
+	PHYSICAL_FREE_LIST.lock().free_space()
+}
+
 pub unsafe fn init_frame_range(frame_range: PageRange) {
 	cfg_if::cfg_if! {
 		if #[cfg(target_arch = "aarch64")] {
@@ -154,4 +165,46 @@ pub fn init() {
 			}
 		}
 	}
+
+	reserve_initrd();
+}
+
+/// Protects the initramfs from being allocated, before it has been unpacked.
+fn reserve_initrd() {
+	let Some(initrd) = env::initrd() else {
+		return;
+	};
+
+	let page_size = usize::try_from(BasePageSize::SIZE).unwrap();
+	let pages = PageRange::new(
+		initrd.start.align_down(page_size),
+		initrd.end.align_up(page_size),
+	)
+	.unwrap();
+	if PHYSICAL_FREE_LIST.lock().allocate_at(pages).is_err() {
+		error!("initramfs at {initrd:#x?} is not part of the physical memory, ignoring it");
+		return;
+	}
+
+	*INITRD.lock() = Some((pages, initrd));
+}
+
+/// Returns the content of the initramfs, if the loader has passed one.
+pub(crate) fn initrd() -> Option<&'static [u8]> {
+	let initrd = INITRD.lock().as_ref()?.1.clone();
+	let ptr = DeviceAlloc.ptr_from::<u8>(PhysAddr::new(initrd.start.try_into().unwrap()));
+	Some(unsafe { slice::from_raw_parts(ptr, initrd.len()) })
+}
+
+/// Returns the memory of the initramfs to the free memory.
+///
+/// # Safety
+///
+/// The slice returned by [`initrd`] must not be used anymore.
+pub(crate) unsafe fn release_initrd() {
+	if let Some((pages, _)) = INITRD.lock().take() {
+		unsafe {
+			PHYSICAL_FREE_LIST.lock().deallocate(pages).unwrap();
+		}
+	}
 }
@@ -0,0 +1,89 @@
+use alloc::alloc::Global;
+use core::alloc::{AllocError, Allocator, Layout};
+use core::cell::Cell;
+use core::ptr::NonNull;
+
+/// Maximum number of blocks, which a [`PoolAlloc`] keeps
+const MAX_BLOCKS: usize = 64;
+
+/// A freed block, which links to the next one
+struct Block {
+	next: Option<NonNull<Block>>,
+}
+
+/// An [`Allocator`] for small objects of a single type, e.g., the nodes of a
+/// [`LinkedList`](alloc::collections::LinkedList).
+///
+/// Freed blocks of the layout of the first allocation are kept and reused by
+/// the next allocations, so that the hot paths do not take the lock of the
+/// global heap. Other layouts and blocks beyond [`MAX_BLOCKS`] go to the heap.
+///
+/// The allocator is not thread-safe and is meant to be owned by a single
+/// core, e.g., by a data structure of its scheduler.
+pub(crate) struct PoolAlloc {
+	/// Layout of the kept blocks
+	layout: Cell<Option<Layout>>,
+	head: Cell<Option<NonNull<Block>>>,
+	len: Cell<usize>,
+}
+
+impl PoolAlloc {
+	pub const fn new() -> Self {
+		Self {
+			layout: Cell::new(None),
+			head: Cell::new(None),
+			len: Cell::new(0),
+		}
+	}
+
+	fn is_pooled(&self, layout: Layout) -> bool {
+		if self.layout.get().is_none()
+			&& layout.size() >= size_of::<Block>()
+			&& layout.align() >= align_of::<Block>()
+		{
+			self.layout.set(Some(layout));
+		}
+		self.layout.get() == Some(layout)
+	}
+}
+
+unsafe impl Allocator for PoolAlloc {
+	fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+		if self.is_pooled(layout)
+			&& let Some(block) = self.head.get()
+		{
+			self.head.set(unsafe { block.as_ref().next });
+			self.len.set(self.len.get() - 1);
+			return Ok(NonNull::slice_from_raw_parts(block.cast(), layout.size()));
+		}
+
+		Global.allocate(layout)
+	}
+
+	unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+		if self.is_pooled(layout) && self.len.get() < MAX_BLOCKS {
+			let block = ptr.cast::<Block>();
+			unsafe {
+				block.write(Block {
+					next: self.head.get(),
+				});
+			}
+			self.head.set(Some(block));
+			self.len.set(self.len.get() + 1);
+			return;
+		}
+
+		unsafe { Global.deallocate(ptr, layout) }
+	}
+}
+
+impl Drop for PoolAlloc {
+	fn drop(&mut self) {
+		while let Some(block) = self.head.get() {
+			self.head.set(unsafe { block.as_ref().next });
+			unsafe {
+				Global.deallocate(block.cast(), self.layout.get().unwrap());
+			}
+		}
+	}
+}
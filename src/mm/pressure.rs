@@ -0,0 +1,275 @@
+//! Memory pressure notifications.
+//!
+//! The available memory is the free part of the heap and the free physical
+//! memory. Its share of the total memory is compared to watermarks and
+//! results in a [`Level`]. Descriptors created by `sys_mempressure` become
+//! readable, when the level rises to or above their level, so that caches of
+//! the application can shrink before allocations fail.
+//!
+//! The kernel samples the level every [`SAMPLE_INTERVAL`] microseconds, when
+//! it handles the waiting tasks. While descriptors exist, the timer is armed
+//! for the next sample.
+//!
+//! Like the pressure stall information of Linux, `/proc/pressure/memory`
+//! accounts the time under pressure. Hermit does not reclaim memory, so
+//! tasks do not stall on memory, but their allocations fail. Instead, `some`
+//! is the time below the low watermark and `full` the time below the critical
+//! watermark. `avg10`, `avg60`, and `avg300` are the shares of this time
+//! over 10 seconds, 1 minute, and 5 minutes in percent.
+
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::task::Waker;
+
+use hermit_sync::InterruptSpinMutex;
+use num_enum::TryFromPrimitive;
+
+use crate::arch::processor::get_timer_ticks;
+use crate::mm::{ALLOCATOR, physicalmem};
+use crate::scheduler::load::FSHIFT;
+
+const FIXED_1: u64 = 1 << FSHIFT;
+
+/// Interval between two samples in microseconds
+const SAMPLE_INTERVAL: u64 = 100_000;
+
+/// Period of the averages in microseconds
+const PERIOD: u64 = 2_000_000;
+
+/// Decay factors of the averages over 10 seconds, 1 minute, and 5 minutes per period
+const EXP: [u64; 3] = [1677, 1981, 2034];
+
+/// Number of periods, after which the averages have decayed to zero
+const MAX_MISSED: u64 = 300 / 2;
+
+/// Pressure level, given by the share of the available memory
+#[derive(TryFromPrimitive, Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u32)]
+pub(crate) enum Level {
+	#[default]
+	None = 0,
+	/// Less than 20 % of the memory are available.
+	Low = 1,
+	/// Less than 10 % of the memory are available.
+	Medium = 2,
+	/// Less than 5 % of the memory are available.
+	Critical = 3,
+}
+
+impl Level {
+	fn current() -> Self {
+		let total = physicalmem::total_memory_size();
+		let (heap_size, allocated) = ALLOCATOR.usage();
+		let available = heap_size.saturating_sub(allocated) + physicalmem::free_memory_size();
+
+		if total == 0 {
+			Self::None
+		} else if available * 100 < total * 5 {
+			Self::Critical
+		} else if available * 100 < total * 10 {
+			Self::Medium
+		} else if available * 100 < total * 20 {
+			Self::Low
+		} else {
+			Self::None
+		}
+	}
+}
+
+/// Notifications of a descriptor, whenever the level rises to or above `level`
+#[derive(Debug)]
+pub(crate) struct Trigger {
+	level: Level,
+	/// Number of notifications since the last read
+	events: AtomicU64,
+	wakers: InterruptSpinMutex<Vec<Waker>>,
+}
+
+impl Trigger {
+	fn fire(&self) {
+		self.events.fetch_add(1, Ordering::Relaxed);
+		for waker in self.wakers.lock().drain(..) {
+			waker.wake();
+		}
+	}
+
+	/// Returns and resets the number of notifications.
+	pub fn take_events(&self) -> u64 {
+		self.events.swap(0, Ordering::Relaxed)
+	}
+
+	/// Registers `waker`, which is woken by the next notification.
+	pub fn register(&self, waker: &Waker) {
+		let mut wakers = self.wakers.lock();
+		if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+			wakers.push(waker.clone());
+		}
+	}
+
+	pub fn has_events(&self) -> bool {
+		self.events.load(Ordering::Relaxed) > 0
+	}
+}
+
+impl Drop for Trigger {
+	fn drop(&mut self) {
+		TRIGGERS.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+#[derive(Debug)]
+struct State {
+	level: Level,
+	last_sample: u64,
+	period_start: u64,
+	/// Time below the low and the critical watermark in microseconds
+	total: [u64; 2],
+	/// Time below the low and the critical watermark within the current period
+	period: [u64; 2],
+	/// Averages of `some` and `full` in percent with [`FSHIFT`] fractional bits
+	averages: [[u64; 3]; 2],
+	triggers: Vec<Weak<Trigger>>,
+}
+
+static STATE: InterruptSpinMutex<State> = InterruptSpinMutex::new(State {
+	level: Level::None,
+	last_sample: 0,
+	period_start: 0,
+	total: [0; 2],
+	period: [0; 2],
+	averages: [[0; 3]; 2],
+	triggers: Vec::new(),
+});
+
+/// Time of the next sample
+static NEXT_SAMPLE: AtomicU64 = AtomicU64::new(0);
+
+/// Number of triggers, which keep the timer armed
+static TRIGGERS: AtomicUsize = AtomicUsize::new(0);
+
+fn decay(average: u64, exp: u64, share: u64) -> u64 {
+	(average * exp + share * (FIXED_1 - exp)) >> FSHIFT
+}
+
+/// Samples the level, if the interval has passed, and notifies the
+/// triggers, whose level has been reached.
+///
+/// Called by the scheduler, when it handles the waiting tasks.
+pub(crate) fn sample() {
+	let now = get_timer_ticks();
+	let next = NEXT_SAMPLE.load(Ordering::Relaxed);
+	if now < next
+		|| NEXT_SAMPLE
+			.compare_exchange(
+				next,
+				now + SAMPLE_INTERVAL,
+				Ordering::Relaxed,
+				Ordering::Relaxed,
+			)
+			.is_err()
+	{
+		return;
+	}
+
+	let Some(mut state) = STATE.try_lock() else {
+		return;
+	};
+
+	// The time since the last sample is accounted to the previous level.
+	let elapsed = now - state.last_sample;
+	state.last_sample = now;
+	for (i, watermark) in [Level::Low, Level::Critical].into_iter().enumerate() {
+		if state.level >= watermark {
+			state.total[i] += elapsed;
+			state.period[i] += elapsed;
+		}
+	}
+
+	let periods = (now - state.period_start) / PERIOD;
+	if periods > 0 {
+		let duration = periods * PERIOD;
+		state.period_start += duration;
+		for i in 0..2 {
+			let share = state.period[i].min(duration) * 100 * FIXED_1 / duration;
+			state.period[i] = 0;
+			for (average, exp) in state.averages[i].iter_mut().zip(EXP) {
+				for _ in 0..periods.min(MAX_MISSED) {
+					*average = decay(*average, exp, share);
+				}
+			}
+		}
+	}
+
+	let level = Level::current();
+	let previous = core::mem::replace(&mut state.level, level);
+	if level <= previous {
+		return;
+	}
+
+	if level == Level::Critical {
+		warn!("Memory pressure is critical");
+	}
+
+	let mut fired = Vec::new();
+	state.triggers.retain(|trigger| {
+		let Some(trigger) = trigger.upgrade() else {
+			return false;
+		};
+		if trigger.level > previous && trigger.level <= level {
+			fired.push(trigger);
+		}
+		true
+	});
+	drop(state);
+
+	for trigger in fired {
+		trigger.fire();
+	}
+}
+
+/// Returns the time of the next sample, if descriptors wait for notifications.
+pub(crate) fn next_sample() -> Option<u64> {
+	(TRIGGERS.load(Ordering::Relaxed) > 0).then_some(NEXT_SAMPLE.load(Ordering::Relaxed))
+}
+
+/// Creates a trigger for `level`, which is notified immediately, if the
+/// level has already been reached.
+pub(crate) fn register(level: Level) -> Arc<Trigger> {
+	let current = Level::current();
+	let trigger = Arc::new(Trigger {
+		level,
+		events: AtomicU64::new(u64::from(current >= level)),
+		wakers: InterruptSpinMutex::new(Vec::new()),
+	});
+	TRIGGERS.fetch_add(1, Ordering::Relaxed);
+
+	let mut state = STATE.lock();
+	state.triggers.retain(|trigger| trigger.strong_count() > 0);
+	state.triggers.push(Arc::downgrade(&trigger));
+	drop(state);
+
+	trigger
+}
+
+/// Time under pressure in the format of `/proc/pressure/memory` of Linux.
+pub(crate) fn proc_pressure() -> String {
+	let state = STATE.lock();
+	let (total, averages) = (state.total, state.averages);
+	drop(state);
+
+	let mut s = String::new();
+	for (name, (total, averages)) in ["some", "full"].into_iter().zip(total.iter().zip(averages)) {
+		write!(s, "{name}").unwrap();
+		for (window, average) in [10, 60, 300].into_iter().zip(averages) {
+			// round to two decimal places
+			let average = average + FIXED_1 / 200;
+			let fraction = ((average & (FIXED_1 - 1)) * 100) >> FSHIFT;
+			write!(s, " avg{window}={}.{fraction:02}", average >> FSHIFT).unwrap();
+		}
+		writeln!(s, " total={total}").unwrap();
+	}
+	s
+}
@@ -0,0 +1,94 @@
+//! Behavior of the kernel after a panic.
+//!
+//! The action is selected by the kernel argument `panic=` or at runtime by
+//! [`sys_set_panic_action`](crate::syscalls::sys_set_panic_action):
+//!
+//! - `panic=exit` or `panic=exit:<code>` exits to the hypervisor with the
+//!   error code (default 1). This is the default, which also reboots the
+//!   system with `supervisor=reboot`.
+//! - `panic=halt` halts the core, so that its state can be inspected with a
+//!   debugger.
+//! - `panic=<seconds>` reboots the system after the given number of seconds.
+//!
+//! The error code is passed to uhyve and to semihosting. With error codes above
+//! 1, QEMU on x86-64 exits with `(code << 1) | 1` through the ISA debug exit
+//! device. With SBI, the error code only distinguishes success from failure.
+//! PSCI does not pass an error code.
+
+use core::sync::atomic::{AtomicI32, AtomicU8, Ordering};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+/// Default error code of a panic
+const DEFAULT_EXIT_CODE: i32 = 1;
+
+#[derive(TryFromPrimitive, IntoPrimitive, PartialEq, Eq, Clone, Copy, Debug)]
+#[repr(u8)]
+pub(crate) enum PanicAction {
+	/// Exit to the hypervisor with an error code.
+	Exit = 0,
+	/// Halt the core.
+	Halt = 1,
+	/// Reboot the system after a number of seconds.
+	Reboot = 2,
+}
+
+static ACTION: AtomicU8 = AtomicU8::new(PanicAction::Exit as u8);
+
+/// Error code of [`PanicAction::Exit`] or seconds before [`PanicAction::Reboot`]
+static VALUE: AtomicI32 = AtomicI32::new(DEFAULT_EXIT_CODE);
+
+/// Selects the behavior after a panic.
+pub(crate) fn set(action: PanicAction, value: i32) {
+	// The action is reset, while the value changes, so that a concurrent
+	// panic never combines the previous action with the new value.
+	ACTION.store(PanicAction::Exit.into(), Ordering::SeqCst);
+	VALUE.store(value, Ordering::SeqCst);
+	ACTION.store(action.into(), Ordering::SeqCst);
+}
+
+/// Applies the `panic=` argument.
+pub(crate) fn init() {
+	let Some(arg) = crate::env::panic_action() else {
+		return;
+	};
+
+	let parsed = match arg.split_once(':') {
+		None if arg == "exit" => Some((PanicAction::Exit, DEFAULT_EXIT_CODE)),
+		None if arg == "halt" => Some((PanicAction::Halt, 0)),
+		Some(("exit", code)) => code.parse().ok().map(|code| (PanicAction::Exit, code)),
+		_ => arg
+			.parse::<u32>()
+			.ok()
+			.and_then(|seconds| i32::try_from(seconds).ok())
+			.map(|seconds| (PanicAction::Reboot, seconds)),
+	};
+
+	match parsed {
+		Some((action, value)) => set(action, value),
+		None => error!("could not parse bootarg: panic={arg}"),
+	}
+}
+
+/// Performs the selected action after a panic has been reported.
+pub(crate) fn handle() -> ! {
+	let value = VALUE.load(Ordering::SeqCst);
+	match PanicAction::try_from(ACTION.load(Ordering::SeqCst)) {
+		Ok(PanicAction::Halt) => {
+			crate::arch::interrupts::disable();
+			loop {
+				crate::arch::processor::halt();
+			}
+		}
+		Ok(PanicAction::Reboot) => {
+			// The timer is polled, since the scheduler may be broken.
+			let seconds = u64::try_from(value).unwrap();
+			let end = crate::arch::processor::get_timer_ticks() + seconds * 1_000_000;
+			while crate::arch::processor::get_timer_ticks() < end {
+				core::hint::spin_loop();
+			}
+			crate::syscalls::reboot(1)
+		}
+		Ok(PanicAction::Exit) | Err(_) => crate::scheduler::shutdown(value),
+	}
+}
@@ -0,0 +1,171 @@
+//! CPU affinity of the tasks.
+//!
+//! Each task has a mask of the cores, on which it may run, and a spawned task
+//! inherits the mask of its parent. Tasks, which are spawned without selecting
+//! a core, are distributed round-robin among the cores of the mask. A core,
+//! which is selected explicitly, is used, even if it is not part of the mask.
+//!
+//! A blocked task cannot move, since its core keeps it until its wakeup.
+//! Therefore, a task moves to a core of its mask, when it changes its own mask
+//! or yields with `sys_yield`. A changed mask of another task takes effect,
+//! when this task yields. The load balancing moves ready tasks only to cores
+//! of their masks.
+
+use alloc::collections::BTreeMap;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::arch::core_local::{core_id, core_scheduler};
+use crate::arch::get_processor_count;
+use crate::env;
+use crate::errno::Errno;
+use crate::scheduler::task::TaskId;
+use crate::scheduler::{CoreId, HOUSEKEEPING_CORE, get_task_handle};
+
+/// Largest number of cores, which a mask describes
+const MAX_CORES: usize = 256;
+
+/// Masks of the tasks, which may not run on all cores
+static AFFINITIES: InterruptTicketMutex<BTreeMap<TaskId, CpuSet>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+
+/// Set of cores like `cpu_set_t` of Linux
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CpuSet([u64; MAX_CORES / 64]);
+
+impl CpuSet {
+	/// Returns the set of all cores of the system.
+	pub fn all() -> Self {
+		let mut set = Self([0; MAX_CORES / 64]);
+		for core_id in 0..get_processor_count().min(MAX_CORES as u32) {
+			set.insert(core_id);
+		}
+		set
+	}
+
+	/// Reads a set from the bitmask `bytes` in the format of `cpu_set_t`.
+	///
+	/// Cores, which do not exist, are ignored.
+	pub fn from_bytes(bytes: &[u8]) -> Self {
+		let mut set = Self([0; MAX_CORES / 64]);
+		for core_id in 0..get_processor_count().min(MAX_CORES as u32) {
+			let byte = bytes.get(core_id as usize / 8).copied().unwrap_or(0);
+			if byte & (1 << (core_id % 8)) != 0 {
+				set.insert(core_id);
+			}
+		}
+		set
+	}
+
+	/// Writes the set as bitmask in the format of `cpu_set_t` to `bytes`.
+	pub fn write_bytes(&self, bytes: &mut [u8]) {
+		bytes.fill(0);
+		for core_id in self.iter() {
+			if let Some(byte) = bytes.get_mut(core_id as usize / 8) {
+				*byte |= 1 << (core_id % 8);
+			}
+		}
+	}
+
+	pub fn insert(&mut self, core_id: CoreId) {
+		self.0[core_id as usize / 64] |= 1 << (core_id % 64);
+	}
+
+	pub fn contains(&self, core_id: CoreId) -> bool {
+		(core_id as usize) < MAX_CORES && self.0[core_id as usize / 64] & (1 << (core_id % 64)) != 0
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0.iter().all(|word| *word == 0)
+	}
+
+	/// Returns the cores of the set in ascending order.
+	pub fn iter(&self) -> impl Iterator<Item = CoreId> + Clone + '_ {
+		(0..MAX_CORES as CoreId).filter(|core_id| self.contains(*core_id))
+	}
+
+	/// Selects the next core of the round robin among the cores of the set.
+	///
+	/// With `housekeeping=dedicated`, the housekeeping core is skipped, unless
+	/// it is the only core of the set.
+	pub fn next_core(&self) -> CoreId {
+		static CORE_COUNTER: AtomicU32 = AtomicU32::new(1);
+
+		let counter = CORE_COUNTER.fetch_add(1, Ordering::SeqCst);
+		let cores = self
+			.iter()
+			.filter(|core_id| *core_id != HOUSEKEEPING_CORE || !env::dedicated_housekeeping());
+		let count = cores.clone().count();
+		if count == 0 {
+			return self.iter().next().unwrap_or(HOUSEKEEPING_CORE);
+		}
+		cores.clone().nth(counter as usize % count).unwrap()
+	}
+}
+
+/// Returns the mask of the task `id`.
+pub(crate) fn get(id: TaskId) -> CpuSet {
+	AFFINITIES
+		.lock()
+		.get(&id)
+		.copied()
+		.unwrap_or_else(CpuSet::all)
+}
+
+/// Returns the mask of the task `id` or `ESRCH`, if the task does not exist.
+pub(crate) fn query(id: TaskId) -> Result<CpuSet, Errno> {
+	if get_task_handle(id).is_none() {
+		return Err(Errno::Srch);
+	}
+	Ok(get(id))
+}
+
+/// Sets the mask of the task `id` to `set`.
+///
+/// Fails with `ESRCH`, if the task does not exist, and with `EINVAL`, if the
+/// set contains no core of the system.
+pub(crate) fn set(id: TaskId, set: CpuSet) -> Result<(), Errno> {
+	if get_task_handle(id).is_none() {
+		return Err(Errno::Srch);
+	}
+	if set.is_empty() {
+		return Err(Errno::Inval);
+	}
+
+	let mut affinities = AFFINITIES.lock();
+	if set == CpuSet::all() {
+		affinities.remove(&id);
+	} else {
+		affinities.insert(id, set);
+	}
+	Ok(())
+}
+
+/// Passes the mask of the task `parent` on to its new child `child`.
+pub(crate) fn inherit(parent: TaskId, child: TaskId) {
+	let mut affinities = AFFINITIES.lock();
+	if let Some(set) = affinities.get(&parent).copied() {
+		affinities.insert(child, set);
+	}
+}
+
+/// Forgets the mask of the finished task `id`.
+pub(crate) fn remove(id: TaskId) {
+	AFFINITIES.lock().remove(&id);
+}
+
+/// Moves the current task to a core of its mask, if its core is not part of it.
+///
+/// The current task must not wait for an event, since its handle in a wait
+/// queue would still refer to the previous core.
+pub(crate) fn migrate() {
+	let scheduler = core_scheduler();
+	let set = get(scheduler.get_current_task_id());
+	if set.contains(core_id()) {
+		return;
+	}
+
+	#[cfg(feature = "smp")]
+	scheduler.migrate_current_task(set.next_core());
+}
@@ -0,0 +1,100 @@
+//! Load balancing between the cores.
+//!
+//! Each core publishes the number of its ready tasks and whether it is idle.
+//! Before an idle core halts, it asks the core with the most ready tasks for
+//! one of them, which this core passes on at its next scheduling decision or
+//! wakeup interrupt. Since a halted core does not notice new work, a busy core
+//! also checks every [`BALANCE_INTERVAL`] at its scheduling decisions, whether
+//! another core is idle, and passes one of its ready tasks on to it.
+//!
+//! Only ready tasks move, and a task moves only to a core of its mask. Tasks,
+//! whose core has been selected explicitly at spawn, stay on this core, and
+//! with `housekeeping=dedicated`, no task moves to the housekeeping core. A
+//! moved task may still have a handle with its previous core in a wait queue,
+//! e.g., if its timeout has woken it up. Such wakeups are forwarded to the core
+//! in the list of tasks.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use hermit_sync::InterruptSpinMutex;
+
+use crate::env;
+use crate::scheduler::{CoreId, HOUSEKEEPING_CORE};
+
+/// Interval in microseconds, after which a busy core looks for idle cores
+pub(crate) const BALANCE_INTERVAL: u64 = 10_000;
+
+/// Interval in microseconds, after which an idle core asks for a task again
+pub(crate) const STEAL_INTERVAL: u64 = 1_000;
+
+/// Loads of all cores
+static CORES: InterruptSpinMutex<Vec<&'static CoreLoad>> = InterruptSpinMutex::new(Vec::new());
+
+pub(crate) struct CoreLoad {
+	core_id: CoreId,
+	/// Number of ready tasks, which wait for the core
+	ready: AtomicU32,
+	/// The core runs its idle task.
+	idle: AtomicBool,
+}
+
+impl CoreLoad {
+	/// Creates the load of the core `core_id`.
+	pub fn register(core_id: CoreId) -> &'static Self {
+		let load: &'static Self = Box::leak(Box::new(Self {
+			core_id,
+			ready: AtomicU32::new(0),
+			idle: AtomicBool::new(true),
+		}));
+		CORES.lock().push(load);
+		load
+	}
+
+	/// Publishes the number of ready tasks and whether the core is idle.
+	///
+	/// This must only be called by the core, which owns the load.
+	pub fn publish(&self, ready: usize, idle: bool) {
+		self.ready
+			.store(ready.try_into().unwrap_or(u32::MAX), Ordering::Relaxed);
+		self.idle.store(idle, Ordering::Relaxed);
+	}
+}
+
+/// Whether tasks may move to the core `core_id`.
+fn accepts_tasks(core_id: CoreId) -> bool {
+	core_id != HOUSEKEEPING_CORE || !env::dedicated_housekeeping()
+}
+
+/// Returns the core with the most ready tasks, which the core `thief` may ask
+/// for one of them.
+pub(crate) fn busiest_core(thief: CoreId) -> Option<CoreId> {
+	if !accepts_tasks(thief) {
+		return None;
+	}
+
+	CORES
+		.lock()
+		.iter()
+		.filter(|load| load.core_id != thief)
+		.map(|load| (load.core_id, load.ready.load(Ordering::Relaxed)))
+		.filter(|(_, ready)| *ready > 0)
+		.max_by_key(|(_, ready)| *ready)
+		.map(|(core_id, _)| core_id)
+}
+
+/// Returns an idle core other than `core_id` without ready tasks, to which
+/// tasks may move.
+pub(crate) fn idle_core(core_id: CoreId) -> Option<CoreId> {
+	CORES
+		.lock()
+		.iter()
+		.find(|load| {
+			load.core_id != core_id
+				&& load.idle.load(Ordering::Relaxed)
+				&& load.ready.load(Ordering::Relaxed) == 0
+				&& accepts_tasks(load.core_id)
+		})
+		.map(|load| load.core_id)
+}
@@ -0,0 +1,38 @@
+//! Cancellation of the pending operations of a finished task.
+//!
+//! A task finishes only on its own core, while it runs. However, it may still
+//! be registered as a waiter, if it has blocked itself, e.g., with
+//! `sys_block_current_task`, and exits before it has switched to another task.
+//! Its core removes it from the blocked tasks, and [`task_exited`] removes it
+//! from the remaining wait queues, so that it is not woken up after it has
+//! finished. In addition, its NVMe IO queue pairs are marked as orphaned, so
+//! that the driver reclaims them later without waiting for the device here, and
+//! its finished children are reaped, since nobody can wait for them anymore.
+
+use crate::scheduler::task::TaskHandle;
+use crate::scheduler::{CHILDREN, WAITING_TASKS};
+use crate::synch::futex;
+
+/// Cancels the pending operations of the task `handle`, which has just finished.
+///
+/// If the task has blocked itself, `blocked` is set.
+pub(super) fn task_exited(handle: TaskHandle, blocked: bool) {
+	let id = handle.get_id();
+	crate::syscalls::forget_blocked_task(id);
+
+	if blocked {
+		futex::cancel_wait(handle);
+		for queue in WAITING_TASKS.lock().values_mut() {
+			queue.retain(|handle| handle.get_id() != id);
+		}
+	}
+
+	#[cfg(feature = "nvme")]
+	if let Some(driver) = crate::drivers::pci::get_nvme_driver() {
+		driver.lock().orphan_io_queue_pairs(id);
+	}
+
+	CHILDREN
+		.lock()
+		.retain(|_, child| child.parent != id || child.exit_code.is_none());
+}
@@ -0,0 +1,106 @@
+//! Load averages of the cores.
+//!
+//! Like Linux, each core samples the number of its runnable tasks every five
+//! seconds and averages the samples exponentially over 1, 5, and 15 minutes.
+//! The averages are fixed-point numbers with [`FSHIFT`] fractional bits.
+//!
+//! A core samples the load, when it reschedules. If a core has been halted
+//! for several intervals, the missed samples are assumed to be zero.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use hermit_sync::SpinMutex;
+
+use crate::scheduler::CoreId;
+
+/// Number of fractional bits of the load averages
+pub(crate) const FSHIFT: u32 = 11;
+const FIXED_1: u64 = 1 << FSHIFT;
+
+/// Decay factors of the averages over 1, 5, and 15 minutes per interval
+const EXP: [u64; 3] = [1884, 2014, 2037];
+
+/// Interval between two samples in microseconds
+const INTERVAL: u64 = 5_000_000;
+
+/// Number of intervals, after which the averages have decayed to zero
+const MAX_MISSED: u64 = 15 * 60 / 5;
+
+/// Load averages of all cores
+static LOADS: SpinMutex<Vec<&'static LoadAverage>> = SpinMutex::new(Vec::new());
+
+pub(crate) struct LoadAverage {
+	averages: [AtomicU64; 3],
+	/// Number of runnable tasks at the last sample
+	runnable: AtomicU32,
+	/// Time of the next sample in microseconds since boot
+	next_sample: AtomicU64,
+}
+
+fn decay(load: u64, exp: u64, runnable: u32) -> u64 {
+	let active = u64::from(runnable) * FIXED_1;
+	let mut new_load = load * exp + active * (FIXED_1 - exp);
+	// round up while the load increases, so that it reaches the number of tasks
+	if active >= load {
+		new_load += FIXED_1 - 1;
+	}
+	new_load >> FSHIFT
+}
+
+impl LoadAverage {
+	/// Creates the load average of the core `core_id`.
+	pub fn register(core_id: CoreId) -> &'static Self {
+		let load: &'static Self = Box::leak(Box::new(Self {
+			averages: [const { AtomicU64::new(0) }; 3],
+			runnable: AtomicU32::new(0),
+			next_sample: AtomicU64::new(0),
+		}));
+		LOADS.lock().insert(core_id.try_into().unwrap(), load);
+		load
+	}
+
+	/// Samples the number of runnable tasks, if the interval has passed.
+	///
+	/// This must only be called by the core, which owns the load average.
+	pub fn update(&self, now: u64, runnable: impl FnOnce() -> u32) {
+		let next_sample = self.next_sample.load(Ordering::Relaxed);
+		if now < next_sample {
+			return;
+		}
+
+		let intervals = (now - next_sample) / INTERVAL + 1;
+		self.next_sample
+			.store(next_sample + intervals * INTERVAL, Ordering::Relaxed);
+
+		let runnable = runnable();
+		self.runnable.store(runnable, Ordering::Relaxed);
+		for (average, exp) in self.averages.iter().zip(EXP) {
+			let mut load = average.load(Ordering::Relaxed);
+			for _ in 1..intervals.min(MAX_MISSED) {
+				load = decay(load, exp, 0);
+			}
+			average.store(decay(load, exp, runnable), Ordering::Relaxed);
+		}
+	}
+
+	pub fn averages(&self) -> [u64; 3] {
+		self.averages
+			.each_ref()
+			.map(|average| average.load(Ordering::Relaxed))
+	}
+}
+
+/// Returns the load averages of all cores summed up and the number of runnable tasks.
+pub(crate) fn total() -> ([u64; 3], u32) {
+	let mut averages = [0; 3];
+	let mut runnable = 0;
+	for load in LOADS.lock().iter() {
+		for (total, average) in averages.iter_mut().zip(load.averages()) {
+			*total += average;
+		}
+		runnable += load.runnable.load(Ordering::Relaxed);
+	}
+	(averages, runnable)
+}
@@ -1,39 +1,54 @@
 #![allow(clippy::type_complexity)]
 
 use alloc::boxed::Box;
-use alloc::collections::{BTreeMap, VecDeque};
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
 use alloc::rc::Rc;
 use alloc::sync::Arc;
-#[cfg(feature = "smp")]
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::ptr;
 #[cfg(all(target_arch = "x86_64", feature = "smp"))]
 use core::sync::atomic::AtomicBool;
-use core::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 
-use ahash::RandomState;
 use crossbeam_utils::Backoff;
-use hashbrown::HashMap;
 use hermit_sync::*;
 #[cfg(target_arch = "riscv64")]
 use riscv::register::sstatus;
 
 use crate::arch::core_local::*;
+use crate::arch::interrupts;
 #[cfg(target_arch = "riscv64")]
 use crate::arch::switch::switch_to_task;
 #[cfg(target_arch = "x86_64")]
 use crate::arch::switch::{switch_to_fpu_owner, switch_to_task};
-use crate::arch::{get_processor_count, interrupts};
 use crate::errno::Errno;
+use crate::fd::table::FdTable;
 use crate::fd::{FileDescriptor, ObjectInterface};
 use crate::kernel::scheduler::TaskStacks;
+use crate::scheduler::realtime::Policy;
 use crate::scheduler::task::*;
-use crate::{arch, io};
+use crate::synch::futex::{self, Flags};
+use crate::{arch, env, io};
 
+pub(crate) mod affinity;
+#[cfg(feature = "smp")]
+mod balance;
+mod cancel;
+pub(crate) mod load;
+pub(crate) mod realtime;
+pub(crate) mod supervisor;
 pub mod task;
+pub(crate) mod unwind;
+
+/// Maximum number of empty futex wait queues, which a core keeps
+const FUTEX_QUEUE_POOL_SIZE: usize = 32;
 
 static NO_TASKS: AtomicU32 = AtomicU32::new(0);
+/// ID of the next task, the IDs are never reused
+static NEXT_TID: AtomicU64 = AtomicU64::new(0);
+/// Number of switches between tasks on all cores
+static CONTEXT_SWITCHES: AtomicU64 = AtomicU64::new(0);
 /// Map between Core ID and per-core scheduler
 #[cfg(feature = "smp")]
 static SCHEDULER_INPUTS: SpinMutex<Vec<&InterruptTicketMutex<SchedulerInput>>> =
@@ -43,19 +58,59 @@ static CORE_HLT_STATE: SpinMutex<Vec<&AtomicBool>> = SpinMutex::new(Vec::new());
 /// Map between Task ID and Queue of waiting tasks
 static WAITING_TASKS: InterruptTicketMutex<BTreeMap<TaskId, VecDeque<TaskHandle>>> =
 	InterruptTicketMutex::new(BTreeMap::new());
-/// Map between Task ID and TaskHandle
+/// Map between Task ID and TaskHandle of the tasks, which have not finished yet
 static TASKS: InterruptTicketMutex<BTreeMap<TaskId, TaskHandle>> =
 	InterruptTicketMutex::new(BTreeMap::new());
+/// Spawned tasks, which have not been reaped by [`waitpid`] or [`join`]
+static CHILDREN: InterruptTicketMutex<BTreeMap<TaskId, Child>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+/// Tasks, whose children remain zombies until they have been reaped
+static RETAINING: InterruptTicketMutex<BTreeSet<TaskId>> =
+	InterruptTicketMutex::new(BTreeSet::new());
+/// Futex, which is incremented whenever a task finishes
+static CHILD_EVENTS: AtomicU32 = AtomicU32::new(0);
+
+/// A task and the task, which has spawned it
+struct Child {
+	parent: TaskId,
+	/// Exit code, once the task has finished
+	exit_code: Option<i32>,
+	/// Nobody waits for the task, so that it is reaped as soon as it finishes.
+	detached: bool,
+}
 
 /// Unique identifier for a core.
 pub type CoreId = u32;
 
+/// Core, which handles the interrupts of the devices, the network, the
+/// console output, and the timers of the kernel, if the other cores are
+/// isolated by `housekeeping=dedicated`
+pub(crate) const HOUSEKEEPING_CORE: CoreId = 0;
+
+/// Whether the current core is left to the application.
+///
+/// Isolated cores only arm their timer for the wakeups of their tasks and
+/// leave the remaining work of the kernel to [`HOUSEKEEPING_CORE`].
+pub(crate) fn is_isolated() -> bool {
+	// The boot arguments are parsed before other cores are started.
+	core_id() != HOUSEKEEPING_CORE && env::dedicated_housekeeping()
+}
+
 #[cfg(feature = "smp")]
 pub(crate) struct SchedulerInput {
 	/// Queue of new tasks
 	new_tasks: VecDeque<NewTask>,
 	/// Queue of task, which are wakeup by another core
 	wakeup_tasks: VecDeque<TaskHandle>,
+	/// Queue of tasks, which move from another core to this core
+	migrated_tasks: VecDeque<MigratedTask>,
+	/// Queue of new priorities and scheduling classes of the tasks of this core
+	priority_changes: VecDeque<(TaskId, Priority, Option<Policy>)>,
+	/// Idle cores, which ask for a ready task of this core
+	steal_requests: VecDeque<CoreId>,
+	/// Earliest network timer of the isolated cores
+	#[cfg(feature = "net")]
+	network_wakeup_time: Option<u64>,
 }
 
 #[cfg(feature = "smp")]
@@ -64,10 +119,23 @@ impl SchedulerInput {
 		Self {
 			new_tasks: VecDeque::new(),
 			wakeup_tasks: VecDeque::new(),
+			migrated_tasks: VecDeque::new(),
+			priority_changes: VecDeque::new(),
+			steal_requests: VecDeque::new(),
+			#[cfg(feature = "net")]
+			network_wakeup_time: None,
 		}
 	}
 }
 
+/// Task, which moves to another core
+#[cfg(feature = "smp")]
+struct MigratedTask(Task);
+
+// SAFETY: The task has been switched out and is only accessed by its new core.
+#[cfg(feature = "smp")]
+unsafe impl Send for MigratedTask {}
+
 #[cfg_attr(any(target_arch = "x86_64", target_arch = "aarch64"), repr(align(128)))]
 #[cfg_attr(
 	not(any(target_arch = "x86_64", target_arch = "aarch64")),
@@ -81,15 +149,36 @@ pub(crate) struct PerCoreScheduler {
 	current_task: Rc<RefCell<Task>>,
 	/// Idle Task
 	idle_task: Rc<RefCell<Task>>,
-	/// Task that currently owns the FPU
+	/// Task whose state is currently in the FPU registers, if it has to be saved
 	#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
-	fpu_owner: Rc<RefCell<Task>>,
+	fpu_owner: Option<Rc<RefCell<Task>>>,
 	/// Queue of tasks, which are ready
 	ready_queue: PriorityTaskQueue,
 	/// Queue of tasks, which are finished and can be released
 	finished_tasks: VecDeque<Rc<RefCell<Task>>>,
+	/// Core, to which the current task moves at the next reschedule
+	#[cfg(feature = "smp")]
+	migrate_to: Option<CoreId>,
+	/// Tasks, which move to another core, once this core has switched away from them
+	#[cfg(feature = "smp")]
+	migrating_tasks: VecDeque<(Rc<RefCell<Task>>, CoreId)>,
 	/// Queue of blocked tasks, sorted by wakeup time.
 	blocked_tasks: BlockedTaskQueue,
+	/// Empty wait queues of futexes, which are reused by the next waits
+	futex_queues: Vec<TaskHandlePriorityQueue>,
+	/// Load average of this core
+	load: &'static load::LoadAverage,
+	/// Number of ready tasks of this core, which other cores see
+	#[cfg(feature = "smp")]
+	balance: &'static balance::CoreLoad,
+	/// Time of the next check for idle cores
+	#[cfg(feature = "smp")]
+	next_balance: u64,
+	/// Earliest time of the next steal request of this core, while it is idle
+	#[cfg(feature = "smp")]
+	next_steal: u64,
+	/// The current task gives way to the tasks of its priority at the next reschedule.
+	yielded: bool,
 }
 
 pub(crate) trait PerCoreSchedulerExt {
@@ -97,6 +186,14 @@ pub(crate) trait PerCoreSchedulerExt {
 	/// Interrupt flag will be cleared during the reschedule
 	fn reschedule(self);
 
+	/// Switches to a ready real-time task, which has a higher priority than
+	/// the current task, e.g., after the current task has woken it up.
+	fn preempt(self);
+
+	/// Lets the ready tasks of the same priority run, even if the current
+	/// task is a real-time task.
+	fn yield_now(self);
+
 	#[cfg(feature = "net")]
 	fn add_network_timer(self, wakeup_time: Option<u64>);
 
@@ -109,15 +206,12 @@ impl PerCoreSchedulerExt for &mut PerCoreScheduler {
 	fn reschedule(self) {
 		without_interrupts(|| {
 			if let Some(last_stack_pointer) = self.scheduler() {
-				let (new_stack_pointer, is_idle) = {
-					let borrowed = self.current_task.borrow();
-					(
-						borrowed.last_stack_pointer,
-						borrowed.status == TaskStatus::Idle,
-					)
-				};
-
-				if is_idle || Rc::ptr_eq(&self.current_task, &self.fpu_owner) {
+				let new_stack_pointer = self.current_task.borrow().last_stack_pointer;
+
+				// The FPU registers are switched lazily on the first use of the FPU
+				// after a switch. This also applies to the idle task, so that kernel
+				// code, which uses the FPU, does not clobber the state of the FPU owner.
+				if self.is_fpu_owner() {
 					unsafe {
 						switch_to_fpu_owner(
 							last_stack_pointer,
@@ -172,8 +266,39 @@ impl PerCoreSchedulerExt for &mut PerCoreScheduler {
 		without_interrupts(|| self.scheduler());
 	}
 
+	fn preempt(self) {
+		let preempted = without_interrupts(|| {
+			let prio = self.ready_queue.get_highest_priority();
+			prio >= REALTIME_PRIO && prio > self.current_task.borrow().prio
+		});
+		if preempted {
+			self.reschedule();
+		}
+	}
+
+	fn yield_now(self) {
+		self.yielded = true;
+		self.reschedule();
+	}
+
 	#[cfg(feature = "net")]
 	fn add_network_timer(self, wakeup_time: Option<u64>) {
+		// isolated cores pass their network timers on to the housekeeping core
+		#[cfg(feature = "smp")]
+		if is_isolated() {
+			if let Some(wakeup_time) = wakeup_time {
+				let mut input = get_scheduler_input(HOUSEKEEPING_CORE).lock();
+				input.network_wakeup_time = Some(
+					input
+						.network_wakeup_time
+						.map_or(wakeup_time, |time| time.min(wakeup_time)),
+				);
+				drop(input);
+				arch::wakeup_core(HOUSEKEEPING_CORE);
+			}
+			return;
+		}
+
 		without_interrupts(|| {
 			self.blocked_tasks.add_network_timer(wakeup_time);
 		});
@@ -181,6 +306,15 @@ impl PerCoreSchedulerExt for &mut PerCoreScheduler {
 
 	fn exit(self, exit_code: i32) -> ! {
 		without_interrupts(|| {
+			// A task may finish after it has blocked itself, but before it has
+			// switched to another task. It must not be woken up anymore.
+			let handle = self.get_current_task_handle();
+			let blocked = self.current_task.borrow().status == TaskStatus::Blocked;
+			if blocked {
+				self.blocked_tasks.cancel(handle.get_id());
+				self.current_task.borrow_mut().status = TaskStatus::Running;
+			}
+
 			// Get the current task.
 			let mut current_task_borrowed = self.current_task.borrow_mut();
 			assert_ne!(
@@ -199,6 +333,17 @@ impl PerCoreSchedulerExt for &mut PerCoreScheduler {
 
 			let current_id = current_task_borrowed.id;
 			drop(current_task_borrowed);
+			TASKS.lock().remove(&current_id);
+			RETAINING.lock().remove(&current_id);
+			affinity::remove(current_id);
+			realtime::remove(current_id);
+			cancel::task_exited(handle, blocked);
+
+			// The FPU state of a finished task does not have to be saved.
+			#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+			if self.is_fpu_owner() {
+				self.fpu_owner = None;
+			}
 
 			// wakeup tasks, which are waiting for task with the identifier id
 			if let Some(mut queue) = WAITING_TASKS.lock().remove(&current_id) {
@@ -206,6 +351,21 @@ impl PerCoreSchedulerExt for &mut PerCoreScheduler {
 					self.custom_wakeup(task);
 				}
 			}
+
+			// The task remains a zombie until it has been reaped, unless it has
+			// been detached or its parent has already finished and cannot reap
+			// it anymore.
+			let mut children = CHILDREN.lock();
+			if let Some(child) = children.get_mut(&current_id) {
+				if !child.detached && get_task_handle(child.parent).is_some() {
+					child.exit_code = Some(exit_code);
+					drop(children);
+					CHILD_EVENTS.fetch_add(1, Ordering::SeqCst);
+					futex::futex_wake(&CHILD_EVENTS, i32::MAX);
+				} else {
+					children.remove(&current_id);
+				}
+			}
 		});
 
 		self.reschedule();
@@ -219,12 +379,10 @@ struct NewTask {
 	arg: usize,
 	prio: Priority,
 	core_id: CoreId,
+	/// The core has been selected explicitly.
+	pinned: bool,
 	stacks: TaskStacks,
-	object_map: Arc<
-		RwSpinLock<
-			HashMap<FileDescriptor, Arc<async_lock::RwLock<dyn ObjectInterface>>, RandomState>,
-		>,
-	>,
+	object_map: Arc<FdTable>,
 }
 
 impl From<NewTask> for Task {
@@ -235,10 +393,12 @@ impl From<NewTask> for Task {
 			arg,
 			prio,
 			core_id,
+			pinned,
 			stacks,
 			object_map,
 		} = value;
 		let mut task = Self::new(tid, core_id, TaskStatus::Ready, prio, stacks, object_map);
+		task.pinned = pinned;
 		task.create_stack_frame(func, arg);
 		task
 	}
@@ -246,15 +406,21 @@ impl From<NewTask> for Task {
 
 impl PerCoreScheduler {
 	/// Spawn a new task.
+	///
+	/// A `pinned` task is not moved to another core by the load balancing.
+	/// Fails with `EOVERFLOW` without creating the task, if its ID would
+	/// exceed `max_id`.
 	pub unsafe fn spawn(
 		func: unsafe extern "C" fn(usize),
 		arg: usize,
 		prio: Priority,
 		core_id: CoreId,
+		pinned: bool,
 		stack_size: usize,
-	) -> TaskId {
+		max_id: TaskId,
+	) -> Result<TaskId, Errno> {
 		// Create the new task.
-		let tid = get_tid();
+		let tid = get_tid(max_id).ok_or(Errno::Overflow)?;
 		let stacks = TaskStacks::new(stack_size);
 		let new_task = NewTask {
 			tid,
@@ -262,15 +428,27 @@ impl PerCoreScheduler {
 			arg,
 			prio,
 			core_id,
+			pinned,
 			stacks,
 			object_map: core_scheduler().get_current_task_object_map(),
 		};
+		affinity::inherit(core_scheduler().get_current_task_id(), tid);
 
 		// Add it to the task lists.
+		let parent = core_scheduler().get_current_task_id();
+		let detached = !RETAINING.lock().contains(&parent);
 		let wakeup = {
 			#[cfg(feature = "smp")]
 			let mut input_locked = get_scheduler_input(core_id).lock();
 			WAITING_TASKS.lock().insert(tid, VecDeque::with_capacity(1));
+			CHILDREN.lock().insert(
+				tid,
+				Child {
+					parent,
+					exit_code: None,
+					detached,
+				},
+			);
 			TASKS.lock().insert(
 				tid,
 				TaskHandle::new(
@@ -307,47 +485,50 @@ impl PerCoreScheduler {
 			arch::wakeup_core(core_id);
 		}
 
-		tid
+		Ok(tid)
 	}
 
 	#[cfg(feature = "newlib")]
-	fn clone_impl(&self, func: extern "C" fn(usize), arg: usize) -> TaskId {
-		static NEXT_CORE_ID: AtomicU32 = AtomicU32::new(1);
-
-		// Get the Core ID of the next CPU.
-		let core_id: CoreId = {
-			// Increase the CPU number by 1.
-			let id = NEXT_CORE_ID.fetch_add(1, Ordering::SeqCst);
-
-			// Check for overflow.
-			if id == arch::get_processor_count() {
-				NEXT_CORE_ID.store(0, Ordering::SeqCst);
-				0
-			} else {
-				id
-			}
-		};
-
+	fn clone_impl(
+		&self,
+		func: extern "C" fn(usize),
+		arg: usize,
+		max_id: TaskId,
+	) -> Result<TaskId, Errno> {
 		// Get the current task.
 		let current_task_borrowed = self.current_task.borrow();
 
+		// Get the Core ID of the next CPU of the mask of the current task.
+		let core_id = affinity::get(current_task_borrowed.id).next_core();
+
 		// Clone the current task.
-		let tid = get_tid();
+		let tid = get_tid(max_id).ok_or(Errno::Overflow)?;
 		let clone_task = NewTask {
 			tid,
 			func,
 			arg,
 			prio: current_task_borrowed.prio,
 			core_id,
+			pinned: false,
 			stacks: TaskStacks::new(current_task_borrowed.stacks.get_user_stack_size()),
 			object_map: current_task_borrowed.object_map.clone(),
 		};
+		affinity::inherit(current_task_borrowed.id, tid);
 
 		// Add it to the task lists.
+		let detached = !RETAINING.lock().contains(&current_task_borrowed.id);
 		let wakeup = {
 			#[cfg(feature = "smp")]
 			let mut input_locked = get_scheduler_input(core_id).lock();
 			WAITING_TASKS.lock().insert(tid, VecDeque::with_capacity(1));
+			CHILDREN.lock().insert(
+				tid,
+				Child {
+					parent: current_task_borrowed.id,
+					exit_code: None,
+					detached,
+				},
+			);
 			TASKS.lock().insert(
 				tid,
 				TaskHandle::new(
@@ -382,12 +563,21 @@ impl PerCoreScheduler {
 			arch::wakeup_core(core_id);
 		}
 
-		tid
+		Ok(tid)
 	}
 
+	/// Creates a new thread based on the configuration of the current thread.
+	///
+	/// Fails with `EOVERFLOW` without creating the thread, if its ID would
+	/// exceed `max_id`.
 	#[cfg(feature = "newlib")]
-	pub fn clone(&self, func: extern "C" fn(usize), arg: usize) -> TaskId {
-		without_interrupts(|| self.clone_impl(func, arg))
+	pub fn clone(
+		&self,
+		func: extern "C" fn(usize),
+		arg: usize,
+		max_id: TaskId,
+	) -> Result<TaskId, Errno> {
+		without_interrupts(|| self.clone_impl(func, arg, max_id))
 	}
 
 	/// Returns `true` if a reschedule is required
@@ -400,6 +590,11 @@ impl PerCoreScheduler {
 	#[inline]
 	pub fn handle_waiting_tasks(&mut self) {
 		without_interrupts(|| {
+			if !is_isolated() {
+				crate::drivers::irq_storm::unmask_expired();
+				crate::mm::pressure::sample();
+				crate::console::flush_deferred();
+			}
 			crate::executor::run();
 			self.blocked_tasks
 				.handle_waiting_tasks(&mut self.ready_queue);
@@ -409,8 +604,9 @@ impl PerCoreScheduler {
 	#[cfg(not(feature = "smp"))]
 	pub fn custom_wakeup(&mut self, task: TaskHandle) {
 		without_interrupts(|| {
-			let task = self.blocked_tasks.custom_wakeup(task);
-			self.ready_queue.push(task);
+			if let Some(task) = self.blocked_tasks.custom_wakeup(task) {
+				self.ready_queue.push(task);
+			}
 		});
 	}
 
@@ -418,16 +614,25 @@ impl PerCoreScheduler {
 	pub fn custom_wakeup(&mut self, task: TaskHandle) {
 		if task.get_core_id() == self.core_id {
 			without_interrupts(|| {
-				let task = self.blocked_tasks.custom_wakeup(task);
-				self.ready_queue.push(task);
+				if let Some(task) = self.blocked_tasks.custom_wakeup(task) {
+					self.ready_queue.push(task);
+				} else {
+					self.forward_wakeup(task);
+				}
 			});
 		} else {
-			get_scheduler_input(task.get_core_id())
-				.lock()
-				.wakeup_tasks
-				.push_back(task);
-			// Wake up the CPU
-			arch::wakeup_core(task.get_core_id());
+			send_wakeup(task.get_core_id(), task);
+		}
+	}
+
+	/// Passes the wakeup of `task`, which is not blocked on this core, on to
+	/// the core, to which the task has moved since its handle has been taken.
+	#[cfg(feature = "smp")]
+	fn forward_wakeup(&self, task: TaskHandle) {
+		if let Some(handle) = get_task_handle(task.get_id())
+			&& handle.get_core_id() != self.core_id
+		{
+			send_wakeup(handle.get_core_id(), handle);
 		}
 	}
 
@@ -439,6 +644,21 @@ impl PerCoreScheduler {
 		});
 	}
 
+	/// Returns an empty wait queue for a futex from the pool of this core.
+	pub fn take_futex_queue(&mut self) -> TaskHandlePriorityQueue {
+		self.futex_queues.pop().unwrap_or_default()
+	}
+
+	/// Returns the empty wait queue of a futex to the pool of this core.
+	///
+	/// The queue keeps its buffers, so that reusing it does not allocate.
+	pub fn put_futex_queue(&mut self, queue: TaskHandlePriorityQueue) {
+		debug_assert!(queue.is_empty());
+		if self.futex_queues.len() < FUTEX_QUEUE_POOL_SIZE {
+			self.futex_queues.push(queue);
+		}
+	}
+
 	#[inline]
 	pub fn get_current_task_handle(&self) -> TaskHandle {
 		without_interrupts(|| {
@@ -459,13 +679,7 @@ impl PerCoreScheduler {
 	}
 
 	#[inline]
-	pub fn get_current_task_object_map(
-		&self,
-	) -> Arc<
-		RwSpinLock<
-			HashMap<FileDescriptor, Arc<async_lock::RwLock<dyn ObjectInterface>>, RandomState>,
-		>,
-	> {
+	pub fn get_current_task_object_map(&self) -> Arc<FdTable> {
 		without_interrupts(|| self.current_task.borrow().object_map.clone())
 	}
 
@@ -476,11 +690,7 @@ impl PerCoreScheduler {
 		&self,
 		fd: FileDescriptor,
 	) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
-		without_interrupts(|| {
-			let current_task = self.current_task.borrow();
-			let object_map = current_task.object_map.read();
-			object_map.get(&fd).cloned().ok_or(Errno::Badf)
-		})
+		without_interrupts(|| self.current_task.borrow().object_map.get(fd))
 	}
 
 	/// Creates a new map between file descriptor and their IO interface and
@@ -488,28 +698,21 @@ impl PerCoreScheduler {
 	#[cfg(feature = "common-os")]
 	#[cfg_attr(not(target_arch = "x86_64"), expect(dead_code))]
 	pub fn recreate_objmap(&self) -> io::Result<()> {
-		let mut map = HashMap::<
-			FileDescriptor,
-			Arc<async_lock::RwLock<dyn ObjectInterface>>,
-			RandomState,
-		>::with_hasher(RandomState::with_seeds(0, 0, 0, 0));
+		let map = FdTable::new();
 
 		without_interrupts(|| {
 			let mut current_task = self.current_task.borrow_mut();
-			let object_map = current_task.object_map.read();
 
 			// clone standard file descriptors
 			for i in 0..3 {
-				if let Some(obj) = object_map.get(&i) {
-					map.insert(i, obj.clone());
+				if let Ok(obj) = current_task.object_map.get(i) {
+					map.insert_at(i, obj)?;
 				}
 			}
 
-			drop(object_map);
-			current_task.object_map = Arc::new(RwSpinLock::new(map));
-		});
-
-		Ok(())
+			current_task.object_map = Arc::new(map);
+			Ok(())
+		})
 	}
 
 	/// Insert a new IO interface and returns a file descriptor as
@@ -518,27 +721,7 @@ impl PerCoreScheduler {
 		&self,
 		obj: Arc<async_lock::RwLock<dyn ObjectInterface>>,
 	) -> io::Result<FileDescriptor> {
-		without_interrupts(|| {
-			let current_task = self.current_task.borrow();
-			let mut object_map = current_task.object_map.write();
-
-			let new_fd = || -> io::Result<FileDescriptor> {
-				let mut fd: FileDescriptor = 0;
-				loop {
-					if !object_map.contains_key(&fd) {
-						break Ok(fd);
-					} else if fd == FileDescriptor::MAX {
-						break Err(Errno::Overflow);
-					}
-
-					fd = fd.saturating_add(1);
-				}
-			};
-
-			let fd = new_fd()?;
-			let _ = object_map.insert(fd, obj.clone());
-			Ok(fd)
-		})
+		without_interrupts(|| self.current_task.borrow().object_map.insert(obj))
 	}
 
 	/// Duplicate a IO interface and returns a new file descriptor as
@@ -546,29 +729,8 @@ impl PerCoreScheduler {
 	pub fn dup_object(&self, fd: FileDescriptor) -> io::Result<FileDescriptor> {
 		without_interrupts(|| {
 			let current_task = self.current_task.borrow();
-			let mut object_map = current_task.object_map.write();
-
-			let obj = (*(object_map.get(&fd).ok_or(Errno::Inval)?)).clone();
-
-			let new_fd = || -> io::Result<FileDescriptor> {
-				let mut fd: FileDescriptor = 0;
-				loop {
-					if !object_map.contains_key(&fd) {
-						break Ok(fd);
-					} else if fd == FileDescriptor::MAX {
-						break Err(Errno::Overflow);
-					}
-
-					fd = fd.saturating_add(1);
-				}
-			};
-
-			let fd = new_fd()?;
-			if object_map.try_insert(fd, obj).is_err() {
-				Err(Errno::Mfile)
-			} else {
-				Ok(fd)
-			}
+			let obj = current_task.object_map.get(fd).map_err(|_| Errno::Inval)?;
+			current_task.object_map.insert(obj)
 		})
 	}
 
@@ -579,15 +741,9 @@ impl PerCoreScheduler {
 	) -> io::Result<FileDescriptor> {
 		without_interrupts(|| {
 			let current_task = self.current_task.borrow();
-			let mut object_map = current_task.object_map.write();
-
-			let obj = object_map.get(&fd1).cloned().ok_or(Errno::Badf)?;
-
-			if object_map.try_insert(fd2, obj).is_err() {
-				Err(Errno::Mfile)
-			} else {
-				Ok(fd2)
-			}
+			let obj = current_task.object_map.get(fd1)?;
+			current_task.object_map.insert_at(fd2, obj)?;
+			Ok(fd2)
 		})
 	}
 
@@ -596,12 +752,7 @@ impl PerCoreScheduler {
 		&self,
 		fd: FileDescriptor,
 	) -> io::Result<Arc<async_lock::RwLock<dyn ObjectInterface>>> {
-		without_interrupts(|| {
-			let current_task = self.current_task.borrow();
-			let mut object_map = current_task.object_map.write();
-
-			object_map.remove(&fd).ok_or(Errno::Badf)
-		})
+		without_interrupts(|| self.current_task.borrow().object_map.remove(fd))
 	}
 
 	#[inline]
@@ -635,34 +786,72 @@ impl PerCoreScheduler {
 	pub fn set_current_task_priority(&mut self, prio: Priority) {
 		without_interrupts(|| {
 			trace!("Change priority of the current task");
-			self.current_task.borrow_mut().prio = prio;
+			let mut current_task_borrowed = self.current_task.borrow_mut();
+			current_task_borrowed.prio = prio;
+			if let Some(handle) = TASKS.lock().get_mut(&current_task_borrowed.id) {
+				handle.set_priority(prio);
+			}
 		});
 	}
 
+	/// Changes the priority of the task `id`.
+	///
+	/// The change of a task of another core is passed to its core.
 	pub fn set_priority(&mut self, id: TaskId, prio: Priority) -> Result<(), ()> {
+		self.set_scheduling(id, prio, None)
+	}
+
+	/// Changes the priority of the task `id` and its scheduling class, unless
+	/// `policy` is `None`.
+	///
+	/// The change of a task of another core is passed to its core.
+	pub fn set_scheduling(
+		&mut self,
+		id: TaskId,
+		prio: Priority,
+		policy: Option<Policy>,
+	) -> Result<(), ()> {
 		trace!("Change priority of task {id} to priority {prio}");
 
 		without_interrupts(|| {
-			let task = get_task_handle(id).ok_or(())?;
-			#[cfg(feature = "smp")]
-			let other_core = task.get_core_id() != self.core_id;
-			#[cfg(not(feature = "smp"))]
-			let other_core = false;
+			let task = {
+				let mut tasks = TASKS.lock();
+				let handle = tasks.get_mut(&id).ok_or(())?;
+				handle.set_priority(prio);
+				*handle
+			};
 
-			if other_core {
-				warn!("Have to change the priority on another core");
-			} else if self.current_task.borrow().id == task.get_id() {
-				self.current_task.borrow_mut().prio = prio;
-			} else {
-				self.ready_queue
-					.set_priority(task, prio)
-					.expect("Do not find valid task in ready queue");
+			#[cfg(feature = "smp")]
+			if task.get_core_id() != self.core_id {
+				get_scheduler_input(task.get_core_id())
+					.lock()
+					.priority_changes
+					.push_back((id, prio, policy));
+				arch::wakeup_core(task.get_core_id());
+				return Ok(());
 			}
 
+			self.change_priority(id, prio, policy);
 			Ok(())
 		})
 	}
 
+	/// Changes the priority and the scheduling class of the task `id` of this core.
+	fn change_priority(&mut self, id: TaskId, prio: Priority, policy: Option<Policy>) {
+		if self.current_task.borrow().id == id {
+			let mut current_task_borrowed = self.current_task.borrow_mut();
+			current_task_borrowed.prio = prio;
+			if let Some(policy) = policy {
+				current_task_borrowed.policy = policy;
+			}
+		} else if self.ready_queue.set_priority(id, prio, policy).is_err()
+			&& self.blocked_tasks.set_priority(id, prio, policy).is_err()
+		{
+			// The task has finished or is moving to another core.
+			debug!("Unable to find task {id} to change its priority");
+		}
+	}
+
 	#[cfg(target_arch = "riscv64")]
 	pub fn set_current_kernel_stack(&self) {
 		let current_task_borrowed = self.current_task.borrow();
@@ -674,20 +863,42 @@ impl PerCoreScheduler {
 		CoreLocal::get().kernel_stack.set(stack);
 	}
 
+	/// Returns whether the FPU registers contain the state of the current task.
+	#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+	fn is_fpu_owner(&self) -> bool {
+		self.fpu_owner
+			.as_ref()
+			.is_some_and(|fpu_owner| Rc::ptr_eq(&self.current_task, fpu_owner))
+	}
+
 	/// Save the FPU context for the current FPU owner and restore it for the current task,
 	/// which wants to use the FPU now.
 	#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 	pub fn fpu_switch(&mut self) {
-		if !Rc::ptr_eq(&self.current_task, &self.fpu_owner) {
+		if !self.is_fpu_owner() {
 			debug!(
-				"Switching FPU owner from task {} to {}",
-				self.fpu_owner.borrow().id,
+				"Switching FPU owner from task {:?} to {}",
+				self.fpu_owner
+					.as_ref()
+					.map(|fpu_owner| fpu_owner.borrow().id),
 				self.current_task.borrow().id
 			);
 
-			self.fpu_owner.borrow_mut().last_fpu_state.save();
+			if let Some(fpu_owner) = &self.fpu_owner {
+				fpu_owner.borrow_mut().last_fpu_state.save();
+			}
 			self.current_task.borrow().last_fpu_state.restore();
-			self.fpu_owner = self.current_task.clone();
+			self.fpu_owner = Some(self.current_task.clone());
+		}
+	}
+
+	/// Saves the FPU context of the current FPU owner, so that kernel code can use the FPU.
+	///
+	/// The next task, which uses the FPU, restores its context without saving the registers.
+	#[cfg(target_arch = "x86_64")]
+	pub(crate) fn fpu_release(&mut self) {
+		if let Some(fpu_owner) = self.fpu_owner.take() {
+			fpu_owner.borrow_mut().last_fpu_state.save();
 		}
 	}
 
@@ -699,19 +910,178 @@ impl PerCoreScheduler {
 		}
 	}
 
+	/// Moves the current task to the core `core_id`.
+	///
+	/// The current task must not wait for an event, since its handle in a wait
+	/// queue would still refer to this core. After the call, the task runs on
+	/// the other core, so that the reference to this scheduler must not be used.
+	#[cfg(feature = "smp")]
+	pub fn migrate_current_task(&mut self, core_id: CoreId) {
+		// A blocked task may still be in a wait queue.
+		if core_id == self.core_id || self.current_task.borrow().status != TaskStatus::Running {
+			return;
+		}
+
+		debug!(
+			"Moving task {} from core {} to core {core_id}",
+			self.get_current_task_id(),
+			self.core_id
+		);
+		without_interrupts(|| self.migrate_to = Some(core_id));
+		self.reschedule();
+	}
+
+	/// Queues the current task for the core `core_id`, once this core has
+	/// switched away from it.
+	#[cfg(feature = "smp")]
+	fn leave_core(&mut self, core_id: CoreId) {
+		// The FPU state has to be in the task, before another core restores it.
+		#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+		if self.is_fpu_owner()
+			&& let Some(fpu_owner) = self.fpu_owner.take()
+		{
+			fpu_owner.borrow_mut().last_fpu_state.save();
+		}
+
+		self.migrating_tasks
+			.push_back((self.current_task.clone(), core_id));
+	}
+
+	/// Passes the tasks, which leave this core, on to their new cores.
+	#[cfg(feature = "smp")]
+	fn send_migrating_tasks(&mut self) {
+		while let Some((task, core_id)) = self.migrating_tasks.pop_front() {
+			send_task(task, core_id);
+		}
+	}
+
+	/// Publishes the number of ready tasks of this core for the load balancing.
+	#[cfg(feature = "smp")]
+	fn publish_load(&self) {
+		let idle = Rc::ptr_eq(&self.current_task, &self.idle_task);
+		self.balance.publish(self.ready_queue.len(), idle);
+	}
+
+	/// Passes a ready task, which may run on the core `core_id`, on to it.
+	#[cfg(feature = "smp")]
+	fn give_task(&mut self, core_id: CoreId) {
+		#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+		let fpu_owner = self.fpu_owner.clone();
+		let task = self.ready_queue.remove_matching(|task| {
+			// The FPU registers may still contain the state of the task.
+			#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+			if fpu_owner
+				.as_ref()
+				.is_some_and(|fpu_owner| Rc::ptr_eq(fpu_owner, task))
+			{
+				return false;
+			}
+
+			let task = task.borrow();
+			!task.pinned && affinity::get(task.id).contains(core_id)
+		});
+
+		if let Some(task) = task {
+			debug!(
+				"Moving task {} from core {} to core {core_id} to balance the load",
+				task.borrow().id,
+				self.core_id
+			);
+			send_task(task, core_id);
+			self.publish_load();
+		}
+	}
+
+	/// Passes a ready task on to each core, which has asked for one.
+	#[cfg(feature = "smp")]
+	fn handle_steal_requests(&mut self) {
+		let steal_requests =
+			core::mem::take(&mut CoreLocal::get().scheduler_input.lock().steal_requests);
+		for thief in steal_requests {
+			self.give_task(thief);
+		}
+	}
+
+	/// Passes a ready task on to an idle core, while the current task keeps
+	/// running, at most every [`balance::BALANCE_INTERVAL`].
+	#[cfg(feature = "smp")]
+	fn balance_load(&mut self, status: TaskStatus) {
+		self.handle_steal_requests();
+
+		let now = arch::processor::get_timer_ticks();
+		if status != TaskStatus::Running || self.ready_queue.is_empty() || now < self.next_balance {
+			return;
+		}
+
+		self.next_balance = now + balance::BALANCE_INTERVAL;
+		if let Some(core_id) = balance::idle_core(self.core_id) {
+			self.give_task(core_id);
+		}
+	}
+
+	/// Asks the core with the most ready tasks to pass one of them on to this
+	/// idle core, at most every [`balance::STEAL_INTERVAL`].
+	#[cfg(feature = "smp")]
+	fn steal_task(&mut self) {
+		let now = arch::processor::get_timer_ticks();
+		if now < self.next_steal {
+			return;
+		}
+
+		self.next_steal = now + balance::STEAL_INTERVAL;
+		if let Some(victim) = balance::busiest_core(self.core_id) {
+			get_scheduler_input(victim)
+				.lock()
+				.steal_requests
+				.push_back(self.core_id);
+			arch::wakeup_core(victim);
+		}
+	}
+
 	#[cfg(feature = "smp")]
 	pub fn check_input(&mut self) {
 		let mut input_locked = CoreLocal::get().scheduler_input.lock();
 
-		while let Some(task) = input_locked.wakeup_tasks.pop_front() {
-			let task = self.blocked_tasks.custom_wakeup(task);
-			self.ready_queue.push(task);
+		let mut forwarded = Vec::new();
+		while let Some(handle) = input_locked.wakeup_tasks.pop_front() {
+			match self.blocked_tasks.custom_wakeup(handle) {
+				Some(task) => self.ready_queue.push(task),
+				None => forwarded.push(handle),
+			}
 		}
 
 		while let Some(new_task) = input_locked.new_tasks.pop_front() {
 			let task = Rc::new(RefCell::new(Task::from(new_task)));
 			self.ready_queue.push(task.clone());
 		}
+
+		while let Some(MigratedTask(task)) = input_locked.migrated_tasks.pop_front() {
+			self.ready_queue.push(Rc::new(RefCell::new(task)));
+		}
+
+		while let Some((id, prio, policy)) = input_locked.priority_changes.pop_front() {
+			self.change_priority(id, prio, policy);
+		}
+
+		#[cfg(feature = "net")]
+		if let Some(wakeup_time) = input_locked.network_wakeup_time.take() {
+			let time = self
+				.blocked_tasks
+				.network_wakeup_time()
+				.map_or(wakeup_time, |time| time.min(wakeup_time));
+			self.blocked_tasks.add_network_timer(Some(time));
+		}
+		drop(input_locked);
+
+		for handle in forwarded {
+			self.forward_wakeup(handle);
+		}
+		self.handle_steal_requests();
+		self.publish_load();
+
+		if !is_isolated() {
+			crate::console::flush_deferred();
+		}
 	}
 
 	/// Only the idle task should call this function.
@@ -731,9 +1101,13 @@ impl PerCoreScheduler {
 			#[cfg(feature = "smp")]
 			core_scheduler.check_input();
 			core_scheduler.cleanup_tasks();
+			#[cfg(feature = "smp")]
+			core_scheduler.send_migrating_tasks();
 
 			if core_scheduler.ready_queue.is_empty() {
 				if backoff.is_completed() {
+					#[cfg(feature = "smp")]
+					core_scheduler.steal_task();
 					interrupts::enable_and_wait();
 					backoff.reset();
 				} else {
@@ -763,26 +1137,57 @@ impl PerCoreScheduler {
 		// Someone wants to give up the CPU
 		// => we have time to cleanup the system
 		self.cleanup_tasks();
+		#[cfg(feature = "smp")]
+		self.send_migrating_tasks();
 
 		// Get information about the current task.
-		let (id, last_stack_pointer, prio, status) = {
+		let (id, last_stack_pointer, prio, policy, status) = {
 			let mut borrowed = self.current_task.borrow_mut();
 			(
 				borrowed.id,
 				ptr::from_mut(&mut borrowed.last_stack_pointer).cast::<usize>(),
 				borrowed.prio,
+				borrowed.policy,
 				borrowed.status,
 			)
 		};
+		let yielded = core::mem::take(&mut self.yielded);
+
+		self.load.update(arch::processor::get_timer_ticks(), || {
+			let running = u32::from(status == TaskStatus::Running);
+			u32::try_from(self.ready_queue.len()).unwrap() + running
+		});
+
+		#[cfg(feature = "smp")]
+		self.balance_load(status);
+
+		// The current task leaves this core, if it has requested to move.
+		#[cfg(feature = "smp")]
+		let migrate_to = self
+			.migrate_to
+			.take()
+			.filter(|_| status == TaskStatus::Running);
+		#[cfg(not(feature = "smp"))]
+		let migrate_to: Option<CoreId> = None;
 
 		let mut new_task = None;
 
-		if status == TaskStatus::Running {
+		if status == TaskStatus::Running && migrate_to.is_none() {
 			// A task is currently running.
 			// Check if a task with a equal or higher priority is available.
-			if let Some(task) = self.ready_queue.pop_with_prio(prio) {
-				new_task = Some(task);
-			}
+			// A real-time task gives way to the tasks of its priority only,
+			// if it yields or its time slice has ended.
+			let time_slice_ended = self
+				.blocked_tasks
+				.time_slice_end()
+				.is_some_and(|end| end <= arch::processor::get_timer_ticks());
+			new_task = match policy {
+				Policy::Fifo if !yielded => self.ready_queue.pop_above(prio),
+				Policy::RoundRobin if !yielded && !time_slice_ended => {
+					self.ready_queue.pop_above(prio)
+				}
+				_ => self.ready_queue.pop_with_prio(prio),
+			};
 		} else {
 			if status == TaskStatus::Finished {
 				// Mark the finished task as invalid and add it to the finished tasks for a later cleanup.
@@ -790,7 +1195,7 @@ impl PerCoreScheduler {
 				self.finished_tasks.push_back(self.current_task.clone());
 			}
 
-			// No task is currently running.
+			// No task is currently running or it leaves this core.
 			// Check if there is any available task and get the one with the highest priority.
 			if let Some(task) = self.ready_queue.pop() {
 				// This available task becomes the new task.
@@ -810,21 +1215,35 @@ impl PerCoreScheduler {
 			if status == TaskStatus::Running {
 				// Mark the running task as ready again and add it back to the queue.
 				self.current_task.borrow_mut().status = TaskStatus::Ready;
+				#[cfg(feature = "smp")]
+				if let Some(core_id) = migrate_to {
+					self.leave_core(core_id);
+				} else {
+					self.ready_queue.push(self.current_task.clone());
+				}
+				#[cfg(not(feature = "smp"))]
 				self.ready_queue.push(self.current_task.clone());
 			}
 
+			#[cfg(feature = "smp")]
+			self.balance
+				.publish(self.ready_queue.len(), Rc::ptr_eq(&task, &self.idle_task));
+
 			// Handle the new task and get information about it.
-			let (new_id, new_stack_pointer) = {
+			let (new_id, new_stack_pointer, new_policy) = {
 				let mut borrowed = task.borrow_mut();
 				if borrowed.status != TaskStatus::Idle {
 					// Mark the new task as running.
 					borrowed.status = TaskStatus::Running;
 				}
 
-				(borrowed.id, borrowed.last_stack_pointer)
+				(borrowed.id, borrowed.last_stack_pointer, borrowed.policy)
 			};
+			self.start_time_slice(new_policy, id != new_id);
 
 			if id != new_id {
+				CONTEXT_SWITCHES.fetch_add(1, Ordering::Relaxed);
+
 				// Tell the scheduler about the new task.
 				debug!(
 					"Switching task from {} to {} (stack {:#X} => {:p})",
@@ -854,24 +1273,93 @@ impl PerCoreScheduler {
 					}
 				}
 			}
+		} else {
+			self.start_time_slice(policy, false);
 		}
 
 		None
 	}
-}
 
-fn get_tid() -> TaskId {
-	static TID_COUNTER: AtomicI32 = AtomicI32::new(0);
-	let guard = TASKS.lock();
+	/// Starts the time slice of the next task, if it is a `SCHED_RR` task.
+	///
+	/// If the task continues to run, a new time slice only starts after the
+	/// previous one, unless `restart` is set.
+	fn start_time_slice(&mut self, policy: Policy, restart: bool) {
+		if policy != Policy::RoundRobin {
+			self.blocked_tasks.set_time_slice_end(None);
+			return;
+		}
 
-	loop {
-		let id = TaskId::from(TID_COUNTER.fetch_add(1, Ordering::SeqCst));
-		if !guard.contains_key(&id) {
-			return id;
+		let now = arch::processor::get_timer_ticks();
+		let running = self
+			.blocked_tasks
+			.time_slice_end()
+			.is_some_and(|end| end > now);
+		if restart || !running {
+			self.blocked_tasks
+				.set_time_slice_end(Some(now + realtime::TIME_SLICE));
 		}
 	}
 }
 
+/// Passes the task `task`, which has left this core, on to the core `core_id`.
+#[cfg(feature = "smp")]
+fn send_task(task: Rc<RefCell<Task>>, core_id: CoreId) {
+	let mut task = Rc::into_inner(task).unwrap().into_inner();
+	task.core_id = core_id;
+	let id = task.id;
+
+	let mut input_locked = get_scheduler_input(core_id).lock();
+	if let Some(handle) = TASKS.lock().get_mut(&id) {
+		// A change of the priority, which has been sent to this core, applies.
+		task.prio = handle.get_priority();
+		*handle = TaskHandle::new(id, handle.get_priority(), core_id);
+	}
+	input_locked.migrated_tasks.push_back(MigratedTask(task));
+	drop(input_locked);
+
+	arch::wakeup_core(core_id);
+}
+
+/// Passes the wakeup of `task` on to the core `core_id`.
+#[cfg(feature = "smp")]
+fn send_wakeup(core_id: CoreId, task: TaskHandle) {
+	get_scheduler_input(core_id)
+		.lock()
+		.wakeup_tasks
+		.push_back(task);
+	// Wake up the CPU
+	arch::wakeup_core(core_id);
+}
+
+/// Returns the ID of a new task or `None`, if it would exceed `max_id`.
+///
+/// The IDs are only used up by the callers with the smaller `max_id`, the
+/// others continue to get IDs.
+fn get_tid(max_id: TaskId) -> Option<TaskId> {
+	NEXT_TID
+		.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |tid| {
+			(tid <= max_id.into()).then_some(tid + 1)
+		})
+		.ok()
+		.map(TaskId::from)
+}
+
+/// Calls the entry point of a task, which has been provided by the application.
+///
+/// A foreign unwind, which escapes from the entry point (e.g., a C++
+/// exception), must not continue into the kernel. It is caught and the task
+/// finishes with the exit code -1.
+pub(crate) fn call_task_entry(func: extern "C" fn(usize), arg: usize) {
+	if unwind::catch_unwind(func, arg).is_err() {
+		warn!(
+			"Task {} has been ended by a foreign unwind",
+			core_scheduler().get_current_task_id()
+		);
+		core_scheduler().exit(-1);
+	}
+}
+
 #[inline]
 pub(crate) fn abort() -> ! {
 	core_scheduler().exit(-1)
@@ -881,7 +1369,7 @@ pub(crate) fn abort() -> ! {
 pub(crate) fn add_current_core() {
 	// Create an idle task for this core.
 	let core_id = core_id();
-	let tid = get_tid();
+	let tid = get_tid(TaskId::MAX).unwrap();
 	let idle_task = Rc::new(RefCell::new(Task::new_idle(tid, core_id)));
 
 	// Add the ID -> Task mapping.
@@ -902,11 +1390,24 @@ pub(crate) fn add_current_core() {
 		core_id,
 		current_task: idle_task.clone(),
 		#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
-		fpu_owner: idle_task.clone(),
+		fpu_owner: Some(idle_task.clone()),
 		idle_task,
 		ready_queue: PriorityTaskQueue::new(),
 		finished_tasks: VecDeque::new(),
+		#[cfg(feature = "smp")]
+		migrate_to: None,
+		#[cfg(feature = "smp")]
+		migrating_tasks: VecDeque::new(),
 		blocked_tasks: BlockedTaskQueue::new(),
+		futex_queues: Vec::with_capacity(FUTEX_QUEUE_POOL_SIZE),
+		load: load::LoadAverage::register(core_id),
+		#[cfg(feature = "smp")]
+		balance: balance::CoreLoad::register(core_id),
+		#[cfg(feature = "smp")]
+		next_balance: 0,
+		#[cfg(feature = "smp")]
+		next_steal: 0,
+		yielded: false,
 	});
 
 	let scheduler = Box::into_raw(boxed_scheduler);
@@ -936,27 +1437,37 @@ fn get_scheduler_input(core_id: CoreId) -> &'static InterruptTicketMutex<Schedul
 	SCHEDULER_INPUTS.lock()[usize::try_from(core_id).unwrap()]
 }
 
+/// Spawns a new task on the core `selector` or, if it is negative, on a core
+/// of the mask of the current task.
+///
+/// Fails with `EOVERFLOW` without creating the task, if its ID would exceed
+/// `max_id`.
 pub unsafe fn spawn(
 	func: unsafe extern "C" fn(usize),
 	arg: usize,
 	prio: Priority,
 	stack_size: usize,
 	selector: isize,
-) -> TaskId {
-	static CORE_COUNTER: AtomicU32 = AtomicU32::new(1);
-
+	max_id: TaskId,
+) -> Result<TaskId, Errno> {
 	let core_id = if selector < 0 {
-		// use Round Robin to schedule the cores
-		CORE_COUNTER.fetch_add(1, Ordering::SeqCst) % get_processor_count()
+		// use Round Robin to schedule the cores of the mask of the current task
+		affinity::get(core_scheduler().get_current_task_id()).next_core()
 	} else {
 		selector as u32
 	};
 
-	unsafe { PerCoreScheduler::spawn(func, arg, prio, core_id, stack_size) }
+	unsafe { PerCoreScheduler::spawn(func, arg, prio, core_id, selector >= 0, stack_size, max_id) }
 }
 
+/// Waits for the task `id` to finish and reaps it.
 #[allow(clippy::result_unit_err)]
 pub fn join(id: TaskId) -> Result<(), ()> {
+	// The task has never been spawned.
+	if id.into() >= NEXT_TID.load(Ordering::SeqCst) {
+		return Err(());
+	}
+
 	let core_scheduler = core_scheduler();
 
 	debug!(
@@ -976,19 +1487,130 @@ pub fn join(id: TaskId) -> Result<(), ()> {
 			drop(waiting_tasks_guard);
 			core_scheduler.reschedule();
 		} else {
+			CHILDREN.lock().remove(&id);
 			return Ok(());
 		}
 	}
 }
 
+/// Detaches the task `id`, so that it is reaped as soon as it finishes.
+///
+/// Fails with `ESRCH`, if the task has never been spawned or has already been
+/// reaped, and with `EINVAL`, if it has already been detached. A detached task
+/// cannot be awaited by [`waitpid`] anymore.
+pub(crate) fn detach(id: TaskId) -> io::Result<()> {
+	let mut children = CHILDREN.lock();
+	let child = children.get_mut(&id).ok_or(Errno::Srch)?;
+	if child.detached {
+		return Err(Errno::Inval);
+	}
+
+	if child.exit_code.is_some() {
+		children.remove(&id);
+	} else {
+		child.detached = true;
+	}
+	Ok(())
+}
+
+/// Sets, whether the tasks, which the current task spawns from now on, remain
+/// zombies after they have finished, until [`waitpid`] or [`join`] reaps them.
+///
+/// Otherwise, they are detached and reaped as soon as they finish, so that
+/// tasks, which are never joined, do not leak. Only the tasks of a parent,
+/// which retains them, can be awaited by [`waitpid`].
+pub(crate) fn retain_children(retain: bool) {
+	let id = core_scheduler().get_current_task_id();
+	if retain {
+		RETAINING.lock().insert(id);
+	} else {
+		RETAINING.lock().remove(&id);
+	}
+}
+
+/// Waits for a task, which has been spawned by the current task, to finish.
+///
+/// If `id` is `None`, any of these tasks is awaited. Reaps the task and
+/// returns its ID and exit code. If `nohang` is set, returns `None` instead of
+/// waiting, if none of the tasks has finished yet. Tasks with IDs beyond
+/// `max_id`, which the caller cannot represent, are not considered, and
+/// neither are the tasks, which have been spawned without
+/// [`retain_children`].
+pub(crate) fn waitpid(
+	id: Option<TaskId>,
+	nohang: bool,
+	max_id: TaskId,
+) -> io::Result<Option<(TaskId, i32)>> {
+	let parent = core_scheduler().get_current_task_id();
+
+	loop {
+		let events = CHILD_EVENTS.load(Ordering::SeqCst);
+
+		{
+			let mut children = CHILDREN.lock();
+			let mut found = false;
+			let mut finished = None;
+			for (&tid, child) in children.iter().filter(|(tid, child)| {
+				child.parent == parent
+					&& !child.detached
+					&& **tid <= max_id
+					&& id.is_none_or(|id| **tid == id)
+			}) {
+				found = true;
+				if let Some(exit_code) = child.exit_code {
+					finished = Some((tid, exit_code));
+					break;
+				}
+			}
+
+			if let Some((tid, exit_code)) = finished {
+				children.remove(&tid);
+				return Ok(Some((tid, exit_code)));
+			}
+			if !found {
+				return Err(Errno::Child);
+			}
+		}
+
+		if nohang {
+			return Ok(None);
+		}
+		futex::futex_wait(&CHILD_EVENTS, events, None, Flags::empty());
+	}
+}
+
 pub fn shutdown(arg: i32) -> ! {
 	crate::syscalls::shutdown(arg)
 }
 
-fn get_task_handle(id: TaskId) -> Option<TaskHandle> {
+/// Returns the handle of the task `id`, if it has not finished yet.
+pub(crate) fn get_task_handle(id: TaskId) -> Option<TaskHandle> {
 	TASKS.lock().get(&id).copied()
 }
 
+/// Returns the ID of the task, which has been spawned last.
+pub(crate) fn last_task_id() -> Option<TaskId> {
+	NEXT_TID
+		.load(Ordering::SeqCst)
+		.checked_sub(1)
+		.map(TaskId::from)
+}
+
+/// Returns the handles of all tasks, ordered by their ID.
+pub(crate) fn tasks() -> Vec<TaskHandle> {
+	TASKS.lock().values().copied().collect()
+}
+
+/// Returns the number of tasks, which have been spawned and have not finished yet.
+pub(crate) fn task_count() -> u32 {
+	NO_TASKS.load(Ordering::SeqCst)
+}
+
+/// Returns the number of switches between tasks since boot.
+pub(crate) fn context_switches() -> u64 {
+	CONTEXT_SWITCHES.load(Ordering::Relaxed)
+}
+
 #[cfg(all(target_arch = "x86_64", feature = "common-os"))]
 pub(crate) static BOOT_ROOT_PAGE_TABLE: OnceCell<usize> = OnceCell::new();
 
@@ -0,0 +1,109 @@
+//! Real-time scheduling classes.
+//!
+//! Tasks of the classes `SCHED_FIFO` and `SCHED_RR` have the priorities from
+//! [`REALTIME_PRIO`] on, so that they run before all tasks of `SCHED_OTHER`.
+//! Each of their priorities 1 to [`MAX_PRIORITY`] has a level of the ready
+//! queues of its own, so that a task of a higher priority always preempts.
+//! Unlike on Linux, the priorities end at 32 instead of 99. They do not age and
+//! keep their core, until they block or yield, or a real-time task of a higher
+//! priority becomes ready. Such a task preempts them at once, if it is woken up
+//! by `sys_futex_wake`, `sys_wakeup_task`, an interrupt or another core. In
+//! addition, a `SCHED_RR` task gives way to the ready tasks of its priority
+//! after a time slice of [`TIME_SLICE`].
+//!
+//! New tasks start with `SCHED_OTHER` and the priority of their spawn.
+
+use alloc::collections::BTreeMap;
+
+use hermit_sync::InterruptTicketMutex;
+use num_enum::TryFromPrimitive;
+
+use crate::arch::core_local::core_scheduler;
+use crate::errno::Errno;
+use crate::scheduler::get_task_handle;
+use crate::scheduler::task::{NO_PRIORITIES, NORMAL_PRIO, Priority, REALTIME_PRIO, TaskId};
+
+/// Time slice of a `SCHED_RR` task in microseconds
+pub(crate) const TIME_SLICE: u64 = 100_000;
+
+/// Lowest priority of a real-time task
+pub(crate) const MIN_PRIORITY: i32 = 1;
+
+/// Highest priority of a real-time task
+pub(crate) const MAX_PRIORITY: i32 = (NO_PRIORITIES - REALTIME_PRIO.into() as usize) as i32;
+
+/// Classes and priorities of the real-time tasks
+static POLICIES: InterruptTicketMutex<BTreeMap<TaskId, (Policy, i32)>> =
+	InterruptTicketMutex::new(BTreeMap::new());
+
+/// Scheduling class of a task with the values of Linux
+#[derive(TryFromPrimitive, Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(i32)]
+pub(crate) enum Policy {
+	Other = 0,
+	Fifo = 1,
+	RoundRobin = 2,
+}
+
+impl Policy {
+	/// Returns the lowest and the highest priority of the class.
+	pub fn priority_range(self) -> (i32, i32) {
+		match self {
+			Self::Other => (0, 0),
+			Self::Fifo | Self::RoundRobin => (MIN_PRIORITY, MAX_PRIORITY),
+		}
+	}
+}
+
+/// Returns the level of the ready queues of the real-time priority `priority`.
+fn level(priority: i32) -> Priority {
+	let offset = u8::try_from(priority - MIN_PRIORITY).unwrap();
+	Priority::from(REALTIME_PRIO.into() + offset)
+}
+
+/// Returns the class and the priority of the task `id` or `ESRCH`, if the task
+/// does not exist.
+pub(crate) fn query(id: TaskId) -> Result<(Policy, i32), Errno> {
+	if get_task_handle(id).is_none() {
+		return Err(Errno::Srch);
+	}
+	Ok(POLICIES
+		.lock()
+		.get(&id)
+		.copied()
+		.unwrap_or((Policy::Other, 0)))
+}
+
+/// Sets the class of the task `id` to `policy` with the priority `priority`.
+///
+/// Fails with `ESRCH`, if the task does not exist, and with `EINVAL`, if the
+/// priority is not valid for the class. A task, which returns to
+/// `SCHED_OTHER`, gets the normal priority.
+pub(crate) fn set(id: TaskId, policy: Policy, priority: i32) -> Result<(), Errno> {
+	let (min, max) = policy.priority_range();
+	if !(min..=max).contains(&priority) {
+		return Err(Errno::Inval);
+	}
+
+	let prio = if policy == Policy::Other {
+		NORMAL_PRIO
+	} else {
+		level(priority)
+	};
+	core_scheduler()
+		.set_scheduling(id, prio, Some(policy))
+		.map_err(|()| Errno::Srch)?;
+
+	let mut policies = POLICIES.lock();
+	if policy == Policy::Other {
+		policies.remove(&id);
+	} else {
+		policies.insert(id, (policy, priority));
+	}
+	Ok(())
+}
+
+/// Forgets the class of the finished task `id`.
+pub(crate) fn remove(id: TaskId) {
+	POLICIES.lock().remove(&id);
+}
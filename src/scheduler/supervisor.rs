@@ -0,0 +1,185 @@
+//! Supervision of tasks with restart policies.
+//!
+//! Each supervised task (a service) gets its own supervisor task, which spawns
+//! it, waits for it to finish and restarts it according to its
+//! [`RestartPolicy`]. Restarts are delayed by an exponential backoff. If the
+//! number of restarts exceeds the limit of the policy, the failure is escalated.
+//!
+//! The application itself cannot be restarted in place, because its static
+//! state is not reinitialized. Failures of the application can only be
+//! escalated to a reboot with the boot argument `supervisor=reboot`.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use hermit_sync::InterruptTicketMutex;
+use num_enum::TryFromPrimitive;
+
+use crate::arch::core_local::core_scheduler;
+use crate::errno::Errno;
+use crate::scheduler::task::{Priority, TaskId};
+use crate::scheduler::{self, PerCoreSchedulerExt};
+use crate::{arch, io};
+
+/// When a service is restarted
+#[derive(TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub(crate) enum Restart {
+	Never = 0,
+	/// If the service returns a non-zero exit code or aborts
+	OnFailure = 1,
+	Always = 2,
+}
+
+/// What happens, if a service exceeds its number of restarts
+#[derive(TryFromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub(crate) enum Escalation {
+	/// The service is not restarted anymore.
+	GiveUp = 0,
+	/// The system is shut down with the exit code of the service.
+	Shutdown = 1,
+	Reboot = 2,
+}
+
+/// Restart policy of a supervised task, as passed by the application
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct RestartPolicy {
+	/// See [`Restart`]
+	pub mode: u32,
+	/// Maximum number of restarts, 0 restarts the task indefinitely
+	pub max_restarts: u32,
+	/// Delay of the first restart in milliseconds, which doubles with each restart
+	pub backoff_ms: u32,
+	/// Upper limit of the delay in milliseconds
+	pub max_backoff_ms: u32,
+	/// See [`Escalation`]
+	pub escalation: u32,
+}
+
+struct Service {
+	func: unsafe extern "C" fn(usize),
+	arg: usize,
+	prio: Priority,
+	stack_size: usize,
+	restart: Restart,
+	max_restarts: u32,
+	backoff_ms: u32,
+	max_backoff_ms: u32,
+	escalation: Escalation,
+	restarts: AtomicU32,
+}
+
+/// All services, indexed by their ID
+static SERVICES: InterruptTicketMutex<Vec<Arc<Service>>> = InterruptTicketMutex::new(Vec::new());
+
+fn sleep_ms(ms: u32) {
+	if ms == 0 {
+		return;
+	}
+
+	let wakeup_time = arch::processor::get_timer_ticks() + u64::from(ms) * 1000;
+	let core_scheduler = core_scheduler();
+	core_scheduler.block_current_task(Some(wakeup_time));
+	core_scheduler.reschedule();
+}
+
+extern "C" fn supervise(id: usize) {
+	let service = SERVICES.lock()[id].clone();
+	let mut backoff_ms = service.backoff_ms;
+	// The supervisor awaits the exit codes of its tasks.
+	scheduler::retain_children(true);
+
+	loop {
+		let spawned = unsafe {
+			scheduler::spawn(
+				service.func,
+				service.arg,
+				service.prio,
+				service.stack_size,
+				-1,
+				TaskId::MAX,
+			)
+		};
+		let Ok(tid) = spawned else {
+			error!("Unable to spawn the task of service {id}");
+			return;
+		};
+		// The supervisor is the parent of the task.
+		let Ok(Some((_, exit_code))) = scheduler::waitpid(Some(tid), false, TaskId::MAX) else {
+			unreachable!("task {tid} of service {id} vanished");
+		};
+
+		let restart = match service.restart {
+			Restart::Never => false,
+			Restart::OnFailure => exit_code != 0,
+			Restart::Always => true,
+		};
+		if !restart {
+			debug!("Service {id} finished with exit code {exit_code}");
+			return;
+		}
+
+		let restarts = service.restarts.load(Ordering::Relaxed);
+		if service.max_restarts != 0 && restarts >= service.max_restarts {
+			error!("Service {id} failed with exit code {exit_code} after {restarts} restarts");
+			match service.escalation {
+				Escalation::GiveUp => return,
+				Escalation::Shutdown => scheduler::shutdown(exit_code),
+				Escalation::Reboot => crate::syscalls::reboot(exit_code),
+			}
+		}
+
+		warn!("Service {id} finished with exit code {exit_code}, restarting in {backoff_ms} ms");
+		sleep_ms(backoff_ms);
+		service.restarts.fetch_add(1, Ordering::Relaxed);
+		backoff_ms = backoff_ms.saturating_mul(2).min(service.max_backoff_ms);
+	}
+}
+
+/// Spawns the task `func` under supervision and returns the ID of the service.
+pub(crate) unsafe fn spawn(
+	func: unsafe extern "C" fn(usize),
+	arg: usize,
+	prio: Priority,
+	stack_size: usize,
+	policy: &RestartPolicy,
+) -> io::Result<usize> {
+	let restart = Restart::try_from(policy.mode).map_err(|_| Errno::Inval)?;
+	let escalation = Escalation::try_from(policy.escalation).map_err(|_| Errno::Inval)?;
+	let service = Arc::new(Service {
+		func,
+		arg,
+		prio,
+		stack_size,
+		restart,
+		max_restarts: policy.max_restarts,
+		backoff_ms: policy.backoff_ms,
+		max_backoff_ms: policy.max_backoff_ms.max(policy.backoff_ms),
+		escalation,
+		restarts: AtomicU32::new(0),
+	});
+
+	let id = {
+		let mut services = SERVICES.lock();
+		services.push(service);
+		services.len() - 1
+	};
+
+	unsafe {
+		scheduler::spawn(supervise, id, prio, stack_size, -1, TaskId::MAX)?;
+	}
+
+	Ok(id)
+}
+
+/// Returns how often the service `id` has been restarted.
+pub(crate) fn restarts(id: usize) -> io::Result<u32> {
+	SERVICES
+		.lock()
+		.get(id)
+		.map(|service| service.restarts.load(Ordering::Relaxed))
+		.ok_or(Errno::Srch)
+}
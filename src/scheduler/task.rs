@@ -6,24 +6,24 @@ use alloc::collections::{LinkedList, VecDeque};
 use alloc::rc::Rc;
 use alloc::sync::Arc;
 use core::cell::RefCell;
-use core::num::NonZeroU64;
+use core::num::{NonZeroU64, TryFromIntError};
 use core::{cmp, fmt};
 
-use ahash::RandomState;
 use crossbeam_utils::CachePadded;
-use hashbrown::HashMap;
-use hermit_sync::{OnceCell, RwSpinLock};
+use hermit_sync::OnceCell;
 use memory_addresses::VirtAddr;
 
 use crate::arch::core_local::*;
 use crate::arch::scheduler::TaskStacks;
 #[cfg(not(feature = "common-os"))]
 use crate::arch::scheduler::TaskTLS;
-use crate::errno::Errno;
 use crate::executor::poll_on;
 use crate::fd::stdio::*;
-use crate::fd::{FileDescriptor, ObjectInterface, STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO};
+use crate::fd::table::FdTable;
+use crate::fd::{STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO};
+use crate::mm::pool_alloc::PoolAlloc;
 use crate::scheduler::CoreId;
+use crate::scheduler::realtime::Policy;
 use crate::{arch, env};
 
 /// Returns the most significant bit.
@@ -40,6 +40,27 @@ fn msb(n: u64) -> Option<u32> {
 	NonZeroU64::new(n).map(|n| u64::BITS - 1 - n.leading_zeros())
 }
 
+/// Sets the One-Shot Timer to `wakeup_time`, to the end of the time slice of
+/// the current task, to the time, at which a masked interrupt line is unmasked,
+/// or to the next sample of the memory pressure, whichever comes first.
+fn arm_oneshot_timer(wakeup_time: Option<u64>, time_slice_end: Option<u64>) {
+	// the housekeeping core samples and unmasks for all cores
+	let time = if crate::scheduler::is_isolated() {
+		[wakeup_time, time_slice_end].into_iter().flatten().min()
+	} else {
+		[
+			wakeup_time,
+			time_slice_end,
+			crate::drivers::irq_storm::next_unmask(),
+			crate::mm::pressure::next_sample(),
+		]
+		.into_iter()
+		.flatten()
+		.min()
+	};
+	arch::set_oneshot_timer(time);
+}
+
 /// The status of the task - used for scheduling
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub(crate) enum TaskStatus {
@@ -52,19 +73,65 @@ pub(crate) enum TaskStatus {
 }
 
 /// Unique identifier for a task (i.e. `pid`).
+///
+/// IDs increase monotonically and are never reused, so that a stored ID
+/// cannot refer to a task, which has been spawned later. The system calls
+/// with 32-bit IDs cannot spawn tasks anymore, once the IDs exceed
+/// [`TaskId::MAX_TID32`], but the ones with 64-bit IDs can.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
-pub struct TaskId(i32);
+pub struct TaskId(u64);
 
 impl TaskId {
-	pub const fn into(self) -> i32 {
+	/// Largest ID, which fits into the 32-bit IDs of the system calls
+	pub const MAX_TID32: Self = TaskId(i32::MAX as u64);
+	/// Largest ID, which fits into the 64-bit IDs of the system calls
+	pub const MAX: Self = TaskId(i64::MAX as u64);
+
+	pub const fn into(self) -> u64 {
 		self.0
 	}
 
-	pub const fn from(x: i32) -> Self {
+	pub const fn from(x: u64) -> Self {
 		TaskId(x)
 	}
 }
 
+/// Converts an ID into the 32-bit ID of the system calls.
+impl TryFrom<TaskId> for i32 {
+	type Error = TryFromIntError;
+
+	fn try_from(id: TaskId) -> Result<Self, Self::Error> {
+		i32::try_from(id.0)
+	}
+}
+
+/// Converts a 32-bit ID of the system calls into an ID.
+impl TryFrom<i32> for TaskId {
+	type Error = TryFromIntError;
+
+	fn try_from(id: i32) -> Result<Self, Self::Error> {
+		u64::try_from(id).map(TaskId)
+	}
+}
+
+/// Converts an ID into the 64-bit ID of the system calls.
+impl TryFrom<TaskId> for i64 {
+	type Error = TryFromIntError;
+
+	fn try_from(id: TaskId) -> Result<Self, Self::Error> {
+		i64::try_from(id.0)
+	}
+}
+
+/// Converts a 64-bit ID of the system calls into an ID.
+impl TryFrom<i64> for TaskId {
+	type Error = TryFromIntError;
+
+	fn try_from(id: i64) -> Result<Self, Self::Error> {
+		u64::try_from(id).map(TaskId)
+	}
+}
+
 impl fmt::Display for TaskId {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		write!(f, "{}", self.0)
@@ -94,12 +161,17 @@ impl fmt::Display for Priority {
 #[allow(dead_code)]
 pub const HIGH_PRIO: Priority = Priority::from(3);
 pub const NORMAL_PRIO: Priority = Priority::from(2);
-#[allow(dead_code)]
 pub const LOW_PRIO: Priority = Priority::from(1);
 pub const IDLE_PRIO: Priority = Priority::from(0);
 
 /// Maximum number of priorities
-pub const NO_PRIORITIES: usize = 31;
+pub const NO_PRIORITIES: usize = 64;
+
+/// Lowest priority of the real-time tasks, which do not age
+pub const REALTIME_PRIO: Priority = Priority::from(32);
+
+/// Time in microseconds, after which a ready task runs before tasks of a higher priority
+const STARVATION_LIMIT: u64 = 100_000;
 
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct TaskHandle {
@@ -131,6 +203,10 @@ impl TaskHandle {
 	pub fn get_priority(&self) -> Priority {
 		self.priority
 	}
+
+	pub fn set_priority(&mut self, priority: Priority) {
+		self.priority = priority;
+	}
 }
 
 impl Ord for TaskHandle {
@@ -186,7 +262,7 @@ impl TaskHandlePriorityQueue {
 		let i = task.priority.into() as usize;
 		//assert!(i < NO_PRIORITIES, "Priority {} is too high", i);
 
-		*self.prio_bitmap |= (1 << i) as u64;
+		*self.prio_bitmap |= 1 << i;
 		if let Some(queue) = &mut self.queues[i] {
 			queue.push_back(task);
 		} else {
@@ -201,7 +277,7 @@ impl TaskHandlePriorityQueue {
 			let task = queue.pop_front();
 
 			if queue.is_empty() {
-				*self.prio_bitmap &= !(1 << queue_index as u64);
+				*self.prio_bitmap &= !(1 << queue_index);
 			}
 
 			task
@@ -238,7 +314,7 @@ impl TaskHandlePriorityQueue {
 			}
 
 			if queue.is_empty() {
-				*self.prio_bitmap &= !(1 << queue_index as u64);
+				*self.prio_bitmap &= !(1 << queue_index);
 			}
 		}
 
@@ -247,6 +323,12 @@ impl TaskHandlePriorityQueue {
 }
 
 /// Realize a priority queue for tasks
+///
+/// Tasks of a higher priority run first. A task, which has been ready for
+/// [`STARVATION_LIMIT`], ages and runs before them, so that tasks of a lower
+/// priority do not starve, if tasks of a higher priority become ready again
+/// and again. Tasks do not age past the real-time priorities from
+/// [`REALTIME_PRIO`] on.
 pub(crate) struct PriorityTaskQueue {
 	queues: [LinkedList<Rc<RefCell<Task>>>; NO_PRIORITIES],
 	prio_bitmap: u64,
@@ -264,10 +346,14 @@ impl PriorityTaskQueue {
 
 	/// Add a task by its priority to the queue
 	pub fn push(&mut self, task: Rc<RefCell<Task>>) {
-		let i = task.borrow().prio.into() as usize;
+		let i = {
+			let mut borrowed = task.borrow_mut();
+			borrowed.ready_since = arch::processor::get_timer_ticks();
+			borrowed.prio.into() as usize
+		};
 		//assert!(i < NO_PRIORITIES, "Priority {} is too high", i);
 
-		self.prio_bitmap |= (1 << i) as u64;
+		self.prio_bitmap |= 1 << i;
 		let queue = &mut self.queues[i];
 		queue.push_back(task);
 	}
@@ -275,7 +361,7 @@ impl PriorityTaskQueue {
 	fn pop_from_queue(&mut self, queue_index: usize) -> Option<Rc<RefCell<Task>>> {
 		let task = self.queues[queue_index].pop_front();
 		if self.queues[queue_index].is_empty() {
-			self.prio_bitmap &= !(1 << queue_index as u64);
+			self.prio_bitmap &= !(1 << queue_index);
 		}
 
 		task
@@ -297,7 +383,7 @@ impl PriorityTaskQueue {
 			let element = split_list.pop_front();
 			queue.append(&mut split_list);
 			if queue.is_empty() {
-				self.prio_bitmap &= !(1 << queue_index as u64);
+				self.prio_bitmap &= !(1 << queue_index);
 			}
 			element
 		} else {
@@ -310,6 +396,28 @@ impl PriorityTaskQueue {
 		self.prio_bitmap == 0
 	}
 
+	/// Returns the number of tasks in the queue.
+	pub fn len(&self) -> usize {
+		self.queues.iter().map(LinkedList::len).sum()
+	}
+
+	/// Removes the task of the highest priority, which has become ready last
+	/// and fulfills `predicate`.
+	#[cfg(feature = "smp")]
+	pub fn remove_matching(
+		&mut self,
+		predicate: impl Fn(&Rc<RefCell<Task>>) -> bool,
+	) -> Option<Rc<RefCell<Task>>> {
+		for queue_index in (0..NO_PRIORITIES).rev() {
+			let queue = &self.queues[queue_index];
+			if let Some(task_index) = queue.iter().rposition(&predicate) {
+				return self.remove_from_queue(task_index, queue_index);
+			}
+		}
+
+		None
+	}
+
 	/// Returns reference to prio_bitmap
 	#[allow(dead_code)]
 	#[inline]
@@ -317,28 +425,64 @@ impl PriorityTaskQueue {
 		&self.prio_bitmap
 	}
 
+	/// Returns the queue with the task below the priority `prio`, which has
+	/// been ready for the longest time, if it has exceeded [`STARVATION_LIMIT`].
+	fn starving_queue(&self, prio: u32) -> Option<usize> {
+		if prio >= u32::from(REALTIME_PRIO.into()) {
+			return None;
+		}
+
+		let mut lower = self.prio_bitmap & ((1 << prio) - 1);
+		let mut oldest: Option<(usize, u64)> = None;
+		while let Some(i) = msb(lower) {
+			lower &= !(1 << i);
+			let ready_since = self.queues[i as usize]
+				.front()
+				.unwrap()
+				.borrow()
+				.ready_since;
+			if oldest.is_none_or(|(_, since)| ready_since < since) {
+				oldest = Some((i as usize, ready_since));
+			}
+		}
+
+		let (i, ready_since) = oldest?;
+		(arch::processor::get_timer_ticks().saturating_sub(ready_since) >= STARVATION_LIMIT)
+			.then_some(i)
+	}
+
 	/// Pop the task with the highest priority from the queue
 	pub fn pop(&mut self) -> Option<Rc<RefCell<Task>>> {
 		if let Some(i) = msb(self.prio_bitmap) {
-			return self.pop_from_queue(i as usize);
+			let i = self.starving_queue(i).unwrap_or(i as usize);
+			return self.pop_from_queue(i);
 		}
 
 		None
 	}
 
 	/// Pop the next task, which has a higher or the same priority as `prio`
+	/// or which has been ready for too long
 	pub fn pop_with_prio(&mut self, prio: Priority) -> Option<Rc<RefCell<Task>>> {
-		if let Some(i) = msb(self.prio_bitmap)
-			&& i >= u32::from(prio.into())
-		{
-			return self.pop_from_queue(i as usize);
+		if let Some(i) = msb(self.prio_bitmap) {
+			if let Some(starving) = self.starving_queue(i.max(prio.into().into())) {
+				return self.pop_from_queue(starving);
+			}
+			if i >= u32::from(prio.into()) {
+				return self.pop_from_queue(i as usize);
+			}
 		}
 
 		None
 	}
 
+	/// Pop the next task, which has a higher priority than `prio`
+	pub fn pop_above(&mut self, prio: Priority) -> Option<Rc<RefCell<Task>>> {
+		let i = msb(self.prio_bitmap).filter(|i| *i > u32::from(prio.into()))?;
+		self.pop_from_queue(i as usize)
+	}
+
 	/// Returns the highest priority of all available task
-	#[cfg(all(any(target_arch = "x86_64", target_arch = "riscv64"), feature = "smp"))]
 	pub fn get_highest_priority(&self) -> Priority {
 		if let Some(i) = msb(self.prio_bitmap) {
 			Priority::from(i.try_into().unwrap())
@@ -347,19 +491,35 @@ impl PriorityTaskQueue {
 		}
 	}
 
-	/// Change priority of specific task
-	pub fn set_priority(&mut self, handle: TaskHandle, prio: Priority) -> Result<(), ()> {
-		let old_priority = handle.get_priority().into() as usize;
-		if let Some(index) = self.queues[old_priority]
-			.iter()
-			.position(|current_task| current_task.borrow().id == handle.id)
-		{
-			let Some(task) = self.remove_from_queue(index, old_priority) else {
-				return Err(());
-			};
-			task.borrow_mut().prio = prio;
-			self.push(task);
-			return Ok(());
+	/// Change priority of specific task and its scheduling class, unless
+	/// `policy` is `None`
+	pub fn set_priority(
+		&mut self,
+		id: TaskId,
+		prio: Priority,
+		policy: Option<Policy>,
+	) -> Result<(), ()> {
+		for old_priority in 0..NO_PRIORITIES {
+			if let Some(index) = self.queues[old_priority]
+				.iter()
+				.position(|current_task| current_task.borrow().id == id)
+			{
+				let Some(task) = self.remove_from_queue(index, old_priority) else {
+					return Err(());
+				};
+				// The task keeps its age.
+				let ready_since = task.borrow().ready_since;
+				{
+					let mut borrowed = task.borrow_mut();
+					borrowed.prio = prio;
+					if let Some(policy) = policy {
+						borrowed.policy = policy;
+					}
+				}
+				self.push(task.clone());
+				task.borrow_mut().ready_since = ready_since;
+				return Ok(());
+			}
 		}
 
 		Err(())
@@ -379,22 +539,26 @@ pub(crate) struct Task {
 	pub status: TaskStatus,
 	/// Task priority,
 	pub prio: Priority,
+	/// Scheduling class of the task
+	pub policy: Policy,
 	/// Last stack pointer before a context switch to another task
 	pub last_stack_pointer: VirtAddr,
 	/// Last stack pointer on the user stack before jumping to kernel space
 	pub user_stack_pointer: VirtAddr,
 	/// Last FPU state before a context switch to another task using the FPU
 	pub last_fpu_state: arch::processor::FPUState,
+	/// Time in microseconds, at which the task has become ready
+	pub ready_since: u64,
+	/// The core has been selected explicitly, so that the load balancing does
+	/// not move the task
+	#[cfg_attr(not(feature = "smp"), expect(dead_code))]
+	pub pinned: bool,
 	/// ID of the core this task is running on
 	pub core_id: CoreId,
 	/// Stack of the task
 	pub stacks: TaskStacks,
 	/// Mapping between file descriptor and the referenced IO interface
-	pub object_map: Arc<
-		RwSpinLock<
-			HashMap<FileDescriptor, Arc<async_lock::RwLock<dyn ObjectInterface>>, RandomState>,
-		>,
-	>,
+	pub object_map: Arc<FdTable>,
 	/// Task Thread-Local-Storage (TLS)
 	#[cfg(not(feature = "common-os"))]
 	pub tls: Option<Box<TaskTLS>>,
@@ -415,11 +579,7 @@ impl Task {
 		task_status: TaskStatus,
 		task_prio: Priority,
 		stacks: TaskStacks,
-		object_map: Arc<
-			RwSpinLock<
-				HashMap<FileDescriptor, Arc<async_lock::RwLock<dyn ObjectInterface>>, RandomState>,
-			>,
-		>,
+		object_map: Arc<FdTable>,
 	) -> Task {
 		debug!("Creating new task {tid} on core {core_id}");
 
@@ -430,6 +590,9 @@ impl Task {
 			last_stack_pointer: VirtAddr::zero(),
 			user_stack_pointer: VirtAddr::zero(),
 			last_fpu_state: arch::processor::FPUState::new(),
+			policy: Policy::Other,
+			ready_since: 0,
+			pinned: false,
 			core_id,
 			stacks,
 			object_map,
@@ -444,69 +607,38 @@ impl Task {
 		debug!("Creating idle task {tid}");
 
 		/// All cores use the same mapping between file descriptor and the referenced object
-		static OBJECT_MAP: OnceCell<
-			Arc<
-				RwSpinLock<
-					HashMap<
-						FileDescriptor,
-						Arc<async_lock::RwLock<dyn ObjectInterface>>,
-						RandomState,
-					>,
-				>,
-			>,
-		> = OnceCell::new();
+		static OBJECT_MAP: OnceCell<Arc<FdTable>> = OnceCell::new();
 
 		if core_id == 0 {
-			OBJECT_MAP
-				.set(Arc::new(RwSpinLock::new(HashMap::<
-					FileDescriptor,
-					Arc<async_lock::RwLock<dyn ObjectInterface>>,
-					RandomState,
-				>::with_hasher(
-					RandomState::with_seeds(0, 0, 0, 0),
-				))))
-				.unwrap();
+			OBJECT_MAP.set(Arc::new(FdTable::new())).unwrap();
 			let objmap = OBJECT_MAP.get().unwrap().clone();
 			let _ = poll_on(async {
-				let mut guard = objmap.write();
 				if env::is_uhyve() {
-					guard
-						.try_insert(
-							STDIN_FILENO,
-							Arc::new(async_lock::RwLock::new(UhyveStdin::new())),
-						)
-						.map_err(|_| Errno::Io)?;
-					guard
-						.try_insert(
-							STDOUT_FILENO,
-							Arc::new(async_lock::RwLock::new(UhyveStdout::new())),
-						)
-						.map_err(|_| Errno::Io)?;
-					guard
-						.try_insert(
-							STDERR_FILENO,
-							Arc::new(async_lock::RwLock::new(UhyveStderr::new())),
-						)
-						.map_err(|_| Errno::Io)?;
+					objmap.insert_at(
+						STDIN_FILENO,
+						Arc::new(async_lock::RwLock::new(UhyveStdin::new())),
+					)?;
+					objmap.insert_at(
+						STDOUT_FILENO,
+						Arc::new(async_lock::RwLock::new(UhyveStdout::new())),
+					)?;
+					objmap.insert_at(
+						STDERR_FILENO,
+						Arc::new(async_lock::RwLock::new(UhyveStderr::new())),
+					)?;
 				} else {
-					guard
-						.try_insert(
-							STDIN_FILENO,
-							Arc::new(async_lock::RwLock::new(GenericStdin::new())),
-						)
-						.map_err(|_| Errno::Io)?;
-					guard
-						.try_insert(
-							STDOUT_FILENO,
-							Arc::new(async_lock::RwLock::new(GenericStdout::new())),
-						)
-						.map_err(|_| Errno::Io)?;
-					guard
-						.try_insert(
-							STDERR_FILENO,
-							Arc::new(async_lock::RwLock::new(GenericStderr::new())),
-						)
-						.map_err(|_| Errno::Io)?;
+					objmap.insert_at(
+						STDIN_FILENO,
+						Arc::new(async_lock::RwLock::new(GenericStdin::new())),
+					)?;
+					objmap.insert_at(
+						STDOUT_FILENO,
+						Arc::new(async_lock::RwLock::new(GenericStdout::new())),
+					)?;
+					objmap.insert_at(
+						STDERR_FILENO,
+						Arc::new(async_lock::RwLock::new(GenericStderr::new())),
+					)?;
 				}
 
 				Ok(())
@@ -520,6 +652,9 @@ impl Task {
 			last_stack_pointer: VirtAddr::zero(),
 			user_stack_pointer: VirtAddr::zero(),
 			last_fpu_state: arch::processor::FPUState::new(),
+			policy: Policy::Other,
+			ready_since: 0,
+			pinned: false,
 			core_id,
 			stacks: TaskStacks::from_boot_stacks(),
 			object_map: OBJECT_MAP.get().unwrap().clone(),
@@ -549,17 +684,35 @@ impl BlockedTask {
 }
 
 pub(crate) struct BlockedTaskQueue {
-	list: LinkedList<BlockedTask>,
+	/// The nodes are kept in a pool of this core, because tasks are blocked
+	/// and woken up frequently by futexes and timeouts.
+	list: LinkedList<BlockedTask, PoolAlloc>,
 	#[cfg(feature = "net")]
 	network_wakeup_time: Option<u64>,
+	/// End of the time slice of the current task, if it is a `SCHED_RR` task
+	time_slice_end: Option<u64>,
 }
 
 impl BlockedTaskQueue {
 	pub const fn new() -> Self {
 		Self {
-			list: LinkedList::new(),
+			list: LinkedList::new_in(PoolAlloc::new()),
 			#[cfg(feature = "net")]
 			network_wakeup_time: None,
+			time_slice_end: None,
+		}
+	}
+
+	pub fn time_slice_end(&self) -> Option<u64> {
+		self.time_slice_end
+	}
+
+	/// Sets the end of the time slice of the current task and arms the
+	/// One-Shot Timer for it.
+	pub fn set_time_slice_end(&mut self, time_slice_end: Option<u64>) {
+		if self.time_slice_end != time_slice_end {
+			self.time_slice_end = time_slice_end;
+			self.arm_timer();
 		}
 	}
 
@@ -586,6 +739,11 @@ impl BlockedTaskQueue {
 		borrowed.status = TaskStatus::Ready;
 	}
 
+	#[cfg(all(feature = "net", feature = "smp"))]
+	pub fn network_wakeup_time(&self) -> Option<u64> {
+		self.network_wakeup_time
+	}
+
 	#[cfg(feature = "net")]
 	pub fn add_network_timer(&mut self, wakeup_time: Option<u64>) {
 		self.network_wakeup_time = wakeup_time;
@@ -597,7 +755,7 @@ impl BlockedTaskQueue {
 			(a, b) => a.or(b),
 		};
 
-		arch::set_oneshot_timer(time);
+		arm_oneshot_timer(time, self.time_slice_end);
 	}
 
 	/// Blocks the given task for `wakeup_time` ticks, or indefinitely if None is given.
@@ -623,17 +781,17 @@ impl BlockedTaskQueue {
 			let mut cursor = self.list.cursor_front_mut();
 			let set_oneshot_timer = || {
 				#[cfg(not(feature = "net"))]
-				arch::set_oneshot_timer(wakeup_time);
+				arm_oneshot_timer(wakeup_time, self.time_slice_end);
 				#[cfg(feature = "net")]
 				match self.network_wakeup_time {
 					Some(time) => {
 						if time > wt {
-							arch::set_oneshot_timer(wakeup_time);
+							arm_oneshot_timer(wakeup_time, self.time_slice_end);
 						} else {
-							arch::set_oneshot_timer(self.network_wakeup_time);
+							arm_oneshot_timer(self.network_wakeup_time, self.time_slice_end);
 						}
 					}
-					_ => arch::set_oneshot_timer(wakeup_time),
+					_ => arm_oneshot_timer(wakeup_time, self.time_slice_end),
 				}
 			};
 
@@ -656,7 +814,32 @@ impl BlockedTaskQueue {
 	}
 
 	/// Manually wake up a blocked task.
-	pub fn custom_wakeup(&mut self, task: TaskHandle) -> Rc<RefCell<Task>> {
+	/// Changes the priority of the blocked task `id` and its scheduling class,
+	/// unless `policy` is `None`.
+	pub fn set_priority(
+		&mut self,
+		id: TaskId,
+		prio: Priority,
+		policy: Option<Policy>,
+	) -> Result<(), ()> {
+		let node = self
+			.list
+			.iter()
+			.find(|node| node.task.borrow().id == id)
+			.ok_or(())?;
+		let mut borrowed = node.task.borrow_mut();
+		borrowed.prio = prio;
+		if let Some(policy) = policy {
+			borrowed.policy = policy;
+		}
+		Ok(())
+	}
+
+	/// Wakes up the task `task`.
+	///
+	/// Returns `None`, if the task is not blocked on this core, e.g., because
+	/// its timeout has already woken it up or it has moved to another core.
+	pub fn custom_wakeup(&mut self, task: TaskHandle) -> Option<Rc<RefCell<Task>>> {
 		let mut first_task = true;
 		let mut cursor = self.list.cursor_front_mut();
 
@@ -678,40 +861,44 @@ impl BlockedTaskQueue {
 				// next task's wakeup time (if any).
 				#[cfg(feature = "net")]
 				if first_task {
-					arch::set_oneshot_timer(cursor.current().map_or_else(
-						|| self.network_wakeup_time,
-						|node| match node.wakeup_time {
-							Some(wt) => {
-								if let Some(timer) = self.network_wakeup_time {
-									if wt < timer { Some(wt) } else { Some(timer) }
-								} else {
-									Some(wt)
+					arm_oneshot_timer(
+						cursor.current().map_or_else(
+							|| self.network_wakeup_time,
+							|node| match node.wakeup_time {
+								Some(wt) => {
+									if let Some(timer) = self.network_wakeup_time {
+										if wt < timer { Some(wt) } else { Some(timer) }
+									} else {
+										Some(wt)
+									}
 								}
-							}
-							None => self.network_wakeup_time,
-						},
-					));
+								None => self.network_wakeup_time,
+							},
+						),
+						self.time_slice_end,
+					);
 				}
 				#[cfg(not(feature = "net"))]
 				if first_task {
-					arch::set_oneshot_timer(
+					arm_oneshot_timer(
 						cursor
 							.current()
 							.map_or_else(|| None, |node| node.wakeup_time),
+						self.time_slice_end,
 					);
 				}
 
 				// Wake it up.
 				Self::mark_ready(&task_ref);
 
-				return task_ref;
+				return Some(task_ref);
 			}
 
 			first_task = false;
 			cursor.move_next();
 		}
 
-		unreachable!();
+		None
 	}
 
 	/// Wakes up all tasks whose wakeup time has elapsed.
@@ -745,6 +932,32 @@ impl BlockedTaskQueue {
 			ready_queue.push(task.task);
 		}
 
+		self.arm_timer();
+	}
+
+	/// Removes the task `id` from the blocked tasks without waking it up, e.g.,
+	/// if it finishes, before it has switched to another task.
+	///
+	/// Returns whether the task has been blocked.
+	pub fn cancel(&mut self, id: TaskId) -> bool {
+		let was_first = self
+			.list
+			.front()
+			.is_some_and(|node| node.task.borrow().id == id);
+		let cancelled = self
+			.list
+			.extract_if(|node| node.task.borrow().id == id)
+			.count() > 0;
+
+		if was_first {
+			self.arm_timer();
+		}
+		cancelled
+	}
+
+	/// Arms the One-Shot Timer for the next wakeup of a blocked task or the
+	/// network or for the end of the time slice.
+	fn arm_timer(&self) {
 		let new_task_wakeup_time = self.list.front().and_then(|task| task.wakeup_time);
 		cfg_if::cfg_if! {
 			if 	#[cfg(feature = "net")] {
@@ -760,6 +973,6 @@ impl BlockedTaskQueue {
 			(Some(task_wt), Some(network_wt)) => Some(u64::min(task_wt, network_wt)),
 		};
 
-		arch::set_oneshot_timer(timer_wakeup_time);
+		arm_oneshot_timer(timer_wakeup_time, self.time_slice_end);
 	}
 }
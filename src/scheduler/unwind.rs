@@ -0,0 +1,128 @@
+//! Catching of foreign unwinds, which escape from the application.
+//!
+//! The kernel is built with `panic = "abort"`, so that `catch_unwind` of Rust
+//! does not catch anything in it. Instead, the application is called through
+//! `__hermit_catch_unwind`, which comes with its own unwind tables and uses the
+//! personality routine of Rust, if the application provides one. A foreign
+//! unwind, e.g., a C++ exception, which reaches it, ends there and is reported
+//! to the caller.
+//!
+//! A panic of Rust is not caught. The entry points of the application are
+//! `extern "C"` functions, at whose boundary a panic aborts. A panic, which
+//! reaches `__hermit_catch_unwind` through foreign frames, aborts as well,
+//! since Rust does not allow to release its exceptions with
+//! `_Unwind_DeleteException`.
+
+#[cfg(target_os = "none")]
+use core::arch::global_asm;
+
+#[cfg(all(target_os = "none", target_arch = "x86_64"))]
+global_asm!(
+	include_str!("unwind/x86_64.s"),
+	include_str!("unwind/lsda.s")
+);
+
+#[cfg(all(target_os = "none", target_arch = "aarch64"))]
+global_asm!(
+	include_str!("unwind/aarch64.s"),
+	include_str!("unwind/lsda.s")
+);
+
+#[cfg(all(target_os = "none", target_arch = "riscv64"))]
+global_asm!(
+	include_str!("unwind/riscv64.s"),
+	include_str!("unwind/lsda.s")
+);
+
+#[cfg(target_os = "none")]
+unsafe extern "C" {
+	fn __hermit_catch_unwind(func: extern "C" fn(usize), arg: usize) -> u32;
+}
+
+/// Calls `func` with `arg` and returns `Err(())`, if a foreign unwind has
+/// escaped from `func`.
+#[cfg(target_os = "none")]
+pub(crate) fn catch_unwind(func: extern "C" fn(usize), arg: usize) -> Result<(), ()> {
+	// SAFETY: `__hermit_catch_unwind` only calls `func` with `arg`.
+	if unsafe { __hermit_catch_unwind(func, arg) } == 0 {
+		Ok(())
+	} else {
+		Err(())
+	}
+}
+
+#[cfg(not(target_os = "none"))]
+pub(crate) fn catch_unwind(func: extern "C" fn(usize), arg: usize) -> Result<(), ()> {
+	func(arg);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use core::sync::atomic::{AtomicUsize, Ordering};
+
+	use super::*;
+
+	static ARG: AtomicUsize = AtomicUsize::new(0);
+
+	extern "C" fn store(arg: usize) {
+		ARG.store(arg, Ordering::Relaxed);
+	}
+
+	/// Reads the unsigned LEB128 number at `pos` and advances `pos`.
+	fn uleb128(data: &[u8], pos: &mut usize) -> u64 {
+		let mut value = 0;
+		let mut shift = 0;
+		loop {
+			let byte = data[*pos];
+			*pos += 1;
+			value |= u64::from(byte & 0x7f) << shift;
+			shift += 7;
+			if byte & 0x80 == 0 {
+				return value;
+			}
+		}
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_catch_unwind_return() {
+		assert_eq!(catch_unwind(store, 0x1234), Ok(()));
+		assert_eq!(ARG.load(Ordering::Relaxed), 0x1234);
+	}
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_catch_unwind_lsda() {
+		unsafe extern "C" {
+			static __hermit_catch_unwind_lsda: u8;
+			static __hermit_catch_unwind_lsda_end: u8;
+		}
+
+		let start = &raw const __hermit_catch_unwind_lsda;
+		let end = &raw const __hermit_catch_unwind_lsda_end;
+		// SAFETY: Both symbols enclose the LSDA in the same section.
+		let lsda = unsafe {
+			core::slice::from_raw_parts(start, usize::try_from(end.offset_from(start)).unwrap())
+		};
+
+		// No landing pad base and no type table, call sites in ULEB128
+		assert_eq!(lsda[..3], [0xff, 0xff, 0x01]);
+		let mut pos = 3;
+		let sites_len = usize::try_from(uleb128(lsda, &mut pos)).unwrap();
+		let sites_end = pos + sites_len;
+
+		// A single call site, whose landing pad follows the call
+		let call = uleb128(lsda, &mut pos);
+		let call_len = uleb128(lsda, &mut pos);
+		let pad = uleb128(lsda, &mut pos);
+		let action = uleb128(lsda, &mut pos);
+		assert_eq!(pos, sites_end);
+		assert!(call_len > 0);
+		assert!(pad >= call + call_len);
+		assert_eq!(action, 1);
+
+		// The action record catches every unwind and has no successor.
+		assert_eq!(lsda[pos..], [0x01, 0x00]);
+	}
+}
@@ -0,0 +1,70 @@
+// Calls `func` in x0 with `arg` in x1 and returns 0, or 1, if an unwind has
+// escaped from `func`.
+.pushsection .text.__hermit_catch_unwind,"ax",%progbits
+.globl __hermit_catch_unwind
+.hidden __hermit_catch_unwind
+.type __hermit_catch_unwind,%function
+.p2align 2
+__hermit_catch_unwind:
+	stp x29, x30, [sp, #-16]!
+.Lhermit_cu_frame:
+	mov x29, sp
+	mov x2, x0
+	mov x0, x1
+.Lhermit_cu_call:
+	blr x2
+.Lhermit_cu_ret:
+	mov w0, #0
+	ldp x29, x30, [sp], #16
+	ret
+.Lhermit_cu_pad:
+	// x0 holds the exception, which is released, if an unwinder exists.
+	adrp x1, .Lhermit_cu_delete
+	ldr x1, [x1, :lo12:.Lhermit_cu_delete]
+	cbz x1, .Lhermit_cu_done
+	blr x1
+.Lhermit_cu_done:
+	mov w0, #1
+	ldp x29, x30, [sp], #16
+	ret
+.Lhermit_cu_end:
+.size __hermit_catch_unwind, .Lhermit_cu_end - __hermit_catch_unwind
+.popsection
+
+.pushsection .eh_frame,"a",%progbits
+.p2align 3
+.Lhermit_cu_cie:
+	.long .Lhermit_cu_cie_end - .Lhermit_cu_cie_id
+.Lhermit_cu_cie_id:
+	.long 0
+	.byte 1
+	.asciz "zPLR"
+	.uleb128 1
+	.sleb128 -8
+	// The return address is in x30.
+	.uleb128 30
+	.uleb128 7
+	.byte 0x9b
+	.long .Lhermit_cu_personality - .
+	.byte 0x1b
+	.byte 0x1b
+	// DW_CFA_def_cfa sp, 0
+	.byte 0x0c, 0x1f, 0x00
+	.p2align 3
+.Lhermit_cu_cie_end:
+	.long .Lhermit_cu_fde_end - .Lhermit_cu_fde_cie
+.Lhermit_cu_fde_cie:
+	.long .Lhermit_cu_fde_cie - .Lhermit_cu_cie
+	.long __hermit_catch_unwind - .
+	.long .Lhermit_cu_end - __hermit_catch_unwind
+	.uleb128 4
+	.long .Lhermit_cu_lsda - .
+	// DW_CFA_advance_loc1, DW_CFA_def_cfa_offset 16, DW_CFA_offset x30, -8
+	// and DW_CFA_offset x29, -16
+	.byte 0x02, .Lhermit_cu_frame - __hermit_catch_unwind
+	.byte 0x0e, 0x10
+	.byte 0x9e, 0x01
+	.byte 0x9d, 0x02
+	.p2align 3
+.Lhermit_cu_fde_end:
+.popsection
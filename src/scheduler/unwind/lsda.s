@@ -0,0 +1,42 @@
+// The language-specific data area of `__hermit_catch_unwind` has a single call
+// site, the call of `func`, whose action record has a positive type filter. The
+// personality routine of Rust treats it as a handler, which catches every
+// unwind, and continues at the landing pad.
+.pushsection .gcc_except_table.__hermit_catch_unwind,"a",%progbits
+.p2align 2
+.globl __hermit_catch_unwind_lsda
+.hidden __hermit_catch_unwind_lsda
+__hermit_catch_unwind_lsda:
+.Lhermit_cu_lsda:
+	// The landing pads are relative to the start of the function and there is
+	// no type table.
+	.byte 0xff
+	.byte 0xff
+	// The call sites are encoded as ULEB128.
+	.byte 0x01
+	.uleb128 .Lhermit_cu_sites_end - .Lhermit_cu_sites
+.Lhermit_cu_sites:
+	.uleb128 .Lhermit_cu_call - __hermit_catch_unwind
+	.uleb128 .Lhermit_cu_ret - .Lhermit_cu_call
+	.uleb128 .Lhermit_cu_pad - __hermit_catch_unwind
+	.uleb128 1
+.Lhermit_cu_sites_end:
+	// The action record: type filter 1 and no next record
+	.sleb128 1
+	.sleb128 0
+.globl __hermit_catch_unwind_lsda_end
+.hidden __hermit_catch_unwind_lsda_end
+__hermit_catch_unwind_lsda_end:
+.popsection
+
+// An application without an unwinder, e.g., one written in C, does not have
+// these symbols, so that the pointers become null.
+.weak rust_eh_personality
+.weak _Unwind_DeleteException
+.pushsection .data.rel.ro.__hermit_catch_unwind,"aw",%progbits
+.p2align 3
+.Lhermit_cu_personality:
+	.quad rust_eh_personality
+.Lhermit_cu_delete:
+	.quad _Unwind_DeleteException
+.popsection
@@ -0,0 +1,76 @@
+// Calls `func` in a0 with `arg` in a1 and returns 0, or 1, if an unwind has
+// escaped from `func`.
+//
+// The tables below contain differences of labels in the code, which the linker
+// must not change by relaxation.
+.option push
+.option norelax
+.pushsection .text.__hermit_catch_unwind,"ax",%progbits
+.globl __hermit_catch_unwind
+.hidden __hermit_catch_unwind
+.type __hermit_catch_unwind,%function
+.p2align 2
+__hermit_catch_unwind:
+	addi sp, sp, -16
+	sd ra, 8(sp)
+.Lhermit_cu_frame:
+	mv t0, a0
+	mv a0, a1
+.Lhermit_cu_call:
+	jalr t0
+.Lhermit_cu_ret:
+	li a0, 0
+	ld ra, 8(sp)
+	addi sp, sp, 16
+	ret
+.Lhermit_cu_pad:
+	// a0 holds the exception, which is released, if an unwinder exists.
+	lla t0, .Lhermit_cu_delete
+	ld t0, 0(t0)
+	beqz t0, .Lhermit_cu_done
+	jalr t0
+.Lhermit_cu_done:
+	li a0, 1
+	ld ra, 8(sp)
+	addi sp, sp, 16
+	ret
+.Lhermit_cu_end:
+.size __hermit_catch_unwind, .Lhermit_cu_end - __hermit_catch_unwind
+.popsection
+
+.pushsection .eh_frame,"a",%progbits
+.p2align 3
+.Lhermit_cu_cie:
+	.long .Lhermit_cu_cie_end - .Lhermit_cu_cie_id
+.Lhermit_cu_cie_id:
+	.long 0
+	.byte 1
+	.asciz "zPLR"
+	.uleb128 1
+	.sleb128 -8
+	// The return address is in ra.
+	.uleb128 1
+	.uleb128 7
+	.byte 0x9b
+	.long .Lhermit_cu_personality - .
+	.byte 0x1b
+	.byte 0x1b
+	// DW_CFA_def_cfa sp, 0
+	.byte 0x0c, 0x02, 0x00
+	.p2align 3
+.Lhermit_cu_cie_end:
+	.long .Lhermit_cu_fde_end - .Lhermit_cu_fde_cie
+.Lhermit_cu_fde_cie:
+	.long .Lhermit_cu_fde_cie - .Lhermit_cu_cie
+	.long __hermit_catch_unwind - .
+	.long .Lhermit_cu_end - __hermit_catch_unwind
+	.uleb128 4
+	.long .Lhermit_cu_lsda - .
+	// DW_CFA_advance_loc1, DW_CFA_def_cfa_offset 16 and DW_CFA_offset ra, -8
+	.byte 0x02, .Lhermit_cu_frame - __hermit_catch_unwind
+	.byte 0x0e, 0x10
+	.byte 0x81, 0x01
+	.p2align 3
+.Lhermit_cu_fde_end:
+.popsection
+.option pop
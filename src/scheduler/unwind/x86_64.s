@@ -0,0 +1,70 @@
+// Calls `func` in rdi with `arg` in rsi and returns 0, or 1, if an unwind has
+// escaped from `func`.
+.pushsection .text.__hermit_catch_unwind,"ax",%progbits
+.globl __hermit_catch_unwind
+.hidden __hermit_catch_unwind
+.type __hermit_catch_unwind,%function
+.p2align 4
+__hermit_catch_unwind:
+	push rbp
+.Lhermit_cu_frame:
+	mov rbp, rsp
+	mov rax, rdi
+	mov rdi, rsi
+.Lhermit_cu_call:
+	call rax
+.Lhermit_cu_ret:
+	xor eax, eax
+	pop rbp
+	ret
+.Lhermit_cu_pad:
+	// rax holds the exception, which is released, if an unwinder exists.
+	mov rdi, rax
+	mov rax, qword ptr [rip + .Lhermit_cu_delete]
+	test rax, rax
+	jz .Lhermit_cu_done
+	call rax
+.Lhermit_cu_done:
+	mov eax, 1
+	pop rbp
+	ret
+.Lhermit_cu_end:
+.size __hermit_catch_unwind, .Lhermit_cu_end - __hermit_catch_unwind
+.popsection
+
+.pushsection .eh_frame,"a",%unwind
+.p2align 3
+.Lhermit_cu_cie:
+	.long .Lhermit_cu_cie_end - .Lhermit_cu_cie_id
+.Lhermit_cu_cie_id:
+	.long 0
+	.byte 1
+	.asciz "zPLR"
+	.uleb128 1
+	.sleb128 -8
+	// The return address is in rip.
+	.uleb128 16
+	.uleb128 7
+	.byte 0x9b
+	.long .Lhermit_cu_personality - .
+	.byte 0x1b
+	.byte 0x1b
+	// DW_CFA_def_cfa rsp, 8 and DW_CFA_offset rip, -8
+	.byte 0x0c, 0x07, 0x08
+	.byte 0x90, 0x01
+	.p2align 3
+.Lhermit_cu_cie_end:
+	.long .Lhermit_cu_fde_end - .Lhermit_cu_fde_cie
+.Lhermit_cu_fde_cie:
+	.long .Lhermit_cu_fde_cie - .Lhermit_cu_cie
+	.long __hermit_catch_unwind - .
+	.long .Lhermit_cu_end - __hermit_catch_unwind
+	.uleb128 4
+	.long .Lhermit_cu_lsda - .
+	// DW_CFA_advance_loc1, DW_CFA_def_cfa_offset 16 and DW_CFA_offset rbp, -16
+	.byte 0x02, .Lhermit_cu_frame - __hermit_catch_unwind
+	.byte 0x0e, 0x10
+	.byte 0x86, 0x02
+	.p2align 3
+.Lhermit_cu_fde_end:
+.popsection
@@ -1,56 +1,454 @@
-use embedded_io::{Read, Write};
-use simple_shell::*;
+//! Debug shell on the serial console.
+//!
+//! The shell owns the input of the console. Until it is activated by pressing
+//! Ctrl-] twice, it passes all input through to the application. While it is
+//! active, the input is interpreted as commands, which inspect the kernel.
+//! `continue` hands the console back to the application.
+//!
+//! The shell supports minimal line editing: the cursor can be moved with the
+//! arrow keys, Home/End, or Ctrl-A/Ctrl-E, and previous commands can be
+//! recalled with the up and down arrow keys.
 
-use crate::interrupts::print_statistics;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::future;
+use core::task::Poll;
 
-fn read() -> Option<u8> {
-	let mut buf = [0; 1];
-	let len = crate::console::CONSOLE.lock().read(&mut buf).ok()?;
-	if len > 0 { Some(buf[0]) } else { None }
+use embedded_io::Write;
+use hermit_sync::InterruptTicketMutex;
+use memory_addresses::VirtAddr;
+
+use crate::arch::mm::paging::{self, BasePageSize, PageSize};
+use crate::console::{CONSOLE, CONSOLE_WAKER};
+use crate::executor::WakerRegistration;
+
+/// Byte, which activates the shell, if it is received twice in a row (Ctrl-])
+const MAGIC: u8 = 0x1d;
+
+const PROMPT: &str = "hermit> ";
+
+/// Number of commands, which are kept in the history
+const HISTORY_LEN: usize = 16;
+
+/// Maximum number of buffered input bytes of the application
+const INPUT_LEN: usize = 4096;
+
+/// Maximum number of bytes printed by `dump`
+const MAX_DUMP_LEN: usize = 4096;
+
+/// Input, which has not been consumed by the application yet
+static INPUT: InterruptTicketMutex<VecDeque<u8>> = InterruptTicketMutex::new(VecDeque::new());
+
+/// Wakes the application, if input is available.
+pub(crate) static INPUT_WAKER: InterruptTicketMutex<WakerRegistration> =
+	InterruptTicketMutex::new(WakerRegistration::new());
+
+/// Reads the input of the application into `buf`.
+pub(crate) fn read_input(buf: &mut [u8]) -> usize {
+	let mut input = INPUT.lock();
+	let len = buf.len().min(input.len());
+	for (dst, byte) in buf.iter_mut().zip(input.drain(..len)) {
+		*dst = byte;
+	}
+	len
+}
+
+pub(crate) fn input_ready() -> bool {
+	!INPUT.lock().is_empty()
+}
+
+fn pass_through(byte: u8) {
+	let mut input = INPUT.lock();
+	if input.len() < INPUT_LEN {
+		input.push_back(byte);
+	}
+}
+
+fn flush() {
+	CONSOLE.lock().flush().ok();
+}
+
+struct Command {
+	name: &'static str,
+	args: &'static str,
+	help: &'static str,
+	/// Returns `false`, if the shell should be deactivated.
+	func: fn(&[&str]) -> bool,
+}
+
+const COMMANDS: &[Command] = &[
+	Command {
+		name: "help",
+		args: "",
+		help: "Print this help message",
+		func: help,
+	},
+	Command {
+		name: "tasks",
+		args: "",
+		help: "List all tasks",
+		func: |_| print_proc("/proc/tasks"),
+	},
+	Command {
+		name: "memory",
+		args: "",
+		help: "Show the usage of the physical memory",
+		func: |_| print_proc("/proc/meminfo"),
+	},
+	Command {
+		name: "mmio",
+		args: "",
+		help: "List the mapped device memory",
+		func: |_| print_proc("/proc/mmio"),
+	},
+	Command {
+		name: "interrupts",
+		args: "",
+		help: "Show the number of received interrupts",
+		func: |_| print_proc("/proc/interrupts"),
+	},
+	Command {
+		name: "dump",
+		args: "<address> [length]",
+		help: "Dump kernel memory",
+		func: dump,
+	},
+	Command {
+		name: "continue",
+		args: "",
+		help: "Return the console to the application",
+		func: |_| false,
+	},
+	Command {
+		name: "shutdown",
+		args: "",
+		help: "Shutdown HermitOS",
+		func: |_| crate::scheduler::shutdown(0),
+	},
+	Command {
+		name: "reboot",
+		args: "",
+		help: "Reboot the system",
+		func: |_| crate::syscalls::reboot(0),
+	},
+];
+
+fn help(_args: &[&str]) -> bool {
+	for command in COMMANDS {
+		println!(
+			"{:<30} {}",
+			format!("{} {}", command.name, command.args),
+			command.help
+		);
+	}
+	true
+}
+
+fn print_proc(path: &str) -> bool {
+	print!("{}", crate::fs::proc::generate(path).unwrap());
+	true
+}
+
+fn parse_number(s: &str) -> Option<usize> {
+	match s.strip_prefix("0x") {
+		Some(hex) => usize::from_str_radix(hex, 16).ok(),
+		None => s.parse().ok(),
+	}
+}
+
+fn dump(args: &[&str]) -> bool {
+	let (Some(addr), len) = (args.first().and_then(|s| parse_number(s)), args.get(1)) else {
+		println!("Usage: dump <address> [length]");
+		return true;
+	};
+	let Some(len) = len.map_or(Some(256), |s| parse_number(s)) else {
+		println!("Usage: dump <address> [length]");
+		return true;
+	};
+	let len = len.min(MAX_DUMP_LEN);
+	let Some(end) = addr.checked_add(len) else {
+		println!("Invalid range");
+		return true;
+	};
+
+	// reading unmapped memory would fault
+	let page_size = BasePageSize::SIZE as usize;
+	let first_page = addr & !(page_size - 1);
+	for page in (first_page..end).step_by(page_size) {
+		if paging::virtual_to_physical(VirtAddr::new(page as u64)).is_none() {
+			println!("{page:#x} is not mapped");
+			return true;
+		}
+	}
+
+	for line in (addr..end).step_by(16) {
+		let bytes = (line..end.min(line + 16))
+			.map(|addr| unsafe { (addr as *const u8).read_volatile() })
+			.collect::<Vec<_>>();
+
+		let mut hex = String::new();
+		for byte in &bytes {
+			hex.push_str(&format!("{byte:02x} "));
+		}
+		let ascii = bytes
+			.iter()
+			.map(|&byte| {
+				if byte.is_ascii_graphic() || byte == b' ' {
+					char::from(byte)
+				} else {
+					'.'
+				}
+			})
+			.collect::<String>();
+		println!("{line:016x}  {hex:<48} {ascii}");
+	}
+	true
+}
+
+/// Executes `line` and returns `false`, if the shell should be deactivated.
+fn execute(line: &str) -> bool {
+	let mut words = line.split_whitespace();
+	let Some(name) = words.next() else {
+		return true;
+	};
+	let args = words.collect::<Vec<_>>();
+
+	match COMMANDS.iter().find(|command| command.name == name) {
+		Some(command) => (command.func)(&args),
+		None => {
+			println!("Unknown command {name}, type `help` for a list of commands");
+			true
+		}
+	}
+}
+
+/// State of an escape sequence of a terminal
+enum Escape {
+	None,
+	/// ESC has been received.
+	Esc,
+	/// `ESC [` or `ESC O` and the decimal parameter have been received.
+	Csi(u8),
+}
+
+/// Line editor with history
+struct Editor {
+	line: Vec<u8>,
+	cursor: usize,
+	history: VecDeque<Vec<u8>>,
+	/// Entry of the history, which is currently shown
+	history_pos: Option<usize>,
+	escape: Escape,
+}
+
+impl Editor {
+	const fn new() -> Self {
+		Self {
+			line: Vec::new(),
+			cursor: 0,
+			history: VecDeque::new(),
+			history_pos: None,
+			escape: Escape::None,
+		}
+	}
+
+	fn redraw(&self) {
+		print!("\r{PROMPT}{}\x1b[K", String::from_utf8_lossy(&self.line));
+		let back = self.line.len() - self.cursor;
+		if back > 0 {
+			print!("\x1b[{back}D");
+		}
+		flush();
+	}
+
+	fn set_line(&mut self, line: Vec<u8>) {
+		self.cursor = line.len();
+		self.line = line;
+	}
+
+	fn history_up(&mut self) {
+		if self.history.is_empty() {
+			return;
+		}
+		let pos = self
+			.history_pos
+			.map_or(self.history.len() - 1, |pos| pos.saturating_sub(1));
+		self.history_pos = Some(pos);
+		self.set_line(self.history[pos].clone());
+	}
+
+	fn history_down(&mut self) {
+		let Some(pos) = self.history_pos else {
+			return;
+		};
+		if pos + 1 < self.history.len() {
+			self.history_pos = Some(pos + 1);
+			self.set_line(self.history[pos + 1].clone());
+		} else {
+			self.history_pos = None;
+			self.set_line(Vec::new());
+		}
+	}
+
+	/// Handles the final byte of an escape sequence.
+	fn escape(&mut self, param: u8, byte: u8) {
+		match (byte, param) {
+			(b'A', _) => self.history_up(),
+			(b'B', _) => self.history_down(),
+			(b'C', _) => self.cursor = (self.cursor + 1).min(self.line.len()),
+			(b'D', _) => self.cursor = self.cursor.saturating_sub(1),
+			(b'H', _) | (b'~', 1 | 7) => self.cursor = 0,
+			(b'F', _) | (b'~', 4 | 8) => self.cursor = self.line.len(),
+			(b'~', 3) => {
+				if self.cursor < self.line.len() {
+					self.line.remove(self.cursor);
+				}
+			}
+			_ => {}
+		}
+	}
+
+	/// Handles an input byte and returns the line, if it has been completed.
+	fn push(&mut self, byte: u8) -> Option<String> {
+		match self.escape {
+			Escape::Esc => {
+				self.escape = if matches!(byte, b'[' | b'O') {
+					Escape::Csi(0)
+				} else {
+					Escape::None
+				};
+				return None;
+			}
+			Escape::Csi(param) => {
+				if byte.is_ascii_digit() {
+					self.escape = Escape::Csi(param.saturating_mul(10).saturating_add(byte - b'0'));
+				} else {
+					self.escape = Escape::None;
+					self.escape(param, byte);
+					self.redraw();
+				}
+				return None;
+			}
+			Escape::None => {}
+		}
+
+		match byte {
+			0x1b => self.escape = Escape::Esc,
+			b'\r' | b'\n' => {
+				println!();
+				let line = core::mem::take(&mut self.line);
+				self.cursor = 0;
+				self.history_pos = None;
+				if !line.iter().all(u8::is_ascii_whitespace) && self.history.back() != Some(&line) {
+					if self.history.len() == HISTORY_LEN {
+						self.history.pop_front();
+					}
+					self.history.push_back(line.clone());
+				}
+				return Some(String::from_utf8_lossy(&line).into_owned());
+			}
+			// Backspace
+			0x7f | 0x08 => {
+				if self.cursor > 0 {
+					self.cursor -= 1;
+					self.line.remove(self.cursor);
+				}
+			}
+			// Ctrl-A
+			0x01 => self.cursor = 0,
+			// Ctrl-E
+			0x05 => self.cursor = self.line.len(),
+			// Ctrl-U
+			0x15 => self.set_line(Vec::new()),
+			// Ctrl-C
+			0x03 => {
+				println!("^C");
+				self.set_line(Vec::new());
+				self.history_pos = None;
+			}
+			byte if byte == b' ' || byte.is_ascii_graphic() => {
+				self.line.insert(self.cursor, byte);
+				self.cursor += 1;
+			}
+			_ => return None,
+		}
+
+		self.redraw();
+		None
+	}
+}
+
+struct Shell {
+	active: bool,
+	/// The first magic byte has been received.
+	magic: bool,
+	editor: Editor,
+}
+
+impl Shell {
+	fn push(&mut self, byte: u8) {
+		if !self.active {
+			match (self.magic, byte == MAGIC) {
+				(false, true) => self.magic = true,
+				(true, true) => {
+					self.magic = false;
+					self.active = true;
+					println!();
+					println!("HermitOS debug shell, type `help` for a list of commands");
+					self.editor.redraw();
+				}
+				(true, false) => {
+					self.magic = false;
+					pass_through(MAGIC);
+					pass_through(byte);
+				}
+				(false, false) => pass_through(byte),
+			}
+			return;
+		}
+
+		if let Some(line) = self.editor.push(byte) {
+			if execute(&line) {
+				self.editor.redraw();
+			} else {
+				self.active = false;
+				println!("Returning the console to the application");
+			}
+		}
+	}
+}
+
+async fn run() {
+	let mut shell = Shell {
+		active: false,
+		magic: false,
+		editor: Editor::new(),
+	};
+
+	future::poll_fn(|cx| {
+		let mut buf = [0; 32];
+		loop {
+			// register before reading, so that no input is missed
+			CONSOLE_WAKER.lock().register(cx.waker());
+			let len = CONSOLE.lock().read_device(&mut buf).unwrap_or(0);
+			if len == 0 {
+				return Poll::<()>::Pending;
+			}
+
+			let waiting = INPUT.lock().len();
+			for &byte in &buf[..len] {
+				shell.push(byte);
+			}
+			if INPUT.lock().len() > waiting {
+				INPUT_WAKER.lock().wake();
+			}
+		}
+	})
+	.await;
 }
 
 pub(crate) fn init() {
-	let (print, read) = (
-		|s: &str| {
-			print!("{s}");
-			// flush buffer to see the input
-			crate::console::CONSOLE.lock().flush().unwrap();
-		},
-		read,
-	);
-	let mut shell = Shell::new(print, read);
-
-	shell.commands.insert(
-		"help",
-		ShellCommand {
-			help: "Print this help message",
-			func: |_, shell| {
-				shell.print_help_screen();
-				Ok(())
-			},
-			aliases: &["?", "h"],
-		},
-	);
-	shell.commands.insert(
-		"interrupts",
-		ShellCommand {
-			help: "Shows the number of received interrupts",
-			func: |_, _| {
-				print_statistics();
-				Ok(())
-			},
-			aliases: &["i"],
-		},
-	);
-	shell.commands.insert(
-		"shutdown",
-		ShellCommand {
-			help: "Shutdown HermitOS",
-			func: |_, _| crate::scheduler::shutdown(0),
-			aliases: &["s"],
-		},
-	);
-
-	// Also supports async
-	crate::executor::spawn(async move { shell.run_async().await });
+	info!("Press Ctrl-] twice to enter the debug shell");
+	crate::executor::spawn(run());
 }
@@ -0,0 +1,174 @@
+//! Hooks of the application, which run on an orderly shutdown.
+//!
+//! The application registers hooks with
+//! [`sys_register_shutdown_hook`](crate::syscalls::sys_register_shutdown_hook).
+//! They run in the reverse order of their registration, before the system is
+//! shut down by [`sys_exit`](crate::syscalls::sys_exit), by returning from
+//! `main`, or by a stop request of the host. On x86-64, the host requests a
+//! stop through the ACPI power button, e.g., with `system_powerdown` in QEMU.
+//! The other platforms do not provide such a request.
+//!
+//! The hooks run in a task of their own and may block, but they share a
+//! timeout, which is given by `shutdown_timeout=<seconds>` (default 5). After
+//! the timeout, the system is shut down, even if hooks are still running. The
+//! hooks do not run after a panic, on `sys_abort`, or on a SysRq command.
+//! A hook, which exits itself, shuts down the system immediately.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use hermit_sync::InterruptTicketMutex;
+
+use crate::arch::core_local::{core_id, core_scheduler};
+use crate::arch::processor::get_timer_ticks;
+use crate::config::USER_STACK_SIZE;
+use crate::errno::Errno;
+use crate::scheduler::task::{NORMAL_PRIO, TaskId};
+use crate::scheduler::{PerCoreScheduler, unwind};
+use crate::synch::futex::{self, Flags};
+
+/// Maximum number of registered hooks
+const MAX_HOOKS: usize = 32;
+
+/// Default timeout of the hooks in seconds
+const DEFAULT_TIMEOUT: u64 = 5;
+
+#[derive(Clone, Copy)]
+struct Hook {
+	func: extern "C" fn(usize),
+	arg: usize,
+}
+
+static HOOKS: InterruptTicketMutex<Vec<Hook>> = InterruptTicketMutex::new(Vec::new());
+
+/// The hooks have been started.
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Becomes 1, when all hooks have returned.
+static DONE: AtomicU32 = AtomicU32::new(0);
+
+/// Time in microseconds since boot, after which the hooks are abandoned
+static DEADLINE: AtomicU64 = AtomicU64::new(0);
+
+/// Identifier of the task running the hooks
+static HOOK_TASK: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Becomes 1, when the host requests a stop.
+static STOP_REQUESTED: AtomicU32 = AtomicU32::new(0);
+
+/// Registers a hook, which is called with `arg` on an orderly shutdown.
+pub(crate) fn register(func: extern "C" fn(usize), arg: usize) -> Result<(), Errno> {
+	if STARTED.load(Ordering::SeqCst) {
+		return Err(Errno::Inval);
+	}
+
+	let mut hooks = HOOKS.lock();
+	if hooks.len() == MAX_HOOKS {
+		return Err(Errno::Nomem);
+	}
+	hooks.push(Hook { func, arg });
+	Ok(())
+}
+
+extern "C" fn hook_task(_arg: usize) {
+	loop {
+		// The lock is released, while a hook runs, because it may block.
+		let Some(hook) = HOOKS.lock().pop() else {
+			break;
+		};
+		if unwind::catch_unwind(hook.func, hook.arg).is_err() {
+			warn!("A shutdown hook has been ended by a foreign unwind");
+		}
+	}
+
+	DONE.store(1, Ordering::SeqCst);
+	futex::futex_wake(&DONE, i32::MAX);
+}
+
+/// Runs the hooks and waits, until they have returned or the timeout has
+/// elapsed.
+fn run_hooks() {
+	let current = core_scheduler().get_current_task_id().into();
+	if STARTED.swap(true, Ordering::SeqCst) {
+		// A hook, which exits, does not wait for itself.
+		if HOOK_TASK.load(Ordering::SeqCst) == current {
+			return;
+		}
+	} else {
+		if HOOKS.lock().is_empty() {
+			DONE.store(1, Ordering::SeqCst);
+			return;
+		}
+
+		let timeout = crate::env::shutdown_timeout().unwrap_or(DEFAULT_TIMEOUT);
+		let deadline = get_timer_ticks().saturating_add(timeout.saturating_mul(1_000_000));
+		DEADLINE.store(deadline, Ordering::SeqCst);
+		debug!("Running shutdown hooks with a timeout of {timeout} seconds");
+		let spawned = unsafe {
+			PerCoreScheduler::spawn(
+				hook_task,
+				0,
+				NORMAL_PRIO,
+				core_id(),
+				true,
+				USER_STACK_SIZE,
+				TaskId::MAX,
+			)
+		};
+		let Ok(id) = spawned else {
+			warn!("Unable to spawn a task for the shutdown hooks");
+			return;
+		};
+		HOOK_TASK.store(id.into(), Ordering::SeqCst);
+	}
+
+	let deadline = DEADLINE.load(Ordering::SeqCst);
+	while DONE.load(Ordering::SeqCst) == 0 {
+		let ret = futex::futex_wait(&DONE, 0, Some(deadline), Flags::empty());
+		if ret == -i32::from(Errno::Timedout) {
+			warn!("Shutdown hooks did not finish in time");
+			break;
+		}
+	}
+}
+
+/// Runs the hooks and shuts the system down.
+pub(crate) fn orderly(arg: i32) -> ! {
+	run_hooks();
+	crate::syscalls::shutdown(arg)
+}
+
+/// Records a stop request of the host.
+///
+/// This function is called by interrupt handlers. The shutdown itself is
+/// performed by the task started with [`watch_stop_requests`].
+pub(crate) fn request_stop() {
+	if STOP_REQUESTED.swap(1, Ordering::SeqCst) == 0 {
+		futex::futex_wake(&STOP_REQUESTED, 1);
+	}
+}
+
+extern "C" fn stop_task(_arg: usize) {
+	while STOP_REQUESTED.load(Ordering::SeqCst) == 0 {
+		futex::futex_wait(&STOP_REQUESTED, 0, None, Flags::empty());
+	}
+
+	info!("Stop requested by the host");
+	orderly(0)
+}
+
+/// Starts a task, which shuts the system down on a stop request of the host.
+pub(crate) fn watch_stop_requests() {
+	unsafe {
+		PerCoreScheduler::spawn(
+			stop_task,
+			0,
+			NORMAL_PRIO,
+			core_id(),
+			true,
+			USER_STACK_SIZE,
+			TaskId::MAX,
+		)
+		.expect("Unable to spawn the task for stop requests");
+	}
+}
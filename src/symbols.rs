@@ -0,0 +1,124 @@
+//! Symbol table of the image, which resolves addresses to function names.
+//!
+//! With the feature `symbols`, the kernel reserves the section
+//! `.hermit_symbols`. The kernel is linked into the application, so the
+//! addresses of its functions are only known after the application has been
+//! linked. Afterwards, `cargo xtask symbols <image>` writes the function
+//! symbols of the image into the section. Without the feature or without the
+//! table, addresses are printed without names.
+//!
+//! The table starts with a [`Header`], which is followed by the entries
+//! sorted by their address and by the names. All addresses are offsets to
+//! the start of the image.
+
+#[cfg(feature = "symbols")]
+use core::cell::UnsafeCell;
+use core::fmt;
+
+/// Magic number at the start of the table
+#[cfg(feature = "symbols")]
+const MAGIC: [u8; 8] = *b"HSYMTAB\0";
+
+#[cfg(feature = "symbols")]
+#[repr(transparent)]
+struct Table(UnsafeCell<[u8; crate::config::SYMBOL_TABLE_SIZE]>);
+
+// SAFETY: The table is only written by the tooling before the image is loaded.
+#[cfg(feature = "symbols")]
+unsafe impl Sync for Table {}
+
+/// The table is an `UnsafeCell`, so that the compiler does not assume, that
+/// it stays zeroed.
+#[cfg(feature = "symbols")]
+#[used]
+#[unsafe(link_section = ".hermit_symbols")]
+static TABLE: Table = Table(UnsafeCell::new([0; crate::config::SYMBOL_TABLE_SIZE]));
+
+#[repr(C)]
+struct Header {
+	magic: [u8; 8],
+	/// Number of entries
+	count: u32,
+	/// Length of the names in bytes
+	names_len: u32,
+}
+
+#[repr(C)]
+struct Entry {
+	/// Offset of the function to the start of the image
+	offset: u32,
+	/// Size of the function in bytes
+	size: u32,
+	/// Offset of the name within the names
+	name_start: u32,
+	/// Length of the name in bytes
+	name_len: u32,
+}
+
+/// Returns the content of the table, if the tooling has written it.
+fn table() -> Option<&'static [u8]> {
+	cfg_if::cfg_if! {
+		if #[cfg(feature = "symbols")] {
+			// SAFETY: The table is not modified at runtime.
+			let table = unsafe { &*TABLE.0.get() };
+			table.starts_with(&MAGIC).then_some(table.as_slice())
+		} else {
+			None
+		}
+	}
+}
+
+fn read<T>(table: &[u8], offset: usize) -> Option<T> {
+	let bytes = table.get(offset..offset.checked_add(size_of::<T>())?)?;
+	Some(unsafe { bytes.as_ptr().cast::<T>().read_unaligned() })
+}
+
+/// Returns the name of the function, which contains `addr`, and the offset
+/// of `addr` within the function.
+pub(crate) fn lookup(addr: usize) -> Option<(&'static str, usize)> {
+	let table = table()?;
+	let header = read::<Header>(table, 0)?;
+	let count = usize::try_from(header.count).unwrap();
+	let entries_start = size_of::<Header>();
+	let names_start = entries_start.checked_add(count.checked_mul(size_of::<Entry>())?)?;
+	let names = table.get(names_start..names_start + usize::try_from(header.names_len).unwrap())?;
+
+	let offset = addr.checked_sub(crate::arch::kernel::get_base_address().as_usize())?;
+	let offset = u32::try_from(offset).ok()?;
+	let entry = |index: usize| read::<Entry>(table, entries_start + index * size_of::<Entry>());
+
+	// Find the last entry, which starts at or before `offset`.
+	let (mut low, mut high) = (0, count);
+	while low < high {
+		let mid = low + (high - low) / 2;
+		if entry(mid)?.offset <= offset {
+			low = mid + 1;
+		} else {
+			high = mid;
+		}
+	}
+	let entry = entry(low.checked_sub(1)?)?;
+	let within = offset - entry.offset;
+	if within >= entry.size.max(1) {
+		return None;
+	}
+
+	let name_start = usize::try_from(entry.name_start).unwrap();
+	let name = names.get(name_start..name_start + usize::try_from(entry.name_len).unwrap())?;
+	let name = core::str::from_utf8(name).ok()?;
+	Some((name, usize::try_from(within).unwrap()))
+}
+
+/// Address, which is printed with the name of its function, if it is known
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Symbolized(pub usize);
+
+impl fmt::Display for Symbolized {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{:#018x}", self.0)?;
+		if let Some((name, offset)) = lookup(self.0) {
+			write!(f, " <{name}+{offset:#x}>")?;
+		}
+		Ok(())
+	}
+}
@@ -10,8 +10,10 @@ use crate::arch::kernel::core_local::core_scheduler;
 use crate::arch::kernel::processor::get_timer_ticks;
 use crate::errno::Errno;
 use crate::scheduler::PerCoreSchedulerExt;
-use crate::scheduler::task::TaskHandlePriorityQueue;
+use crate::scheduler::task::{TaskHandle, TaskHandlePriorityQueue};
 
+/// Wait queues of the futexes, which are taken from and returned to the
+/// pools of the cores, so that waiting does not allocate in the common case
 // TODO: Replace with a concurrent hashmap.
 static PARKING_LOT: InterruptTicketMutex<HashMap<usize, TaskHandlePriorityQueue, RandomState>> =
 	InterruptTicketMutex::new(HashMap::with_hasher(RandomState::with_seeds(0, 0, 0, 0)));
@@ -55,7 +57,10 @@ pub(crate) fn futex_wait(
 	let scheduler = core_scheduler();
 	scheduler.block_current_task(wakeup_time);
 	let handle = scheduler.get_current_task_handle();
-	parking_lot.entry(addr(address)).or_default().push(handle);
+	parking_lot
+		.entry(addr(address))
+		.or_insert_with(|| scheduler.take_futex_queue())
+		.push(handle);
 	drop(parking_lot);
 
 	loop {
@@ -69,7 +74,7 @@ pub(crate) fn futex_wait(
 				// If we are not in the waking queue, this must have been a wakeup.
 				wakeup = !queue.get_mut().remove(handle);
 				if queue.get().is_empty() {
-					queue.remove();
+					scheduler.put_futex_queue(queue.remove());
 				}
 			}
 
@@ -87,7 +92,8 @@ pub(crate) fn futex_wait(
 				return 0;
 			} else {
 				// A spurious wakeup occurred, sleep again.
-				// Tasks do not change core, so the handle in the parking lot is still current.
+				// If the task has moved to another core, the wakeup of the handle in the
+				// parking lot is forwarded to its current core.
 				scheduler.block_current_task(wakeup_time);
 			}
 		}
@@ -124,7 +130,10 @@ pub(crate) fn futex_wait_and_set(
 	let scheduler = core_scheduler();
 	scheduler.block_current_task(wakeup_time);
 	let handle = scheduler.get_current_task_handle();
-	parking_lot.entry(addr(address)).or_default().push(handle);
+	parking_lot
+		.entry(addr(address))
+		.or_insert_with(|| scheduler.take_futex_queue())
+		.push(handle);
 	drop(parking_lot);
 
 	loop {
@@ -138,7 +147,7 @@ pub(crate) fn futex_wait_and_set(
 				// If we are not in the waking queue, this must have been a wakeup.
 				wakeup = !queue.get_mut().remove(handle);
 				if queue.get().is_empty() {
-					queue.remove();
+					scheduler.put_futex_queue(queue.remove());
 				}
 			}
 
@@ -156,7 +165,8 @@ pub(crate) fn futex_wait_and_set(
 				return 0;
 			} else {
 				// A spurious wakeup occurred, sleep again.
-				// Tasks do not change core, so the handle in the parking lot is still current.
+				// If the task has moved to another core, the wakeup of the handle in the
+				// parking lot is forwarded to its current core.
 				scheduler.block_current_task(wakeup_time);
 			}
 		}
@@ -191,7 +201,7 @@ pub(crate) fn futex_wake(address: *const AtomicU32, count: i32) -> i32 {
 	}
 
 	if queue.get().is_empty() {
-		queue.remove();
+		scheduler.put_futex_queue(queue.remove());
 	}
 
 	woken
@@ -226,7 +236,7 @@ pub(crate) fn futex_wake_or_set(address: &AtomicU32, count: i32, new_value: u32)
 	}
 
 	if queue.get().is_empty() {
-		queue.remove();
+		scheduler.put_futex_queue(queue.remove());
 	}
 
 	if woken == 0 {
@@ -235,3 +245,12 @@ pub(crate) fn futex_wake_or_set(address: &AtomicU32, count: i32, new_value: u32)
 
 	woken
 }
+
+/// Removes the task `handle` from the wait queues of all futexes, e.g., if it
+/// finishes, before it has switched to another task.
+pub(crate) fn cancel_wait(handle: TaskHandle) {
+	PARKING_LOT.lock().retain(|_, queue| {
+		queue.remove(handle);
+		!queue.is_empty()
+	});
+}
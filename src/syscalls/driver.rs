@@ -0,0 +1,148 @@
+#[cfg(feature = "net")]
+use core::ffi::{CStr, c_char};
+
+#[cfg(feature = "net")]
+use crate::drivers::binding::{self, Device};
+use crate::drivers::failure::{self, DriverFailure};
+use crate::drivers::irq_affinity;
+use crate::errno::Errno;
+#[cfg(feature = "net")]
+use crate::executor::network::NIC;
+
+#[cfg(feature = "net")]
+fn device_from_name(name: *const c_char) -> Result<Device, Errno> {
+	if name.is_null() {
+		return Err(Errno::Inval);
+	}
+
+	let name = unsafe { CStr::from_ptr(name) }
+		.to_str()
+		.map_err(|_| Errno::Inval)?;
+	Device::from_name(name)
+}
+
+/// Detaches the kernel driver from the device `name` (e.g. `net0`).
+///
+/// Afterwards, the kernel no longer uses the device and its interrupts are disabled.
+/// A detached network card can be driven through [`sys_net_raw_send`] and
+/// [`sys_net_raw_recv`].
+#[cfg(feature = "net")]
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_driver_unbind(name: *const c_char) -> i32 {
+	device_from_name(name)
+		.and_then(binding::unbind)
+		.map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Binds the device `name` to its kernel driver again.
+#[cfg(feature = "net")]
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_driver_bind(name: *const c_char) -> i32 {
+	device_from_name(name)
+		.and_then(binding::bind)
+		.map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Returns 1 if the device `name` is bound to its kernel driver and 0 if not.
+#[cfg(feature = "net")]
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_driver_is_bound(name: *const c_char) -> i32 {
+	device_from_name(name)
+		.and_then(binding::is_bound)
+		.map_or_else(|e| -i32::from(e), i32::from)
+}
+
+/// Receives a raw frame from the detached network card.
+///
+/// Returns the number of received bytes or `-EAGAIN`, if no frame is available.
+/// Frames, which are larger than `len`, are truncated.
+#[cfg(feature = "net")]
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_net_raw_recv(buf: *mut u8, len: usize) -> isize {
+	if buf.is_null() {
+		return -isize::try_from(i32::from(Errno::Inval)).unwrap();
+	}
+
+	let slice = unsafe { core::slice::from_raw_parts_mut(buf, len) };
+	NIC.lock()
+		.as_nic_mut()
+		.map_err(|_| Errno::Nodev)
+		.and_then(|nic| nic.raw_receive(slice))
+		.map_or_else(
+			|e| isize::try_from(-i32::from(e)).unwrap(),
+			|v| v.try_into().unwrap(),
+		)
+}
+
+/// Transmits the raw frame `buf` through the detached network card.
+///
+/// Returns the number of transmitted bytes or `-EAGAIN`, if the device is busy.
+#[cfg(feature = "net")]
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_net_raw_send(buf: *const u8, len: usize) -> isize {
+	if buf.is_null() {
+		return -isize::try_from(i32::from(Errno::Inval)).unwrap();
+	}
+
+	let slice = unsafe { core::slice::from_raw_parts(buf, len) };
+	NIC.lock()
+		.as_nic_mut()
+		.map_err(|_| Errno::Nodev)
+		.and_then(|nic| nic.raw_transmit(slice))
+		.map_or_else(
+			|e| isize::try_from(-i32::from(e)).unwrap(),
+			|v| v.try_into().unwrap(),
+		)
+}
+
+/// Copies the failures of drivers, which could not initialize their device,
+/// into `buf`, which has room for `len` entries.
+///
+/// Returns the number of all failures, which may be larger than `len`.
+/// Optional devices, whose drivers failed, are unavailable, but the
+/// application may decide to continue without them.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_driver_failures(buf: *mut DriverFailure, len: usize) -> isize {
+	if buf.is_null() && len != 0 {
+		return -isize::try_from(i32::from(Errno::Inval)).unwrap();
+	}
+
+	let slice = if len == 0 {
+		&mut []
+	} else {
+		unsafe { core::slice::from_raw_parts_mut(buf, len) }
+	};
+	failure::failures(slice).try_into().unwrap()
+}
+
+/// Routes the interrupt line `irq` of a device to the core `core_id`.
+///
+/// At boot, the lines are distributed among the cores, see `irqaffinity=`.
+/// Returns `-EINVAL`, if the core does not exist, and `-ENOSYS`, if the line
+/// cannot be routed, e.g., on architectures other than x86-64.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_irq_set_affinity(irq: u32, core_id: u32) -> i32 {
+	let Ok(irq) = u8::try_from(irq) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	irq_affinity::set(irq, core_id).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Returns the core, to which the interrupt line `irq` of a device is routed.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_irq_get_affinity(irq: u32) -> i32 {
+	let Ok(irq) = u8::try_from(irq) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	irq_affinity::get(irq).try_into().unwrap()
+}
@@ -1,6 +1,8 @@
 use core::sync::atomic::AtomicU32;
 
+use crate::arch::core_local::core_scheduler;
 use crate::errno::Errno;
+use crate::scheduler::PerCoreSchedulerExt;
 use crate::synch::futex::{self as synch, Flags};
 use crate::time::timespec;
 
@@ -43,6 +45,7 @@ pub unsafe extern "C" fn sys_futex_wait(
 /// Returns -EINVAL if `address` is null.
 /// `address` is used only for its address.
 /// It is safe to pass a dangling pointer.
+/// A woken real-time task of a higher priority preempts the current task.
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_futex_wake(address: *mut u32, count: i32) -> i32 {
@@ -50,5 +53,9 @@ pub unsafe extern "C" fn sys_futex_wake(address: *mut u32, count: i32) -> i32 {
 		return -i32::from(Errno::Inval);
 	}
 
-	synch::futex_wake(address as *const AtomicU32, count)
+	let woken = synch::futex_wake(address as *const AtomicU32, count);
+	if woken > 0 {
+		core_scheduler().preempt();
+	}
+	woken
 }
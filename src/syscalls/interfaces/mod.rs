@@ -54,4 +54,12 @@ pub trait SyscallInterface: Send + Sync {
 
 		arch::processor::shutdown(error_code)
 	}
+
+	/// Resets the system, if the hypervisor supports it, and shuts it down with
+	/// `error_code` otherwise.
+	fn reboot(&self, _error_code: i32) -> ! {
+		panic_println!("rebooting");
+
+		arch::processor::reboot()
+	}
 }
@@ -88,4 +88,9 @@ impl SyscallInterface for Uhyve {
 			arch::processor::halt();
 		}
 	}
+
+	/// Uhyve cannot reset the virtual machine.
+	fn reboot(&self, error_code: i32) -> ! {
+		self.shutdown(error_code)
+	}
 }
@@ -8,8 +8,12 @@ use crate::arch;
 #[cfg(target_arch = "x86_64")]
 use crate::arch::mm::paging::PageTableEntryFlagsExt;
 use crate::arch::mm::paging::{BasePageSize, PageSize, PageTableEntryFlags};
+use crate::errno::Errno;
+use crate::fd::{self, FileDescriptor};
+use crate::mm::file_mapping;
 use crate::mm::physicalmem::PHYSICAL_FREE_LIST;
 use crate::mm::virtualmem::KERNEL_FREE_LIST;
+use crate::syscalls::{file_offset, off_t};
 
 bitflags! {
 	#[repr(transparent)]
@@ -26,18 +30,27 @@ bitflags! {
 	}
 }
 
-/// Creates a new virtual memory mapping of the `size` specified with
-/// protection bits specified in `prot_flags`.
-#[hermit_macro::system(errno)]
-#[unsafe(no_mangle)]
-pub extern "C" fn sys_mmap(size: usize, prot_flags: MemoryProtection, ret: &mut *mut u8) -> i32 {
-	let size = size.align_up(BasePageSize::SIZE as usize);
+bitflags! {
+	#[repr(transparent)]
+	#[derive(Debug, Copy, Clone, Default)]
+	pub struct MapFlags: u32 {
+		/// Changes are written back to the file.
+		const Shared = 0x01;
+		/// Changes are private to the mapping.
+		const Private = 0x02;
+	}
+}
+
+const MS_ASYNC: i32 = 1;
+const MS_INVALIDATE: i32 = 2;
+const MS_SYNC: i32 = 4;
+
+fn map_memory(size: usize, prot_flags: MemoryProtection) -> VirtAddr {
 	let layout = PageLayout::from_size(size).unwrap();
 	let page_range = KERNEL_FREE_LIST.lock().allocate(layout).unwrap();
 	let virtual_address = VirtAddr::from(page_range.start());
 	if prot_flags.is_empty() {
-		*ret = virtual_address.as_mut_ptr();
-		return 0;
+		return virtual_address;
 	}
 	let frame_layout = PageLayout::from_size(size).unwrap();
 	let frame_range = PHYSICAL_FREE_LIST.lock().allocate(frame_layout).unwrap();
@@ -56,11 +69,88 @@ pub extern "C" fn sys_mmap(size: usize, prot_flags: MemoryProtection, ret: &mut
 
 	arch::mm::paging::map::<BasePageSize>(virtual_address, physical_address, count, flags);
 
+	virtual_address
+}
+
+/// Creates a new virtual memory mapping of the `size` specified with
+/// protection bits specified in `prot_flags`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_mmap(size: usize, prot_flags: MemoryProtection, ret: &mut *mut u8) -> i32 {
+	let size = size.align_up(BasePageSize::SIZE as usize);
+	*ret = map_memory(size, prot_flags).as_mut_ptr();
+
+	0
+}
+
+/// Maps `size` bytes of the file `fd`, starting at `offset`, with the
+/// protection bits specified in `prot_flags`.
+///
+/// `flags` has to contain either [`MapFlags::Shared`], in which case changes
+/// are written back to the file by [`sys_msync`] and [`sys_munmap`], or
+/// [`MapFlags::Private`]. `offset` has to be a multiple of the page size.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_mmap_file(
+	fd: FileDescriptor,
+	offset: off_t,
+	size: usize,
+	prot_flags: MemoryProtection,
+	flags: MapFlags,
+	ret: &mut *mut u8,
+) -> i32 {
+	let shared = match (
+		flags.contains(MapFlags::Shared),
+		flags.contains(MapFlags::Private),
+	) {
+		(true, false) => true,
+		(false, true) => false,
+		_ => return -i32::from(Errno::Inval),
+	};
+	let offset = match file_offset(offset) {
+		Ok(offset) => offset,
+		Err(err) => return -i32::from(err),
+	};
+	if size == 0 || !offset.is_multiple_of(BasePageSize::SIZE as usize) {
+		return -i32::from(Errno::Inval);
+	}
+	let object = match fd::get_object(fd) {
+		Ok(object) => object,
+		Err(err) => return -i32::from(err),
+	};
+
+	let size = size.align_up(BasePageSize::SIZE as usize);
+	// The content of the file is copied into the mapping.
+	let virtual_address = map_memory(size, prot_flags | MemoryProtection::Read);
+	if let Err(err) = file_mapping::map(virtual_address, size, object, offset, shared) {
+		unmap_memory(virtual_address, size);
+		return -i32::from(err);
+	}
+
 	*ret = virtual_address.as_mut_ptr();
 
 	0
 }
 
+/// Writes the shared file mappings within `size` bytes at `ptr` back to their files.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_msync(ptr: *mut u8, size: usize, flags: i32) -> i32 {
+	let virtual_address = VirtAddr::from_ptr(ptr);
+	if flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0
+		|| flags & (MS_ASYNC | MS_SYNC) == MS_ASYNC | MS_SYNC
+		|| !virtual_address.is_aligned_to(BasePageSize::SIZE)
+	{
+		return -i32::from(Errno::Inval);
+	}
+
+	// Mappings are always written back synchronously.
+	match file_mapping::sync(virtual_address, size) {
+		Ok(()) => 0,
+		Err(err) => -i32::from(err),
+	}
+}
+
 /// Unmaps memory at the specified `ptr` for `size` bytes.
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
@@ -68,6 +158,15 @@ pub extern "C" fn sys_munmap(ptr: *mut u8, size: usize) -> i32 {
 	let virtual_address = VirtAddr::from_ptr(ptr);
 	let size = size.align_up(BasePageSize::SIZE as usize);
 
+	if let Err(err) = file_mapping::unmap(virtual_address, size) {
+		error!("Unable to write back file mapping at {virtual_address:X}: {err:?}");
+	}
+	unmap_memory(virtual_address, size);
+
+	0
+}
+
+fn unmap_memory(virtual_address: VirtAddr, size: usize) {
 	if let Some(physical_address) = arch::mm::paging::virtual_to_physical(virtual_address) {
 		arch::mm::paging::unmap::<BasePageSize>(
 			virtual_address,
@@ -86,8 +185,6 @@ pub extern "C" fn sys_munmap(ptr: *mut u8, size: usize) -> i32 {
 	unsafe {
 		KERNEL_FREE_LIST.lock().deallocate(range).unwrap();
 	}
-
-	0
 }
 
 /// Configures the protections associated with a region of virtual memory
@@ -122,6 +219,41 @@ pub extern "C" fn sys_mprotect(ptr: *mut u8, size: usize, prot_flags: MemoryProt
 	}
 }
 
+/// Reports, which pages of the `size` bytes at `ptr` are resident.
+///
+/// Like on Linux, `vec` receives one byte per page, whose lowest bit is set,
+/// if the page is resident. Hermit does not swap, so every mapped page is
+/// resident. Fails with `ENOMEM`, if a page of the range is not mapped, which
+/// includes the pages reserved by [`sys_mmap`] with [`MemoryProtection::None`],
+/// or if the range exceeds the address space.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_mincore(ptr: *mut u8, size: usize, vec: *mut u8) -> i32 {
+	let virtual_address = VirtAddr::from_ptr(ptr);
+	if !virtual_address.is_aligned_to(BasePageSize::SIZE) {
+		return -i32::from(Errno::Inval);
+	}
+	if ptr.addr().checked_add(size).is_none() {
+		return -i32::from(Errno::Nomem);
+	}
+	if vec.is_null() {
+		return -i32::from(Errno::Fault);
+	}
+
+	let count = size.div_ceil(BasePageSize::SIZE as usize);
+	let mapped = (0..count).all(|i| {
+		let page = virtual_address + i as u64 * BasePageSize::SIZE;
+		arch::mm::paging::virtual_to_physical(page).is_some()
+	});
+	if !mapped {
+		return -i32::from(Errno::Nomem);
+	}
+
+	let vec = unsafe { core::slice::from_raw_parts_mut(vec, count) };
+	vec.fill(1);
+	0
+}
+
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub extern "C" fn sys_mlock(_addr: *const c_void, _size: usize) -> i32 {
@@ -149,3 +281,44 @@ pub extern "C" fn sys_munlockall(_flags: c_int) -> i32 {
 	// Hermit does not do any swapping yet.
 	0
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(target_os = "none")]
+	#[test_case]
+	fn test_mincore() {
+		let size = 2 * BasePageSize::SIZE as usize;
+		let mut ptr = core::ptr::null_mut();
+		assert_eq!(
+			sys_mmap(
+				size,
+				MemoryProtection::Read | MemoryProtection::Write,
+				&mut ptr
+			),
+			0
+		);
+		let mut vec = [0u8; 2];
+		assert_eq!(unsafe { sys_mincore(ptr, size - 1, vec.as_mut_ptr()) }, 0);
+		assert_eq!(vec, [1, 1]);
+		assert_eq!(
+			unsafe { sys_mincore(ptr.wrapping_add(1), size, vec.as_mut_ptr()) },
+			-i32::from(Errno::Inval)
+		);
+
+		let mut reserved = core::ptr::null_mut();
+		assert_eq!(sys_mmap(size, MemoryProtection::None, &mut reserved), 0);
+		assert_eq!(
+			unsafe { sys_mincore(reserved, size, vec.as_mut_ptr()) },
+			-i32::from(Errno::Nomem)
+		);
+
+		// The range wraps around the end of the address space.
+		assert_eq!(
+			unsafe { sys_mincore(ptr, usize::MAX, vec.as_mut_ptr()) },
+			-i32::from(Errno::Nomem)
+		);
+		assert_eq!(unsafe { sys_munmap(ptr, size) }, 0);
+	}
+}
@@ -1,16 +1,19 @@
 #![allow(clippy::result_unit_err)]
 
 use alloc::ffi::CString;
+use alloc::sync::Arc;
 #[cfg(all(target_os = "none", not(feature = "common-os")))]
 use core::alloc::{GlobalAlloc, Layout};
-use core::ffi::{CStr, c_char};
+use core::ffi::{CStr, c_char, c_ulong};
 use core::marker::PhantomData;
 use core::ptr::null;
+use core::slice;
 
 use dirent_display::Dirent64Display;
 use hermit_sync::Lazy;
 
 pub use self::condvar::*;
+pub use self::driver::*;
 pub use self::entropy::*;
 pub use self::futex::*;
 pub use self::processor::*;
@@ -24,16 +27,20 @@ pub use self::timer::*;
 use crate::env;
 use crate::errno::{Errno, ToErrno};
 use crate::executor::block_on;
+use crate::fd::eventqueue::{EventQueueEvent, EventQueueOp};
 use crate::fd::{
-	self, AccessOption, AccessPermission, EventFlags, FileDescriptor, OpenOption, PollFd,
-	dup_object, dup_object2, get_object, isatty, remove_object,
+	self, AccessOption, AccessPermission, EventFlags, FallocateMode, FileDescriptor, OpenOption,
+	PollFd, dup_object, dup_object2, get_object, isatty, remove_object,
 };
-use crate::fs::{self, FileAttr, SeekWhence};
+use crate::fs::watch::WatchMask;
+use crate::fs::{self, FileAttr, FileTimes, RenameFlags, SeekWhence, StatFs};
 #[cfg(all(target_os = "none", not(feature = "common-os")))]
 use crate::mm::ALLOCATOR;
 use crate::syscalls::interfaces::SyscallInterface;
+use crate::time::timespec;
 
 mod condvar;
+mod driver;
 mod entropy;
 mod futex;
 pub(crate) mod interfaces;
@@ -45,7 +52,7 @@ mod processor;
 #[cfg(feature = "newlib")]
 mod recmutex;
 mod semaphore;
-#[cfg(any(feature = "net", feature = "vsock"))]
+#[cfg(any(feature = "net", feature = "vsock", feature = "unix"))]
 pub mod socket;
 mod spinlock;
 mod system;
@@ -62,16 +69,30 @@ pub(crate) static SYS: Lazy<&'static dyn SyscallInterface> = Lazy::new(|| {
 	}
 });
 
+/// Offset and size of a file, which is 64 bits wide on all targets
+#[allow(non_camel_case_types)]
+pub type off_t = i64;
+
+/// Converts the offset or size `value` of a file to `usize`.
+///
+/// Fails with `EINVAL`, if it is negative, and with `EFBIG`, if it does not
+/// fit into the address space of a 32-bit target.
+fn file_offset(value: off_t) -> Result<usize, Errno> {
+	usize::try_from(value).map_err(|_| if value < 0 { Errno::Inval } else { Errno::Fbig })
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 /// Describes  a  region  of  memory, beginning at `iov_base` address and with the size of `iov_len` bytes.
-struct iovec {
+pub struct iovec {
 	/// Starting address
 	pub iov_base: *mut u8,
 	/// Size of the memory pointed to by iov_base.
 	pub iov_len: usize,
 }
 
+const _: () = assert!(size_of::<iovec>() == 2 * size_of::<usize>());
+
 const IOV_MAX: usize = 1024;
 
 pub(crate) fn init() {
@@ -248,9 +269,22 @@ pub(crate) fn shutdown(arg: i32) -> ! {
 	// print some performance statistics
 	crate::arch::kernel::print_statistics();
 
+	// The application cannot be restarted in place, because its static state
+	// is not reinitialized. Thus, the system is rebooted instead.
+	if arg != 0 && env::reboot_on_failure() {
+		SYS.reboot(arg)
+	}
+
 	SYS.shutdown(arg)
 }
 
+/// Resets the system.
+pub(crate) fn reboot(arg: i32) -> ! {
+	crate::arch::kernel::print_statistics();
+
+	SYS.reboot(arg)
+}
+
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_unlink(name: *const c_char) -> i32 {
@@ -278,6 +312,187 @@ pub unsafe extern "C" fn sys_rmdir(name: *const c_char) -> i32 {
 	crate::fs::remove_dir(name).map_or_else(|e| -i32::from(e), |()| 0)
 }
 
+/// Mounts the file system `fstype` on `source` (a block device such as
+/// `/dev/nvme0n1p1` or the tag of a virtio-fs device) at `target`.
+///
+/// No mount flags are supported, so `flags` has to be zero.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_mount(
+	source: *const c_char,
+	target: *const c_char,
+	fstype: *const c_char,
+	flags: c_ulong,
+) -> i32 {
+	if flags != 0 {
+		return -i32::from(Errno::Inval);
+	}
+
+	let source = unsafe { CStr::from_ptr(source) }.to_str().unwrap();
+	let target = unsafe { CStr::from_ptr(target) }.to_str().unwrap();
+	let fstype = unsafe { CStr::from_ptr(fstype) }.to_str().unwrap();
+
+	crate::fs::mount(source, target, fstype).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Unmounts the file system at `target`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_umount(target: *const c_char) -> i32 {
+	let target = unsafe { CStr::from_ptr(target) }.to_str().unwrap();
+
+	crate::fs::umount(target).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Creates a descriptor, from which the events of file system watches are read.
+///
+/// `flags` may contain `O_NONBLOCK`, so that reads fail with `EAGAIN`
+/// instead of waiting for events.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_fswatch_init(flags: i32) -> i32 {
+	let Some(flags) = OpenOption::from_bits(flags) else {
+		return -i32::from(Errno::Inval);
+	};
+	if !(OpenOption::O_NONBLOCK | OpenOption::O_CLOEXEC).contains(flags) {
+		return -i32::from(Errno::Inval);
+	}
+
+	fs::watch::init(flags.contains(OpenOption::O_NONBLOCK)).unwrap_or_else(|e| -i32::from(e))
+}
+
+/// Watches `path` for the events `mask` (`IN_CREATE`, `IN_MODIFY`,
+/// `IN_DELETE`, and `IN_DELETE_SELF` of `inotify`) and returns the watch
+/// descriptor, which is part of the events read from `fd`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_fswatch_add(
+	fd: FileDescriptor,
+	path: *const c_char,
+	mask: u32,
+) -> i32 {
+	let path = unsafe { CStr::from_ptr(path) }.to_str().unwrap();
+
+	fs::watch::add(fd, path, WatchMask::from_bits_truncate(mask)).unwrap_or_else(|e| -i32::from(e))
+}
+
+/// Removes the watch `wd` of the descriptor `fd`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_fswatch_rm(fd: FileDescriptor, wd: i32) -> i32 {
+	fs::watch::remove(fd, wd).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Creates the symbolic link `linkpath`, which points to `target`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_symlink(target: *const c_char, linkpath: *const c_char) -> i32 {
+	let target = unsafe { CStr::from_ptr(target) }.to_str().unwrap();
+	let linkpath = unsafe { CStr::from_ptr(linkpath) }.to_str().unwrap();
+
+	crate::fs::symlink(target, linkpath).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Places the target of the symbolic link `name` in `buf`, which has a size of
+/// `bufsiz` bytes. The target is truncated and not null-terminated.
+/// Returns the number of bytes placed in `buf`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_readlink(name: *const c_char, buf: *mut u8, bufsiz: usize) -> isize {
+	let name = unsafe { CStr::from_ptr(name) }.to_str().unwrap();
+
+	match crate::fs::readlink(name) {
+		Ok(target) => {
+			let len = target.len().min(bufsiz);
+			unsafe {
+				core::ptr::copy_nonoverlapping(target.as_ptr(), buf, len);
+			}
+			len.try_into().unwrap()
+		}
+		Err(e) => isize::try_from(-i32::from(e)).unwrap(),
+	}
+}
+
+/// Places the canonical absolute form of `path` in `buf`, which has a size of
+/// `size` bytes.
+///
+/// Repeated slashes, `.`, `..`, and symbolic links are resolved and the path
+/// has to exist. The result is null-terminated. Returns the length of the
+/// path without the terminating null byte.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_realpath(path: *const c_char, buf: *mut u8, size: usize) -> isize {
+	let Ok(path) = unsafe { CStr::from_ptr(path) }.to_str() else {
+		return -isize::try_from(i32::from(Errno::Inval)).unwrap();
+	};
+
+	match fs::realpath(path) {
+		Ok(resolved) if resolved.len() < size => {
+			unsafe {
+				core::ptr::copy_nonoverlapping(resolved.as_ptr(), buf, resolved.len());
+				buf.add(resolved.len()).write(0);
+			}
+			resolved.len().try_into().unwrap()
+		}
+		Ok(_) => -isize::try_from(i32::from(Errno::Range)).unwrap(),
+		Err(e) => isize::try_from(-i32::from(e)).unwrap(),
+	}
+}
+
+/// Renames the file `oldpath` to `newpath` and replaces `newpath`, if it exists.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_rename(oldpath: *const c_char, newpath: *const c_char) -> i32 {
+	let oldpath = unsafe { CStr::from_ptr(oldpath) }.to_str().unwrap();
+	let newpath = unsafe { CStr::from_ptr(newpath) }.to_str().unwrap();
+
+	fs::rename(oldpath, newpath, RenameFlags::empty()).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Renames the file `oldpath` to `newpath` with `RENAME_NOREPLACE` or
+/// `RENAME_EXCHANGE`. Relative paths are only supported relative to the
+/// current working directory (`AT_FDCWD`).
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_renameat2(
+	olddirfd: FileDescriptor,
+	oldpath: *const c_char,
+	newdirfd: FileDescriptor,
+	newpath: *const c_char,
+	flags: u32,
+) -> i32 {
+	const AT_FDCWD: i32 = -100;
+
+	let Some(flags) = RenameFlags::from_bits(flags) else {
+		return -i32::from(Errno::Inval);
+	};
+	let (Ok(oldpath), Ok(newpath)) = (
+		unsafe { CStr::from_ptr(oldpath) }.to_str(),
+		unsafe { CStr::from_ptr(newpath) }.to_str(),
+	) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	if (!oldpath.starts_with('/') && olddirfd != AT_FDCWD)
+		|| (!newpath.starts_with('/') && newdirfd != AT_FDCWD)
+	{
+		warn!("renameat2 with directory relative to fd is not implemented!");
+		return -i32::from(Errno::Nosys);
+	}
+
+	fs::rename(oldpath, newpath, flags).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Creates the hard link `newpath` to the file `oldpath`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_link(oldpath: *const c_char, newpath: *const c_char) -> i32 {
+	let oldpath = unsafe { CStr::from_ptr(oldpath) }.to_str().unwrap();
+	let newpath = unsafe { CStr::from_ptr(newpath) }.to_str().unwrap();
+
+	crate::fs::link(oldpath, newpath).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_stat(name: *const c_char, stat: *mut FileAttr) -> i32 {
@@ -306,6 +521,26 @@ pub unsafe extern "C" fn sys_lstat(name: *const c_char, stat: *mut FileAttr) ->
 	}
 }
 
+/// Returns the capacity of the file system, which contains the file `name`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_statfs(name: *const c_char, buf: *mut StatFs) -> i32 {
+	if buf.is_null() {
+		return -i32::from(Errno::Inval);
+	}
+	let Ok(name) = unsafe { CStr::from_ptr(name) }.to_str() else {
+		return -i32::from(Errno::Inval);
+	};
+
+	match fs::statfs(name) {
+		Ok(statfs) => unsafe {
+			*buf = statfs;
+			0
+		},
+		Err(e) => -i32::from(e),
+	}
+}
+
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_fstat(fd: FileDescriptor, stat: *mut FileAttr) -> i32 {
@@ -485,11 +720,98 @@ pub unsafe extern "C" fn sys_fchmod(fd: FileDescriptor, mode: u32) -> i32 {
 		.unwrap_or_else(|e| -i32::from(e))
 }
 
+/// Converts the timestamps of `utimensat` into [`FileTimes`].
+///
+/// A null pointer sets both timestamps to the current time.
+unsafe fn file_times(times: *const timespec) -> Option<FileTimes> {
+	const UTIME_NOW: i32 = (1 << 30) - 1;
+	const UTIME_OMIT: i32 = (1 << 30) - 2;
+
+	let now = timespec::from_usec(crate::arch::kernel::systemtime::now_micros() as i64);
+	if times.is_null() {
+		return Some(FileTimes {
+			atime: Some(now),
+			mtime: Some(now),
+		});
+	}
+
+	let convert = |time: timespec| match time.tv_nsec {
+		UTIME_NOW => Some(Some(now)),
+		UTIME_OMIT => Some(None),
+		0..=999_999_999 => Some(Some(time)),
+		_ => None,
+	};
+	let times = unsafe { slice::from_raw_parts(times, 2) };
+	Some(FileTimes {
+		atime: convert(times[0])?,
+		mtime: convert(times[1])?,
+	})
+}
+
+/// Sets the access and modification times of the file `name`.
+///
+/// `times` points to the access and the modification time. If `name` is a
+/// null pointer, the timestamps of `dirfd` are set. Relative paths are only
+/// supported relative to the current working directory (`AT_FDCWD`).
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_utimensat(
+	dirfd: FileDescriptor,
+	name: *const c_char,
+	times: *const timespec,
+	flags: i32,
+) -> i32 {
+	const AT_SYMLINK_NOFOLLOW: i32 = 0x100;
+	const AT_FDCWD: i32 = -100;
+
+	if flags & !AT_SYMLINK_NOFOLLOW != 0 {
+		return -i32::from(Errno::Inval);
+	}
+	let Some(times) = (unsafe { file_times(times) }) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	if name.is_null() {
+		return crate::fd::utimens(dirfd, times).map_or_else(|e| -i32::from(e), |()| 0);
+	}
+
+	let Ok(name) = unsafe { CStr::from_ptr(name) }.to_str() else {
+		return -i32::from(Errno::Inval);
+	};
+	if !name.starts_with('/') && dirfd != AT_FDCWD {
+		warn!("utimensat with directory relative to fd is not implemented!");
+		return -i32::from(Errno::Nosys);
+	}
+
+	let follow = flags & AT_SYMLINK_NOFOLLOW == 0;
+	fs::utimens(name, times, follow).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Sets the access and modification times of `fd`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_futimens(fd: FileDescriptor, times: *const timespec) -> i32 {
+	let Some(times) = (unsafe { file_times(times) }) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	crate::fd::utimens(fd, times).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub extern "C" fn sys_close(fd: FileDescriptor) -> i32 {
 	let obj = remove_object(fd);
-	obj.map_or_else(|e| -i32::from(e), |_| 0)
+	obj.map_or_else(
+		|e| -i32::from(e),
+		|v| {
+			// The locks are released with the last file descriptor of the open file description.
+			if Arc::strong_count(&v) == 1 {
+				fs::lock::release(&v);
+			}
+			0
+		},
+	)
 }
 
 #[hermit_macro::system(errno)]
@@ -565,20 +887,94 @@ pub unsafe extern "C" fn sys_write(fd: FileDescriptor, buf: *const u8, len: usiz
 	unsafe { write(fd, buf, len) }
 }
 
+/// Writes up to `count` bytes of the file `fd` to `socket` without copying
+/// them through the user space.
+///
+/// If `offset` is not null, the file is read at `*offset`, which is advanced
+/// by the number of written bytes. Otherwise, the file is read at its
+/// position. Returns the number of written bytes.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sendfile(
+	fd: FileDescriptor,
+	socket: FileDescriptor,
+	offset: *mut off_t,
+	count: usize,
+) -> isize {
+	let offset = unsafe { offset.as_mut() };
+	let start = match offset.as_deref() {
+		Some(&offset) => match file_offset(offset) {
+			Ok(offset) => Some(offset),
+			Err(e) => return (-i32::from(e)).try_into().unwrap(),
+		},
+		None => None,
+	};
+
+	match fd::sendfile(fd, socket, start, count) {
+		Ok(len) => {
+			if let Some(offset) = offset {
+				*offset += off_t::try_from(len).unwrap();
+			}
+			len.try_into().unwrap()
+		}
+		Err(e) => (-i32::from(e)).try_into().unwrap(),
+	}
+}
+
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_ftruncate(fd: FileDescriptor, size: off_t) -> i32 {
+	file_offset(size)
+		.and_then(|size| fd::truncate(fd, size))
+		.map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Allocates or deallocates `len` bytes at `offset` of the file `fd`.
+///
+/// By default, the range is allocated and the file is extended, if the range
+/// reaches beyond its end. With `FALLOC_FL_KEEP_SIZE`, the size of the file is
+/// not changed and `FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE` deallocates the range.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_fallocate(fd: FileDescriptor, mode: i32, offset: off_t, len: off_t) -> i32 {
+	let Some(mode) = FallocateMode::from_bits(mode) else {
+		return -i32::from(Errno::Opnotsupp);
+	};
+	let (offset, len) = match (file_offset(offset), file_offset(len)) {
+		(Ok(offset), Ok(len)) => (offset, len),
+		(Err(e), _) | (_, Err(e)) => return -i32::from(e),
+	};
+
+	fd::allocate(fd, mode, offset, len).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Ensures that all data written to `fd` reached stable storage.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_fsync(fd: FileDescriptor) -> i32 {
+	fd::fsync(fd).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Ensures that all data written to `fd` reached stable storage.
+///
+/// Hermit does not distinguish between data and metadata, which is only
+/// needed to retrieve the data. Therefore, this is equivalent to [`sys_fsync`].
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn sys_ftruncate(fd: FileDescriptor, size: usize) -> i32 {
-	fd::truncate(fd, size).map_or_else(|e| -i32::from(e), |()| 0)
+pub extern "C" fn sys_fdatasync(fd: FileDescriptor) -> i32 {
+	fd::fsync(fd).map_or_else(|e| -i32::from(e), |()| 0)
 }
 
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn sys_truncate(path: *const c_char, size: usize) -> i32 {
+pub unsafe extern "C" fn sys_truncate(path: *const c_char, size: off_t) -> i32 {
 	let Ok(path) = unsafe { CStr::from_ptr(path) }.to_str() else {
 		return -i32::from(Errno::Inval);
 	};
 
-	fs::truncate(path, size).map_or_else(|e| -i32::from(e), |()| 0)
+	file_offset(size)
+		.and_then(|size| fs::truncate(path, size))
+		.map_or_else(|e| -i32::from(e), |()| 0)
 }
 
 /// `write()` attempts to write `nbyte` of data to the object referenced by the
@@ -703,12 +1099,165 @@ pub extern "C" fn sys_fcntl(fd: i32, cmd: i32, arg: i32) -> i32 {
 	}
 }
 
+/// Description of an advisory record lock
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct Flock {
+	/// Type of the lock (`F_RDLCK`, `F_WRLCK`, or `F_UNLCK`)
+	pub l_type: i16,
+	/// Interpretation of `l_start` (`SEEK_SET`, `SEEK_CUR`, or `SEEK_END`)
+	pub l_whence: i16,
+	/// Start of the locked range
+	pub l_start: i64,
+	/// Number of locked bytes, zero locks up to the end of the file
+	pub l_len: i64,
+	/// Owner of a conflicting lock, always -1.
+	pub l_pid: i32,
+}
+
+const F_RDLCK: i16 = 0;
+const F_WRLCK: i16 = 1;
+const F_UNLCK: i16 = 2;
+
+/// Returns the range from start to end, which is described by `flock`.
+fn flock_range(
+	obj: &Arc<async_lock::RwLock<dyn fd::ObjectInterface>>,
+	flock: &Flock,
+) -> crate::io::Result<(u64, u64)> {
+	let whence = u8::try_from(flock.l_whence).map_err(|_| Errno::Inval)?;
+	let base = match SeekWhence::try_from(whence).map_err(|_| Errno::Inval)? {
+		SeekWhence::Set => 0,
+		SeekWhence::Cur => block_on(
+			async { obj.read().await.lseek(0, SeekWhence::Cur).await },
+			None,
+		)?
+		.try_into()
+		.unwrap(),
+		SeekWhence::End => block_on(async { obj.read().await.fstat().await }, None)?.st_size,
+		_ => return Err(Errno::Inval),
+	};
+
+	let start = base.checked_add(flock.l_start).ok_or(Errno::Overflow)?;
+	let (start, end) = match flock.l_len {
+		0 => (start, None),
+		len if len > 0 => (start, Some(start.checked_add(len).ok_or(Errno::Overflow)?)),
+		len => (start.checked_add(len).ok_or(Errno::Overflow)?, Some(start)),
+	};
+	let start = u64::try_from(start).map_err(|_| Errno::Inval)?;
+	let end = end
+		.map_or(Ok(u64::MAX), u64::try_from)
+		.map_err(|_| Errno::Inval)?;
+
+	Ok((start, end))
+}
+
+/// Acquires, releases, or tests an advisory record lock on the file `fd`.
+///
+/// `cmd` is either `F_GETLK`, `F_SETLK`, or `F_SETLKW`. In contrast to POSIX,
+/// the locks are owned by the open file description (see [`fs::lock`]).
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_fcntl_lock(fd: FileDescriptor, cmd: i32, flock: *mut Flock) -> i32 {
+	const F_GETLK: i32 = 5;
+	const F_SETLK: i32 = 6;
+	const F_SETLKW: i32 = 7;
+
+	if flock.is_null() {
+		return -i32::from(Errno::Inval);
+	}
+	let flock = unsafe { &mut *flock };
+	let ty = match flock.l_type {
+		F_RDLCK => Some(fs::lock::LockType::Read),
+		F_WRLCK => Some(fs::lock::LockType::Write),
+		F_UNLCK => None,
+		_ => return -i32::from(Errno::Inval),
+	};
+
+	let result = get_object(fd).and_then(|obj| {
+		let (start, end) = flock_range(&obj, flock)?;
+		match cmd {
+			F_GETLK => {
+				let ty = ty.ok_or(Errno::Inval)?;
+				match fs::lock::test(&obj, fs::lock::LockKind::Record, ty, start, end)? {
+					Some(lock) => {
+						flock.l_type = match lock.ty {
+							fs::lock::LockType::Read => F_RDLCK,
+							fs::lock::LockType::Write => F_WRLCK,
+						};
+						flock.l_whence = i16::from(u8::from(SeekWhence::Set));
+						flock.l_start = lock.start.try_into().unwrap();
+						flock.l_len = if lock.end == u64::MAX {
+							0
+						} else {
+							(lock.end - lock.start).try_into().unwrap()
+						};
+						flock.l_pid = -1;
+					}
+					None => flock.l_type = F_UNLCK,
+				}
+				Ok(())
+			}
+			F_SETLK | F_SETLKW => fs::lock::lock(
+				&obj,
+				fs::lock::LockKind::Record,
+				ty,
+				start,
+				end,
+				cmd == F_SETLKW,
+			),
+			_ => Err(Errno::Inval),
+		}
+	});
+
+	result.map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Acquires or releases an advisory lock of the whole file `fd`.
+///
+/// `operation` is either `LOCK_SH`, `LOCK_EX`, or `LOCK_UN`, optionally
+/// combined with `LOCK_NB` to fail with `EWOULDBLOCK` instead of waiting.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_flock(fd: FileDescriptor, operation: i32) -> i32 {
+	const LOCK_SH: i32 = 1;
+	const LOCK_EX: i32 = 2;
+	const LOCK_NB: i32 = 4;
+	const LOCK_UN: i32 = 8;
+
+	let ty = match operation & !LOCK_NB {
+		LOCK_SH => Some(fs::lock::LockType::Read),
+		LOCK_EX => Some(fs::lock::LockType::Write),
+		LOCK_UN => None,
+		_ => return -i32::from(Errno::Inval),
+	};
+
+	get_object(fd)
+		.and_then(|obj| {
+			fs::lock::lock(
+				&obj,
+				fs::lock::LockKind::File,
+				ty,
+				0,
+				u64::MAX,
+				operation & LOCK_NB == 0,
+			)
+		})
+		.map_or_else(|e| -i32::from(e), |()| 0)
+}
+
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
-pub extern "C" fn sys_lseek(fd: FileDescriptor, offset: isize, whence: i32) -> isize {
+pub extern "C" fn sys_lseek(fd: FileDescriptor, offset: off_t, whence: i32) -> off_t {
 	let whence = u8::try_from(whence).unwrap();
 	let whence = SeekWhence::try_from(whence).unwrap();
-	crate::fd::lseek(fd, offset, whence).unwrap_or_else(|e| isize::try_from(-i32::from(e)).unwrap())
+	// The offset is relative, so that it may be negative.
+	let Ok(offset) = isize::try_from(offset) else {
+		return (-i32::from(Errno::Overflow)).into();
+	};
+	crate::fd::lseek(fd, offset, whence).map_or_else(
+		|e| (-i32::from(e)).into(),
+		|pos| off_t::try_from(pos).unwrap(),
+	)
 }
 
 #[repr(C)]
@@ -866,6 +1415,90 @@ pub extern "C" fn sys_eventfd(initval: u64, flags: i16) -> i32 {
 	}
 }
 
+/// Creates a descriptor, which becomes readable, when the memory pressure
+/// rises to or above `level`.
+///
+/// `level` is `1` (low, less than 20 % of the memory are available), `2`
+/// (medium, less than 10 %) or `3` (critical, less than 5 %). The descriptor
+/// is notified immediately, if the level has already been reached. A read
+/// returns the number of notifications since the last read as `u64`.
+/// `flags` may contain `O_NONBLOCK` and `O_CLOEXEC`, which has no effect.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_mempressure(level: u32, flags: i32) -> i32 {
+	let Ok(level) = crate::mm::pressure::Level::try_from(level) else {
+		return -i32::from(Errno::Inval);
+	};
+	if level == crate::mm::pressure::Level::None
+		|| flags & !(OpenOption::O_NONBLOCK | OpenOption::O_CLOEXEC).bits() != 0
+	{
+		return -i32::from(Errno::Inval);
+	}
+
+	let nonblocking = flags & OpenOption::O_NONBLOCK.bits() != 0;
+	crate::fd::mempressure(level, nonblocking).unwrap_or_else(|e| -i32::from(e))
+}
+
+/// Creates an event queue, which reports the readiness of the descriptors
+/// registered with it, like `epoll_create1`.
+///
+/// `flags` may only contain `O_CLOEXEC`, which has no effect.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_eventqueue_create(flags: i32) -> i32 {
+	if flags & !OpenOption::O_CLOEXEC.bits() != 0 {
+		return -i32::from(Errno::Inval);
+	}
+
+	crate::fd::eventqueue::create().unwrap_or_else(|e| -i32::from(e))
+}
+
+/// Adds (`1`), removes (`2`), or modifies (`3`) the registration of `fd` in
+/// the event queue `epfd` like `epoll_ctl`. `event` may be null for removals.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_eventqueue_ctl(
+	epfd: i32,
+	op: i32,
+	fd: i32,
+	event: *const EventQueueEvent,
+) -> i32 {
+	let Ok(op) = EventQueueOp::try_from(op) else {
+		return -i32::from(Errno::Inval);
+	};
+	let event = unsafe { event.as_ref() }.copied();
+
+	crate::fd::eventqueue::ctl(epfd, op, fd, event).map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Waits for events of the event queue `epfd` like `epoll_wait` and returns
+/// the number of events, which have been written to `events`.
+///
+/// A negative `timeout` waits forever, otherwise zero is returned after
+/// `timeout` milliseconds without events.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_eventqueue_wait(
+	epfd: i32,
+	events: *mut EventQueueEvent,
+	maxevents: i32,
+	timeout: i32,
+) -> i32 {
+	let Ok(len) = usize::try_from(maxevents) else {
+		return -i32::from(Errno::Inval);
+	};
+	if events.is_null() || len == 0 {
+		return -i32::from(Errno::Inval);
+	}
+	let events = unsafe { core::slice::from_raw_parts_mut(events, len) };
+	let timeout = u64::try_from(timeout)
+		.ok()
+		.map(core::time::Duration::from_millis);
+
+	crate::fd::eventqueue::wait(epfd, events, timeout)
+		.map_or_else(|e| -i32::from(e), |count| count.try_into().unwrap())
+}
+
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub extern "C" fn sys_image_start_addr() -> usize {
@@ -1,6 +1,10 @@
+use hermit_sync::InterruptTicketMutex;
 use vroom::{Dma, IoQueuePairId, Namespace, NamespaceId};
 
+use crate::core_scheduler;
+use crate::drivers::nvme::{IoQueuePairOwner, NvmeDriver, PollingMode};
 use crate::drivers::pci::get_nvme_driver;
+use crate::syscalls::tasks::usleep;
 
 // TODO: error messages
 #[derive(Debug)]
@@ -18,6 +22,31 @@ pub(crate) enum SysNvmeError {
 	CouldNotReadFromIoQueuePair = 11,
 	CouldNotWriteToIoQueuePair = 12,
 	CouldNotClearNamespace = 13,
+	CouldNotFlushIoQueuePair = 14,
+	IoQueuePairNotOwned = 15,
+	InvalidPollingMode = 16,
+}
+
+/// Checks that the current task may use the IO queue pair with ID `io_queue_pair_id`.
+fn check_access(driver: &NvmeDriver, io_queue_pair_id: &IoQueuePairId) -> Result<(), SysNvmeError> {
+	driver.check_access(io_queue_pair_id, core_scheduler().get_current_task_id())
+}
+
+/// Completes the submitted commands of the IO queue pair with ID
+/// `io_queue_pair_id`.
+///
+/// With hybrid polling, the current task sleeps first without holding the
+/// driver, so that other tasks can run on this core.
+fn complete_io(
+	driver: &InterruptTicketMutex<NvmeDriver>,
+	io_queue_pair_id: &IoQueuePairId,
+) -> Result<(), SysNvmeError> {
+	let sleep_time = driver.lock().hybrid_sleep_time(io_queue_pair_id);
+	usleep(sleep_time);
+
+	let mut driver = driver.lock();
+	check_access(&driver, io_queue_pair_id)?;
+	driver.complete_io_with_io_queue_pair(io_queue_pair_id)
 }
 
 #[hermit_macro::system]
@@ -177,9 +206,14 @@ pub unsafe extern "C" fn sys_nvme_create_io_queue_pair(
 		}
 		let resulting_io_queue_pair_id = unsafe { &mut *resulting_io_queue_pair_id };
 		let driver = get_nvme_driver().ok_or(SysNvmeError::DeviceDoesNotExist)?;
-		let io_queue_pair_id = driver
-			.lock()
-			.create_io_queue_pair(namespace_id, number_of_entries)?;
+		let owner = IoQueuePairOwner::Task {
+			id: core_scheduler().get_current_task_id(),
+			shared: false,
+		};
+		let io_queue_pair_id =
+			driver
+				.lock()
+				.create_io_queue_pair(namespace_id, number_of_entries, owner)?;
 		*resulting_io_queue_pair_id = io_queue_pair_id;
 		Ok(())
 	}
@@ -194,7 +228,9 @@ pub unsafe extern "C" fn sys_nvme_create_io_queue_pair(
 pub unsafe extern "C" fn sys_nvme_delete_io_queue_pair(io_queue_pair_id: IoQueuePairId) -> usize {
 	fn inner(io_queue_pair_id: IoQueuePairId) -> Result<(), SysNvmeError> {
 		let driver = get_nvme_driver().ok_or(SysNvmeError::DeviceDoesNotExist)?;
-		driver.lock().delete_io_queue_pair(io_queue_pair_id)
+		let mut driver = driver.lock();
+		check_access(&driver, &io_queue_pair_id)?;
+		driver.delete_io_queue_pair(io_queue_pair_id)
 	}
 	match inner(io_queue_pair_id) {
 		Ok(()) => 0,
@@ -216,9 +252,9 @@ pub unsafe extern "C" fn sys_nvme_allocate_buffer(
 	) -> Result<(), SysNvmeError> {
 		let resulting_buffer_pointer = unsafe { &mut *resulting_buffer_pointer };
 		let driver = get_nvme_driver().ok_or(SysNvmeError::DeviceDoesNotExist)?;
-		let buffer = driver
-			.lock()
-			.allocate_buffer(io_queue_pair_id, number_of_elements)?;
+		let driver = driver.lock();
+		check_access(&driver, io_queue_pair_id)?;
+		let buffer = driver.allocate_buffer(io_queue_pair_id, number_of_elements)?;
 		*resulting_buffer_pointer = buffer;
 		Ok(())
 	}
@@ -238,7 +274,9 @@ pub unsafe extern "C" fn sys_nvme_deallocate_buffer(
 		let _ = buffer;
 		let buffer: Dma<u8> = unsafe { core::ptr::read(buffer) };
 		let driver = get_nvme_driver().ok_or(SysNvmeError::DeviceDoesNotExist)?;
-		driver.lock().deallocate_buffer(io_queue_pair_id, buffer)
+		let driver = driver.lock();
+		check_access(&driver, io_queue_pair_id)?;
+		driver.deallocate_buffer(io_queue_pair_id, buffer)
 	}
 	match inner(io_queue_pair_id, buffer) {
 		Ok(()) => 0,
@@ -260,9 +298,15 @@ pub unsafe extern "C" fn sys_nvme_read_from_io_queue_pair(
 	) -> Result<(), SysNvmeError> {
 		let buffer = unsafe { &mut *buffer };
 		let driver = get_nvme_driver().ok_or(SysNvmeError::DeviceDoesNotExist)?;
-		driver
-			.lock()
-			.read_from_io_queue_pair(io_queue_pair_id, buffer, logical_block_address)
+		let mut guard = driver.lock();
+		check_access(&guard, io_queue_pair_id)?;
+		if guard.polling_mode(io_queue_pair_id) == PollingMode::Classic {
+			return guard.read_from_io_queue_pair(io_queue_pair_id, buffer, logical_block_address);
+		}
+
+		guard.submit_read_to_io_queue_pair(io_queue_pair_id, buffer, logical_block_address)?;
+		drop(guard);
+		complete_io(driver, io_queue_pair_id)
 	}
 	match inner(io_queue_pair_id, buffer, logical_block_address) {
 		Ok(()) => 0,
@@ -284,9 +328,15 @@ pub unsafe extern "C" fn sys_nvme_write_to_io_queue_pair(
 	) -> Result<(), SysNvmeError> {
 		let buffer = unsafe { &*buffer };
 		let driver = get_nvme_driver().ok_or(SysNvmeError::DeviceDoesNotExist)?;
-		driver
-			.lock()
-			.write_to_io_queue_pair(io_queue_pair_id, buffer, logical_block_address)
+		let mut guard = driver.lock();
+		check_access(&guard, io_queue_pair_id)?;
+		if guard.polling_mode(io_queue_pair_id) == PollingMode::Classic {
+			return guard.write_to_io_queue_pair(io_queue_pair_id, buffer, logical_block_address);
+		}
+
+		guard.submit_write_to_io_queue_pair(io_queue_pair_id, buffer, logical_block_address)?;
+		drop(guard);
+		complete_io(driver, io_queue_pair_id)
 	}
 	match inner(io_queue_pair_id, buffer, logical_block_address) {
 		Ok(()) => 0,
@@ -308,9 +358,9 @@ pub unsafe extern "C" fn sys_nvme_submit_read_to_io_queue_pair(
 	) -> Result<(), SysNvmeError> {
 		let buffer = unsafe { &mut *buffer };
 		let driver = get_nvme_driver().ok_or(SysNvmeError::DeviceDoesNotExist)?;
-		driver
-			.lock()
-			.submit_read_to_io_queue_pair(io_queue_pair_id, buffer, logical_block_address)
+		let mut driver = driver.lock();
+		check_access(&driver, io_queue_pair_id)?;
+		driver.submit_read_to_io_queue_pair(io_queue_pair_id, buffer, logical_block_address)
 	}
 	match inner(io_queue_pair_id, buffer, logical_block_address) {
 		Ok(()) => 0,
@@ -332,9 +382,9 @@ pub unsafe extern "C" fn sys_nvme_submit_write_to_io_queue_pair(
 	) -> Result<(), SysNvmeError> {
 		let buffer = unsafe { &*buffer };
 		let driver = get_nvme_driver().ok_or(SysNvmeError::DeviceDoesNotExist)?;
-		driver
-			.lock()
-			.submit_write_to_io_queue_pair(io_queue_pair_id, buffer, logical_block_address)
+		let mut driver = driver.lock();
+		check_access(&driver, io_queue_pair_id)?;
+		driver.submit_write_to_io_queue_pair(io_queue_pair_id, buffer, logical_block_address)
 	}
 	match inner(io_queue_pair_id, buffer, logical_block_address) {
 		Ok(()) => 0,
@@ -349,12 +399,75 @@ pub unsafe extern "C" fn sys_nvme_complete_io_with_io_queue_pair(
 ) -> usize {
 	fn inner(io_queue_pair_id: &IoQueuePairId) -> Result<(), SysNvmeError> {
 		let driver = get_nvme_driver().ok_or(SysNvmeError::DeviceDoesNotExist)?;
-		driver
-			.lock()
-			.complete_io_with_io_queue_pair(io_queue_pair_id)
+		check_access(&driver.lock(), io_queue_pair_id)?;
+		complete_io(driver, io_queue_pair_id)
+	}
+	match inner(io_queue_pair_id) {
+		Ok(()) => 0,
+		Err(error) => error as usize,
+	}
+}
+
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_nvme_flush_io_queue_pair(io_queue_pair_id: &IoQueuePairId) -> usize {
+	fn inner(io_queue_pair_id: &IoQueuePairId) -> Result<(), SysNvmeError> {
+		let driver = get_nvme_driver().ok_or(SysNvmeError::DeviceDoesNotExist)?;
+		let mut driver = driver.lock();
+		check_access(&driver, io_queue_pair_id)?;
+		driver.flush_io_queue_pair(io_queue_pair_id)
 	}
 	match inner(io_queue_pair_id) {
 		Ok(()) => 0,
 		Err(error) => error as usize,
 	}
 }
+
+/// Allows other tasks than the creator to use and delete the IO queue pair, if `shared` is true.
+///
+/// By default, only the task, which has created an IO queue pair, may use it.
+/// Only the creator may change this.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_nvme_share_io_queue_pair(
+	io_queue_pair_id: &IoQueuePairId,
+	shared: bool,
+) -> usize {
+	fn inner(io_queue_pair_id: &IoQueuePairId, shared: bool) -> Result<(), SysNvmeError> {
+		let driver = get_nvme_driver().ok_or(SysNvmeError::DeviceDoesNotExist)?;
+		driver.lock().share_io_queue_pair(
+			io_queue_pair_id,
+			core_scheduler().get_current_task_id(),
+			shared,
+		)
+	}
+	match inner(io_queue_pair_id, shared) {
+		Ok(()) => 0,
+		Err(error) => error as usize,
+	}
+}
+
+/// Selects, how the completions of the IO queue pair are awaited.
+///
+/// With the mode 0, the completion queue is polled at once, which yields the
+/// lowest latency. With the mode 1 (hybrid polling), the task sleeps for half
+/// of the mean latency of the previous commands first, which leaves the core to
+/// other tasks, and polls afterwards.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_nvme_set_polling_mode(
+	io_queue_pair_id: &IoQueuePairId,
+	mode: u8,
+) -> usize {
+	fn inner(io_queue_pair_id: &IoQueuePairId, mode: u8) -> Result<(), SysNvmeError> {
+		let mode = PollingMode::try_from(mode).map_err(|_| SysNvmeError::InvalidPollingMode)?;
+		let driver = get_nvme_driver().ok_or(SysNvmeError::DeviceDoesNotExist)?;
+		let driver = driver.lock();
+		check_access(&driver, io_queue_pair_id)?;
+		driver.set_polling_mode(io_queue_pair_id, mode)
+	}
+	match inner(io_queue_pair_id, mode) {
+		Ok(()) => 0,
+		Err(error) => error as usize,
+	}
+}
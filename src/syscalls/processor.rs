@@ -1,4 +1,5 @@
 use crate::arch::get_processor_count;
+use crate::errno::Errno;
 
 /// Returns the number of processors currently online.
 #[hermit_macro::system]
@@ -19,3 +20,38 @@ pub extern "C" fn sys_available_parallelism() -> usize {
 pub extern "C" fn sys_get_processor_frequency() -> u16 {
 	crate::arch::processor::get_frequency()
 }
+
+/// CPU features, which are available to applications
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct CpuFeatures {
+	/// Version of this structure, currently 1
+	pub version: u32,
+	/// Width of the widest vector registers in bits, zero if there are none
+	pub vector_width: u32,
+	/// Architecture-specific feature bits (see `CpuFeatureFlags` of the architecture)
+	pub flags: u64,
+}
+
+/// Fills `features` with the CPU features, which are available to applications.
+///
+/// In contrast to CPUID or reading ID registers directly, this accounts for
+/// the features, which the kernel has enabled, and works on all architectures.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_cpu_features(features: *mut CpuFeatures) -> i32 {
+	if features.is_null() {
+		return -i32::from(Errno::Inval);
+	}
+
+	let cpu_features = CpuFeatures {
+		version: 1,
+		vector_width: crate::arch::processor::vector_width(),
+		flags: crate::arch::processor::cpu_features().bits(),
+	};
+	unsafe {
+		features.write(cpu_features);
+	}
+
+	0
+}
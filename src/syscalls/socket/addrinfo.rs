@@ -429,14 +429,12 @@ fn getaddrinfo_node(
 	};
 
 	if ai_flags.contains(Ai::ADDRCONFIG) {
-		if want_ipv4 {
-			// Currently, Hermit always has an IPv4 address
-			want_ipv4 = true;
-		}
-		if want_ipv6 {
-			// Currently, Hermit never has an IPv4 address
-			want_ipv6 = false;
-			error!("getaddrinfo(AI_ADDRCONFIG) was called wanting an IPv6 address");
+		// Without any configured address, both families are still returned,
+		// so that loopback addresses can be resolved.
+		let (has_ipv4, has_ipv6) = configured_families();
+		if has_ipv4 || has_ipv6 {
+			want_ipv4 &= has_ipv4;
+			want_ipv6 &= has_ipv6;
 		}
 	}
 
@@ -473,6 +471,20 @@ fn getaddrinfo_node(
 		return Err(Eai::Noname);
 	}
 
+	if nodename.eq_ignore_ascii_case("localhost") || nodename.eq_ignore_ascii_case("localhost.") {
+		let ip_addrs = [
+			IpAddr::V4(Ipv4Addr::LOCALHOST),
+			IpAddr::V6(Ipv6Addr::LOCALHOST),
+		]
+		.into_iter()
+		.filter(|addr| addr.is_ipv4() && want_ipv4 || addr.is_ipv6() && want_ipv6)
+		.collect::<Vec<_>>();
+		if ip_addrs.is_empty() {
+			return Err(Eai::Noname);
+		}
+		return Ok(ip_addrs);
+	}
+
 	cfg_if::cfg_if! {
 		if #[cfg(feature = "dns")] {
 			resolve(nodename, ai_flags, ai_family, want_ipv4, want_ipv6)
@@ -483,6 +495,37 @@ fn getaddrinfo_node(
 	}
 }
 
+/// Returns, whether the network interface has an IPv4 and an IPv6 address
+/// other than the unspecified, loopback, and link-local addresses.
+fn configured_families() -> (bool, bool) {
+	cfg_if::cfg_if! {
+		if #[cfg(feature = "net")] {
+			use smoltcp::wire::IpAddress;
+
+			use crate::executor::network::NIC;
+
+			let mut guard = NIC.lock();
+			let Ok(nic) = guard.as_nic_mut() else {
+				return (false, false);
+			};
+			let mut families = (false, false);
+			for cidr in nic.ip_addrs() {
+				match cidr.address() {
+					IpAddress::Ipv4(addr) => {
+						families.0 |= !addr.is_unspecified() && !addr.is_loopback();
+					}
+					IpAddress::Ipv6(addr) => {
+						families.1 |= !addr.is_loopback() && !addr.is_unicast_link_local();
+					}
+				}
+			}
+			families
+		} else {
+			(false, false)
+		}
+	}
+}
+
 #[cfg(feature = "dns")]
 fn resolve(
 	nodename: &str,
@@ -493,33 +536,22 @@ fn resolve(
 ) -> Result<Vec<IpAddr>, Eai> {
 	use smoltcp::wire::DnsQueryType;
 
-	use crate::errno::ToErrno;
-	use crate::executor::block_on;
-	use crate::executor::network::{self, NIC, get_query_result};
-
-	macro_rules! try_io {
-		($expr:expr $(,)?) => {
-			match $expr {
-				Ok(val) => val,
-				Err(err) => {
-					i32::from(err).set_errno();
-					return Err(Eai::System);
-				}
-			}
-		};
-	}
-
-	let query = |name: &str, query: DnsQueryType| {
-		let mut guard = NIC.lock();
-		let nic = guard.as_nic_mut().unwrap();
-		let query = nic.start_query(name, query).unwrap();
-		nic.poll_common(network::now());
-		query
+	use super::resolver;
+	use crate::errno::{Errno, ToErrno};
+
+	let query = |name: &str, query_type: DnsQueryType| match resolver::lookup(name, query_type) {
+		Ok(addrs) => Ok(addrs),
+		// The name may still have addresses of the other type.
+		Err(Errno::Noent) => Ok(Vec::new()),
+		Err(Errno::Time) => Err(Eai::Again),
+		Err(err) => {
+			i32::from(err).set_errno();
+			Err(Eai::System)
+		}
 	};
 
-	let ipv6_query = want_ipv6.then(|| query(nodename, DnsQueryType::Aaaa));
-	let ipv6_results = ipv6_query.map(|query| block_on(get_query_result(query), None));
-	let ipv6_results = try_io!(ipv6_results.transpose()).unwrap_or_default();
+	let ipv6_results = want_ipv6.then(|| query(nodename, DnsQueryType::Aaaa));
+	let ipv6_results = ipv6_results.transpose()?.unwrap_or_default();
 	let mut ipv6_results = ipv6_results
 		.into_iter()
 		.map(IpAddr::from)
@@ -529,9 +561,8 @@ fn resolve(
 		&& ai_family == Af::Inet6
 		&& (ipv6_results.is_empty() || ai_flags.contains(Ai::ALL));
 
-	let ipv4_query = (want_ipv4 || ipv6_mapped).then(|| query(nodename, DnsQueryType::A));
-	let ipv4_results = ipv4_query.map(|query| block_on(get_query_result(query), None));
-	let ipv4_results = try_io!(ipv4_results.transpose()).unwrap_or_default();
+	let ipv4_results = (want_ipv4 || ipv6_mapped).then(|| query(nodename, DnsQueryType::A));
+	let ipv4_results = ipv4_results.transpose()?.unwrap_or_default();
 	let mut ipv4_results = ipv4_results
 		.into_iter()
 		.map(IpAddr::from)
@@ -548,6 +579,9 @@ fn resolve(
 	}
 
 	ipv4_results.append(&mut ipv6_results);
+	if ipv4_results.is_empty() {
+		return Err(Eai::Noname);
+	}
 	Ok(ipv4_results)
 }
 
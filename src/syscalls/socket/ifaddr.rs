@@ -0,0 +1,180 @@
+//! System calls, which enumerate the network interfaces and their addresses
+//! and configure their MTU.
+
+use smoltcp::wire::{HardwareAddress, IpCidr};
+
+use super::Af;
+use crate::errno::Errno;
+use crate::executor::network::NIC;
+use crate::executor::route;
+
+/// The interface is up.
+pub const IFF_UP: u32 = 0x1;
+/// The interface supports broadcasts.
+pub const IFF_BROADCAST: u32 = 0x2;
+/// The interface is the loopback interface.
+pub const IFF_LOOPBACK: u32 = 0x8;
+/// The interface is driven by the network stack, i.e., it is not detached.
+pub const IFF_RUNNING: u32 = 0x40;
+/// The interface supports multicast.
+pub const IFF_MULTICAST: u32 = 0x1000;
+
+/// Maximum number of addresses of an interface, which are returned
+pub const IFADDR_MAX_ADDRS: usize = 8;
+
+/// Address of an interface
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ifaddr_addr {
+	/// `AF_INET` or `AF_INET6`
+	pub family: u8,
+	/// Length of the prefix of the subnet in bits
+	pub prefix_len: u8,
+	/// IPv4 address in the first 4 bytes or IPv6 address, in network byte order
+	pub addr: [u8; 16],
+}
+
+/// Network interface and its addresses
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ifaddr {
+	/// Name of the interface, terminated by a null byte
+	pub name: [u8; 16],
+	/// Combination of `IFF_UP`, `IFF_BROADCAST`, `IFF_LOOPBACK`,
+	/// `IFF_RUNNING`, and `IFF_MULTICAST`
+	pub flags: u32,
+	/// MAC address, which is zero for the loopback interface
+	pub mac: [u8; 6],
+	/// Number of valid entries of `addrs`
+	pub naddrs: u32,
+	pub addrs: [ifaddr_addr; IFADDR_MAX_ADDRS],
+}
+
+fn to_ifaddr_addr(cidr: &IpCidr) -> ifaddr_addr {
+	let mut addr = ifaddr_addr {
+		prefix_len: cidr.prefix_len(),
+		..Default::default()
+	};
+	match cidr {
+		IpCidr::Ipv4(cidr) => {
+			addr.family = Af::Inet.into();
+			addr.addr[..4].copy_from_slice(&cidr.address().octets());
+		}
+		IpCidr::Ipv6(cidr) => {
+			addr.family = Af::Inet6.into();
+			addr.addr.copy_from_slice(&cidr.address().octets());
+		}
+	}
+	addr
+}
+
+/// Copies the interface `index` and its addresses into `ifa`.
+///
+/// The interfaces are numbered like by [`sys_netstat`](super::sys_netstat),
+/// so that they can be enumerated until `-ENODEV` is returned. Unconfigured
+/// IPv4 addresses are omitted. Addresses beyond `IFADDR_MAX_ADDRS` are not
+/// returned.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_getifaddr(index: u32, ifa: *mut ifaddr) -> i32 {
+	if ifa.is_null() {
+		return -i32::from(Errno::Fault);
+	}
+
+	let mut guard = NIC.lock();
+	let Ok(nic) = guard.as_nic_mut() else {
+		return -i32::from(Errno::Netdown);
+	};
+	let index = usize::try_from(index).unwrap();
+	let Some(iface) = nic.interface(index) else {
+		return -i32::from(Errno::Nodev);
+	};
+
+	let name = if index < nic.interface_count() {
+		route::interface_name(index)
+	} else {
+		"lo".into()
+	};
+	let mut entry = ifaddr {
+		flags: IFF_UP,
+		..Default::default()
+	};
+	let len = name.len().min(entry.name.len() - 1);
+	entry.name[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+	if name == "lo" {
+		entry.flags |= IFF_LOOPBACK | IFF_RUNNING;
+	} else {
+		entry.flags |= IFF_BROADCAST;
+		if cfg!(feature = "udp") {
+			entry.flags |= IFF_MULTICAST;
+		}
+		if index != 0 || !nic.is_detached() {
+			entry.flags |= IFF_RUNNING;
+		}
+		if let HardwareAddress::Ethernet(mac) = iface.hardware_addr() {
+			entry.mac = mac.0;
+		}
+	}
+
+	let addrs = iface
+		.ip_addrs()
+		.iter()
+		.filter(|cidr| !matches!(cidr, IpCidr::Ipv4(cidr) if cidr.address().is_unspecified()))
+		.take(IFADDR_MAX_ADDRS);
+	for (dest, cidr) in entry.addrs.iter_mut().zip(addrs) {
+		*dest = to_ifaddr_addr(cidr);
+		entry.naddrs += 1;
+	}
+	drop(guard);
+
+	unsafe {
+		ifa.write(entry);
+	}
+	0
+}
+
+/// Returns the MTU of the IP layer of the interface `index`.
+///
+/// It is lower than the configured MTU, while a router reports a lower MTU
+/// of a path, see [`sys_setmtu`].
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_getmtu(index: u32) -> i32 {
+	let mut guard = NIC.lock();
+	let Ok(nic) = guard.as_nic_mut() else {
+		return -i32::from(Errno::Netdown);
+	};
+	let index = usize::try_from(index).unwrap();
+	if index >= nic.interface_count() {
+		return -i32::from(Errno::Nodev);
+	}
+
+	nic.mtu(index).map_or(-i32::from(Errno::Nodev), i32::from)
+}
+
+/// Sets the MTU of the IP layer of the interface `index` to `mtu` bytes.
+///
+/// The MTU may be raised up to the MTU of the network card, which is given
+/// at boot by `net.mtu=<bytes>`, e.g., `net.mtu=9000` for jumbo frames.
+/// If `mtu` is zero, the MTU of the network card is restored. The interface
+/// resolves the addresses of its neighbors again.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_setmtu(index: u32, mtu: u32) -> i32 {
+	let mtu = match mtu {
+		0 => None,
+		mtu => match u16::try_from(mtu) {
+			Ok(mtu) => Some(mtu),
+			Err(_) => return -i32::from(Errno::Inval),
+		},
+	};
+
+	let mut guard = NIC.lock();
+	let Ok(nic) = guard.as_nic_mut() else {
+		return -i32::from(Errno::Netdown);
+	};
+	let index = usize::try_from(index).unwrap();
+	nic.set_mtu(index, mtu)
+		.map_or_else(|e| -i32::from(e), |()| 0)
+}
@@ -0,0 +1,281 @@
+//! System calls, which send and receive the data of several buffers with
+//! ancillary data, like `sendmsg` and `recvmsg` of Linux.
+//!
+//! `recvmsg` reports the time of reception of UDP datagrams (`SO_TIMESTAMP`)
+//! and their destination address (`IP_PKTINFO`, `IPV6_RECVPKTINFO`). The
+//! time is taken, when the socket hands the datagram out, since smoltcp does
+//! not record the arrival of packets. Ancillary data of `sendmsg` is ignored.
+
+use alloc::borrow::Cow;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::mem::size_of;
+use core::slice;
+
+#[cfg(feature = "udp")]
+use smoltcp::wire::IpAddress;
+
+#[cfg(feature = "udp")]
+use super::{IP_PKTINFO, IPV6_PKTINFO, Ipproto, in_addr, in6_addr};
+use super::{
+	MSG_CTRUNC, MSG_TRUNC, SCM_TIMESTAMP, SOL_SOCKET, endpoint_from_sockaddr, socklen_t,
+	write_endpoint,
+};
+use crate::errno::Errno;
+use crate::fd::{self, SocketOption, get_object};
+use crate::syscalls::{IOV_MAX, iovec};
+
+/// Message of `sendmsg` and `recvmsg`
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct msghdr {
+	/// Address of the peer or null
+	pub msg_name: *mut c_void,
+	pub msg_namelen: socklen_t,
+	/// Buffers of the data
+	pub msg_iov: *mut iovec,
+	pub msg_iovlen: usize,
+	/// Buffer of the ancillary data
+	pub msg_control: *mut c_void,
+	pub msg_controllen: usize,
+	/// Flags of the received message, e.g., `MSG_TRUNC`
+	pub msg_flags: i32,
+}
+
+const _: () = assert!(size_of::<msghdr>() == 7 * size_of::<usize>());
+
+/// Header of a control message, which is followed by its data
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct cmsghdr {
+	/// Length of the header and the data
+	pub cmsg_len: usize,
+	pub cmsg_level: i32,
+	pub cmsg_type: i32,
+}
+
+const _: () = assert!(size_of::<cmsghdr>() == size_of::<usize>() + 8);
+
+/// Destination of an IPv4 datagram (`IP_PKTINFO`)
+#[cfg(feature = "udp")]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct in_pktinfo {
+	/// Index of the interface, 0 is `eth0`
+	pub ipi_ifindex: i32,
+	/// Local address, which received the datagram
+	pub ipi_spec_dst: in_addr,
+	/// Destination address of the datagram
+	pub ipi_addr: in_addr,
+}
+
+/// Destination of an IPv6 datagram (`IPV6_PKTINFO`)
+#[cfg(feature = "udp")]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct in6_pktinfo {
+	pub ipi6_addr: in6_addr,
+	/// Index of the interface, 0 is `eth0`
+	pub ipi6_ifindex: u32,
+}
+
+/// Rounds `len` up to the alignment of control messages.
+const fn cmsg_align(len: usize) -> usize {
+	len.next_multiple_of(size_of::<usize>())
+}
+
+/// Writes control messages into the buffer of a [`msghdr`].
+struct ControlWriter {
+	buf: *mut u8,
+	capacity: usize,
+	len: usize,
+	/// A message has not fit into the buffer.
+	truncated: bool,
+}
+
+impl ControlWriter {
+	/// Appends the control message `value` of the level `level` and the type `type_`.
+	fn push<T>(&mut self, level: i32, type_: i32, value: T) {
+		let header_len = cmsg_align(size_of::<cmsghdr>());
+		let cmsg_len = header_len + size_of::<T>();
+		if self.buf.is_null() || self.capacity - self.len < cmsg_len {
+			self.truncated = true;
+			return;
+		}
+
+		let header = cmsghdr {
+			cmsg_len,
+			cmsg_level: level,
+			cmsg_type: type_,
+		};
+		unsafe {
+			let start = self.buf.add(self.len);
+			start.cast::<cmsghdr>().write_unaligned(header);
+			start.add(header_len).cast::<T>().write_unaligned(value);
+		}
+		self.len = (self.len + cmsg_align(cmsg_len)).min(self.capacity);
+	}
+}
+
+/// Returns the buffers of `msg`.
+unsafe fn iovecs<'a>(msg: &msghdr) -> Result<&'a [iovec], Errno> {
+	if msg.msg_iovlen > IOV_MAX {
+		return Err(Errno::Inval);
+	}
+	if msg.msg_iovlen == 0 {
+		return Ok(&[]);
+	}
+	if msg.msg_iov.is_null() {
+		return Err(Errno::Inval);
+	}
+	Ok(unsafe { slice::from_raw_parts(msg.msg_iov, msg.msg_iovlen) })
+}
+
+/// Sends the data of the buffers of `msg` as one message.
+///
+/// If `msg_name` is not null, the message is sent to this address like with
+/// `sendto`. Otherwise, the socket has to be connected. The ancillary data
+/// and `flags` are ignored.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sendmsg(fd: i32, msg: *const msghdr, _flags: i32) -> isize {
+	let Some(msg) = (unsafe { msg.as_ref() }) else {
+		return (-i32::from(Errno::Inval)).try_into().unwrap();
+	};
+	let iovecs = match unsafe { iovecs(msg) } {
+		Ok(iovecs) => iovecs,
+		Err(e) => return (-i32::from(e)).try_into().unwrap(),
+	};
+
+	let buffer = |iov: &iovec| unsafe { slice::from_raw_parts(iov.iov_base, iov.iov_len) };
+	// A datagram has to be sent at once, so the buffers are gathered.
+	let data = match iovecs {
+		[iov] => Cow::Borrowed(buffer(iov)),
+		_ => Cow::Owned(iovecs.iter().flat_map(buffer).copied().collect::<Vec<_>>()),
+	};
+
+	let result = if msg.msg_name.is_null() {
+		fd::write(fd, &data)
+	} else {
+		unsafe { endpoint_from_sockaddr(msg.msg_name.cast(), msg.msg_namelen) }.and_then(
+			|endpoint| {
+				let obj = get_object(fd)?;
+				let timeout = fd::socket_timeout(&obj, SocketOption::SendTimeout);
+				fd::block_on_io(
+					async { obj.read().await.sendto(&data, endpoint).await },
+					timeout,
+				)
+			},
+		)
+	};
+	result.map_or_else(
+		|e| isize::try_from(-i32::from(e)).unwrap(),
+		|v| v.try_into().unwrap(),
+	)
+}
+
+/// Receives a message and scatters its data into the buffers of `msg`.
+///
+/// The sender is written to `msg_name`, if it is not null, and `msg_flags`
+/// reports with `MSG_TRUNC` that a datagram has been truncated and with
+/// `MSG_CTRUNC` that the ancillary data has not fit into `msg_control`.
+/// `flags` have to be 0.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_recvmsg(fd: i32, msg: *mut msghdr, flags: i32) -> isize {
+	let Some(msg) = (unsafe { msg.as_mut() }) else {
+		return (-i32::from(Errno::Inval)).try_into().unwrap();
+	};
+	if flags != 0 {
+		return (-i32::from(Errno::Inval)).try_into().unwrap();
+	}
+	let iovecs = match unsafe { iovecs(msg) } {
+		Ok(iovecs) => iovecs,
+		Err(e) => return (-i32::from(e)).try_into().unwrap(),
+	};
+
+	let buffer = |iov: &iovec| unsafe { slice::from_raw_parts_mut(iov.iov_base, iov.iov_len) };
+	// A datagram has to be received at once, so it is scattered afterwards.
+	let mut gathered = Vec::new();
+	let data = match iovecs {
+		[iov] => buffer(iov),
+		_ => {
+			gathered = vec![0; iovecs.iter().map(|iov| iov.iov_len).sum()];
+			&mut gathered[..]
+		}
+	};
+
+	let result = get_object(fd).and_then(|obj| {
+		let timeout = fd::socket_timeout(&obj, SocketOption::RecvTimeout);
+		fd::block_on_io(async { obj.read().await.recvmsg(data).await }, timeout)
+	});
+	let message = match result {
+		Ok(message) => message,
+		Err(e) => return (-i32::from(e)).try_into().unwrap(),
+	};
+
+	if iovecs.len() > 1 {
+		let mut remainder = &gathered[..message.len];
+		for iov in iovecs {
+			let len = remainder.len().min(iov.iov_len);
+			let (head, tail) = remainder.split_at(len);
+			buffer(iov)[..len].copy_from_slice(head);
+			remainder = tail;
+		}
+	}
+
+	match message.endpoint {
+		Some(endpoint) if !msg.msg_name.is_null() => {
+			if let Err(e) =
+				unsafe { write_endpoint(endpoint, msg.msg_name.cast(), &mut msg.msg_namelen) }
+			{
+				return (-i32::from(e)).try_into().unwrap();
+			}
+		}
+		_ => msg.msg_namelen = 0,
+	}
+
+	let mut control = ControlWriter {
+		buf: msg.msg_control.cast(),
+		capacity: msg.msg_controllen,
+		len: 0,
+		truncated: false,
+	};
+	if let Some(timestamp) = message.timestamp {
+		control.push(SOL_SOCKET, SCM_TIMESTAMP, timestamp);
+	}
+	#[cfg(feature = "udp")]
+	if let Some((addr, interface)) = message.packet_info {
+		match addr {
+			IpAddress::Ipv4(addr) => control.push(
+				i32::from(u8::from(Ipproto::Ip)),
+				IP_PKTINFO,
+				in_pktinfo {
+					ipi_ifindex: interface.try_into().unwrap(),
+					ipi_spec_dst: addr.into(),
+					ipi_addr: addr.into(),
+				},
+			),
+			IpAddress::Ipv6(addr) => control.push(
+				i32::from(u8::from(Ipproto::Ipv6)),
+				IPV6_PKTINFO,
+				in6_pktinfo {
+					ipi6_addr: addr.into(),
+					ipi6_ifindex: interface.try_into().unwrap(),
+				},
+			),
+		}
+	}
+	msg.msg_controllen = control.len;
+
+	msg.msg_flags = 0;
+	if message.truncated {
+		msg.msg_flags |= MSG_TRUNC;
+	}
+	if control.truncated {
+		msg.msg_flags |= MSG_CTRUNC;
+	}
+
+	message.len.try_into().unwrap()
+}
@@ -2,33 +2,73 @@
 #![allow(nonstandard_style)]
 
 mod addrinfo;
+#[cfg(feature = "net")]
+mod ifaddr;
+mod message;
+#[cfg(feature = "net")]
+mod neighbor;
+#[cfg(feature = "net")]
+mod netstat;
+#[cfg(feature = "pcap")]
+mod pcap;
+#[cfg(feature = "dns")]
+mod resolver;
+#[cfg(feature = "net")]
+mod route;
 
 use alloc::boxed::Box;
+#[cfg(feature = "unix")]
+use alloc::string::String;
 use alloc::sync::Arc;
+#[cfg(feature = "unix")]
+use alloc::vec::Vec;
 use core::ffi::{c_char, c_void};
 use core::mem::{self, size_of};
 use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 #[allow(unused_imports)]
 use core::ops::DerefMut;
+use core::time::Duration;
 
-use cfg_if::cfg_if;
 use num_enum::{IntoPrimitive, TryFromPrimitive, TryFromPrimitiveError};
 #[cfg(feature = "net")]
 use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint};
+#[cfg(feature = "raw")]
+use smoltcp::wire::{IpProtocol, IpVersion};
 
+#[cfg(feature = "net")]
+pub use self::ifaddr::*;
+pub use self::message::*;
+#[cfg(feature = "net")]
+pub use self::neighbor::*;
+#[cfg(feature = "net")]
+pub use self::netstat::*;
+#[cfg(feature = "pcap")]
+pub use self::pcap::*;
+#[cfg(feature = "net")]
+pub use self::route::*;
 use crate::errno::Errno;
 #[cfg(feature = "net")]
 use crate::executor::network::{NIC, NetworkState};
+#[cfg(feature = "net")]
+use crate::executor::socket_buffer;
+#[cfg(feature = "udp")]
+use crate::fd::MulticastOption;
+#[cfg(feature = "raw")]
+use crate::fd::socket::raw;
 #[cfg(feature = "tcp")]
 use crate::fd::socket::tcp;
 #[cfg(feature = "udp")]
 use crate::fd::socket::udp;
+#[cfg(feature = "unix")]
+use crate::fd::socket::unix::{self, UnixAddress};
 #[cfg(feature = "vsock")]
 use crate::fd::socket::vsock::{self, VsockEndpoint, VsockListenEndpoint};
 use crate::fd::{
-	self, Endpoint, ListenEndpoint, ObjectInterface, SocketOption, get_object, insert_object,
+	self, Endpoint, ListenEndpoint, ObjectInterface, SocketOption, SocketOptionValue, get_object,
+	insert_object,
 };
 use crate::syscalls::block_on;
+use crate::time::timeval;
 
 #[derive(TryFromPrimitive, IntoPrimitive, PartialEq, Eq, Clone, Copy, Debug)]
 #[repr(u8)]
@@ -54,33 +94,47 @@ impl From<IpAddr> for Af {
 #[repr(u8)]
 pub enum Ipproto {
 	Ip = 0,
+	Icmp = 1,
 	Ipv6 = 41,
+	Icmpv6 = 58,
 	Tcp = 6,
 	Udp = 17,
 }
 
+pub const IPV6_UNICAST_HOPS: i32 = 16;
 pub const IPV6_ADD_MEMBERSHIP: i32 = 12;
 pub const IPV6_DROP_MEMBERSHIP: i32 = 13;
+pub const IPV6_MULTICAST_HOPS: i32 = 18;
 pub const IPV6_MULTICAST_LOOP: i32 = 19;
 pub const IPV6_V6ONLY: i32 = 27;
+pub const IPV6_RECVPKTINFO: i32 = 49;
+pub const IPV6_PKTINFO: i32 = 50;
 pub const IP_TOS: i32 = 1;
 pub const IP_TTL: i32 = 2;
 pub const IP_MULTICAST_TTL: i32 = 5;
 pub const IP_MULTICAST_LOOP: i32 = 7;
 pub const IP_ADD_MEMBERSHIP: i32 = 3;
 pub const IP_DROP_MEMBERSHIP: i32 = 4;
+pub const IP_PKTINFO: i32 = 8;
 pub const SOL_SOCKET: i32 = 4095;
 pub const SO_REUSEADDR: i32 = 0x0004;
 pub const SO_KEEPALIVE: i32 = 0x0008;
 pub const SO_BROADCAST: i32 = 0x0020;
 pub const SO_LINGER: i32 = 0x0080;
+pub const SO_TIMESTAMP: i32 = 0x0400;
 pub const SO_SNDBUF: i32 = 0x1001;
 pub const SO_RCVBUF: i32 = 0x1002;
 pub const SO_SNDTIMEO: i32 = 0x1005;
 pub const SO_RCVTIMEO: i32 = 0x1006;
 pub const SO_ERROR: i32 = 0x1007;
 pub const TCP_NODELAY: i32 = 1;
+pub const TCP_KEEPIDLE: i32 = 4;
+pub const TCP_KEEPINTVL: i32 = 5;
+pub const TCP_KEEPCNT: i32 = 6;
 pub const MSG_PEEK: i32 = 1;
+pub const MSG_CTRUNC: i32 = 0x08;
+pub const MSG_TRUNC: i32 = 0x20;
+pub const SCM_TIMESTAMP: i32 = SO_TIMESTAMP;
 pub type sa_family_t = u8;
 pub type socklen_t = u32;
 pub type in_addr_t = u32;
@@ -149,6 +203,8 @@ pub struct sockaddr {
 	pub sa_data: [c_char; 14],
 }
 
+const _: () = assert!(size_of::<sockaddr>() == 16);
+
 #[derive(Clone, Debug)]
 pub enum sockaddrBox {
 	sockaddr(Box<sockaddr>),
@@ -308,6 +364,8 @@ pub struct sockaddr_in {
 	pub sin_zero: [c_char; 8],
 }
 
+const _: () = assert!(size_of::<sockaddr_in>() == 16);
+
 #[cfg(feature = "net")]
 impl From<sockaddr_in> for IpListenEndpoint {
 	fn from(addr: sockaddr_in) -> IpListenEndpoint {
@@ -380,6 +438,8 @@ pub struct sockaddr_in6 {
 	pub sin6_scope_id: u32,
 }
 
+const _: () = assert!(size_of::<sockaddr_in6>() == 28);
+
 #[cfg(feature = "net")]
 impl From<sockaddr_in6> for IpListenEndpoint {
 	fn from(addr: sockaddr_in6) -> IpListenEndpoint {
@@ -464,6 +524,75 @@ pub struct sockaddr_un {
 	pub sun_path: [c_char; 104],
 }
 
+/// Parses the first `namelen` bytes at `name` as `sockaddr_un`.
+///
+/// An empty path denotes an unnamed socket and a path, which starts with a
+/// null byte, an abstract name.
+#[cfg(feature = "unix")]
+unsafe fn unix_address(name: *const sockaddr, namelen: socklen_t) -> Result<UnixAddress, Errno> {
+	let offset = mem::offset_of!(sockaddr_un, sun_path);
+	let namelen = usize::try_from(namelen)
+		.unwrap()
+		.min(size_of::<sockaddr_un>());
+	if namelen < offset {
+		return Err(Errno::Inval);
+	}
+
+	// Only `namelen` bytes of the address have to be readable.
+	let mut addr = sockaddr_un {
+		sun_len: 0,
+		sun_family: 0,
+		sun_path: [0; 104],
+	};
+	unsafe {
+		core::ptr::copy_nonoverlapping(name.cast::<u8>(), (&raw mut addr).cast::<u8>(), namelen);
+	}
+
+	let mut path = addr.sun_path[..namelen - offset].iter().map(|&c| c as u8);
+	match path.next() {
+		None => Ok(UnixAddress::Unnamed),
+		Some(0) => Ok(UnixAddress::Abstract(path.collect())),
+		Some(first) => {
+			let path = core::iter::once(first)
+				.chain(path.take_while(|&c| c != 0))
+				.collect();
+			String::from_utf8(path)
+				.map(UnixAddress::Path)
+				.map_err(|_| Errno::Inval)
+		}
+	}
+}
+
+/// Copies `address` as `sockaddr_un` into the buffer `addr` of `*addrlen`
+/// bytes and stores the length of the address in `addrlen`. As on Linux, the
+/// address is truncated, if the buffer is too small.
+#[cfg(feature = "unix")]
+unsafe fn write_unix_address(address: &UnixAddress, addr: *mut sockaddr, addrlen: &mut socklen_t) {
+	let name: Vec<u8> = match address {
+		UnixAddress::Unnamed => Vec::new(),
+		UnixAddress::Path(path) => path.bytes().chain([0]).collect(),
+		UnixAddress::Abstract(name) => [0].into_iter().chain(name.iter().copied()).collect(),
+	};
+
+	let mut sun_path = [0; 104];
+	let name = &name[..name.len().min(sun_path.len())];
+	for (c, &byte) in sun_path.iter_mut().zip(name) {
+		*c = byte as c_char;
+	}
+	let len = mem::offset_of!(sockaddr_un, sun_path) + name.len();
+	let sun = sockaddr_un {
+		sun_len: len.try_into().unwrap(),
+		sun_family: Af::Unix.into(),
+		sun_path,
+	};
+
+	let copied = len.min(usize::try_from(*addrlen).unwrap());
+	unsafe {
+		core::ptr::copy_nonoverlapping((&raw const sun).cast::<u8>(), addr.cast::<u8>(), copied);
+	}
+	*addrlen = len.try_into().unwrap();
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct ip_mreq {
@@ -526,13 +655,8 @@ pub unsafe extern "C" fn sys_getaddrbyname(
 	inaddr: *mut u8,
 	len: usize,
 ) -> i32 {
-	use alloc::borrow::ToOwned;
-
 	use smoltcp::wire::DnsQueryType;
 
-	use crate::executor::block_on;
-	use crate::executor::network::get_query_result;
-
 	if len != size_of::<in_addr>() && len != size_of::<in6_addr>() {
 		return -i32::from(Errno::Inval);
 	}
@@ -548,22 +672,11 @@ pub unsafe extern "C" fn sys_getaddrbyname(
 	};
 
 	let name = unsafe { core::ffi::CStr::from_ptr(name) };
-	let name = if let Ok(name) = name.to_str() {
-		name.to_owned()
-	} else {
+	let Ok(name) = name.to_str() else {
 		return -i32::from(Errno::Inval);
 	};
 
-	let query = {
-		let mut guard = NIC.lock();
-		let nic = guard.as_nic_mut().unwrap();
-		let query = nic.start_query(&name, query_type).unwrap();
-		nic.poll_common(crate::executor::network::now());
-
-		query
-	};
-
-	match block_on(get_query_result(query), None) {
+	match resolver::lookup(name, query_type) {
 		Ok(addr_vec) => {
 			let slice = unsafe { core::slice::from_raw_parts_mut(inaddr, len) };
 
@@ -598,10 +711,36 @@ pub extern "C" fn sys_socket(domain: i32, type_: i32, protocol: i32) -> i32 {
 	match (sock, proto) {
 		(_, Ipproto::Ip | Ipproto::Ipv6)
 		| (Sock::Stream, Ipproto::Tcp)
-		| (Sock::Dgram, Ipproto::Udp) => {}
+		| (Sock::Dgram, Ipproto::Udp)
+		| (Sock::Raw, Ipproto::Icmp | Ipproto::Icmpv6) => {}
 		(_, _) => return -i32::from(Errno::Inval),
 	}
 
+	#[cfg(feature = "unix")]
+	if domain == Af::Unix && proto == Ipproto::Ip && (sock == Sock::Stream || sock == Sock::Dgram) {
+		let socket: Arc<async_lock::RwLock<dyn ObjectInterface>> = if sock == Sock::Stream {
+			Arc::new(async_lock::RwLock::new(unix::StreamSocket::new()))
+		} else {
+			Arc::new(async_lock::RwLock::new(unix::DatagramSocket::new()))
+		};
+
+		if sock_flags.contains(SockFlags::SOCK_NONBLOCK) {
+			block_on(
+				async {
+					socket
+						.write()
+						.await
+						.set_status_flags(fd::StatusFlags::O_NONBLOCK)
+						.await
+				},
+				None,
+			)
+			.unwrap();
+		}
+
+		return insert_object(socket).unwrap_or_else(|e| -i32::from(e));
+	}
+
 	#[cfg(feature = "vsock")]
 	if domain == Af::Vsock && sock == Sock::Stream {
 		let mut socket = vsock::Socket::new();
@@ -611,9 +750,7 @@ pub extern "C" fn sys_socket(domain: i32, type_: i32, protocol: i32) -> i32 {
 		}
 
 		let socket = Arc::new(async_lock::RwLock::new(socket));
-		let fd = insert_object(socket).expect("FD is already used");
-
-		return fd;
+		return insert_object(socket).unwrap_or_else(|e| -i32::from(e));
 	}
 
 	#[cfg(feature = "net")]
@@ -624,7 +761,9 @@ pub extern "C" fn sys_socket(domain: i32, type_: i32, protocol: i32) -> i32 {
 		if let NetworkState::Initialized(nic) = &mut *guard {
 			#[cfg(feature = "udp")]
 			if sock == Sock::Dgram {
-				let handle = nic.create_udp_handle().unwrap();
+				let Ok(handle) = nic.create_udp_handle() else {
+					return -i32::from(Errno::Nobufs);
+				};
 				drop(guard);
 				let mut socket = udp::Socket::new(handle, domain);
 
@@ -633,14 +772,14 @@ pub extern "C" fn sys_socket(domain: i32, type_: i32, protocol: i32) -> i32 {
 				}
 
 				let socket = Arc::new(async_lock::RwLock::new(socket));
-				let fd = insert_object(socket).expect("FD is already used");
-
-				return fd;
+				return insert_object(socket).unwrap_or_else(|e| -i32::from(e));
 			}
 
 			#[cfg(feature = "tcp")]
 			if sock == Sock::Stream {
-				let handle = nic.create_tcp_handle().unwrap();
+				let Ok(handle) = nic.create_tcp_handle() else {
+					return -i32::from(Errno::Nobufs);
+				};
 				drop(guard);
 				let mut socket = tcp::Socket::new(handle, domain);
 
@@ -649,16 +788,129 @@ pub extern "C" fn sys_socket(domain: i32, type_: i32, protocol: i32) -> i32 {
 				}
 
 				let socket = Arc::new(async_lock::RwLock::new(socket));
-				let fd = insert_object(socket).expect("FD is already used");
+				return insert_object(socket).unwrap_or_else(|e| -i32::from(e));
+			}
+		}
+	}
+
+	#[cfg(feature = "raw")]
+	if sock == Sock::Raw
+		&& matches!(
+			(domain, proto),
+			(Af::Inet, Ipproto::Icmp) | (Af::Inet6, Ipproto::Icmpv6)
+		) {
+		let mut guard = NIC.lock();
+
+		if let NetworkState::Initialized(nic) = &mut *guard {
+			let (ip_version, protocol) = if domain == Af::Inet {
+				(IpVersion::Ipv4, IpProtocol::Icmp)
+			} else {
+				(IpVersion::Ipv6, IpProtocol::Icmpv6)
+			};
+			let Ok(handle) = nic.create_raw_handle(ip_version, protocol) else {
+				return -i32::from(Errno::Nobufs);
+			};
+			drop(guard);
+			let mut socket = raw::Socket::new(handle, domain);
 
-				return fd;
+			if sock_flags.contains(SockFlags::SOCK_NONBLOCK) {
+				block_on(socket.set_status_flags(fd::StatusFlags::O_NONBLOCK), None).unwrap();
 			}
+
+			let socket = Arc::new(async_lock::RwLock::new(socket));
+			return insert_object(socket).unwrap_or_else(|e| -i32::from(e));
 		}
 	}
 
 	-i32::from(Errno::Inval)
 }
 
+/// Creates a pair of connected sockets of the domain `AF_UNIX` and stores
+/// their file descriptors in `sv`.
+#[cfg(feature = "unix")]
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_socketpair(
+	domain: i32,
+	type_: i32,
+	protocol: i32,
+	sv: *mut i32,
+) -> i32 {
+	debug!("sys_socketpair: domain {domain}, type {type_:?}, protocol {protocol}");
+
+	if sv.is_null() {
+		return -i32::from(Errno::Inval);
+	}
+
+	let Ok(Ok(domain)) = u8::try_from(domain).map(Af::try_from) else {
+		return -i32::from(Errno::Inval);
+	};
+	if domain != Af::Unix {
+		return -i32::from(Errno::Opnotsupp);
+	}
+
+	let Some((sock, sock_flags)) = Sock::from_bits(type_) else {
+		return -i32::from(Errno::Inval);
+	};
+	if protocol != 0 {
+		return -i32::from(Errno::Protonosupport);
+	}
+
+	let sockets: [Arc<async_lock::RwLock<dyn ObjectInterface>>; 2] = match sock {
+		Sock::Stream => {
+			let (first, second) = unix::StreamSocket::pair();
+			[
+				Arc::new(async_lock::RwLock::new(first)),
+				Arc::new(async_lock::RwLock::new(second)),
+			]
+		}
+		Sock::Dgram => {
+			let (first, second) = unix::DatagramSocket::pair();
+			[
+				Arc::new(async_lock::RwLock::new(first)),
+				Arc::new(async_lock::RwLock::new(second)),
+			]
+		}
+		_ => return -i32::from(Errno::Inval),
+	};
+
+	if sock_flags.contains(SockFlags::SOCK_NONBLOCK) {
+		for socket in &sockets {
+			block_on(
+				async {
+					socket
+						.write()
+						.await
+						.set_status_flags(fd::StatusFlags::O_NONBLOCK)
+						.await
+				},
+				None,
+			)
+			.unwrap();
+		}
+	}
+
+	let [first, second] = sockets;
+	let first = match insert_object(first) {
+		Ok(fd) => fd,
+		Err(e) => return -i32::from(e),
+	};
+	let second = match insert_object(second) {
+		Ok(fd) => fd,
+		Err(e) => {
+			let _ = fd::remove_object(first);
+			return -i32::from(e);
+		}
+	};
+
+	unsafe {
+		*sv = first;
+		*sv.add(1) = second;
+	}
+
+	0
+}
+
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_accept(fd: i32, addr: *mut sockaddr, addrlen: *mut socklen_t) -> i32 {
@@ -666,13 +918,20 @@ pub unsafe extern "C" fn sys_accept(fd: i32, addr: *mut sockaddr, addrlen: *mut
 	obj.map_or_else(
 		|e| -i32::from(e),
 		|v| {
-			block_on(async { v.write().await.accept().await }, None).map_or_else(
+			let timeout = fd::socket_timeout(&v, SocketOption::RecvTimeout);
+			fd::block_on_io(async { v.write().await.accept().await }, timeout).map_or_else(
 				|e| -i32::from(e),
-				#[cfg_attr(not(feature = "net"), expect(unused_variables))]
+				#[cfg_attr(
+					not(any(feature = "net", feature = "unix", feature = "vsock")),
+					expect(unused_variables)
+				)]
 				|(obj, endpoint)| match endpoint {
 					#[cfg(feature = "net")]
 					Endpoint::Ip(endpoint) => {
-						let new_fd = insert_object(obj).unwrap();
+						let new_fd = match insert_object(obj) {
+							Ok(fd) => fd,
+							Err(e) => return -i32::from(e),
+						};
 
 						if !addr.is_null() && !addrlen.is_null() {
 							let addrlen = unsafe { &mut *addrlen };
@@ -701,7 +960,10 @@ pub unsafe extern "C" fn sys_accept(fd: i32, addr: *mut sockaddr, addrlen: *mut
 					}
 					#[cfg(feature = "vsock")]
 					Endpoint::Vsock(endpoint) => {
-						let new_fd = insert_object(v.clone()).unwrap();
+						let new_fd = match insert_object(obj) {
+							Ok(fd) => fd,
+							Err(e) => return -i32::from(e),
+						};
 
 						if !addr.is_null() && !addrlen.is_null() {
 							let addrlen = unsafe { &mut *addrlen };
@@ -713,6 +975,19 @@ pub unsafe extern "C" fn sys_accept(fd: i32, addr: *mut sockaddr, addrlen: *mut
 							}
 						}
 
+						new_fd
+					}
+					#[cfg(feature = "unix")]
+					Endpoint::Unix(address) => {
+						let new_fd = match insert_object(obj) {
+							Ok(fd) => fd,
+							Err(e) => return -i32::from(e),
+						};
+
+						if !addr.is_null() && !addrlen.is_null() {
+							unsafe { write_unix_address(&address, addr, &mut *addrlen) };
+						}
+
 						new_fd
 					}
 				},
@@ -734,6 +1009,116 @@ pub extern "C" fn sys_listen(fd: i32, backlog: i32) -> i32 {
 	)
 }
 
+/// Occupancy of the queue of a listening socket, see [`sys_listen_stats`]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct listen_stats {
+	/// Connections, which wait to be accepted
+	pub depth: u32,
+	/// Maximum number of connections, which can wait
+	pub backlog: u32,
+	/// Connection attempts, which have found the queue full
+	///
+	/// TCP listeners do not count overflows, since smoltcp refuses them
+	/// without notice.
+	pub overflows: u64,
+}
+
+/// Writes the occupancy of the queue of the listening socket `fd` to `stats`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_listen_stats(fd: i32, stats: *mut listen_stats) -> i32 {
+	let Some(stats) = (unsafe { stats.as_mut() }) else {
+		return -i32::from(Errno::Fault);
+	};
+
+	let obj = get_object(fd);
+	obj.map_or_else(
+		|e| -i32::from(e),
+		|v| {
+			block_on(async { v.read().await.listen_queue().await }, None).map_or_else(
+				|e| -i32::from(e),
+				|queue| {
+					*stats = listen_stats {
+						depth: queue.depth.try_into().unwrap_or(u32::MAX),
+						backlog: queue.backlog.try_into().unwrap_or(u32::MAX),
+						overflows: queue.overflows,
+					};
+					0
+				},
+			)
+		},
+	)
+}
+
+/// Accepts up to `len` connections on the listening socket `fd` and writes
+/// their file descriptors to `fds`.
+///
+/// Like `accept`, the call waits for the first connection, unless the
+/// socket is non-blocking. The connections, which are pending afterwards,
+/// are accepted without waiting. Returns the number of accepted connections.
+/// The addresses of the peers are returned by `getpeername`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_accept_batch(fd: i32, fds: *mut i32, len: usize) -> isize {
+	if fds.is_null() || len == 0 {
+		return -isize::try_from(i32::from(Errno::Inval)).unwrap();
+	}
+	let fds = unsafe { core::slice::from_raw_parts_mut(fds, len) };
+
+	let obj = match get_object(fd) {
+		Ok(obj) => obj,
+		Err(e) => return -isize::try_from(i32::from(e)).unwrap(),
+	};
+
+	// Only the wait for the first connection can time out.
+	let timeout = fd::socket_timeout(&obj, SocketOption::RecvTimeout);
+	let result = fd::block_on_io(
+		async {
+			let mut guard = obj.write().await;
+			let (first, _) = guard.accept().await?;
+			fds[0] = insert_object(first)?;
+
+			// The pending connections are taken without waiting for more.
+			// The accepted connection is returned, even if this fails.
+			let Ok(status_flags) = guard.status_flags().await else {
+				return Ok(1);
+			};
+			if guard
+				.set_status_flags(status_flags | fd::StatusFlags::O_NONBLOCK)
+				.await
+				.is_err()
+			{
+				return Ok(1);
+			}
+
+			let mut count = 1;
+			while count < len {
+				match guard.accept().await {
+					Ok((obj, _)) => match insert_object(obj) {
+						Ok(new_fd) => {
+							fds[count] = new_fd;
+							count += 1;
+						}
+						// The connection is closed, when it is dropped.
+						Err(_) => break,
+					},
+					Err(_) => break,
+				}
+			}
+
+			let _ = guard.set_status_flags(status_flags).await;
+			Ok(count)
+		},
+		timeout,
+	);
+
+	match result {
+		Ok(count) => count.try_into().unwrap(),
+		Err(e) => -isize::try_from(i32::from(e)).unwrap(),
+	}
+}
+
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_bind(fd: i32, name: *const sockaddr, namelen: socklen_t) -> i32 {
@@ -785,6 +1170,15 @@ pub unsafe extern "C" fn sys_bind(fd: i32, name: *const sockaddr, namelen: sockl
 				)
 				.map_or_else(|e| -i32::from(e), |()| 0)
 			}
+			#[cfg(feature = "unix")]
+			Af::Unix => match unsafe { unix_address(name, namelen) } {
+				Ok(address) => block_on(
+					async { v.write().await.bind(ListenEndpoint::Unix(address)).await },
+					None,
+				)
+				.map_or_else(|e| -i32::from(e), |()| 0),
+				Err(e) => -i32::from(e),
+			},
 			_ => -i32::from(Errno::Inval),
 		},
 	)
@@ -823,6 +1217,11 @@ pub unsafe extern "C" fn sys_connect(fd: i32, name: *const sockaddr, namelen: so
 			}
 			Endpoint::Vsock(VsockEndpoint::from(unsafe { *name.cast::<sockaddr_vm>() }))
 		}
+		#[cfg(feature = "unix")]
+		Af::Unix => match unsafe { unix_address(name, namelen) } {
+			Ok(address) => Endpoint::Unix(address),
+			Err(e) => return -i32::from(e),
+		},
 		_ => {
 			return -i32::from(Errno::Inval);
 		}
@@ -890,6 +1289,11 @@ pub unsafe extern "C" fn sys_getsockname(
 								-i32::from(Errno::Inval)
 							}
 						}
+						#[cfg(feature = "unix")]
+						Endpoint::Unix(address) => {
+							unsafe { write_unix_address(&address, addr, addrlen) };
+							0
+						}
 					}
 				} else {
 					-i32::from(Errno::Inval)
@@ -901,6 +1305,209 @@ pub unsafe extern "C" fn sys_getsockname(
 	)
 }
 
+/// Reads the hop limit of multicast datagrams, which may be passed as byte or as `i32`.
+#[cfg(feature = "udp")]
+fn multicast_hop_limit(optval: *const c_void, optlen: socklen_t) -> Result<u8, Errno> {
+	let value = match usize::try_from(optlen).unwrap() {
+		1 => i32::from(unsafe { *optval.cast::<u8>() }),
+		len if len == size_of::<i32>() => unsafe { *optval.cast::<i32>() },
+		_ => return Err(Errno::Inval),
+	};
+	match value {
+		// -1 selects the default
+		-1 => Ok(1),
+		// 0 is rejected, since the datagrams are not looped back to the host.
+		1..=255 => Ok(value.try_into().unwrap()),
+		_ => Err(Errno::Inval),
+	}
+}
+
+/// Parses the options of `sys_setsockopt`, which configure multicast.
+///
+/// Returns `None`, if the option is not related to multicast.
+#[cfg(feature = "udp")]
+fn multicast_option(
+	level: Ipproto,
+	optname: i32,
+	optval: *const c_void,
+	optlen: socklen_t,
+) -> Option<Result<MulticastOption, Errno>> {
+	let optlen_fits = |size: usize| usize::try_from(optlen).unwrap() >= size;
+	let opt = match (level, optname) {
+		(Ipproto::Ip, IP_MULTICAST_TTL) | (Ipproto::Ipv6, IPV6_MULTICAST_HOPS) => {
+			multicast_hop_limit(optval, optlen).map(MulticastOption::HopLimit)
+		}
+		(Ipproto::Ip, IP_ADD_MEMBERSHIP | IP_DROP_MEMBERSHIP) => {
+			if !optlen_fits(size_of::<ip_mreq>()) {
+				return Some(Err(Errno::Inval));
+			}
+			// The interface is ignored, since there is only one.
+			let mreq = unsafe { optval.cast::<ip_mreq>().read_unaligned() };
+			let group = Ipv4Addr::from(mreq.imr_multiaddr.s_addr.to_ne_bytes());
+			if optname == IP_ADD_MEMBERSHIP {
+				Ok(MulticastOption::AddMembership(group.into()))
+			} else {
+				Ok(MulticastOption::DropMembership(group.into()))
+			}
+		}
+		(Ipproto::Ipv6, IPV6_ADD_MEMBERSHIP | IPV6_DROP_MEMBERSHIP) => {
+			if !optlen_fits(size_of::<ipv6_mreq>()) {
+				return Some(Err(Errno::Inval));
+			}
+			let mreq = unsafe { optval.cast::<ipv6_mreq>().read_unaligned() };
+			let group = Ipv6Addr::from(mreq.ipv6mr_multiaddr.s6_addr);
+			if optname == IPV6_ADD_MEMBERSHIP {
+				Ok(MulticastOption::AddMembership(group.into()))
+			} else {
+				Ok(MulticastOption::DropMembership(group.into()))
+			}
+		}
+		_ => return None,
+	};
+	Some(opt)
+}
+
+/// Maps an option of the level `SOL_SOCKET` to a [`SocketOption`].
+fn socket_level_option(optname: i32) -> Option<SocketOption> {
+	let opt = match optname {
+		SO_REUSEADDR => SocketOption::ReuseAddr,
+		SO_KEEPALIVE => SocketOption::KeepAlive,
+		SO_LINGER => SocketOption::Linger,
+		SO_SNDBUF => SocketOption::SendBuffer,
+		SO_RCVBUF => SocketOption::RecvBuffer,
+		SO_SNDTIMEO => SocketOption::SendTimeout,
+		SO_RCVTIMEO => SocketOption::RecvTimeout,
+		SO_ERROR => SocketOption::Error,
+		SO_TIMESTAMP => SocketOption::Timestamp,
+		_ => return None,
+	};
+	Some(opt)
+}
+
+/// Maps an option of the level `IPPROTO_TCP` to a [`SocketOption`].
+fn tcp_level_option(optname: i32) -> Option<SocketOption> {
+	let opt = match optname {
+		TCP_NODELAY => SocketOption::TcpNoDelay,
+		TCP_KEEPIDLE => SocketOption::TcpKeepIdle,
+		TCP_KEEPINTVL => SocketOption::TcpKeepInterval,
+		TCP_KEEPCNT => SocketOption::TcpKeepCount,
+		_ => return None,
+	};
+	Some(opt)
+}
+
+/// Maps an option of the levels `IPPROTO_IP` and `IPPROTO_IPV6` to a [`SocketOption`].
+fn ip_level_option(level: Ipproto, optname: i32) -> Option<SocketOption> {
+	match (level, optname) {
+		(Ipproto::Ip, IP_PKTINFO) | (Ipproto::Ipv6, IPV6_RECVPKTINFO) => {
+			Some(SocketOption::PacketInfo)
+		}
+		_ => None,
+	}
+}
+
+/// Reads the value of `opt`, which is passed to `setsockopt`.
+unsafe fn read_option_value(
+	opt: SocketOption,
+	optval: *const c_void,
+	optlen: socklen_t,
+) -> Result<SocketOptionValue, Errno> {
+	let optlen = usize::try_from(optlen).unwrap();
+	if optval.is_null() {
+		return Err(Errno::Inval);
+	}
+
+	match opt {
+		SocketOption::Linger => {
+			if optlen < size_of::<linger>() {
+				return Err(Errno::Inval);
+			}
+			let value = unsafe { optval.cast::<linger>().read_unaligned() };
+			let seconds = u64::try_from(value.l_linger).map_err(|_| Errno::Inval)?;
+			Ok(SocketOptionValue::Duration(
+				(value.l_onoff != 0).then(|| Duration::from_secs(seconds)),
+			))
+		}
+		SocketOption::SendTimeout | SocketOption::RecvTimeout => {
+			if optlen < size_of::<timeval>() {
+				return Err(Errno::Inval);
+			}
+			let tv = unsafe { optval.cast::<timeval>().read_unaligned() };
+			if !(0..1_000_000).contains(&tv.tv_usec) {
+				return Err(Errno::Dom);
+			}
+			let micros = tv
+				.into_usec()
+				.and_then(|micros| u64::try_from(micros).ok())
+				.ok_or(Errno::Inval)?;
+			// A zero timeout blocks forever.
+			Ok(SocketOptionValue::Duration(
+				(micros > 0).then(|| Duration::from_micros(micros)),
+			))
+		}
+		SocketOption::Error => Err(Errno::Noprotoopt),
+		_ => {
+			if optlen < size_of::<i32>() {
+				return Err(Errno::Inval);
+			}
+			let value = unsafe { optval.cast::<i32>().read_unaligned() };
+			match opt {
+				SocketOption::SendBuffer
+				| SocketOption::RecvBuffer
+				| SocketOption::TcpKeepIdle
+				| SocketOption::TcpKeepInterval
+				| SocketOption::TcpKeepCount => Ok(SocketOptionValue::Int(value)),
+				_ => Ok(SocketOptionValue::Flag(value != 0)),
+			}
+		}
+	}
+}
+
+/// Writes the value of `opt`, which is returned by `getsockopt`.
+unsafe fn write_option_value(
+	opt: SocketOption,
+	value: SocketOptionValue,
+	optval: *mut c_void,
+	optlen: &mut socklen_t,
+) -> Result<(), Errno> {
+	unsafe fn write<T>(value: T, optval: *mut c_void, optlen: &mut socklen_t) -> Result<(), Errno> {
+		if usize::try_from(*optlen).unwrap() < size_of::<T>() {
+			return Err(Errno::Inval);
+		}
+		unsafe {
+			optval.cast::<T>().write_unaligned(value);
+		}
+		*optlen = size_of::<T>().try_into().unwrap();
+		Ok(())
+	}
+
+	match (opt, value) {
+		(SocketOption::Linger, SocketOptionValue::Duration(time)) => {
+			let value = linger {
+				l_onoff: time.is_some().into(),
+				l_linger: time.map_or(0, |d| d.as_secs().try_into().unwrap_or(i32::MAX)),
+			};
+			unsafe { write(value, optval, optlen) }
+		}
+		(_, SocketOptionValue::Duration(timeout)) => {
+			let micros = timeout.map_or(0, |d| d.as_micros().try_into().unwrap_or(i64::MAX));
+			unsafe { write(timeval::from_usec(micros), optval, optlen) }
+		}
+		(_, SocketOptionValue::Flag(flag)) => unsafe { write(i32::from(flag), optval, optlen) },
+		(_, SocketOptionValue::Int(value)) => unsafe { write(value, optval, optlen) },
+	}
+}
+
+fn setsockopt(fd: i32, opt: SocketOption, value: SocketOptionValue) -> i32 {
+	get_object(fd).map_or_else(
+		|e| -i32::from(e),
+		|v| {
+			block_on(async { v.write().await.setsockopt(opt, value).await }, None)
+				.map_or_else(|e| -i32::from(e), |()| 0)
+		},
+	)
+}
+
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_setsockopt(
@@ -910,8 +1517,16 @@ pub unsafe extern "C" fn sys_setsockopt(
 	optval: *const c_void,
 	optlen: socklen_t,
 ) -> i32 {
-	if level == SOL_SOCKET && optname == SO_REUSEADDR {
-		return 0;
+	if level == SOL_SOCKET {
+		debug!("sys_setsockopt: {fd}, level SOL_SOCKET, optname {optname}");
+
+		let Some(opt) = socket_level_option(optname) else {
+			return -i32::from(Errno::Noprotoopt);
+		};
+		return match unsafe { read_option_value(opt, optval, optlen) } {
+			Ok(value) => setsockopt(fd, opt, value),
+			Err(e) => -i32::from(e),
+		};
 	}
 
 	let Ok(Ok(level)) = u8::try_from(level).map(Ipproto::try_from) else {
@@ -920,36 +1535,96 @@ pub unsafe extern "C" fn sys_setsockopt(
 
 	debug!("sys_setsockopt: {fd}, level {level:?}, optname {optname}");
 
-	if level == Ipproto::Tcp
-		&& optname == TCP_NODELAY
-		&& optlen == u32::try_from(size_of::<i32>()).unwrap()
+	// Sockets, which are bound to the unspecified IPv6 address, accept IPv4
+	// connections as well. Restricting them to IPv6 is not supported.
+	if level == Ipproto::Ipv6 && optname == IPV6_V6ONLY {
+		return 0;
+	}
+
+	// Multicast datagrams are not looped back to the sockets of the host, so
+	// disabling the loopback has no effect.
+	#[cfg(feature = "udp")]
+	if (level == Ipproto::Ip && optname == IP_MULTICAST_LOOP)
+		|| (level == Ipproto::Ipv6 && optname == IPV6_MULTICAST_LOOP)
+	{
+		return 0;
+	}
+
+	#[cfg(feature = "raw")]
+	if (level == Ipproto::Ip && optname == IP_TTL)
+		|| (level == Ipproto::Ipv6 && optname == IPV6_UNICAST_HOPS)
 	{
-		if optval.is_null() {
+		if optval.is_null() || optlen != u32::try_from(size_of::<i32>()).unwrap() {
 			return -i32::from(Errno::Inval);
 		}
+		let hop_limit = match unsafe { *optval.cast::<i32>() } {
+			-1 => raw::DEFAULT_HOP_LIMIT,
+			value @ 1..=255 => value.try_into().unwrap(),
+			_ => return -i32::from(Errno::Inval),
+		};
+		return get_object(fd).map_or_else(
+			|e| -i32::from(e),
+			|v| {
+				block_on(
+					async { v.write().await.set_hop_limit(hop_limit).await },
+					None,
+				)
+				.map_or_else(|e| -i32::from(e), |()| 0)
+			},
+		);
+	}
 
-		let value = unsafe { *optval.cast::<i32>() };
-		let obj = get_object(fd);
-		obj.map_or_else(
+	#[cfg(feature = "udp")]
+	if !optval.is_null()
+		&& let Some(opt) = multicast_option(level, optname, optval, optlen)
+	{
+		let opt = match opt {
+			Ok(opt) => opt,
+			Err(e) => return -i32::from(e),
+		};
+		return get_object(fd).map_or_else(
 			|e| -i32::from(e),
 			|v| {
 				block_on(
-					async {
-						v.read()
-							.await
-							.setsockopt(SocketOption::TcpNoDelay, value != 0)
-							.await
-					},
+					async { v.write().await.set_multicast_option(opt).await },
 					None,
 				)
 				.map_or_else(|e| -i32::from(e), |()| 0)
 			},
-		)
+		);
+	}
+
+	if let Some(opt) = ip_level_option(level, optname) {
+		return match unsafe { read_option_value(opt, optval, optlen) } {
+			Ok(value) => setsockopt(fd, opt, value),
+			Err(e) => -i32::from(e),
+		};
+	}
+
+	if level == Ipproto::Tcp
+		&& let Some(opt) = tcp_level_option(optname)
+	{
+		match unsafe { read_option_value(opt, optval, optlen) } {
+			Ok(value) => setsockopt(fd, opt, value),
+			Err(e) => -i32::from(e),
+		}
 	} else {
 		-i32::from(Errno::Inval)
 	}
 }
 
+fn getsockopt(fd: i32, opt: SocketOption, optval: *mut c_void, optlen: *mut socklen_t) -> i32 {
+	if optval.is_null() || optlen.is_null() {
+		return -i32::from(Errno::Inval);
+	}
+
+	let optlen = unsafe { &mut *optlen };
+	get_object(fd)
+		.and_then(|v| block_on(async { v.read().await.getsockopt(opt).await }, None))
+		.and_then(|value| unsafe { write_option_value(opt, value, optval, optlen) })
+		.map_or_else(|e| -i32::from(e), |()| 0)
+}
+
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_getsockopt(
@@ -959,47 +1634,107 @@ pub unsafe extern "C" fn sys_getsockopt(
 	optval: *mut c_void,
 	optlen: *mut socklen_t,
 ) -> i32 {
+	if level == SOL_SOCKET {
+		debug!("sys_getsockopt: {fd}, level SOL_SOCKET, optname {optname}");
+
+		return match socket_level_option(optname) {
+			Some(opt) => getsockopt(fd, opt, optval, optlen),
+			None => -i32::from(Errno::Noprotoopt),
+		};
+	}
+
 	let Ok(Ok(level)) = u8::try_from(level).map(Ipproto::try_from) else {
 		return -i32::from(Errno::Inval);
 	};
 
 	debug!("sys_getsockopt: {fd}, level {level:?}, optname {optname}");
 
-	if level == Ipproto::Tcp && optname == TCP_NODELAY {
-		if optval.is_null() || optlen.is_null() {
-			return -i32::from(Errno::Inval);
-		}
-
-		let optval = unsafe { &mut *optval.cast::<i32>() };
-		let optlen = unsafe { &mut *optlen };
-		let obj = get_object(fd);
-		obj.map_or_else(
-			|e| -i32::from(e),
-			|v| {
-				block_on(
-					async { v.read().await.getsockopt(SocketOption::TcpNoDelay).await },
-					None,
-				)
-				.map_or_else(
-					|e| -i32::from(e),
-					|value| {
-						if value {
-							*optval = 1;
-						} else {
-							*optval = 0;
-						}
-						*optlen = core::mem::size_of::<i32>().try_into().unwrap();
+	if let Some(opt) = ip_level_option(level, optname) {
+		return getsockopt(fd, opt, optval, optlen);
+	}
 
-						0
-					},
-				)
-			},
-		)
+	if level == Ipproto::Tcp
+		&& let Some(opt) = tcp_level_option(optname)
+	{
+		getsockopt(fd, opt, optval, optlen)
 	} else {
 		-i32::from(Errno::Inval)
 	}
 }
 
+/// Limits of the buffers of TCP, UDP, and raw sockets, see
+/// [`sys_socket_set_buffer_sizes`]
+#[cfg(feature = "net")]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct socket_buffer_sizes {
+	/// Capacity of the receive buffer of new sockets
+	pub rmem_default: usize,
+	/// Maximum capacity of the receive buffer given by `SO_RCVBUF`
+	pub rmem_max: usize,
+	/// Capacity of the send buffer of new sockets
+	pub wmem_default: usize,
+	/// Maximum capacity of the send buffer given by `SO_SNDBUF`
+	pub wmem_max: usize,
+	/// Memory, which the buffers of all sockets may occupy together
+	pub mem_max: usize,
+	/// Maximum number of sockets, `usize::MAX` if unlimited
+	pub max_sockets: usize,
+}
+
+/// Writes the current limits of the socket buffers to `sizes`.
+#[cfg(feature = "net")]
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_socket_get_buffer_sizes(sizes: *mut socket_buffer_sizes) -> i32 {
+	let Some(sizes) = (unsafe { sizes.as_mut() }) else {
+		return -i32::from(Errno::Fault);
+	};
+
+	let limits = socket_buffer::limits();
+	*sizes = socket_buffer_sizes {
+		rmem_default: limits.rx_default,
+		rmem_max: limits.rx_max,
+		wmem_default: limits.tx_default,
+		wmem_max: limits.tx_max,
+		mem_max: limits.budget,
+		max_sockets: limits.max_sockets,
+	};
+	0
+}
+
+/// Changes the limits of the socket buffers like the arguments
+/// `net.rmem_default=` ... `net.max_sockets=`.
+///
+/// Fields, which are 0, are left unchanged. The capacities are clamped like
+/// the ones of the arguments, which `sys_socket_get_buffer_sizes` returns.
+/// The limits apply to the sockets, which are created afterwards, and to
+/// `SO_RCVBUF` and `SO_SNDBUF`. Existing sockets keep their buffers, even if
+/// they exceed a lowered budget.
+#[cfg(feature = "net")]
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_socket_set_buffer_sizes(sizes: *const socket_buffer_sizes) -> i32 {
+	let Some(sizes) = (unsafe { sizes.as_ref() }) else {
+		return -i32::from(Errno::Fault);
+	};
+
+	let set = |limit: &mut usize, value: usize| {
+		if value != 0 {
+			*limit = value;
+		}
+	};
+	socket_buffer::update_limits(|limits| {
+		set(&mut limits.rx_max, sizes.rmem_max);
+		set(&mut limits.tx_max, sizes.wmem_max);
+		set(&mut limits.rx_default, sizes.rmem_default);
+		set(&mut limits.tx_default, sizes.wmem_default);
+		set(&mut limits.budget, sizes.mem_max);
+		set(&mut limits.max_sockets, sizes.max_sockets);
+	});
+	0
+}
+
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_getpeername(
@@ -1046,6 +1781,10 @@ pub unsafe extern "C" fn sys_getpeername(
 								return -i32::from(Errno::Inval);
 							}
 						}
+						#[cfg(feature = "unix")]
+						Endpoint::Unix(address) => unsafe {
+							write_unix_address(&address, addr, addrlen);
+						},
 					}
 				} else {
 					return -i32::from(Errno::Inval);
@@ -1100,64 +1839,113 @@ pub unsafe extern "C" fn sys_recv(fd: i32, buf: *mut u8, len: usize, flags: i32)
 	}
 }
 
-#[hermit_macro::system(errno)]
-#[unsafe(no_mangle)]
-pub unsafe extern "C" fn sys_sendto(
-	fd: i32,
-	buf: *const u8,
-	len: usize,
-	_flags: i32,
+/// Converts the address `addr` of the length `addr_len` into an endpoint.
+unsafe fn endpoint_from_sockaddr(
 	addr: *const sockaddr,
 	addr_len: socklen_t,
-) -> isize {
-	let endpoint;
-
+) -> Result<Endpoint, Errno> {
 	if addr.is_null() || addr_len == 0 {
-		return (-i32::from(Errno::Inval)).try_into().unwrap();
+		return Err(Errno::Inval);
 	}
 
-	cfg_if! {
-		if #[cfg(feature = "net")] {
-			let Ok(sa_family) = (unsafe { Af::try_from((*addr).sa_family) }) else {
-				return (-i32::from(Errno::Inval)).try_into().unwrap();
-			};
-
-			if sa_family == Af::Inet {
-				if addr_len < u32::try_from(size_of::<sockaddr_in>()).unwrap() {
-					return (-i32::from(Errno::Inval)).try_into().unwrap();
-				}
+	let sa_family = unsafe { Af::try_from((*addr).sa_family) }.map_err(|_| Errno::Inval)?;
 
-				endpoint = Some(Endpoint::Ip(IpEndpoint::from(unsafe {*(addr.cast::<sockaddr_in>())})));
-			} else if sa_family == Af::Inet6 {
-				if addr_len < u32::try_from(size_of::<sockaddr_in6>()).unwrap() {
-					return (-i32::from(Errno::Inval)).try_into().unwrap();
-				}
+	match sa_family {
+		#[cfg(feature = "net")]
+		Af::Inet => {
+			if addr_len < u32::try_from(size_of::<sockaddr_in>()).unwrap() {
+				return Err(Errno::Inval);
+			}
 
-				endpoint = Some(Endpoint::Ip(IpEndpoint::from(unsafe { *(addr.cast::<sockaddr_in6>()) })));
-			} else {
-				endpoint = None;
+			Ok(Endpoint::Ip(IpEndpoint::from(unsafe {
+				*(addr.cast::<sockaddr_in>())
+			})))
+		}
+		#[cfg(feature = "net")]
+		Af::Inet6 => {
+			if addr_len < u32::try_from(size_of::<sockaddr_in6>()).unwrap() {
+				return Err(Errno::Inval);
 			}
-		} else {
-			endpoint = None;
+
+			Ok(Endpoint::Ip(IpEndpoint::from(unsafe {
+				*(addr.cast::<sockaddr_in6>())
+			})))
 		}
+		#[cfg(feature = "unix")]
+		Af::Unix => unsafe { unix_address(addr, addr_len) }.map(Endpoint::Unix),
+		_ => Err(Errno::Inval),
 	}
+}
 
-	if let Some(endpoint) = endpoint {
-		let slice = unsafe { core::slice::from_raw_parts(buf, len) };
-		let obj = get_object(fd);
-
-		obj.map_or_else(
-			|e| isize::try_from(-i32::from(e)).unwrap(),
-			|v| {
-				block_on(async { v.read().await.sendto(slice, endpoint).await }, None).map_or_else(
-					|e| isize::try_from(-i32::from(e)).unwrap(),
-					|v| v.try_into().unwrap(),
-				)
-			},
-		)
-	} else {
-		(-i32::from(Errno::Inval)).try_into().unwrap()
+/// Writes `endpoint` into `addr`, which has room for `addrlen` bytes, and
+/// sets `addrlen` to the length of the address.
+unsafe fn write_endpoint(
+	endpoint: Endpoint,
+	addr: *mut sockaddr,
+	addrlen: &mut socklen_t,
+) -> Result<(), Errno> {
+	match endpoint {
+		#[cfg(feature = "net")]
+		Endpoint::Ip(endpoint) => match endpoint.addr {
+			IpAddress::Ipv4(_) => {
+				if *addrlen < u32::try_from(size_of::<sockaddr_in>()).unwrap() {
+					return Err(Errno::Inval);
+				}
+				let addr = unsafe { &mut *addr.cast() };
+				*addr = sockaddr_in::from(endpoint);
+				*addrlen = size_of::<sockaddr_in>().try_into().unwrap();
+			}
+			IpAddress::Ipv6(_) => {
+				if *addrlen < u32::try_from(size_of::<sockaddr_in6>()).unwrap() {
+					return Err(Errno::Inval);
+				}
+				let addr = unsafe { &mut *addr.cast() };
+				*addr = sockaddr_in6::from(endpoint);
+				*addrlen = size_of::<sockaddr_in6>().try_into().unwrap();
+			}
+		},
+		#[cfg(feature = "unix")]
+		Endpoint::Unix(address) => unsafe {
+			write_unix_address(&address, addr, addrlen);
+		},
+		#[cfg(feature = "vsock")]
+		_ => return Err(Errno::Inval),
 	}
+	Ok(())
+}
+
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sendto(
+	fd: i32,
+	buf: *const u8,
+	len: usize,
+	_flags: i32,
+	addr: *const sockaddr,
+	addr_len: socklen_t,
+) -> isize {
+	let endpoint = match unsafe { endpoint_from_sockaddr(addr, addr_len) } {
+		Ok(endpoint) => endpoint,
+		Err(e) => return (-i32::from(e)).try_into().unwrap(),
+	};
+
+	let slice = unsafe { core::slice::from_raw_parts(buf, len) };
+	let obj = get_object(fd);
+
+	obj.map_or_else(
+		|e| isize::try_from(-i32::from(e)).unwrap(),
+		|v| {
+			let timeout = fd::socket_timeout(&v, SocketOption::SendTimeout);
+			fd::block_on_io(
+				async { v.read().await.sendto(slice, endpoint).await },
+				timeout,
+			)
+			.map_or_else(
+				|e| isize::try_from(-i32::from(e)).unwrap(),
+				|v| v.try_into().unwrap(),
+			)
+		},
+	)
 }
 
 #[hermit_macro::system(errno)]
@@ -1175,42 +1963,15 @@ pub unsafe extern "C" fn sys_recvfrom(
 	obj.map_or_else(
 		|e| isize::try_from(-i32::from(e)).unwrap(),
 		|v| {
-			block_on(async { v.read().await.recvfrom(slice).await }, None).map_or_else(
+			let timeout = fd::socket_timeout(&v, SocketOption::RecvTimeout);
+			fd::block_on_io(async { v.read().await.recvfrom(slice).await }, timeout).map_or_else(
 				|e| isize::try_from(-i32::from(e)).unwrap(),
 				|(len, endpoint)| {
-					if !addr.is_null() && !addrlen.is_null() {
-						#[allow(unused_variables)]
-						let addrlen = unsafe { &mut *addrlen };
-
-						match endpoint {
-							#[cfg(feature = "net")]
-							Endpoint::Ip(endpoint) => match endpoint.addr {
-								IpAddress::Ipv4(_) => {
-									if *addrlen >= u32::try_from(size_of::<sockaddr_in>()).unwrap()
-									{
-										let addr = unsafe { &mut *addr.cast() };
-										*addr = sockaddr_in::from(endpoint);
-										*addrlen = size_of::<sockaddr_in>().try_into().unwrap();
-									} else {
-										return (-i32::from(Errno::Inval)).try_into().unwrap();
-									}
-								}
-								IpAddress::Ipv6(_) => {
-									if *addrlen >= u32::try_from(size_of::<sockaddr_in6>()).unwrap()
-									{
-										let addr = unsafe { &mut *addr.cast() };
-										*addr = sockaddr_in6::from(endpoint);
-										*addrlen = size_of::<sockaddr_in6>().try_into().unwrap();
-									} else {
-										return (-i32::from(Errno::Inval)).try_into().unwrap();
-									}
-								}
-							},
-							#[cfg(feature = "vsock")]
-							_ => {
-								return (-i32::from(Errno::Inval)).try_into().unwrap();
-							}
-						}
+					if !addr.is_null()
+						&& !addrlen.is_null()
+						&& let Err(e) = unsafe { write_endpoint(endpoint, addr, &mut *addrlen) }
+					{
+						return (-i32::from(e)).try_into().unwrap();
 					}
 
 					len.try_into().unwrap()
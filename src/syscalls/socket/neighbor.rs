@@ -0,0 +1,100 @@
+//! System calls, which insert and remove static entries of the neighbor
+//! caches, e.g., where ARP is restricted.
+
+use smoltcp::wire::EthernetAddress;
+
+use super::route::address;
+use super::{Af, sa_family_t};
+use crate::errno::Errno;
+use crate::executor::neighbor::Neighbor;
+use crate::executor::network::NIC;
+
+/// Static entry of a neighbor cache
+///
+/// IPv4 addresses occupy the first four bytes of `address`.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct neighbor_entry {
+	/// Address family of the address
+	pub family: sa_family_t,
+	pub _pad: [u8; 3],
+	/// Index of the interface, 0 is `eth0`
+	pub interface: u32,
+	pub address: [u8; 16],
+	/// Ethernet address of the neighbor
+	pub hwaddr: [u8; 6],
+	pub _pad2: [u8; 2],
+}
+
+/// Converts `entry` into a static entry.
+fn from_entry(entry: &neighbor_entry) -> Result<Neighbor, Errno> {
+	let family = Af::try_from(entry.family).map_err(|_| Errno::Afnosupport)?;
+	if !matches!(family, Af::Inet | Af::Inet6) {
+		return Err(Errno::Afnosupport);
+	}
+
+	let addr = address(family, &entry.address);
+	let hardware_addr = EthernetAddress(entry.hwaddr);
+	if !addr.is_unicast() || !hardware_addr.is_unicast() {
+		return Err(Errno::Inval);
+	}
+
+	Ok(Neighbor {
+		addr,
+		hardware_addr,
+		interface: entry.interface.try_into().map_err(|_| Errno::Nodev)?,
+	})
+}
+
+/// Adds the static entry `entry`, which replaces the entry of the same
+/// address on the same interface.
+///
+/// The kernel keeps the entry in the neighbor cache of the interface, so that
+/// the interface does not resolve the address through ARP or NDP. A static
+/// IPv4 address has to be in a subnet of the interface.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_neighbor_add(entry: *const neighbor_entry) -> i32 {
+	let Some(entry) = (unsafe { entry.as_ref() }) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	let result = from_entry(entry).and_then(|neighbor| {
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().map_err(|_| Errno::Netdown)?;
+		if neighbor.interface >= nic.interface_count() {
+			return Err(Errno::Nodev);
+		}
+		nic.add_neighbor(neighbor);
+		Ok(())
+	});
+	result.map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Removes the static entry of the address and the interface of `entry`.
+///
+/// The Ethernet address of `entry` is ignored. The interface keeps the
+/// address in its neighbor cache, until the entry expires after one minute.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_neighbor_del(entry: *const neighbor_entry) -> i32 {
+	let Some(entry) = (unsafe { entry.as_ref() }) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	let result = Af::try_from(entry.family)
+		.map_err(|_| Errno::Afnosupport)
+		.and_then(|family| {
+			if !matches!(family, Af::Inet | Af::Inet6) {
+				return Err(Errno::Afnosupport);
+			}
+
+			let addr = address(family, &entry.address);
+			let interface = entry.interface.try_into().map_err(|_| Errno::Srch)?;
+			let mut guard = NIC.lock();
+			let nic = guard.as_nic_mut().map_err(|_| Errno::Netdown)?;
+			nic.remove_neighbor(addr, interface).ok_or(Errno::Srch)?;
+			Ok(())
+		});
+	result.map_or_else(|e| -i32::from(e), |()| 0)
+}
@@ -0,0 +1,112 @@
+//! System call, which reads the statistics of the network interfaces.
+
+use crate::errno::Errno;
+use crate::executor::netstat::{InterfaceStatistics, Protocol};
+use crate::executor::network::NIC;
+use crate::executor::route;
+
+/// Frames and bytes of a protocol
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct netstat_protocol {
+	pub rx_packets: u64,
+	pub rx_bytes: u64,
+	pub tx_packets: u64,
+	pub tx_bytes: u64,
+}
+
+/// Statistics of a network interface
+///
+/// The totals contain the frames of all protocols including `other`.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct netstat {
+	/// Name of the interface, terminated by a null byte
+	pub name: [u8; 16],
+	pub rx_packets: u64,
+	pub rx_bytes: u64,
+	/// Received frames, whose headers are truncated
+	pub rx_errors: u64,
+	/// Received frames of protocols, which are not handled
+	pub rx_dropped: u64,
+	pub tx_packets: u64,
+	pub tx_bytes: u64,
+	/// Transmissions, which have been deferred, because the device had no free buffer
+	pub tx_busy: u64,
+	pub arp: netstat_protocol,
+	/// ICMP and ICMPv6
+	pub icmp: netstat_protocol,
+	pub tcp: netstat_protocol,
+	pub udp: netstat_protocol,
+	pub other: netstat_protocol,
+}
+
+fn protocol(statistics: &InterfaceStatistics, protocol: Protocol) -> netstat_protocol {
+	let (rx_packets, rx_bytes) = statistics.rx(protocol);
+	let (tx_packets, tx_bytes) = statistics.tx(protocol);
+	netstat_protocol {
+		rx_packets,
+		rx_bytes,
+		tx_packets,
+		tx_bytes,
+	}
+}
+
+fn to_netstat(name: &str, statistics: &InterfaceStatistics) -> netstat {
+	let mut stats = netstat {
+		rx_errors: statistics.rx_errors(),
+		rx_dropped: statistics.rx_dropped(),
+		tx_busy: statistics.tx_busy(),
+		arp: protocol(statistics, Protocol::Arp),
+		icmp: protocol(statistics, Protocol::Icmp),
+		tcp: protocol(statistics, Protocol::Tcp),
+		udp: protocol(statistics, Protocol::Udp),
+		other: protocol(statistics, Protocol::Other),
+		..Default::default()
+	};
+
+	let len = name.len().min(stats.name.len() - 1);
+	stats.name[..len].copy_from_slice(&name.as_bytes()[..len]);
+	for counters in [stats.arp, stats.icmp, stats.tcp, stats.udp, stats.other] {
+		stats.rx_packets += counters.rx_packets;
+		stats.rx_bytes += counters.rx_bytes;
+		stats.tx_packets += counters.tx_packets;
+		stats.tx_bytes += counters.tx_bytes;
+	}
+	stats
+}
+
+/// Copies the statistics of the interface `index` into `stats`.
+///
+/// The index 0 is `eth0` and the loopback interface `lo` beside the network
+/// cards follows the last network card. Returns `-ENODEV` for an index
+/// beyond the last interface, so that the interfaces can be enumerated.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_netstat(index: u32, stats: *mut netstat) -> i32 {
+	if stats.is_null() {
+		return -i32::from(Errno::Fault);
+	}
+
+	let mut guard = NIC.lock();
+	let Ok(nic) = guard.as_nic_mut() else {
+		return -i32::from(Errno::Netdown);
+	};
+	let index = usize::try_from(index).unwrap();
+	let Some(statistics) = nic.statistics(index) else {
+		return -i32::from(Errno::Nodev);
+	};
+
+	let name = if index < nic.interface_count() {
+		route::interface_name(index)
+	} else {
+		"lo".into()
+	};
+	let snapshot = to_netstat(&name, statistics);
+	drop(guard);
+
+	unsafe {
+		stats.write(snapshot);
+	}
+	0
+}
@@ -0,0 +1,80 @@
+//! System call, which captures the frames of the network interfaces.
+
+use crate::errno::Errno;
+use crate::executor::capture::{Direction, Filter};
+use crate::fd::OpenOption;
+
+/// Captures the received and the transmitted frames.
+pub const PCAP_DIR_ANY: u8 = 0;
+/// Captures only the received frames.
+pub const PCAP_DIR_IN: u8 = 1;
+/// Captures only the transmitted frames.
+pub const PCAP_DIR_OUT: u8 = 2;
+
+/// Maximum number of bytes of a frame, which are captured
+pub const PCAP_SNAPLEN_MAX: u32 = 65535;
+
+/// Filter of a packet capture, whose fields match any frame, if they are zero
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct pcap_filter {
+	/// `PCAP_DIR_ANY`, `PCAP_DIR_IN`, or `PCAP_DIR_OUT`
+	pub direction: u8,
+	/// Protocol of IPv4 or IPv6, e.g., `IPPROTO_TCP`
+	pub protocol: u8,
+	/// Ethertype, e.g., `0x0800` for IPv4, in host byte order
+	pub ethertype: u16,
+	/// Source or destination port of TCP or UDP in host byte order
+	pub port: u16,
+}
+
+/// Creates a descriptor, from which the frames of all network interfaces,
+/// which match `filter`, are read.
+///
+/// A read returns the captured frames in the format of libpcap, starting
+/// with its file header, so that the output can be analyzed with `tcpdump -r`
+/// or Wireshark. Each frame is truncated to `snaplen` bytes, or to
+/// `PCAP_SNAPLEN_MAX`, if `snaplen` is zero. `filter` may be null to capture
+/// all frames. Frames, which arrive while the buffer of the descriptor is
+/// full, are dropped. `flags` may contain `O_NONBLOCK` and `O_CLOEXEC`, which
+/// has no effect.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_pcap_open(
+	filter: *const pcap_filter,
+	snaplen: u32,
+	flags: i32,
+) -> i32 {
+	if flags & !(OpenOption::O_NONBLOCK | OpenOption::O_CLOEXEC).bits() != 0
+		|| snaplen > PCAP_SNAPLEN_MAX
+	{
+		return -i32::from(Errno::Inval);
+	}
+
+	let filter = if filter.is_null() {
+		pcap_filter::default()
+	} else {
+		unsafe { *filter }
+	};
+	let direction = match filter.direction {
+		PCAP_DIR_ANY => None,
+		PCAP_DIR_IN => Some(Direction::Received),
+		PCAP_DIR_OUT => Some(Direction::Transmitted),
+		_ => return -i32::from(Errno::Inval),
+	};
+	let filter = Filter {
+		direction,
+		ethertype: filter.ethertype,
+		protocol: filter.protocol,
+		port: filter.port,
+	};
+	let snaplen = if snaplen == 0 {
+		PCAP_SNAPLEN_MAX
+	} else {
+		snaplen
+	};
+
+	let nonblocking = flags & OpenOption::O_NONBLOCK.bits() != 0;
+	crate::fd::pcap(filter, snaplen.try_into().unwrap(), nonblocking)
+		.unwrap_or_else(|e| -i32::from(e))
+}
@@ -0,0 +1,376 @@
+//! Stub resolver for the name lookups of `sys_getaddrinfo`.
+//!
+//! Queries are sent over UDP through the DNS socket of the network stack,
+//! which tries the configured name servers in turn. A query, which has not
+//! been answered within `HERMIT_DNS_TIMEOUT` seconds (default 5), is started
+//! again, up to `HERMIT_DNS_ATTEMPTS` times in total (default 2). If all
+//! attempts time out and TCP is available, each name server is finally asked
+//! over TCP, which also passes networks that drop DNS over UDP.
+
+use alloc::vec::Vec;
+use core::str::FromStr;
+use core::time::Duration;
+
+use smoltcp::wire::{DnsQueryType, IpAddress};
+
+use crate::errno::Errno;
+use crate::executor::block_on;
+use crate::executor::network::{self, NIC, get_query_result};
+use crate::io;
+
+const DEFAULT_TIMEOUT: u64 = 5;
+const DEFAULT_ATTEMPTS: u32 = 2;
+
+fn timeout() -> Duration {
+	let secs = hermit_var!("HERMIT_DNS_TIMEOUT")
+		.and_then(|secs| u64::from_str(&secs).ok())
+		.unwrap_or(DEFAULT_TIMEOUT);
+	Duration::from_secs(secs)
+}
+
+fn attempts() -> u32 {
+	hermit_var!("HERMIT_DNS_ATTEMPTS")
+		.and_then(|attempts| u32::from_str(&attempts).ok())
+		.unwrap_or(DEFAULT_ATTEMPTS)
+		.max(1)
+}
+
+/// Looks up the addresses of `name`.
+///
+/// Returns [`Errno::Noent`], if the name does not exist or has no address of
+/// the requested type, and [`Errno::Time`], if no name server has answered.
+pub(crate) fn lookup(name: &str, query_type: DnsQueryType) -> io::Result<Vec<IpAddress>> {
+	let timeout = timeout();
+
+	for attempt in 1..=attempts() {
+		let query = {
+			let mut guard = NIC.lock();
+			let nic = guard.as_nic_mut().map_err(|_| Errno::Netdown)?;
+			let query = nic.start_query(name, query_type)?;
+			nic.poll_common(network::now());
+			query
+		};
+
+		match block_on(get_query_result(query), Some(timeout)) {
+			Err(Errno::Time) => {
+				debug!("DNS query for {name} timed out (attempt {attempt})");
+				let mut guard = NIC.lock();
+				let nic = guard.as_nic_mut().map_err(|_| Errno::Netdown)?;
+				nic.get_mut_dns_socket()?.cancel_query(query);
+			}
+			result => return result,
+		}
+	}
+
+	cfg_if::cfg_if! {
+		if #[cfg(feature = "tcp")] {
+			tcp::lookup(name, query_type, timeout)
+		} else {
+			Err(Errno::Time)
+		}
+	}
+}
+
+#[cfg(feature = "tcp")]
+mod tcp {
+	use alloc::vec::Vec;
+	use core::time::Duration;
+
+	use smoltcp::wire::{DnsQueryType, IpAddress, IpEndpoint, Ipv4Address, Ipv6Address};
+
+	use crate::errno::Errno;
+	use crate::executor::block_on;
+	use crate::executor::network::NIC;
+	use crate::fd::socket::tcp;
+	use crate::fd::{Endpoint, ObjectInterface};
+	use crate::io;
+	use crate::syscalls::socket::Af;
+
+	const DNS_PORT: u16 = 53;
+
+	/// Recursion desired
+	const FLAG_RD: u16 = 0x0100;
+	/// The message is a response.
+	const FLAG_QR: u16 = 0x8000;
+
+	const RCODE_NXDOMAIN: u16 = 3;
+
+	const TYPE_A: u16 = 1;
+	const TYPE_AAAA: u16 = 28;
+	const CLASS_IN: u16 = 1;
+
+	fn record_type(query_type: DnsQueryType) -> io::Result<u16> {
+		match query_type {
+			DnsQueryType::A => Ok(TYPE_A),
+			DnsQueryType::Aaaa => Ok(TYPE_AAAA),
+			_ => Err(Errno::Inval),
+		}
+	}
+
+	/// Builds a query for `name` including the length prefix of DNS over TCP.
+	fn build_query(id: u16, name: &str, rtype: u16) -> io::Result<Vec<u8>> {
+		let mut message = vec![0, 0];
+		message.extend_from_slice(&id.to_be_bytes());
+		message.extend_from_slice(&FLAG_RD.to_be_bytes());
+		// one question, no answer, authority, or additional records
+		message.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+
+		for label in name.trim_end_matches('.').split('.') {
+			let len = u8::try_from(label.len()).map_err(|_| Errno::Inval)?;
+			if len == 0 || len > 63 {
+				return Err(Errno::Inval);
+			}
+			message.push(len);
+			message.extend_from_slice(label.as_bytes());
+		}
+		message.push(0);
+		if message.len() - 14 > 255 {
+			return Err(Errno::Inval);
+		}
+
+		message.extend_from_slice(&rtype.to_be_bytes());
+		message.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+		let len = u16::try_from(message.len() - 2).unwrap();
+		message[..2].copy_from_slice(&len.to_be_bytes());
+		Ok(message)
+	}
+
+	fn read_u16(message: &[u8], pos: usize) -> io::Result<u16> {
+		let bytes = message.get(pos..pos + 2).ok_or(Errno::Io)?;
+		Ok(u16::from_be_bytes(bytes.try_into().unwrap()))
+	}
+
+	/// Returns the position after the (possibly compressed) name at `pos`.
+	fn skip_name(message: &[u8], mut pos: usize) -> io::Result<usize> {
+		loop {
+			let len = *message.get(pos).ok_or(Errno::Io)?;
+			match len {
+				0 => return Ok(pos + 1),
+				len if len & 0xc0 == 0xc0 => return Ok(pos + 2),
+				len => pos += 1 + usize::from(len),
+			}
+		}
+	}
+
+	/// Extracts the addresses of type `rtype` from the response `message`.
+	fn parse_response(message: &[u8], id: u16, rtype: u16) -> io::Result<Vec<IpAddress>> {
+		let flags = read_u16(message, 2)?;
+		if read_u16(message, 0)? != id || flags & FLAG_QR == 0 {
+			return Err(Errno::Io);
+		}
+		match flags & 0xf {
+			0 => {}
+			RCODE_NXDOMAIN => return Err(Errno::Noent),
+			rcode => {
+				debug!("DNS server responded with error {rcode}");
+				return Err(Errno::Io);
+			}
+		}
+
+		let questions = read_u16(message, 4)?;
+		let answers = read_u16(message, 6)?;
+		let mut pos = 12;
+		for _ in 0..questions {
+			pos = skip_name(message, pos)? + 4;
+		}
+
+		let mut addrs = Vec::new();
+		for _ in 0..answers {
+			pos = skip_name(message, pos)?;
+			let record_type = read_u16(message, pos)?;
+			let class = read_u16(message, pos + 2)?;
+			let len = usize::from(read_u16(message, pos + 8)?);
+			pos += 10;
+			let data = message.get(pos..pos + len).ok_or(Errno::Io)?;
+			pos += len;
+
+			// Other records like the CNAMEs of the name are skipped.
+			if record_type != rtype || class != CLASS_IN {
+				continue;
+			}
+			let addr = if rtype == TYPE_A {
+				<[u8; 4]>::try_from(data).map(|octets| IpAddress::from(Ipv4Address::from(octets)))
+			} else {
+				<[u8; 16]>::try_from(data).map(|octets| IpAddress::from(Ipv6Address::from(octets)))
+			};
+			if let Ok(addr) = addr {
+				addrs.push(addr);
+			}
+		}
+
+		if addrs.is_empty() {
+			return Err(Errno::Noent);
+		}
+		Ok(addrs)
+	}
+
+	async fn read_exact(socket: &tcp::Socket, mut buffer: &mut [u8]) -> io::Result<()> {
+		while !buffer.is_empty() {
+			let n = socket.read(buffer).await?;
+			if n == 0 {
+				return Err(Errno::Io);
+			}
+			buffer = &mut buffer[n..];
+		}
+		Ok(())
+	}
+
+	async fn exchange(
+		socket: &mut tcp::Socket,
+		server: IpAddress,
+		query: &[u8],
+	) -> io::Result<Vec<u8>> {
+		socket
+			.connect(Endpoint::Ip(IpEndpoint::new(server, DNS_PORT)))
+			.await?;
+		if socket.write(query).await? != query.len() {
+			return Err(Errno::Io);
+		}
+
+		let mut len = [0u8; 2];
+		read_exact(socket, &mut len).await?;
+		let mut response = vec![0; usize::from(u16::from_be_bytes(len))];
+		read_exact(socket, &mut response).await?;
+		Ok(response)
+	}
+
+	/// Queries the name servers one after another over TCP.
+	pub(super) fn lookup(
+		name: &str,
+		query_type: DnsQueryType,
+		timeout: Duration,
+	) -> io::Result<Vec<IpAddress>> {
+		let rtype = record_type(query_type)?;
+		let servers = {
+			let mut guard = NIC.lock();
+			let nic = guard.as_nic_mut().map_err(|_| Errno::Netdown)?;
+			nic.dns_servers().to_vec()
+		};
+
+		let mut result = Err(Errno::Time);
+		for server in servers {
+			// The identifier only has to be unique among the queries on this connection.
+			let id = crate::arch::kernel::systemtime::now_micros() as u16;
+			let query = build_query(id, name, rtype)?;
+
+			let handle = NIC
+				.lock()
+				.as_nic_mut()
+				.map_err(|_| Errno::Netdown)?
+				.create_tcp_handle()
+				.map_err(|()| Errno::Nobufs)?;
+			let af = match server {
+				IpAddress::Ipv4(_) => Af::Inet,
+				IpAddress::Ipv6(_) => Af::Inet6,
+			};
+			let mut socket = tcp::Socket::new(handle, af);
+
+			debug!("Query {server} for {name} over TCP");
+			result = block_on(exchange(&mut socket, server, &query), Some(timeout))
+				.and_then(|response| parse_response(&response, id, rtype));
+			match result {
+				Ok(_) | Err(Errno::Noent) => return result,
+				Err(err) => debug!("DNS query over TCP to {server} failed: {err:?}"),
+			}
+		}
+		result
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		const ID: u16 = 0x1234;
+
+		/// Returns a response to the query for `example.com`, which has the
+		/// response code `rcode` and consists of the `answers`.
+		fn response(rcode: u16, answers: &[(u16, &[u8])]) -> Vec<u8> {
+			let mut message = build_query(ID, "example.com", TYPE_A).unwrap()[2..].to_vec();
+			let flags = FLAG_QR | FLAG_RD | 0x80 | rcode;
+			message[2..4].copy_from_slice(&flags.to_be_bytes());
+			let count = u16::try_from(answers.len()).unwrap();
+			message[6..8].copy_from_slice(&count.to_be_bytes());
+
+			for (rtype, data) in answers {
+				// The name refers to the question at offset 12.
+				message.extend_from_slice(&[0xc0, 12]);
+				message.extend_from_slice(&rtype.to_be_bytes());
+				message.extend_from_slice(&CLASS_IN.to_be_bytes());
+				message.extend_from_slice(&300u32.to_be_bytes());
+				let len = u16::try_from(data.len()).unwrap();
+				message.extend_from_slice(&len.to_be_bytes());
+				message.extend_from_slice(data);
+			}
+			message
+		}
+
+		#[cfg(target_os = "none")]
+		#[test_case]
+		fn test_dns_build_query() {
+			let query = build_query(ID, "example.com.", TYPE_AAAA).unwrap();
+			let mut expected = vec![0, 29, 0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+			expected.extend_from_slice(b"\x07example\x03com\x00");
+			expected.extend_from_slice(&[0, 28, 0, 1]);
+			assert_eq!(query, expected);
+
+			let label = "a".repeat(64);
+			for name in ["a..b", "", label.as_str()] {
+				assert_eq!(build_query(ID, name, TYPE_A), Err(Errno::Inval), "{name}");
+			}
+		}
+
+		#[cfg(target_os = "none")]
+		#[test_case]
+		fn test_dns_parse_response() {
+			// A CNAME, which points to the name of the question
+			const CNAME: u16 = 5;
+			let message = response(
+				0,
+				&[
+					(CNAME, &[0xc0, 12]),
+					(TYPE_A, &[93, 184, 216, 34]),
+					(TYPE_AAAA, &[0; 16]),
+					(TYPE_A, &[10, 0, 0, 1]),
+				],
+			);
+			assert_eq!(
+				parse_response(&message, ID, TYPE_A).unwrap(),
+				[
+					IpAddress::from(Ipv4Address::new(93, 184, 216, 34)),
+					IpAddress::from(Ipv4Address::new(10, 0, 0, 1)),
+				]
+			);
+			assert_eq!(
+				parse_response(&message, ID, TYPE_AAAA).unwrap(),
+				[IpAddress::from(Ipv6Address::UNSPECIFIED)]
+			);
+		}
+
+		#[cfg(target_os = "none")]
+		#[test_case]
+		fn test_dns_parse_error_response() {
+			let message = response(0, &[(TYPE_A, &[10, 0, 0, 1])]);
+			assert_eq!(parse_response(&message, ID + 1, TYPE_A), Err(Errno::Io));
+			assert_eq!(
+				parse_response(&message[..message.len() - 1], ID, TYPE_A),
+				Err(Errno::Io)
+			);
+			// A query is not a response.
+			let query = build_query(ID, "example.com", TYPE_A).unwrap();
+			assert_eq!(parse_response(&query[2..], ID, TYPE_A), Err(Errno::Io));
+
+			assert_eq!(
+				parse_response(&response(RCODE_NXDOMAIN, &[]), ID, TYPE_A),
+				Err(Errno::Noent)
+			);
+			// Server failure
+			assert_eq!(
+				parse_response(&response(2, &[]), ID, TYPE_A),
+				Err(Errno::Io)
+			);
+			// The name exists, but has no address of the type.
+			assert_eq!(parse_response(&message, ID, TYPE_AAAA), Err(Errno::Noent));
+		}
+	}
+}
@@ -0,0 +1,210 @@
+//! System calls, which inspect and change the routing table.
+
+use core::slice;
+
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv6Address};
+
+use super::{Af, sa_family_t};
+use crate::errno::Errno;
+use crate::executor::network::NIC;
+use crate::executor::route::{self, Route};
+
+/// Entry of the routing table
+///
+/// IPv4 addresses occupy the first four bytes of `destination` and `gateway`.
+/// The subnets of the interfaces have the unspecified address as gateway.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct route_entry {
+	/// Address family of the destination and the gateway
+	pub family: sa_family_t,
+	/// Prefix length of the destination
+	pub prefix_len: u8,
+	pub _pad: u16,
+	/// Index of the interface, 0 is `eth0`
+	pub interface: u32,
+	pub destination: [u8; 16],
+	pub gateway: [u8; 16],
+}
+
+pub(super) fn address(family: Af, octets: &[u8; 16]) -> IpAddress {
+	match family {
+		Af::Inet => Ipv4Address::new(octets[0], octets[1], octets[2], octets[3]).into(),
+		_ => Ipv6Address::from(*octets).into(),
+	}
+}
+
+pub(super) fn octets(addr: IpAddress) -> [u8; 16] {
+	let mut octets = [0; 16];
+	match addr {
+		IpAddress::Ipv4(addr) => octets[..4].copy_from_slice(&addr.octets()),
+		IpAddress::Ipv6(addr) => octets = addr.octets(),
+	}
+	octets
+}
+
+/// Returns the address family and the destination of `entry`.
+fn destination(entry: &route_entry) -> Result<(Af, IpCidr), Errno> {
+	let family = Af::try_from(entry.family).map_err(|_| Errno::Afnosupport)?;
+	let max_len = match family {
+		Af::Inet => 32,
+		Af::Inet6 => 128,
+		_ => return Err(Errno::Afnosupport),
+	};
+	if entry.prefix_len > max_len {
+		return Err(Errno::Inval);
+	}
+
+	let cidr = IpCidr::new(address(family, &entry.destination), entry.prefix_len);
+	Ok((family, route::network(cidr)))
+}
+
+/// Converts `entry` into a route through a gateway.
+fn from_entry(entry: &route_entry) -> Result<Route, Errno> {
+	let (family, destination) = destination(entry)?;
+	let gateway = address(family, &entry.gateway);
+	if gateway.is_unspecified() {
+		return Err(Errno::Inval);
+	}
+
+	Ok(Route {
+		destination,
+		gateway: Some(gateway),
+		interface: entry.interface.try_into().map_err(|_| Errno::Nodev)?,
+	})
+}
+
+fn to_entry(route: &Route) -> route_entry {
+	let destination = route.destination.address();
+	route_entry {
+		family: match destination {
+			IpAddress::Ipv4(_) => Af::Inet.into(),
+			IpAddress::Ipv6(_) => Af::Inet6.into(),
+		},
+		prefix_len: route.destination.prefix_len(),
+		interface: route.interface.try_into().unwrap(),
+		destination: octets(destination),
+		gateway: route.gateway.map_or([0; 16], octets),
+		..Default::default()
+	}
+}
+
+/// Adds the route `entry` through a gateway, which replaces the route to the
+/// same destination.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_route_add(entry: *const route_entry) -> i32 {
+	let Some(entry) = (unsafe { entry.as_ref() }) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	let result = from_entry(entry).and_then(|route| {
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().map_err(|_| Errno::Netdown)?;
+		if route.interface >= nic.interface_count() {
+			return Err(Errno::Nodev);
+		}
+		nic.add_route(route);
+		Ok(())
+	});
+	result.map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Removes the route to the destination of `entry`.
+///
+/// The gateway and the interface of `entry` are ignored.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_route_del(entry: *const route_entry) -> i32 {
+	let Some(entry) = (unsafe { entry.as_ref() }) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	let result = destination(entry).and_then(|(_, destination)| {
+		let mut guard = NIC.lock();
+		let nic = guard.as_nic_mut().map_err(|_| Errno::Netdown)?;
+		nic.remove_route(destination).ok_or(Errno::Srch)?;
+		Ok(())
+	});
+	result.map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Replaces the default route of the address family `family` by a route
+/// through `gateway` on the interface `interface`, e.g., to fail over to
+/// another router.
+///
+/// `gateway` points to an address of 4 bytes for `AF_INET` and of 16 bytes
+/// for `AF_INET6`. If `gateway` is null, the default route is removed.
+/// A later lease of DHCP or advertisement of a router sets its gateway again.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_gateway_set(
+	family: sa_family_t,
+	gateway: *const u8,
+	interface: u32,
+) -> i32 {
+	let Ok(family) = Af::try_from(family) else {
+		return -i32::from(Errno::Afnosupport);
+	};
+	let len = match family {
+		Af::Inet => 4,
+		Af::Inet6 => 16,
+		_ => return -i32::from(Errno::Afnosupport),
+	};
+
+	let gateway = (!gateway.is_null()).then(|| {
+		let mut octets = [0; 16];
+		octets[..len].copy_from_slice(unsafe { slice::from_raw_parts(gateway, len) });
+		address(family, &octets)
+	});
+	if gateway.is_some_and(|gateway| gateway.is_unspecified()) {
+		return -i32::from(Errno::Inval);
+	}
+	let destination = route::default_destination(address(family, &[0; 16]));
+
+	let mut guard = NIC.lock();
+	let result = guard
+		.as_nic_mut()
+		.map_err(|_| Errno::Netdown)
+		.and_then(|nic| match gateway {
+			Some(gateway) => {
+				let interface = usize::try_from(interface).map_err(|_| Errno::Nodev)?;
+				if interface >= nic.interface_count() {
+					return Err(Errno::Nodev);
+				}
+				nic.add_route(Route::default_via(gateway, interface));
+				Ok(())
+			}
+			None => nic.remove_route(destination).map(drop).ok_or(Errno::Srch),
+		});
+	result.map_or_else(|e| -i32::from(e), |()| 0)
+}
+
+/// Copies the routing table into `buf`, which has room for `len` entries.
+///
+/// The subnets of the interfaces precede the routes through gateways.
+/// Returns the number of all entries, which may be larger than `len`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_route_list(buf: *mut route_entry, len: usize) -> isize {
+	if buf.is_null() && len != 0 {
+		return -isize::try_from(i32::from(Errno::Inval)).unwrap();
+	}
+
+	let mut guard = NIC.lock();
+	let Ok(nic) = guard.as_nic_mut() else {
+		return -isize::try_from(i32::from(Errno::Netdown)).unwrap();
+	};
+	let routes = nic.routes();
+	drop(guard);
+
+	let slice = if len == 0 {
+		&mut []
+	} else {
+		unsafe { core::slice::from_raw_parts_mut(buf, len) }
+	};
+	for (entry, route) in slice.iter_mut().zip(&routes) {
+		*entry = to_entry(route);
+	}
+	routes.len().try_into().unwrap()
+}
@@ -1,4 +1,10 @@
+use core::ffi::{c_long, c_uint, c_ulong, c_ushort};
+
 use crate::arch::mm::paging::{BasePageSize, PageSize};
+use crate::errno::Errno;
+use crate::mm::physicalmem;
+use crate::panic::PanicAction;
+use crate::scheduler;
 
 /// Returns the base page size, in bytes, of the current system.
 #[hermit_macro::system]
@@ -6,3 +12,262 @@ use crate::arch::mm::paging::{BasePageSize, PageSize};
 pub extern "C" fn sys_getpagesize() -> i32 {
 	BasePageSize::SIZE.try_into().unwrap()
 }
+
+/// Number of fractional bits of the load averages in [`sysinfo`]
+const SI_LOAD_SHIFT: u32 = 16;
+
+/// Statistics of the system, which match Linux' `struct sysinfo`
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct sysinfo {
+	/// Seconds since boot
+	pub uptime: c_long,
+	/// Load averages over 1, 5, and 15 minutes with [`SI_LOAD_SHIFT`] fractional bits
+	pub loads: [c_ulong; 3],
+	pub totalram: c_ulong,
+	pub freeram: c_ulong,
+	pub sharedram: c_ulong,
+	pub bufferram: c_ulong,
+	pub totalswap: c_ulong,
+	pub freeswap: c_ulong,
+	/// Number of tasks
+	pub procs: c_ushort,
+	pub pad: c_ushort,
+	pub totalhigh: c_ulong,
+	pub freehigh: c_ulong,
+	/// Size of the unit of the memory sizes in bytes
+	pub mem_unit: c_uint,
+}
+
+/// Fills `info` with the uptime, the load averages, the memory usage, and the
+/// number of tasks.
+///
+/// The load averages are the sum of the load averages of all cores.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sysinfo(info: *mut sysinfo) -> i32 {
+	if info.is_null() {
+		return -i32::from(Errno::Fault);
+	}
+
+	let (loads, _) = scheduler::load::total();
+	let info_data = sysinfo {
+		uptime: (crate::arch::processor::get_timer_ticks() / 1_000_000)
+			.try_into()
+			.unwrap(),
+		loads: loads.map(|load| load << (SI_LOAD_SHIFT - scheduler::load::FSHIFT)),
+		totalram: physicalmem::total_memory_size().try_into().unwrap(),
+		freeram: physicalmem::free_memory_size().try_into().unwrap(),
+		procs: scheduler::task_count().try_into().unwrap_or(c_ushort::MAX),
+		mem_unit: 1,
+		..Default::default()
+	};
+	unsafe {
+		info.write(info_data);
+	}
+
+	0
+}
+
+/// Selects the scheduler counters of [`kstats`]
+pub const KSTATS_SCHED: u32 = 1 << 0;
+/// Selects the network counters of [`kstats`]
+pub const KSTATS_NET: u32 = 1 << 1;
+/// Selects the block device counters of [`kstats`]
+pub const KSTATS_BLOCK: u32 = 1 << 2;
+/// Selects the memory counters of [`kstats`]
+pub const KSTATS_MEM: u32 = 1 << 3;
+/// Selects all counters of [`kstats`]
+pub const KSTATS_ALL: u32 = KSTATS_SCHED | KSTATS_NET | KSTATS_BLOCK | KSTATS_MEM;
+
+/// Snapshot of the kernel counters taken by [`sys_kstats`]
+///
+/// Counters of groups, which have not been selected, or of subsystems, which
+/// have not been compiled in, are zero.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct kstats {
+	/// Microseconds since boot, when the snapshot has been taken
+	pub timestamp: u64,
+	/// Groups, which have been filled in
+	pub groups: u32,
+	pub _pad: u32,
+	/// Switches between tasks on all cores
+	pub context_switches: u64,
+	pub tasks: u64,
+	pub runnable_tasks: u64,
+	/// Frames received by the network device
+	pub rx_packets: u64,
+	pub rx_bytes: u64,
+	/// Frames transmitted by the network device
+	pub tx_packets: u64,
+	pub tx_bytes: u64,
+	/// Requests completed by the physical block devices
+	pub block_reads: u64,
+	pub block_read_bytes: u64,
+	pub block_writes: u64,
+	pub block_written_bytes: u64,
+	pub block_flushes: u64,
+	/// Physical memory in bytes
+	pub total_memory: u64,
+	pub free_memory: u64,
+}
+
+/// Takes a snapshot of the kernel counters selected by `groups`.
+///
+/// The counters are read at once with interrupts disabled, so that a
+/// benchmark can diff two snapshots without the counters of the current core
+/// advancing between the reads. Returns `-EINVAL` for unknown groups.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_kstats(groups: u32, stats: *mut kstats) -> i32 {
+	if stats.is_null() {
+		return -i32::from(Errno::Fault);
+	}
+	if groups & !KSTATS_ALL != 0 {
+		return -i32::from(Errno::Inval);
+	}
+
+	let snapshot = hermit_sync::without_interrupts(|| {
+		let mut snapshot = kstats {
+			timestamp: crate::arch::processor::get_timer_ticks(),
+			..Default::default()
+		};
+
+		if groups & KSTATS_SCHED != 0 {
+			snapshot.groups |= KSTATS_SCHED;
+			snapshot.context_switches = scheduler::context_switches();
+			snapshot.tasks = scheduler::task_count().into();
+			snapshot.runnable_tasks = scheduler::load::total().1.into();
+		}
+
+		#[cfg(feature = "net")]
+		if groups & KSTATS_NET != 0 {
+			use crate::drivers::net::STATISTICS;
+
+			snapshot.groups |= KSTATS_NET;
+			(snapshot.rx_packets, snapshot.rx_bytes) = STATISTICS.rx();
+			(snapshot.tx_packets, snapshot.tx_bytes) = STATISTICS.tx();
+		}
+
+		#[cfg(feature = "block")]
+		if groups & KSTATS_BLOCK != 0 {
+			use crate::drivers::block::STATISTICS;
+
+			snapshot.groups |= KSTATS_BLOCK;
+			(snapshot.block_reads, snapshot.block_read_bytes) = STATISTICS.reads();
+			(snapshot.block_writes, snapshot.block_written_bytes) = STATISTICS.writes();
+			snapshot.block_flushes = STATISTICS.flushes();
+		}
+
+		if groups & KSTATS_MEM != 0 {
+			snapshot.groups |= KSTATS_MEM;
+			snapshot.total_memory = physicalmem::total_memory_size().try_into().unwrap();
+			snapshot.free_memory = physicalmem::free_memory_size().try_into().unwrap();
+		}
+
+		snapshot
+	});
+	unsafe {
+		stats.write(snapshot);
+	}
+
+	0
+}
+
+/// Reads all messages of the kernel log like `SYSLOG_ACTION_READ_ALL` of Linux' `syslog`
+pub const KLOG_ACTION_READ_ALL: i32 = 3;
+/// Reads and clears all messages of the kernel log
+pub const KLOG_ACTION_READ_CLEAR: i32 = 4;
+/// Clears the kernel log
+pub const KLOG_ACTION_CLEAR: i32 = 5;
+/// Returns the size of the kernel log buffers
+pub const KLOG_ACTION_SIZE_BUFFER: i32 = 10;
+
+#[cfg(not(feature = "klog"))]
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_klog(_action: i32, _buf: *mut u8, _len: usize) -> isize {
+	error!("Please enable the feature 'klog' to read the kernel log.");
+	-isize::try_from(i32::from(Errno::Nosys)).unwrap()
+}
+
+/// Accesses the buffered kernel messages of all cores like Linux' `syslog`.
+///
+/// The read actions copy the newest messages, which fit into `buf`, as text
+/// with one message per line and return the number of bytes. The messages of
+/// the cores are ordered by their timestamps.
+#[cfg(feature = "klog")]
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_klog(action: i32, buf: *mut u8, len: usize) -> isize {
+	use crate::logging::klog;
+
+	match action {
+		KLOG_ACTION_READ_ALL | KLOG_ACTION_READ_CLEAR => {
+			if buf.is_null() && len != 0 {
+				return -isize::try_from(i32::from(Errno::Inval)).unwrap();
+			}
+
+			let text = klog::merged();
+			if action == KLOG_ACTION_READ_CLEAR {
+				klog::clear();
+			}
+
+			// Only whole lines are copied.
+			let text = text.as_bytes();
+			let mut start = text.len().saturating_sub(len);
+			if start > 0 {
+				start = text[start - 1..]
+					.iter()
+					.position(|&byte| byte == b'\n')
+					.map_or(text.len(), |i| start + i);
+			}
+			let text = &text[start..];
+			if !text.is_empty() {
+				let slice = unsafe { core::slice::from_raw_parts_mut(buf, text.len()) };
+				slice.copy_from_slice(text);
+			}
+			text.len().try_into().unwrap()
+		}
+		KLOG_ACTION_CLEAR => {
+			klog::clear();
+			0
+		}
+		KLOG_ACTION_SIZE_BUFFER => klog::capacity().try_into().unwrap(),
+		_ => -isize::try_from(i32::from(Errno::Inval)).unwrap(),
+	}
+}
+
+/// Selects the behavior after a panic.
+///
+/// `action` is 0 to exit with the error code `value`, 1 to halt, or 2 to
+/// reboot after `value` seconds. Returns `-EINVAL` for other actions or a
+/// negative number of seconds.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_set_panic_action(action: i32, value: i32) -> i32 {
+	let Ok(Ok(action)) = u8::try_from(action).map(PanicAction::try_from) else {
+		return -i32::from(Errno::Inval);
+	};
+	if action == PanicAction::Reboot && value < 0 {
+		return -i32::from(Errno::Inval);
+	}
+
+	crate::panic::set(action, value);
+	0
+}
+
+/// Registers `func`, which is called with `arg` on an orderly shutdown.
+///
+/// The hooks run in the reverse order of their registration and share the
+/// timeout given by `shutdown_timeout=<seconds>`. Returns `-ENOMEM`, if too
+/// many hooks have been registered, or `-EINVAL`, if the shutdown has
+/// already started.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_register_shutdown_hook(func: extern "C" fn(usize), arg: usize) -> i32 {
+	crate::shutdown::register(func, arg).map_or_else(|e| -i32::from(e), |()| 0)
+}
@@ -1,4 +1,5 @@
 use alloc::collections::BTreeMap;
+use core::slice;
 
 use hermit_sync::InterruptTicketMutex;
 
@@ -7,13 +8,33 @@ use crate::arch::processor::{get_frequency, get_timestamp};
 use crate::config::USER_STACK_SIZE;
 use crate::errno::Errno;
 use crate::scheduler::PerCoreSchedulerExt;
-use crate::scheduler::task::{Priority, TaskHandle, TaskId};
+use crate::scheduler::affinity::{self, CpuSet};
+use crate::scheduler::realtime::{self, Policy};
+use crate::scheduler::supervisor::{self, RestartPolicy};
+use crate::scheduler::task::{LOW_PRIO, NORMAL_PRIO, Priority, TaskHandle, TaskId};
 use crate::time::timespec;
 use crate::{arch, scheduler};
 
 #[cfg(feature = "newlib")]
 pub type SignalHandler = extern "C" fn(i32);
 pub type Tid = i32;
+pub type Tid64 = i64;
+
+/// Returns `id` as [`Tid`] or `-EOVERFLOW`, if it does not fit.
+///
+/// The system calls with a [`Tid`] only spawn and reap tasks up to
+/// [`TaskId::MAX_TID32`], so that their IDs fit.
+fn tid(id: TaskId) -> Tid {
+	Tid::try_from(id).unwrap_or(-i32::from(Errno::Overflow))
+}
+
+/// Returns `id` as [`Tid64`] or `-EOVERFLOW`, if it does not fit.
+fn tid64(id: TaskId) -> Tid64 {
+	Tid64::try_from(id).unwrap_or(-i64::from(i32::from(Errno::Overflow)))
+}
+
+/// Return immediately, if no task has finished.
+const WNOHANG: i32 = 1;
 
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
@@ -27,7 +48,7 @@ pub extern "C" fn sys_getpid() -> Tid {
 pub unsafe extern "C" fn sys_getprio(id: *const Tid) -> i32 {
 	let task = core_scheduler().get_current_task_handle();
 
-	if id.is_null() || unsafe { *id } == task.get_id().into() {
+	if id.is_null() || TaskId::try_from(unsafe { *id }) == Ok(task.get_id()) {
 		i32::from(task.get_priority().into())
 	} else {
 		-i32::from(Errno::Inval)
@@ -43,7 +64,7 @@ pub unsafe extern "C" fn sys_setprio(_id: *const Tid, _prio: i32) -> i32 {
 
 fn exit(arg: i32) -> ! {
 	debug!("Exit program with error code {arg}!");
-	super::shutdown(arg)
+	crate::shutdown::orderly(arg)
 }
 
 #[hermit_macro::system]
@@ -62,7 +83,8 @@ pub extern "C" fn sys_thread_exit(status: i32) -> ! {
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub extern "C" fn sys_abort() -> ! {
-	exit(-1)
+	debug!("Abort program!");
+	super::shutdown(-1)
 }
 
 pub(super) fn usleep(usecs: u64) {
@@ -121,11 +143,14 @@ pub unsafe extern "C" fn sys_nanosleep(rqtp: *const timespec, _rmtp: *mut timesp
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn sys_clone(id: *mut Tid, func: extern "C" fn(usize), arg: usize) -> i32 {
-	let task_id = core_scheduler().clone(func, arg);
+	let task_id = match core_scheduler().clone(func, arg, TaskId::MAX_TID32) {
+		Ok(task_id) => task_id,
+		Err(e) => return -i32::from(e),
+	};
 
 	if !id.is_null() {
 		unsafe {
-			*id = task_id.into();
+			*id = tid(task_id);
 		}
 	}
 
@@ -135,7 +160,71 @@ pub unsafe extern "C" fn sys_clone(id: *mut Tid, func: extern "C" fn(usize), arg
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub extern "C" fn sys_yield() {
-	core_scheduler().reschedule();
+	// The task has been asked to move, if its core is not part of its mask anymore.
+	affinity::migrate();
+	core_scheduler().yield_now();
+}
+
+/// Returns the task `id`, where 0 is the current task.
+fn affinity_task(id: Tid) -> Result<TaskId, Errno> {
+	if id == 0 {
+		Ok(core_scheduler().get_current_task_id())
+	} else {
+		TaskId::try_from(id).map_err(|_| Errno::Srch)
+	}
+}
+
+/// Restricts the task `id` to the cores of the bitmask `mask` of `cpusetsize`
+/// bytes in the format of `cpu_set_t`.
+///
+/// `id` 0 is the current task, which moves to a core of the mask at once.
+/// Another task moves, when it yields. Tasks, which are spawned afterwards,
+/// inherit the mask. Returns `-EINVAL`, if the mask contains no core of the
+/// system, and `-ESRCH`, if the task does not exist.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sched_setaffinity(id: Tid, cpusetsize: usize, mask: *const u8) -> i32 {
+	if mask.is_null() {
+		return -i32::from(Errno::Fault);
+	}
+
+	let set = CpuSet::from_bytes(unsafe { slice::from_raw_parts(mask, cpusetsize) });
+	let task = match affinity_task(id) {
+		Ok(task) => task,
+		Err(e) => return -i32::from(e),
+	};
+	if let Err(e) = affinity::set(task, set) {
+		return -i32::from(e);
+	}
+	if task == core_scheduler().get_current_task_id() {
+		affinity::migrate();
+	}
+
+	0
+}
+
+/// Writes the mask of the cores, on which the task `id` may run, to the
+/// bitmask `mask` of `cpusetsize` bytes in the format of `cpu_set_t`.
+///
+/// `id` 0 is the current task. Returns `-EINVAL`, if the mask does not hold
+/// all cores of the system.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sched_getaffinity(id: Tid, cpusetsize: usize, mask: *mut u8) -> i32 {
+	if mask.is_null() {
+		return -i32::from(Errno::Fault);
+	}
+	if cpusetsize * 8 < usize::try_from(arch::get_processor_count()).unwrap() {
+		return -i32::from(Errno::Inval);
+	}
+
+	match affinity_task(id).and_then(affinity::query) {
+		Ok(set) => {
+			set.write_bytes(unsafe { slice::from_raw_parts_mut(mask, cpusetsize) });
+			0
+		}
+		Err(e) => -i32::from(e),
+	}
 }
 
 #[cfg(feature = "newlib")]
@@ -163,7 +252,19 @@ pub unsafe extern "C" fn sys_spawn2(
 	stack_size: usize,
 	selector: isize,
 ) -> Tid {
-	unsafe { scheduler::spawn(func, arg, Priority::from(prio), stack_size, selector).into() }
+	match unsafe {
+		scheduler::spawn(
+			func,
+			arg,
+			Priority::from(prio),
+			stack_size,
+			selector,
+			TaskId::MAX_TID32,
+		)
+	} {
+		Ok(id) => tid(id),
+		Err(e) => -i32::from(e),
+	}
 }
 
 #[hermit_macro::system]
@@ -175,8 +276,18 @@ pub unsafe extern "C" fn sys_spawn(
 	prio: u8,
 	selector: isize,
 ) -> i32 {
-	let new_id = unsafe {
-		scheduler::spawn(func, arg, Priority::from(prio), USER_STACK_SIZE, selector).into()
+	let new_id = match unsafe {
+		scheduler::spawn(
+			func,
+			arg,
+			Priority::from(prio),
+			USER_STACK_SIZE,
+			selector,
+			TaskId::MAX_TID32,
+		)
+	} {
+		Ok(id) => tid(id),
+		Err(e) => return -i32::from(e),
 	};
 
 	if !id.is_null() {
@@ -188,15 +299,205 @@ pub unsafe extern "C" fn sys_spawn(
 	0
 }
 
+/// Spawns a task like `sys_spawn2` and stores its 64-bit ID in `id`.
+///
+/// The 32-bit IDs of `sys_spawn` and `sys_spawn2` are used up after 2^31
+/// tasks, since the IDs are never reused, but the 64-bit IDs are not.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_spawn64(
+	id: *mut Tid64,
+	func: unsafe extern "C" fn(usize),
+	arg: usize,
+	prio: u8,
+	stack_size: usize,
+	selector: isize,
+) -> i32 {
+	let new_id = match unsafe {
+		scheduler::spawn(
+			func,
+			arg,
+			Priority::from(prio),
+			stack_size,
+			selector,
+			TaskId::MAX,
+		)
+	} {
+		Ok(id) => tid64(id),
+		Err(e) => return -i32::from(e),
+	};
+
+	if let Some(id) = unsafe { id.as_mut() } {
+		*id = new_id;
+	}
+
+	0
+}
+
+/// Spawns a task, which is restarted according to `policy`, if it finishes.
+///
+/// Returns the ID of the service, which identifies the task across restarts.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_spawn_supervised(
+	func: unsafe extern "C" fn(usize),
+	arg: usize,
+	prio: u8,
+	stack_size: usize,
+	policy: *const RestartPolicy,
+) -> i32 {
+	let Some(policy) = (unsafe { policy.as_ref() }) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	match unsafe { supervisor::spawn(func, arg, Priority::from(prio), stack_size, policy) } {
+		Ok(id) => i32::try_from(id).unwrap(),
+		Err(e) => -i32::from(e),
+	}
+}
+
+/// Returns how often the supervised task `service` has been restarted.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_supervised_restarts(service: i32) -> i32 {
+	let Ok(service) = usize::try_from(service) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	match supervisor::restarts(service) {
+		Ok(restarts) => i32::try_from(restarts).unwrap_or(i32::MAX),
+		Err(e) => -i32::from(e),
+	}
+}
+
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub extern "C" fn sys_join(id: Tid) -> i32 {
-	match scheduler::join(TaskId::from(id)) {
+	let Ok(id) = TaskId::try_from(id) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	match scheduler::join(id) {
 		Ok(()) => 0,
 		_ => -i32::from(Errno::Inval),
 	}
 }
 
+/// Waits for the task with the 64-bit ID `id` like `sys_join`.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_join64(id: Tid64) -> i32 {
+	let Ok(id) = TaskId::try_from(id) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	match scheduler::join(id) {
+		Ok(()) => 0,
+		_ => -i32::from(Errno::Inval),
+	}
+}
+
+/// Detaches the task `id`, so that it is reaped as soon as it finishes, instead
+/// of remaining until `sys_join` or `sys_waitpid`.
+///
+/// Returns `-ESRCH`, if the task does not exist or has already been reaped,
+/// and `-EINVAL`, if it has already been detached.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_detach(id: Tid) -> i32 {
+	let Ok(id) = TaskId::try_from(id) else {
+		return -i32::from(Errno::Srch);
+	};
+
+	match scheduler::detach(id) {
+		Ok(()) => 0,
+		Err(e) => -i32::from(e),
+	}
+}
+
+/// Detaches the task with the 64-bit ID `id` like `sys_detach`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_detach64(id: Tid64) -> i32 {
+	let Ok(id) = TaskId::try_from(id) else {
+		return -i32::from(Errno::Srch);
+	};
+
+	match scheduler::detach(id) {
+		Ok(()) => 0,
+		Err(e) => -i32::from(e),
+	}
+}
+
+/// Sets, whether the tasks, which the current task spawns from now on, remain
+/// zombies after they have finished, until `sys_join` or `sys_waitpid` reaps
+/// them.
+///
+/// By default, they are reaped as soon as they finish, unless `sys_join` waits
+/// for them, and `sys_waitpid` cannot await them. A nonzero `retain` opts into
+/// keeping their exit codes, zero opts out again.
+#[hermit_macro::system]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_retain_children(retain: i32) -> i32 {
+	scheduler::retain_children(retain != 0);
+	0
+}
+
+/// Waits for a task, which has been spawned by the current task, to finish.
+///
+/// `pid` is either the ID of such a task, or `-1` or `0` to wait for any of them.
+/// Returns the ID of the reaped task and stores its exit status in the format
+/// of `WEXITSTATUS` in `status`. With `WNOHANG`, 0 is returned, if none of the
+/// tasks has finished yet. Only the tasks, which have been spawned after
+/// `sys_retain_children`, can be awaited.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_waitpid(pid: Tid, status: *mut i32, options: i32) -> Tid {
+	match unsafe { waitpid(pid.into(), status, options, TaskId::MAX_TID32) } {
+		Ok(Some(id)) => tid(id),
+		Ok(None) => 0,
+		Err(e) => -i32::from(e),
+	}
+}
+
+/// Waits for a task like `sys_waitpid`, but with 64-bit IDs.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_waitpid64(pid: Tid64, status: *mut i32, options: i32) -> Tid64 {
+	match unsafe { waitpid(pid, status, options, TaskId::MAX) } {
+		Ok(Some(id)) => tid64(id),
+		Ok(None) => 0,
+		Err(e) => -i64::from(i32::from(e)),
+	}
+}
+
+/// Reaps a task for `sys_waitpid` and `sys_waitpid64`, whose IDs reach up to
+/// `max_id`.
+unsafe fn waitpid(
+	pid: i64,
+	status: *mut i32,
+	options: i32,
+	max_id: TaskId,
+) -> Result<Option<TaskId>, Errno> {
+	if options & !WNOHANG != 0 {
+		return Err(Errno::Inval);
+	}
+	let id = match pid {
+		-1 | 0 => None,
+		pid if pid > 0 => Some(TaskId::try_from(pid).unwrap()),
+		// Hermit has no process groups.
+		_ => return Err(Errno::Child),
+	};
+
+	let Some((id, exit_code)) = scheduler::waitpid(id, options & WNOHANG != 0, max_id)? else {
+		return Ok(None);
+	};
+	if let Some(status) = unsafe { status.as_mut() } {
+		*status = (exit_code & 0xff) << 8;
+	}
+	Ok(Some(id))
+}
+
 /// Mapping between blocked tasks and their TaskHandle
 static BLOCKED_TASKS: InterruptTicketMutex<BTreeMap<TaskId, TaskHandle>> =
 	InterruptTicketMutex::new(BTreeMap::new());
@@ -211,6 +512,11 @@ fn block_current_task(timeout: Option<u64>) {
 	core_scheduler.block_current_task(wakeup_time);
 }
 
+/// Forgets the finished task `id`, so that `sys_wakeup_task` does not wake it up.
+pub(crate) fn forget_blocked_task(id: TaskId) {
+	BLOCKED_TASKS.lock().remove(&id);
+}
+
 /// Set the current task state to `blocked`
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
@@ -229,10 +535,14 @@ pub extern "C" fn sys_block_current_task_with_timeout(timeout: u64) {
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
 pub extern "C" fn sys_wakeup_task(id: Tid) {
-	let task_id = TaskId::from(id);
+	let Ok(task_id) = TaskId::try_from(id) else {
+		return;
+	};
 
-	if let Some(handle) = BLOCKED_TASKS.lock().remove(&task_id) {
+	let handle = BLOCKED_TASKS.lock().remove(&task_id);
+	if let Some(handle) = handle {
 		core_scheduler().custom_wakeup(handle);
+		core_scheduler().preempt();
 	}
 }
 
@@ -247,15 +557,226 @@ pub extern "C" fn sys_get_priority() -> u8 {
 #[hermit_macro::system(errno)]
 #[unsafe(no_mangle)]
 pub extern "C" fn sys_set_priority(id: Tid, prio: u8) {
+	let Ok(id) = TaskId::try_from(id) else {
+		return;
+	};
+
 	if prio > 0 {
 		core_scheduler()
-			.set_priority(TaskId::from(id), Priority::from(prio))
+			.set_priority(id, Priority::from(prio))
 			.expect("Unable to set priority");
 	} else {
 		panic!("Invalid priority {}", prio);
 	}
 }
 
+/// `which` of `setpriority` and `getpriority`, which selects a thread
+pub const PRIO_PROCESS: i32 = 0;
+
+/// Returns the task `who` of `setpriority` and `getpriority`, 0 is the current task.
+fn priority_target(which: i32, who: i32) -> Result<TaskId, Errno> {
+	if which != PRIO_PROCESS {
+		return Err(Errno::Inval);
+	}
+	if who == 0 {
+		return Ok(core_scheduler().get_current_task_id());
+	}
+	let id = TaskId::try_from(who).map_err(|_| Errno::Srch)?;
+	scheduler::get_task_handle(id).ok_or(Errno::Srch)?;
+	Ok(id)
+}
+
+/// Maps the nice value `nice` to a priority.
+///
+/// A nice value of 0 is [`NORMAL_PRIO`], every step below 0 raises the priority
+/// by one up to 22 for -20, and a positive nice value is [`LOW_PRIO`].
+fn nice_to_priority(nice: i32) -> Priority {
+	let prio = (i32::from(NORMAL_PRIO.into()) - nice).clamp(LOW_PRIO.into().into(), 22);
+	Priority::from(u8::try_from(prio).unwrap())
+}
+
+/// Maps the priority `prio` to a nice value between -20 and 19.
+fn priority_to_nice(prio: Priority) -> i32 {
+	(i32::from(NORMAL_PRIO.into()) - i32::from(prio.into())).clamp(-20, 19)
+}
+
+/// Sets the priority of the thread `who` to the nice value `nice`.
+///
+/// `which` has to be `PRIO_PROCESS` and `who` is the identifier of a thread,
+/// 0 is the current thread. Nice values outside of -20 to 19 are clamped. The
+/// nice value of a real-time thread has no effect.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_setpriority(which: i32, who: i32, nice: i32) -> i32 {
+	let id = match priority_target(which, who) {
+		Ok(id) => id,
+		Err(e) => return -i32::from(e),
+	};
+	if !matches!(realtime::query(id), Ok((Policy::Other, _))) {
+		return 0;
+	}
+
+	let prio = nice_to_priority(nice.clamp(-20, 19));
+	if core_scheduler().set_priority(id, prio).is_err() {
+		return -i32::from(Errno::Srch);
+	}
+	0
+}
+
+/// Returns the nice value of the thread `who` as `20 - nice`, i.e., between 1
+/// and 40, like the system call `getpriority` of Linux.
+///
+/// `which` has to be `PRIO_PROCESS` and `who` is the identifier of a thread,
+/// 0 is the current thread.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_getpriority(which: i32, who: i32) -> i32 {
+	let id = match priority_target(which, who) {
+		Ok(id) => id,
+		Err(e) => return -i32::from(e),
+	};
+
+	match scheduler::get_task_handle(id) {
+		Some(handle) => 20 - priority_to_nice(handle.get_priority()),
+		None => -i32::from(Errno::Srch),
+	}
+}
+
+/// Scheduling parameters of `sched_setscheduler` and `sched_getparam`
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct sched_param {
+	/// Priority between 1 and 32 of a real-time task, 0 otherwise
+	pub sched_priority: i32,
+}
+
+/// Sets the scheduling class of the task `id` to `policy` with the priority
+/// of `param`.
+///
+/// `policy` is `SCHED_OTHER` (0), `SCHED_FIFO` (1), or `SCHED_RR` (2), and `id`
+/// 0 is the current task. Real-time tasks have priorities between 1 and 32,
+/// which are higher than the priorities of all other tasks, and `SCHED_OTHER`
+/// requires 0. Returns `-EINVAL` for an unknown class or a priority outside of
+/// its range and `-ESRCH`, if the task does not exist.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sched_setscheduler(
+	id: Tid,
+	policy: i32,
+	param: *const sched_param,
+) -> i32 {
+	let Some(param) = (unsafe { param.as_ref() }) else {
+		return -i32::from(Errno::Fault);
+	};
+	let Ok(policy) = Policy::try_from(policy) else {
+		return -i32::from(Errno::Inval);
+	};
+
+	let result =
+		affinity_task(id).and_then(|task| realtime::set(task, policy, param.sched_priority));
+	if let Err(e) = result {
+		return -i32::from(e);
+	}
+	// The current task may have lowered its priority below a ready task and
+	// starts its time slice as `SCHED_RR` task.
+	core_scheduler().reschedule();
+
+	0
+}
+
+/// Returns the scheduling class of the task `id`, where 0 is the current task.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_sched_getscheduler(id: Tid) -> i32 {
+	match affinity_task(id).and_then(realtime::query) {
+		Ok((policy, _)) => policy as i32,
+		Err(e) => -i32::from(e),
+	}
+}
+
+/// Sets the priority of the task `id` to the priority of `param` without
+/// changing its scheduling class.
+///
+/// `id` 0 is the current task. Returns `-EINVAL`, if the priority is outside
+/// of the range of the class.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sched_setparam(id: Tid, param: *const sched_param) -> i32 {
+	let Some(param) = (unsafe { param.as_ref() }) else {
+		return -i32::from(Errno::Fault);
+	};
+
+	let result = affinity_task(id).and_then(|task| {
+		let (policy, _) = realtime::query(task)?;
+		realtime::set(task, policy, param.sched_priority)
+	});
+	if let Err(e) = result {
+		return -i32::from(e);
+	}
+	core_scheduler().reschedule();
+
+	0
+}
+
+/// Writes the priority of the task `id` to `param`, which is 0 for a task of
+/// `SCHED_OTHER`.
+///
+/// `id` 0 is the current task.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sched_getparam(id: Tid, param: *mut sched_param) -> i32 {
+	let Some(param) = (unsafe { param.as_mut() }) else {
+		return -i32::from(Errno::Fault);
+	};
+
+	match affinity_task(id).and_then(realtime::query) {
+		Ok((_, priority)) => {
+			param.sched_priority = priority;
+			0
+		}
+		Err(e) => -i32::from(e),
+	}
+}
+
+/// Returns the highest priority of the scheduling class `policy`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_sched_get_priority_max(policy: i32) -> i32 {
+	Policy::try_from(policy).map_or(-i32::from(Errno::Inval), |policy| policy.priority_range().1)
+}
+
+/// Returns the lowest priority of the scheduling class `policy`.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub extern "C" fn sys_sched_get_priority_min(policy: i32) -> i32 {
+	Policy::try_from(policy).map_or(-i32::from(Errno::Inval), |policy| policy.priority_range().0)
+}
+
+/// Writes the time slice of the task `id` to `interval`, which is 0 unless
+/// the task is a `SCHED_RR` task.
+///
+/// `id` 0 is the current task.
+#[hermit_macro::system(errno)]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn sys_sched_rr_get_interval(id: Tid, interval: *mut timespec) -> i32 {
+	let Some(interval) = (unsafe { interval.as_mut() }) else {
+		return -i32::from(Errno::Fault);
+	};
+
+	match affinity_task(id).and_then(realtime::query) {
+		Ok((policy, _)) => {
+			let slice = if policy == Policy::RoundRobin {
+				realtime::TIME_SLICE
+			} else {
+				0
+			};
+			*interval = timespec::from_usec(slice.try_into().unwrap());
+			0
+		}
+		Err(e) => -i32::from(e),
+	}
+}
+
 /// Set priority of the current thread
 #[hermit_macro::system]
 #[unsafe(no_mangle)]
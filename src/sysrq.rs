@@ -0,0 +1,147 @@
+//! Emergency commands on the serial console, similar to Linux' magic SysRq key.
+//!
+//! Ctrl-\ followed by a command key triggers the command directly from the
+//! interrupt handler of the UART, so that it works even if the application
+//! does not make progress anymore. Ctrl-\ twice sends Ctrl-\ to the
+//! application.
+//!
+//! The backtrace of `c` follows the frame pointers and is only complete, if
+//! the kernel has been built with `-Cforce-frame-pointers=yes`.
+
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use memory_addresses::VirtAddr;
+
+use crate::arch::mm::paging;
+use crate::symbols::Symbolized;
+
+/// Byte, which introduces a command (Ctrl-\)
+const ESCAPE: u8 = 0x1c;
+
+/// Maximum number of frames of a backtrace
+const MAX_FRAMES: usize = 32;
+
+/// Ctrl-\ has been received.
+static ESCAPED: AtomicBool = AtomicBool::new(false);
+
+/// Command, which has been received but not run yet, or 0
+static PENDING: AtomicU8 = AtomicU8::new(0);
+
+const COMMANDS: &[(u8, &str)] = &[
+	(b'h', "print this help message"),
+	(b't', "list all tasks"),
+	(b'm', "show the usage of the physical memory"),
+	(b'i', "show the number of received interrupts"),
+	(b'c', "panic with a backtrace"),
+	(b'b', "reboot immediately"),
+	(b'o', "power off immediately"),
+];
+
+/// Filters a byte received by the UART.
+///
+/// Returns the byte, if it is input of the application. Commands are
+/// recorded and run by [`run_pending`], after the UART has been released.
+pub(crate) fn receive(byte: u8) -> Option<u8> {
+	if !ESCAPED.swap(false, Ordering::Relaxed) {
+		if byte == ESCAPE {
+			ESCAPED.store(true, Ordering::Relaxed);
+			return None;
+		}
+		return Some(byte);
+	}
+
+	if byte == ESCAPE {
+		return Some(byte);
+	}
+
+	PENDING.store(byte, Ordering::Relaxed);
+	None
+}
+
+/// Runs the command, which has been received last.
+pub(crate) fn run_pending() {
+	let key = PENDING.swap(0, Ordering::Relaxed);
+	if key == 0 {
+		return;
+	}
+
+	println!();
+	match key {
+		b't' => print_proc("/proc/tasks"),
+		b'm' => print_proc("/proc/meminfo"),
+		b'i' => print_proc("/proc/interrupts"),
+		b'c' => {
+			print_backtrace();
+			panic!("SysRq: forced panic");
+		}
+		b'b' => {
+			println!("SysRq: rebooting");
+			crate::syscalls::reboot(0);
+		}
+		b'o' => {
+			println!("SysRq: powering off");
+			crate::scheduler::shutdown(0);
+		}
+		_ => {
+			println!("SysRq commands (Ctrl-\\ followed by the key):");
+			for (key, help) in COMMANDS {
+				println!("  {} - {help}", char::from(*key));
+			}
+		}
+	}
+}
+
+fn print_proc(path: &str) {
+	print!("{}", crate::fs::proc::generate(path).unwrap());
+}
+
+fn frame_pointer() -> usize {
+	let fp: usize;
+	cfg_if::cfg_if! {
+		if #[cfg(target_arch = "x86_64")] {
+			unsafe { core::arch::asm!("mov {}, rbp", out(reg) fp, options(nomem, nostack)) };
+		} else if #[cfg(target_arch = "aarch64")] {
+			unsafe { core::arch::asm!("mov {}, x29", out(reg) fp, options(nomem, nostack)) };
+		} else if #[cfg(target_arch = "riscv64")] {
+			unsafe { core::arch::asm!("mv {}, s0", out(reg) fp, options(nomem, nostack)) };
+		}
+	}
+	fp
+}
+
+/// Prints the return addresses by following the chain of frame pointers.
+fn print_backtrace() {
+	println!("Backtrace:");
+	let mut fp = frame_pointer();
+	for frame in 0..MAX_FRAMES {
+		let valid = fp != 0
+			&& fp.is_multiple_of(align_of::<usize>())
+			&& paging::virtual_to_physical(VirtAddr::new(fp as u64)).is_some();
+		if !valid {
+			break;
+		}
+
+		// The frame pointer points to the saved frame pointer of the caller,
+		// which is followed by the return address. On RISC-V, both are stored
+		// below the frame pointer.
+		#[cfg(not(target_arch = "riscv64"))]
+		let (next, ret) = unsafe {
+			let frame = fp as *const usize;
+			(frame.read(), frame.add(1).read())
+		};
+		#[cfg(target_arch = "riscv64")]
+		let (next, ret) = unsafe {
+			let frame = fp as *const usize;
+			(frame.sub(2).read(), frame.sub(1).read())
+		};
+		if ret == 0 {
+			break;
+		}
+
+		println!("  #{frame:<2} {}", Symbolized(ret));
+		if next <= fp {
+			break;
+		}
+		fp = next;
+	}
+}
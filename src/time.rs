@@ -11,8 +11,11 @@ pub type suseconds_t = i32;
 
 /// Represent the number of seconds and microseconds since
 /// the Epoch (1970-01-01 00:00:00 +0000 (UTC))
+///
+/// The alignment is fixed to 8 bytes, so that the layout does not depend on
+/// the alignment of `i64` of the target, which is 4 bytes on some 32-bit targets.
 #[derive(Copy, Clone, Debug)]
-#[repr(C)]
+#[repr(C, align(8))]
 pub struct timeval {
 	/// seconds
 	pub tv_sec: time_t,
@@ -20,6 +23,8 @@ pub struct timeval {
 	pub tv_usec: suseconds_t,
 }
 
+const _: () = assert!(size_of::<timeval>() == 16);
+
 impl timeval {
 	pub fn from_usec(microseconds: i64) -> Self {
 		Self {
@@ -43,10 +48,14 @@ pub struct itimerval {
 	pub it_value: timeval,
 }
 
+const _: () = assert!(size_of::<itimerval>() == 32);
+
 /// Represent the number of seconds and nanoseconds since
 /// the Epoch (1970-01-01 00:00:00 +0000 (UTC))
+///
+/// Like [`timeval`], the alignment is fixed to 8 bytes.
 #[derive(Copy, Clone, Debug, Default)]
-#[repr(C)]
+#[repr(C, align(8))]
 pub struct timespec {
 	/// seconds
 	pub tv_sec: time_t,
@@ -54,6 +63,8 @@ pub struct timespec {
 	pub tv_nsec: i32,
 }
 
+const _: () = assert!(size_of::<timespec>() == 16);
+
 impl timespec {
 	pub fn from_usec(microseconds: i64) -> Self {
 		Self {
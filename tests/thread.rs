@@ -12,19 +12,23 @@ extern crate alloc;
 extern crate hermit;
 
 use core::ptr;
-use core::sync::atomic::AtomicU32;
-use core::sync::atomic::Ordering::Relaxed;
+use core::sync::atomic::Ordering::{Relaxed, SeqCst};
+use core::sync::atomic::{AtomicU32, AtomicU64};
 
 mod common;
 
 use alloc::vec;
+use alloc::vec::Vec;
 
 use hermit::errno::Errno;
-use hermit::syscalls::{sys_futex_wait, sys_futex_wake, sys_join, sys_spawn2, sys_usleep};
+use hermit::syscalls::{
+	sys_clock_gettime, sys_futex_wait, sys_futex_wake, sys_join, sys_spawn2, sys_usleep, sys_yield,
+};
 use hermit::time::timespec;
 
 const USER_STACK_SIZE: usize = 0x0010_0000;
 const NORMAL_PRIO: u8 = 2;
+const CLOCK_MONOTONIC: i32 = 4;
 
 extern "C" fn thread_func(i: usize) {
 	println!("this is thread number {}", i);
@@ -93,6 +97,54 @@ pub fn test_futex() {
 	assert_eq!(ret, 0);
 }
 
+/// Latest monotonic time in microseconds observed by any thread
+static LAST_MONOTONIC: AtomicU64 = AtomicU64::new(0);
+
+/// Number of times, a thread observed the monotonic clock going backwards
+static BACKWARDS: AtomicU32 = AtomicU32::new(0);
+
+fn monotonic_micros() -> u64 {
+	let mut ts = timespec::default();
+	let ret = unsafe { sys_clock_gettime(CLOCK_MONOTONIC, &raw mut ts) };
+	assert_eq!(ret, 0);
+	ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1_000
+}
+
+extern "C" fn monotonic_func(_arg: usize) {
+	let end = monotonic_micros() + 500_000;
+	let mut i = 0u32;
+	loop {
+		// The time observed by another thread has to be loaded before the clock is read.
+		let last = LAST_MONOTONIC.load(SeqCst);
+		let now = monotonic_micros();
+		if now < last {
+			BACKWARDS.fetch_add(1, Relaxed);
+		}
+		LAST_MONOTONIC.fetch_max(now, SeqCst);
+		if now >= end {
+			break;
+		}
+
+		i += 1;
+		if i.is_multiple_of(1000) {
+			sys_yield();
+		}
+	}
+}
+
+#[test_case]
+pub fn test_monotonic_clock() {
+	let children = (0..8)
+		.map(|i| unsafe { sys_spawn2(monotonic_func, i, NORMAL_PRIO, USER_STACK_SIZE, -1) })
+		.collect::<Vec<_>>();
+	for child in children {
+		assert!(child >= 0);
+		sys_join(child);
+	}
+
+	assert_eq!(BACKWARDS.load(Relaxed), 0);
+}
+
 #[test_case]
 pub fn test_thread_local() {
 	#[repr(C, align(0x10))]
@@ -1,8 +1,10 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::env::{self, VarError};
+use std::path::PathBuf;
 
 use anyhow::Result;
 use clap::Args;
+use xshell::cmd;
 
 use crate::cargo_build::CargoBuild;
 
@@ -10,7 +12,7 @@ use crate::cargo_build::CargoBuild;
 #[derive(Args)]
 pub struct Build {
 	#[command(flatten)]
-	cargo_build: CargoBuild,
+	pub cargo_build: CargoBuild,
 
 	/// Enable the `-Z instrument-mcount` flag.
 	#[arg(long)]
@@ -19,6 +21,10 @@ pub struct Build {
 	/// Enable the `-Z randomize-layout` flag.
 	#[arg(long)]
 	pub randomize_layout: bool,
+
+	/// Only include syscalls, which are referenced by these object files or archives of the application.
+	#[arg(long, id = "APPLICATION")]
+	pub syscalls_from: Vec<PathBuf>,
 }
 
 impl Build {
@@ -104,11 +110,49 @@ impl Build {
 			rustflags.push("-Zrandomize-layout")
 		}
 
+		let used_syscalls = self.used_syscalls_cfgs()?;
+		rustflags.extend(used_syscalls.iter().map(String::as_str));
+
 		rustflags.extend(self.cargo_build.artifact.arch.rustflags());
 
 		Ok(rustflags.join("\x1f"))
 	}
 
+	/// Returns the `--cfg` flags, which restrict the exported syscalls to those used by the application.
+	fn used_syscalls_cfgs(&self) -> Result<Vec<String>> {
+		if self.syscalls_from.is_empty() {
+			return Ok(vec![]);
+		}
+
+		let sh = crate::sh()?;
+		let application = &self.syscalls_from;
+		let nm = crate::binutil("nm").unwrap();
+		let stdout = cmd!(
+			sh,
+			"{nm} --undefined-only --just-symbol-name {application...}"
+		)
+		.output()?
+		.stdout;
+		let stdout = String::from_utf8(stdout)?;
+
+		let used_syscalls = stdout
+			.lines()
+			.map(str::trim)
+			.filter(|symbol| symbol.starts_with("sys_"))
+			.collect::<BTreeSet<_>>();
+		eprintln!(
+			"Including {} syscalls used by the application",
+			used_syscalls.len()
+		);
+
+		let mut cfgs = vec!["--cfg".to_string(), "hermit_used_syscalls".to_string()];
+		for syscall in used_syscalls {
+			cfgs.push("--cfg".to_string());
+			cfgs.push(format!("hermit_used_syscall=\"{syscall}\""));
+		}
+		Ok(cfgs)
+	}
+
 	fn export_syms(&self) -> Result<()> {
 		let archive = self.cargo_build.artifact.dist_archive();
 
@@ -11,7 +11,7 @@ pub struct CargoBuild {
 
 	/// Do not activate the `default` feature.
 	#[arg(long)]
-	no_default_features: bool,
+	pub no_default_features: bool,
 
 	/// Space or comma separated list of features to activate.
 	#[arg(long)]
@@ -51,6 +51,9 @@ pub enum Device {
 	/// Cadence Gigabit Ethernet MAC (GEM).
 	CadenceGem,
 
+	/// Intel 82540EM (e1000).
+	E1000,
+
 	/// RTL8139.
 	Rtl8139,
 
@@ -343,7 +346,10 @@ impl Qemu {
 						format!("{netdev_options},model=cadence_gem"),
 					]
 				}
-				device @ (Device::Rtl8139 | Device::VirtioNetMmio | Device::VirtioNetPci) => {
+				device @ (Device::E1000
+				| Device::Rtl8139
+				| Device::VirtioNetMmio
+				| Device::VirtioNetPci) => {
 					let mut netdev_args = vec![
 						"-netdev".to_string(),
 						netdev_options.to_string(),
@@ -353,6 +359,7 @@ impl Qemu {
 					let mut device_arg = match device {
 						Device::VirtioNetPci => "virtio-net-pci,netdev=net0,disable-legacy=on",
 						Device::VirtioNetMmio => "virtio-net-device,netdev=net0",
+						Device::E1000 => "e1000,netdev=net0",
 						Device::Rtl8139 => "rtl8139,netdev=net0",
 						_ => unreachable!(),
 					}
@@ -25,6 +25,11 @@ impl Clippy {
 				.run()?;
 			clippy().arg("--no-default-features").run()?;
 			clippy().arg("--all-features").run()?;
+			clippy()
+				.arg("--no-default-features")
+				.arg("--features=tiny")
+				.run()?;
+			clippy().arg("--features=full").run()?;
 			clippy()
 				.arg("--no-default-features")
 				.arg("--features=tcp")
@@ -9,6 +9,8 @@ mod cargo_build;
 mod ci;
 mod clippy;
 mod doc;
+mod size_report;
+mod symbols;
 
 use std::env;
 use std::path::{Path, PathBuf};
@@ -26,6 +28,8 @@ enum Cli {
 	Ci(ci::Ci),
 	Clippy(clippy::Clippy),
 	Doc(doc::Doc),
+	SizeReport(size_report::SizeReport),
+	Symbols(symbols::Symbols),
 }
 
 impl Cli {
@@ -35,6 +39,8 @@ impl Cli {
 			Self::Ci(ci) => ci.run(),
 			Self::Clippy(clippy) => clippy.run(),
 			Self::Doc(doc) => doc.run(),
+			Self::SizeReport(size_report) => size_report.run(),
+			Self::Symbols(symbols) => symbols.run(),
 		}
 	}
 }
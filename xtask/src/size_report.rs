@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use xshell::cmd;
+
+use crate::arch::Arch;
+use crate::archive::Archive;
+use crate::artifact::Artifact;
+use crate::build::Build;
+use crate::cargo_build::CargoBuild;
+
+/// Configuration profiles, which are built without the `default` feature.
+const PROFILES: &[&str] = &["tiny", "default", "full"];
+
+/// Build the kernel with each configuration profile and report its size.
+#[derive(Args)]
+pub struct SizeReport {
+	/// Target architecture.
+	#[arg(value_enum, long)]
+	pub arch: Arch,
+
+	/// Directory for all generated artifacts.
+	#[arg(long, id = "DIRECTORY")]
+	pub target_dir: Option<PathBuf>,
+}
+
+/// Section sizes of an archive in bytes.
+struct Size {
+	text: u64,
+	data: u64,
+	bss: u64,
+}
+
+impl Size {
+	fn total(&self) -> u64 {
+		self.text + self.data + self.bss
+	}
+}
+
+impl SizeReport {
+	pub fn run(self) -> Result<()> {
+		let target_dir = self
+			.target_dir
+			.clone()
+			.unwrap_or_else(|| crate::project_root().join("target"))
+			.join("size-report");
+
+		let mut sizes = Vec::new();
+		for profile in PROFILES {
+			let build = Build {
+				cargo_build: CargoBuild {
+					artifact: Artifact {
+						arch: self.arch,
+						target_dir: Some(target_dir.join(profile)),
+						artifact_dir: None,
+						release: true,
+						profile: None,
+					},
+					no_default_features: true,
+					features: vec![profile.to_string()],
+				},
+				instrument_mcount: false,
+				randomize_layout: false,
+				syscalls_from: vec![],
+			};
+			let archive = build.cargo_build.artifact.dist_archive();
+
+			build.run()?;
+			sizes.push((profile, size(&archive)?));
+		}
+
+		let default = sizes
+			.iter()
+			.find(|(profile, _)| **profile == "default")
+			.map_or(0, |(_, size)| size.total());
+
+		println!("Kernel size for {}", self.arch.name());
+		println!(
+			"{:<10} {:>10} {:>10} {:>10} {:>10} {:>12}",
+			"profile", "text", "data", "bss", "total", "vs. default"
+		);
+		for (profile, size) in sizes {
+			let total = size.total();
+			let diff = i128::from(total) - i128::from(default);
+			println!(
+				"{profile:<10} {:>10} {:>10} {:>10} {total:>10} {diff:>+12}",
+				size.text, size.data, size.bss
+			);
+		}
+
+		Ok(())
+	}
+}
+
+/// Sums up the sections of all object files of `archive`.
+///
+/// The sizes are an upper bound, since the linker drops unused sections.
+fn size(archive: &Archive) -> Result<Size> {
+	let sh = crate::sh()?;
+	let archive = archive.as_ref();
+
+	let llvm_size = crate::binutil("size").unwrap();
+	let stdout = cmd!(sh, "{llvm_size} --format=berkeley --totals {archive}")
+		.output()?
+		.stdout;
+	let stdout = String::from_utf8(stdout)?;
+
+	// text    data     bss     dec     hex filename
+	let totals = stdout
+		.lines()
+		.find(|line| line.trim_end().ends_with("(TOTALS)"))
+		.context("llvm-size did not report totals")?;
+	let mut columns = totals.split_whitespace();
+	let mut next = || -> Result<u64> {
+		Ok(columns
+			.next()
+			.context("invalid llvm-size output")?
+			.parse()?)
+	};
+
+	Ok(Size {
+		text: next()?,
+		data: next()?,
+		bss: next()?,
+	})
+}
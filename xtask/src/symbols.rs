@@ -0,0 +1,155 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::Args;
+use goblin::elf::Elf;
+use goblin::elf::program_header::PT_LOAD;
+use goblin::elf::sym::STT_FUNC;
+
+/// Section, which the kernel reserves with the feature `symbols`
+const SECTION: &str = ".hermit_symbols";
+/// Magic number at the start of the table, see `src/symbols.rs`
+const MAGIC: &[u8; 8] = b"HSYMTAB\0";
+
+/// Write the function symbols of a linked image into its symbol table section.
+#[derive(Args)]
+pub struct Symbols {
+	/// Image, which has been linked with a kernel with the feature `symbols`.
+	pub image: PathBuf,
+}
+
+impl Symbols {
+	pub fn run(self) -> Result<()> {
+		let sh = crate::sh()?;
+		let mut bytes = sh.read_binary_file(&self.image)?;
+
+		let (range, table) = {
+			let elf = Elf::parse(&bytes)?;
+			let section = elf
+				.section_headers
+				.iter()
+				.find(|header| elf.shdr_strtab.get_at(header.sh_name) == Some(SECTION))
+				.ok_or_else(|| anyhow!("{} has no section {SECTION}", self.image.display()))?;
+			let range = section
+				.file_range()
+				.context("the symbol table section has no content")?;
+
+			let base = elf
+				.program_headers
+				.iter()
+				.filter(|header| header.p_type == PT_LOAD)
+				.map(|header| header.p_vaddr)
+				.min()
+				.context("the image has no loadable segments")?;
+
+			let mut functions = elf
+				.syms
+				.iter()
+				.filter(|sym| sym.st_type() == STT_FUNC && sym.st_value >= base)
+				.filter_map(|sym| {
+					let name = elf.strtab.get_at(sym.st_name)?;
+					Some((sym.st_value - base, sym.st_size, demangle(name)))
+				})
+				.collect::<Vec<_>>();
+			functions.sort_by_key(|(offset, _, _)| *offset);
+			functions.dedup_by_key(|(offset, _, _)| *offset);
+
+			(range, table(&functions)?)
+		};
+
+		if table.len() > range.len() {
+			bail!(
+				"the symbol table needs {} bytes, but the section only has {} bytes",
+				table.len(),
+				range.len()
+			);
+		}
+		bytes[range.start..range.start + table.len()].copy_from_slice(&table);
+		sh.write_file(&self.image, bytes)?;
+
+		eprintln!("Wrote the symbol table to {}", self.image.display());
+		Ok(())
+	}
+}
+
+/// Encodes the table like `Header` and `Entry` of `src/symbols.rs`.
+fn table(functions: &[(u64, u64, String)]) -> Result<Vec<u8>> {
+	let mut entries = Vec::new();
+	let mut names = Vec::new();
+	for (offset, size, name) in functions {
+		entries.extend_from_slice(&u32::try_from(*offset)?.to_le_bytes());
+		entries.extend_from_slice(&u32::try_from(*size)?.to_le_bytes());
+		entries.extend_from_slice(&u32::try_from(names.len())?.to_le_bytes());
+		entries.extend_from_slice(&u32::try_from(name.len())?.to_le_bytes());
+		names.extend_from_slice(name.as_bytes());
+	}
+
+	let mut table = Vec::new();
+	table.extend_from_slice(MAGIC);
+	table.extend_from_slice(&u32::try_from(functions.len())?.to_le_bytes());
+	table.extend_from_slice(&u32::try_from(names.len())?.to_le_bytes());
+	table.extend_from_slice(&entries);
+	table.extend_from_slice(&names);
+	Ok(table)
+}
+
+/// Demangles a symbol of the legacy Rust mangling scheme without its hash,
+/// e.g., `_ZN6hermit5sysrq15print_backtrace17h0123456789abcdefE` to
+/// `hermit::sysrq::print_backtrace`. Other symbols are returned unchanged.
+fn demangle(symbol: &str) -> String {
+	let Some(mut rest) = symbol
+		.strip_prefix("_ZN")
+		.and_then(|rest| rest.strip_suffix('E'))
+	else {
+		return symbol.to_string();
+	};
+
+	let mut path = Vec::new();
+	while !rest.is_empty() {
+		let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+		let Ok(len) = rest[..digits].parse::<usize>() else {
+			return symbol.to_string();
+		};
+		let Some(segment) = rest.get(digits..digits + len) else {
+			return symbol.to_string();
+		};
+		// Segments, which start with an escape, are prefixed with `_`.
+		path.push(
+			segment
+				.strip_prefix('_')
+				.filter(|segment| segment.starts_with('$'))
+				.unwrap_or(segment),
+		);
+		rest = &rest[digits + len..];
+	}
+
+	if path
+		.last()
+		.is_some_and(|hash| hash.len() == 17 && hash.starts_with('h'))
+	{
+		path.pop();
+	}
+
+	let mut name = path.join("::");
+	for (escape, c) in [
+		("$LT$", "<"),
+		("$GT$", ">"),
+		("$RF$", "&"),
+		("$BP$", "*"),
+		("$LP$", "("),
+		("$RP$", ")"),
+		("$C$", ","),
+		("$u20$", " "),
+		("$u27$", "'"),
+		("$u5b$", "["),
+		("$u5d$", "]"),
+		("$u7b$", "{"),
+		("$u7d$", "}"),
+		("$u7e$", "~"),
+		("$SP$", "@"),
+		("..", "::"),
+	] {
+		name = name.replace(escape, c);
+	}
+	name
+}